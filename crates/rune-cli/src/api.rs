@@ -144,23 +144,43 @@ async fn branches() -> Json<Vec<serde_json::Value>> {
     Json(out)
 }
 async fn branch_create(Json(req): Json<BranchCreate>) -> Json<serde_json::Value> {
-    let s = rune_store::Store::discover(std::env::current_dir().unwrap()).unwrap();
-    std::fs::create_dir_all(s.rune_dir.join("refs/heads")).unwrap();
-    std::fs::write(s.rune_dir.join("refs/heads").join(&req.name), b"").unwrap();
-    Json(serde_json::json!({"created": req.name}))
+    match rune_store::Store::discover(std::env::current_dir().unwrap_or_default()) {
+        Ok(s) => Json(create_branch_in(&s, &req.name)),
+        Err(e) => Json(serde_json::json!({"error": e.to_string()})),
+    }
 }
 async fn checkout(Json(req): Json<CheckoutReq>) -> Json<serde_json::Value> {
-    let s = rune_store::Store::discover(std::env::current_dir().unwrap()).unwrap();
-    let r = format!("refs/heads/{}", req.name);
-    if s.read_ref(&r).is_none() && !s.rune_dir.join(&r).exists() {
-        return Json(serde_json::json!({"error": "branch not found"}));
+    match rune_store::Store::discover(std::env::current_dir().unwrap_or_default()) {
+        Ok(s) => Json(checkout_in(&s, &req.name)),
+        Err(e) => Json(serde_json::json!({"error": e.to_string()})),
+    }
+}
+
+/// Create branch `name` in `s`; names that break the ref rules, such as
+/// ones climbing out of `refs/heads`, are refused
+fn create_branch_in(s: &rune_store::Store, name: &str) -> serde_json::Value {
+    match s.create_branch(name) {
+        Ok(()) => serde_json::json!({"created": name}),
+        Err(e) => serde_json::json!({"error": e.to_string()}),
+    }
+}
+
+/// Point HEAD of `s` at existing branch `name`
+fn checkout_in(s: &rune_store::Store, name: &str) -> serde_json::Value {
+    if let Err(rule) = rune_store::check_ref_format(name) {
+        return serde_json::json!({"error": format!("'{}' is not a valid branch name: {}", name, rule)});
+    }
+    if !s.branch_exists(name) {
+        return serde_json::json!({"error": "branch not found"});
+    }
+    match s.set_head(&format!("refs/heads/{}", name)) {
+        Ok(()) => serde_json::json!({"switched": name}),
+        Err(e) => serde_json::json!({"error": e.to_string()}),
     }
-    s.set_head(&r).unwrap();
-    Json(serde_json::json!({"switched": req.name}))
 }
 
 async fn lfs_track(Json(req): Json<LfsTrackReq>) -> Json<serde_json::Value> {
-    let l = rune_lfs::Lfs::open(std::env::current_dir().unwrap()).unwrap();
+    let l = crate::commands::lfs::open_lfs().unwrap();
     let mut cfg = l.config().unwrap();
     for p in req.patterns {
         if !cfg.patterns.contains(&p) {
@@ -171,7 +191,7 @@ async fn lfs_track(Json(req): Json<LfsTrackReq>) -> Json<serde_json::Value> {
     Json(serde_json::json!({"tracked": cfg.patterns}))
 }
 async fn lfs_clean(Json(req): Json<LfsCleanReq>) -> Json<serde_json::Value> {
-    let l = rune_lfs::Lfs::open(std::env::current_dir().unwrap()).unwrap();
+    let l = crate::commands::lfs::open_lfs().unwrap();
     match l.clean_to_pointer(&req.path).unwrap() {
         Some(ptr) => {
             Json(serde_json::json!({"oid": ptr.oid, "size": ptr.size, "chunks": ptr.chunks.len()}))
@@ -180,40 +200,34 @@ async fn lfs_clean(Json(req): Json<LfsCleanReq>) -> Json<serde_json::Value> {
     }
 }
 async fn lfs_smudge(Json(req): Json<LfsSmudgeReq>) -> Json<serde_json::Value> {
-    let l = rune_lfs::Lfs::open(std::env::current_dir().unwrap()).unwrap();
-    match l.smudge_from_pointer(&req.path).unwrap() {
-        true => Json(serde_json::json!({"ok": true})),
-        false => Json(serde_json::json!({"error":"not a pointer"})),
+    let l = crate::commands::lfs::open_lfs().unwrap();
+    match l.smudge_from_pointer(&req.path) {
+        Ok(true) => Json(serde_json::json!({"ok": true})),
+        Ok(false) => Json(serde_json::json!({"error":"not a pointer"})),
+        Err(e) => Json(serde_json::json!({"error": e.to_string()})),
     }
 }
 async fn lfs_push(Json(req): Json<LfsPushReq>) -> Json<serde_json::Value> {
     use rune_lfs::Pointer;
     use serde_json::json;
-    let l = rune_lfs::Lfs::open(std::env::current_dir().unwrap()).unwrap();
+    let l = crate::commands::lfs::open_lfs().unwrap();
     let cfg = l.config().unwrap();
     let remote = cfg
         .remote
         .clone()
         .unwrap_or_else(|| "http://127.0.0.1:7420".into());
-    let s = std::fs::read_to_string(&req.path).unwrap_or_default();
-    if !s.starts_with("version https://rune-lfs/v1") {
-        return Json(json!({"error":"not a pointer"}));
-    }
-    let oid = s
-        .lines()
-        .find(|l| l.starts_with("oid "))
-        .unwrap()
-        .trim_start_matches("oid ")
-        .to_string();
-    let dir = l
-        .root
-        .join(".rune/lfs/objects")
-        .join(&oid[0..2])
-        .join(&oid[2..4])
-        .join(&oid);
+    let oid = match rune_lfs::read_pointer_file(std::path::Path::new(&req.path)) {
+        Ok(Some(pointer)) => pointer.oid,
+        Ok(None) => return Json(json!({"error":"not a pointer"})),
+        Err(e) => return Json(json!({"error": e.to_string()})),
+    };
+    let dir = l.chunk_dir(&oid);
     let pj = std::fs::read(dir.join("pointer.json")).unwrap();
     let ptr: Pointer = serde_json::from_slice(&pj).unwrap();
-    let client = reqwest::Client::new();
+    let client = match crate::commands::lfs::remote_client(&cfg) {
+        Ok(client) => client,
+        Err(e) => return Json(json!({"error": e.to_string()})),
+    };
     let missing: Vec<String> = client
         .post(format!("{}/lfs/has", remote))
         .json(&json!({"oid": &oid, "chunks": ptr.chunks}))
@@ -231,7 +245,8 @@ async fn lfs_push(Json(req): Json<LfsPushReq>) -> Json<serde_json::Value> {
         .unwrap();
     let mut uploaded = 0usize;
     for cid in missing {
-        let data = std::fs::read(dir.join(&cid)).unwrap();
+        let idx = ptr.chunks.iter().position(|c| *c == cid).unwrap_or_default();
+        let data = l.read_chunk(&ptr, idx).unwrap();
         client
             .post(format!("{}/lfs/upload", cfg.remote.as_ref().unwrap()))
             .json(&json!({"oid": &oid, "chunk": cid, "data": data}))
@@ -245,13 +260,16 @@ async fn lfs_push(Json(req): Json<LfsPushReq>) -> Json<serde_json::Value> {
 async fn lfs_pull(Json(req): Json<LfsPullReq>) -> Json<serde_json::Value> {
     use rune_lfs::Pointer;
     use serde_json::json;
-    let l = rune_lfs::Lfs::open(std::env::current_dir().unwrap()).unwrap();
+    let l = crate::commands::lfs::open_lfs().unwrap();
     let cfg = l.config().unwrap();
     let remote = cfg
         .remote
         .clone()
         .unwrap_or_else(|| "http://127.0.0.1:7420".into());
-    let client = reqwest::Client::new();
+    let client = match crate::commands::lfs::remote_client(&cfg) {
+        Ok(client) => client,
+        Err(e) => return Json(json!({"error": e.to_string()})),
+    };
     let pj: Vec<u8> = client
         .post(format!("{}/lfs/download", remote))
         .json(&json!({"oid": &req.oid, "chunk": "pointer.json"}))
@@ -701,3 +719,31 @@ pub async fn run_api(addr: String) -> Result<()> {
     println!("🔮 Rune API at http://{}", addr);
     serve_api(addr).await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_branch_names_cannot_leave_refs() {
+        let temp = TempDir::new().unwrap();
+        let store = rune_store::Store::open(temp.path()).unwrap();
+        store.create().unwrap();
+        let before = store.list_branches().unwrap();
+
+        for name in ["../x", "../../../x"] {
+            assert!(create_branch_in(&store, name).get("error").is_some(), "{}", name);
+            assert!(checkout_in(&store, name).get("error").is_some(), "{}", name);
+        }
+        assert!(!store.rune_dir.join("refs/x").exists());
+        assert!(!store.rune_dir.join("x").exists());
+        assert!(!temp.path().join("x").exists());
+        assert_eq!(store.list_branches().unwrap(), before);
+        assert_eq!(store.head_ref(), "refs/heads/main");
+
+        assert_eq!(create_branch_in(&store, "feature")["created"], "feature");
+        assert_eq!(checkout_in(&store, "feature")["switched"], "feature");
+        assert_eq!(store.head_ref(), "refs/heads/feature");
+    }
+}
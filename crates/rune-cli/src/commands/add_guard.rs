@@ -0,0 +1,252 @@
+//! Guardrails for `rune add` on whole directories
+//!
+//! A directory argument is expanded to the changed and untracked files
+//! below it that aren't ignored. Before anything is staged, `rune add`
+//! offers to ignore well-known build and dependency directories found in
+//! the expansion, and asks for confirmation when what is left is larger
+//! than `core.add_guard_files` files or `core.add_guard_bytes` bytes.
+//! `--no-guard` skips both.
+
+use anyhow::Result;
+use rune_core::ignore::{IgnoreEngine, IgnoreRule, RuleType};
+use rune_store::{PathChange, Store};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// Directories that are almost always build output or installed
+/// dependencies, never source
+pub const JUNK_DIRS: [&str; 4] = ["node_modules", "target", ".venv", "build"];
+
+/// Directories listed in the summary shown before a large addition
+pub const SUMMARY_DIRS: usize = 5;
+
+/// A file about to be staged
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingFile {
+    pub path: String,
+    pub bytes: u64,
+    /// Found by expanding a directory argument rather than named directly
+    pub expanded: bool,
+}
+
+/// Files and bytes below one directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirSummary {
+    pub dir: String,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// What `rune add <paths>` is about to stage
+#[derive(Debug, Clone, Default)]
+pub struct Expansion {
+    pub files: Vec<PendingFile>,
+}
+
+fn is_same_or_below(path: &str, dir: &str) -> bool {
+    dir.is_empty()
+        || path == dir
+        || path
+            .strip_prefix(dir)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+impl Expansion {
+    pub fn bytes(&self) -> u64 {
+        self.files.iter().map(|f| f.bytes).sum()
+    }
+
+    /// Whether it is more than `max_files` files or `max_bytes` bytes
+    pub fn exceeds(&self, max_files: usize, max_bytes: u64) -> bool {
+        self.files.len() > max_files || self.bytes() > max_bytes
+    }
+
+    /// Top-level directories by bytes staged in them, largest first; files
+    /// at the root count as `.`
+    pub fn biggest_dirs(&self, limit: usize) -> Vec<DirSummary> {
+        let mut dirs = self.group(|path| match path.split_once('/') {
+            Some((top, _)) => Some(top.to_string()),
+            None => Some(".".to_string()),
+        });
+        dirs.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.dir.cmp(&b.dir)));
+        dirs.truncate(limit);
+        dirs
+    }
+
+    /// Directories named in `JUNK_DIRS` that files from expanded directory
+    /// arguments are in, outermost only
+    pub fn junk_dirs(&self) -> Vec<DirSummary> {
+        let expanded = Expansion {
+            files: self.files.iter().filter(|f| f.expanded).cloned().collect(),
+        };
+        expanded.group(|path| {
+            let parts: Vec<&str> = path.split('/').collect();
+            let junk = parts[..parts.len() - 1]
+                .iter()
+                .position(|part| JUNK_DIRS.contains(part))?;
+            Some(parts[..=junk].join("/"))
+        })
+    }
+
+    /// Drop the expanded files below any of `dirs`
+    pub fn exclude(&mut self, dirs: &[String]) {
+        self.files
+            .retain(|f| !f.expanded || !dirs.iter().any(|dir| is_same_or_below(&f.path, dir)));
+    }
+
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.files.iter().map(|f| PathBuf::from(&f.path)).collect()
+    }
+
+    fn group(&self, dir_of: impl Fn(&str) -> Option<String>) -> Vec<DirSummary> {
+        let mut dirs: BTreeMap<String, DirSummary> = BTreeMap::new();
+        for file in &self.files {
+            let Some(dir) = dir_of(&file.path) else {
+                continue;
+            };
+            let summary = dirs.entry(dir.clone()).or_insert(DirSummary {
+                dir,
+                files: 0,
+                bytes: 0,
+            });
+            summary.files += 1;
+            summary.bytes += file.bytes;
+        }
+        dirs.into_values().collect()
+    }
+}
+
+/// Expand the directories among `paths` to the changed and untracked files
+/// below them that aren't ignored; other paths are kept as given
+pub fn expand(store: &Store, paths: &[PathBuf]) -> Result<Expansion> {
+    let size = |path: &str| {
+        fs::symlink_metadata(store.root.join(path))
+            .map(|m| m.len())
+            .unwrap_or(0)
+    };
+    let mut changed: Option<Vec<String>> = None;
+    let mut ignore: Option<IgnoreEngine> = None;
+    let mut expansion = Expansion::default();
+    for path in paths {
+        let rel = store.checked_path(path)?;
+        if !store.root.join(&rel).is_dir() {
+            expansion.files.push(PendingFile {
+                bytes: size(&rel),
+                path: rel,
+                expanded: false,
+            });
+            continue;
+        }
+        if changed.is_none() {
+            let status = store.status()?;
+            let mut files: Vec<String> =
+                status.working.into_iter().chain(status.untracked).collect();
+            for rename in status.renamed {
+                if let PathChange::Renamed { from, to, .. } = rename {
+                    files.extend([from, to]);
+                }
+            }
+            files.sort();
+            files.dedup();
+            changed = Some(files);
+            ignore = Some(IgnoreEngine::new(&store.root)?);
+        }
+        let (Some(changed), Some(ignore)) = (&changed, &mut ignore) else {
+            continue;
+        };
+        for file in changed.iter().filter(|file| is_same_or_below(file, &rel)) {
+            if ignore.should_ignore(file) || expansion.files.iter().any(|f| &f.path == file) {
+                continue;
+            }
+            expansion.files.push(PendingFile {
+                path: file.clone(),
+                bytes: size(file),
+                expanded: true,
+            });
+        }
+    }
+    Ok(expansion)
+}
+
+/// Add a project ignore rule for each of `dirs`
+pub fn ignore_dirs(store: &Store, dirs: &[String]) -> Result<()> {
+    let mut engine = IgnoreEngine::new(&store.root)?;
+    for dir in dirs {
+        engine.add_rule(IgnoreRule {
+            pattern: format!("{}/", dir),
+            rule_type: RuleType::Ignore,
+            priority: 0,
+            description: Some("Build output or dependencies, ignored by rune add".to_string()),
+            condition: None,
+        });
+    }
+    engine.save_config()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending(path: &str, bytes: u64) -> PendingFile {
+        PendingFile {
+            path: path.to_string(),
+            bytes,
+            expanded: true,
+        }
+    }
+
+    #[test]
+    fn test_junk_dirs_and_biggest_dirs() {
+        let mut expansion = Expansion {
+            files: vec![
+                pending("README.md", 10),
+                pending("src/main.rs", 200),
+                pending("web/node_modules/a/index.js", 300),
+                pending("web/node_modules/b/node_modules/c.js", 400),
+                pending("target", 5),
+                PendingFile {
+                    path: "build/keep.txt".to_string(),
+                    bytes: 1,
+                    expanded: false,
+                },
+            ],
+        };
+        assert!(expansion.exceeds(5, u64::MAX));
+        assert!(expansion.exceeds(6, 915));
+        assert!(!expansion.exceeds(6, 916));
+
+        // A file named like a junk directory is not one, and named paths
+        // were asked for
+        let junk = expansion.junk_dirs();
+        assert_eq!(
+            junk,
+            [DirSummary {
+                dir: "web/node_modules".to_string(),
+                files: 2,
+                bytes: 700
+            }]
+        );
+
+        let biggest: Vec<(String, u64)> = expansion
+            .biggest_dirs(3)
+            .into_iter()
+            .map(|d| (d.dir, d.bytes))
+            .collect();
+        assert_eq!(
+            biggest,
+            [
+                ("web".to_string(), 700),
+                ("src".to_string(), 200),
+                (".".to_string(), 15)
+            ]
+        );
+
+        expansion.exclude(&["web/node_modules".to_string(), "build".to_string()]);
+        let left: Vec<&str> = expansion.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(
+            left,
+            ["README.md", "src/main.rs", "target", "build/keep.txt"]
+        );
+    }
+}
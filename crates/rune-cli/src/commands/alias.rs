@@ -0,0 +1,351 @@
+//! `rune alias` and the expansion of aliases and command defaults
+//!
+//! Both live in `.rune/config.toml`: `[alias]` maps a name to a command
+//! line, `[defaults]` maps a command to arguments it gets unless the
+//! command line already sets the same options. `expand` rewrites the
+//! arguments before clap parses them. Everything after `--` is passed
+//! through untouched.
+
+use crate::style::Style;
+use anyhow::{bail, Result};
+use clap::{Command, Subcommand};
+use rune_store::Store;
+use std::collections::{BTreeMap, HashSet};
+
+/// Aliases an alias may go through before expansion gives up
+const MAX_ALIAS_DEPTH: usize = 5;
+
+#[derive(Subcommand, Debug)]
+pub enum AliasCmd {
+    /// List aliases and command defaults
+    List,
+    /// Define an alias, e.g. `rune alias set lg "log -n 20 --graph"`
+    Set {
+        /// Alias name, or with --defaults the command to give arguments to
+        name: String,
+        /// Command line it stands for; quote arguments with spaces
+        command: String,
+        /// Set the default arguments of command NAME instead
+        #[arg(long)]
+        defaults: bool,
+    },
+    /// Remove an alias
+    Unset {
+        /// Alias name, or with --defaults the command
+        name: String,
+        /// Remove the default arguments of command NAME instead
+        #[arg(long)]
+        defaults: bool,
+    },
+}
+
+/// Run `cmd`; `cli` is the command tree, to tell built-in commands apart
+pub fn run(cmd: AliasCmd, cli: &Command) -> Result<()> {
+    let store = Store::discover(std::env::current_dir()?)?;
+    let mut cfg = store.config();
+    match cmd {
+        AliasCmd::List => {
+            if cfg.alias.is_empty() && cfg.defaults.is_empty() {
+                Style::info("No aliases or command defaults configured");
+                return Ok(());
+            }
+            for (name, command) in &cfg.alias {
+                println!("{} = {}", name, command);
+            }
+            for (name, args) in &cfg.defaults {
+                println!("{} (defaults) = {}", name, args);
+            }
+            return Ok(());
+        }
+        AliasCmd::Set { name, command, defaults: true } => {
+            if cli.find_subcommand(&name).is_none() {
+                bail!("there is no command '{}' to give defaults to", name);
+            }
+            split_command_line(&command)?;
+            cfg.defaults.insert(name.clone(), command.clone());
+            Style::success(&format!("rune {} now defaults to `{}`", name, command));
+        }
+        AliasCmd::Set { name, command, defaults: false } => {
+            if name.is_empty() || name.starts_with('-') || name.contains(char::is_whitespace) {
+                bail!("'{}' can't be an alias name", name);
+            }
+            if cli.find_subcommand(&name).is_some() {
+                bail!("'{}' is a built-in command; an alias can't replace it", name);
+            }
+            if split_command_line(&command)?.is_empty() {
+                bail!("alias '{}' needs a command to stand for", name);
+            }
+            cfg.alias.insert(name.clone(), command.clone());
+            expand(vec!["rune".into(), name.clone()], &cfg.alias, &BTreeMap::new(), cli)?;
+            Style::success(&format!("rune {} now runs `rune {}`", name, command));
+        }
+        AliasCmd::Unset { name, defaults } => {
+            let (removed, what) = if defaults {
+                (cfg.defaults.remove(&name), "command defaults")
+            } else {
+                (cfg.alias.remove(&name), "alias")
+            };
+            if removed.is_none() {
+                bail!("no {} named '{}'", what, name);
+            }
+            Style::success(&format!("Removed {} '{}'", what, name));
+        }
+    }
+    store.write_config(&cfg)
+}
+
+/// `args` (program name first) with an alias in command position replaced
+/// by what it stands for, repeatedly, and the command's defaults added
+/// unless the command line sets the same options
+pub fn expand(
+    mut args: Vec<String>,
+    aliases: &BTreeMap<String, String>,
+    defaults: &BTreeMap<String, String>,
+    cli: &Command,
+) -> Result<Vec<String>> {
+    let Some(pos) = command_position(&args, cli) else {
+        return Ok(args);
+    };
+
+    let mut chain: Vec<String> = Vec::new();
+    while let Some(line) = aliases.get(&args[pos]) {
+        let name = args[pos].clone();
+        if cli.find_subcommand(&name).is_some() {
+            bail!(
+                "alias '{}' shadows the built-in command; remove it with `rune alias unset {}`",
+                name,
+                name
+            );
+        }
+        if chain.contains(&name) {
+            bail!("alias '{}' expands to itself: {} -> {}", chain[0], chain.join(" -> "), name);
+        }
+        chain.push(name.clone());
+        if chain.len() > MAX_ALIAS_DEPTH {
+            bail!(
+                "alias '{}' goes through more than {} aliases: {}",
+                chain[0],
+                MAX_ALIAS_DEPTH,
+                chain.join(" -> ")
+            );
+        }
+        let expansion = split_command_line(line)?;
+        if expansion.is_empty() {
+            bail!("alias '{}' is empty", name);
+        }
+        args.splice(pos..=pos, expansion);
+    }
+
+    let Some(subcommand) = cli.find_subcommand(&args[pos]) else {
+        return Ok(args);
+    };
+    let Some(line) = defaults.get(subcommand.get_name()) else {
+        return Ok(args);
+    };
+    let given: HashSet<String> = options(subcommand, &args[pos + 1..]).into_iter().map(|(key, _)| key).collect();
+    let added: Vec<String> = options(subcommand, &split_command_line(line)?)
+        .into_iter()
+        .filter(|(key, _)| !given.contains(key))
+        .flat_map(|(_, tokens)| tokens)
+        .collect();
+    args.splice(pos + 1..pos + 1, added);
+    Ok(args)
+}
+
+/// Split `line` into arguments the way a shell would: whitespace separates
+/// them, quotes keep spaces, backslash escapes the next character except
+/// inside single quotes
+pub fn split_command_line(line: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some('\''), '\'') | (Some('"'), '"') => quote = None,
+            (Some('"'), '\\') => match chars.next() {
+                Some(next @ ('"' | '\\')) => word.push(next),
+                Some(next) => {
+                    word.push('\\');
+                    word.push(next);
+                }
+                None => word.push('\\'),
+            },
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, '\\') => {
+                word.extend(chars.next());
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if let Some(q) = quote {
+        bail!("unterminated {} in `{}`", q, line);
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// Index of the command name in `args`, past the program name and any
+/// global options
+fn command_position(args: &[String], cli: &Command) -> Option<usize> {
+    let mut i = 1;
+    while let Some(arg) = args.get(i) {
+        if arg == "--" {
+            return None;
+        }
+        if !arg.starts_with('-') {
+            return Some(i);
+        }
+        let takes_separate_value = arg
+            .strip_prefix("--")
+            .filter(|long| !long.contains('='))
+            .and_then(|long| cli.get_arguments().find(|a| a.get_long() == Some(long)))
+            .is_some_and(|a| a.get_action().takes_values());
+        i += if takes_separate_value { 2 } else { 1 };
+    }
+    None
+}
+
+/// The options in `tokens`, up to a `--`, each as the argument it sets
+/// (its id, or the option as written when `cmd` doesn't know it) with the
+/// tokens that make it up. Anything else counts as its own option.
+fn options(cmd: &Command, tokens: &[String]) -> Vec<(String, Vec<String>)> {
+    let mut options = Vec::new();
+    let mut tokens = tokens.iter();
+    while let Some(token) = tokens.next() {
+        if token == "--" {
+            break;
+        }
+        let (arg, inline_value) = if let Some(long) = token.strip_prefix("--") {
+            let (name, value) = match long.split_once('=') {
+                Some((name, _)) => (name, true),
+                None => (long, false),
+            };
+            (cmd.get_arguments().find(|a| a.get_long() == Some(name)), value)
+        } else if let Some(short) = token.strip_prefix('-').filter(|s| !s.is_empty()) {
+            let mut chars = short.chars();
+            let flag = chars.next();
+            (cmd.get_arguments().find(|a| a.get_short() == flag), chars.next().is_some())
+        } else {
+            options.push((token.clone(), vec![token.clone()]));
+            continue;
+        };
+        let Some(arg) = arg else {
+            options.push((token.split('=').next().unwrap_or(token).to_string(), vec![token.clone()]));
+            continue;
+        };
+        let mut group = vec![token.clone()];
+        if arg.get_action().takes_values() && !inline_value {
+            group.extend(tokens.next().cloned());
+        }
+        options.push((arg.get_id().to_string(), group));
+    }
+    options
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, ArgAction};
+
+    fn cli() -> Command {
+        Command::new("rune")
+            .arg(Arg::new("verbose").short('v').long("verbose").action(ArgAction::SetTrue).global(true))
+            .arg(Arg::new("rune_dir").long("rune-dir").global(true))
+            .subcommand(
+                Command::new("log")
+                    .arg(Arg::new("limit").short('n').long("limit"))
+                    .arg(Arg::new("graph").long("graph").action(ArgAction::SetTrue))
+                    .arg(Arg::new("paths").num_args(0..)),
+            )
+            .subcommand(Command::new("commit").arg(Arg::new("message").short('m').long("message")))
+    }
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    fn map(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    fn expand_aliases(args: &[&str], aliases: &[(&str, &str)]) -> Result<Vec<String>> {
+        expand(strings(args), &map(aliases), &BTreeMap::new(), &cli())
+    }
+
+    #[test]
+    fn test_alias_keeps_quoted_arguments_and_double_dash() {
+        let aliases = [("fix", r#"commit -m "quick fix: don't \"panic\"""#), ("lp", "log -- 'my dir'")];
+        let args = expand_aliases(&["rune", "-v", "fix", "--", "-n"], &aliases).unwrap();
+        assert_eq!(args, strings(&["rune", "-v", "commit", "-m", r#"quick fix: don't "panic""#, "--", "-n"]));
+
+        let args = expand_aliases(&["rune", "--rune-dir", "fix", "lp", "a b"], &aliases).unwrap();
+        assert_eq!(args, strings(&["rune", "--rune-dir", "fix", "log", "--", "my dir", "a b"]));
+
+        // Nothing before `--` names a command
+        let args = expand_aliases(&["rune", "--", "fix"], &aliases).unwrap();
+        assert_eq!(args, strings(&["rune", "--", "fix"]));
+        assert!(split_command_line("log 'open").is_err());
+    }
+
+    #[test]
+    fn test_recursive_alias_is_an_error() {
+        let err = expand_aliases(&["rune", "a"], &[("a", "b --graph"), ("b", "a")]).unwrap_err().to_string();
+        assert!(err.contains("a -> b -> a"), "{}", err);
+
+        let err = expand_aliases(&["rune", "log"], &[("log", "log -n 5")]).unwrap_err().to_string();
+        assert!(err.contains("shadows the built-in command"), "{}", err);
+    }
+
+    #[test]
+    fn test_alias_of_alias_resolves_up_to_the_depth_limit() {
+        let args = expand_aliases(&["rune", "lg", "src"], &[("lg", "l --graph"), ("l", "log -n 20")]).unwrap();
+        assert_eq!(args, strings(&["rune", "log", "-n", "20", "--graph", "src"]));
+
+        let mut aliases: BTreeMap<String, String> =
+            (1..MAX_ALIAS_DEPTH).map(|i| (format!("a{}", i), format!("a{}", i + 1))).collect();
+        aliases.insert(format!("a{}", MAX_ALIAS_DEPTH), "log".into());
+        let run = |aliases: &BTreeMap<String, String>, name: &str| {
+            expand(strings(&["rune", name]), aliases, &BTreeMap::new(), &cli())
+        };
+        assert_eq!(run(&aliases, "a1").unwrap(), strings(&["rune", "log"]));
+
+        aliases.insert("a0".into(), "a1".into());
+        let err = run(&aliases, "a0").unwrap_err().to_string();
+        assert!(err.contains("more than 5 aliases"), "{}", err);
+    }
+
+    #[test]
+    fn test_defaults_yield_to_explicit_options() {
+        let defaults = map(&[("log", "-n 20 --graph")]);
+        let run = |args: &[&str]| expand(strings(args), &BTreeMap::new(), &defaults, &cli()).unwrap();
+
+        assert_eq!(run(&["rune", "log"]), strings(&["rune", "log", "-n", "20", "--graph"]));
+        assert_eq!(run(&["rune", "log", "--limit", "5"]), strings(&["rune", "log", "--graph", "--limit", "5"]));
+        assert_eq!(run(&["rune", "log", "-n5", "--graph"]), strings(&["rune", "log", "-n5", "--graph"]));
+        // Options after `--` are paths, not overrides
+        assert_eq!(run(&["rune", "log", "--", "-n"]), strings(&["rune", "log", "-n", "20", "--graph", "--", "-n"]));
+        assert_eq!(run(&["rune", "commit", "-m", "x"]), strings(&["rune", "commit", "-m", "x"]));
+
+        // Defaults of the command an alias names apply too
+        let args = expand(strings(&["rune", "lg"]), &map(&[("lg", "log --limit=3")]), &defaults, &cli()).unwrap();
+        assert_eq!(args, strings(&["rune", "log", "--graph", "--limit=3"]));
+    }
+}
@@ -0,0 +1,130 @@
+//! Moving large files into LFS as they are committed
+//!
+//! With `core.auto_lfs_threshold` set, `rune commit` looks at what is
+//! staged first: each regular file over the threshold goes through the LFS
+//! clean filter, its pointer is staged in its place, and the LFS manifest
+//! records it as auto-migrated so `rune lfs status` and `rune lfs migrate`
+//! can tell it apart. Cleaning reads the working file, so a file edited
+//! since it was staged is left alone rather than committing the edit.
+
+use crate::style::Style;
+use anyhow::Result;
+use rune_lfs::Lfs;
+use rune_store::Store;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+/// A staged file the commit moved into LFS
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Spilled {
+    pub path: String,
+    pub size: u64,
+}
+
+/// Move the staged files over `core.auto_lfs_threshold` into LFS and stage
+/// their pointers instead; nothing happens while the threshold is unset
+pub fn spill_large_files(store: &Store) -> Result<Vec<Spilled>> {
+    let Some(threshold) = store.config().core.auto_lfs_threshold else {
+        return Ok(Vec::new());
+    };
+    let oversized = store.oversized_staged(threshold)?;
+    if oversized.is_empty() {
+        return Ok(Vec::new());
+    }
+    let lfs = Lfs::open(&store.root, &store.rune_dir)?;
+    let index = store.read_index()?;
+    let mut spilled = Vec::new();
+    let _batch = store.batch_index_writes();
+    for (path, size) in oversized {
+        let current = fs::read(store.root.join(&path)).ok().map(|content| blake3::hash(&content).to_hex().to_string());
+        if current.as_ref() != index.blobs.get(&path) {
+            continue;
+        }
+        lfs.auto_migrate(&path)?;
+        store.stage_file(&path)?;
+        spilled.push(Spilled { path, size });
+    }
+    Ok(spilled)
+}
+
+/// Pattern to suggest tracking for `path`: `*.<ext>`, or the path itself
+/// when it has no extension
+pub fn suggested_pattern(path: &str) -> String {
+    match Path::new(path).extension() {
+        Some(ext) => format!("*.{}", ext.to_string_lossy()),
+        None => path.to_string(),
+    }
+}
+
+/// Tell the user which files went to LFS and how to track them for good
+pub fn print_notice(spilled: &[Spilled], threshold: u64) {
+    if spilled.is_empty() {
+        return;
+    }
+    Style::warning(&format!(
+        "Moved {} file(s) over core.auto_lfs_threshold ({} bytes) to LFS:",
+        spilled.len(),
+        threshold
+    ));
+    for file in spilled {
+        println!("  📦 {} ({} bytes)", file.path, file.size);
+    }
+    let patterns: BTreeSet<String> = spilled.iter().map(|file| suggested_pattern(&file.path)).collect();
+    let patterns: Vec<String> = patterns.into_iter().map(|pattern| format!("\"{}\"", pattern)).collect();
+    Style::info(&format!("Track them from now on with: rune lfs track {}", patterns.join(" ")));
+    Style::info("The working copies are now pointers; `rune lfs smudge <path>` brings the content back");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support::temp_repo;
+    use rune_lfs::looks_like_pointer;
+    use tempfile::TempDir;
+
+    fn repo_with_threshold(threshold: u64) -> (TempDir, Store) {
+        let (temp, store) = temp_repo();
+        let mut cfg = store.config();
+        cfg.core.auto_lfs_threshold = Some(threshold);
+        store.write_config(&cfg).unwrap();
+        (temp, store)
+    }
+
+    #[test]
+    fn test_files_over_the_threshold_are_staged_as_pointers() {
+        let (temp, store) = repo_with_threshold(1000);
+        fs::write(temp.path().join("video.mp4"), vec![3u8; 5000]).unwrap();
+        fs::write(temp.path().join("notes.txt"), "small\n").unwrap();
+        store.stage_file("video.mp4").unwrap();
+        store.stage_file("notes.txt").unwrap();
+
+        let spilled = spill_large_files(&store).unwrap();
+        assert_eq!(spilled, [Spilled { path: "video.mp4".into(), size: 5000 }]);
+        assert_eq!(suggested_pattern(&spilled[0].path), "*.mp4");
+
+        let staged = store.staged_content("video.mp4").unwrap().unwrap();
+        assert!(looks_like_pointer(&staged));
+        assert_eq!(store.staged_content("notes.txt").unwrap().unwrap(), b"small\n");
+        let lfs = Lfs::open(&store.root, &store.rune_dir).unwrap();
+        assert_eq!(lfs.auto_migrated().unwrap(), ["video.mp4"]);
+        assert!(store.oversized_staged(1000).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_nothing_moves_without_a_threshold_or_after_an_edit() {
+        let (temp, store) = repo_with_threshold(1000);
+        fs::write(temp.path().join("data.bin"), vec![1u8; 2000]).unwrap();
+        store.stage_file("data.bin").unwrap();
+        // Edited after staging: the staged version is not what cleaning would read
+        fs::write(temp.path().join("data.bin"), vec![2u8; 2000]).unwrap();
+        assert!(spill_large_files(&store).unwrap().is_empty());
+
+        let mut cfg = store.config();
+        cfg.core.auto_lfs_threshold = None;
+        store.write_config(&cfg).unwrap();
+        store.stage_file("data.bin").unwrap();
+        assert!(spill_large_files(&store).unwrap().is_empty());
+        assert_eq!(store.staged_content("data.bin").unwrap().unwrap(), vec![2u8; 2000]);
+    }
+}
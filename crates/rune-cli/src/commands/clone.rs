@@ -2,6 +2,7 @@ use anyhow::Result;
 use clap::Args;
 use rune_remote::RemoteCommands;
 use std::path::PathBuf;
+use crate::exit_codes::{CliError, ErrorKind};
 
 #[derive(Debug, Args)]
 pub struct CloneArgs {
@@ -24,6 +25,9 @@ pub struct CloneArgs {
     /// Depth of history to clone
     #[arg(long)]
     pub depth: Option<u32>,
+    /// Workspace profile from .runeworkspaces/ to set up after cloning
+    #[arg(long)]
+    pub workspace: Option<String>,
 }
 
 pub async fn handle_clone_command(args: CloneArgs) -> Result<()> {
@@ -41,13 +45,18 @@ pub async fn handle_clone_command(args: CloneArgs) -> Result<()> {
     
     // Check if directory already exists
     if local_path.exists() {
-        anyhow::bail!("Directory '{}' already exists", local_dir);
+        let message = format!("Directory '{}' already exists", local_dir);
+        return Err(CliError::new(ErrorKind::PreconditionFailed, message).into());
     }
     
     // Clone the repository
     RemoteCommands::clone(&args.url, Some(&local_dir), args.token).await?;
     
     println!("✅ Repository cloned successfully into '{}'", local_dir);
+    if let Some(profile) = &args.workspace {
+        let layout = rune_core::layout::RepoLayout::discover(&local_path)?;
+        crate::commands::workspace::use_profile(&layout, profile)?;
+    }
     println!("");
     println!("Next steps:");
     println!("  cd {}", local_dir);
@@ -81,7 +90,8 @@ fn extract_repo_name(url: &str) -> Result<String> {
         .trim_end_matches(".git");
     
     if name.is_empty() {
-        anyhow::bail!("Could not determine repository name from URL: {}", url);
+        let message = format!("Could not determine repository name from URL: {}", url);
+        return Err(CliError::new(ErrorKind::Usage, message).into());
     }
     
     Ok(name.to_string())
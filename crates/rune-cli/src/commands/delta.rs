@@ -1,5 +1,7 @@
 use anyhow::Result;
 use clap::Subcommand;
+use rune_core::attributes::{AttributeEngine, DiffHandling};
+use crate::exit_codes::{CliError, ErrorKind};
 
 #[derive(Subcommand, Debug)]
 pub enum DeltaCmd {
@@ -54,12 +56,12 @@ pub fn run(cmd: DeltaCmd) -> Result<()> {
             let b = std::fs::read(base)?;
             let n = std::fs::read(new)?;
             let p = rune_delta::make(&b, &n, chunk)?;
-            std::fs::write(out, serde_json::to_vec_pretty(&p)?)?;
+            rune_delta::write_patch(&p, std::fs::File::create(out)?)?;
             println!("delta written");
         }
         DeltaCmd::Apply { base, patch, out } => {
             let b = std::fs::read(base)?;
-            let p: rune_delta::Patch = serde_json::from_slice(&std::fs::read(patch)?)?;
+            let p = rune_delta::read_patch(std::fs::File::open(patch)?)?;
             let r = rune_delta::apply(&b, &p)?;
             if let Some(pp) = out.parent() {
                 std::fs::create_dir_all(pp)?;
@@ -72,7 +74,7 @@ pub fn run(cmd: DeltaCmd) -> Result<()> {
                 "character" | "char" => rune_delta::DiffMode::Character,
                 "word" => rune_delta::DiffMode::Word,
                 "line" => rune_delta::DiffMode::Line,
-                _ => anyhow::bail!("Invalid diff mode. Use: character, word, or line"),
+                _ => return Err(CliError::new(ErrorKind::Usage, "Invalid diff mode. Use: character, word, or line").into()),
             };
 
             let options = rune_delta::DiffOptions {
@@ -87,10 +89,24 @@ pub fn run(cmd: DeltaCmd) -> Result<()> {
                 // Single file diff
                 let old_content = std::fs::read(&old)?;
                 let new_content = std::fs::read(&new)?;
-                
-                let diff_result = rune_delta::enhanced_diff(&old_content, &new_content, &options)?;
+
                 println!("📄 Diff: {} -> {}", old.display(), new.display());
-                println!("{}", diff_result);
+                match diff_handling(&new) {
+                    DiffHandling::Binary => {
+                        println!("Binary files {} and {} differ", old.display(), new.display());
+                    }
+                    DiffHandling::Generated => {
+                        println!(
+                            "Generated file, diff suppressed ({} -> {} bytes)",
+                            old_content.len(),
+                            new_content.len()
+                        );
+                    }
+                    DiffHandling::Text => {
+                        let diff_result = rune_delta::enhanced_diff(&old_content, &new_content, &options)?;
+                        println!("{}", diff_result);
+                    }
+                }
             } else if old.is_dir() && new.is_dir() {
                 // Directory diff with rename/copy detection
                 use std::collections::HashMap;
@@ -172,7 +188,7 @@ pub fn run(cmd: DeltaCmd) -> Result<()> {
                     }
                 }
             } else {
-                anyhow::bail!("Both paths must be either files or directories");
+                return Err(CliError::new(ErrorKind::Usage, "Both paths must be either files or directories").into());
             }
         }
         DeltaCmd::Similarity { file1, file2 } => {
@@ -195,3 +211,16 @@ pub fn run(cmd: DeltaCmd) -> Result<()> {
     }
     Ok(())
 }
+
+/// Look up `.runeattributes` for a file when it lives inside a rune repository
+fn diff_handling(path: &std::path::Path) -> DiffHandling {
+    let Ok(full) = path.canonicalize() else {
+        return DiffHandling::Text;
+    };
+    let Ok(store) = rune_store::Store::discover(full.parent().unwrap_or(&full)) else {
+        return DiffHandling::Text;
+    };
+    AttributeEngine::new(&store.root)
+        .map(|attributes| attributes.diff_handling(&full))
+        .unwrap_or(DiffHandling::Text)
+}
@@ -1,7 +1,9 @@
 use anyhow::Result;
 use clap::{ArgAction, Args, Subcommand, ValueEnum};
-use rune_draft::DraftManager;
-use rune_store::Store;
+use rune_draft::{DraftBranchGuard, DraftManager, DraftPolicy, DraftSafetyNet, DraftSource, Page, ReviewFormat};
+use rune_store::{OperationKind, Store};
+use std::io::{BufRead, Write};
+use crate::exit_codes::{CliError, ErrorKind};
 use crate::style::Style;
 
 #[derive(Debug, Clone, ValueEnum)]
@@ -10,6 +12,12 @@ pub enum OutputFormat {
     Json,
 }
 
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ReviewOutput {
+    Markdown,
+    Html,
+}
+
 #[derive(Debug, Args)]
 pub struct DraftArgs {
     #[command(subcommand)]
@@ -28,6 +36,15 @@ pub enum DraftCmd {
         /// Tags to apply to the draft
         #[arg(short, long, action = ArgAction::Append)]
         tags: Vec<String>,
+        /// Only capture changes to these files or directories
+        #[arg(long, num_args = 1..)]
+        paths: Vec<std::path::PathBuf>,
+        /// Capture the staged content instead of the working tree
+        #[arg(long)]
+        staged: bool,
+        /// Revert the captured files to their base content afterwards
+        #[arg(long)]
+        remove: bool,
     },
     /// List all drafts
     List {
@@ -40,6 +57,12 @@ pub enum DraftCmd {
         /// Output format
         #[arg(long, value_enum, default_value = "table")]
         format: OutputFormat,
+        /// Skip this many of the matching drafts, newest first
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+        /// Show at most this many drafts
+        #[arg(long)]
+        limit: Option<usize>,
     },
     /// Apply a draft to the working directory
     Apply {
@@ -72,6 +95,26 @@ pub enum DraftCmd {
         #[arg(short, long)]
         content: bool,
     },
+    /// Render a draft as a review document with metadata and diffs against
+    /// its base commit, for reading without applying it
+    Review {
+        /// Draft ID or name to review
+        draft: String,
+        /// Write the document to this file instead of printing it; a
+        /// `.html` name selects HTML
+        #[arg(short, long)]
+        output: Option<std::path::PathBuf>,
+        /// Document format; markdown unless --open or the output name
+        /// says otherwise
+        #[arg(long, value_enum)]
+        format: Option<ReviewOutput>,
+        /// Render HTML to a temporary file and open it in the browser
+        #[arg(long)]
+        open: bool,
+        /// Show every diff in full instead of cutting long ones off
+        #[arg(long)]
+        full: bool,
+    },
     /// Create an automatic checkpoint
     Checkpoint {
         /// Optional name for the checkpoint
@@ -106,21 +149,39 @@ pub enum DraftCmd {
 
 pub fn execute_draft_command(args: DraftArgs) -> Result<()> {
     let store = Store::discover(&std::env::current_dir()?)?;
-    let mut draft_manager = DraftManager::new(store)?;
+    let mut draft_manager = DraftManager::new(Store::open(&store.root)?)?;
 
     match args.command {
-        DraftCmd::Create { name, description, tags } => {
-            let draft_id = draft_manager.create_draft(name.clone(), description)?;
+        DraftCmd::Create { name, description, tags, paths, staged, remove } => {
+            let source = if staged { DraftSource::StagedOnly } else { DraftSource::Worktree };
+            let cwd = std::env::current_dir()?;
+            let paths: Vec<_> = paths.iter().map(|p| cwd.join(p)).collect();
+            let selection = (!paths.is_empty()).then_some(paths.as_slice());
+            let capture = draft_manager.create_draft_from(selection, source, name.clone(), description)?;
+            for path in &capture.skipped {
+                Style::warning(&format!("Skipped {}: no changes", path.strip_prefix(&cwd).unwrap_or(path).display()));
+            }
             
             if !tags.is_empty() {
-                draft_manager.add_tags(&draft_id, tags)?;
+                draft_manager.add_tags(&capture.id, tags)?;
+            }
+            if remove {
+                draft_manager.remove_captured(&capture.id)?;
             }
             
-            Style::success(&format!("Created draft '{}' ({})", name, &draft_id[..8]));
+            Style::success(&format!(
+                "Created draft '{}' ({}) with {} files",
+                name,
+                &capture.id[..8],
+                capture.captured.len()
+            ));
+            if remove {
+                Style::info(&format!("Reverted {}", capture.captured.join(", ")));
+            }
         }
 
-        DraftCmd::List { tags, active, format } => {
-            let drafts = draft_manager.list_drafts()?;
+        DraftCmd::List { tags, active, format, offset, limit } => {
+            let drafts = draft_manager.list_draft_summaries(Page::default())?;
             
             let filtered_drafts: Vec<_> = drafts
                 .into_iter()
@@ -134,6 +195,7 @@ pub fn execute_draft_command(args: DraftArgs) -> Result<()> {
                     true
                 })
                 .collect();
+            let filtered_drafts = Page::new(offset, limit).apply(filtered_drafts);
 
             match format {
                 OutputFormat::Table => {
@@ -157,7 +219,7 @@ pub fn execute_draft_command(args: DraftArgs) -> Result<()> {
                             id_short,
                             draft.name,
                             draft.author.name,
-                            draft.files.len(),
+                            draft.file_count,
                             created,
                             tags_str
                         );
@@ -201,7 +263,12 @@ pub fn execute_draft_command(args: DraftArgs) -> Result<()> {
                 }
             }
 
+            let draft_file = draft_manager.drafts_dir().join(format!("{}.json", draft_id));
+            let journal = store
+                .begin_operation(OperationKind::DraftDelete, &draft_info.name, draft_info.is_active)?
+                .watch(&store, draft_file)?;
             draft_manager.delete_draft(&draft_id)?;
+            journal.finish(&store)?;
             Style::success(&format!("Deleted draft '{}'", draft_info.name));
         }
 
@@ -250,13 +317,54 @@ pub fn execute_draft_command(args: DraftArgs) -> Result<()> {
             }
         }
 
+        DraftCmd::Review { draft, output, format, open, full } => {
+            let draft_id = resolve_draft_identifier(&draft_manager, &draft)?;
+            let html_name = output
+                .as_ref()
+                .and_then(|path| path.extension())
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("html") || ext.eq_ignore_ascii_case("htm"));
+            let format = match format {
+                Some(ReviewOutput::Html) => ReviewFormat::Html,
+                Some(ReviewOutput::Markdown) => ReviewFormat::Markdown,
+                None if open || html_name => ReviewFormat::Html,
+                None => ReviewFormat::Markdown,
+            };
+            let limit = (!full).then_some(rune_draft::REVIEW_DIFF_LINE_LIMIT);
+            let review = draft_manager.render_review_limited(&draft_id, format, limit)?;
+            if open {
+                let path = std::env::temp_dir().join(format!("rune-draft-review-{}.html", &draft_id[..8]));
+                let html = match format {
+                    ReviewFormat::Html => review.clone(),
+                    ReviewFormat::Markdown => draft_manager.render_review_limited(&draft_id, ReviewFormat::Html, limit)?,
+                };
+                std::fs::write(&path, html)?;
+                open::that(&path)?;
+                Style::info(&format!("Opened {}", path.display()));
+            }
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, &review)?;
+                    Style::success(&format!("Wrote review of draft '{}' to {}", draft, path.display()));
+                }
+                // Printed alone so it can be piped
+                None if !open => {
+                    print!("{}", review);
+                    return Ok(());
+                }
+                None => {}
+            }
+            if !full && review.contains("Diff truncated:") {
+                Style::info("Some diffs were cut off; use --full to include them whole");
+            }
+        }
+
         DraftCmd::Checkpoint { name } => {
             let draft_id = draft_manager.create_checkpoint(name)?;
             Style::success(&format!("Created checkpoint ({})", &draft_id[..8]));
         }
 
         DraftCmd::Cleanup { keep_days, force } => {
-            let drafts = draft_manager.list_drafts()?;
+            let drafts = draft_manager.list_draft_summaries(Page::default())?;
             let cleanup_days = keep_days.unwrap_or(draft_manager.config().auto_cleanup_days);
             
             let old_drafts: Vec<_> = drafts
@@ -308,7 +416,7 @@ pub fn execute_draft_command(args: DraftArgs) -> Result<()> {
 
 /// Resolve a draft identifier (name or ID) to a full draft ID
 fn resolve_draft_identifier(manager: &DraftManager, identifier: &str) -> Result<String> {
-    let drafts = manager.list_drafts()?;
+    let drafts = manager.list_draft_summaries(Page::default())?;
     
     // First try exact ID match
     if drafts.iter().any(|d| d.id == identifier) {
@@ -325,5 +433,113 @@ fn resolve_draft_identifier(manager: &DraftManager, identifier: &str) -> Result<
         return Ok(draft.id.clone());
     }
     
-    anyhow::bail!("No draft found with identifier '{}'", identifier);
+    Err(CliError::new(ErrorKind::Usage, format!("No draft found with identifier '{}'", identifier)).into())
+}
+
+/// The repository store with a `DraftBranchGuard` installed, for commands
+/// that move HEAD or bring in another branch
+pub fn guarded_store(policy: DraftPolicy) -> Result<Store> {
+    let mut store = safety_netted_store()?;
+    store.add_branch_hook(DraftBranchGuard::new(policy));
+    Ok(store)
+}
+
+/// The repository store with a `DraftSafetyNet` installed, for commands that
+/// can throw away uncommitted work
+pub fn safety_netted_store() -> Result<Store> {
+    let mut store = Store::discover(std::env::current_dir()?)?;
+    store.add_branch_hook(DraftSafetyNet);
+    Ok(store)
+}
+
+/// After switching to `branch`, offer to re-apply the draft that was
+/// auto-shelved when we last left it. Returns whether it was re-applied.
+pub fn offer_reapply(store: &Store, branch: &str, input: &mut impl BufRead) -> Result<bool> {
+    let mut manager = DraftManager::new(Store::open(&store.root)?)?;
+    let Some(draft) = manager.auto_shelved_for(branch)? else {
+        return Ok(false);
+    };
+    print!(
+        "Re-apply draft '{}' shelved when you left {}? [Y/n] ",
+        draft.name,
+        Style::branch_name(branch)
+    );
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    let answer = answer.trim().to_lowercase();
+    // Either way the offer is made once; the draft stays in `rune draft list`
+    manager.forget_auto_shelved(branch)?;
+    if answer.is_empty() || answer.starts_with('y') {
+        manager.apply_draft(&draft.id)?;
+        Style::success(&format!("Applied draft '{}'", draft.name));
+        Ok(true)
+    } else {
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support::{author, temp_repo};
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// `main` with one commit and a `feature` branch one commit ahead,
+    /// with an (empty) draft applied on `main`
+    fn repo_with_draft() -> (TempDir, Store, String) {
+        let (temp_dir, store) = temp_repo();
+        fs::write(temp_dir.path().join("a.txt"), "a\n").unwrap();
+        store.stage_file("a.txt").unwrap();
+        store.commit("base", author()).unwrap();
+        store.create_branch("feature").unwrap();
+        store.checkout_branch("feature").unwrap();
+        fs::write(temp_dir.path().join("b.txt"), "b\n").unwrap();
+        store.stage_file("b.txt").unwrap();
+        store.commit("feature", author()).unwrap();
+        store.checkout_branch("main").unwrap();
+
+        let mut manager = DraftManager::new(Store::open(temp_dir.path()).unwrap()).unwrap();
+        let id = manager.create_draft("wip".into(), None).unwrap();
+        manager.apply_draft(&id).unwrap();
+        (temp_dir, store, id)
+    }
+
+    #[test]
+    fn test_reapply_offered_on_return() {
+        let (temp_dir, mut store, id) = repo_with_draft();
+        store.add_branch_hook(DraftBranchGuard::new(DraftPolicy::AutoShelve));
+        store.checkout_branch("feature").unwrap();
+        let manager = DraftManager::new(Store::open(temp_dir.path()).unwrap()).unwrap();
+        assert!(!manager.get_draft(&id).unwrap().is_active);
+
+        // Nothing was shelved for `feature`
+        let mut input = std::io::Cursor::new(b"y\n".to_vec());
+        assert!(!offer_reapply(&store, "feature", &mut input).unwrap());
+
+        store.checkout_branch("main").unwrap();
+        let mut input = std::io::Cursor::new(b"\n".to_vec());
+        assert!(offer_reapply(&store, "main", &mut input).unwrap());
+
+        let manager = DraftManager::new(Store::open(temp_dir.path()).unwrap()).unwrap();
+        assert!(manager.get_draft(&id).unwrap().is_active);
+        assert!(manager.auto_shelved_for("main").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_declined_reapply_is_not_offered_again() {
+        let (temp_dir, mut store, id) = repo_with_draft();
+        store.add_branch_hook(DraftBranchGuard::new(DraftPolicy::AutoShelve));
+        store.checkout_branch("feature").unwrap();
+        store.checkout_branch("main").unwrap();
+
+        let mut input = std::io::Cursor::new(b"n\n".to_vec());
+        assert!(!offer_reapply(&store, "main", &mut input).unwrap());
+        let manager = DraftManager::new(Store::open(temp_dir.path()).unwrap()).unwrap();
+        assert!(!manager.get_draft(&id).unwrap().is_active);
+        let mut input = std::io::Cursor::new(b"y\n".to_vec());
+        assert!(!offer_reapply(&store, "main", &mut input).unwrap());
+    }
 }
@@ -0,0 +1,640 @@
+//! `rune explore --interactive`: a commit picker with a diff preview
+//!
+//! `Explorer` holds the list, filter and navigation state and knows nothing
+//! about the terminal; `run` feeds it key presses from crossterm and draws
+//! it. The picked commit id goes to stdout so the command can be used as
+//! `rune revert $(rune explore -I)`; the interface itself is drawn on stderr.
+
+use anyhow::Result;
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{self, ClearType},
+};
+use rune_core::Commit;
+use rune_store::{Store, DELETED_BLOB};
+use std::io::{IsTerminal, Write};
+
+use crate::style::{self, Style};
+use colored::Colorize;
+
+/// Lines of context around each change in the preview
+const PREVIEW_CONTEXT: usize = 3;
+
+/// What the list shows for one commit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitRow {
+    pub id: String,
+    pub subject: String,
+    pub author: String,
+    pub time: i64,
+    pub files: Vec<String>,
+}
+
+impl From<Commit> for CommitRow {
+    fn from(commit: Commit) -> Self {
+        Self {
+            subject: commit.message.lines().next().unwrap_or_default().to_string(),
+            id: commit.id,
+            author: commit.author.name,
+            time: commit.time,
+            files: commit.files,
+        }
+    }
+}
+
+/// Key presses the explorer reacts to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Enter,
+    Esc,
+    Backspace,
+    Char(char),
+}
+
+/// What the caller has to do after a key press
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Redraw,
+    /// Load the diff of this commit and hand it to `Explorer::show_preview`
+    Preview(String),
+    /// Leave, printing the selected commit id if there is one
+    Quit(Option<String>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterKind {
+    Message,
+    Path,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mode {
+    List,
+    /// Typing a filter; `previous` is restored on Esc
+    Prompt { kind: FilterKind, previous: String },
+    Preview { lines: Vec<String>, scroll: usize },
+}
+
+/// Commit list with message and path filters, a cursor and a preview pane
+#[derive(Debug)]
+pub struct Explorer {
+    rows: Vec<CommitRow>,
+    /// Indexes into `rows` that pass both filters
+    visible: Vec<usize>,
+    message_filter: String,
+    path_filter: String,
+    /// Position of the selection within `visible`
+    cursor: usize,
+    /// First entry of `visible` on screen
+    offset: usize,
+    /// Rows that fit on screen
+    page: usize,
+    mode: Mode,
+}
+
+impl Explorer {
+    /// Explorer over `commits`, newest first, showing `page` rows at a time
+    pub fn new(commits: impl IntoIterator<Item = CommitRow>, page: usize) -> Self {
+        let rows: Vec<CommitRow> = commits.into_iter().collect();
+        let visible = (0..rows.len()).collect();
+        Self {
+            rows,
+            visible,
+            message_filter: String::new(),
+            path_filter: String::new(),
+            cursor: 0,
+            offset: 0,
+            page: page.max(1),
+            mode: Mode::List,
+        }
+    }
+
+    pub fn mode(&self) -> &Mode {
+        &self.mode
+    }
+
+    pub fn filter(&self, kind: FilterKind) -> &str {
+        match kind {
+            FilterKind::Message => &self.message_filter,
+            FilterKind::Path => &self.path_filter,
+        }
+    }
+
+    /// Rows currently on screen, with whether each is the selected one
+    pub fn screen(&self) -> impl Iterator<Item = (&CommitRow, bool)> {
+        self.visible
+            .iter()
+            .enumerate()
+            .skip(self.offset)
+            .take(self.page)
+            .map(|(pos, &row)| (&self.rows[row], pos == self.cursor))
+    }
+
+    pub fn visible_len(&self) -> usize {
+        self.visible.len()
+    }
+
+    pub fn selected(&self) -> Option<&CommitRow> {
+        self.visible.get(self.cursor).map(|&row| &self.rows[row])
+    }
+
+    pub fn page(&self) -> usize {
+        self.page
+    }
+
+    pub fn set_page(&mut self, page: usize) {
+        self.page = page.max(1);
+        self.scroll_to_cursor();
+    }
+
+    /// Show `lines` (the selected commit's diff) in the preview pane
+    pub fn show_preview(&mut self, lines: Vec<String>) {
+        self.mode = Mode::Preview { lines, scroll: 0 };
+    }
+
+    pub fn handle(&mut self, key: Key) -> Action {
+        match &mut self.mode {
+            Mode::List => self.handle_list(key),
+            Mode::Prompt { kind, previous } => {
+                let (kind, previous) = (*kind, previous.clone());
+                self.handle_prompt(kind, previous, key)
+            }
+            Mode::Preview { lines, scroll } => {
+                let last = lines.len().saturating_sub(1);
+                match key {
+                    Key::Up | Key::Char('k') => *scroll = scroll.saturating_sub(1),
+                    Key::Down | Key::Char('j') => *scroll = (*scroll + 1).min(last),
+                    Key::PageUp => *scroll = scroll.saturating_sub(self.page),
+                    Key::PageDown | Key::Char(' ') => *scroll = (*scroll + self.page).min(last),
+                    Key::Home | Key::Char('g') => *scroll = 0,
+                    Key::End | Key::Char('G') => *scroll = last,
+                    Key::Char('s') => return Action::Quit(self.selected().map(|row| row.id.clone())),
+                    Key::Char('q') | Key::Esc | Key::Enter => self.mode = Mode::List,
+                    _ => {}
+                }
+                Action::Redraw
+            }
+        }
+    }
+
+    fn handle_list(&mut self, key: Key) -> Action {
+        match key {
+            Key::Up | Key::Char('k') => self.move_cursor(-1),
+            Key::Down | Key::Char('j') => self.move_cursor(1),
+            Key::PageUp => self.move_cursor(-(self.page as isize)),
+            Key::PageDown | Key::Char(' ') => self.move_cursor(self.page as isize),
+            Key::Home | Key::Char('g') => self.move_cursor(isize::MIN),
+            Key::End | Key::Char('G') => self.move_cursor(isize::MAX),
+            Key::Enter => {
+                if let Some(row) = self.selected() {
+                    return Action::Preview(row.id.clone());
+                }
+            }
+            Key::Char('/') => self.start_prompt(FilterKind::Message),
+            Key::Char('f') => self.start_prompt(FilterKind::Path),
+            Key::Char('s') => return Action::Quit(self.selected().map(|row| row.id.clone())),
+            Key::Char('q') => return Action::Quit(None),
+            Key::Esc => {
+                self.message_filter.clear();
+                self.path_filter.clear();
+                self.refilter();
+            }
+            _ => {}
+        }
+        Action::Redraw
+    }
+
+    fn handle_prompt(&mut self, kind: FilterKind, previous: String, key: Key) -> Action {
+        match key {
+            Key::Char(c) => self.filter_mut(kind).push(c),
+            Key::Backspace => {
+                self.filter_mut(kind).pop();
+            }
+            Key::Enter => self.mode = Mode::List,
+            Key::Esc => {
+                *self.filter_mut(kind) = previous;
+                self.mode = Mode::List;
+            }
+            _ => return Action::Redraw,
+        }
+        // Narrow the list as the filter is typed
+        self.refilter();
+        Action::Redraw
+    }
+
+    fn start_prompt(&mut self, kind: FilterKind) {
+        let previous = self.filter(kind).to_string();
+        self.mode = Mode::Prompt { kind, previous };
+    }
+
+    fn filter_mut(&mut self, kind: FilterKind) -> &mut String {
+        match kind {
+            FilterKind::Message => &mut self.message_filter,
+            FilterKind::Path => &mut self.path_filter,
+        }
+    }
+
+    /// Recompute the visible rows, keeping the selection on the same commit
+    /// when it still passes the filters
+    fn refilter(&mut self) {
+        let selected = self.visible.get(self.cursor).copied();
+        let message = self.message_filter.to_lowercase();
+        let path = self.path_filter.as_str();
+        self.visible = self
+            .rows
+            .iter()
+            .enumerate()
+            .filter(|(_, row)| message.is_empty() || row.subject.to_lowercase().contains(&message))
+            .filter(|(_, row)| path.is_empty() || row.files.iter().any(|f| f.contains(path)))
+            .map(|(i, _)| i)
+            .collect();
+        self.cursor = selected
+            .and_then(|row| self.visible.iter().position(|&v| v == row))
+            .unwrap_or(0);
+        self.offset = 0;
+        self.scroll_to_cursor();
+    }
+
+    fn move_cursor(&mut self, delta: isize) {
+        let last = self.visible.len().saturating_sub(1);
+        self.cursor = self.cursor.saturating_add_signed(delta).min(last);
+        self.scroll_to_cursor();
+    }
+
+    fn scroll_to_cursor(&mut self) {
+        if self.cursor < self.offset {
+            self.offset = self.cursor;
+        } else if self.cursor >= self.offset + self.page {
+            self.offset = self.cursor + 1 - self.page;
+        }
+    }
+}
+
+/// Run the picker, or print the plain log when not attached to a terminal
+pub fn run(store: &Store, count: usize) -> Result<()> {
+    let mut commits: Vec<Commit> = store.log_stream()?.collect();
+    if commits.is_empty() {
+        Style::info("No commits yet. Use 'rune commit' to create your first commit.");
+        return Ok(());
+    }
+    commits.reverse();
+
+    if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        let now = chrono::Utc::now().timestamp();
+        for commit in commits.into_iter().take(count).map(CommitRow::from) {
+            println!(
+                "{} {} {} ({})",
+                Style::commit_hash(short(&commit.id)),
+                commit.subject,
+                commit.author.dimmed(),
+                style::format_duration(now - commit.time).dimmed()
+            );
+        }
+        return Ok(());
+    }
+
+    let (_, height) = screen_size();
+    let mut explorer = Explorer::new(commits.into_iter().map(CommitRow::from), list_height(height));
+
+    let picked = {
+        let _screen = Screen::enter()?;
+        let mut out = std::io::stderr();
+        loop {
+            draw(&mut out, &explorer)?;
+            let action = match event::read()? {
+                Event::Key(key) if key.kind != KeyEventKind::Release => match translate(key) {
+                    Some(key) => explorer.handle(key),
+                    None => continue,
+                },
+                Event::Resize(_, height) => {
+                    explorer.set_page(list_height(height));
+                    continue;
+                }
+                _ => continue,
+            };
+            match action {
+                Action::Redraw => {}
+                Action::Preview(id) => explorer.show_preview(commit_diff(store, &id)?),
+                Action::Quit(picked) => break picked,
+            }
+        }
+    };
+
+    if let Some(id) = picked {
+        println!("{}", id);
+    }
+    Ok(())
+}
+
+/// Terminal size, assuming 80x24 when the terminal doesn't report one
+pub(crate) fn screen_size() -> (u16, u16) {
+    match terminal::size() {
+        Ok((width, height)) if width > 0 && height > 0 => (width, height),
+        _ => (80, 24),
+    }
+}
+
+/// Rows left for the list under the header and above the footer
+fn list_height(terminal_height: u16) -> usize {
+    (terminal_height as usize).saturating_sub(2).max(1)
+}
+
+pub(crate) fn translate(key: KeyEvent) -> Option<Key> {
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+        // Ctrl-C leaves like q does
+        return matches!(key.code, KeyCode::Char('c')).then_some(Key::Char('q'));
+    }
+    Some(match key.code {
+        KeyCode::Up => Key::Up,
+        KeyCode::Down => Key::Down,
+        KeyCode::PageUp => Key::PageUp,
+        KeyCode::PageDown => Key::PageDown,
+        KeyCode::Home => Key::Home,
+        KeyCode::End => Key::End,
+        KeyCode::Enter => Key::Enter,
+        KeyCode::Esc => Key::Esc,
+        KeyCode::Backspace => Key::Backspace,
+        KeyCode::Char(c) => Key::Char(c),
+        _ => return None,
+    })
+}
+
+/// Raw mode on the alternate screen for as long as it lives
+pub(crate) struct Screen;
+
+impl Screen {
+    pub(crate) fn enter() -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(std::io::stderr(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(Self)
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(std::io::stderr(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn draw(out: &mut impl Write, explorer: &Explorer) -> Result<()> {
+    let (width, height) = screen_size();
+    let width = width as usize;
+    queue!(out, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+
+    let footer = match explorer.mode() {
+        Mode::Preview { lines, scroll } => {
+            let id = explorer.selected().map(|row| short(&row.id)).unwrap_or_default();
+            header(out, &format!("commit {}  ({}/{})", id, scroll + 1, lines.len()), width)?;
+            for (y, line) in lines.iter().skip(*scroll).take(explorer.page()).enumerate() {
+                queue!(out, cursor::MoveTo(0, y as u16 + 1), Print(clip(line, width)))?;
+            }
+            "↑/↓ scroll  PgUp/PgDn page  s select  q back".to_string()
+        }
+        mode => {
+            let mut title = format!("{} commits", explorer.visible_len());
+            for (kind, label) in [(FilterKind::Message, "message"), (FilterKind::Path, "path")] {
+                if !explorer.filter(kind).is_empty() {
+                    title.push_str(&format!("  {}: {}", label, explorer.filter(kind)));
+                }
+            }
+            header(out, &title, width)?;
+            let now = chrono::Utc::now().timestamp();
+            for (y, (row, selected)) in explorer.screen().enumerate() {
+                let line = format!(
+                    "{}  {}  {}, {}",
+                    short(&row.id),
+                    row.subject,
+                    row.author,
+                    style::format_duration(now - row.time)
+                );
+                queue!(out, cursor::MoveTo(0, y as u16 + 1))?;
+                if selected {
+                    queue!(out, SetAttribute(Attribute::Reverse))?;
+                }
+                queue!(out, Print(clip(&line, width)), SetAttribute(Attribute::Reset))?;
+            }
+            match mode {
+                Mode::Prompt { kind: FilterKind::Message, .. } => format!("/{}", explorer.filter(FilterKind::Message)),
+                Mode::Prompt { kind: FilterKind::Path, .. } => format!("path: {}", explorer.filter(FilterKind::Path)),
+                _ => "↑/↓ move  enter diff  / message  f path  esc clear  s select  q quit".to_string(),
+            }
+        }
+    };
+    queue!(
+        out,
+        cursor::MoveTo(0, height.saturating_sub(1)),
+        SetAttribute(Attribute::Dim),
+        Print(clip(&footer, width)),
+        SetAttribute(Attribute::Reset)
+    )?;
+    out.flush()?;
+    Ok(())
+}
+
+pub(crate) fn header(out: &mut impl Write, text: &str, width: usize) -> Result<()> {
+    queue!(out, SetAttribute(Attribute::Bold), Print(clip(text, width)), SetAttribute(Attribute::Reset))?;
+    Ok(())
+}
+
+fn short(id: &str) -> &str {
+    &id[..id.len().min(8)]
+}
+
+pub(crate) fn clip(line: &str, width: usize) -> String {
+    line.chars().take(width).collect()
+}
+
+/// Diffstat followed by the full diff of `id` against its parent
+fn commit_diff(store: &Store, id: &str) -> Result<Vec<String>> {
+    let commit = store.resolve_commit(id)?;
+    let read = |blob: Option<&str>| -> Option<String> {
+        match blob {
+            None | Some(DELETED_BLOB) => Some(String::new()),
+            Some(blob) => store
+                .read_blob(blob)
+                .ok()
+                .filter(|content| !rune_core::eol::looks_binary(content))
+                .map(|content| String::from_utf8_lossy(&content).into_owned()),
+        }
+    };
+
+    let mut stat = Vec::new();
+    let mut diff = Vec::new();
+    for file in &commit.files {
+        let Some(new) = commit.tree.get(file) else {
+            stat.push(format!(" {} | no recorded content", file));
+            continue;
+        };
+        let old = commit.parent.as_deref().and_then(|p| store.blob_at(p, file));
+        let (Some(old_text), Some(new_text)) = (read(old.as_deref()), read(Some(new))) else {
+            stat.push(format!(" {} | binary", file));
+            continue;
+        };
+        let hunks = rune_delta::hunks::line_hunks(&old_text, &new_text, PREVIEW_CONTEXT);
+        let (mut added, mut removed) = (0, 0);
+        diff.push(format!("--- a/{}", file));
+        diff.push(format!("+++ b/{}", file));
+        for hunk in &hunks {
+            for line in &hunk.lines {
+                match line {
+                    rune_delta::hunks::HunkLine::Add(_) => added += 1,
+                    rune_delta::hunks::HunkLine::Remove(_) => removed += 1,
+                    rune_delta::hunks::HunkLine::Context(_) => {}
+                }
+            }
+            diff.extend(hunk.to_string().lines().map(str::to_string));
+        }
+        stat.push(format!(" {} | +{} -{}", file, added, removed));
+    }
+
+    let mut lines = vec![
+        format!("commit {}", commit.id),
+        format!("Author: {} <{}>", commit.author.name, commit.author.email),
+        String::new(),
+    ];
+    lines.extend(commit.message.lines().map(|line| format!("    {}", line)));
+    lines.push(String::new());
+    lines.append(&mut stat);
+    lines.push(String::new());
+    lines.append(&mut diff);
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(id: &str, subject: &str, files: &[&str]) -> CommitRow {
+        CommitRow {
+            id: id.to_string(),
+            subject: subject.to_string(),
+            author: "Test".to_string(),
+            time: 0,
+            files: files.iter().map(|f| f.to_string()).collect(),
+        }
+    }
+
+    fn explorer(page: usize) -> Explorer {
+        let rows = (0..25).map(|i| {
+            let file = if i % 5 == 0 { "src/parser.rs" } else { "docs/guide.md" };
+            let subject = if i % 3 == 0 { format!("Fix bug {}", i) } else { format!("Add feature {}", i) };
+            row(&format!("c{:02}", i), &subject, &[file])
+        });
+        Explorer::new(rows, page)
+    }
+
+    fn ids(explorer: &Explorer) -> Vec<String> {
+        explorer.screen().map(|(row, _)| row.id.clone()).collect()
+    }
+
+    fn type_text(explorer: &mut Explorer, text: &str) {
+        for c in text.chars() {
+            explorer.handle(Key::Char(c));
+        }
+    }
+
+    #[test]
+    fn test_page_down_scrolls_list() {
+        let mut explorer = explorer(10);
+        assert_eq!(explorer.selected().unwrap().id, "c00");
+
+        explorer.handle(Key::PageDown);
+        assert_eq!(explorer.selected().unwrap().id, "c10");
+        assert_eq!(ids(&explorer).first().unwrap(), "c01");
+        assert_eq!(ids(&explorer).last().unwrap(), "c10");
+
+        explorer.handle(Key::PageDown);
+        explorer.handle(Key::PageDown);
+        assert_eq!(explorer.selected().unwrap().id, "c24");
+
+        explorer.handle(Key::PageUp);
+        assert_eq!(explorer.selected().unwrap().id, "c14");
+        explorer.handle(Key::Home);
+        assert_eq!(explorer.selected().unwrap().id, "c00");
+        explorer.handle(Key::Up);
+        assert_eq!(explorer.selected().unwrap().id, "c00");
+    }
+
+    #[test]
+    fn test_message_filter_narrows_list() {
+        let mut explorer = explorer(50);
+        explorer.handle(Key::Char('/'));
+        type_text(&mut explorer, "fix");
+        assert_eq!(explorer.visible_len(), 9);
+        assert!(explorer.screen().all(|(row, _)| row.subject.starts_with("Fix")));
+
+        explorer.handle(Key::Enter);
+        assert_eq!(explorer.mode(), &Mode::List);
+        explorer.handle(Key::Char('f'));
+        type_text(&mut explorer, "parser");
+        // Both filters apply: i % 3 == 0 and i % 5 == 0
+        assert_eq!(ids(&explorer), vec!["c00", "c15"]);
+
+        explorer.handle(Key::Esc);
+        assert_eq!(explorer.filter(FilterKind::Path), "");
+        assert_eq!(explorer.visible_len(), 9);
+
+        explorer.handle(Key::Esc);
+        assert_eq!(explorer.visible_len(), 25);
+    }
+
+    #[test]
+    fn test_selection_survives_filtering() {
+        let mut explorer = explorer(50);
+        for _ in 0..6 {
+            explorer.handle(Key::Down);
+        }
+        assert_eq!(explorer.selected().unwrap().id, "c06");
+
+        explorer.handle(Key::Char('/'));
+        type_text(&mut explorer, "bug");
+        assert_eq!(explorer.selected().unwrap().id, "c06");
+        explorer.handle(Key::Backspace);
+        explorer.handle(Key::Backspace);
+        explorer.handle(Key::Backspace);
+        assert_eq!(explorer.visible_len(), 25);
+        assert_eq!(explorer.selected().unwrap().id, "c06");
+
+        type_text(&mut explorer, "feature 7");
+        assert_eq!(explorer.selected().unwrap().id, "c07");
+    }
+
+    #[test]
+    fn test_select_returns_commit_id() {
+        let mut explorer = explorer(10);
+        explorer.handle(Key::Down);
+        explorer.handle(Key::Down);
+        assert_eq!(explorer.handle(Key::Enter), Action::Preview("c02".to_string()));
+
+        explorer.show_preview(vec!["line".to_string(); 30]);
+        explorer.handle(Key::PageDown);
+        assert!(matches!(explorer.mode(), Mode::Preview { scroll: 10, .. }));
+        explorer.handle(Key::Char('q'));
+        assert_eq!(explorer.mode(), &Mode::List);
+
+        assert_eq!(explorer.handle(Key::Char('s')), Action::Quit(Some("c02".to_string())));
+        assert_eq!(explorer.handle(Key::Char('q')), Action::Quit(None));
+    }
+
+    #[test]
+    fn test_empty_filter_result() {
+        let mut explorer = explorer(10);
+        explorer.handle(Key::Char('/'));
+        type_text(&mut explorer, "nothing matches");
+        assert_eq!(explorer.visible_len(), 0);
+        assert!(explorer.selected().is_none());
+        explorer.handle(Key::Enter);
+        assert_eq!(explorer.handle(Key::Enter), Action::Redraw);
+        assert_eq!(explorer.handle(Key::Char('s')), Action::Quit(None));
+    }
+}
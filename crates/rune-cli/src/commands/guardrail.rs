@@ -0,0 +1,165 @@
+//! The built-in commit size guardrail
+//!
+//! An installable `pre-commit` check that runs the workspace performance
+//! limits (`rune workspace limits`) over the staged files. It is switched on
+//! and off through the hook registry in `.rune/hooks/registry.json`, next to
+//! the quality and secret scan bundles, and like any pre-commit hook it is
+//! skipped by `--no-verify`.
+
+use crate::style::Style;
+use anyhow::Result;
+use rune_store::{HookEvent, Store};
+use rune_workspace::{CommitValidation, WorkspaceManager};
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Registry key of the guardrail
+pub const GUARDRAIL_HOOK: &str = "pre-commit-guardrail";
+
+fn registry_path(store: &Store) -> PathBuf {
+    store.rune_dir.join("hooks").join("registry.json")
+}
+
+fn read_registry(store: &Store) -> Result<Map<String, Value>> {
+    let path = registry_path(store);
+    if !path.exists() {
+        return Ok(Map::new());
+    }
+    Ok(serde_json::from_str(&fs::read_to_string(&path)?).unwrap_or_default())
+}
+
+fn write_registry(store: &Store, registry: &Map<String, Value>) -> Result<()> {
+    let path = registry_path(store);
+    fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))?;
+    fs::write(path, serde_json::to_string_pretty(registry)?)?;
+    Ok(())
+}
+
+/// Register the guardrail as an enabled pre-commit hook
+pub fn install(store: &Store) -> Result<()> {
+    let mut registry = read_registry(store)?;
+    registry.insert(
+        GUARDRAIL_HOOK.to_string(),
+        json!({
+            "enabled": true,
+            "type": "pre-commit",
+            "builtin": true,
+            "description": "Commit size guardrail: workspace performance limits on staged files",
+            "created": chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string()
+        }),
+    );
+    write_registry(store, &registry)
+}
+
+/// `rune hooks config <hook> --enable <bool>` for any registered hook
+pub fn set_enabled(store: &Store, hook: &str, enabled: bool) -> Result<()> {
+    let mut registry = read_registry(store)?;
+    let Some(entry) = registry.get_mut(hook).and_then(Value::as_object_mut) else {
+        anyhow::bail!("Hook '{}' is not registered; see .rune/hooks/registry.json", hook);
+    };
+    entry.insert("enabled".into(), Value::Bool(enabled));
+    write_registry(store, &registry)
+}
+
+/// Whether the guardrail is registered and enabled
+pub fn is_enabled(store: &Store) -> bool {
+    read_registry(store)
+        .ok()
+        .and_then(|r| r.get(GUARDRAIL_HOOK)?.get("enabled")?.as_bool())
+        .unwrap_or(false)
+}
+
+/// Run the workspace limits over the staged files. Without a workspace
+/// configuration the default limits apply.
+pub fn validate_staged(store: &Store) -> Result<CommitValidation> {
+    let workspace = match WorkspaceManager::load(&store.rune_dir) {
+        Ok(workspace) => workspace,
+        Err(_) => WorkspaceManager::new(store.root.clone(), &store.rune_dir, "default".into())?,
+    };
+    // Staged removals have nothing left to measure
+    let staged: Vec<PathBuf> = store
+        .read_index()?
+        .entries
+        .keys()
+        .map(PathBuf::from)
+        .filter(|p| store.root.join(p).exists())
+        .collect();
+    workspace.validate_commit_files(&staged)
+}
+
+/// Everything that runs before a commit: the guardrail when enabled, then
+/// the `pre-commit` script. Guardrail warnings are printed; its errors
+/// block the commit.
+pub fn run_pre_commit(store: &Store) -> Result<()> {
+    if is_enabled(store) {
+        let validation = validate_staged(store)?;
+        for warning in &validation.warnings {
+            Style::warning(warning);
+        }
+        if !validation.valid {
+            anyhow::bail!(
+                "{} blocked the commit:\n  {}\nRaise the limits with 'rune workspace limits' or use --no-verify to skip it",
+                GUARDRAIL_HOOK,
+                validation.errors.join("\n  ")
+            );
+        }
+    }
+    store.run_hook(&HookEvent::PreCommit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support::{author, temp_repo};
+    use rune_workspace::PerformanceLimits;
+
+    #[test]
+    fn test_guardrail_blocks_oversized_and_blocked_files() {
+        let (temp, store) = temp_repo();
+        install(&store).unwrap();
+        let mut workspace = WorkspaceManager::new(temp.path().to_path_buf(), &store.rune_dir, "w".into()).unwrap();
+        workspace
+            .update_performance_limits(PerformanceLimits { max_file_size_mb: 1, warn_file_size_mb: 1, ..Default::default() })
+            .unwrap();
+
+        fs::write(temp.path().join("big.bin"), vec![b'x'; 2 * 1024 * 1024 + 1]).unwrap();
+        fs::write(temp.path().join("tool.exe"), "MZ").unwrap();
+        store.stage_file("big.bin").unwrap();
+        store.stage_file("tool.exe").unwrap();
+
+        let err = run_pre_commit(&store).unwrap_err().to_string();
+        assert!(err.contains("pre-commit-guardrail blocked the commit"), "{}", err);
+        assert!(err.contains("File too large: big.bin (2 MB, limit: 1 MB)"), "{}", err);
+        assert!(err.contains("Blocked file type: tool.exe (extension: .exe)"), "{}", err);
+        assert!(store.log().is_empty());
+
+        // Switched off through the registry, the same staged set goes through
+        set_enabled(&store, GUARDRAIL_HOOK, false).unwrap();
+        assert!(!is_enabled(&store));
+        run_pre_commit(&store).unwrap();
+    }
+
+    #[test]
+    fn test_clean_commit_passes_guardrail() {
+        let (temp, store) = temp_repo();
+        install(&store).unwrap();
+        assert!(is_enabled(&store));
+        fs::write(temp.path().join("main.rs"), "fn main() {}\n").unwrap();
+        store.stage_file("main.rs").unwrap();
+
+        let validation = validate_staged(&store).unwrap();
+        assert!(validation.valid && validation.errors.is_empty());
+        assert_eq!(validation.file_count, 1);
+        run_pre_commit(&store).unwrap();
+        store.commit("clean", author()).unwrap();
+        assert_eq!(store.log().len(), 1);
+    }
+
+    #[test]
+    fn test_unknown_hook_cannot_be_configured() {
+        let (_temp, store) = temp_repo();
+        let err = set_enabled(&store, "pre-commit-nope", true).unwrap_err();
+        assert!(err.to_string().contains("not registered"));
+    }
+}
@@ -1,5 +1,5 @@
 use crate::intelligence::{
-    IntelligentFileAnalyzer, IntelligenceConfig,
+    detect_repository_line_ending, IntelligentFileAnalyzer, IntelligenceConfig,
     HealthStatus, CachePriority
 };
 use std::path::Path;
@@ -119,6 +119,10 @@ pub fn analyze_file(file_path: String) -> Result<()> {
     println!("{}", "🧠 Analyzing file...".cyan().bold());
 
     let mut analyzer = IntelligentFileAnalyzer::new();
+    let repo_root = rune_store::Store::discover(std::env::current_dir()?)
+        .map(|store| store.root)
+        .unwrap_or_else(|_| std::path::PathBuf::from("."));
+    analyzer.set_repo_line_ending(detect_repository_line_ending(&repo_root));
     let analysis = analyzer.analyze_file(&file_path)?;
     
     analyzer.display_analysis(&analysis);
@@ -1,6 +1,9 @@
 use anyhow::Result;
 use clap::Subcommand;
-use rune_lfs::{Lfs, Pointer};
+use rune_lfs::{Chunking, Lfs, LfsConfig, MigrationPlanItem, Pointer};
+use rune_remote::RemoteUrl;
+use rune_security::pinning::{self, CertPin};
+use rune_store::Store;
 
 #[derive(Subcommand, Debug)]
 pub enum LfsCmd {
@@ -20,7 +23,13 @@ pub enum LfsCmd {
     },
     /// Convert LFS pointer back to file (smudge)
     Clean {
-        path: std::path::PathBuf,
+        #[arg(required = true)]
+        paths: Vec<std::path::PathBuf>,
+    },
+    /// Turn LFS pointers back into regular files and drop their objects
+    Dewatch {
+        #[arg(required = true)]
+        paths: Vec<std::path::PathBuf>,
     },
     /// Configure LFS settings
     Config {
@@ -30,6 +39,36 @@ pub enum LfsCmd {
         chunk_size: Option<usize>,
         #[arg(long)]
         migration_threshold: Option<String>,
+        /// How new objects are split: `fixed` or `cdc` (content-defined, deduplicated)
+        #[arg(long)]
+        chunking: Option<Chunking>,
+        /// Smallest content-defined chunk, e.g. 256KB
+        #[arg(long)]
+        cdc_min: Option<String>,
+        /// Target content-defined chunk size, e.g. 1MB
+        #[arg(long)]
+        cdc_avg: Option<String>,
+        /// Largest content-defined chunk, e.g. 4MB
+        #[arg(long)]
+        cdc_max: Option<String>,
+        /// Upload and download no faster than this per second, e.g. 5MB; 0 for no limit
+        #[arg(long)]
+        max_rate: Option<String>,
+        /// Times to retry a chunk after a transient failure
+        #[arg(long)]
+        retries: Option<u32>,
+        /// Only talk to a remote presenting this certificate (sha256:<hex>); repeatable
+        #[arg(long = "pin-cert", value_name = "FINGERPRINT")]
+        pin_certs: Vec<String>,
+        /// Remove all certificate pins
+        #[arg(long, conflicts_with = "pin_certs")]
+        clear_pins: bool,
+        /// Endpoint of an S3-compatible service for `s3://` remotes, e.g. https://minio.local:9000
+        #[arg(long)]
+        s3_endpoint: Option<String>,
+        /// Region to sign `s3://` requests for
+        #[arg(long)]
+        s3_region: Option<String>,
         #[arg(long)]
         list: bool,
     },
@@ -51,6 +90,9 @@ pub enum LfsCmd {
         /// Migrate entire directory
         #[arg(long)]
         directory: Option<std::path::PathBuf>,
+        /// Migrate at most this many files per run; later runs resume
+        #[arg(long)]
+        batch: Option<usize>,
     },
     /// Show LFS status and statistics
     Status,
@@ -80,6 +122,12 @@ pub enum LfsCmd {
     },
     /// Verify integrity of LFS objects
     Verify,
+    /// Find pointer files damaged by editors and optionally rewrite them
+    Doctor {
+        /// Rewrite every pointer whose object can be identified
+        #[arg(long)]
+        fix: bool,
+    },
     /// Clean up orphaned chunks and stale locks
     Cleanup {
         #[arg(long, help = "Maximum age for stale locks (in hours)", default_value = "24")]
@@ -102,19 +150,19 @@ pub enum LfsCmd {
 pub async fn run(cmd: LfsCmd) -> Result<()> {
     match cmd {
         LfsCmd::Track { patterns } => {
-            let lfs = Lfs::open(std::env::current_dir()?)?;
+            let lfs = open_lfs()?;
             for pattern in patterns {
                 lfs.add_pattern(&pattern)?;
             }
         }
         LfsCmd::Untrack { patterns } => {
-            let lfs = Lfs::open(std::env::current_dir()?)?;
+            let lfs = open_lfs()?;
             for pattern in patterns {
                 lfs.remove_pattern(&pattern)?;
             }
         }
         LfsCmd::Ls => {
-            let lfs = Lfs::open(std::env::current_dir()?)?;
+            let lfs = open_lfs()?;
             let cfg = lfs.config()?;
             println!("📋 LFS Tracked Patterns:");
             if cfg.patterns.is_empty() {
@@ -126,7 +174,7 @@ pub async fn run(cmd: LfsCmd) -> Result<()> {
             }
         }
         LfsCmd::Smudge { path } => {
-            let lfs = Lfs::open(std::env::current_dir()?)?;
+            let lfs = open_lfs()?;
             let rel = path.to_string_lossy().to_string();
             if lfs.smudge_from_pointer(&rel)? {
                 println!("✅ Smudged {}", rel);
@@ -134,10 +182,29 @@ pub async fn run(cmd: LfsCmd) -> Result<()> {
                 println!("ℹ️  Not a pointer: {}", rel);
             }
         }
-        LfsCmd::Clean { path } => {
-            let lfs = Lfs::open(std::env::current_dir()?)?;
-            let rel = path.to_string_lossy().to_string();
-            if let Some(ptr) = lfs.clean_to_pointer(&rel)? {
+        LfsCmd::Dewatch { paths } => {
+            let lfs = open_lfs()?;
+            for path in paths {
+                let rel = path.to_string_lossy().to_string();
+                let report = lfs.dewatch_file(&rel)?;
+                if report.still_used_by.is_empty() {
+                    println!("✅ Restored {} ({} bytes); object {} removed", rel, report.size, report.oid);
+                } else {
+                    println!(
+                        "✅ Restored {} ({} bytes); object {} kept for {}",
+                        rel,
+                        report.size,
+                        report.oid,
+                        report.still_used_by.join(", ")
+                    );
+                }
+            }
+        }
+        LfsCmd::Clean { paths } => {
+            let lfs = open_lfs()?;
+            let rels: Vec<String> = paths.iter().map(|p| p.to_string_lossy().to_string()).collect();
+            let staged = lfs.stage_paths(&rels)?;
+            for (rel, ptr) in &staged {
                 println!(
                     "✅ Cleaned {}; oid={} size={} chunks={}",
                     rel,
@@ -145,21 +212,64 @@ pub async fn run(cmd: LfsCmd) -> Result<()> {
                     ptr.size,
                     ptr.chunks.len()
                 );
-            } else {
+            }
+            for rel in rels.iter().filter(|rel| !staged.iter().any(|(s, _)| s == *rel)) {
                 println!("ℹ️  Not tracked: {}", rel);
             }
         }
-        LfsCmd::Config { remote, chunk_size, migration_threshold, list } => {
-            let lfs = Lfs::open(std::env::current_dir()?)?;
+        LfsCmd::Config {
+            remote,
+            chunk_size,
+            migration_threshold,
+            chunking,
+            cdc_min,
+            cdc_avg,
+            cdc_max,
+            max_rate,
+            retries,
+            pin_certs,
+            clear_pins,
+            s3_endpoint,
+            s3_region,
+            list,
+        } => {
+            let lfs = open_lfs()?;
             
             if list {
                 let cfg = lfs.config()?;
                 println!("📋 LFS Configuration:");
                 println!("  Remote: {:?}", cfg.remote.unwrap_or_else(|| "Not set".to_string()));
                 println!("  Chunk size: {} bytes", cfg.chunk_size);
+                match cfg.chunking {
+                    Chunking::Fixed => println!("  Chunking: fixed"),
+                    Chunking::Cdc => println!(
+                        "  Chunking: cdc ({}/{}/{} bytes min/avg/max)",
+                        cfg.cdc.min, cfg.cdc.avg, cfg.cdc.max
+                    ),
+                }
                 println!("  Migration threshold: {} bytes", cfg.migration_threshold);
                 println!("  Upload enabled: {}", cfg.upload_enabled);
                 println!("  Download enabled: {}", cfg.download_enabled);
+                match cfg.transfer.max_bytes_per_sec {
+                    0 => println!("  Max rate: unlimited"),
+                    rate => println!("  Max rate: {} bytes/s", rate),
+                }
+                println!("  Retries: {} (backoff from {} ms)", cfg.transfer.retries, cfg.transfer.backoff_ms);
+                if cfg.s3.endpoint.is_some() || cfg.s3.region.is_some() {
+                    println!(
+                        "  S3 endpoint: {} ({})",
+                        cfg.s3.endpoint.as_deref().unwrap_or("AWS"),
+                        cfg.s3.region.as_deref().unwrap_or("us-east-1")
+                    );
+                }
+                if cfg.pinned_certs.is_empty() {
+                    println!("  Pinned certificates: none");
+                } else {
+                    println!("  Pinned certificates:");
+                    for pin in &cfg.pinned_certs {
+                        println!("    {}", pin);
+                    }
+                }
                 return Ok(());
             }
             
@@ -173,42 +283,86 @@ pub async fn run(cmd: LfsCmd) -> Result<()> {
                 let threshold = parse_size(&t)?;
                 lfs.set_migration_threshold(threshold)?;
             }
+            if chunking.is_some() || cdc_min.is_some() || cdc_avg.is_some() || cdc_max.is_some() {
+                let cfg = lfs.config()?;
+                let mut sizes = cfg.cdc;
+                for (value, field) in [(cdc_min, &mut sizes.min), (cdc_avg, &mut sizes.avg), (cdc_max, &mut sizes.max)] {
+                    if let Some(v) = value {
+                        *field = parse_size(&v)? as usize;
+                    }
+                }
+                lfs.set_chunking(chunking.unwrap_or(cfg.chunking), sizes)?;
+            }
+            if max_rate.is_some() || retries.is_some() {
+                let mut transfer = lfs.config()?.transfer;
+                if let Some(rate) = max_rate {
+                    transfer.max_bytes_per_sec = parse_size(&rate)?;
+                }
+                transfer.retries = retries.unwrap_or(transfer.retries);
+                lfs.set_transfer(transfer)?;
+            }
+            if clear_pins {
+                lfs.set_pinned_certs(Vec::new())?;
+                println!("✓ Removed LFS certificate pins");
+            }
+            if !pin_certs.is_empty() {
+                let mut pins = lfs.config()?.pinned_certs;
+                for pin in CertPin::parse_all(&pin_certs)? {
+                    let pin = pin.to_string();
+                    if !pins.contains(&pin) {
+                        println!("✓ Pinned LFS remote certificate {}", pin);
+                        pins.push(pin);
+                    }
+                }
+                lfs.set_pinned_certs(pins)?;
+            }
+            if s3_endpoint.is_some() || s3_region.is_some() {
+                let mut cfg = lfs.config()?;
+                cfg.s3.endpoint = s3_endpoint.or(cfg.s3.endpoint);
+                cfg.s3.region = s3_region.or(cfg.s3.region);
+                lfs.write_config(&cfg)?;
+                println!("✓ Set LFS S3 endpoint; credentials come from RUNE_S3_ACCESS_KEY_ID and RUNE_S3_SECRET_ACCESS_KEY");
+            }
         }
-        LfsCmd::Migrate { min_size, dry_run, directory } => {
-            let lfs = Lfs::open(std::env::current_dir()?)?;
+        LfsCmd::Migrate { min_size, dry_run, directory, batch } => {
+            let lfs = open_lfs()?;
             let threshold = parse_size(&min_size)?;
-            
-            // Update migration threshold
             let mut cfg = lfs.config()?;
             cfg.migration_threshold = threshold;
-            lfs.write_config(&cfg)?;
             
-            if let Some(dir) = directory {
-                println!("🔄 Migrating directory: {}", dir.display());
-                if !dry_run {
-                    let migrated = lfs.migrate_directory(&dir)?;
-                    println!("✅ Migrated {} files to LFS", migrated.len());
-                    for file in migrated {
-                        println!("  📁 {}", file);
-                    }
-                } else {
-                    println!("🔍 Dry run - would migrate files larger than {} bytes", threshold);
-                }
+            // Relative to where the command runs, like the other path arguments
+            let dir = std::env::current_dir()?.join(directory.unwrap_or_default());
+            println!("🔄 Migrating directory: {}", dir.display());
+            if dry_run {
+                println!("🔍 Dry run - nothing is changed");
+                print_migration_plan(&lfs.plan_migration_with(&dir, &cfg)?);
             } else {
-                println!("🔄 Migrating current directory...");
-                if !dry_run {
-                    let migrated = lfs.migrate_directory(&std::env::current_dir()?)?;
-                    println!("✅ Migrated {} files to LFS", migrated.len());
-                    for file in migrated {
-                        println!("  📁 {}", file);
-                    }
-                } else {
-                    println!("🔍 Dry run - would migrate files larger than {} bytes", threshold);
+                // Update migration threshold
+                lfs.write_config(&cfg)?;
+                let report = lfs.migrate_directory(&dir, batch)?;
+                println!(
+                    "✅ Migrated {}, skipped {} already migrated, {} failed",
+                    report.migrated.len(),
+                    report.skipped.len(),
+                    report.failed.len()
+                );
+                for file in &report.migrated {
+                    println!("  📁 {}", file);
+                }
+                let auto = lfs.migration_manifest()?.auto;
+                for file in report.skipped.iter().filter(|file| auto.contains(*file)) {
+                    println!("  ⏭  {} (auto-migrated at commit)", file);
+                }
+                for (file, err) in &report.failed {
+                    println!("  ❌ {}: {}", file, err);
+                }
+                if report.remaining > 0 {
+                    println!("⏸  {} files left; run the migration again to continue", report.remaining);
                 }
             }
         }
         LfsCmd::Status => {
-            let lfs = Lfs::open(std::env::current_dir()?)?;
+            let lfs = open_lfs()?;
             let stats = lfs.get_stats()?;
             let cfg = lfs.config()?;
             
@@ -219,10 +373,18 @@ pub async fn run(cmd: LfsCmd) -> Result<()> {
             println!("  Remote files: {}", stats.remote_files);
             println!("  Local only: {}", stats.local_only_files);
             println!("  Remote server: {:?}", cfg.remote.unwrap_or_else(|| "Not configured".to_string()));
+            let auto = lfs.auto_migrated()?;
+            if !auto.is_empty() {
+                println!("  Auto-migrated at commit (over core.auto_lfs_threshold):");
+                for path in &auto {
+                    println!("    📦 {} (auto)", path);
+                }
+            }
         }
         LfsCmd::Sync => {
-            let lfs = Lfs::open(std::env::current_dir()?)?;
-            lfs.sync_with_server()?;
+            let lfs = open_lfs()?;
+            // Object store remotes use a blocking client
+            tokio::task::spawn_blocking(move || lfs.sync_with_server()).await??;
         }
         LfsCmd::Push { path } => {
             push(path).await?;
@@ -241,7 +403,7 @@ pub async fn run(cmd: LfsCmd) -> Result<()> {
             list_locks().await?;
         }
         LfsCmd::PartialFetch { oid, start, length, output } => {
-            let lfs = Lfs::open(std::env::current_dir()?)?;
+            let lfs = open_lfs()?;
             let data = lfs.partial_fetch(&oid, start, length)?;
             
             if let Some(output_path) = output {
@@ -258,7 +420,7 @@ pub async fn run(cmd: LfsCmd) -> Result<()> {
             }
         }
         LfsCmd::Verify => {
-            let lfs = Lfs::open(std::env::current_dir()?)?;
+            let lfs = open_lfs()?;
             let corrupted = lfs.verify_integrity()?;
             
             if corrupted.is_empty() {
@@ -270,11 +432,33 @@ pub async fn run(cmd: LfsCmd) -> Result<()> {
                 }
             }
         }
+        LfsCmd::Doctor { fix } => {
+            let lfs = open_lfs()?;
+            let report = lfs.doctor(fix)?;
+            if report.damaged.is_empty() {
+                println!("✅ {} pointer files checked, none damaged", report.checked);
+            } else {
+                println!("⚠️  {} of {} pointer files damaged:", report.damaged.len(), report.checked);
+                for diagnosis in &report.damaged {
+                    let outcome = match (&diagnosis.recovered, diagnosis.repaired) {
+                        (_, true) => "rewritten",
+                        (Some(_), false) => "repairable with --fix",
+                        (None, false) => "oid not recoverable; restore it from history",
+                    };
+                    println!("  🔴 {}: {} ({})", diagnosis.path, diagnosis.problem, outcome);
+                }
+            }
+        }
         LfsCmd::Cleanup { max_age_hours: _ } => {
-            let lfs = Lfs::open(std::env::current_dir()?)?;
+            let lfs = open_lfs()?;
             
             println!("🧹 Cleaning up LFS storage...");
-            let _orphaned = lfs.cleanup_orphaned_chunks()?;
+            let orphaned = lfs.cleanup_orphaned_chunks()?;
+            println!("  Removed {} orphaned object directories", orphaned);
+            let pruned = lfs.prune_chunks()?;
+            if pruned > 0 {
+                println!("  Removed {} unreferenced shared chunks", pruned);
+            }
             
             // Clean up stale locks using the existing locking system
             let mut lock_manager = rune_lfs::locking::LockManager::new();
@@ -283,7 +467,7 @@ pub async fn run(cmd: LfsCmd) -> Result<()> {
             println!("✅ Cleanup completed");
         }
         LfsCmd::Info { oid } => {
-            let lfs = Lfs::open(std::env::current_dir()?)?;
+            let lfs = open_lfs()?;
             let info = lfs.get_object_info(&oid)?;
             
             println!("📊 LFS Object Info:");
@@ -297,7 +481,7 @@ pub async fn run(cmd: LfsCmd) -> Result<()> {
             println!("  Complete locally: {}", if info.is_complete { "Yes" } else { "No" });
         }
         LfsCmd::Stream { oid, cmd } => {
-            let lfs = Lfs::open(std::env::current_dir()?)?;
+            let lfs = open_lfs()?;
             
             if let Some(command) = cmd {
                 println!("🔄 Streaming {} to command: {}", oid, command);
@@ -343,7 +527,7 @@ pub async fn run(cmd: LfsCmd) -> Result<()> {
 }
 
 // Helper function to parse size strings like "10MB", "1GB", etc.
-fn parse_size(size_str: &str) -> Result<u64> {
+pub fn parse_size(size_str: &str) -> Result<u64> {
     let size_str = size_str.to_uppercase();
     let (number_part, unit_part) = if size_str.ends_with("GB") {
         (size_str.trim_end_matches("GB"), 1024 * 1024 * 1024)
@@ -361,40 +545,72 @@ fn parse_size(size_str: &str) -> Result<u64> {
     Ok(number * unit_part)
 }
 
+/// One line per file of a migration dry run, then the totals
+pub fn print_migration_plan(plan: &[MigrationPlanItem]) {
+    let (skipped, migrating): (Vec<_>, Vec<_>) =
+        plan.iter().partition(|item| item.reason.is_skipped());
+    for item in &migrating {
+        println!("  📁 {} ({} bytes, {} chunks): {}", item.path, item.size, item.chunks, item.reason);
+    }
+    for item in &skipped {
+        println!("  ⏭  {}: {}", item.path, item.reason);
+    }
+    println!(
+        "Would migrate {} files ({} bytes), skip {} already migrated",
+        migrating.len(),
+        migrating.iter().map(|item| item.size).sum::<u64>(),
+        skipped.len()
+    );
+}
+
+/// LFS store of the repository containing the current directory
+pub fn open_lfs() -> Result<Lfs> {
+    let store = Store::discover(std::env::current_dir()?)?;
+    let internal_dirs = store.config().core.internal_dirs;
+    Ok(Lfs::open(store.root, store.rune_dir)?.with_internal_dirs(&internal_dirs))
+}
+
+/// Client for the LFS remote, bound to the certificates pinned in `cfg`
+pub fn remote_client(cfg: &LfsConfig) -> Result<reqwest::Client> {
+    pinning::http_client(&CertPin::parse_all(&cfg.pinned_certs)?)
+}
+
+/// Whether the configured remote is an object store that `Lfs` talks to
+/// directly rather than a Shrine
+fn object_store_remote(cfg: &LfsConfig) -> bool {
+    cfg.remote.as_deref().is_some_and(rune_lfs::backend::is_object_store_url)
+}
+
 async fn push(path: std::path::PathBuf) -> Result<()> {
-    let lfs = Lfs::open(std::env::current_dir()?)?;
+    let lfs = open_lfs()?;
     let cfg = lfs.config()?;
-    let remote = cfg
-        .remote
-        .clone()
-        .ok_or_else(|| anyhow::anyhow!("set remote with `rune lfs config --remote <URL>`"))?;
+    if object_store_remote(&cfg) {
+        let Some(pointer) = rune_lfs::read_pointer_file(&path)? else {
+            anyhow::bail!("{} is not a pointer. Run `rune lfs clean {}` first.", path.display(), path.display());
+        };
+        return tokio::task::spawn_blocking(move || lfs.upload_to_server(&pointer.oid)).await?;
+    }
+    let remote = RemoteUrl::parse(
+        cfg.remote
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("set remote with `rune lfs config --remote <URL>`"))?,
+    )?;
     let rel = path.to_string_lossy().to_string();
-    let s = std::fs::read_to_string(&rel).unwrap_or_default();
-    if !s.starts_with("version https://rune-lfs/v1") {
+    let Some(pointer) = rune_lfs::read_pointer_file(&path)? else {
         anyhow::bail!(
             "{} is not a pointer. Run `rune lfs clean {}` first.",
             rel,
             rel
         );
-    }
-    let oid = s
-        .lines()
-        .find(|l| l.starts_with("oid "))
-        .unwrap()
-        .trim_start_matches("oid ")
-        .to_string();
-    let dir = lfs
-        .root
-        .join(".rune/lfs/objects")
-        .join(&oid[0..2])
-        .join(&oid[2..4])
-        .join(&oid);
+    };
+    let oid = pointer.oid;
+    let dir = lfs.chunk_dir(&oid);
     let pj = std::fs::read(dir.join("pointer.json"))?;
     let ptr: Pointer = serde_json::from_slice(&pj)?;
     // Ask server which chunks it already has (resumable uploads)
-    let client = reqwest::Client::new();
+    let client = remote_client(&cfg)?;
     let missing: Vec<String> = client
-        .post(format!("{}/lfs/has", remote))
+        .post(remote.endpoint("lfs/has"))
         .json(&serde_json::json!({"oid": &oid, "chunks": ptr.chunks}))
         .send()
         .await?
@@ -403,15 +619,16 @@ async fn push(path: std::path::PathBuf) -> Result<()> {
         .await?;
     // Always ensure pointer.json exists remotely
     client
-        .post(format!("{}/lfs/upload", remote))
+        .post(remote.endpoint("lfs/upload"))
         .json(&serde_json::json!({"oid": &oid, "chunk": "pointer.json", "data": pj}))
         .send()
         .await?
         .error_for_status()?;
     for cid in &missing {
-        let data = std::fs::read(dir.join(&cid))?;
+        let idx = ptr.chunks.iter().position(|c| c == cid).unwrap_or_default();
+        let data = lfs.read_chunk(&ptr, idx)?;
         client
-            .post(format!("{}/lfs/upload", remote))
+            .post(remote.endpoint("lfs/upload"))
             .json(&serde_json::json!({"oid": &oid, "chunk": cid, "data": data}))
             .send()
             .await?
@@ -421,15 +638,35 @@ async fn push(path: std::path::PathBuf) -> Result<()> {
     Ok(())
 }
 async fn pull(oid: String, out: std::path::PathBuf) -> Result<()> {
-    let lfs = Lfs::open(std::env::current_dir()?)?;
+    let lfs = open_lfs()?;
     let cfg = lfs.config()?;
-    let remote = cfg
-        .remote
-        .clone()
-        .ok_or_else(|| anyhow::anyhow!("set remote with `rune lfs config --remote <URL>`"))?;
-    let client = reqwest::Client::new();
+    if object_store_remote(&cfg) {
+        let id = oid.clone();
+        let outbuf = tokio::task::spawn_blocking(move || -> Result<Vec<u8>> {
+            lfs.download_from_server(&id)?;
+            let mut outbuf = Vec::new();
+            lfs.stream_process(&id, |chunk| {
+                outbuf.extend_from_slice(chunk);
+                Ok(())
+            })?;
+            Ok(outbuf)
+        })
+        .await??;
+        if let Some(pp) = out.parent() {
+            std::fs::create_dir_all(pp)?;
+        }
+        std::fs::write(&out, &outbuf)?;
+        println!("pulled {} -> {}", oid, out.display());
+        return Ok(());
+    }
+    let remote = RemoteUrl::parse(
+        cfg.remote
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("set remote with `rune lfs config --remote <URL>`"))?,
+    )?;
+    let client = remote_client(&cfg)?;
     let pj: Vec<u8> = client
-        .post(format!("{}/lfs/download", remote))
+        .post(remote.endpoint("lfs/download"))
         .json(&serde_json::json!({"oid": &oid, "chunk": "pointer.json"}))
         .send()
         .await?
@@ -440,7 +677,7 @@ async fn pull(oid: String, out: std::path::PathBuf) -> Result<()> {
     let mut outbuf = Vec::with_capacity(ptr.size as usize);
     for cid in ptr.chunks {
         let part: Vec<u8> = client
-            .post(format!("{}/lfs/download", remote))
+            .post(remote.endpoint("lfs/download"))
             .json(&serde_json::json!({"oid": &oid, "chunk": cid}))
             .send()
             .await?
@@ -458,7 +695,7 @@ async fn pull(oid: String, out: std::path::PathBuf) -> Result<()> {
 }
 async fn list_locks() -> Result<()> {
     let url = std::env::var("RUNE_SHRINE").unwrap_or_else(|_| "http://127.0.0.1:7420".into());
-    let v: serde_json::Value = reqwest::get(format!("{}/locks/list", url))
+    let v: serde_json::Value = reqwest::get(RemoteUrl::parse(&url)?.endpoint("locks/list"))
         .await?
         .json()
         .await?;
@@ -469,7 +706,7 @@ async fn lock_cmd(path: String, owner: String, unlock: bool) -> Result<()> {
     let url = std::env::var("RUNE_SHRINE").unwrap_or_else(|_| "http://127.0.0.1:7420".into());
     let c = reqwest::Client::new();
     let route = if unlock { "unlock" } else { "lock" };
-    c.post(format!("{}/locks/{}", url, route))
+    c.post(RemoteUrl::parse(&url)?.endpoint(&format!("locks/{}", route)))
         .json(&serde_json::json!({"path": path, "owner": owner}))
         .send()
         .await?
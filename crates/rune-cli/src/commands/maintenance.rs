@@ -0,0 +1,316 @@
+//! `rune maintenance` and the check that runs after mutating commands
+//!
+//! The schedule, lock and record live in `rune_store`; this module knows what
+//! each task does. After a command, due quick tasks run before it returns and
+//! the slow ones are handed to a detached `rune maintenance run`, whose output
+//! goes to `.rune/maintenance.log`.
+
+use crate::style::Style;
+use anyhow::Result;
+use clap::Subcommand;
+use rune_draft::DraftManager;
+use rune_lfs::Lfs;
+use rune_store::{rotate_file, MaintenanceTask, Store};
+use std::fs;
+use std::process::{Command, Stdio};
+
+/// Reflog entries older than this are dropped
+const REFLOG_KEEP_DAYS: u32 = 90;
+
+/// `signals.log` is rotated past this size
+const SIGNALS_LOG_LIMIT: u64 = 1024 * 1024;
+
+#[derive(Subcommand, Debug)]
+pub enum MaintenanceCmd {
+    /// Show what is due, when each task last ran and any failures
+    Status,
+    /// Run maintenance now: the due tasks, or the ones named
+    Run {
+        /// Task to run: gc, reflog, lfs, drafts or signals; repeatable
+        #[arg(long = "task", value_name = "TASK")]
+        tasks: Vec<MaintenanceTask>,
+        /// Run every enabled task whether it is due or not
+        #[arg(long, conflicts_with = "tasks")]
+        all: bool,
+        /// Return at once and run in a background process that logs to .rune/maintenance.log
+        #[arg(long)]
+        detach: bool,
+    },
+    /// Pack every object into one pack, replacing loose objects and older packs
+    Repack {
+        /// Drop corrupt pack entries, packing a healthy copy where one exists
+        #[arg(long)]
+        repair: bool,
+    },
+}
+
+pub fn run(cmd: MaintenanceCmd) -> Result<()> {
+    let store = Store::discover(std::env::current_dir()?)?;
+    match cmd {
+        MaintenanceCmd::Status => show_status(&store),
+        MaintenanceCmd::Run { tasks, all, detach } => {
+            let now = chrono::Utc::now().timestamp();
+            let tasks = if all {
+                let cfg = store.config().maintenance;
+                MaintenanceTask::ALL.into_iter().filter(|t| cfg.task(*t).is_enabled()).collect()
+            } else if tasks.is_empty() {
+                store.due_maintenance(now)?
+            } else {
+                tasks
+            };
+            if tasks.is_empty() {
+                Style::info("Nothing is due");
+                return Ok(());
+            }
+            if detach {
+                spawn_detached(&store, &tasks)?;
+                Style::info(&format!(
+                    "Running {} in the background; see {}",
+                    names(&tasks),
+                    store.maintenance_log_path().display()
+                ));
+                return Ok(());
+            }
+            let Some(outcome) = store.run_maintenance(&tasks, |task| run_task(&store, task))? else {
+                Style::warning("Maintenance is already running");
+                return Ok(());
+            };
+            for (task, summary) in &outcome.succeeded {
+                Style::success(&format!("{}: {}", task, summary));
+            }
+            for (task, error) in &outcome.failed {
+                Style::error(&format!("{}: {}", task, error));
+            }
+            if !outcome.failed.is_empty() {
+                anyhow::bail!("{} maintenance task(s) failed", outcome.failed.len());
+            }
+            Ok(())
+        }
+        MaintenanceCmd::Repack { repair } => {
+            let report = store.repack(repair)?;
+            match &report.pack {
+                Some(pack) => Style::success(&format!(
+                    "Packed {} objects into {} ({} loose removed)",
+                    report.objects,
+                    pack.display(),
+                    report.loose_removed
+                )),
+                None => Style::info("Nothing to pack"),
+            }
+            for id in &report.repaired {
+                Style::info(&format!("Repaired {} from a healthy copy", id));
+            }
+            for id in &report.unrecoverable {
+                Style::error(&format!("{} has no healthy copy", id));
+            }
+            for pack in &report.quarantined {
+                Style::warning(&format!("Quarantined {}", pack.display()));
+            }
+            Ok(())
+        }
+    }
+}
+
+/// `rune verify`: check that stored objects still hash to their ids.
+/// Loose objects are skipped with `packs_only`.
+pub fn verify(packs_only: bool) -> Result<()> {
+    let store = Store::discover(std::env::current_dir()?)?;
+    let mut bad = 0;
+    for check in store.verify_packs()? {
+        let name = check.pack.file_name().unwrap_or_default().to_string_lossy().to_string();
+        if check.corrupt.is_empty() {
+            Style::success(&format!("{}: {} entries ok", name, check.entries));
+        }
+        for entry in &check.corrupt {
+            Style::error(&format!("{}: {} at offset {}: {}", name, entry.path, entry.offset, entry.reason));
+        }
+        bad += check.corrupt.len();
+    }
+    if !packs_only {
+        let damaged = store.verify_loose_objects()?;
+        for id in &damaged {
+            Style::error(&format!("loose object {} does not match its content", id));
+        }
+        bad += damaged.len();
+    }
+    if bad > 0 {
+        anyhow::bail!("{} corrupt objects; `rune maintenance repack --repair` rebuilds packs from healthy copies", bad);
+    }
+    Ok(())
+}
+
+/// Do the work of `task`, returning a one-line summary
+pub fn run_task(store: &Store, task: MaintenanceTask) -> Result<String> {
+    match task {
+        MaintenanceTask::Gc => {
+            // Gc rewrites history and objects in place, so rather than wait on
+            // a command that is writing them it leaves this run out
+            let Some(lock) = store.try_lock_repo()? else {
+                return Ok("skipped, another command holds the repository lock".into());
+            };
+            let report = store.dedup_objects(&lock)?;
+            let repack = store.repack(false)?;
+            let packed = store.pack_refs()?;
+            drop(lock);
+            if !report.corrupt.is_empty() {
+                anyhow::bail!(
+                    "{} objects do not match their content: {}; `rune verify` lists them",
+                    report.corrupt.len(),
+                    report.corrupt.join(", ")
+                );
+            }
+            Ok(format!(
+                "{} objects checked, {} duplicates removed, {} bytes saved, {} loose objects packed, {} refs packed",
+                report.objects_scanned, report.duplicates_removed, report.bytes_saved, repack.loose_removed, packed
+            ))
+        }
+        MaintenanceTask::Reflog => {
+            let pruned = store.prune_all_reflogs(REFLOG_KEEP_DAYS)?;
+            Ok(format!("{} entries older than {} days pruned", pruned, REFLOG_KEEP_DAYS))
+        }
+        MaintenanceTask::Lfs => {
+            // Opening the LFS store creates it; a repository without one has nothing to clean
+            if !store.rune_dir.join("lfs").exists() {
+                return Ok("no LFS store".into());
+            }
+            let lfs = Lfs::open(&store.root, &store.rune_dir)?;
+            let orphaned = lfs.cleanup_orphaned_chunks()?;
+            let pruned = lfs.prune_chunks()?;
+            Ok(format!("{} orphaned objects and {} unreferenced chunks removed", orphaned, pruned))
+        }
+        MaintenanceTask::Drafts => {
+            let removed = DraftManager::new(Store::open(&store.root)?)?.cleanup_old_drafts()?;
+            Ok(format!("{} expired drafts removed", removed))
+        }
+        MaintenanceTask::Signals => {
+            let log = store.rune_dir.join(rune_planning::SIGNAL_DIR).join("signals.log");
+            Ok(if rotate_file(&log, SIGNALS_LOG_LIMIT)? { "signals.log rotated" } else { "signals.log under limit" }.into())
+        }
+    }
+}
+
+/// `rune config get maintenance.<key>`: `auto`, `<task>.enabled` or
+/// `<task>.interval_hours`
+pub fn get_setting(store: &Store, key: &str) -> Result<String> {
+    let cfg = store.config().maintenance;
+    Ok(match parse_key(key)? {
+        (None, "auto") => cfg.auto.to_string(),
+        (Some(task), "enabled") => cfg.task(task).is_enabled().to_string(),
+        (Some(task), "interval_hours") => (cfg.interval_secs(task) / 3600).to_string(),
+        _ => unreachable!("parse_key only returns known fields"),
+    })
+}
+
+/// `rune config set maintenance.<key> <value>`
+pub fn set_setting(store: &Store, key: &str, value: &str) -> Result<()> {
+    let mut cfg = store.config();
+    let flag = || value.parse::<bool>().map_err(|_| anyhow::anyhow!("{} must be true or false, got '{}'", key, value));
+    match parse_key(key)? {
+        (None, "auto") => cfg.maintenance.auto = flag()?,
+        (Some(task), "enabled") => cfg.maintenance.task_mut(task).enabled = Some(flag()?),
+        (Some(task), "interval_hours") => {
+            let hours = value
+                .parse::<u64>()
+                .ok()
+                .filter(|h| *h > 0)
+                .ok_or_else(|| anyhow::anyhow!("{} must be a positive number of hours, got '{}'", key, value))?;
+            cfg.maintenance.task_mut(task).interval_hours = Some(hours);
+        }
+        _ => unreachable!("parse_key only returns known fields"),
+    }
+    store.write_config(&cfg)
+}
+
+fn parse_key(key: &str) -> Result<(Option<MaintenanceTask>, &str)> {
+    let rest = key.strip_prefix("maintenance.").unwrap_or(key);
+    match rest.split_once('.') {
+        None if rest == "auto" => Ok((None, rest)),
+        Some((task, field)) if matches!(field, "enabled" | "interval_hours") => Ok((Some(task.parse()?), field)),
+        _ => anyhow::bail!(
+            "unknown setting '{}'; use maintenance.auto, maintenance.<task>.enabled or maintenance.<task>.interval_hours",
+            key
+        ),
+    }
+}
+
+/// The check after a successful mutating command. Due quick tasks run now,
+/// the rest in a detached process. Problems are logged, never reported as
+/// a failure of the command that triggered the check.
+pub fn after_command() {
+    let Ok(store) = std::env::current_dir().map_err(anyhow::Error::from).and_then(Store::discover) else {
+        return;
+    };
+    let Ok(due) = store.maintenance_after_command(chrono::Utc::now().timestamp()) else {
+        return;
+    };
+    let (quick, slow): (Vec<_>, Vec<_>) = due.into_iter().partition(|task| task.is_quick());
+    // Quick tasks first, so the lock is free again when the background run starts
+    if !quick.is_empty() {
+        let _ = store.run_maintenance(&quick, |task| run_task(&store, task));
+    }
+    if !slow.is_empty() {
+        if let Err(e) = spawn_detached(&store, &slow) {
+            let _ = append_log(&store, &format!("could not start background maintenance: {:#}", e));
+        }
+    }
+}
+
+/// Start `rune maintenance run` for `tasks` in the background with its
+/// output appended to the maintenance log
+fn spawn_detached(store: &Store, tasks: &[MaintenanceTask]) -> Result<()> {
+    let log = fs::OpenOptions::new().create(true).append(true).open(store.maintenance_log_path())?;
+    let mut cmd = Command::new(std::env::current_exe()?);
+    cmd.args(["maintenance", "run"]);
+    for task in tasks {
+        cmd.args(["--task", task.as_str()]);
+    }
+    cmd.current_dir(&store.root)
+        .env("NO_COLOR", "1")
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        .spawn()?;
+    Ok(())
+}
+
+fn append_log(store: &Store, line: &str) -> Result<()> {
+    use std::io::Write;
+    let mut log = fs::OpenOptions::new().create(true).append(true).open(store.maintenance_log_path())?;
+    writeln!(log, "{} {}", chrono::Utc::now().to_rfc3339(), line)?;
+    Ok(())
+}
+
+fn names(tasks: &[MaintenanceTask]) -> String {
+    tasks.iter().map(|t| t.as_str()).collect::<Vec<_>>().join(", ")
+}
+
+fn show_status(store: &Store) -> Result<()> {
+    let now = chrono::Utc::now().timestamp();
+    let auto = store.config().maintenance.auto;
+    Style::section_header(&format!("Maintenance (after commands: {})", if auto { "on" } else { "off" }));
+    let when = |t: i64| {
+        chrono::DateTime::from_timestamp(t, 0)
+            .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default()
+    };
+    for status in store.maintenance_status(now)? {
+        let schedule = if !status.enabled {
+            "disabled".to_string()
+        } else if status.due {
+            "due now".to_string()
+        } else {
+            format!("due {}", status.next_due.map(when).unwrap_or_default())
+        };
+        let last = match (status.record.last_run, &status.record.last_result) {
+            (None, _) => "never ran".to_string(),
+            (Some(t), Some(result)) if status.record.last_error.is_none() => format!("{}: {}", when(t), result),
+            (Some(t), _) => when(t),
+        };
+        println!("  {:<8} every {:>4}h  {:<22} last {}", status.task, status.interval_hours, schedule, last);
+        if let Some(error) = &status.record.last_error {
+            Style::error(&format!("    last run failed: {}", error));
+        }
+    }
+    println!("  Log: {}", store.maintenance_log_path().display());
+    Ok(())
+}
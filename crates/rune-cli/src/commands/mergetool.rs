@@ -0,0 +1,209 @@
+use anyhow::Result;
+use rune_store::{ConflictSide, RepoOperation, Store};
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+use crate::style::Style;
+
+/// Resolve conflicts of the merge or revert in progress, one file at a time, with the
+/// tool from `[mergetool] cmd` or an ours/theirs prompt when none is set
+pub fn run(paths: Vec<String>) -> Result<()> {
+    let store = Store::discover(std::env::current_dir()?)?;
+    let selected = |conflict: &String| {
+        paths.iter().any(|p| {
+            let p = p.trim_end_matches('/');
+            conflict == p || conflict.starts_with(&format!("{}/", p))
+        })
+    };
+    let conflicts: Vec<String> = store
+        .unresolved_conflicts()?
+        .into_iter()
+        .filter(|c| paths.is_empty() || selected(c))
+        .collect();
+
+    if conflicts.is_empty() {
+        Style::info("No files need merging");
+        return Ok(());
+    }
+
+    let tool = store.config().mergetool.cmd;
+    for path in &conflicts {
+        let resolved = match &tool {
+            Some(template) => {
+                Style::info(&format!("Launching merge tool for {}", Style::file_path(path)));
+                run_tool(&store, path, template)?
+            }
+            None => prompt_resolution(&store, path, &mut std::io::stdin().lock())?,
+        };
+        if resolved {
+            Style::success(&format!("{} resolved", path));
+        } else {
+            Style::warning(&format!("{} left unresolved", path));
+        }
+    }
+
+    let remaining = store.unresolved_conflicts()?;
+    if remaining.is_empty() {
+        match store.operation_in_progress()? {
+            Some(RepoOperation::Revert { .. }) => {
+                Style::info("All conflicts resolved; complete the revert with: rune revert --continue")
+            }
+            _ => Style::info("All conflicts resolved; complete the merge with: rune merge --continue"),
+        }
+    }
+    Ok(())
+}
+
+/// Launch the configured tool on one file. The file counts as resolved when
+/// the tool exits successfully and left no conflict markers behind.
+pub fn run_tool(store: &Store, path: &str, template: &str) -> Result<bool> {
+    // Tools expect all three inputs to exist, even for add/add or delete conflicts
+    let empty = store.conflict_version_path(path, ConflictSide::Base)?.with_file_name("empty");
+    let input = |side: ConflictSide| -> Result<String> {
+        let version = store.conflict_version_path(path, side)?;
+        if version.exists() {
+            return Ok(shell_quote(&version));
+        }
+        std::fs::write(&empty, b"")?;
+        Ok(shell_quote(&empty))
+    };
+
+    let merged = store.root.join(path);
+    let command = template
+        .replace("$BASE", &input(ConflictSide::Base)?)
+        .replace("$LOCAL", &input(ConflictSide::Ours)?)
+        .replace("$REMOTE", &input(ConflictSide::Theirs)?)
+        .replace("$MERGED", &shell_quote(&merged));
+
+    let status = if cfg!(windows) {
+        std::process::Command::new("cmd").args(["/C", &command]).status()?
+    } else {
+        std::process::Command::new("sh").args(["-c", &command]).status()?
+    };
+    if !status.success() {
+        return Ok(false);
+    }
+
+    let content = std::fs::read(&merged).unwrap_or_default();
+    let text = String::from_utf8_lossy(&content);
+    if text.lines().any(|l| l.starts_with("<<<<<<<") || l.starts_with(">>>>>>>")) {
+        return Ok(false);
+    }
+    store.mark_resolved(path)?;
+    Ok(true)
+}
+
+/// Built-in fallback: take ours or theirs, show the versions, or skip
+fn prompt_resolution(store: &Store, path: &str, input: &mut impl BufRead) -> Result<bool> {
+    loop {
+        print!(
+            "{}: use (o)urs, (t)heirs, (s)how versions, or s(k)ip? ",
+            Style::file_path(path)
+        );
+        std::io::stdout().flush()?;
+
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            return Ok(false);
+        }
+        match answer.trim() {
+            "o" | "ours" => {
+                store.resolve_conflict(path, ConflictSide::Ours)?;
+                return Ok(true);
+            }
+            "t" | "theirs" => {
+                store.resolve_conflict(path, ConflictSide::Theirs)?;
+                return Ok(true);
+            }
+            "s" | "show" => {
+                let versions = store.conflict_versions(path)?;
+                for (label, content) in [
+                    ("base", &versions.base),
+                    ("ours", &versions.ours),
+                    ("theirs", &versions.theirs),
+                ] {
+                    println!("--- {} ---", label);
+                    match content {
+                        Some(content) => println!("{}", String::from_utf8_lossy(content)),
+                        None => println!("(file does not exist)"),
+                    }
+                }
+            }
+            "k" | "skip" => return Ok(false),
+            _ => {}
+        }
+    }
+}
+
+fn shell_quote(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if cfg!(windows) {
+        format!("\"{}\"", path)
+    } else {
+        format!("'{}'", path.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support::{author, temp_repo};
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// `main` and `feature` both change `song.txt` from the same base
+    fn conflicted_repo() -> (TempDir, Store) {
+        let (temp_dir, store) = temp_repo();
+        let commit = |content: &str, msg: &str| {
+            fs::write(temp_dir.path().join("song.txt"), content).unwrap();
+            store.stage_file("song.txt").unwrap();
+            store.commit(msg, author()).unwrap();
+        };
+
+        commit("verse\n", "base");
+        store.create_branch("feature").unwrap();
+        commit("verse\nours\n", "ours");
+        store.checkout_branch("feature").unwrap();
+        commit("verse\ntheirs\n", "theirs");
+        store.checkout_branch("main").unwrap();
+        fs::write(temp_dir.path().join("song.txt"), "verse\nours\n").unwrap();
+
+        store.merge_branch("feature", false, None).unwrap();
+        (temp_dir, store)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_tool_that_writes_merged_output_resolves_file() {
+        let (temp_dir, store) = conflicted_repo();
+        let script = temp_dir.path().join("fake-tool.sh");
+        fs::write(&script, "#!/bin/sh\ncat \"$2\" \"$3\" > \"$4\"\n").unwrap();
+
+        let template = format!("sh {} $BASE $LOCAL $REMOTE $MERGED", script.display());
+        assert!(run_tool(&store, "song.txt", &template).unwrap());
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("song.txt")).unwrap(),
+            "verse\nours\nverse\ntheirs\n"
+        );
+        assert!(store.unresolved_conflicts().unwrap().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_failing_tool_leaves_file_unresolved() {
+        let (_temp_dir, store) = conflicted_repo();
+        assert!(!run_tool(&store, "song.txt", "false").unwrap());
+        assert_eq!(store.unresolved_conflicts().unwrap(), vec!["song.txt"]);
+    }
+
+    #[test]
+    fn test_prompt_takes_theirs() {
+        let (temp_dir, store) = conflicted_repo();
+        let mut input = std::io::Cursor::new(b"show\nt\n".to_vec());
+        assert!(prompt_resolution(&store, "song.txt", &mut input).unwrap());
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("song.txt")).unwrap(),
+            "verse\ntheirs\n"
+        );
+    }
+}
@@ -1,10 +1,30 @@
+pub mod add_guard;
 pub mod advanced;
+pub mod alias;
+pub mod auto_lfs;
 pub mod clone;
 pub mod delta;
 pub mod draft;
+pub mod explore;
+pub mod guardrail;
 pub mod intelligence;
 pub mod lfs;
+pub mod maintenance;
+pub mod mergetool;
+pub mod notes;
 pub mod plan;
+pub mod pull;
+pub mod push;
+pub mod release;
+pub mod resolve;
 pub mod remote;
 pub mod shrine;
+pub mod split;
+pub mod stats;
+pub mod tag_sign;
+pub mod template;
+#[cfg(test)]
+mod test_support;
+pub mod transfer;
+pub mod work;
 pub mod workspace;
@@ -0,0 +1,76 @@
+//! `rune notes between`: release notes for the commits between two tags
+//!
+//! The notes are the same `ReleaseNotes` `rune autoflow release` writes to
+//! CHANGELOG.md, printed as markdown or JSON.
+
+use crate::exit_codes::{CliError, ErrorKind};
+use crate::style::Style;
+use anyhow::Result;
+use clap::Subcommand;
+use rune_store::{ReleaseNotes, ReleaseNotesOptions, Store};
+use serde::Serialize;
+
+#[derive(Subcommand, Debug)]
+pub enum NotesCmd {
+    /// Notes for the commits reachable from TO but not from FROM, e.g.
+    /// `rune notes between v1.3.0 v1.4.0`
+    Between {
+        /// Tag or revision the notes start after; with --since-last-tag,
+        /// the revision they end at
+        from: Option<String>,
+        /// Tag or revision the notes end at
+        to: Option<String>,
+        /// Start after the nearest earlier tag instead of FROM
+        #[arg(long)]
+        since_last_tag: bool,
+        /// Output format
+        #[arg(long, default_value = "md", value_parser = ["md", "json"])]
+        format: String,
+        /// Keep "Merge branch ..." commits
+        #[arg(long)]
+        include_merges: bool,
+    },
+}
+
+#[derive(Serialize)]
+struct NotesJson<'a> {
+    from: Option<&'a str>,
+    to: &'a str,
+    #[serde(flatten)]
+    notes: &'a ReleaseNotes,
+}
+
+fn usage(message: &str) -> anyhow::Error {
+    CliError::new(ErrorKind::Usage, message).into()
+}
+
+pub fn run(cmd: NotesCmd) -> Result<()> {
+    let store = Store::discover(std::env::current_dir()?)?;
+    let NotesCmd::Between { from, to, since_last_tag, format, include_merges } = cmd;
+    let (from, to) = match (since_last_tag, from, to) {
+        (true, _, Some(_)) => return Err(usage("--since-last-tag takes only the revision the notes end at")),
+        (true, to, None) => {
+            let to = to.unwrap_or_else(|| "HEAD".to_string());
+            (store.previous_tag(&to)?, to)
+        }
+        (false, Some(from), to) => (Some(from), to.unwrap_or_else(|| "HEAD".to_string())),
+        (false, None, _) => return Err(usage("Give the tag the notes start after, or use --since-last-tag")),
+    };
+    let notes = store.release_notes(from.as_deref(), &to, &ReleaseNotesOptions { include_merges })?;
+
+    if format == "json" {
+        let json = NotesJson { from: from.as_deref(), to: &to, notes: &notes };
+        println!("{}", serde_json::to_string_pretty(&json)?);
+        return Ok(());
+    }
+    let range = match &from {
+        Some(from) => format!("{}...{}", from, to),
+        None => to.clone(),
+    };
+    if notes.is_empty() {
+        Style::info(&format!("No changes in {}", range));
+    } else {
+        print!("## {}\n{}", range, notes.to_markdown());
+    }
+    Ok(())
+}
@@ -1,8 +1,9 @@
 use anyhow::Result;
 use clap::{Args, Subcommand};
 use crate::style::Style;
-use rune_planning::{PlanStore, PlanStatus, create_plan, update_status, add_task, add_task_with_meta, update_roots, parse_plan_query, filter_plans, StreamStore, generate_workspace_insights, generate_plan_insights, PLAN_DIR};
-use std::env;
+use rune_planning::{PlanStore, PlanStatus, create_plan, update_status, add_task, add_task_with_meta, update_roots, parse_plan_query, filter_plans, StreamStore, InsightCache, generate_plan_insights, export_plans, export_streams, ExportFormat, webhook};
+use rune_core::layout::RepoLayout;
+use std::{env, fs, path::{Path, PathBuf}};
 
 #[derive(Debug, Args)]
 pub struct PlanArgs {
@@ -55,24 +56,52 @@ pub enum PlanCmd {
     StreamAttach { stream_id: String, plan_id: String },
     /// Generate insights (all plans or one plan if id provided)
     Insights { #[arg(long)] id: Option<String> },
+    /// Export plans (one CSV row per task), optionally filtered by a slice query
+    Export {
+        #[arg(long, value_enum, default_value_t)]
+        format: ExportFormat,
+        /// Slice query, e.g. "status=active tag=backend"
+        #[arg(long)]
+        query: Option<String>,
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Export streams
+    StreamExport {
+        #[arg(long, value_enum, default_value_t)]
+        format: ExportFormat,
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Show, set or clear the URL plan events are POSTed to
+    Webhook {
+        url: Option<String>,
+        #[arg(long, conflicts_with = "url")]
+        clear: bool,
+    },
+    /// Send queued webhook events now and report how it went
+    Sync,
 }
 
 // Execute plan related commands using rune-planning crate
-pub fn execute_plan_command(args: PlanArgs) -> Result<()> {
-    // Root dir is current working directory
-    let root = env::current_dir()?;
-    let store = PlanStore::new(&root);
-    let stream_store = StreamStore::new(&root);
+pub async fn execute_plan_command(args: PlanArgs) -> Result<()> {
+    // Plans live in the repository's metadata, or in ./.rune outside a repository
+    let cwd = env::current_dir()?;
+    let layout = RepoLayout::discover(&cwd).or_else(|_| RepoLayout::at(&cwd))?;
+    let store = PlanStore::new(&layout.rune_dir);
+    let stream_store = StreamStore::new(&layout.rune_dir);
+    let syncing = matches!(args.command, PlanCmd::Sync);
     match args.command {
         PlanCmd::Init => {
             store.ensure()?;
-            Style::success("Initialized .rune/plans");
+            Style::success(&format!("Initialized {}", store.dir().display()));
         }
         PlanCmd::Create { title, tags } => {
             store.ensure()?;
             let plan = create_plan(&store, &title, tags.as_deref());
             match plan {
-                Ok(p) => Style::success(&format!("Created plan {} -> {}/{}.md", p.id, PLAN_DIR, p.id)),
+                Ok(p) => Style::success(&format!("Created plan {} -> {}", p.id, store.path_for(&p.id).display())),
                 Err(e) => Style::error(&format!("Failed creating plan: {e}")),
             }
         }
@@ -85,8 +114,28 @@ pub fn execute_plan_command(args: PlanArgs) -> Result<()> {
             for p in plans { println!("{:<10} {:<12} {}", p.id, p.status.as_str(), p.title); }
         }
         PlanCmd::Show { id, insights } => {
-            let plan = store.load(&id)?;
+            let mut plan = store.load(&id)?;
+            // Recorded commits are listed below with their subjects instead
+            let recorded = std::mem::take(&mut plan.commits);
             println!("{}", plan.to_markdown());
+            let log = rune_store::Store::discover(&cwd).map(|repo| repo.log()).unwrap_or_default();
+            let mut linked: Vec<(String, String)> = recorded
+                .iter()
+                .map(|id| {
+                    let subject = log.iter().find(|c| &c.id == id).map(|c| c.message.lines().next().unwrap_or("").to_string());
+                    (id.clone(), subject.unwrap_or_else(|| "(not in this repository)".into()))
+                })
+                .collect();
+            for c in log.iter().rev() {
+                if !recorded.contains(&c.id) && rune_core::trailers::plan_reference(&c.message).as_deref() == Some(plan.id.as_str()) {
+                    linked.push((c.id.clone(), c.message.lines().next().unwrap_or("").to_string()));
+                }
+            }
+            if !linked.is_empty() {
+                println!("## Commits\n");
+                for (id, subject) in linked { println!("- {} {}", &id[..8.min(id.len())], subject); }
+                println!();
+            }
             if insights {
                 let ins = generate_plan_insights(&plan);
                 if !ins.messages.is_empty() {
@@ -170,13 +219,124 @@ pub fn execute_plan_command(args: PlanArgs) -> Result<()> {
                 for m in ins.messages { println!("- {m}"); }
             } else {
                 let plans = store.load_all()?;
-                let ws = generate_workspace_insights(&plans);
+                let mut cache = InsightCache::load(&layout.rune_dir);
+                let ws = cache.workspace_insights(&plans);
+                cache.save(&layout.rune_dir)?;
                 println!("Workspace summary:");
                 for s in ws.summary { println!("- {s}"); }
                 println!("\nPer-plan:");
                 for pi in ws.plan_insights { if !pi.messages.is_empty() { println!("{}:", pi.plan_id); for m in pi.messages { println!("  - {m}"); } } }
             }
         }
+        PlanCmd::Export { format, query, output } => {
+            let mut plans = store.load_all()?;
+            if let Some(q) = query { plans = filter_plans(&plans, &parse_plan_query(&q)); }
+            write_export(&export_plans(&plans, format)?, output.as_deref(), &format!("{} plans", plans.len()))?;
+        }
+        PlanCmd::StreamExport { format, output } => {
+            let streams = stream_store.list()?;
+            write_export(&export_streams(&streams, format)?, output.as_deref(), &format!("{} streams", streams.len()))?;
+        }
+        PlanCmd::Webhook { url, clear } => {
+            if clear {
+                webhook::set_webhook(&layout.rune_dir, None)?;
+                Style::success("Webhook cleared; queued events stay in the outbox");
+            } else if let Some(url) = url {
+                webhook::set_webhook(&layout.rune_dir, Some(&url))?;
+                Style::success(&format!("Plan events will be POSTed to {url}"));
+            } else {
+                let cfg = rune_planning::PlanningConfig::load(&layout.rune_dir)?;
+                println!("{}", cfg.webhook_url.as_deref().unwrap_or("(no webhook)"));
+                for (_, entry) in webhook::outbox(&layout.rune_dir)? {
+                    let error = entry.last_error.as_deref().unwrap_or("not sent yet");
+                    println!("  queued {} {} ({} attempts: {error})", entry.event.event, entry.event.plan_id, entry.attempts);
+                }
+            }
+        }
+        PlanCmd::Sync => {
+            let report = webhook::deliver_outbox(&layout.rune_dir).await?;
+            match report.error {
+                Some(error) => Style::warning(&format!("Webhook delivery failed ({error}); {} event(s) still queued", report.pending)),
+                None if report.pending > 0 => Style::info(&format!("Another delivery is running; {} event(s) queued", report.pending)),
+                None => Style::success(&format!("Delivered {} event(s)", report.delivered)),
+            }
+        }
+    }
+    // What this command queued, plus anything earlier runs couldn't send,
+    // goes out in the background so a slow webhook never holds it up
+    if !syncing && !webhook::outbox(&layout.rune_dir)?.is_empty() {
+        if let Err(e) = spawn_delivery(&layout) {
+            Style::warning(&format!("Could not start webhook delivery ({e:#}); run `rune plan sync`"));
+        }
+    }
+    Ok(())
+}
+
+/// Run `rune plan sync` detached, with its output discarded; failures are
+/// recorded on the queued events and shown by `rune plan webhook`
+fn spawn_delivery(layout: &RepoLayout) -> Result<()> {
+    std::process::Command::new(env::current_exe()?)
+        .args(["plan", "sync"])
+        .current_dir(&layout.root)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()?;
+    Ok(())
+}
+
+fn write_export(content: &str, output: Option<&Path>, what: &str) -> Result<()> {
+    match output {
+        Some(path) => {
+            fs::write(path, content)?;
+            Style::success(&format!("Exported {what} to {}", path.display()));
+        }
+        None => print!("{content}"),
     }
     Ok(())
 }
+
+/// Record `commit` against the plan its message references (see
+/// `rune_core::trailers::plan_reference`). Returns the plan id when a new
+/// link was recorded; references to plans that don't exist are ignored.
+pub fn link_commit(repo: &rune_store::Store, commit: &rune_core::Commit) -> Result<Option<String>> {
+    let Some(plan_id) = rune_core::trailers::plan_reference(&commit.message) else { return Ok(None) };
+    let store = PlanStore::new(&repo.rune_dir);
+    if !store.path_for(&plan_id).exists() {
+        return Ok(None);
+    }
+    Ok(rune_planning::link_commit(&store, &plan_id, &commit.id)?.then_some(plan_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support::{author, temp_repo};
+    use rune_core::trailers::{self, Trailer};
+
+    #[test]
+    fn test_commit_with_plan_trailer_is_recorded() {
+        let (temp, repo) = temp_repo();
+        let plans = PlanStore::new(&repo.rune_dir);
+        create_plan(&plans, "Linked work", None).unwrap();
+
+        fs::write(temp.path().join("a.txt"), "a").unwrap();
+        repo.stage_file("a.txt").unwrap();
+        let message = trailers::append("Start linked work", &[Trailer::new("Plan", "PLAN-001")]);
+        let commit = repo.commit(&message, author()).unwrap();
+        assert_eq!(link_commit(&repo, &commit).unwrap().as_deref(), Some("PLAN-001"));
+        assert_eq!(link_commit(&repo, &commit).unwrap(), None);
+        assert_eq!(plans.load("PLAN-001").unwrap().commits, [commit.id.as_str()]);
+
+        // Unknown plans and commits without a reference leave plans alone
+        fs::write(temp.path().join("b.txt"), "b").unwrap();
+        repo.stage_file("b.txt").unwrap();
+        let other = repo.commit("Plan: PLAN-042", author()).unwrap();
+        assert_eq!(link_commit(&repo, &other).unwrap(), None);
+        fs::write(temp.path().join("c.txt"), "c").unwrap();
+        repo.stage_file("c.txt").unwrap();
+        let plain = repo.commit("Unrelated", author()).unwrap();
+        assert_eq!(link_commit(&repo, &plain).unwrap(), None);
+        assert_eq!(plans.load("PLAN-001").unwrap().commits, [commit.id]);
+    }
+}
@@ -0,0 +1,547 @@
+//! Bringing a remote branch into the current one
+//!
+//! A pull is a row of steps: set local changes aside in a draft (with
+//! autostash), fetch, integrate as `pull.mode` says, and put the changes
+//! back. Every step that changes anything is journaled as a `pull`
+//! operation. When a step fails, or the pull is refused part way, the
+//! journaled steps are undone newest first, so the repository is left as
+//! the pull found it. Conflicts stop the pull without undoing anything:
+//! they are left for the user to resolve.
+//!
+//! Alongside how it ended, a pull reports what the fetch did to the
+//! remote-tracking ref and how much history it brought in, in the same
+//! shape as a push report.
+
+use crate::commands::push::tracking_ref;
+use crate::exit_codes::{CliError, ErrorKind};
+use anyhow::{Context, Result};
+use rune_draft::{DraftManager, DraftSource};
+use rune_remote::{PullReport, RefStatus, SyncClient};
+use rune_store::{
+    BranchOperation, CancelToken, MergeInput, MergeOutcome, MergeResult, OperationKind, PendingOperation, PullMode,
+    ReplayResult, Store, StoreError, DELETED_BLOB,
+};
+use std::collections::HashSet;
+use std::fs;
+
+/// What to pull and how
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PullOptions {
+    pub remote: String,
+    pub branch: String,
+    pub mode: PullMode,
+    /// Set uncommitted changes aside in a draft and re-apply them afterwards
+    pub autostash: bool,
+    /// Stops the pull while it writes files, rolling everything back
+    pub cancel: CancelToken,
+}
+
+impl PullOptions {
+    /// Pull `branch` from `remote` as the repository's `[pull]` config says
+    pub fn from_config(store: &Store, remote: &str, branch: &str) -> Self {
+        let cfg = store.config().pull;
+        Self {
+            remote: remote.to_string(),
+            branch: branch.to_string(),
+            mode: cfg.mode,
+            autostash: cfg.autostash,
+            cancel: CancelToken::default(),
+        }
+    }
+
+    fn upstream(&self) -> String {
+        format!("{}/{}", self.remote, self.branch)
+    }
+}
+
+/// The steps of a pull, in the order they run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PullStep {
+    Autostash,
+    Fetch,
+    Integrate,
+    Unstash,
+}
+
+impl PullStep {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PullStep::Autostash => "autostash",
+            PullStep::Fetch => "fetch",
+            PullStep::Integrate => "integrate",
+            PullStep::Unstash => "unstash",
+        }
+    }
+}
+
+/// How a pull ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PullOutcome {
+    /// The current branch already has everything the remote branch has
+    UpToDate,
+    /// The current branch moved forward; `from` is `None` when it was unborn
+    FastForwarded { from: Option<String>, to: String },
+    /// A merge commit joins the two histories
+    Merged { commit: String },
+    /// This many local commits were replayed on top of `onto`
+    Rebased { onto: String, replayed: usize },
+    /// `step` left conflicts in `paths`. After a merge conflict the
+    /// autostashed changes are still in the `stash` draft; after an unstash
+    /// conflict the draft is kept too, in case the resolution goes wrong.
+    Conflicted { step: PullStep, paths: Vec<String>, stash: Option<String> },
+    /// Nothing was pulled, for `reason`; the repository is as it was
+    Refused { reason: String },
+}
+
+/// Pull `options.branch` from `options.remote` through `client` into the
+/// current branch. A failing step rolls back the ones before it; the error
+/// says whether that worked.
+pub async fn pull_branch(store: &Store, options: &PullOptions, client: &impl SyncClient) -> Result<PullOutcome> {
+    Ok(pull_branch_with_report(store, options, client).await?.0)
+}
+
+/// `pull_branch`, also returning what the fetch updated and transferred.
+/// The report is empty when the pull stopped before fetching or was
+/// refused and rolled back.
+pub async fn pull_branch_with_report(
+    store: &Store,
+    options: &PullOptions,
+    client: &impl SyncClient,
+) -> Result<(PullOutcome, PullReport)> {
+    if store.operation_in_progress()?.is_some() {
+        return Err(StoreError::MergeInProgress { action: "pull" }.into());
+    }
+    if store.current_branch().is_none() {
+        return Err(StoreError::NotOnBranch.into());
+    }
+    store.run_branch_hooks(BranchOperation::Pull, &options.branch)?;
+
+    let mut pull = Pull { store, options, journaled: 0, stash: None, report: PullReport::new(&options.remote) };
+    match pull.run(client).await {
+        Ok(PullOutcome::Refused { reason }) => {
+            pull.roll_back().context("the pull was refused but could not be rolled back")?;
+            Ok((PullOutcome::Refused { reason }, PullReport::new(&options.remote)))
+        }
+        Ok(outcome) => Ok((outcome, pull.report)),
+        Err(err) => match pull.roll_back() {
+            Ok(()) => Err(err.context(format!("Pull from {} failed; nothing was changed", options.upstream()))),
+            Err(rollback) => Err(err.context(format!(
+                "Pull from {} failed and could not be rolled back ({:#}); see `rune undo`",
+                options.upstream(),
+                rollback
+            ))),
+        },
+    }
+}
+
+/// A pull in progress: how many steps it journaled, the draft holding
+/// autostashed changes and what the fetch did
+struct Pull<'a> {
+    store: &'a Store,
+    options: &'a PullOptions,
+    journaled: usize,
+    stash: Option<String>,
+    report: PullReport,
+}
+
+impl Pull<'_> {
+    async fn run(&mut self, client: &impl SyncClient) -> Result<PullOutcome> {
+        let status = self.store.status()?;
+        if !status.working.is_empty() || !status.staging.is_empty() || !status.renamed.is_empty() {
+            if !self.options.autostash {
+                return Ok(PullOutcome::Refused {
+                    reason: "you have uncommitted changes; commit them first, or pull with --autostash".to_string(),
+                });
+            }
+            let journal = self.begin(PullStep::Autostash)?;
+            let stashed = self.autostash();
+            self.record(journal)?;
+            stashed?;
+        }
+
+        let journal = self.begin(PullStep::Fetch)?;
+        let fetched = self.fetch(client).await;
+        self.record(journal)?;
+        let Some(tip) = fetched? else {
+            let message = format!("'{}' has no branch '{}'", self.options.remote, self.options.branch);
+            return Err(CliError::new(ErrorKind::Usage, message).into());
+        };
+
+        // A merge stopped on conflicts is left to `rune merge --abort`
+        // rather than the journal, as `rune merge` does
+        let journal = self.begin(PullStep::Integrate)?;
+        let integrated = self.integrate(&tip);
+        if !matches!(integrated, Ok(PullOutcome::Conflicted { .. })) {
+            self.record(journal)?;
+        }
+        let outcome = integrated?;
+        if matches!(outcome, PullOutcome::Conflicted { .. } | PullOutcome::Refused { .. }) {
+            return Ok(outcome);
+        }
+
+        let Some(stash) = self.stash.clone() else {
+            return Ok(outcome);
+        };
+        let journal = self.begin(PullStep::Unstash)?;
+        let unstashed = self.unstash(&stash);
+        self.record(journal)?;
+        let conflicts = unstashed?;
+        if !conflicts.is_empty() {
+            return Ok(PullOutcome::Conflicted { step: PullStep::Unstash, paths: conflicts, stash: Some(stash) });
+        }
+        Ok(outcome)
+    }
+
+    fn begin(&self, step: PullStep) -> Result<PendingOperation> {
+        let description = format!("{}: {}", self.options.upstream(), step.as_str());
+        self.store.begin_operation(OperationKind::Pull, description, true)
+    }
+
+    /// Journal a step, whether or not it succeeded, so a failed step's
+    /// partial changes are rolled back too
+    fn record(&mut self, journal: PendingOperation) -> Result<()> {
+        if journal.finish(self.store)?.is_some() {
+            self.journaled += 1;
+        }
+        Ok(())
+    }
+
+    fn drafts(&self) -> Result<DraftManager> {
+        DraftManager::new(Store::open(&self.store.root)?)
+    }
+
+    fn autostash(&mut self) -> Result<()> {
+        let mut drafts = self.drafts()?;
+        let name = format!("autostash before pulling {}", self.options.upstream());
+        let capture = drafts.create_draft_from(None, DraftSource::Worktree, name, None)?;
+        self.stash = Some(capture.id.clone());
+        drafts.remove_captured(&capture.id)
+    }
+
+    async fn fetch(&mut self, client: &impl SyncClient) -> Result<Option<String>> {
+        let tracking = tracking_ref(&self.options.remote, &self.options.branch);
+        let old_id = self.store.read_ref(&tracking);
+        let known: HashSet<String> = self.store.log().into_iter().map(|c| c.id).collect();
+        let tip = client
+            .fetch(self.store, &self.options.branch)
+            .await
+            .with_context(|| format!("Failed to fetch {}", self.options.upstream()))?;
+        let Some(tip) = tip else {
+            return Ok(None);
+        };
+        let ancestry = self.store.ancestry(&tip);
+        for commit in ancestry.iter().filter(|c| !known.contains(&c.id)) {
+            self.report.stats.add(self.content_size(commit));
+        }
+        let status = match &old_id {
+            None => RefStatus::New,
+            Some(old) if *old == tip => RefStatus::UpToDate,
+            Some(old) if ancestry.iter().any(|c| c.id == *old) => RefStatus::FastForward,
+            Some(_) => RefStatus::Forced,
+        };
+        self.store.write_ref(&tracking, &tip)?;
+        self.report.updates.push(rune_remote::RefUpdate {
+            local_ref: tracking,
+            remote_ref: format!("refs/heads/{}", self.options.branch),
+            old_id,
+            new_id: tip.clone(),
+            status,
+        });
+        Ok(Some(tip))
+    }
+
+    /// Bytes of new content `commit` brought in, for the transfer stats
+    fn content_size(&self, commit: &rune_core::Commit) -> u64 {
+        commit
+            .files
+            .iter()
+            .filter_map(|path| commit.tree.get(path).filter(|b| b.as_str() != DELETED_BLOB))
+            .filter_map(|blob| self.store.read_blob(blob).ok())
+            .map(|content| content.len() as u64)
+            .sum()
+    }
+
+    fn integrate(&self, tip: &str) -> Result<PullOutcome> {
+        let tip = self.store.resolve_commit(tip)?.id;
+        let upstream = self.options.upstream();
+        let head = self.store.head_commit();
+        let base = head.as_deref().and_then(|h| self.store.merge_base(h, &tip));
+        if head.as_deref() == Some(tip.as_str()) || base.as_deref() == Some(tip.as_str()) {
+            return Ok(PullOutcome::UpToDate);
+        }
+        if head.is_none() || base == head {
+            self.store.fast_forward_with(
+                &tip,
+                &format!("pull: fast-forward to {}", upstream),
+                &self.options.cancel,
+                &mut |_, _| {},
+            )?;
+            return Ok(PullOutcome::FastForwarded { from: head, to: tip });
+        }
+
+        match self.options.mode {
+            PullMode::FfOnly => Ok(PullOutcome::Refused {
+                reason: format!(
+                    "{} and {} have diverged and pull.mode is ff-only; \
+                     set pull.mode to merge or rebase, or pass --mode",
+                    self.store.current_branch().unwrap_or_default(),
+                    upstream
+                ),
+            }),
+            PullMode::Merge => match self.store.merge_commit(&upstream, &tip, false, None)? {
+                MergeResult::Conflicts(paths) => {
+                    Ok(PullOutcome::Conflicted { step: PullStep::Integrate, paths, stash: self.stash.clone() })
+                }
+                MergeResult::Success | MergeResult::FastForward => {
+                    Ok(PullOutcome::Merged { commit: self.store.head_commit().unwrap_or_default() })
+                }
+            },
+            PullMode::Rebase => match self.store.replay_onto_with(
+                &tip,
+                &format!("pull: rebase onto {}", upstream),
+                &self.options.cancel,
+                &mut |_, _| {},
+            )? {
+                ReplayResult::Replayed(replayed) => Ok(PullOutcome::Rebased { onto: tip, replayed }),
+                ReplayResult::Conflicts(paths) => Ok(PullOutcome::Refused {
+                    reason: format!(
+                        "rebasing onto {} would conflict in {}; pull with --mode merge to resolve them",
+                        upstream,
+                        paths.join(", ")
+                    ),
+                }),
+            },
+        }
+    }
+
+    /// Re-apply the stash on top of what was pulled, merging files the
+    /// pull changed too. Returns the files left with conflicts; without
+    /// any, the stash draft is deleted.
+    fn unstash(&self, stash: &str) -> Result<Vec<String>> {
+        let mut drafts = self.drafts()?;
+        let draft = drafts.get_draft(stash)?;
+        let tree = self.store.head_commit().map(|h| self.store.tree_snapshot(&h)).unwrap_or_default();
+        let read = |blob: Option<&String>| -> Result<Vec<u8>> {
+            Ok(blob.map(|b| self.store.read_blob(b)).transpose()?.unwrap_or_default())
+        };
+
+        let mut conflicts = Vec::new();
+        for file in draft.files.values() {
+            let rel = file.path.strip_prefix(&self.store.root).unwrap_or(&file.path).to_string_lossy().replace('\\', "/");
+            let pulled = tree.get(&rel).filter(|b| *b != DELETED_BLOB);
+            if pulled == file.original_hash.as_ref() {
+                if file.is_deleted {
+                    if file.path.exists() {
+                        fs::remove_file(&file.path)?;
+                    }
+                } else {
+                    if let Some(parent) = file.path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::write(&file.path, &file.content)?;
+                }
+                continue;
+            }
+            if file.is_deleted {
+                // Deleted here, changed upstream: keep upstream's file
+                conflicts.push(rel);
+                continue;
+            }
+            let (base, ours) = (read(file.original_hash.as_ref())?, read(pulled)?);
+            let input = MergeInput {
+                path: &rel,
+                base: &base,
+                ours: &ours,
+                theirs: &file.content,
+                ours_label: "HEAD",
+                theirs_label: "autostash",
+                conflict_style: self.store.config().merge.conflict_style,
+            };
+            match self.store.merge_file(&input)? {
+                MergeOutcome::Merged(content) => fs::write(&file.path, content)?,
+                MergeOutcome::Conflict { content, .. } => {
+                    fs::write(&file.path, content)?;
+                    conflicts.push(rel);
+                }
+            }
+        }
+        if conflicts.is_empty() {
+            drafts.delete_draft(stash)?;
+        }
+        conflicts.sort();
+        Ok(conflicts)
+    }
+
+    /// Undo every journaled step, newest first, and drop the stash draft;
+    /// undoing the autostash already put its changes back
+    fn roll_back(&mut self) -> Result<()> {
+        while self.journaled > 0 {
+            self.store.undo_operation()?;
+            self.journaled -= 1;
+        }
+        if let Some(stash) = self.stash.take() {
+            self.drafts()?.delete_draft(&stash)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support::{author, temp_repo};
+    use anyhow::bail;
+    use rune_remote::{PushRequest, SyncResponse};
+    use std::collections::BTreeMap;
+    use tempfile::TempDir;
+
+    fn commit_file(store: &Store, path: &str, content: &str) -> String {
+        fs::write(store.root.join(path), content).unwrap();
+        store.stage_file(path).unwrap();
+        store.commit(&format!("Write {}", path), author()).unwrap().id
+    }
+
+    /// A repository on disk standing in for the remote
+    struct LocalRemote {
+        store: Store,
+    }
+
+    impl SyncClient for LocalRemote {
+        async fn push(&self, _request: &PushRequest) -> Result<SyncResponse> {
+            bail!("the fixture remote is read-only")
+        }
+
+        async fn fetch(&self, store: &Store, branch: &str) -> Result<Option<String>> {
+            let mut stream = Vec::new();
+            self.store.export_stream(&mut stream)?;
+            let tips = store.import_history(&mut stream.as_slice())?;
+            Ok(tips.get(&format!("refs/heads/{}", branch)).cloned())
+        }
+    }
+
+    /// Claims a tip it never sends
+    struct LyingRemote;
+
+    impl SyncClient for LyingRemote {
+        async fn push(&self, _request: &PushRequest) -> Result<SyncResponse> {
+            bail!("the fixture remote is read-only")
+        }
+
+        async fn fetch(&self, _store: &Store, _branch: &str) -> Result<Option<String>> {
+            Ok(Some("0123456789abcdef".to_string()))
+        }
+    }
+
+    fn options(mode: PullMode, autostash: bool) -> PullOptions {
+        PullOptions { remote: "origin".into(), branch: "main".into(), mode, autostash, cancel: Default::default() }
+    }
+
+    /// A remote with a.txt and b.txt, and a local clone of it made by pulling
+    async fn cloned() -> (TempDir, LocalRemote, TempDir, Store) {
+        let (remote_temp, remote) = temp_repo();
+        commit_file(&remote, "a.txt", "one\ntwo\nthree\n");
+        commit_file(&remote, "b.txt", "b\n");
+        let remote = LocalRemote { store: remote };
+        let (local_temp, local) = temp_repo();
+        let outcome = pull_branch(&local, &options(PullMode::FfOnly, false), &remote).await.unwrap();
+        assert!(matches!(outcome, PullOutcome::FastForwarded { from: None, .. }), "{:?}", outcome);
+        (remote_temp, remote, local_temp, local)
+    }
+
+    fn refs(store: &Store) -> BTreeMap<String, Option<String>> {
+        ["refs/heads/main", "refs/remotes/origin/main"].iter().map(|r| (r.to_string(), store.read_ref(r))).collect()
+    }
+
+    #[tokio::test]
+    async fn test_ff_only_fast_forwards() {
+        let (_rt, remote, temp, local) = cloned().await;
+        assert_eq!(fs::read_to_string(temp.path().join("b.txt")).unwrap(), "b\n");
+        let tip = commit_file(&remote.store, "c.txt", "c\n");
+
+        let (outcome, report) = pull_branch_with_report(&local, &options(PullMode::FfOnly, false), &remote).await.unwrap();
+        assert!(matches!(&outcome, PullOutcome::FastForwarded { to, .. } if *to == tip), "{:?}", outcome);
+        let [update] = &report.updates[..] else { panic!("expected one ref update, got {:?}", report.updates) };
+        assert_eq!((update.local_ref.as_str(), &update.status), ("refs/remotes/origin/main", &RefStatus::FastForward));
+        assert_eq!(report.stats, rune_remote::TransferStats { objects: 1, bytes: 2 });
+        assert_eq!(local.head_commit(), Some(tip.clone()));
+        assert_eq!(local.read_ref("refs/remotes/origin/main"), Some(tip));
+        assert_eq!(fs::read_to_string(temp.path().join("c.txt")).unwrap(), "c\n");
+        assert_eq!(pull_branch(&local, &options(PullMode::FfOnly, false), &remote).await.unwrap(), PullOutcome::UpToDate);
+    }
+
+    #[tokio::test]
+    async fn test_ff_only_refuses_diverged_branches() {
+        let (_rt, remote, _temp, local) = cloned().await;
+        commit_file(&remote.store, "c.txt", "c\n");
+        commit_file(&local, "d.txt", "d\n");
+        let before = refs(&local);
+
+        let outcome = pull_branch(&local, &options(PullMode::FfOnly, false), &remote).await.unwrap();
+        let PullOutcome::Refused { reason } = outcome else { panic!("expected a refusal, got {:?}", outcome) };
+        assert!(reason.contains("diverged") && reason.contains("pull.mode"), "{}", reason);
+        assert_eq!(refs(&local), before);
+    }
+
+    #[tokio::test]
+    async fn test_merge_joins_diverged_branches() {
+        let (_rt, remote, temp, local) = cloned().await;
+        let theirs = commit_file(&remote.store, "c.txt", "c\n");
+        let ours = commit_file(&local, "d.txt", "d\n");
+
+        let outcome = pull_branch(&local, &options(PullMode::Merge, false), &remote).await.unwrap();
+        let PullOutcome::Merged { commit } = outcome else { panic!("expected a merge, got {:?}", outcome) };
+        let merge = local.resolve_commit(&commit).unwrap();
+        assert_eq!(merge.parent, Some(ours));
+        assert_eq!(merge.merge_parent, Some(theirs));
+        assert_eq!(fs::read_to_string(temp.path().join("c.txt")).unwrap(), "c\n");
+        assert!(local.status().unwrap().working.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_autostash_is_reapplied_over_the_pull() {
+        let (_rt, remote, temp, local) = cloned().await;
+        commit_file(&remote.store, "a.txt", "one\ntwo\nthree\nfour\n");
+        fs::write(temp.path().join("a.txt"), "zero\none\ntwo\nthree\n").unwrap();
+        fs::write(temp.path().join("b.txt"), "b, edited\n").unwrap();
+
+        let refused = pull_branch(&local, &options(PullMode::Merge, false), &remote).await.unwrap();
+        assert!(matches!(refused, PullOutcome::Refused { .. }), "{:?}", refused);
+
+        let outcome = pull_branch(&local, &options(PullMode::Merge, true), &remote).await.unwrap();
+        assert!(matches!(outcome, PullOutcome::FastForwarded { .. }), "{:?}", outcome);
+        assert_eq!(fs::read_to_string(temp.path().join("a.txt")).unwrap(), "zero\none\ntwo\nthree\nfour\n");
+        assert_eq!(fs::read_to_string(temp.path().join("b.txt")).unwrap(), "b, edited\n");
+        assert!(DraftManager::new(Store::open(temp.path()).unwrap()).unwrap().list_drafts().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_autostash_conflict_keeps_the_stash() {
+        let (_rt, remote, temp, local) = cloned().await;
+        commit_file(&remote.store, "a.txt", "one\nTWO\nthree\n");
+        fs::write(temp.path().join("a.txt"), "one\n2\nthree\n").unwrap();
+
+        let outcome = pull_branch(&local, &options(PullMode::Merge, true), &remote).await.unwrap();
+        let PullOutcome::Conflicted { step, paths, stash } = outcome else {
+            panic!("expected conflicts, got {:?}", outcome)
+        };
+        assert_eq!((step, paths), (PullStep::Unstash, vec!["a.txt".to_string()]));
+        let content = fs::read_to_string(temp.path().join("a.txt")).unwrap();
+        assert!(content.contains("<<<<<<<") && content.contains("TWO") && content.contains('2'), "{}", content);
+        let drafts = DraftManager::new(Store::open(temp.path()).unwrap()).unwrap();
+        assert!(drafts.get_draft(&stash.unwrap()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_failure_midway_rolls_everything_back() {
+        let (_rt, _remote, temp, local) = cloned().await;
+        fs::write(temp.path().join("a.txt"), "local edit\n").unwrap();
+        let before = refs(&local);
+
+        let err = pull_branch(&local, &options(PullMode::Merge, true), &LyingRemote).await.unwrap_err();
+        assert!(format!("{:#}", err).contains("nothing was changed"), "{:#}", err);
+        assert_eq!(refs(&local), before);
+        assert_eq!(fs::read_to_string(temp.path().join("a.txt")).unwrap(), "local edit\n");
+        assert_eq!(local.status().unwrap().working, ["a.txt"]);
+        assert!(DraftManager::new(Store::open(temp.path()).unwrap()).unwrap().list_drafts().unwrap().is_empty());
+    }
+}
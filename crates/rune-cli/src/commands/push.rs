@@ -0,0 +1,679 @@
+//! Sending branches to a remote
+//!
+//! Each ref update is worked out from the remote-tracking ref
+//! (`refs/remotes/<remote>/<branch>`), so `pre-push` runs and can refuse the
+//! push before the client is asked to send anything. Commits go out in
+//! batches recorded in a transfer manifest, so a push that fails halfway
+//! resumes with the commits the server doesn't have yet.
+//!
+//! A push of several branches comes back as a `PushReport` with one entry
+//! per ref. `pre-push` runs once for the whole push and a refusal rejects
+//! every ref. A ref refused by the remote or for not being a fast-forward
+//! is reported as rejected and the others still go out; only failing to
+//! reach the remote stops the whole push.
+
+use crate::exit_codes::{CliError, ErrorKind};
+use crate::style::{format_size, Style};
+use anyhow::Result;
+use colored::Colorize;
+use rune_core::Commit;
+use rune_remote::{
+    FileChange, FileOperation, HasItem, PushReport, PushRequest, RefStatus, RemoteConfig, SyncClient, SyncReport,
+    SyncResponse, TransferDirection, TransferItem, TransferManifest, TransferStats,
+};
+use rune_store::{HookEvent, OperationKind, RefUpdate, Store, StoreError, DELETED_BLOB, NULL_ID};
+
+/// What to push and how, as given to `rune push`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PushOptions {
+    pub remote: String,
+    pub branch: String,
+    pub tags: bool,
+    pub all_tags: bool,
+    pub force: bool,
+    /// Make `<remote>/<branch>` the upstream of every branch that goes out
+    pub set_upstream: bool,
+    pub dry_run: bool,
+    /// Push every branch with commits instead of `branch`
+    pub all: bool,
+    /// Skip the `pre-push` hook
+    pub no_verify: bool,
+}
+
+/// The ref `branch` would update on `remote`
+pub fn ref_update(store: &Store, remote: &str, branch: &str) -> Result<RefUpdate> {
+    let local_ref = format!("refs/heads/{}", branch);
+    let local_id = store
+        .read_ref(&local_ref)
+        .ok_or_else(|| StoreError::BranchNotFound(branch.to_string()))?;
+    Ok(RefUpdate {
+        remote_id: store
+            .read_ref(&tracking_ref(remote, branch))
+            .unwrap_or_else(|| NULL_ID.to_string()),
+        remote_ref: local_ref.clone(),
+        local_ref,
+        local_id,
+    })
+}
+
+/// Where the last known position of `branch` on `remote` is kept
+pub fn tracking_ref(remote: &str, branch: &str) -> String {
+    format!("refs/remotes/{}/{}", remote, branch)
+}
+
+/// What pushing `branch` would do, before asking the hook or the remote:
+/// a non-fast-forward is rejected unless `force` is set
+pub fn planned_update(store: &Store, remote: &str, branch: &str, force: bool) -> Result<rune_remote::RefUpdate> {
+    let update = ref_update(store, remote, branch)?;
+    let status = if update.remote_id == NULL_ID {
+        RefStatus::New
+    } else if update.remote_id == update.local_id {
+        RefStatus::UpToDate
+    } else if store.ancestry(&update.local_id).iter().any(|c| c.id == update.remote_id) {
+        RefStatus::FastForward
+    } else if force {
+        RefStatus::Forced
+    } else {
+        RefStatus::Rejected("non-fast-forward; pull first, or push with --force".to_string())
+    };
+    Ok(rune_remote::RefUpdate {
+        old_id: (update.remote_id != NULL_ID).then_some(update.remote_id),
+        local_ref: update.local_ref,
+        remote_ref: update.remote_ref,
+        new_id: update.local_id,
+        status,
+    })
+}
+
+/// Commits sent per request
+pub const PUSH_BATCH: usize = 100;
+
+/// Push `branch` through `client`. Unless `no_verify` is set, `pre-push`
+/// runs first and a refusal rejects the ref without touching the client.
+/// On success the remote-tracking ref moves to the pushed commit.
+pub async fn push_branch(
+    store: &Store,
+    remote: &RemoteConfig,
+    branch: &str,
+    force: bool,
+    no_verify: bool,
+    client: &impl SyncClient,
+) -> Result<PushReport> {
+    push_branch_in_batches(store, remote, branch, force, no_verify, PUSH_BATCH, client).await
+}
+
+/// Push each of `branches` in turn, carrying on past rejected ones
+pub async fn push_branches(
+    store: &Store,
+    remote: &RemoteConfig,
+    branches: &[String],
+    force: bool,
+    no_verify: bool,
+    client: &impl SyncClient,
+) -> Result<PushReport> {
+    push_branches_in_batches(store, remote, branches, force, no_verify, PUSH_BATCH, client).await
+}
+
+/// `push_branch` sending at most `batch` commits per request. Progress is
+/// kept in a transfer manifest: after a failure the next push of the branch
+/// skips what the server confirms it already holds.
+pub async fn push_branch_in_batches(
+    store: &Store,
+    remote: &RemoteConfig,
+    branch: &str,
+    force: bool,
+    no_verify: bool,
+    batch: usize,
+    client: &impl SyncClient,
+) -> Result<PushReport> {
+    push_branches_in_batches(store, remote, &[branch.to_string()], force, no_verify, batch, client).await
+}
+
+/// `push_branches` sending at most `batch` commits per request. Every ref
+/// is planned first so `pre-push` runs once, with all the updates that
+/// would go out; if it refuses, they are all rejected and nothing is sent.
+pub async fn push_branches_in_batches(
+    store: &Store,
+    remote: &RemoteConfig,
+    branches: &[String],
+    force: bool,
+    no_verify: bool,
+    batch: usize,
+    client: &impl SyncClient,
+) -> Result<PushReport> {
+    let mut report = PushReport::new(&remote.name);
+    let mut pending = Vec::new();
+    for branch in branches {
+        let planned = planned_update(store, &remote.name, branch, force)?;
+        if !matches!(planned.status, RefStatus::UpToDate | RefStatus::Rejected(_)) {
+            pending.push((branch, report.updates.len(), ref_update(store, &remote.name, branch)?));
+        }
+        report.updates.push(planned);
+    }
+    if pending.is_empty() {
+        return Ok(report);
+    }
+
+    if !no_verify {
+        let updates: Vec<RefUpdate> = pending.iter().map(|(_, _, update)| update.clone()).collect();
+        let url = remote.push_url.as_deref().unwrap_or(&remote.url);
+        let verdict = store.run_hook(&HookEvent::PrePush { remote: &remote.name, url, updates: &updates });
+        match verdict {
+            Err(err) if matches!(err.downcast_ref::<StoreError>(), Some(StoreError::HookRejected { .. })) => {
+                for (_, index, _) in &pending {
+                    report.updates[*index].status = RefStatus::Rejected(err.to_string());
+                }
+                return Ok(report);
+            }
+            verdict => verdict?,
+        }
+    }
+
+    for (branch, index, update) in &pending {
+        if let Some(reason) = push_ref(store, remote, branch, update, force, batch, client, &mut report.stats).await? {
+            report.updates[*index].status = RefStatus::Rejected(reason);
+        }
+    }
+    Ok(report)
+}
+
+/// Send one branch whose hook already passed, counting what goes out in
+/// `stats`. Returns why the remote refused it, if it did.
+#[allow(clippy::too_many_arguments)]
+async fn push_ref(
+    store: &Store,
+    remote: &RemoteConfig,
+    branch: &str,
+    update: &RefUpdate,
+    force: bool,
+    batch: usize,
+    client: &impl SyncClient,
+    stats: &mut TransferStats,
+) -> Result<Option<String>> {
+    let commits = outgoing_commits(store, update);
+    let (mut manifest, resumed) = resume_or_start(store, &remote.name, branch, &update.local_id, &commits)?;
+    if resumed {
+        confirm_with_server(store, &mut manifest, client).await?;
+    }
+    let response = send_pending(store, &mut manifest, &commits, branch, force, batch.max(1), client, stats).await?;
+    if !response.success {
+        let mut reason = response.message;
+        if !response.conflicts.is_empty() {
+            reason = format!("{} ({})", reason, response.conflicts.join("; "));
+        }
+        return Ok(Some(reason));
+    }
+    manifest.remove(&store.rune_dir)?;
+    let journal = store
+        .begin_operation(OperationKind::Push, format!("{} {}", remote.name, branch), false)?
+        .not_undoable(format!("the commits are already on '{}'; revert them and push again", remote.name));
+    store.write_ref(&tracking_ref(&remote.name, branch), &update.local_id)?;
+    journal.finish(store)?;
+    Ok(None)
+}
+
+/// The error a push ends with when some refs were rejected. The others
+/// went through, so it is a precondition failure rather than a remote one;
+/// the details carry the whole report.
+pub fn rejection_error(report: &PushReport) -> Option<CliError> {
+    let rejected: Vec<&str> = report.rejected().map(|u| u.local_ref.as_str()).collect();
+    if rejected.is_empty() {
+        return None;
+    }
+    let message = format!(
+        "{} of {} ref(s) rejected by {}: {}",
+        rejected.len(),
+        report.updates.len(),
+        report.remote,
+        rejected.join(", ")
+    );
+    let details = serde_json::to_value(report).unwrap_or_default();
+    Some(CliError::new(ErrorKind::PreconditionFailed, message).with_details(details))
+}
+
+/// One line per ref, git style, then what was sent or received. Shared
+/// with `rune pull`, whose reports have the same shape.
+pub fn print_report(report: &SyncReport, direction: TransferDirection) {
+    let (heading, moved) = match direction {
+        TransferDirection::Push => ("To", "sent"),
+        TransferDirection::Pull => ("From", "received"),
+    };
+    println!("{} {}", heading, report.remote);
+    for update in &report.updates {
+        let flag = match &update.status {
+            RefStatus::New => "*".green(),
+            RefStatus::FastForward => " ".normal(),
+            RefStatus::Forced => "+".yellow(),
+            RefStatus::UpToDate => "=".dimmed(),
+            RefStatus::Rejected(_) => "!".red(),
+        };
+        let range = match &update.status {
+            RefStatus::New => "[new]".to_string(),
+            RefStatus::UpToDate => "[up to date]".to_string(),
+            RefStatus::Rejected(_) => "[rejected]".to_string(),
+            _ => update.range(),
+        };
+        let line = format!(" {} {:<18} {} → {}", flag, range, update.local_ref, update.remote_ref);
+        match &update.status {
+            RefStatus::Rejected(reason) => println!("{} ({})", line, reason.red()),
+            RefStatus::Forced => println!("{} (forced)", line),
+            _ => println!("{}", line),
+        }
+    }
+    if report.stats.objects > 0 {
+        Style::info(&format!("{} commit(s), {} {}", report.stats.objects, format_size(report.stats.bytes), moved));
+    }
+}
+
+/// The manifest of an earlier, unfinished push of the same commit, or a new
+/// one, and whether it was resumed. A manifest for a different commit is
+/// stale and replaced.
+fn resume_or_start(
+    store: &Store,
+    remote: &str,
+    branch: &str,
+    target: &str,
+    commits: &[rune_remote::Commit],
+) -> Result<(TransferManifest, bool)> {
+    if let Some(existing) = TransferManifest::find(&store.rune_dir, TransferDirection::Push, remote, branch)? {
+        if existing.target == target {
+            return Ok((existing, true));
+        }
+        existing.remove(&store.rune_dir)?;
+    }
+    let items = commits.iter().map(TransferItem::for_commit).collect::<Result<Vec<_>>>()?;
+    let manifest = TransferManifest::new(TransferDirection::Push, remote, branch, target, items);
+    manifest.save(&store.rune_dir)?;
+    Ok((manifest, false))
+}
+
+/// Settle a resumed manifest against what the server holds: an item counts
+/// as sent only when the server has an intact copy, whether or not the
+/// interrupted run got to record it.
+async fn confirm_with_server(store: &Store, manifest: &mut TransferManifest, client: &impl SyncClient) -> Result<()> {
+    let items: Vec<HasItem> = manifest.items.iter().map(HasItem::from).collect();
+    let missing = client.missing(&items).await?;
+    for item in &mut manifest.items {
+        item.done = !missing.contains(&item.id);
+    }
+    manifest.save(&store.rune_dir)
+}
+
+/// Send the commits the manifest has not seen accepted, `batch` at a time,
+/// counting each one that gets an answer in `stats`
+#[allow(clippy::too_many_arguments)]
+async fn send_pending(
+    store: &Store,
+    manifest: &mut TransferManifest,
+    commits: &[rune_remote::Commit],
+    branch: &str,
+    force: bool,
+    batch: usize,
+    client: &impl SyncClient,
+    stats: &mut TransferStats,
+) -> Result<SyncResponse> {
+    let mut pending: Vec<&rune_remote::Commit> = commits.iter().filter(|c| !manifest.is_done(&c.hash)).collect();
+    // Everything arrived but the branch may not have moved: the last commit
+    // goes again, which the server accepts without storing it twice
+    if pending.is_empty() {
+        pending.extend(commits.last());
+    }
+    let mut processed = 0;
+    for chunk in pending.chunks(batch) {
+        let request = PushRequest {
+            commits: chunk.iter().map(|c| (*c).clone()).collect(),
+            branch: branch.to_string(),
+            force,
+        };
+        let response = client.push(&request).await.map_err(|e| {
+            e.context(format!(
+                "push interrupted at {}%; run it again to resume, or `rune transfer abort {}` to start over",
+                manifest.percent(),
+                manifest.id
+            ))
+        })?;
+        for commit in chunk {
+            stats.add(rune_remote::transfer::commit_bytes(commit)?.len() as u64);
+        }
+        if !response.success {
+            return Ok(response);
+        }
+        processed += response.commits_processed;
+        manifest.mark_done(chunk.iter().map(|c| c.hash.as_str()));
+        manifest.save(&store.rune_dir)?;
+    }
+    Ok(SyncResponse {
+        success: true,
+        message: format!("Successfully pushed {} commits", processed),
+        commits_processed: processed,
+        conflicts: Vec::new(),
+    })
+}
+
+/// Commits between the remote's last known tip and ours, oldest first
+fn outgoing_commits(store: &Store, update: &RefUpdate) -> Vec<rune_remote::Commit> {
+    let log = store.log();
+    let find = |id: &str| log.iter().find(|c| c.id == id);
+    let mut outgoing = Vec::new();
+    let mut current = find(&update.local_id);
+    while let Some(commit) = current.filter(|c| c.id != update.remote_id) {
+        let parent = commit.parent.as_deref().and_then(find);
+        outgoing.push(sync_commit(commit, parent));
+        current = parent;
+    }
+    outgoing.reverse();
+    outgoing
+}
+
+fn sync_commit(commit: &Commit, parent: Option<&Commit>) -> rune_remote::Commit {
+    let files = commit
+        .files
+        .iter()
+        .map(|path| {
+            let blob = commit.tree.get(path).filter(|b| b.as_str() != DELETED_BLOB);
+            let existed = parent.and_then(|p| p.tree.get(path)).is_some_and(|b| b != DELETED_BLOB);
+            FileChange {
+                path: path.clone(),
+                operation: match (blob, existed) {
+                    (None, _) => FileOperation::Deleted,
+                    (Some(_), true) => FileOperation::Modified,
+                    (Some(_), false) => FileOperation::Added,
+                },
+                content_hash: blob.cloned(),
+            }
+        })
+        .collect();
+    rune_remote::Commit {
+        hash: commit.id.clone(),
+        message: commit.message.clone(),
+        author: format!("{} <{}>", commit.author.name, commit.author.email),
+        timestamp: chrono::DateTime::from_timestamp(commit.time, 0).unwrap_or_default(),
+        parent: commit.parent.clone(),
+        files,
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::commands::test_support::temp_repo;
+    use rune_core::Author;
+    use std::cell::RefCell;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    /// Records requests instead of sending them
+    #[derive(Default)]
+    struct MockClient {
+        requests: RefCell<Vec<PushRequest>>,
+    }
+
+    impl SyncClient for MockClient {
+        async fn push(&self, request: &PushRequest) -> Result<SyncResponse> {
+            self.requests.borrow_mut().push(request.clone());
+            Ok(SyncResponse {
+                success: true,
+                message: "ok".to_string(),
+                commits_processed: request.commits.len(),
+                conflicts: Vec::new(),
+            })
+        }
+    }
+
+    /// Stores what it is sent and can drop the connection after a number of
+    /// commits, keeping the ones that made it
+    #[derive(Default)]
+    struct FlakyServer {
+        stored: RefCell<std::collections::BTreeMap<String, String>>,
+        head: RefCell<Option<String>>,
+        received: RefCell<Vec<String>>,
+        accept_before_failing: std::cell::Cell<Option<usize>>,
+    }
+
+    impl SyncClient for FlakyServer {
+        async fn push(&self, request: &PushRequest) -> Result<SyncResponse> {
+            for commit in &request.commits {
+                if self.accept_before_failing.get() == Some(0) {
+                    anyhow::bail!("connection reset by peer");
+                }
+                self.accept_before_failing.set(self.accept_before_failing.get().map(|n| n - 1));
+                self.received.borrow_mut().push(commit.message.clone());
+                let digest = TransferItem::for_commit(commit)?.digest;
+                self.stored.borrow_mut().insert(commit.hash.clone(), digest);
+            }
+            *self.head.borrow_mut() = request.commits.last().map(|c| c.hash.clone());
+            Ok(SyncResponse {
+                success: true,
+                message: "ok".to_string(),
+                commits_processed: request.commits.len(),
+                conflicts: Vec::new(),
+            })
+        }
+
+        async fn missing(&self, items: &[HasItem]) -> Result<Vec<String>> {
+            let stored = self.stored.borrow();
+            Ok(items
+                .iter()
+                .filter(|i| stored.get(&i.id) != Some(&i.digest))
+                .map(|i| i.id.clone())
+                .collect())
+        }
+    }
+
+    fn repo_with_commits(messages: &[&str]) -> (TempDir, Store) {
+        let (temp, store) = temp_repo();
+        let author = Author { name: "Dev".into(), email: "dev@example.com".into() };
+        for (i, message) in messages.iter().enumerate() {
+            fs::write(temp.path().join("file.txt"), i.to_string()).unwrap();
+            store.stage_file("file.txt").unwrap();
+            store.commit(message, author.clone()).unwrap();
+        }
+        (temp, store)
+    }
+
+    fn install_pre_push(store: &Store, script: &str) {
+        let path = store.rune_dir.join("hooks/pre-push");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, format!("#!/bin/sh\n{}", script)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    fn origin() -> RemoteConfig {
+        RemoteConfig { url: "http://example.com/repo".into(), ..RemoteConfig::default() }
+    }
+
+    /// A shrine serving an empty repository on a free local port, and a
+    /// remote pointing at it
+    async fn shrine_remote() -> (TempDir, RemoteConfig) {
+        let temp = TempDir::new().unwrap();
+        Store::open(temp.path()).unwrap().create().unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(rune_remote::serve(rune_remote::Shrine { root: temp.path().to_path_buf() }, listener));
+        (temp, RemoteConfig { url, ..RemoteConfig::default() })
+    }
+
+    #[tokio::test]
+    async fn test_rejecting_pre_push_stops_before_the_client() {
+        let (_temp, store) = repo_with_commits(&["Start", "Half done"]);
+        // Refuse direct pushes to main
+        install_pre_push(
+            &store,
+            "while read local_ref local_id remote_ref remote_id; do\n  [ \"$remote_ref\" = refs/heads/main ] && exit 1\ndone\nexit 0\n",
+        );
+        let client = MockClient::default();
+        let report = push_branch(&store, &origin(), "main", false, false, &client).await.unwrap();
+
+        let RefStatus::Rejected(reason) = &report.updates[0].status else {
+            panic!("expected a rejection, got {:?}", report.updates[0].status)
+        };
+        assert!(reason.contains("pre-push hook rejected"), "{}", reason);
+        assert!(client.requests.borrow().is_empty());
+        assert!(store.read_ref("refs/remotes/origin/main").is_none());
+
+        // --no-verify skips the hook
+        let report = push_branch(&store, &origin(), "main", false, true, &client).await.unwrap();
+        assert_eq!(report.updates[0].status, RefStatus::New);
+        assert_eq!(client.requests.borrow().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_mixed_push_reports_each_ref() {
+        let (temp, store) = repo_with_commits(&["One"]);
+        let (server, remote) = shrine_remote().await;
+        let client = rune_remote::HttpSyncClient::new(remote.clone());
+        let one = store.read_ref("refs/heads/main").unwrap();
+        store.write_ref("refs/heads/feature", &one).unwrap();
+        store.write_ref("refs/heads/docs", &one).unwrap();
+        let branches = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        let report = push_branches(&store, &remote, &branches(&["feature", "docs"]), false, false, &client).await.unwrap();
+        assert!(report.is_complete(), "{:?}", report);
+
+        fs::write(temp.path().join("file.txt"), "two").unwrap();
+        store.stage_file("file.txt").unwrap();
+        let two = store.commit("Two", Author { name: "Dev".into(), email: "dev@example.com".into() }).unwrap().id;
+        store.write_ref("refs/heads/feature", &two).unwrap();
+        // Someone else moved release past ours
+        store.write_ref("refs/heads/release", &one).unwrap();
+        store.write_ref("refs/remotes/origin/release", &two).unwrap();
+        install_pre_push(&store, "cat >> pre-push.log\n");
+
+        let names = branches(&["main", "feature", "release", "docs"]);
+        let report = push_branches(&store, &remote, &names, false, false, &client).await.unwrap();
+        let statuses: Vec<&RefStatus> = report.updates.iter().map(|u| &u.status).collect();
+        assert!(matches!(
+            statuses[..],
+            [RefStatus::New, RefStatus::FastForward, RefStatus::Rejected(_), RefStatus::UpToDate]
+        ), "{:?}", statuses);
+        assert_eq!(report.updates[1].old_id.as_deref(), Some(one.as_str()));
+        assert_eq!(report.updates[1].new_id, two);
+        // main sends both commits, feature the new one
+        assert_eq!(report.stats.objects, 3);
+        assert!(report.stats.bytes > 0);
+
+        // The accepted refs moved on both sides; release stayed put
+        assert_eq!(fs::read_to_string(server.path().join(".rune/refs/heads/feature")).unwrap().trim(), two);
+        assert_eq!(store.read_ref("refs/remotes/origin/main").as_deref(), Some(two.as_str()));
+        assert_eq!(store.read_ref("refs/remotes/origin/release").as_deref(), Some(two.as_str()));
+        assert!(!server.path().join(".rune/refs/heads/release").exists());
+        // The hook saw only the refs that went out
+        assert_eq!(fs::read_to_string(temp.path().join("pre-push.log")).unwrap().lines().count(), 2);
+
+        let err = rejection_error(&report).unwrap();
+        assert!(err.message.starts_with("1 of 4 ref(s) rejected") && err.message.contains("refs/heads/release"));
+        let reason = err.details["updates"][2]["status"]["rejected"].as_str().unwrap_or_default().to_string();
+        assert!(reason.contains("non-fast-forward"), "{}", reason);
+        assert_eq!(crate::exit_codes::classify(&err.into()), ErrorKind::PreconditionFailed);
+        assert!(rejection_error(&PushReport::new("origin")).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_pre_push_runs_once_with_every_ref() {
+        let (temp, store) = repo_with_commits(&["One"]);
+        let head = store.read_ref("refs/heads/main").unwrap();
+        store.write_ref("refs/heads/feature", &head).unwrap();
+        install_pre_push(&store, "echo run >> runs.log\ncat >> pre-push.log\n");
+        let client = MockClient::default();
+        let branches = vec!["main".to_string(), "feature".to_string()];
+
+        push_branches(&store, &origin(), &branches, false, false, &client).await.unwrap();
+        assert_eq!(fs::read_to_string(temp.path().join("runs.log")).unwrap().lines().count(), 1);
+        let log = fs::read_to_string(temp.path().join("pre-push.log")).unwrap();
+        let refs: Vec<&str> = log.lines().map(|l| l.split(' ').next().unwrap()).collect();
+        assert_eq!(refs, ["refs/heads/main", "refs/heads/feature"]);
+
+        // A refusal rejects every ref and sends nothing
+        fs::write(temp.path().join("file.txt"), "more").unwrap();
+        store.stage_file("file.txt").unwrap();
+        let next = store.commit("More", Author { name: "Dev".into(), email: "dev@example.com".into() }).unwrap().id;
+        store.write_ref("refs/heads/feature", &next).unwrap();
+        install_pre_push(&store, "exit 1\n");
+        let sent = client.requests.borrow().len();
+        let report = push_branches(&store, &origin(), &branches, false, false, &client).await.unwrap();
+        assert_eq!(report.rejected().count(), 2);
+        assert_eq!(client.requests.borrow().len(), sent);
+    }
+
+    #[tokio::test]
+    async fn test_push_sends_only_new_commits() {
+        let (temp, store) = repo_with_commits(&["One", "Two"]);
+        install_pre_push(&store, "cat >> pre-push.log\n");
+        let client = MockClient::default();
+
+        push_branch(&store, &origin(), "main", false, false, &client).await.unwrap();
+        let head = store.read_ref("refs/heads/main").unwrap();
+        assert_eq!(store.read_ref("refs/remotes/origin/main").as_deref(), Some(head.as_str()));
+
+        fs::write(temp.path().join("file.txt"), "three").unwrap();
+        store.stage_file("file.txt").unwrap();
+        store.commit("Three", Author { name: "Dev".into(), email: "dev@example.com".into() }).unwrap();
+        push_branch(&store, &origin(), "main", false, false, &client).await.unwrap();
+
+        let requests = client.requests.borrow().clone();
+        let messages: Vec<Vec<&str>> = requests
+            .iter()
+            .map(|r| r.commits.iter().map(|c| c.message.as_str()).collect())
+            .collect();
+        assert_eq!(messages, [vec!["One", "Two"], vec!["Three"]]);
+        assert!(matches!(requests[0].commits[0].files[0].operation, FileOperation::Added));
+        assert!(matches!(requests[1].commits[0].files[0].operation, FileOperation::Modified));
+
+        // One line per push, naming what the remote had before
+        let log = fs::read_to_string(temp.path().join("pre-push.log")).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with(NULL_ID));
+        assert!(lines[1].ends_with(&head));
+
+        // Nothing new: no hook, no request
+        push_branch(&store, &origin(), "main", false, false, &client).await.unwrap();
+        assert_eq!(client.requests.borrow().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_push_resumes_with_the_remaining_commits() {
+        let (_temp, store) = repo_with_commits(&["c1", "c2", "c3", "c4", "c5"]);
+        let clean = FlakyServer::default();
+        push_branch_in_batches(&store, &origin(), "main", false, true, 2, &clean).await.unwrap();
+        fs::remove_file(store.rune_dir.join("refs/remotes/origin/main")).unwrap();
+
+        // The link drops in the middle of the second batch: c3 arrives but
+        // the reply doesn't
+        let server = FlakyServer { accept_before_failing: Some(3).into(), ..Default::default() };
+        let err = push_branch_in_batches(&store, &origin(), "main", false, true, 2, &server).await.unwrap_err();
+        assert!(format!("{:#}", err).contains("connection reset"), "{:#}", err);
+        assert!(store.read_ref("refs/remotes/origin/main").is_none());
+        let [manifest] = &rune_remote::list_transfers(&store.rune_dir).unwrap()[..] else {
+            panic!("expected one transfer in flight");
+        };
+        assert_eq!(manifest.items.iter().filter(|i| i.done).count(), 2);
+        assert!(manifest.percent() > 0 && manifest.percent() < 100);
+
+        server.received.borrow_mut().clear();
+        server.accept_before_failing.set(None);
+        push_branch_in_batches(&store, &origin(), "main", false, true, 2, &server).await.unwrap();
+
+        // c3 is on the server already, so only c4 and c5 go again
+        assert_eq!(*server.received.borrow(), ["c4", "c5"]);
+        assert_eq!(*server.stored.borrow(), *clean.stored.borrow());
+        assert_eq!(*server.head.borrow(), *clean.head.borrow());
+        assert_eq!(store.read_ref("refs/remotes/origin/main"), store.read_ref("refs/heads/main"));
+        assert!(rune_remote::list_transfers(&store.rune_dir).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_resume_resends_what_the_server_lost() {
+        let (_temp, store) = repo_with_commits(&["c1", "c2", "c3"]);
+        let server = FlakyServer { accept_before_failing: Some(2).into(), ..Default::default() };
+        push_branch_in_batches(&store, &origin(), "main", false, true, 1, &server).await.unwrap_err();
+
+        // The manifest says c1 went through, but the server's copy is damaged
+        let c1 = store.log()[0].id.clone();
+        server.stored.borrow_mut().insert(c1, "damaged".into());
+        server.received.borrow_mut().clear();
+        server.accept_before_failing.set(None);
+        push_branch_in_batches(&store, &origin(), "main", false, true, 1, &server).await.unwrap();
+        assert_eq!(*server.received.borrow(), ["c1", "c3"]);
+    }
+}
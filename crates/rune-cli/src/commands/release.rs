@@ -0,0 +1,566 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use rune_core::{Author, Commit};
+use rune_store::{Index, ReleaseNotes, ReleaseNotesOptions, Store};
+pub use rune_store::{parse_conventional, ConventionalCommit};
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use crate::exit_codes::{CliError, ErrorKind};
+use crate::style::Style;
+
+const CHANGELOG: &str = "CHANGELOG.md";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    pub fn bump(self, bump: Bump) -> Version {
+        match bump {
+            Bump::Major => Version { major: self.major + 1, minor: 0, patch: 0 },
+            Bump::Minor => Version { minor: self.minor + 1, patch: 0, ..self },
+            Bump::Patch => Version { patch: self.patch + 1, ..self },
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl std::str::FromStr for Version {
+    type Err = anyhow::Error;
+
+    /// `1.2.3` or `v1.2.3`; pre-release and build suffixes such as
+    /// `-alpha.4` are ignored
+    fn from_str(s: &str) -> Result<Self> {
+        let core = s.trim().trim_start_matches('v');
+        let core = core.split(['-', '+']).next().unwrap_or_default();
+        let parts: Vec<u64> = core
+            .split('.')
+            .map(|p| p.parse::<u64>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| anyhow::anyhow!("Invalid version '{}'", s))?;
+        match parts[..] {
+            [major, minor, patch] => Ok(Version { major, minor, patch }),
+            _ => anyhow::bail!("Invalid version '{}': expected MAJOR.MINOR.PATCH", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Bump {
+    Patch,
+    Minor,
+    Major,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionFileKind {
+    Cargo,
+    PackageJson,
+    /// The whole file is the version, e.g. `VERSION`
+    Plain,
+}
+
+#[derive(Debug, Clone)]
+pub struct VersionFile {
+    /// Relative to the repository root
+    pub path: String,
+    pub kind: VersionFileKind,
+    /// Version string exactly as written in the file
+    pub raw: String,
+    pub version: Version,
+}
+
+impl VersionFile {
+    fn kind_for(path: &str) -> VersionFileKind {
+        match Path::new(path).file_name().and_then(|n| n.to_str()) {
+            Some("Cargo.toml") => VersionFileKind::Cargo,
+            Some("package.json") => VersionFileKind::PackageJson,
+            _ => VersionFileKind::Plain,
+        }
+    }
+
+    fn read(root: &Path, path: &str) -> Result<VersionFile> {
+        let content = fs::read_to_string(root.join(path))
+            .with_context(|| format!("Failed to read version file {}", path))?;
+        let kind = Self::kind_for(path);
+        let raw = match kind {
+            VersionFileKind::Cargo => {
+                let doc: toml::Value = toml::from_str(&content)
+                    .with_context(|| format!("Failed to parse {}", path))?;
+                let package = doc.get("package").and_then(|p| p.get("version")).and_then(|v| v.as_str());
+                let workspace = doc
+                    .get("workspace")
+                    .and_then(|w| w.get("package"))
+                    .and_then(|p| p.get("version"))
+                    .and_then(|v| v.as_str());
+                package
+                    .or(workspace)
+                    .with_context(|| format!("No [package] or [workspace.package] version in {}", path))?
+                    .to_string()
+            }
+            VersionFileKind::PackageJson => {
+                let doc: serde_json::Value = serde_json::from_str(&content)
+                    .with_context(|| format!("Failed to parse {}", path))?;
+                doc.get("version")
+                    .and_then(|v| v.as_str())
+                    .with_context(|| format!("No \"version\" field in {}", path))?
+                    .to_string()
+            }
+            VersionFileKind::Plain => content.trim().to_string(),
+        };
+        let version = raw.parse()?;
+        Ok(VersionFile { path: path.to_string(), kind, raw, version })
+    }
+
+    /// `content` with the version replaced, leaving the rest of the file as is
+    fn with_version(&self, content: &str, version: Version) -> Result<String> {
+        let new = version.to_string();
+        match self.kind {
+            VersionFileKind::Plain => Ok(format!("{}\n", new)),
+            VersionFileKind::PackageJson => {
+                let field = content
+                    .find("\"version\"")
+                    .with_context(|| format!("No \"version\" field in {}", self.path))?;
+                let start = field
+                    + content[field..]
+                        .find(&format!("\"{}\"", self.raw))
+                        .with_context(|| format!("Unexpected version format in {}", self.path))?;
+                Ok(format!("{}\"{}\"{}", &content[..start], new, &content[start + self.raw.len() + 2..]))
+            }
+            VersionFileKind::Cargo => {
+                // The first `version = "..."` of [package], or [workspace.package]
+                // when the package inherits its version
+                let mut section = String::new();
+                let mut replaced = false;
+                let mut out = String::with_capacity(content.len());
+                for line in content.split_inclusive('\n') {
+                    let trimmed = line.trim();
+                    if trimmed.starts_with('[') {
+                        section = trimmed.trim_matches(['[', ']']).trim().to_string();
+                    }
+                    let key = trimmed.split('=').next().unwrap_or_default().trim();
+                    if !replaced
+                        && (section == "package" || section == "workspace.package")
+                        && key == "version"
+                        && line.contains(&format!("\"{}\"", self.raw))
+                    {
+                        out.push_str(&line.replacen(&format!("\"{}\"", self.raw), &format!("\"{}\"", new), 1));
+                        replaced = true;
+                    } else {
+                        out.push_str(line);
+                    }
+                }
+                if !replaced {
+                    anyhow::bail!("Could not find version \"{}\" in {}", self.raw, self.path);
+                }
+                Ok(out)
+            }
+        }
+    }
+}
+
+/// The configured version file, or the first of `Cargo.toml`,
+/// `package.json` and `VERSION` that exists
+pub fn detect_version(root: &Path, configured: Option<&str>) -> Result<VersionFile> {
+    if let Some(path) = configured {
+        return VersionFile::read(root, path);
+    }
+    ["Cargo.toml", "package.json", "VERSION"]
+        .into_iter()
+        .find(|p| root.join(p).is_file())
+        .map(|p| VersionFile::read(root, p))
+        .unwrap_or_else(|| {
+            anyhow::bail!("No version file found; set [release] version_file in .rune/config.toml")
+        })
+}
+
+/// Breaking changes bump major, features minor, anything else patch
+pub fn bump_from_commits(commits: &[Commit]) -> Bump {
+    commits
+        .iter()
+        .filter_map(|c| parse_conventional(&c.message))
+        .map(|c| match c.kind.as_str() {
+            _ if c.breaking => Bump::Major,
+            "feat" => Bump::Minor,
+            _ => Bump::Patch,
+        })
+        .max()
+        .unwrap_or(Bump::Patch)
+}
+
+/// Markdown changelog section for `version`: the release notes `rune
+/// notes between` prints for the same commits
+pub fn changelog_section(version: Version, date: &str, commits: &[Commit]) -> String {
+    let notes = ReleaseNotes::from_commits(commits, &ReleaseNotesOptions::default());
+    format!("## [{}] - {}\n{}", version, date, notes.to_markdown())
+}
+
+/// `changelog` with `section` inserted above the newest release
+fn prepend_changelog(changelog: Option<&str>, section: &str) -> String {
+    let Some(existing) = changelog else {
+        return format!("# Changelog\n\n{}", section);
+    };
+    let insert_at = if existing.starts_with("# ") {
+        existing.find("\n## ").map(|i| i + 1).unwrap_or(existing.len())
+    } else {
+        0
+    };
+    let (head, tail) = existing.split_at(insert_at);
+    let mut out = head.to_string();
+    if !out.is_empty() && !out.ends_with("\n\n") {
+        out.push_str(if out.ends_with('\n') { "\n" } else { "\n\n" });
+    }
+    out.push_str(section);
+    if !tail.is_empty() {
+        out.push('\n');
+        out.push_str(tail);
+    }
+    out
+}
+
+/// Everything `rune autoflow release` is about to do
+#[derive(Debug, Clone)]
+pub struct ReleasePlan {
+    pub version_file: VersionFile,
+    pub next: Version,
+    pub bump: Bump,
+    /// Tag the commit list starts after, `None` for a first release
+    pub previous_tag: Option<String>,
+    /// Commits since `previous_tag`, newest first
+    pub commits: Vec<Commit>,
+    pub changelog: String,
+    pub tag: String,
+    pub branch: Option<String>,
+    pub message: String,
+}
+
+impl ReleasePlan {
+    pub fn files(&self) -> Vec<&str> {
+        vec![self.version_file.path.as_str(), CHANGELOG]
+    }
+}
+
+/// Work out the release without touching the repository. `bump` overrides
+/// the conventional-commit analysis.
+pub fn plan_release(store: &Store, bump: Option<Bump>, release_branch: bool) -> Result<ReleasePlan> {
+    let head = store
+        .read_ref(&store.head_ref())
+        .context("No commits yet, nothing to release")?;
+    if !store.status()?.staging.is_empty() {
+        let message = "There are staged changes; commit or unstage them before releasing";
+        return Err(CliError::new(ErrorKind::PreconditionFailed, message).into());
+    }
+
+    let version_file = detect_version(&store.root, store.config().release.version_file.as_deref())?;
+    let previous_tag = store.describe(&head)?.map(|(tag, _)| tag);
+    let commits = store.commits_since(previous_tag.as_deref(), &head)?;
+    if commits.is_empty() {
+        let message = format!(
+            "No commits since {}, nothing to release",
+            previous_tag.as_deref().unwrap_or("the start of history")
+        );
+        return Err(CliError::new(ErrorKind::NothingToDo, message).into());
+    }
+
+    let bump = bump.unwrap_or_else(|| bump_from_commits(&commits));
+    let next = version_file.version.bump(bump);
+    let tag = format!("v{}", next);
+    if store.tag_exists(&tag) {
+        return Err(CliError::new(ErrorKind::PreconditionFailed, format!("Tag '{}' already exists", tag)).into());
+    }
+    let branch = release_branch.then(|| format!("release/{}.{}", next.major, next.minor));
+    if let Some(branch) = &branch {
+        if store.branch_exists(branch) {
+            return Err(rune_store::StoreError::BranchExists(branch.clone()).into());
+        }
+    }
+
+    let date = chrono::Utc::now().format("%Y-%m-%d").to_string();
+    Ok(ReleasePlan {
+        changelog: changelog_section(next, &date, &commits),
+        message: format!("chore(release): {}", tag),
+        version_file,
+        next,
+        bump,
+        previous_tag,
+        commits,
+        tag,
+        branch,
+    })
+}
+
+pub fn print_plan(plan: &ReleasePlan) {
+    println!(
+        "\n🚀 Release {} → {} ({:?} bump)",
+        plan.version_file.raw.dimmed(),
+        plan.next.to_string().green().bold(),
+        plan.bump
+    );
+    match &plan.previous_tag {
+        Some(tag) => println!("   {} commits since {}", plan.commits.len(), tag.yellow()),
+        None => println!("   {} commits (first release)", plan.commits.len()),
+    }
+    for commit in &plan.commits {
+        println!(
+            "   {} {}",
+            Style::commit_hash(&commit.id[..commit.id.len().min(8)]),
+            commit.message.lines().next().unwrap_or_default()
+        );
+    }
+    println!("\n📝 Files to update:");
+    for file in plan.files() {
+        println!("   {}", Style::file_path(file));
+    }
+    println!("\n📦 Commit: {}", plan.message);
+    println!("🏷️  Annotated tag: {}", plan.tag.yellow());
+    if let Some(branch) = &plan.branch {
+        println!("🌿 Branch: {}", Style::branch_name(branch));
+    }
+    println!("\n{}", "Changelog:".bold());
+    for line in plan.changelog.lines() {
+        println!("   {}", line);
+    }
+}
+
+/// Carry out `plan`. `confirm` is asked before each step; declining (or any
+/// error) stops the release, and nothing is tagged unless the release
+/// commit was made.
+pub fn execute(
+    store: &Store,
+    plan: &ReleasePlan,
+    author: Author,
+    confirm: &mut dyn FnMut(&str) -> Result<bool>,
+) -> Result<Option<String>> {
+    let version_path = store.root.join(&plan.version_file.path);
+    let changelog_path = store.root.join(CHANGELOG);
+    let original_version = fs::read_to_string(&version_path)?;
+    let original_changelog = fs::read_to_string(&changelog_path).ok();
+    let original_index = store.read_index()?;
+
+    let restore = |index: &Index| -> Result<()> {
+        fs::write(&version_path, &original_version)?;
+        match &original_changelog {
+            Some(content) => fs::write(&changelog_path, content)?,
+            None if changelog_path.exists() => fs::remove_file(&changelog_path)?,
+            None => {}
+        }
+        store.write_index(index)
+    };
+
+    if !confirm(&format!("Update {} and {} to {}?", plan.version_file.path, CHANGELOG, plan.next))? {
+        return Ok(None);
+    }
+    let commit = (|| -> Result<Commit> {
+        fs::write(&version_path, plan.version_file.with_version(&original_version, plan.next)?)?;
+        fs::write(&changelog_path, prepend_changelog(original_changelog.as_deref(), &plan.changelog))?;
+        if !confirm(&format!("Commit '{}'?", plan.message))? {
+            anyhow::bail!("Release cancelled before committing");
+        }
+        for file in plan.files() {
+            store.stage_file(file)?;
+        }
+        store.commit(&plan.message, author)
+    })();
+    let commit = match commit {
+        Ok(commit) => commit,
+        Err(e) => {
+            restore(&original_index).context("Failed to roll back release files")?;
+            return Err(e);
+        }
+    };
+
+    if !confirm(&format!("Create tag {}?", plan.tag))? {
+        Style::warning(&format!(
+            "Stopped before tagging; release commit {} is untagged",
+            &commit.id[..8]
+        ));
+        return Ok(None);
+    }
+    store.create_annotated_tag(&plan.tag, &commit.id, &plan.changelog)?;
+
+    if let Some(branch) = &plan.branch {
+        if confirm(&format!("Create branch {}?", branch))? {
+            store.create_branch(branch)?;
+        }
+    }
+    Ok(Some(commit.id))
+}
+
+/// `rune autoflow release`
+pub fn run(bump: Option<Bump>, release_branch: bool, dry_run: bool, interactive: bool, author: Author) -> Result<()> {
+    let store = Store::discover(std::env::current_dir()?)?;
+    let plan = plan_release(&store, bump, release_branch)?;
+    print_plan(&plan);
+
+    if dry_run {
+        println!("\n💡 Run without {} to release", "--dry-run".yellow());
+        return Ok(());
+    }
+
+    let stdin = std::io::stdin();
+    let mut confirm = |question: &str| -> Result<bool> {
+        if !interactive {
+            return Ok(true);
+        }
+        print!("{} [y/N] ", question);
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        stdin.read_line(&mut answer)?;
+        Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+    };
+
+    match execute(&store, &plan, author, &mut confirm)? {
+        Some(_) => Style::success(&format!("Released {}", plan.tag)),
+        None => Style::warning("Release stopped"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support::{author, temp_repo};
+    use tempfile::TempDir;
+
+    /// Released as v0.1.0, followed by a feature and a fix
+    fn fixture_repo() -> (TempDir, Store) {
+        let (temp_dir, store) = temp_repo();
+        let commit = |path: &str, content: &str, message: &str| {
+            fs::write(temp_dir.path().join(path), content).unwrap();
+            store.stage_file(path).unwrap();
+            store.commit(message, author()).unwrap()
+        };
+
+        let first = commit(
+            "Cargo.toml",
+            "[package]\nname = \"demo\"\nversion = \"0.1.0\"\n\n[dependencies]\nserde = { version = \"1.0\" }\n",
+            "chore: initial import",
+        );
+        store.create_annotated_tag("v0.1.0", &first.id, "first").unwrap();
+        commit("src.rs", "fn a() {}\n", "feat(cli): add stats command");
+        commit("src.rs", "fn a() { }\n", "fix: handle empty log");
+        (temp_dir, store)
+    }
+
+    #[test]
+    fn test_parse_conventional() {
+        let parsed = parse_conventional("feat(api)!: drop v1 endpoints").unwrap();
+        assert_eq!(parsed.kind, "feat");
+        assert_eq!(parsed.scope.as_deref(), Some("api"));
+        assert!(parsed.breaking);
+        assert!(parse_conventional("fix: x\n\nBREAKING CHANGE: y").unwrap().breaking);
+        assert!(parse_conventional("Merge branch 'x'").is_none());
+    }
+
+    #[test]
+    fn test_next_version_from_commits() {
+        let (_temp_dir, store) = fixture_repo();
+        let plan = plan_release(&store, None, false).unwrap();
+        assert_eq!(plan.previous_tag.as_deref(), Some("v0.1.0"));
+        assert_eq!(plan.commits.len(), 2);
+        assert_eq!(plan.bump, Bump::Minor);
+        assert_eq!(plan.next.to_string(), "0.2.0");
+
+        let explicit = plan_release(&store, Some(Bump::Major), false).unwrap();
+        assert_eq!(explicit.next.to_string(), "1.0.0");
+    }
+
+    #[test]
+    fn test_changelog_groups_commits_by_type() {
+        let (_temp_dir, store) = fixture_repo();
+        let plan = plan_release(&store, None, false).unwrap();
+        let changelog = &plan.changelog;
+
+        assert!(changelog.starts_with("## [0.2.0] - "));
+        let features = changelog.find("### Features").unwrap();
+        let fixes = changelog.find("### Bug Fixes").unwrap();
+        assert!(features < fixes);
+        assert!(changelog.contains("- **cli:** add stats command ("));
+        assert!(changelog.contains("- handle empty log ("));
+        assert!(!changelog.contains("initial import"));
+
+        let release = Commit { message: "chore(release): v0.1.0".into(), ..plan.commits[0].clone() };
+        let section = changelog_section(plan.next, "2024-01-01", &[release]);
+        assert_eq!(section, "## [0.2.0] - 2024-01-01\n");
+    }
+
+    #[test]
+    fn test_release_updates_files_and_tags() {
+        let (temp_dir, store) = fixture_repo();
+        let plan = plan_release(&store, None, true).unwrap();
+        let commit = execute(&store, &plan, author(), &mut |_| Ok(true)).unwrap().unwrap();
+
+        let cargo = fs::read_to_string(temp_dir.path().join("Cargo.toml")).unwrap();
+        assert!(cargo.contains("version = \"0.2.0\""));
+        // Dependency versions are left alone
+        assert!(cargo.contains("serde = { version = \"1.0\" }"));
+        let changelog = fs::read_to_string(temp_dir.path().join(CHANGELOG)).unwrap();
+        assert!(changelog.starts_with("# Changelog\n\n## [0.2.0]"));
+
+        assert_eq!(store.tag_commit("v0.2.0").as_deref(), Some(commit.as_str()));
+        assert_eq!(store.log().last().unwrap().message, "chore(release): v0.2.0");
+        assert!(store.branch_exists("release/0.2"));
+        assert_eq!(store.describe(&commit).unwrap(), Some(("v0.2.0".to_string(), 0)));
+    }
+
+    #[test]
+    fn test_dry_run_plan_does_not_mutate() {
+        let (temp_dir, store) = fixture_repo();
+        let cargo_before = fs::read_to_string(temp_dir.path().join("Cargo.toml")).unwrap();
+        let commits_before = store.log().len();
+
+        let plan = plan_release(&store, None, true).unwrap();
+        print_plan(&plan);
+
+        assert_eq!(fs::read_to_string(temp_dir.path().join("Cargo.toml")).unwrap(), cargo_before);
+        assert!(!temp_dir.path().join(CHANGELOG).exists());
+        assert_eq!(store.log().len(), commits_before);
+        assert_eq!(store.list_tags().unwrap(), vec!["v0.1.0"]);
+        assert!(!store.branch_exists("release/0.2"));
+    }
+
+    #[test]
+    fn test_declining_commit_rolls_back_without_tagging() {
+        let (temp_dir, store) = fixture_repo();
+        let cargo_before = fs::read_to_string(temp_dir.path().join("Cargo.toml")).unwrap();
+        let plan = plan_release(&store, None, false).unwrap();
+
+        let mut answers = vec![true, false].into_iter();
+        let result = execute(&store, &plan, author(), &mut |_| Ok(answers.next().unwrap()));
+        assert!(result.is_err());
+
+        assert_eq!(fs::read_to_string(temp_dir.path().join("Cargo.toml")).unwrap(), cargo_before);
+        assert!(!temp_dir.path().join(CHANGELOG).exists());
+        assert!(!store.tag_exists("v0.2.0"));
+        assert!(store.read_index().unwrap().entries.is_empty());
+    }
+
+    #[test]
+    fn test_version_file_formats() {
+        let json = VersionFile {
+            path: "package.json".into(),
+            kind: VersionFileKind::PackageJson,
+            raw: "1.2.3".into(),
+            version: "1.2.3".parse().unwrap(),
+        };
+        let updated = json
+            .with_version("{\n  \"name\": \"x\",\n  \"version\": \"1.2.3\"\n}\n", "1.3.0".parse().unwrap())
+            .unwrap();
+        assert_eq!(updated, "{\n  \"name\": \"x\",\n  \"version\": \"1.3.0\"\n}\n");
+
+        assert_eq!("v0.3.0-alpha.4".parse::<Version>().unwrap().to_string(), "0.3.0");
+        assert_eq!(prepend_changelog(Some("# Changelog\n\n## [0.1.0]\n- a\n"), "## [0.2.0]\n- b\n"),
+            "# Changelog\n\n## [0.2.0]\n- b\n\n## [0.1.0]\n- a\n");
+    }
+}
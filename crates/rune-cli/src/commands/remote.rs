@@ -1,7 +1,10 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::{Args, Subcommand};
-use rune_remote::{RemoteCommands, RemoteManager};
+use rune_remote::{HttpSyncClient, RemoteCommands, RemoteConfig, RemoteManager, SyncClient};
+use rune_security::pinning::CertPin;
+use rune_store::{PruneReport, Store};
 use std::path::Path;
+use crate::exit_codes::{CliError, ErrorKind};
 
 #[derive(Debug, Args)]
 pub struct RemoteArgs {
@@ -46,6 +49,17 @@ pub enum RemoteCommand {
         /// Authentication token
         token: String,
     },
+    /// Pin the certificates a remote's server may present
+    Pin {
+        /// Remote name
+        name: String,
+        /// Certificate fingerprints (sha256:<hex>)
+        #[arg(required_unless_present = "clear")]
+        fingerprints: Vec<String>,
+        /// Remove all pins from the remote
+        #[arg(long, conflicts_with = "fingerprints")]
+        clear: bool,
+    },
     /// Test remote connection
     Test {
         /// Remote name (tests all if not specified)
@@ -64,6 +78,17 @@ pub enum RemoteCommand {
         #[arg(long)]
         dry_run: bool,
     },
+    /// Set whether fetches from a remote prune stale tracking branches
+    SetPrune {
+        /// Remote name
+        name: String,
+        /// `true` or `false`
+        #[arg(required_unless_present = "unset")]
+        value: Option<bool>,
+        /// Fall back to the global fetch.prune setting
+        #[arg(long, conflicts_with = "value")]
+        unset: bool,
+    },
     /// Update all remotes
     Update {
         /// Prune stale branches during update
@@ -77,7 +102,8 @@ pub async fn handle_remote_command(args: RemoteArgs) -> Result<()> {
     
     // Check if we're in a Rune repository
     if !current_dir.join(".rune").exists() {
-        anyhow::bail!("Not in a Rune repository. Use 'rune init' to create one.");
+        let message = "Not in a Rune repository. Use 'rune init' to create one.";
+        return Err(CliError::new(ErrorKind::NotARepo, message).into());
     }
     
     match args.command {
@@ -96,6 +122,17 @@ pub async fn handle_remote_command(args: RemoteArgs) -> Result<()> {
         RemoteCommand::SetAuth { name, token } => {
             RemoteCommands::set_auth(&current_dir, &name, &token)?;
         }
+        RemoteCommand::Pin { name, fingerprints, clear } => {
+            let mut manager = RemoteManager::new(&current_dir)?;
+            if clear {
+                manager.set_remote_pins(&name, Vec::new())?;
+                println!("✓ Removed certificate pins from '{}'", name);
+            } else {
+                let pins: Vec<String> = CertPin::parse_all(&fingerprints)?.iter().map(|p| p.to_string()).collect();
+                manager.set_remote_pins(&name, pins)?;
+                println!("✓ Pinned {} certificate(s) for '{}'", fingerprints.len(), name);
+            }
+        }
         RemoteCommand::Test { name } => {
             RemoteCommands::test(&current_dir, name.as_deref()).await?;
         }
@@ -103,7 +140,16 @@ pub async fn handle_remote_command(args: RemoteArgs) -> Result<()> {
             show_remote_info(&current_dir, &name)?;
         }
         RemoteCommand::Prune { name, dry_run } => {
-            prune_remote_branches(&current_dir, &name, dry_run)?;
+            prune_remote_branches(&current_dir, &name, dry_run).await?;
+        }
+        RemoteCommand::SetPrune { name, value, unset } => {
+            let mut manager = RemoteManager::new(&current_dir)?;
+            let value = if unset { None } else { value };
+            manager.set_remote_prune(&name, value)?;
+            match value {
+                Some(prune) => println!("✓ Set prune for '{}' to {}", name, prune),
+                None => println!("✓ '{}' now follows fetch.prune", name),
+            }
         }
         RemoteCommand::Update { prune } => {
             update_all_remotes(&current_dir, prune).await?;
@@ -113,38 +159,55 @@ pub async fn handle_remote_command(args: RemoteArgs) -> Result<()> {
     Ok(())
 }
 
-fn prune_remote_branches(repo_path: &Path, remote_name: &str, dry_run: bool) -> Result<()> {
-    let manager = RemoteManager::new(repo_path)?;
-    
-    if manager.get_remote(remote_name).is_none() {
-        anyhow::bail!("Remote '{}' not found", remote_name);
+/// `origin/main` as the tracking ref `refs/remotes/origin/main`; full ref
+/// names pass through
+pub fn upstream_ref(upstream: &str) -> String {
+    if upstream.starts_with("refs/") {
+        upstream.to_string()
+    } else {
+        format!("refs/remotes/{}", upstream)
     }
+}
 
-    // For demonstration - in a real implementation, this would identify stale branches
-    let stale_branches = vec![
-        format!("refs/remotes/{}/feature/old-branch", remote_name),
-        format!("refs/remotes/{}/hotfix/closed-issue", remote_name),
-    ];
+/// Whether fetches from `remote` prune: its own `prune` setting, else `fetch.prune`
+pub fn prune_enabled(store: &Store, remote: &RemoteConfig) -> bool {
+    remote.prune.unwrap_or_else(|| store.config().fetch.prune)
+}
 
-    if stale_branches.is_empty() {
-        println!("No stale remote-tracking branches found for '{}'", remote_name);
-        return Ok(());
-    }
+/// Remove the tracking refs of `remote` for branches `client` no longer lists
+pub async fn prune_remote(store: &Store, remote: &str, dry_run: bool, client: &impl SyncClient) -> Result<PruneReport> {
+    let branches = client
+        .branches()
+        .await
+        .with_context(|| format!("Failed to list the branches of '{}'", remote))?;
+    store.prune_remote_refs(remote, &branches, dry_run)
+}
 
-    if dry_run {
-        println!("Would prune {} stale remote-tracking branches:", stale_branches.len());
-        for branch in &stale_branches {
-            println!("  - {}", branch);
-        }
-    } else {
-        println!("Pruning {} stale remote-tracking branches:", stale_branches.len());
-        for branch in &stale_branches {
-            println!("  - {}", branch);
-            // In real implementation: remove the branch reference
-        }
-        println!("Pruned {} stale branches from '{}'", stale_branches.len(), remote_name);
+pub fn print_prune_report(remote: &str, report: &PruneReport, dry_run: bool) {
+    if report.is_empty() {
+        println!("No stale remote-tracking branches found for '{}'", remote);
+        return;
+    }
+    let verb = if dry_run { "Would prune" } else { "Pruned" };
+    println!("{} {} stale remote-tracking branch(es) from '{}':", verb, report.pruned.len(), remote);
+    for tracking in &report.pruned {
+        println!("  - {}", tracking);
+    }
+    for (branch, upstream) in &report.upstreams_cleared {
+        let verb = if dry_run { "would lose" } else { "no longer has" };
+        println!("  Branch '{}' {} its upstream {}", branch, verb, upstream);
     }
+}
 
+async fn prune_remote_branches(repo_path: &Path, remote_name: &str, dry_run: bool) -> Result<()> {
+    let manager = RemoteManager::new(repo_path)?;
+    let remote = manager
+        .get_remote(remote_name)
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("Remote '{}' not found", remote_name))?;
+    let store = Store::open(repo_path)?;
+    let report = prune_remote(&store, remote_name, dry_run, &HttpSyncClient::new(remote)).await?;
+    print_prune_report(remote_name, &report, dry_run);
     Ok(())
 }
 
@@ -157,6 +220,7 @@ async fn update_all_remotes(repo_path: &Path, prune: bool) -> Result<()> {
         return Ok(());
     }
 
+    let store = Store::open(repo_path)?;
     println!("Updating {} remotes...", remotes.len());
     
     for remote in &remotes {
@@ -165,9 +229,9 @@ async fn update_all_remotes(repo_path: &Path, prune: bool) -> Result<()> {
         // For demonstration - in real implementation, this would fetch from each remote
         println!("  Fetching refs from {}", remote.url);
         
-        if prune {
+        if prune || prune_enabled(&store, remote) {
             println!("  Pruning stale branches for '{}'...", remote.name);
-            prune_remote_branches(repo_path, &remote.name, false)?;
+            prune_remote_branches(repo_path, &remote.name, false).await?;
         }
         
         println!("  ✓ Updated '{}'", remote.name);
@@ -189,6 +253,15 @@ fn show_remote_info(repo_path: &Path, name: &str) -> Result<()> {
             }
             println!("  Default: {}", if remote.default { "yes" } else { "no" });
             println!("  Authentication: {}", if remote.token.is_some() { "configured" } else { "none" });
+            if let Some(prune) = remote.prune {
+                println!("  Prune on fetch: {}", if prune { "yes" } else { "no" });
+            }
+            if !remote.pinned_certs.is_empty() {
+                println!("  Pinned certificates:");
+                for pin in &remote.pinned_certs {
+                    println!("    {}", pin);
+                }
+            }
             println!("  Fetch refs:");
             for ref_spec in &remote.fetch_refs {
                 println!("    {}", ref_spec);
@@ -199,9 +272,72 @@ fn show_remote_info(repo_path: &Path, name: &str) -> Result<()> {
             }
         }
         None => {
-            anyhow::bail!("Remote '{}' not found", name);
+            return Err(CliError::new(ErrorKind::Usage, format!("Remote '{}' not found", name)).into());
         }
     }
     
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support::temp_repo;
+    use rune_remote::{PushRequest, SyncResponse};
+    use std::cell::RefCell;
+
+    /// A remote whose branch list the test changes between fetches
+    struct FakeRemote {
+        branches: RefCell<Vec<String>>,
+    }
+
+    impl SyncClient for FakeRemote {
+        async fn push(&self, _request: &PushRequest) -> Result<SyncResponse> {
+            unreachable!("pruning never pushes")
+        }
+
+        async fn branches(&self) -> Result<Vec<String>> {
+            Ok(self.branches.borrow().clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_branch_deleted_remotely_is_pruned() {
+        let (_temp, store) = temp_repo();
+        let remote = FakeRemote { branches: RefCell::new(vec!["main".into(), "feature".into()]) };
+        for branch in remote.branches.borrow().iter() {
+            store.write_ref(&format!("refs/remotes/origin/{}", branch), "abc123").unwrap();
+        }
+        store.set_branch_upstream("feature", Some(&upstream_ref("origin/feature"))).unwrap();
+
+        // Nothing is gone yet
+        assert!(prune_remote(&store, "origin", false, &remote).await.unwrap().is_empty());
+
+        remote.branches.borrow_mut().retain(|b| b != "feature");
+        let preview = prune_remote(&store, "origin", true, &remote).await.unwrap();
+        assert_eq!(preview.pruned, ["refs/remotes/origin/feature"]);
+        assert!(store.read_ref("refs/remotes/origin/feature").is_some());
+        assert!(store.branch_upstream("feature").is_some());
+
+        let report = prune_remote(&store, "origin", false, &remote).await.unwrap();
+        assert_eq!(report, preview);
+        assert!(store.read_ref("refs/remotes/origin/feature").is_none());
+        assert!(store.branch_upstream("feature").is_none());
+        assert_eq!(store.read_ref("refs/remotes/origin/main").as_deref(), Some("abc123"));
+    }
+
+    #[test]
+    fn test_remote_prune_setting_overrides_fetch_prune() {
+        let (_temp, store) = temp_repo();
+        let mut remote = RemoteConfig::default();
+        assert!(!prune_enabled(&store, &remote));
+
+        let mut cfg = store.config();
+        cfg.fetch.prune = true;
+        store.write_config(&cfg).unwrap();
+        assert!(prune_enabled(&store, &remote));
+
+        remote.prune = Some(false);
+        assert!(!prune_enabled(&store, &remote));
+    }
+}
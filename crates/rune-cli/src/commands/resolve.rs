@@ -0,0 +1,521 @@
+//! `rune conflicts --interactive`: resolve a paused merge one hunk at a time
+//!
+//! `parse` splits a conflicted file into plain text and conflict hunks, and
+//! `Resolution` records a choice per hunk and renders the file, keeping the
+//! markers of hunks that were skipped. Only `run` and `resolve_file` touch
+//! the terminal or the editor, so the choices can be tested without either.
+//! A file whose markers don't parse (nested, unclosed or stray) is opened in
+//! the editor whole instead.
+
+use crate::style::Style;
+use anyhow::{Context, Result};
+use colored::Colorize;
+use rune_store::{RepoOperation, Store};
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+/// Lines of unchanged text shown around a hunk
+const CONTEXT_LINES: usize = 3;
+
+/// One conflict region. Lines keep their line endings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    pub ours_label: String,
+    pub theirs_label: String,
+    pub ours: Vec<String>,
+    /// The common ancestor's lines, present in diff3-style markers
+    pub base: Option<Vec<String>>,
+    pub theirs: Vec<String>,
+    /// The region as it appears in the file, markers included
+    raw: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    Text(Vec<String>),
+    Conflict(Hunk),
+}
+
+/// What to put in place of a hunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Choice {
+    Ours,
+    Theirs,
+    /// Ours followed by theirs
+    Both,
+    Edited(Vec<String>),
+}
+
+/// Markers that don't form well-nested conflict regions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Malformed {
+    /// 1-based
+    pub line: usize,
+    pub reason: &'static str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Marker {
+    Start,
+    Base,
+    Middle,
+    End,
+}
+
+fn marker(line: &str) -> Option<Marker> {
+    let line = line.trim_end_matches(['\n', '\r']);
+    let labelled = |prefix: &str| {
+        line.strip_prefix(prefix).is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+    };
+    if labelled("<<<<<<<") {
+        Some(Marker::Start)
+    } else if labelled("|||||||") {
+        Some(Marker::Base)
+    } else if line == "=======" {
+        Some(Marker::Middle)
+    } else if labelled(">>>>>>>") {
+        Some(Marker::End)
+    } else {
+        None
+    }
+}
+
+/// The label after a marker, e.g. `HEAD` in `<<<<<<< HEAD`
+fn label(line: &str) -> String {
+    line.trim_end_matches(['\n', '\r']).get(7..).unwrap_or_default().trim().to_string()
+}
+
+/// Split `text` into plain text and conflict hunks
+pub fn parse(text: &str) -> Result<Vec<Segment>, Malformed> {
+    #[derive(PartialEq)]
+    enum Side {
+        Ours,
+        Base,
+        Theirs,
+    }
+    let mut segments = Vec::new();
+    let mut plain: Vec<String> = Vec::new();
+    let mut open: Option<(Side, Hunk)> = None;
+    for (i, line) in text.split_inclusive('\n').enumerate() {
+        let malformed = |reason| Malformed { line: i + 1, reason };
+        let Some((side, hunk)) = open.as_mut() else {
+            match marker(line) {
+                Some(Marker::Start) => {
+                    if !plain.is_empty() {
+                        segments.push(Segment::Text(std::mem::take(&mut plain)));
+                    }
+                    let hunk = Hunk {
+                        ours_label: label(line),
+                        theirs_label: String::new(),
+                        ours: Vec::new(),
+                        base: None,
+                        theirs: Vec::new(),
+                        raw: vec![line.to_string()],
+                    };
+                    open = Some((Side::Ours, hunk));
+                }
+                Some(_) => return Err(malformed("conflict marker outside a conflict")),
+                None => plain.push(line.to_string()),
+            }
+            continue;
+        };
+        hunk.raw.push(line.to_string());
+        match (marker(line), &side) {
+            (Some(Marker::Start), _) => return Err(malformed("conflict starts inside another one")),
+            (Some(Marker::Base), Side::Ours) => {
+                *side = Side::Base;
+                hunk.base = Some(Vec::new());
+            }
+            (Some(Marker::Middle), Side::Ours | Side::Base) => *side = Side::Theirs,
+            (Some(Marker::End), Side::Theirs) => {
+                hunk.theirs_label = label(line);
+                let (_, hunk) = open.take().expect("a conflict is open");
+                segments.push(Segment::Conflict(hunk));
+            }
+            (Some(_), _) => return Err(malformed("conflict marker out of order")),
+            (None, Side::Ours) => hunk.ours.push(line.to_string()),
+            (None, Side::Base) => hunk.base.get_or_insert_with(Vec::new).push(line.to_string()),
+            (None, Side::Theirs) => hunk.theirs.push(line.to_string()),
+        }
+    }
+    if open.is_some() {
+        return Err(Malformed { line: text.split_inclusive('\n').count(), reason: "conflict is never closed" });
+    }
+    if !plain.is_empty() {
+        segments.push(Segment::Text(plain));
+    }
+    Ok(segments)
+}
+
+/// Choices made so far for one file's hunks, in order
+#[derive(Debug, Clone)]
+pub struct Resolution {
+    segments: Vec<Segment>,
+    /// Index in `segments` of each hunk
+    hunks: Vec<usize>,
+    choices: Vec<Option<Choice>>,
+    /// The hunk being decided; `hunks.len()` once every hunk was visited
+    cursor: usize,
+}
+
+impl Resolution {
+    pub fn new(segments: Vec<Segment>) -> Self {
+        let hunks: Vec<usize> = segments
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| matches!(s, Segment::Conflict(_)))
+            .map(|(i, _)| i)
+            .collect();
+        let choices = vec![None; hunks.len()];
+        Self { segments, hunks, choices, cursor: 0 }
+    }
+
+    pub fn hunk_count(&self) -> usize {
+        self.hunks.len()
+    }
+
+    /// Position and content of the hunk waiting for a choice
+    pub fn current(&self) -> Option<(usize, &Hunk)> {
+        let segment = self.hunks.get(self.cursor)?;
+        match &self.segments[*segment] {
+            Segment::Conflict(hunk) => Some((self.cursor, hunk)),
+            Segment::Text(_) => unreachable!("hunks only index conflicts"),
+        }
+    }
+
+    /// Settle the current hunk and move to the next
+    pub fn choose(&mut self, choice: Choice) {
+        if let Some(slot) = self.choices.get_mut(self.cursor) {
+            *slot = Some(choice);
+            self.cursor += 1;
+        }
+    }
+
+    /// Leave the current hunk conflicted and move to the next
+    pub fn skip(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.hunks.len());
+    }
+
+    /// Hunks still without a choice
+    pub fn unresolved(&self) -> usize {
+        self.choices.iter().filter(|c| c.is_none()).count()
+    }
+
+    /// Up to `n` lines of text before and after the current hunk
+    pub fn context(&self, n: usize) -> (&[String], &[String]) {
+        let Some(&segment) = self.hunks.get(self.cursor) else {
+            return (&[], &[]);
+        };
+        let text = |i: Option<usize>| match i.and_then(|i| self.segments.get(i)) {
+            Some(Segment::Text(lines)) => lines.as_slice(),
+            _ => &[],
+        };
+        let before = text(segment.checked_sub(1));
+        let after = text(Some(segment + 1));
+        (&before[before.len().saturating_sub(n)..], &after[..after.len().min(n)])
+    }
+
+    /// The file with every chosen hunk replaced and the others as they were
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let mut hunk = 0;
+        for segment in &self.segments {
+            let lines: Vec<&String> = match segment {
+                Segment::Text(lines) => lines.iter().collect(),
+                Segment::Conflict(h) => {
+                    let choice = &self.choices[hunk];
+                    hunk += 1;
+                    match choice {
+                        None => h.raw.iter().collect(),
+                        Some(Choice::Ours) => h.ours.iter().collect(),
+                        Some(Choice::Theirs) => h.theirs.iter().collect(),
+                        Some(Choice::Both) => h.ours.iter().chain(&h.theirs).collect(),
+                        Some(Choice::Edited(lines)) => lines.iter().collect(),
+                    }
+                }
+            };
+            for line in lines {
+                out.push_str(line);
+            }
+        }
+        out
+    }
+}
+
+/// Walk every unresolved file of the merge or revert in progress
+pub fn run(input: &mut impl BufRead) -> Result<()> {
+    let store = Store::discover(std::env::current_dir()?)?;
+    let conflicts = store.unresolved_conflicts()?;
+    if conflicts.is_empty() {
+        Style::info("No conflicts to resolve");
+        return Ok(());
+    }
+    let editor = editor();
+    let mut resolved = Vec::new();
+    for path in &conflicts {
+        if resolve_file(&store, path, input, &editor)? {
+            resolved.push(path);
+        }
+    }
+
+    println!();
+    Style::info(&format!("{} of {} conflicted files resolved", resolved.len(), conflicts.len()));
+    let remaining = store.unresolved_conflicts()?;
+    for path in &remaining {
+        println!("  still conflicted: {}", Style::file_path(path));
+    }
+    if remaining.is_empty() {
+        match store.operation_in_progress()? {
+            Some(RepoOperation::Revert { .. }) => Style::info("Complete the revert with: rune revert --continue"),
+            _ => Style::info("Complete the merge with: rune merge --continue"),
+        }
+    }
+    Ok(())
+}
+
+/// Ask about each hunk of `path`, write the result and mark the file
+/// resolved when no hunk is left. Returns whether it was.
+pub fn resolve_file(store: &Store, path: &str, input: &mut impl BufRead, editor: &str) -> Result<bool> {
+    let full = store.root.join(path);
+    let text = String::from_utf8_lossy(&std::fs::read(&full)?).into_owned();
+    let segments = match parse(&text) {
+        Ok(segments) => segments,
+        Err(malformed) => {
+            Style::warning(&format!(
+                "{}: {} at line {}; opening the whole file in the editor",
+                path, malformed.reason, malformed.line
+            ));
+            return edit_whole_file(store, path, editor);
+        }
+    };
+    let mut resolution = Resolution::new(segments);
+    if resolution.hunk_count() == 0 {
+        // Markers already gone, e.g. fixed by hand
+        store.mark_resolved(path)?;
+        return Ok(true);
+    }
+
+    while let Some((i, hunk)) = resolution.current() {
+        show_hunk(path, i, resolution.hunk_count(), hunk, resolution.context(CONTEXT_LINES));
+        let hunk = hunk.clone();
+        loop {
+            print!("(o)urs, (t)heirs, (b)oth, (e)dit, (s)kip? ");
+            std::io::stdout().flush()?;
+            let mut answer = String::new();
+            if input.read_line(&mut answer)? == 0 {
+                // Out of input: leave the rest for later
+                while resolution.current().is_some() {
+                    resolution.skip();
+                }
+                break;
+            }
+            match answer.trim() {
+                "o" | "ours" => resolution.choose(Choice::Ours),
+                "t" | "theirs" => resolution.choose(Choice::Theirs),
+                "b" | "both" => resolution.choose(Choice::Both),
+                "e" | "edit" => match edit_hunk(store, &hunk, editor)? {
+                    Some(lines) => resolution.choose(Choice::Edited(lines)),
+                    None => {
+                        Style::warning("The edit still has conflict markers; choose again");
+                        continue;
+                    }
+                },
+                "s" | "skip" => resolution.skip(),
+                _ => continue,
+            }
+            break;
+        }
+    }
+
+    std::fs::write(&full, resolution.render())?;
+    let left = resolution.unresolved();
+    if left > 0 {
+        Style::warning(&format!("{}: {} of {} conflicts left", path, left, resolution.hunk_count()));
+        return Ok(false);
+    }
+    store.mark_resolved(path)?;
+    Style::success(&format!("{} resolved", path));
+    Ok(true)
+}
+
+fn show_hunk(path: &str, i: usize, count: usize, hunk: &Hunk, (before, after): (&[String], &[String])) {
+    println!("\n{} conflict {} of {}", Style::file_path(path), i + 1, count);
+    for line in before {
+        print!("  {}", line.dimmed());
+    }
+    println!("{}", format!("<<<<<<< {}", hunk.ours_label).green());
+    for line in &hunk.ours {
+        print!("{}", line.green());
+    }
+    if let Some(base) = &hunk.base {
+        println!("{}", "||||||| base".dimmed());
+        for line in base {
+            print!("{}", line.dimmed());
+        }
+    }
+    println!("=======");
+    for line in &hunk.theirs {
+        print!("{}", line.red());
+    }
+    println!("{}", format!(">>>>>>> {}", hunk.theirs_label).red());
+    for line in after {
+        print!("  {}", line.dimmed());
+    }
+}
+
+/// Let the editor rewrite one hunk; `None` when markers are left in it
+fn edit_hunk(store: &Store, hunk: &Hunk, editor: &str) -> Result<Option<Vec<String>>> {
+    let file = store.rune_dir.join("CONFLICT_EDIT");
+    std::fs::write(&file, hunk.raw.concat())?;
+    run_editor(editor, &file)?;
+    let edited = std::fs::read_to_string(&file)?;
+    std::fs::remove_file(&file)?;
+    if edited.split_inclusive('\n').any(|line| marker(line).is_some()) {
+        return Ok(None);
+    }
+    Ok(Some(edited.split_inclusive('\n').map(str::to_string).collect()))
+}
+
+/// Editor mode for a file whose markers don't parse: resolved once the
+/// editor leaves it without any markers
+fn edit_whole_file(store: &Store, path: &str, editor: &str) -> Result<bool> {
+    run_editor(editor, &store.root.join(path))?;
+    let text = String::from_utf8_lossy(&std::fs::read(store.root.join(path))?).into_owned();
+    if text.split_inclusive('\n').any(|line| marker(line).is_some()) {
+        Style::warning(&format!("{} still has conflict markers", path));
+        return Ok(false);
+    }
+    store.mark_resolved(path)?;
+    Style::success(&format!("{} resolved", path));
+    Ok(true)
+}
+
+/// `$VISUAL`, else `$EDITOR`, else vi
+fn editor() -> String {
+    std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string())
+}
+
+fn run_editor(editor: &str, file: &Path) -> Result<()> {
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or("vi");
+    let status = std::process::Command::new(program)
+        .args(parts)
+        .arg(file)
+        .status()
+        .with_context(|| format!("Failed to start editor '{}'", editor))?;
+    if !status.success() {
+        anyhow::bail!("Editor '{}' exited with {}", editor, status);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support::{author, temp_repo};
+    use std::fs;
+    use tempfile::TempDir;
+
+    const TWO_CONFLICTS: &str = "intro\n\
+        <<<<<<< HEAD\n\
+        ours one\n\
+        =======\n\
+        theirs one\n\
+        >>>>>>> feature\n\
+        middle\n\
+        <<<<<<< HEAD\n\
+        ours two\n\
+        ||||||| base\n\
+        base two\n\
+        =======\n\
+        theirs two\n\
+        >>>>>>> feature\n\
+        outro\n";
+
+    /// `main` and `feature` both change `song.txt` from the same base
+    fn conflicted_repo() -> (TempDir, Store) {
+        let (temp_dir, store) = temp_repo();
+        let commit = |content: &str, msg: &str| {
+            fs::write(temp_dir.path().join("song.txt"), content).unwrap();
+            store.stage_file("song.txt").unwrap();
+            store.commit(msg, author()).unwrap();
+        };
+        commit("verse\n", "base");
+        store.create_branch("feature").unwrap();
+        commit("verse\nours\n", "ours");
+        store.checkout_branch("feature").unwrap();
+        commit("verse\ntheirs\n", "theirs");
+        store.checkout_branch("main").unwrap();
+        fs::write(temp_dir.path().join("song.txt"), "verse\nours\n").unwrap();
+        store.merge_branch("feature", false, None).unwrap();
+        (temp_dir, store)
+    }
+
+    #[test]
+    fn test_two_conflicts_resolved_ours_then_theirs() {
+        let mut resolution = Resolution::new(parse(TWO_CONFLICTS).unwrap());
+        assert_eq!(resolution.hunk_count(), 2);
+        let (_, first) = resolution.current().unwrap();
+        assert_eq!((first.ours_label.as_str(), first.theirs_label.as_str()), ("HEAD", "feature"));
+        assert_eq!(resolution.context(3), (&["intro\n".to_string()][..], &["middle\n".to_string()][..]));
+
+        resolution.choose(Choice::Ours);
+        let (i, second) = resolution.current().unwrap();
+        assert_eq!((i, second.base.clone()), (1, Some(vec!["base two\n".to_string()])));
+        resolution.choose(Choice::Theirs);
+        assert!(resolution.current().is_none());
+        assert_eq!(resolution.unresolved(), 0);
+        assert_eq!(resolution.render(), "intro\nours one\nmiddle\ntheirs two\noutro\n");
+
+        // Skipping keeps that hunk's markers; both keeps ours first
+        let mut resolution = Resolution::new(parse(TWO_CONFLICTS).unwrap());
+        resolution.skip();
+        resolution.choose(Choice::Both);
+        assert_eq!(resolution.unresolved(), 1);
+        let rendered = resolution.render();
+        assert!(rendered.starts_with("intro\n<<<<<<< HEAD\nours one\n=======\n"), "{}", rendered);
+        assert!(rendered.ends_with("middle\nours two\ntheirs two\noutro\n"), "{}", rendered);
+    }
+
+    #[test]
+    fn test_malformed_markers_are_reported() {
+        let nested = "<<<<<<< HEAD\na\n<<<<<<< HEAD\nb\n=======\nc\n>>>>>>> x\n";
+        assert_eq!(parse(nested).unwrap_err(), Malformed { line: 3, reason: "conflict starts inside another one" });
+        assert_eq!(parse("a\n=======\nb\n").unwrap_err().line, 2);
+        assert_eq!(parse("<<<<<<< HEAD\na\n=======\nb\n").unwrap_err().reason, "conflict is never closed");
+        // Look-alikes with more characters are content
+        assert_eq!(parse("<<<<<<<<\n========\n").unwrap().len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_edit_round_trips_through_the_editor() {
+        let (temp_dir, store) = conflicted_repo();
+        let script = temp_dir.path().join("fake-editor.sh");
+        fs::write(&script, "#!/bin/sh\nsed -e '/^[<=>|]\\{7\\}/d' \"$1\" > \"$1.new\" && mv \"$1.new\" \"$1\"\n").unwrap();
+        let editor = format!("sh {}", script.display());
+
+        let mut input = std::io::Cursor::new(b"e\n".to_vec());
+        assert!(resolve_file(&store, "song.txt", &mut input, &editor).unwrap());
+        assert_eq!(fs::read_to_string(temp_dir.path().join("song.txt")).unwrap(), "verse\nours\ntheirs\n");
+        assert!(store.unresolved_conflicts().unwrap().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_malformed_file_goes_to_editor_mode() {
+        let (temp_dir, store) = conflicted_repo();
+        let song = temp_dir.path().join("song.txt");
+        fs::write(&song, "verse\n<<<<<<< HEAD\n<<<<<<< HEAD\nours\n=======\ntheirs\n>>>>>>> feature\n").unwrap();
+        let script = temp_dir.path().join("fake-editor.sh");
+        fs::write(&script, "#!/bin/sh\nprintf 'verse\\nfixed\\n' > \"$1\"\n").unwrap();
+
+        // No hunk prompts are read: the editor gets the whole file
+        let mut input = std::io::Cursor::new(Vec::new());
+        assert!(resolve_file(&store, "song.txt", &mut input, &format!("sh {}", script.display())).unwrap());
+        assert_eq!(fs::read_to_string(&song).unwrap(), "verse\nfixed\n");
+        assert!(store.unresolved_conflicts().unwrap().is_empty());
+    }
+}
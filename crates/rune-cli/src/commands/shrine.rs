@@ -1,14 +1,28 @@
 use anyhow::Result;
-use rune_remote::Shrine;
+use rune_remote::{AuthService, Permission, Shrine, ShrineHost};
+use std::path::PathBuf;
 #[derive(clap::Subcommand, Debug)]
 pub enum ShrineCmd {
     Serve {
         #[arg(long, default_value = "127.0.0.1:7420")]
         addr: String,
+        /// Host every repository under this directory at /<name>/ instead
+        /// of serving the current one
+        #[arg(long)]
+        repos_dir: Option<PathBuf>,
     },
 }
-pub async fn serve(addr: String) -> Result<()> {
+pub async fn serve(addr: String, repos_dir: Option<PathBuf>) -> Result<()> {
     let addr: std::net::SocketAddr = addr.parse()?;
+    if let Some(dir) = repos_dir {
+        std::fs::create_dir_all(&dir)?;
+        let mut auth = AuthService::new();
+        let token = auth.generate_token("admin".to_string(), vec![Permission::Admin])?;
+        let host = ShrineHost::new(dir, auth);
+        println!("🕯️  Rune shrine hosting {} at http://{}", host.repos_dir.display(), addr);
+        println!("   Admin token: {}", token);
+        return rune_remote::run_host(host, addr).await;
+    }
     let shrine = Shrine {
         root: std::env::current_dir()?,
     };
@@ -0,0 +1,151 @@
+//! `rune split`: break the last commit into several
+//!
+//! Each round shows what the original commit changed that the new commits
+//! don't have yet, in the same picker `rune work --interactive` uses, and
+//! commits the files or hunks picked. Quitting leaves the rest staged.
+
+use anyhow::Result;
+use rune_core::Author;
+use rune_delta::hunks::{apply_hunks_fuzzy, line_hunks};
+use rune_store::{SplitChange, Store, DELETED_BLOB};
+use std::collections::BTreeMap;
+
+use super::work::{self, Category, Pick, WorkFile, WorkSession};
+use crate::style::Style;
+
+/// Lines of context around each hunk
+const HUNK_CONTEXT: usize = 3;
+
+/// Split `commit` (the tip of the current branch) interactively
+pub fn run(store: &Store, commit: &str, force: bool, committer: Author) -> Result<()> {
+    work::require_terminal(
+        "rune split",
+        "Split from a terminal, or use `rune rollback --soft` and commit the pieces with `rune commit`",
+    )?;
+    let original = store.split_commit(commit, force)?;
+    let subject = original.message.lines().next().unwrap_or_default().to_string();
+    let mut parts = 0;
+    loop {
+        let remaining = store.split_remaining(&original)?;
+        if remaining.is_empty() {
+            break;
+        }
+        let files = remaining.iter().map(|change| work_file(store, change)).collect::<Result<Vec<_>>>()?;
+        let mut session = WorkSession::new(files, Some(subject.clone()));
+        let Some(message) = work::drive(store, &mut session)? else {
+            Style::info(&format!(
+                "Split stopped after {} commit(s); the rest of {} is staged. \
+                 Commit it with `rune commit`, or `rune undo` to get the original back",
+                parts,
+                &original.id[..8]
+            ));
+            return Ok(());
+        };
+        let picked = picked_blobs(store, &session, &remaining)?;
+        let part = store.commit_split_part(&original, &picked, &message, committer.clone())?;
+        parts += 1;
+        println!("{} {}", Style::commit_hash(&part.id[..8]), message);
+    }
+    store.finish_split(&original, parts)?;
+    Style::success(&format!("Split {} into {} commits", &original.id[..8], parts));
+    Ok(())
+}
+
+/// `change` as an entry of the picker, with hunks when both sides are text
+fn work_file(store: &Store, change: &SplitChange) -> Result<WorkFile> {
+    let read = |blob: &Option<String>| -> Result<Option<String>> {
+        let Some(blob) = blob else {
+            return Ok(Some(String::new()));
+        };
+        let content = store.read_blob(blob)?;
+        if rune_core::eol::looks_binary(&content) {
+            return Ok(None);
+        }
+        Ok(String::from_utf8(content).ok())
+    };
+    let category = match (&change.from, &change.to) {
+        (None, _) => Category::Added,
+        (_, None) => Category::Deleted,
+        _ => Category::Modified,
+    };
+    let (base, target) = (read(&change.from)?, read(&change.to)?);
+    let hunks = match (&base, &target) {
+        (Some(base), Some(target)) if category == Category::Modified => line_hunks(base, target, HUNK_CONTEXT),
+        _ => Vec::new(),
+    };
+    Ok(WorkFile { path: change.path.clone(), category, hunks, base: base.unwrap_or_default() })
+}
+
+/// Blob per picked path: the original's for whole files, the current
+/// content with the picked hunks applied for partly picked ones
+fn picked_blobs(store: &Store, session: &WorkSession, remaining: &[SplitChange]) -> Result<BTreeMap<String, String>> {
+    let mut picked = BTreeMap::new();
+    for (i, (file, change)) in session.files().iter().zip(remaining).enumerate() {
+        let blob = match session.pick(i) {
+            Pick::Nothing => continue,
+            Pick::All => change.to.clone().unwrap_or_else(|| DELETED_BLOB.to_string()),
+            Pick::Some => {
+                let result = apply_hunks_fuzzy(&file.base, &session.picked_hunks(i), 0);
+                if !result.is_clean() {
+                    anyhow::bail!("picked hunks of {} don't apply", file.path);
+                }
+                store.write_blob(result.content.as_bytes())?
+            }
+        };
+        picked.insert(file.path.clone(), blob);
+    }
+    Ok(picked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::explore::Key;
+    use crate::commands::test_support::{author, temp_repo};
+
+    #[test]
+    fn test_hunks_of_one_file_go_to_separate_commits() {
+        let (temp, store) = temp_repo();
+        let lines: Vec<String> = (1..=20).map(|i| format!("line {}", i)).collect();
+        std::fs::write(temp.path().join("a.txt"), lines.join("\n") + "\n").unwrap();
+        store.stage_file("a.txt").unwrap();
+        store.commit("base", author()).unwrap();
+        let mut edited = lines.clone();
+        edited[0] = "top".to_string();
+        edited[19] = "bottom".to_string();
+        std::fs::write(temp.path().join("a.txt"), edited.join("\n") + "\n").unwrap();
+        std::fs::write(temp.path().join("b.txt"), "b\n").unwrap();
+        store.stage_file("a.txt").unwrap();
+        store.stage_file("b.txt").unwrap();
+        let big = store.commit("Edit both ends and add b", author()).unwrap();
+
+        let original = store.split_commit("HEAD", false).unwrap();
+        let remaining = store.split_remaining(&original).unwrap();
+        let files = remaining.iter().map(|c| work_file(&store, c)).collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(files[0].hunks.len(), 2);
+        assert_eq!(files[1].category, Category::Added);
+
+        // First commit: the top hunk of a.txt only
+        let mut session = WorkSession::new(files, None);
+        session.handle(Key::Enter);
+        session.handle(Key::Char('y'));
+        session.handle(Key::Char('n'));
+        let picked = picked_blobs(&store, &session, &remaining).unwrap();
+        store.commit_split_part(&original, &picked, "Edit top", author()).unwrap();
+        let first = String::from_utf8(store.read_blob(&picked["a.txt"]).unwrap()).unwrap();
+        assert!(first.starts_with("top\n") && first.ends_with("line 20\n"));
+
+        // Second commit: everything left
+        let remaining = store.split_remaining(&original).unwrap();
+        let files = remaining.iter().map(|c| work_file(&store, c)).collect::<Result<Vec<_>>>().unwrap();
+        let mut session = WorkSession::new(files, None);
+        session.handle(Key::Char('1'));
+        session.handle(Key::Char('2'));
+        let picked = picked_blobs(&store, &session, &remaining).unwrap();
+        store.commit_split_part(&original, &picked, "Edit bottom, add b", author()).unwrap();
+
+        assert!(store.split_remaining(&original).unwrap().is_empty());
+        store.finish_split(&original, 2).unwrap();
+        assert_eq!(store.tree_hash(&store.head_commit().unwrap()), store.tree_hash(&big.id));
+    }
+}
@@ -0,0 +1,135 @@
+use anyhow::Result;
+use colored::Colorize;
+use rune_store::{StatsOptions, StatsReport, Store};
+use std::fmt::Write;
+
+use crate::style::{format_size, Style};
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Widest histogram bar, in characters
+const BAR_WIDTH: usize = 30;
+
+pub fn run(options: StatsOptions, json: bool) -> Result<()> {
+    let store = Store::discover(std::env::current_dir()?)?;
+    let report = store.stats(&options)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else if report.commits == 0 {
+        Style::info("No commits in the selected time window.");
+    } else {
+        print!("{}", render(&report));
+    }
+    Ok(())
+}
+
+/// Table view of `report` as printed by `rune stats`
+pub fn render(report: &StatsReport) -> String {
+    let mut out = String::new();
+
+    let _ = writeln!(out, "{}", "Contributors".bold().underline());
+    for author in &report.authors {
+        let _ = write!(out, "  {:<24} {:>5} commits", author.name, author.commits);
+        if report.line_counts {
+            let _ = write!(
+                out,
+                "  {} {}",
+                format!("+{}", author.lines_added).green(),
+                format!("-{}", author.lines_removed).red()
+            );
+        }
+        let _ = writeln!(out);
+    }
+
+    if !report.hotspots.is_empty() {
+        let _ = writeln!(out, "\n{}", "Hotspots".bold().underline());
+        for file in &report.hotspots {
+            let _ = writeln!(out, "  {:>5}  {}", file.commits, Style::file_path(&file.path));
+        }
+    }
+
+    if !report.largest_files.is_empty() {
+        let _ = writeln!(out, "\n{}", "Largest files in HEAD".bold().underline());
+        for file in &report.largest_files {
+            let _ = writeln!(out, "  {:>10}  {}", format_size(file.size), Style::file_path(&file.path));
+        }
+    }
+
+    let _ = writeln!(out, "\n{}", "Activity by weekday (UTC)".bold().underline());
+    let busiest = report.weekdays.iter().copied().max().unwrap_or(0);
+    for (day, count) in WEEKDAYS.iter().zip(report.weekdays) {
+        let _ = writeln!(out, "  {}  {:>5} {}", day, count, bar(count, busiest));
+    }
+
+    let _ = writeln!(out, "\n{}", "Activity by hour (UTC)".bold().underline());
+    let busiest = report.hours.iter().copied().max().unwrap_or(0);
+    for (hour, count) in report.hours.iter().enumerate().filter(|(_, c)| **c > 0) {
+        let _ = writeln!(out, "  {:02}:00  {:>5} {}", hour, count, bar(*count, busiest));
+    }
+
+    let _ = writeln!(out);
+    let _ = writeln!(out, "Commits: {}", report.commits);
+    if let Some(avg) = report.avg_seconds_between_commits {
+        let _ = writeln!(out, "Average time between commits: {}", format_interval(avg));
+    }
+    if !report.line_counts {
+        let _ = writeln!(out, "{}", "Line counts skipped (--fast)".dimmed());
+    }
+    out
+}
+
+fn bar(count: usize, max: usize) -> String {
+    if max == 0 {
+        return String::new();
+    }
+    "█".repeat((count * BAR_WIDTH).div_ceil(max))
+}
+
+fn format_interval(seconds: i64) -> String {
+    match seconds {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3_600 => format!("{}m", s / 60),
+        s if s < 86_400 => format!("{}h {}m", s / 3_600, s % 3_600 / 60),
+        s => format!("{}d {}h", s / 86_400, s % 86_400 / 3_600),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rune_store::{AuthorStats, FileChurn};
+
+    #[test]
+    fn test_render_lists_authors_and_hotspots() {
+        colored::control::set_override(false);
+        let mut report = StatsReport {
+            commits: 3,
+            authors: vec![AuthorStats {
+                name: "Ada".into(),
+                email: "ada@example.com".into(),
+                commits: 3,
+                lines_added: 12,
+                lines_removed: 4,
+            }],
+            hotspots: vec![FileChurn { path: "src/main.rs".into(), commits: 3 }],
+            avg_seconds_between_commits: Some(90_000),
+            line_counts: true,
+            ..Default::default()
+        };
+        report.weekdays[0] = 3;
+        report.hours[9] = 3;
+
+        let text = render(&report);
+        assert!(text.contains("Ada"));
+        assert!(text.contains("+12 -4"));
+        assert!(text.contains("src/main.rs"));
+        assert!(text.contains("09:00"));
+        assert!(text.contains("1d 1h"));
+    }
+
+    #[test]
+    fn test_format_interval() {
+        assert_eq!(format_interval(45), "45s");
+        assert_eq!(format_interval(3_660), "1h 1m");
+    }
+}
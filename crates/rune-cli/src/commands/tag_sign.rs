@@ -0,0 +1,67 @@
+//! Keys for `rune tag create --sign` and `rune tag verify`
+//!
+//! The key lives in the PKCS#8 file `signing.key` names, relative to the
+//! repository root unless absolute. The first signed tag creates it there
+//! and lists its public key under `[signing] allowed_signers`, so tags from
+//! this repository verify here; other clones need that entry added.
+
+use crate::style::Style;
+use anyhow::Result;
+use rune_store::{Store, TagKey};
+use std::fs;
+use std::path::PathBuf;
+
+/// Where `signing.key` points, if set
+pub fn key_path(store: &Store) -> Option<PathBuf> {
+    store.config().signing.key.map(|key| store.root.join(key))
+}
+
+/// The key to sign tags with as `signer`, created on first use
+pub fn signing_key(store: &Store, signer: &str) -> Result<TagKey> {
+    let path = key_path(store).ok_or_else(|| {
+        anyhow::anyhow!("signing.key is not set; run `rune config set signing.key <path>` to choose where the signing key lives")
+    })?;
+    if path.exists() {
+        return TagKey::from_pkcs8(&fs::read(&path)?, signer);
+    }
+    let pkcs8 = TagKey::generate()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, &pkcs8)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+    }
+    let key = TagKey::from_pkcs8(&pkcs8, signer)?;
+    let mut cfg = store.config();
+    cfg.signing.allowed_signers.insert(key.public_key(), signer.to_string());
+    store.write_config(&cfg)?;
+    Style::info(&format!("Created a signing key at {} (public key {})", path.display(), key.public_key()));
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support::temp_repo;
+
+    #[test]
+    fn test_first_signature_creates_and_allows_the_key() {
+        let (temp, store) = temp_repo();
+        assert!(signing_key(&store, "Ada <ada@example.com>").is_err());
+
+        let mut cfg = store.config();
+        cfg.signing.key = Some("keys/tags.pk8".into());
+        store.write_config(&cfg).unwrap();
+        let key = signing_key(&store, "Ada <ada@example.com>").unwrap();
+        assert!(temp.path().join("keys/tags.pk8").exists());
+        assert_eq!(
+            store.config().signing.allowed_signers.get(&key.public_key()).map(String::as_str),
+            Some("Ada <ada@example.com>")
+        );
+        // Loaded, not replaced, the next time
+        assert_eq!(signing_key(&store, "Ada <ada@example.com>").unwrap().public_key(), key.public_key());
+    }
+}
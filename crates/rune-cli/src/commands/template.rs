@@ -0,0 +1,453 @@
+//! Repository templates for `rune init --template`
+//!
+//! A template is a `template.toml` describing what a new repository of some
+//! kind needs: LFS patterns, ignore templates, workspace limits, a directory
+//! skeleton and starter plans. The built-in ones are compiled in; user
+//! templates are directories holding a `template.toml`, either given by path
+//! or kept under `~/.config/rune/templates/<name>/`.
+//!
+//! Applying a template only ever adds what is missing. Anything the user has
+//! already configured is kept and reported as a warning.
+
+use anyhow::{Context, Result};
+use rune_core::ignore::IgnoreConfig;
+use rune_planning::{PlanStore, Task};
+use rune_workspace::{PerformanceLimits, WorkspaceManager};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const TEMPLATE_FILE: &str = "template.toml";
+
+const BUILTIN: &[(&str, &str)] = &[
+    (
+        "rust-lib",
+        r#"
+description = "Rust library crate"
+ignore_templates = ["rust"]
+directories = ["src", "tests", "benches", "examples"]
+
+[limits]
+max_file_size_mb = 10
+"#,
+    ),
+    (
+        "game-unity",
+        r#"
+description = "Unity game with art assets in LFS"
+lfs_patterns = ["*.psd", "*.png", "*.tga", "*.fbx", "*.wav", "*.mp3", "*.ogg", "*.mp4", "*.unitypackage"]
+ignore_templates = ["unity"]
+directories = ["Assets/Art", "Assets/Audio", "Assets/Prefabs", "Assets/Scenes", "Assets/Scripts", "Packages", "ProjectSettings"]
+
+[limits]
+max_file_size_mb = 2048
+warn_file_size_mb = 100
+max_binary_files_per_commit = 500
+
+[[plans]]
+title = "First playable"
+tags = ["milestone"]
+tasks = ["Set up the main scene", "Import placeholder art", "Player controller"]
+"#,
+    ),
+    (
+        "game-unreal",
+        r#"
+description = "Unreal Engine game with content in LFS"
+lfs_patterns = ["*.uasset", "*.umap", "*.fbx", "*.psd", "*.png", "*.tga", "*.wav", "*.bik"]
+ignore_templates = ["unreal"]
+directories = ["Config", "Content", "Source", "Plugins"]
+
+[limits]
+max_file_size_mb = 4096
+warn_file_size_mb = 200
+max_binary_files_per_commit = 1000
+
+[[plans]]
+title = "First playable"
+tags = ["milestone"]
+tasks = ["Create the default map", "Import placeholder content", "Game mode and player pawn"]
+"#,
+    ),
+    (
+        "docs-site",
+        r#"
+description = "Documentation site (Markdown sources, images in LFS)"
+lfs_patterns = ["*.png", "*.jpg", "*.gif", "*.mp4", "*.pdf"]
+ignore_templates = ["node"]
+directories = ["docs", "docs/assets", "static"]
+
+[limits]
+max_file_size_mb = 50
+"#,
+    ),
+];
+
+/// Contents of a `template.toml`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RepoTemplate {
+    pub description: String,
+    /// Written to `.runeattributes` as `<pattern> lfs`
+    pub lfs_patterns: Vec<String>,
+    /// Names of built-in ignore templates (`rust`, `node`, `unity`, ...)
+    pub ignore_templates: Vec<String>,
+    pub limits: LimitOverrides,
+    /// Directories to create, relative to the working tree
+    pub directories: Vec<String>,
+    pub plans: Vec<PlanTemplate>,
+}
+
+/// `PerformanceLimits` fields a template sets; the rest keep their defaults
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitOverrides {
+    pub max_file_size_mb: Option<u64>,
+    pub max_files_per_commit: Option<usize>,
+    pub max_binary_files_per_commit: Option<usize>,
+    pub warn_file_size_mb: Option<u64>,
+    pub blocked_extensions: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PlanTemplate {
+    pub title: String,
+    pub tags: Vec<String>,
+    pub tasks: Vec<String>,
+}
+
+/// A template as listed by `rune init --list-templates`
+#[derive(Debug, Clone)]
+pub struct TemplateInfo {
+    pub name: String,
+    pub description: String,
+    /// `None` for built-in templates
+    pub path: Option<PathBuf>,
+}
+
+/// What `apply_template` changed and what it left alone
+#[derive(Debug, Default)]
+pub struct TemplateReport {
+    pub applied: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl RepoTemplate {
+    pub fn parse(text: &str) -> Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+
+    fn from_dir(dir: &Path) -> Result<Self> {
+        let file = dir.join(TEMPLATE_FILE);
+        let text = fs::read_to_string(&file).with_context(|| format!("Failed to read {}", file.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid template {}", file.display()))
+    }
+}
+
+/// `~/.config/rune/templates`
+pub fn user_templates_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".config").join("rune").join("templates"))
+}
+
+/// Resolve `name_or_path`: a directory (or its `template.toml`) on disk,
+/// then a built-in name, then a template under `user_dir`
+pub fn find_template(name_or_path: &str, user_dir: Option<&Path>) -> Result<RepoTemplate> {
+    let path = Path::new(name_or_path);
+    if path.is_file() {
+        return RepoTemplate::from_dir(path.parent().unwrap_or(Path::new(".")));
+    }
+    if path.join(TEMPLATE_FILE).is_file() {
+        return RepoTemplate::from_dir(path);
+    }
+    if let Some((_, text)) = BUILTIN.iter().find(|(name, _)| *name == name_or_path) {
+        return RepoTemplate::parse(text);
+    }
+    if let Some(dir) = user_dir.map(|d| d.join(name_or_path)) {
+        if dir.join(TEMPLATE_FILE).is_file() {
+            return RepoTemplate::from_dir(&dir);
+        }
+    }
+    anyhow::bail!(
+        "Unknown template '{}'; see `rune init --list-templates`",
+        name_or_path
+    )
+}
+
+/// Built-in templates followed by the ones under `user_dir`, by name
+pub fn list_templates(user_dir: Option<&Path>) -> Result<Vec<TemplateInfo>> {
+    let mut templates: Vec<TemplateInfo> = BUILTIN
+        .iter()
+        .map(|(name, text)| {
+            Ok(TemplateInfo { name: name.to_string(), description: RepoTemplate::parse(text)?.description, path: None })
+        })
+        .collect::<Result<_>>()?;
+    let mut user = Vec::new();
+    if let Some(Ok(entries)) = user_dir.map(fs::read_dir) {
+        for entry in entries.flatten() {
+            let dir = entry.path();
+            if !dir.join(TEMPLATE_FILE).is_file() {
+                continue;
+            }
+            let description = match RepoTemplate::from_dir(&dir) {
+                Ok(t) => t.description,
+                Err(e) => format!("(invalid: {:#})", e),
+            };
+            user.push(TemplateInfo { name: entry.file_name().to_string_lossy().to_string(), description, path: Some(dir) });
+        }
+    }
+    user.sort_by(|a, b| a.name.cmp(&b.name));
+    templates.extend(user);
+    Ok(templates)
+}
+
+/// Apply `template` to the repository with working tree `root` and
+/// metadata directory `rune_dir`, adding only what is missing
+pub fn apply_template(template: &RepoTemplate, root: &Path, rune_dir: &Path) -> Result<TemplateReport> {
+    let mut report = TemplateReport::default();
+    apply_lfs_patterns(template, root, &mut report)?;
+    apply_ignore_templates(template, root, &mut report)?;
+    apply_limits(template, root, rune_dir, &mut report)?;
+
+    let mut created = Vec::new();
+    for dir in &template.directories {
+        let path = root.join(dir);
+        if !path.is_dir() {
+            fs::create_dir_all(&path).with_context(|| format!("Failed to create {}", path.display()))?;
+            created.push(format!("{}/", dir));
+        }
+    }
+    if !created.is_empty() {
+        report.applied.push(format!("created {}", created.join(" ")));
+    }
+
+    if !template.plans.is_empty() {
+        let plans = PlanStore::new(rune_dir);
+        let existing = plans.load_all()?;
+        for wanted in &template.plans {
+            if existing.iter().any(|p| p.title == wanted.title) {
+                report.warnings.push(format!("plan '{}' already exists; left unchanged", wanted.title));
+                continue;
+            }
+            let mut plan = rune_planning::create_plan(&plans, &wanted.title, Some(&wanted.tags.join(",")))?;
+            if !wanted.tasks.is_empty() {
+                plan.tasks = wanted
+                    .tasks
+                    .iter()
+                    .map(|t| Task { description: t.clone(), done: false, task_type: None, effort: None, path: None, tags: vec![] })
+                    .collect();
+                plans.save(&plan)?;
+            }
+            report.applied.push(format!("plan {} '{}'", plan.id, plan.title));
+        }
+    }
+    Ok(report)
+}
+
+/// Append `<pattern> lfs` to the root `.runeattributes` for patterns it
+/// doesn't mention yet
+fn apply_lfs_patterns(template: &RepoTemplate, root: &Path, report: &mut TemplateReport) -> Result<()> {
+    if template.lfs_patterns.is_empty() {
+        return Ok(());
+    }
+    let path = root.join(".runeattributes");
+    let mut content = fs::read_to_string(&path).unwrap_or_default();
+    let mut added = Vec::new();
+    for pattern in &template.lfs_patterns {
+        let existing = content
+            .lines()
+            .map(str::split_whitespace)
+            .find_map(|mut parts| (parts.next() == Some(pattern.as_str())).then(|| parts.collect::<Vec<_>>()));
+        match existing {
+            Some(attrs) if attrs.iter().any(|a| *a == "lfs" || *a == "filter=lfs") => {}
+            Some(attrs) => report.warnings.push(format!(
+                ".runeattributes already sets '{} {}'; not adding lfs",
+                pattern,
+                attrs.join(" ")
+            )),
+            None => {
+                if !content.is_empty() && !content.ends_with('\n') {
+                    content.push('\n');
+                }
+                content.push_str(&format!("{} lfs\n", pattern));
+                added.push(pattern.as_str());
+            }
+        }
+    }
+    if !added.is_empty() {
+        fs::write(&path, content)?;
+        report.applied.push(format!("LFS tracks {}", added.join(" ")));
+    }
+    Ok(())
+}
+
+fn apply_ignore_templates(template: &RepoTemplate, root: &Path, report: &mut TemplateReport) -> Result<()> {
+    if template.ignore_templates.is_empty() {
+        return Ok(());
+    }
+    let path = root.join(".runeignore.yml");
+    let mut config = if path.exists() { IgnoreConfig::load_from_file(&path)? } else { IgnoreConfig::default() };
+    let mut changed = false;
+    for name in &template.ignore_templates {
+        if config.apply_template(name)? {
+            report.applied.push(format!("ignore template {}", name));
+            changed = true;
+        } else {
+            report.warnings.push(format!("ignore template {} is already applied", name));
+        }
+    }
+    if changed {
+        config.save_to_file(&path)?;
+    }
+    Ok(())
+}
+
+/// Template limits go into a new workspace configuration; an existing one
+/// is the user's and is never modified
+fn apply_limits(template: &RepoTemplate, root: &Path, rune_dir: &Path, report: &mut TemplateReport) -> Result<()> {
+    let overrides = &template.limits;
+    if let Ok(existing) = WorkspaceManager::load(rune_dir) {
+        let limits = &existing.config.performance_limits;
+        let mut differs = |key: &str, current: String, wanted: Option<String>| {
+            if let Some(wanted) = wanted.filter(|w| *w != current) {
+                report.warnings.push(format!("keeping {} = {} (template suggests {})", key, current, wanted));
+            }
+        };
+        differs("max_file_size_mb", limits.max_file_size_mb.to_string(), overrides.max_file_size_mb.map(|v| v.to_string()));
+        differs("max_files_per_commit", limits.max_files_per_commit.to_string(), overrides.max_files_per_commit.map(|v| v.to_string()));
+        differs(
+            "max_binary_files_per_commit",
+            limits.max_binary_files_per_commit.to_string(),
+            overrides.max_binary_files_per_commit.map(|v| v.to_string()),
+        );
+        differs("warn_file_size_mb", limits.warn_file_size_mb.to_string(), overrides.warn_file_size_mb.map(|v| v.to_string()));
+        differs(
+            "blocked_extensions",
+            limits.blocked_extensions.join(","),
+            overrides.blocked_extensions.as_ref().map(|v| v.join(",")),
+        );
+        return Ok(());
+    }
+
+    let name = root.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| "default".to_string());
+    let mut manager = WorkspaceManager::new(root.to_path_buf(), rune_dir, name)?;
+    let limits: &mut PerformanceLimits = &mut manager.config.performance_limits;
+    if let Some(v) = overrides.max_file_size_mb {
+        limits.max_file_size_mb = v;
+    }
+    if let Some(v) = overrides.max_files_per_commit {
+        limits.max_files_per_commit = v;
+    }
+    if let Some(v) = overrides.max_binary_files_per_commit {
+        limits.max_binary_files_per_commit = v;
+    }
+    if let Some(v) = overrides.warn_file_size_mb {
+        limits.warn_file_size_mb = v;
+    }
+    if let Some(v) = &overrides.blocked_extensions {
+        limits.blocked_extensions = v.clone();
+    }
+    manager.save()?;
+    report.applied.push("workspace limits".to_string());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rune_lfs::Lfs;
+    use rune_store::Store;
+    use tempfile::TempDir;
+
+    fn init(temp: &TempDir, template: &str) -> TemplateReport {
+        let store = Store::open(temp.path()).unwrap();
+        store.create().unwrap();
+        let template = find_template(template, None).unwrap();
+        apply_template(&template, &store.root, &store.rune_dir).unwrap()
+    }
+
+    #[test]
+    fn test_builtin_templates_parse() {
+        let names: Vec<String> = list_templates(None).unwrap().into_iter().map(|t| t.name).collect();
+        assert_eq!(names, ["rust-lib", "game-unity", "game-unreal", "docs-site"]);
+        for name in names {
+            let template = find_template(&name, None).unwrap();
+            let mut config = IgnoreConfig::default();
+            for ignore in &template.ignore_templates {
+                assert!(config.apply_template(ignore).unwrap(), "{}: {}", name, ignore);
+            }
+        }
+        assert!(find_template("no-such-template", None).is_err());
+    }
+
+    #[test]
+    fn test_unity_template_configures_repo() {
+        let temp = TempDir::new().unwrap();
+        let report = init(&temp, "game-unity");
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+
+        let lfs = Lfs::open(temp.path(), temp.path().join(".rune")).unwrap();
+        assert!(lfs.is_tracked("Assets/Art/hero.psd").unwrap());
+        assert!(!lfs.is_tracked("Assets/Scripts/Player.cs").unwrap());
+
+        let ignore = IgnoreConfig::load_from_file(temp.path().join(".runeignore.yml")).unwrap();
+        assert_eq!(ignore.templates, ["unity"]);
+        assert!(ignore.project.iter().any(|r| r.pattern == "Library/"));
+
+        for dir in ["Assets/Art", "Assets/Scenes", "ProjectSettings"] {
+            assert!(temp.path().join(dir).is_dir(), "{}", dir);
+        }
+        let workspace = WorkspaceManager::load(&temp.path().join(".rune")).unwrap();
+        assert_eq!(workspace.config.performance_limits.max_file_size_mb, 2048);
+        let plans = PlanStore::new(temp.path().join(".rune")).load_all().unwrap();
+        assert_eq!(plans.len(), 1);
+        assert_eq!(plans[0].tasks.len(), 3);
+    }
+
+    #[test]
+    fn test_reinit_keeps_user_edits() {
+        let temp = TempDir::new().unwrap();
+        init(&temp, "game-unity");
+        let rune_dir = temp.path().join(".rune");
+        let mut workspace = WorkspaceManager::load(&rune_dir).unwrap();
+        workspace.config.performance_limits.max_file_size_mb = 64;
+        workspace.save().unwrap();
+        let attributes = temp.path().join(".runeattributes");
+        let edited = fs::read_to_string(&attributes).unwrap().replace("*.png lfs", "*.png -lfs");
+        fs::write(&attributes, &edited).unwrap();
+
+        let report = init(&temp, "game-unity");
+        assert!(report.applied.is_empty(), "{:?}", report.applied);
+        assert!(report.warnings.iter().any(|w| w.contains("max_file_size_mb = 64")));
+        assert!(report.warnings.iter().any(|w| w.contains("*.png -lfs")));
+        assert!(report.warnings.iter().any(|w| w.contains("First playable")));
+        assert_eq!(WorkspaceManager::load(&rune_dir).unwrap().config.performance_limits.max_file_size_mb, 64);
+        assert_eq!(fs::read_to_string(&attributes).unwrap(), edited);
+        assert_eq!(PlanStore::new(&rune_dir).load_all().unwrap().len(), 1);
+        let ignore = IgnoreConfig::load_from_file(temp.path().join(".runeignore.yml")).unwrap();
+        assert_eq!(ignore.project.iter().filter(|r| r.pattern == "Library/").count(), 1);
+    }
+
+    #[test]
+    fn test_user_template_from_directory() {
+        let templates = TempDir::new().unwrap();
+        let dir = templates.path().join("studio");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join(TEMPLATE_FILE),
+            "description = \"Studio layout\"\nlfs_patterns = [\"*.blend\"]\ndirectories = [\"art/src\"]\n",
+        )
+        .unwrap();
+        fs::create_dir_all(templates.path().join("not-a-template")).unwrap();
+
+        let listed = list_templates(Some(templates.path())).unwrap();
+        let studio = listed.last().unwrap();
+        assert_eq!((studio.name.as_str(), studio.description.as_str()), ("studio", "Studio layout"));
+        assert_eq!(listed.len(), BUILTIN.len() + 1);
+
+        let by_name = find_template("studio", Some(templates.path())).unwrap();
+        let by_path = find_template(dir.to_str().unwrap(), None).unwrap();
+        assert_eq!(by_name.lfs_patterns, by_path.lfs_patterns);
+        assert!(RepoTemplate::parse("lfs = [\"*.psd\"]").is_err());
+    }
+}
@@ -0,0 +1,56 @@
+//! `rune transfer`: pushes that stopped partway and can be resumed
+
+use crate::style::Style;
+use anyhow::Result;
+use clap::Subcommand;
+use rune_store::Store;
+
+#[derive(Subcommand, Debug)]
+pub enum TransferCmd {
+    /// Show unfinished transfers and how far each got
+    List,
+    /// Forget an unfinished transfer; the next push starts over
+    Abort {
+        /// Transfer id, as shown by `rune transfer list`
+        id: String,
+    },
+}
+
+pub fn run(cmd: TransferCmd) -> Result<()> {
+    let store = Store::discover(std::env::current_dir()?)?;
+    match cmd {
+        TransferCmd::List => {
+            let transfers = rune_remote::list_transfers(&store.rune_dir)?;
+            if transfers.is_empty() {
+                Style::info("No transfers in progress");
+                return Ok(());
+            }
+            for t in transfers {
+                let (done, total) = t.bytes();
+                let items_done = t.items.iter().filter(|i| i.done).count();
+                let started = chrono::DateTime::from_timestamp(t.started_at, 0)
+                    .map(|d| d.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_default();
+                println!(
+                    "{}  {:<4} {}/{}  {:>3}%  {}/{} commits, {}/{} bytes  started {}",
+                    t.id,
+                    t.direction,
+                    t.remote,
+                    t.branch,
+                    t.percent(),
+                    items_done,
+                    t.items.len(),
+                    done,
+                    total,
+                    started
+                );
+            }
+            Ok(())
+        }
+        TransferCmd::Abort { id } => {
+            let t = rune_remote::abort_transfer(&store.rune_dir, &id)?;
+            Style::success(&format!("Aborted {} of {} to {} at {}%", t.direction, t.branch, t.remote, t.percent()));
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,673 @@
+//! `rune work --interactive`: pick files or hunks to stage, then commit
+//!
+//! `WorkSession` holds the file list, what has been picked and which screen
+//! is showing, and knows nothing about the terminal; `run` feeds it key
+//! presses from crossterm and draws it. Picks reach the staging area only
+//! when the session ends, whether by committing or by quitting, so quitting
+//! keeps what was picked staged and adds nothing else.
+
+use anyhow::{Context, Result};
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyEventKind},
+    queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{self, ClearType},
+};
+use rune_delta::hunks::{apply_hunks_fuzzy, line_hunks, Hunk};
+use rune_store::{PathChange, Store};
+use std::io::{IsTerminal, Write};
+
+use super::explore::{clip, header, screen_size, translate, Key, Screen};
+
+/// Lines of context around each hunk
+const HUNK_CONTEXT: usize = 3;
+
+/// How a file differs from what is staged for it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Modified,
+    Untracked,
+    Deleted,
+    /// New in a commit being split
+    Added,
+}
+
+impl Category {
+    fn label(self) -> &'static str {
+        match self {
+            Category::Modified => "modified",
+            Category::Untracked => "untracked",
+            Category::Deleted => "deleted",
+            Category::Added => "added",
+        }
+    }
+}
+
+/// One file with unstaged changes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkFile {
+    pub path: String,
+    pub category: Category,
+    /// Hunks from the staged (or committed) version to the working copy;
+    /// empty for new, deleted and binary files, which are staged whole
+    pub hunks: Vec<Hunk>,
+    /// Staged version the hunks apply to
+    pub base: String,
+}
+
+/// How much of a file has been picked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pick {
+    Nothing,
+    Some,
+    All,
+}
+
+/// What the caller has to do after a key press
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Redraw,
+    /// Get the commit message from the editor and hand it to
+    /// `WorkSession::set_message`
+    EditMessage,
+    /// Stage the picks and commit them with this message
+    Commit(String),
+    /// Stage the picks and leave without committing
+    Quit,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mode {
+    Files,
+    /// Going through the hunks of `file`, with `hunk` the one shown
+    Hunks { file: usize, hunk: usize },
+    /// Typing the commit message
+    Message { text: String },
+}
+
+/// Files with unstaged changes, which of them or of their hunks are picked,
+/// and the file highlighted for the preview
+#[derive(Debug)]
+pub struct WorkSession {
+    files: Vec<WorkFile>,
+    /// Per file, one flag per hunk, or a single flag for the whole file when
+    /// it has no hunks
+    picked: Vec<Vec<bool>>,
+    cursor: usize,
+    /// Message offered at the prompt; without one the editor is opened
+    suggestion: Option<String>,
+    /// Why the last key did nothing
+    notice: Option<String>,
+    mode: Mode,
+}
+
+impl WorkSession {
+    pub fn new(files: Vec<WorkFile>, suggestion: Option<String>) -> Self {
+        let picked = files.iter().map(|f| vec![false; f.hunks.len().max(1)]).collect();
+        Self { files, picked, cursor: 0, suggestion, notice: None, mode: Mode::Files }
+    }
+
+    pub fn mode(&self) -> &Mode {
+        &self.mode
+    }
+
+    pub fn files(&self) -> &[WorkFile] {
+        &self.files
+    }
+
+    pub fn highlighted(&self) -> Option<&WorkFile> {
+        self.files.get(self.cursor)
+    }
+
+    pub fn notice(&self) -> Option<&str> {
+        self.notice.as_deref()
+    }
+
+    pub fn pick(&self, file: usize) -> Pick {
+        let picked = &self.picked[file];
+        if picked.iter().all(|p| *p) {
+            Pick::All
+        } else if picked.iter().any(|p| *p) {
+            Pick::Some
+        } else {
+            Pick::Nothing
+        }
+    }
+
+    /// Picked hunks of `file`, in order
+    pub fn picked_hunks(&self, file: usize) -> Vec<Hunk> {
+        self.files[file]
+            .hunks
+            .iter()
+            .zip(&self.picked[file])
+            .filter(|(_, picked)| **picked)
+            .map(|(hunk, _)| hunk.clone())
+            .collect()
+    }
+
+    /// Finish the message step with `text` from the editor; lines starting
+    /// with `#` are dropped and an empty message goes back to the file list
+    pub fn set_message(&mut self, text: &str) -> Action {
+        let message = text
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .collect::<Vec<_>>()
+            .join("\n")
+            .trim()
+            .to_string();
+        if message.is_empty() {
+            self.notice = Some("Empty message; nothing committed".to_string());
+            self.mode = Mode::Files;
+            return Action::Redraw;
+        }
+        Action::Commit(message)
+    }
+
+    pub fn handle(&mut self, key: Key) -> Action {
+        self.notice = None;
+        match self.mode.clone() {
+            Mode::Files => self.handle_files(key),
+            Mode::Hunks { file, hunk } => self.handle_hunks(file, hunk, key),
+            Mode::Message { text } => self.handle_message(text, key),
+        }
+    }
+
+    fn handle_files(&mut self, key: Key) -> Action {
+        let last = self.files.len().saturating_sub(1);
+        match key {
+            Key::Up | Key::Char('k') => self.cursor = self.cursor.saturating_sub(1),
+            Key::Down | Key::Char('j') => self.cursor = (self.cursor + 1).min(last),
+            Key::Char(c @ '1'..='9') => {
+                let file = c as usize - '1' as usize;
+                if file < self.files.len() {
+                    self.cursor = file;
+                    self.toggle(file);
+                }
+            }
+            Key::Char(' ') if !self.files.is_empty() => self.toggle(self.cursor),
+            Key::Enter | Key::Char('h') if !self.files.is_empty() => {
+                if self.files[self.cursor].hunks.is_empty() {
+                    self.notice = Some(format!(
+                        "{} is {}; it can only be staged whole",
+                        self.files[self.cursor].path,
+                        self.files[self.cursor].category.label()
+                    ));
+                } else {
+                    self.mode = Mode::Hunks { file: self.cursor, hunk: 0 };
+                }
+            }
+            Key::Char('c') => {
+                if (0..self.files.len()).all(|f| self.pick(f) == Pick::Nothing) {
+                    self.notice = Some("Nothing picked to commit".to_string());
+                } else if let Some(text) = self.suggestion.clone() {
+                    self.mode = Mode::Message { text };
+                } else {
+                    self.mode = Mode::Message { text: String::new() };
+                    return Action::EditMessage;
+                }
+            }
+            Key::Char('q') | Key::Esc => return Action::Quit,
+            _ => {}
+        }
+        Action::Redraw
+    }
+
+    fn handle_hunks(&mut self, file: usize, hunk: usize, key: Key) -> Action {
+        let count = self.files[file].hunks.len();
+        let next = |session: &mut Self| {
+            session.mode = if hunk + 1 < count {
+                Mode::Hunks { file, hunk: hunk + 1 }
+            } else {
+                Mode::Files
+            };
+        };
+        match key {
+            Key::Char('y') => {
+                self.picked[file][hunk] = true;
+                next(self);
+            }
+            Key::Char('n') => {
+                self.picked[file][hunk] = false;
+                next(self);
+            }
+            Key::Char('a') | Key::Char('d') => {
+                let pick = key == Key::Char('a');
+                self.picked[file][hunk..].iter_mut().for_each(|p| *p = pick);
+                self.mode = Mode::Files;
+            }
+            Key::Up | Key::Char('k') => self.mode = Mode::Hunks { file, hunk: hunk.saturating_sub(1) },
+            Key::Down | Key::Char('j') => next(self),
+            Key::Char('q') | Key::Esc => self.mode = Mode::Files,
+            _ => {}
+        }
+        Action::Redraw
+    }
+
+    fn handle_message(&mut self, mut text: String, key: Key) -> Action {
+        match key {
+            Key::Char(c) => text.push(c),
+            Key::Backspace => {
+                text.pop();
+            }
+            Key::Enter => return self.set_message(&text),
+            Key::Esc => {
+                self.mode = Mode::Files;
+                return Action::Redraw;
+            }
+            _ => {}
+        }
+        self.mode = Mode::Message { text };
+        Action::Redraw
+    }
+
+    fn toggle(&mut self, file: usize) {
+        let pick = self.pick(file) != Pick::All;
+        self.picked[file].iter_mut().for_each(|p| *p = pick);
+    }
+
+    /// Lines of the preview pane: the hunks of the highlighted file, or of
+    /// the file whose hunks are being picked
+    pub fn preview(&self) -> Vec<String> {
+        let (file, only) = match self.mode {
+            Mode::Hunks { file, hunk } => (file, Some(hunk)),
+            _ => (self.cursor, None),
+        };
+        let Some(work) = self.files.get(file) else {
+            return Vec::new();
+        };
+        let mut lines = vec![format!("--- a/{}", work.path), format!("+++ b/{}", work.path)];
+        if work.hunks.is_empty() {
+            lines.push(format!("({} file, staged whole)", work.category.label()));
+        }
+        for (i, hunk) in work.hunks.iter().enumerate() {
+            if only.is_none() || only == Some(i) {
+                lines.extend(hunk.to_string().lines().map(str::to_string));
+            }
+        }
+        lines
+    }
+}
+
+/// Message `rune work --quick` offers for committing `paths`
+pub fn suggest_message(paths: &[String]) -> String {
+    match paths {
+        [one] => format!("Update {}", one),
+        _ => format!("Update {} files", paths.len()),
+    }
+}
+
+/// Unstaged changes of the working tree, modified files first, then
+/// untracked and deleted ones
+pub fn unstaged_files(store: &Store) -> Result<Vec<WorkFile>> {
+    let status = store.status()?;
+    let mut files = Vec::new();
+    let mut untracked = status.untracked;
+    let mut working = status.working;
+    for rename in status.renamed {
+        if let PathChange::Renamed { from, to, .. } = rename {
+            working.push(from);
+            untracked.push(to);
+        }
+    }
+    for path in working {
+        let Ok(content) = std::fs::read(store.root.join(&path)) else {
+            files.push(WorkFile { path, category: Category::Deleted, hunks: Vec::new(), base: String::new() });
+            continue;
+        };
+        let base = staged_text(store, &path)?;
+        let hunks = match (&base, String::from_utf8(content)) {
+            (Some(base), Ok(text)) if !rune_core::eol::looks_binary(text.as_bytes()) => {
+                line_hunks(base, &text, HUNK_CONTEXT)
+            }
+            _ => Vec::new(),
+        };
+        files.push(WorkFile { path, category: Category::Modified, hunks, base: base.unwrap_or_default() });
+    }
+    files.extend(untracked.into_iter().map(|path| WorkFile {
+        path,
+        category: Category::Untracked,
+        hunks: Vec::new(),
+        base: String::new(),
+    }));
+    files.sort_by_key(|f| f.category as u8);
+    Ok(files)
+}
+
+/// Text the staging area (or else HEAD) has for `path`; `None` for binary
+/// content
+fn staged_text(store: &Store, path: &str) -> Result<Option<String>> {
+    let content = match store.staged_content(path)? {
+        Some(content) => content,
+        None => match store.head_commit().and_then(|head| store.blob_at(&head, path)) {
+            Some(blob) => store.read_blob(&blob)?,
+            None => Vec::new(),
+        },
+    };
+    if rune_core::eol::looks_binary(&content) {
+        return Ok(None);
+    }
+    Ok(String::from_utf8(content).ok())
+}
+
+/// Write the picks of `session` to the staging area: whole files as they
+/// are in the working tree, partly picked ones as their staged version with
+/// the picked hunks applied. Returns the paths staged.
+pub fn stage_picks(store: &Store, session: &WorkSession) -> Result<Vec<String>> {
+    let _batch = store.batch_index_writes();
+    let mut staged = Vec::new();
+    for (i, file) in session.files().iter().enumerate() {
+        match session.pick(i) {
+            Pick::Nothing => continue,
+            Pick::All => store.stage_file(&file.path)?,
+            Pick::Some => {
+                let result = apply_hunks_fuzzy(&file.base, &session.picked_hunks(i), 0);
+                if !result.is_clean() {
+                    anyhow::bail!("picked hunks of {} no longer apply", file.path);
+                }
+                store.stage_content(&file.path, result.content.as_bytes())?;
+            }
+        }
+        staged.push(file.path.clone());
+    }
+    Ok(staged)
+}
+
+/// Run the session. Picks are staged when it ends; the commit message is
+/// returned when the user asked to commit.
+pub fn run(store: &Store, quick: bool) -> Result<Option<String>> {
+    require_terminal(
+        "rune work --interactive",
+        "Stage with `rune add <files>` and commit with `rune work --message \"...\"`, \
+         or use `rune work --all --quick`",
+    )?;
+    let files = unstaged_files(store)?;
+    if files.is_empty() {
+        return Ok(None);
+    }
+    let suggestion = quick.then(|| {
+        let mut paths: Vec<String> = store.read_index().map(|i| i.entries.into_keys().collect()).unwrap_or_default();
+        paths.extend(files.iter().map(|f| f.path.clone()));
+        paths.sort();
+        paths.dedup();
+        suggest_message(&paths)
+    });
+    let mut session = WorkSession::new(files, suggestion);
+    let message = drive(store, &mut session)?;
+    stage_picks(store, &session)?;
+    Ok(message)
+}
+
+/// Fail with `alternative` as guidance unless stdin and stderr are a
+/// terminal, rather than waiting for keys that never come
+pub fn require_terminal(command: &str, alternative: &str) -> Result<()> {
+    if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        anyhow::bail!("`{}` needs a terminal.\n{}", command, alternative);
+    }
+    Ok(())
+}
+
+/// Show `session` and feed it key presses until the user commits or quits,
+/// opening the editor when it asks for a message. Returns the message on
+/// commit.
+pub fn drive(store: &Store, session: &mut WorkSession) -> Result<Option<String>> {
+    loop {
+        let action = {
+            let _screen = Screen::enter()?;
+            let mut out = std::io::stderr();
+            loop {
+                draw(&mut out, session)?;
+                let action = match event::read()? {
+                    Event::Key(key) if key.kind != KeyEventKind::Release => match translate(key) {
+                        Some(key) => session.handle(key),
+                        None => continue,
+                    },
+                    _ => continue,
+                };
+                if action != Action::Redraw {
+                    break action;
+                }
+            }
+        };
+        match action {
+            Action::EditMessage => {
+                let text = edit_message(store)?;
+                if let Action::Commit(message) = session.set_message(&text) {
+                    return Ok(Some(message));
+                }
+            }
+            Action::Commit(message) => return Ok(Some(message)),
+            Action::Quit => return Ok(None),
+            Action::Redraw => {}
+        }
+    }
+}
+
+/// Open `$VISUAL` or `$EDITOR` (vi when neither is set) on a message file
+/// and return what was saved
+fn edit_message(store: &Store) -> Result<String> {
+    let path = store.rune_dir.join("WORK_EDITMSG");
+    std::fs::write(&path, "\n# Enter the commit message; lines starting with '#' are ignored.\n")?;
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or("vi");
+    let status = std::process::Command::new(program)
+        .args(parts)
+        .arg(&path)
+        .status()
+        .with_context(|| format!("Failed to start editor '{}'", editor))?;
+    if !status.success() {
+        anyhow::bail!("Editor '{}' exited with {}", editor, status);
+    }
+    Ok(std::fs::read_to_string(&path)?)
+}
+
+fn draw(out: &mut impl Write, session: &WorkSession) -> Result<()> {
+    let (width, height) = screen_size();
+    let width = width as usize;
+    queue!(out, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+    header(out, &format!("{} files with changes to pick from", session.files().len()), width)?;
+
+    let mut y = 1u16;
+    for (i, file) in session.files().iter().enumerate() {
+        let mark = match session.pick(i) {
+            Pick::All => "[x]",
+            Pick::Some => "[~]",
+            Pick::Nothing => "[ ]",
+        };
+        let line = format!("{} {:>2}  {:<9}  {}", mark, i + 1, file.category.label(), file.path);
+        queue!(out, cursor::MoveTo(0, y))?;
+        if session.highlighted().is_some_and(|h| h.path == file.path) {
+            queue!(out, SetAttribute(Attribute::Reverse))?;
+        }
+        queue!(out, Print(clip(&line, width)), SetAttribute(Attribute::Reset))?;
+        y += 1;
+    }
+
+    y += 1;
+    let footer_row = height.saturating_sub(1);
+    for line in session.preview() {
+        if y >= footer_row {
+            break;
+        }
+        queue!(out, cursor::MoveTo(0, y), Print(clip(&line, width)))?;
+        y += 1;
+    }
+
+    let footer = match (session.notice(), session.mode()) {
+        (Some(notice), _) => notice.to_string(),
+        (None, Mode::Files) => "1-9/space toggle  ↑/↓ move  enter hunks  c commit  q quit".to_string(),
+        (None, Mode::Hunks { file, hunk }) => format!(
+            "hunk {}/{}  y stage  n skip  a rest  d none  ↑/↓ move  q back",
+            hunk + 1,
+            session.files()[*file].hunks.len()
+        ),
+        (None, Mode::Message { text }) => format!("message: {}", text),
+    };
+    queue!(
+        out,
+        cursor::MoveTo(0, footer_row),
+        SetAttribute(Attribute::Dim),
+        Print(clip(&footer, width)),
+        SetAttribute(Attribute::Reset)
+    )?;
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support::{author, temp_repo};
+    use tempfile::TempDir;
+
+    /// Repository with `a.txt` (twenty lines) and `b.txt` committed, then
+    /// `a.txt` edited at both ends, `b.txt` deleted and `new.txt` added
+    fn edited_repo() -> (TempDir, Store) {
+        let (temp, store) = temp_repo();
+        let lines: Vec<String> = (1..=20).map(|i| format!("line {}", i)).collect();
+        std::fs::write(temp.path().join("a.txt"), lines.join("\n") + "\n").unwrap();
+        std::fs::write(temp.path().join("b.txt"), "b\n").unwrap();
+        store.stage_file("a.txt").unwrap();
+        store.stage_file("b.txt").unwrap();
+        store.commit("base", author()).unwrap();
+
+        let mut edited = lines.clone();
+        edited[0] = "line 1 changed".to_string();
+        edited[19] = "line 20 changed".to_string();
+        std::fs::write(temp.path().join("a.txt"), edited.join("\n") + "\n").unwrap();
+        std::fs::remove_file(temp.path().join("b.txt")).unwrap();
+        std::fs::write(temp.path().join("new.txt"), "new\n").unwrap();
+        (temp, store)
+    }
+
+    fn session(store: &Store, suggestion: Option<&str>) -> WorkSession {
+        WorkSession::new(unstaged_files(store).unwrap(), suggestion.map(str::to_string))
+    }
+
+    fn type_text(session: &mut WorkSession, text: &str) {
+        for c in text.chars() {
+            session.handle(Key::Char(c));
+        }
+    }
+
+    #[test]
+    fn test_files_are_categorized() {
+        let (_temp, store) = edited_repo();
+        let files = unstaged_files(&store).unwrap();
+        let listed: Vec<(&str, Category)> = files.iter().map(|f| (f.path.as_str(), f.category)).collect();
+        assert_eq!(
+            listed,
+            [("a.txt", Category::Modified), ("new.txt", Category::Untracked), ("b.txt", Category::Deleted)]
+        );
+        assert_eq!(files[0].hunks.len(), 2);
+    }
+
+    #[test]
+    fn test_number_keys_toggle_files() {
+        let (_temp, store) = edited_repo();
+        let mut session = session(&store, None);
+        session.handle(Key::Char('2'));
+        assert_eq!(session.pick(1), Pick::All);
+        assert_eq!(session.highlighted().unwrap().path, "new.txt");
+        session.handle(Key::Char('2'));
+        assert_eq!(session.pick(1), Pick::Nothing);
+        // No fourth file
+        session.handle(Key::Char('4'));
+        assert_eq!(session.highlighted().unwrap().path, "new.txt");
+
+        session.handle(Key::Char('3'));
+        session.handle(Key::Up);
+        session.handle(Key::Char(' '));
+        assert_eq!(session.pick(1), Pick::All);
+        assert_eq!(stage_picks(&store, &session).unwrap(), ["new.txt", "b.txt"]);
+        assert_eq!(store.read_index().unwrap().entries.len(), 2);
+    }
+
+    #[test]
+    fn test_hunk_stage_takes_only_picked_hunks() {
+        let (temp, store) = edited_repo();
+        let mut session = session(&store, None);
+        session.handle(Key::Enter);
+        assert_eq!(session.mode(), &Mode::Hunks { file: 0, hunk: 0 });
+        assert!(session.preview().iter().any(|l| l == "+line 1 changed"));
+        assert!(!session.preview().iter().any(|l| l == "+line 20 changed"));
+        session.handle(Key::Char('n'));
+        session.handle(Key::Char('y'));
+        assert_eq!(session.mode(), &Mode::Files);
+        assert_eq!(session.pick(0), Pick::Some);
+
+        stage_picks(&store, &session).unwrap();
+        let staged = String::from_utf8(store.staged_content("a.txt").unwrap().unwrap()).unwrap();
+        assert!(staged.starts_with("line 1\n"));
+        assert!(staged.ends_with("line 20 changed\n"));
+        // The skipped hunk is still an unstaged change
+        assert!(store.status().unwrap().working.contains(&"a.txt".to_string()));
+        assert!(std::fs::read_to_string(temp.path().join("a.txt")).unwrap().starts_with("line 1 changed"));
+    }
+
+    #[test]
+    fn test_whole_files_have_no_hunks_to_pick() {
+        let (_temp, store) = edited_repo();
+        let mut session = session(&store, None);
+        session.handle(Key::Down);
+        session.handle(Key::Enter);
+        assert_eq!(session.mode(), &Mode::Files);
+        assert!(session.notice().unwrap().contains("untracked"));
+    }
+
+    #[test]
+    fn test_message_entry_commits_with_suggestion() {
+        let (_temp, store) = edited_repo();
+        let mut session = session(&store, Some("Update 3 files"));
+        assert_eq!(session.handle(Key::Char('c')), Action::Redraw);
+        assert_eq!(session.notice(), Some("Nothing picked to commit"));
+
+        session.handle(Key::Char('1'));
+        session.handle(Key::Char('c'));
+        assert_eq!(session.mode(), &Mode::Message { text: "Update 3 files".to_string() });
+        for _ in 0.."3 files".len() {
+            session.handle(Key::Backspace);
+        }
+        type_text(&mut session, "a.txt");
+        assert_eq!(session.handle(Key::Enter), Action::Commit("Update a.txt".to_string()));
+    }
+
+    #[test]
+    fn test_editor_message_without_suggestion() {
+        let (_temp, store) = edited_repo();
+        let mut session = session(&store, None);
+        session.handle(Key::Char('1'));
+        assert_eq!(session.handle(Key::Char('c')), Action::EditMessage);
+        assert_eq!(session.set_message("\n# comment\n"), Action::Redraw);
+        assert_eq!(session.mode(), &Mode::Files);
+        assert_eq!(
+            session.set_message("Tidy a\n# Enter the commit message\n"),
+            Action::Commit("Tidy a".to_string())
+        );
+    }
+
+    #[test]
+    fn test_quit_keeps_only_explicit_staging() {
+        let (_temp, store) = edited_repo();
+        store.stage_file("new.txt").unwrap();
+        let mut session = session(&store, None);
+        // new.txt is already staged and so not offered again
+        assert_eq!(session.files().len(), 2);
+        session.handle(Key::Enter);
+        session.handle(Key::Char('y'));
+        // Backing out of the hunks keeps what was picked so far
+        session.handle(Key::Char('q'));
+        assert_eq!(session.handle(Key::Char('q')), Action::Quit);
+
+        stage_picks(&store, &session).unwrap();
+        let index = store.read_index().unwrap();
+        let staged: Vec<&str> = index.entries.keys().map(String::as_str).collect();
+        assert_eq!(staged, ["a.txt", "new.txt"]);
+        let a = String::from_utf8(store.staged_content("a.txt").unwrap().unwrap()).unwrap();
+        assert!(a.starts_with("line 1 changed\n") && a.ends_with("line 20\n"));
+        assert_eq!(store.log().len(), 1);
+    }
+}
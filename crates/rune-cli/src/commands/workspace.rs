@@ -1,7 +1,11 @@
 use anyhow::Result;
 use clap::Subcommand;
-use rune_workspace::WorkspaceManager;
-use std::path::PathBuf;
+use rune_workspace::{WorkspaceManager, WorkspaceProfile, PROFILES_DIR};
+use rune_core::layout::RepoLayout;
+use rune_store::Store;
+use std::collections::BTreeSet;
+use std::path::{Component, Path, PathBuf};
+use crate::exit_codes::{CliError, ErrorKind};
 
 #[derive(Subcommand, Debug)]
 pub enum WorkspaceCmd {
@@ -68,21 +72,36 @@ pub enum WorkspaceCmd {
         #[arg(long, help = "Show current limits")]
         show: bool,
     },
+    /// Set up the workspace from a profile committed in .runeworkspaces/
+    Use {
+        #[arg(help = "Profile name, e.g. frontend for .runeworkspaces/frontend.toml")]
+        profile: String,
+    },
+    /// List the profiles committed in .runeworkspaces/
+    ListProfiles,
+    /// Update the workspace to the committed version of its profile, keeping local changes
+    SyncProfile {
+        #[arg(long, help = "Only show what changed in the profile")]
+        dry_run: bool,
+    },
 }
 
 pub fn run(cmd: WorkspaceCmd) -> Result<()> {
+    // Workspace state lives in the repository's metadata, or in ./.rune
+    // outside a repository
     let current_dir = std::env::current_dir()?;
+    let layout = RepoLayout::discover(&current_dir).or_else(|_| RepoLayout::at(&current_dir))?;
 
     match cmd {
         WorkspaceCmd::Init { name } => {
-            let mut workspace = WorkspaceManager::new(current_dir, name.clone())?;
+            let mut workspace = WorkspaceManager::new(layout.root.clone(), &layout.rune_dir, name.clone())?;
             workspace.save()?;
             println!("✓ Initialized virtual workspace: {}", name);
             println!("  Configuration saved to: .rune/workspace/config.json");
         }
 
         WorkspaceCmd::AddRoot { name, path, include } => {
-            let mut workspace = WorkspaceManager::load(current_dir)?;
+            let mut workspace = WorkspaceManager::load(&layout.rune_dir)?;
             let patterns = if include.is_empty() {
                 vec!["**/*".to_string()]
             } else {
@@ -92,12 +111,13 @@ pub fn run(cmd: WorkspaceCmd) -> Result<()> {
         }
 
         WorkspaceCmd::RemoveRoot { name } => {
-            let mut workspace = WorkspaceManager::load(current_dir)?;
+            let mut workspace = WorkspaceManager::load(&layout.rune_dir)?;
             workspace.remove_virtual_root(&name)?;
+            sync_sparse_index(&current_dir, &workspace)?;
         }
 
         WorkspaceCmd::List => {
-            let workspace = WorkspaceManager::load(current_dir)?;
+            let workspace = WorkspaceManager::load(&layout.rune_dir)?;
             let roots = workspace.list_virtual_roots();
             
             if roots.is_empty() {
@@ -123,10 +143,10 @@ pub fn run(cmd: WorkspaceCmd) -> Result<()> {
         }
 
         WorkspaceCmd::Toggle { name, activate, deactivate } => {
-            let mut workspace = WorkspaceManager::load(current_dir)?;
+            let mut workspace = WorkspaceManager::load(&layout.rune_dir)?;
             
             if activate && deactivate {
-                anyhow::bail!("Cannot both activate and deactivate");
+                return Err(CliError::new(ErrorKind::Usage, "Cannot both activate and deactivate").into());
             }
             
             if activate {
@@ -134,12 +154,13 @@ pub fn run(cmd: WorkspaceCmd) -> Result<()> {
             } else if deactivate {
                 workspace.set_virtual_root_active(&name, false)?;
             } else {
-                anyhow::bail!("Must specify either --activate or --deactivate");
+                return Err(CliError::new(ErrorKind::Usage, "Must specify either --activate or --deactivate").into());
             }
+            sync_sparse_index(&current_dir, &workspace)?;
         }
 
         WorkspaceCmd::View { count_only } => {
-            let workspace = WorkspaceManager::load(current_dir)?;
+            let workspace = WorkspaceManager::load(&layout.rune_dir)?;
             let files = workspace.get_workspace_files()?;
             
             if count_only {
@@ -160,21 +181,21 @@ pub fn run(cmd: WorkspaceCmd) -> Result<()> {
         }
 
         WorkspaceCmd::Include { patterns } => {
-            let mut workspace = WorkspaceManager::load(current_dir)?;
+            let mut workspace = WorkspaceManager::load(&layout.rune_dir)?;
             for pattern in patterns {
                 workspace.add_include_pattern(pattern)?;
             }
         }
 
         WorkspaceCmd::Exclude { patterns } => {
-            let mut workspace = WorkspaceManager::load(current_dir)?;
+            let mut workspace = WorkspaceManager::load(&layout.rune_dir)?;
             for pattern in patterns {
                 workspace.add_exclude_pattern(pattern)?;
             }
         }
 
         WorkspaceCmd::Validate { files } => {
-            let workspace = WorkspaceManager::load(current_dir)?;
+            let workspace = WorkspaceManager::load(&layout.rune_dir)?;
             let validation = workspace.validate_commit_files(&files)?;
             
             println!("📋 Validation Results:");
@@ -199,7 +220,7 @@ pub fn run(cmd: WorkspaceCmd) -> Result<()> {
                 println!("\n✅ All files pass validation");
             } else {
                 println!("\n❌ Validation failed - fix errors before committing");
-                std::process::exit(1);
+                return Err(CliError::new(ErrorKind::PreconditionFailed, "Validation failed").into());
             }
         }
 
@@ -210,7 +231,7 @@ pub fn run(cmd: WorkspaceCmd) -> Result<()> {
             warn_size, 
             show 
         } => {
-            let mut workspace = WorkspaceManager::load(current_dir)?;
+            let mut workspace = WorkspaceManager::load(&layout.rune_dir)?;
             
             if show {
                 let limits = &workspace.config.performance_limits;
@@ -253,7 +274,102 @@ pub fn run(cmd: WorkspaceCmd) -> Result<()> {
                 println!("No limits specified. Use --show to see current limits.");
             }
         }
+
+        WorkspaceCmd::Use { profile } => {
+            use_profile(&layout, &profile)?;
+        }
+
+        WorkspaceCmd::ListProfiles => {
+            let profiles = WorkspaceProfile::list(&layout.root)?;
+            if profiles.is_empty() {
+                println!("No workspace profiles in {}/", PROFILES_DIR);
+                return Ok(());
+            }
+            let current = WorkspaceManager::load(&layout.rune_dir).ok().and_then(|w| w.config.profile).map(|p| p.name);
+            println!("📁 Workspace profiles:");
+            for profile in profiles {
+                let marker = if current.as_deref() == Some(profile.name.as_str()) { "*" } else { " " };
+                println!("  {} {:<16} {}", marker, profile.name, profile.description);
+            }
+        }
+
+        WorkspaceCmd::SyncProfile { dry_run } => {
+            let mut workspace = WorkspaceManager::load(&layout.rune_dir)?;
+            let sync = workspace.sync_profile(dry_run)?;
+            if sync.from_version == sync.to_version {
+                println!("✓ Workspace is up to date with its profile ({})", sync.to_version);
+                return Ok(());
+            }
+            println!("Profile changed ({} -> {}):", sync.from_version, sync.to_version);
+            for change in &sync.changes {
+                println!("  {}", change);
+            }
+            if dry_run {
+                println!("Run without --dry-run to apply; local changes are kept.");
+            } else {
+                let overrides = workspace.local_overrides()?;
+                println!("✓ Profile applied{}", if overrides.is_empty() { "" } else { ", local changes kept" });
+                sync_sparse_index(&current_dir, &workspace)?;
+            }
+        }
     }
 
     Ok(())
 }
+
+/// Make the workspace of the repository at `layout` the committed profile
+/// `name`
+pub fn use_profile(layout: &RepoLayout, name: &str) -> Result<()> {
+    let workspace = WorkspaceManager::use_profile(layout.root.clone(), &layout.rune_dir, name)?;
+    let version = workspace.config.profile.as_ref().map(|p| p.version.as_str()).unwrap_or_default();
+    println!("✓ Using workspace profile '{}' ({})", name, version);
+    for (root_name, root) in workspace.list_virtual_roots() {
+        println!("  {} -> {}", root_name, root.path.display());
+    }
+    sync_sparse_index(&layout.root, &workspace)
+}
+
+/// With `[sparse] index` on, collapse the top-level directories no active
+/// virtual root is in and expand the rest
+fn sync_sparse_index(current_dir: &Path, workspace: &WorkspaceManager) -> Result<()> {
+    let Ok(store) = Store::discover(current_dir) else {
+        return Ok(());
+    };
+    if !store.config().sparse.index {
+        return Ok(());
+    }
+    let roots: Vec<PathBuf> =
+        workspace.list_virtual_roots().into_iter().filter(|(_, root)| root.active).map(|(_, root)| root.path.clone()).collect();
+    let excluded = excluded_dirs(&store, &roots);
+    let (collapsed, expanded) = store.set_sparse_dirs(&excluded)?;
+    if !collapsed.is_empty() {
+        println!("  Left out of the index: {}", collapsed.iter().map(|d| format!("{}/", d)).collect::<Vec<_>>().join(", "));
+    }
+    if !expanded.is_empty() {
+        println!("  Back in the index: {}", expanded.iter().map(|d| format!("{}/", d)).collect::<Vec<_>>().join(", "));
+    }
+    Ok(())
+}
+
+/// Top-level directories of HEAD none of `active_roots` lies in; nothing
+/// when no root is active or one covers the whole repository
+fn excluded_dirs(store: &Store, active_roots: &[PathBuf]) -> BTreeSet<String> {
+    let mut kept = BTreeSet::new();
+    for root in active_roots {
+        match root.components().find(|c| !matches!(c, Component::CurDir)) {
+            Some(Component::Normal(top)) => {
+                kept.insert(top.to_string_lossy().to_string());
+            }
+            _ => return BTreeSet::new(),
+        }
+    }
+    if kept.is_empty() {
+        return BTreeSet::new();
+    }
+    let head_tree = store.head_commit().map(|id| store.tree_snapshot(&id)).unwrap_or_default();
+    head_tree
+        .keys()
+        .filter_map(|path| path.split_once('/').map(|(top, _)| top.to_string()))
+        .filter(|top| !kept.contains(top) && !top.starts_with('.'))
+        .collect()
+}
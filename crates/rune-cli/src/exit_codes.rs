@@ -0,0 +1,244 @@
+//! Exit codes and the JSON error envelope scripts rely on
+//!
+//! | Code | Kind                  | Meaning                                        |
+//! |------|-----------------------|------------------------------------------------|
+//! | 0    |                       | success                                        |
+//! | 1    | `unexpected`          | anything not listed below                      |
+//! | 2    | `usage`               | bad arguments, or naming something not there   |
+//! | 3    | `conflicts`           | the operation stopped with conflicts           |
+//! | 4    | `not_a_repo`          | no repository here                             |
+//! | 5    | `nothing_to_do`       | nothing to commit, apply or continue           |
+//! | 6    | `precondition_failed` | dirty worktree, operation in progress, hook... |
+//! | 7    | `remote`              | the network or the remote failed               |
+//!
+//! Commands raise a `CliError` when the kind isn't already clear from the
+//! error itself; `classify` also knows the `StoreError` variants and
+//! network failures. With `--format json` the failure goes to stderr as one
+//! `{code, kind, message, details}` object instead of prose.
+
+use rune_store::StoreError;
+use serde_json::{json, Value};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    Unexpected,
+    Usage,
+    Conflicts,
+    NotARepo,
+    NothingToDo,
+    PreconditionFailed,
+    Remote,
+}
+
+impl ErrorKind {
+    pub fn code(self) -> u8 {
+        match self {
+            ErrorKind::Unexpected => 1,
+            ErrorKind::Usage => 2,
+            ErrorKind::Conflicts => 3,
+            ErrorKind::NotARepo => 4,
+            ErrorKind::NothingToDo => 5,
+            ErrorKind::PreconditionFailed => 6,
+            ErrorKind::Remote => 7,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorKind::Unexpected => "unexpected",
+            ErrorKind::Usage => "usage",
+            ErrorKind::Conflicts => "conflicts",
+            ErrorKind::NotARepo => "not_a_repo",
+            ErrorKind::NothingToDo => "nothing_to_do",
+            ErrorKind::PreconditionFailed => "precondition_failed",
+            ErrorKind::Remote => "remote",
+        }
+    }
+}
+
+/// A failure whose kind the command decided
+#[derive(Debug)]
+pub struct CliError {
+    pub kind: ErrorKind,
+    pub message: String,
+    /// Extra fields for the JSON envelope, e.g. the conflicted paths
+    pub details: Value,
+}
+
+impl CliError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self { kind, message: message.into(), details: json!({}) }
+    }
+
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = details;
+        self
+    }
+
+    /// The operation stopped with `paths` in conflict
+    pub fn conflicts(message: impl Into<String>, paths: &[String]) -> Self {
+        Self::new(ErrorKind::Conflicts, message).with_details(json!({ "paths": paths }))
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CliError {}
+
+fn store_error_kind(err: &StoreError) -> ErrorKind {
+    match err {
+        StoreError::NotARepo { .. } => ErrorKind::NotARepo,
+        StoreError::NothingToCommit
+        | StoreError::NoMergeInProgress
+        | StoreError::NoRebaseInProgress
+        | StoreError::NoRevertInProgress
+        | StoreError::NoAmInProgress => ErrorKind::NothingToDo,
+        StoreError::UnresolvedConflicts(_) => ErrorKind::Conflicts,
+        StoreError::CommitNotFound(_)
+        | StoreError::AmbiguousCommit { .. }
+        | StoreError::BranchNotFound(_)
+        | StoreError::TagNotFound(_)
+        | StoreError::InvalidRefName { .. }
+        | StoreError::PathOutsideRepo(_)
+        | StoreError::NotInConflict(_) => ErrorKind::Usage,
+        StoreError::NoCommits
+        | StoreError::BranchExists(_)
+        | StoreError::RefWouldMove { .. }
+        | StoreError::UnrelatedHistories(_)
+        | StoreError::NotOnBranch
+        | StoreError::UncommittedChanges
+        | StoreError::MergeInProgress { .. }
+        | StoreError::HookRejected { .. }
+        | StoreError::OutsideSparse { .. }
+        | StoreError::ObjectTooLarge { .. }
+        | StoreError::ObjectStoreOverQuota { .. }
+        | StoreError::TagNotSigned(_)
+        | StoreError::BadTagSignature(_)
+        | StoreError::UntrustedTagSigner { .. } => ErrorKind::PreconditionFailed,
+        StoreError::Cancelled | StoreError::CorruptObject { .. } => ErrorKind::Unexpected,
+    }
+}
+
+fn is_network_io(err: &std::io::Error) -> bool {
+    use std::io::ErrorKind as Io;
+    matches!(
+        err.kind(),
+        Io::ConnectionRefused | Io::ConnectionReset | Io::ConnectionAborted | Io::NotConnected | Io::TimedOut
+    )
+}
+
+/// The kind of the first error in `err`'s chain that has one
+pub fn classify(err: &anyhow::Error) -> ErrorKind {
+    for cause in err.chain() {
+        if let Some(cli) = cause.downcast_ref::<CliError>() {
+            return cli.kind;
+        }
+        if let Some(store) = cause.downcast_ref::<StoreError>() {
+            return store_error_kind(store);
+        }
+        if cause.is::<clap::Error>() {
+            return ErrorKind::Usage;
+        }
+        if cause.is::<reqwest::Error>() {
+            return ErrorKind::Remote;
+        }
+        if cause.downcast_ref::<std::io::Error>().is_some_and(is_network_io) {
+            return ErrorKind::Remote;
+        }
+    }
+    ErrorKind::Unexpected
+}
+
+/// `{code, kind, message, details}` for `err`
+pub fn envelope(err: &anyhow::Error) -> Value {
+    let kind = classify(err);
+    let mut details = json!({});
+    for cause in err.chain() {
+        if let Some(cli) = cause.downcast_ref::<CliError>() {
+            details = cli.details.clone();
+            break;
+        }
+        match cause.downcast_ref::<StoreError>() {
+            Some(StoreError::NotARepo { path, .. }) => details = json!({ "path": path }),
+            Some(StoreError::UnresolvedConflicts(paths)) => details = json!({ "paths": paths }),
+            _ => continue,
+        }
+        break;
+    }
+    let causes: Vec<String> = err.chain().skip(1).map(|cause| cause.to_string()).collect();
+    if let (Some(fields), false) = (details.as_object_mut(), causes.is_empty()) {
+        fields.insert("causes".into(), json!(causes));
+    }
+    let message = match err.downcast_ref::<clap::Error>() {
+        // Clap's rendering carries usage lines and hints; the first line is the error
+        Some(clap) => clap.to_string().lines().next().unwrap_or_default().trim_start_matches("error: ").to_string(),
+        None => err.to_string(),
+    };
+    json!({ "code": kind.code(), "kind": kind.name(), "message": message, "details": details })
+}
+
+/// Whether the command line asks for JSON output, so failures should be
+/// JSON too
+pub fn wants_json(args: &[String]) -> bool {
+    args.iter()
+        .zip(args.iter().skip(1).map(Some).chain([None]))
+        .any(|(arg, next)| arg == "--format=json" || (arg == "--format" && next.is_some_and(|n| n == "json")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_errors_map_to_their_exit_codes() {
+        let store = |e: StoreError| classify(&anyhow::Error::new(e));
+        assert_eq!(store(StoreError::NothingToCommit).code(), 5);
+        assert_eq!(store(StoreError::UncommittedChanges).code(), 6);
+        assert_eq!(store(StoreError::UnresolvedConflicts(vec![])).code(), 3);
+        assert_eq!(store(StoreError::BranchNotFound("x".into())).code(), 2);
+
+        // Context on top keeps the kind underneath
+        let wrapped = Err::<(), _>(StoreError::NotARepo { path: "/tmp".into(), reason: "none".into() })
+            .context("Merge failed")
+            .unwrap_err();
+        assert_eq!(classify(&wrapped), ErrorKind::NotARepo);
+        let io = anyhow::Error::new(std::io::Error::from(std::io::ErrorKind::ConnectionRefused)).context("pushing");
+        assert_eq!(classify(&io), ErrorKind::Remote);
+        assert_eq!(classify(&anyhow::anyhow!("disk exploded")), ErrorKind::Unexpected);
+    }
+
+    #[test]
+    fn test_envelope_fields() {
+        let err = anyhow::Error::new(CliError::conflicts("Merge stopped with conflicts", &["a.txt".to_string()]));
+        assert_eq!(
+            envelope(&err),
+            json!({
+                "code": 3,
+                "kind": "conflicts",
+                "message": "Merge stopped with conflicts",
+                "details": { "paths": ["a.txt"] }
+            })
+        );
+
+        let err = anyhow::anyhow!("disk exploded").context("writing index");
+        let envelope = envelope(&err);
+        assert_eq!(envelope["code"], 1);
+        assert_eq!(envelope["message"], "writing index");
+        assert_eq!(envelope["details"]["causes"], json!(["disk exploded"]));
+    }
+
+    #[test]
+    fn test_wants_json() {
+        let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert!(wants_json(&args(&["rune", "notes", "between", "v1", "--format", "json"])));
+        assert!(wants_json(&args(&["rune", "status", "--format=json"])));
+        assert!(!wants_json(&args(&["rune", "notes", "between", "v1", "--format", "md"])));
+        assert!(!wants_json(&args(&["rune", "commit", "-m", "json"])));
+    }
+}
@@ -1,6 +1,6 @@
 use colored::*;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::Path;
 use walkdir;
@@ -44,11 +44,73 @@ pub struct FileAnalysis {
     pub file_type: FileType,
     pub language: Language,
     pub size_bytes: u64,
+    /// Format recognised from the content itself (magic bytes or a shebang)
+    pub detected_format: Option<String>,
+    pub is_binary: bool,
+    /// Why the file looks machine-generated, if it does
+    pub generated: Option<String>,
+    /// Shannon entropy of the sampled bytes, in bits per byte (0-8)
+    pub entropy: f64,
+    /// Line ending style of text files with at least one line break
+    pub line_ending: Option<LineEnding>,
+    /// Pattern to pass to `rune lfs track` when `suggested_lfs` is set
+    pub lfs_pattern: Option<String>,
     pub security_issues: Vec<SecurityIssue>,
     pub performance_impact: PerformanceImpact,
     pub suggestions: Vec<Suggestion>,
 }
 
+pub use rune_core::eol::LineEnding;
+use rune_core::eol::detect_line_ending;
+
+/// Aggregated analysis of every file below a directory
+#[derive(Debug, Clone)]
+pub struct DirectoryAnalysis {
+    /// Paths relative to the analyzed directory
+    pub files: Vec<(String, FileAnalysis)>,
+    pub total_size: u64,
+    /// Most common line ending among text files
+    pub line_ending: Option<LineEnding>,
+}
+
+impl DirectoryAnalysis {
+    pub fn binary_files(&self) -> impl Iterator<Item = &(String, FileAnalysis)> {
+        self.files.iter().filter(|(_, a)| a.is_binary)
+    }
+
+    /// LFS patterns with the number of files and bytes each would cover
+    pub fn lfs_candidates(&self) -> BTreeMap<String, (usize, u64)> {
+        let mut patterns = BTreeMap::new();
+        for (_, analysis) in &self.files {
+            if let Some(pattern) = &analysis.lfs_pattern {
+                let entry = patterns.entry(pattern.clone()).or_insert((0, 0));
+                entry.0 += 1;
+                entry.1 += analysis.size_bytes;
+            }
+        }
+        patterns
+    }
+
+    pub fn generated_files(&self) -> Vec<(&str, &str)> {
+        self.files
+            .iter()
+            .filter_map(|(path, a)| a.generated.as_deref().map(|why| (path.as_str(), why)))
+            .collect()
+    }
+
+    /// Text files whose line endings differ from the directory's dominant style
+    pub fn line_ending_mismatches(&self) -> Vec<&str> {
+        let Some(dominant) = self.line_ending else {
+            return Vec::new();
+        };
+        self.files
+            .iter()
+            .filter(|(_, a)| a.line_ending.is_some_and(|le| le != dominant))
+            .map(|(path, _)| path.as_str())
+            .collect()
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum FileType {
     SourceCode(Language),
@@ -167,6 +229,8 @@ pub enum InsightSeverity {
 pub struct IntelligentFileAnalyzer {
     pub config: IntelligenceConfig,
     cache: HashMap<String, FileAnalysis>,
+    /// Line ending most of the repository uses, for consistency warnings
+    repo_line_ending: Option<LineEnding>,
     repository_insights: RepositoryInsights,
     predictive_model: PredictiveModel,
 }
@@ -319,6 +383,12 @@ impl Default for FileAnalysis {
             file_type: FileType::Unknown,
             language: Language::Other("Unknown".to_string()),
             size_bytes: 0,
+            detected_format: None,
+            is_binary: false,
+            generated: None,
+            entropy: 0.0,
+            line_ending: None,
+            lfs_pattern: None,
             security_issues: vec![],
             performance_impact: PerformanceImpact {
                 storage_efficiency: 1.0,
@@ -334,6 +404,7 @@ impl IntelligentFileAnalyzer {
         Self {
             config: IntelligenceConfig::default(),
             cache: HashMap::new(),
+            repo_line_ending: None,
             repository_insights: RepositoryInsights::default(),
             predictive_model: PredictiveModel::default(),
         }
@@ -343,6 +414,7 @@ impl IntelligentFileAnalyzer {
         Self {
             config,
             cache: HashMap::new(),
+            repo_line_ending: None,
             repository_insights: RepositoryInsights::default(),
             predictive_model: PredictiveModel::default(),
         }
@@ -866,9 +938,31 @@ impl IntelligentFileAnalyzer {
             .unwrap_or("")
             .to_lowercase();
 
-        let file_type = self.detect_file_type(&extension, file_size, file_path);
-        let language = self.detect_language(&extension);
-        let contents = fs::read_to_string(file_path).unwrap_or_default();
+        // Content sniffing only ever looks at the start of the file
+        let head = read_head(file_path, SNIFF_BYTES)?;
+        let magic = sniff_magic(&head);
+        let is_binary = magic.is_some() || head.contains(&0);
+        let shebang = shebang_language(&head);
+
+        let file_type = match (&magic, &shebang) {
+            (Some((_, kind)), _) => kind.clone(),
+            (None, _) if is_binary => FileType::Binary,
+            (None, Some(lang)) if extension.is_empty() => FileType::SourceCode(lang.clone()),
+            _ => self.detect_file_type(&extension, file_size, file_path),
+        };
+        let language = match (self.detect_language(&extension), shebang) {
+            (Language::Other(_), Some(lang)) => lang,
+            (lang, _) => lang,
+        };
+        let detected_format = magic
+            .map(|(name, _)| name.to_string())
+            .or_else(|| shebang_interpreter(&head).map(|i| format!("{} script (shebang)", i)));
+
+        let contents = if !is_binary && file_size <= MAX_TEXT_SCAN_BYTES {
+            fs::read_to_string(file_path).unwrap_or_default()
+        } else {
+            String::new()
+        };
 
         let security_issues = if self.config.features.security_analysis {
             self.analyze_security(file_path, &contents)
@@ -885,17 +979,30 @@ impl IntelligentFileAnalyzer {
             }
         };
 
-        let suggestions = self.generate_suggestions(file_path, file_size, &security_issues);
+        let sample = if contents.is_empty() { &head[..] } else { contents.as_bytes() };
+        let generated = if is_binary { None } else { detect_generated(file_path, sample) };
+        let line_ending = if is_binary { None } else { detect_line_ending(sample) };
 
-        let analysis = FileAnalysis {
-            suggested_lfs: file_size > self.config.lfs_threshold_mb * 1024 * 1024,
+        let lfs_threshold = self.config.lfs_threshold_mb * 1024 * 1024;
+        let suggested_lfs =
+            file_size > lfs_threshold || (is_binary && file_size > BINARY_LFS_THRESHOLD_BYTES);
+
+        let mut analysis = FileAnalysis {
+            suggested_lfs,
             file_type,
             language,
             size_bytes: file_size,
+            detected_format,
+            is_binary,
+            generated,
+            entropy: shannon_entropy(&head),
+            line_ending,
+            lfs_pattern: suggested_lfs.then(|| lfs_pattern_for(file_path)),
             security_issues,
             performance_impact,
-            suggestions,
+            suggestions: Vec::new(),
         };
+        analysis.suggestions = self.generate_suggestions(file_path, &analysis);
 
         self.cache.insert(file_path.to_string(), analysis.clone());
         Ok(analysis)
@@ -997,24 +1104,64 @@ impl IntelligentFileAnalyzer {
         }
     }
 
-    fn generate_suggestions(
-        &self,
-        file_path: &str,
-        file_size: u64,
-        security_issues: &[SecurityIssue],
-    ) -> Vec<Suggestion> {
+    fn generate_suggestions(&self, file_path: &str, analysis: &FileAnalysis) -> Vec<Suggestion> {
         let mut suggestions = Vec::new();
+        let extension = Path::new(file_path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("");
 
-        if file_size > 50_000_000 {
-            // 50MB
+        if let Some(pattern) = &analysis.lfs_pattern {
+            let what = if extension.is_empty() {
+                "file".to_string()
+            } else {
+                format!(".{} file", extension.to_lowercase())
+            };
             suggestions.push(Suggestion {
-                action: "Consider using Git LFS for this large file".to_string(),
+                action: format!(
+                    "This {} {} should be LFS-tracked (pattern {})",
+                    format_size(analysis.size_bytes),
+                    what,
+                    pattern
+                ),
                 priority: "Medium".to_string(),
                 category: SuggestionCategory::Performance,
             });
         }
 
-        if !security_issues.is_empty() {
+        if let Some(reason) = &analysis.generated {
+            let action = if reason == "lockfile" {
+                "This is a lockfile; keep it committed but mark it `generated` in .runeattributes so diffs stay collapsed".to_string()
+            } else {
+                format!("This looks generated ({}); consider adding it to .runeignore", reason)
+            };
+            suggestions.push(Suggestion {
+                action,
+                priority: "Low".to_string(),
+                category: SuggestionCategory::Organization,
+            });
+        }
+
+        match (analysis.line_ending, self.repo_line_ending) {
+            (Some(LineEnding::Mixed), _) => suggestions.push(Suggestion {
+                action: "This text file mixes CRLF and LF line endings".to_string(),
+                priority: "Low".to_string(),
+                category: SuggestionCategory::Organization,
+            }),
+            (Some(LineEnding::Crlf), Some(LineEnding::Lf)) => suggestions.push(Suggestion {
+                action: "This text file has CRLF line endings inconsistent with the repo (LF)".to_string(),
+                priority: "Low".to_string(),
+                category: SuggestionCategory::Organization,
+            }),
+            (Some(LineEnding::Lf), Some(LineEnding::Crlf)) => suggestions.push(Suggestion {
+                action: "This text file has LF line endings inconsistent with the repo (CRLF)".to_string(),
+                priority: "Low".to_string(),
+                category: SuggestionCategory::Organization,
+            }),
+            _ => {}
+        }
+
+        if !analysis.security_issues.is_empty() {
             suggestions.push(Suggestion {
                 action: "Review and secure sensitive content".to_string(),
                 priority: "High".to_string(),
@@ -1022,14 +1169,9 @@ impl IntelligentFileAnalyzer {
             });
         }
 
-        let extension = Path::new(file_path)
-            .extension()
-            .and_then(|ext| ext.to_str())
-            .unwrap_or("");
-
         if matches!(extension, "tmp" | "log" | "cache") {
             suggestions.push(Suggestion {
-                action: "Consider adding to .gitignore".to_string(),
+                action: "Consider adding to .runeignore".to_string(),
                 priority: "Low".to_string(),
                 category: SuggestionCategory::Organization,
             });
@@ -1038,11 +1180,65 @@ impl IntelligentFileAnalyzer {
         suggestions
     }
 
+    /// Line ending used by the repository, for CRLF/LF consistency suggestions
+    pub fn set_repo_line_ending(&mut self, line_ending: Option<LineEnding>) {
+        self.repo_line_ending = line_ending;
+    }
+
+    /// Analyze every file below `root` (skipping VCS and build directories)
+    pub fn analyze_directory(&mut self, root: &Path) -> Result<DirectoryAnalysis, std::io::Error> {
+        let mut files = Vec::new();
+        let mut total_size = 0;
+
+        let walker = walkdir::WalkDir::new(root)
+            .into_iter()
+            .filter_entry(|e| e.depth() == 0 || !is_skipped_dir(e));
+        for entry in walker.filter_map(|e| e.ok()) {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let path = entry.path().to_string_lossy().to_string();
+            let analysis = self.analyze_file(&path)?;
+            let rel = entry
+                .path()
+                .strip_prefix(root)
+                .unwrap_or(entry.path())
+                .to_string_lossy()
+                .replace('\\', "/");
+            total_size += analysis.size_bytes;
+            files.push((rel, analysis));
+        }
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let line_ending = dominant_line_ending(files.iter().map(|(_, a)| a.line_ending));
+        let previous = self.repo_line_ending.replace(line_ending.unwrap_or(LineEnding::Lf));
+        for (path, analysis) in &mut files {
+            analysis.suggestions = self.generate_suggestions(path, analysis);
+        }
+        self.repo_line_ending = previous;
+
+        Ok(DirectoryAnalysis { files, total_size, line_ending })
+    }
+
     pub fn display_analysis(&self, analysis: &FileAnalysis) {
         println!("📊 {}", "File Analysis".cyan().bold());
         println!("  Type: {:?}", analysis.file_type);
         println!("  Language: {:?}", analysis.language);
-        println!("  Size: {} bytes", analysis.size_bytes);
+        println!("  Size: {} ({} bytes)", format_size(analysis.size_bytes), analysis.size_bytes);
+        if let Some(format) = &analysis.detected_format {
+            println!("  Detected: {}", format);
+        }
+        println!(
+            "  Content: {}, entropy {:.2} bits/byte",
+            if analysis.is_binary { "binary" } else { "text" },
+            analysis.entropy
+        );
+        if let Some(line_ending) = analysis.line_ending {
+            println!("  Line endings: {:?}", line_ending);
+        }
+        if let Some(reason) = &analysis.generated {
+            println!("  Generated: {}", reason.yellow());
+        }
         println!(
             "  LFS Suggested: {}",
             if analysis.suggested_lfs {
@@ -1237,3 +1433,318 @@ impl PredictiveInsight {
         );
     }
 }
+
+/// Bytes read from the start of a file for content sniffing and entropy
+const SNIFF_BYTES: usize = 8192;
+/// Larger text files are not read in full
+const MAX_TEXT_SCAN_BYTES: u64 = 8 * 1024 * 1024;
+/// Binary files above this size are LFS candidates regardless of the configured threshold
+const BINARY_LFS_THRESHOLD_BYTES: u64 = 10 * 1024 * 1024;
+
+fn read_head(path: &str, limit: usize) -> std::io::Result<Vec<u8>> {
+    use std::io::Read;
+    let mut head = Vec::with_capacity(limit);
+    fs::File::open(path)?.take(limit as u64).read_to_end(&mut head)?;
+    Ok(head)
+}
+
+/// Recognise common binary formats from their magic bytes
+fn sniff_magic(head: &[u8]) -> Option<(&'static str, FileType)> {
+    let at = |offset: usize, magic: &[u8]| head.len() >= offset + magic.len() && &head[offset..offset + magic.len()] == magic;
+
+    if at(0, b"\x89PNG\r\n\x1a\n") {
+        Some(("PNG image", FileType::Media))
+    } else if at(0, &[0xFF, 0xD8, 0xFF]) {
+        Some(("JPEG image", FileType::Media))
+    } else if at(0, b"GIF87a") || at(0, b"GIF89a") {
+        Some(("GIF image", FileType::Media))
+    } else if at(0, b"RIFF") && at(8, b"WAVE") {
+        Some(("WAV audio", FileType::Media))
+    } else if at(0, b"RIFF") && at(8, b"WEBP") {
+        Some(("WebP image", FileType::Media))
+    } else if at(0, b"ID3") || at(0, &[0xFF, 0xFB]) {
+        Some(("MP3 audio", FileType::Media))
+    } else if at(0, b"OggS") {
+        Some(("Ogg media", FileType::Media))
+    } else if at(0, b"fLaC") {
+        Some(("FLAC audio", FileType::Media))
+    } else if at(4, b"ftyp") {
+        Some(("MP4/QuickTime video", FileType::Media))
+    } else if at(0, b"8BPS") {
+        Some(("Photoshop document", FileType::Media))
+    } else if at(0, b"Kaydara FBX Binary") {
+        Some(("FBX model", FileType::Binary))
+    } else if at(0, b"glTF") {
+        Some(("glTF binary model", FileType::Binary))
+    } else if at(0, b"%PDF-") {
+        Some(("PDF document", FileType::Documentation))
+    } else if at(0, b"PK\x03\x04") {
+        Some(("ZIP archive", FileType::Archive))
+    } else if at(0, &[0x1F, 0x8B]) {
+        Some(("gzip archive", FileType::Archive))
+    } else if at(0, b"7z\xBC\xAF\x27\x1C") {
+        Some(("7-Zip archive", FileType::Archive))
+    } else if at(0, b"\x7fELF") {
+        Some(("ELF executable", FileType::Binary))
+    } else if at(0, b"MZ") {
+        Some(("Windows executable", FileType::Binary))
+    } else if at(0, &[0xCF, 0xFA, 0xED, 0xFE]) || at(0, &[0xCA, 0xFE, 0xBA, 0xBE]) {
+        Some(("Mach-O executable", FileType::Binary))
+    } else {
+        None
+    }
+}
+
+/// Interpreter named on a `#!` line, without path or `env`
+fn shebang_interpreter(head: &[u8]) -> Option<String> {
+    let first = head.strip_prefix(b"#!")?;
+    let line = String::from_utf8_lossy(first.split(|b| *b == b'\n').next()?).to_string();
+    let mut parts = line.split_whitespace();
+    let mut program = parts.next()?.rsplit('/').next()?.to_string();
+    if program == "env" {
+        program = parts.find(|p| !p.starts_with('-'))?.to_string();
+    }
+    Some(program)
+}
+
+fn shebang_language(head: &[u8]) -> Option<Language> {
+    let interpreter = shebang_interpreter(head)?;
+    let name = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    Some(match name {
+        "python" => Language::Python,
+        "node" | "deno" | "bun" => Language::JavaScript,
+        "ts-node" => Language::TypeScript,
+        "sh" | "bash" | "zsh" | "dash" | "ksh" | "fish" => Language::Other("shell".to_string()),
+        other => Language::Other(other.to_string()),
+    })
+}
+
+/// Why a text file looks machine-generated: lockfiles, minified bundles, codegen output
+fn detect_generated(file_path: &str, sample: &[u8]) -> Option<String> {
+    let name = Path::new(file_path)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("");
+
+    const LOCKFILES: &[&str] = &[
+        "Cargo.lock",
+        "package-lock.json",
+        "yarn.lock",
+        "pnpm-lock.yaml",
+        "poetry.lock",
+        "Pipfile.lock",
+        "Gemfile.lock",
+        "composer.lock",
+        "go.sum",
+    ];
+    if LOCKFILES.contains(&name) {
+        return Some("lockfile".to_string());
+    }
+
+    if name.ends_with(".pb.go")
+        || name.ends_with("_pb2.py")
+        || name.ends_with(".pb.h")
+        || name.ends_with(".pb.cc")
+        || name.ends_with("_pb.js")
+    {
+        return Some("protobuf output".to_string());
+    }
+
+    let text = String::from_utf8_lossy(&sample[..sample.len().min(SNIFF_BYTES)]);
+    let header: String = text.lines().take(5).collect::<Vec<_>>().join("\n");
+    if header.contains("Code generated by protoc") || header.contains("Generated by the protocol buffer compiler") {
+        return Some("protobuf output".to_string());
+    }
+    if header.contains("@generated") || header.contains("DO NOT EDIT") {
+        return Some("marked as generated".to_string());
+    }
+
+    let is_script = name.ends_with(".js") || name.ends_with(".css") || name.ends_with(".mjs");
+    if name.ends_with(".min.js") || name.ends_with(".min.css") {
+        return Some("minified bundle".to_string());
+    }
+    if is_script && sample.len() > 1024 {
+        let lines = sample.iter().filter(|b| **b == b'\n').count().max(1);
+        if sample.len() / lines > 500 {
+            return Some("minified bundle".to_string());
+        }
+    }
+    None
+}
+
+fn dominant_line_ending(endings: impl Iterator<Item = Option<LineEnding>>) -> Option<LineEnding> {
+    let (mut lf, mut crlf) = (0usize, 0usize);
+    for ending in endings.flatten() {
+        match ending {
+            LineEnding::Lf => lf += 1,
+            LineEnding::Crlf => crlf += 1,
+            LineEnding::Mixed => {}
+        }
+    }
+    match (lf, crlf) {
+        (0, 0) => None,
+        _ if crlf > lf => Some(LineEnding::Crlf),
+        _ => Some(LineEnding::Lf),
+    }
+}
+
+/// Most common line ending among a sample of text files in the repository
+pub fn detect_repository_line_ending(root: &Path) -> Option<LineEnding> {
+    let walker = walkdir::WalkDir::new(root)
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !is_skipped_dir(e));
+    let endings = walker
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .take(200)
+        .filter_map(|e| read_head(&e.path().to_string_lossy(), SNIFF_BYTES).ok())
+        .filter(|head| sniff_magic(head).is_none() && !head.contains(&0))
+        .map(|head| detect_line_ending(&head));
+    dominant_line_ending(endings)
+}
+
+fn is_skipped_dir(entry: &walkdir::DirEntry) -> bool {
+    entry.file_type().is_dir()
+        && matches!(
+            entry.file_name().to_str(),
+            Some(".rune" | ".git" | "target" | "node_modules")
+        )
+}
+
+fn shannon_entropy(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+    let mut counts = [0usize; 256];
+    for b in data {
+        counts[*b as usize] += 1;
+    }
+    let len = data.len() as f64;
+    counts
+        .iter()
+        .filter(|c| **c > 0)
+        .map(|c| {
+            let p = *c as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+fn lfs_pattern_for(file_path: &str) -> String {
+    let path = Path::new(file_path);
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => format!("*.{}", ext.to_lowercase()),
+        None => path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or(file_path)
+            .to_string(),
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{}B", bytes)
+    } else {
+        format!("{:.0}{}", size, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn analyze(dir: &TempDir, name: &str, content: &[u8]) -> FileAnalysis {
+        let path = dir.path().join(name);
+        fs::write(&path, content).unwrap();
+        IntelligentFileAnalyzer::new()
+            .analyze_file(path.to_str().unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_png_detected_from_magic_bytes() {
+        let dir = TempDir::new().unwrap();
+        let mut png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR".to_vec();
+        png.extend_from_slice(&[0u8; 64]);
+
+        // The extension lies; the content decides
+        let analysis = analyze(&dir, "logo.dat", &png);
+        assert!(analysis.is_binary);
+        assert_eq!(analysis.file_type, FileType::Media);
+        assert_eq!(analysis.detected_format.as_deref(), Some("PNG image"));
+        assert!(!analysis.suggested_lfs);
+    }
+
+    #[test]
+    fn test_shebang_sets_language() {
+        let dir = TempDir::new().unwrap();
+        let analysis = analyze(&dir, "deploy", b"#!/usr/bin/env python3\nprint('hi')\n");
+        assert_eq!(analysis.language, Language::Python);
+        assert_eq!(analysis.file_type, FileType::SourceCode(Language::Python));
+        assert!(!analysis.is_binary);
+    }
+
+    #[test]
+    fn test_minified_js_flagged_generated() {
+        let dir = TempDir::new().unwrap();
+        let bundle = "var a=function(b){return b+1};".repeat(200);
+        let analysis = analyze(&dir, "bundle.js", bundle.as_bytes());
+        assert_eq!(analysis.generated.as_deref(), Some("minified bundle"));
+        assert!(analysis.suggestions.iter().any(|s| s.action.contains(".runeignore")));
+
+        let lock = analyze(&dir, "Cargo.lock", b"[[package]]\nname = \"x\"\n");
+        assert_eq!(lock.generated.as_deref(), Some("lockfile"));
+    }
+
+    #[test]
+    fn test_large_fbx_recommends_lfs_pattern() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("hero.FBX");
+        let file = fs::File::create(&path).unwrap();
+        {
+            use std::io::Write;
+            let mut writer = &file;
+            writer.write_all(b"Kaydara FBX Binary  \x00\x1a\x00").unwrap();
+        }
+        // Sparse file: 180MB without writing 180MB
+        file.set_len(180 * 1024 * 1024).unwrap();
+
+        let analysis = IntelligentFileAnalyzer::new()
+            .analyze_file(path.to_str().unwrap())
+            .unwrap();
+        assert!(analysis.is_binary);
+        assert_eq!(analysis.detected_format.as_deref(), Some("FBX model"));
+        assert!(analysis.suggested_lfs);
+        assert_eq!(analysis.lfs_pattern.as_deref(), Some("*.fbx"));
+        assert!(analysis
+            .suggestions
+            .iter()
+            .any(|s| s.action == "This 180MB .fbx file should be LFS-tracked (pattern *.fbx)"));
+    }
+
+    #[test]
+    fn test_directory_flags_crlf_outlier() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("a.txt"), "one\ntwo\n").unwrap();
+        fs::write(dir.path().join("b.txt"), "one\ntwo\n").unwrap();
+        fs::write(dir.path().join("c.txt"), "one\r\ntwo\r\n").unwrap();
+        fs::create_dir_all(dir.path().join(".rune")).unwrap();
+        fs::write(dir.path().join(".rune/ignored.txt"), "x\r\n").unwrap();
+
+        let report = IntelligentFileAnalyzer::new().analyze_directory(dir.path()).unwrap();
+        assert_eq!(report.files.len(), 3);
+        assert_eq!(report.line_ending, Some(LineEnding::Lf));
+        assert_eq!(report.line_ending_mismatches(), vec!["c.txt"]);
+        let (_, c) = &report.files[2];
+        assert!(c.suggestions.iter().any(|s| s.action.contains("CRLF line endings inconsistent")));
+    }
+}
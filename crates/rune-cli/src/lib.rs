@@ -1,5 +1,6 @@
 pub mod api;
 pub mod commands;
+pub mod exit_codes;
 pub mod style;
 pub mod intelligence;
 
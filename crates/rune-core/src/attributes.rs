@@ -0,0 +1,360 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use regex::Regex;
+use anyhow::{Context, Result};
+
+use crate::ignore::IgnoreEngine;
+
+/// Per-path attributes, loaded from `.runeattributes` files.
+///
+/// Each non-comment line is a pattern followed by whitespace separated
+/// attributes:
+///
+/// ```text
+/// *.png        binary lfs lockable
+/// dist/**      generated
+/// *.lock       merge=ours -diff
+/// ```
+///
+/// `attr` sets an attribute, `-attr` unsets it, `!attr` returns it to the
+/// unspecified state and `attr=value` assigns a value. Later lines override
+/// earlier ones, and files in subdirectories override their parents, so the
+/// last matching rule always wins.
+pub const ATTRIBUTES_FILE: &str = ".runeattributes";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttrValue {
+    /// `attr`
+    Set,
+    /// `-attr`
+    Unset,
+    /// `attr=value`
+    Value(String),
+}
+
+impl AttrValue {
+    /// Render the value the way `rune check-attr` prints it
+    pub fn display(&self) -> &str {
+        match self {
+            AttrValue::Set => "set",
+            AttrValue::Unset => "unset",
+            AttrValue::Value(v) => v,
+        }
+    }
+}
+
+/// How diff output should treat a path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffHandling {
+    Text,
+    Binary,
+    Generated,
+}
+
+#[derive(Debug, Clone)]
+struct AttrRule {
+    /// Directory of the `.runeattributes` file, relative to the root ("" for the root)
+    base: String,
+    /// Match only against the file name when the pattern has no slash
+    basename_only: bool,
+    regex: Regex,
+    /// `None` resets the attribute to unspecified (`!attr`)
+    attrs: Vec<(String, Option<AttrValue>)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AttributeEngine {
+    project_root: PathBuf,
+    rules: Vec<AttrRule>,
+}
+
+impl AttributeEngine {
+    /// Load the root `.runeattributes` and every nested one below it
+    pub fn new<P: AsRef<Path>>(project_root: P) -> Result<Self> {
+        let project_root = project_root.as_ref().to_path_buf();
+        let mut files = Vec::new();
+        collect_attribute_files(&project_root, &mut files)?;
+
+        // Shallower files first so deeper ones are applied later and win.
+        files.sort_by_key(|p| (p.components().count(), p.clone()));
+
+        let mut engine = Self { project_root: project_root.clone(), rules: Vec::new() };
+        for file in files {
+            let content = fs::read_to_string(&file)
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            let base = file
+                .parent()
+                .and_then(|p| p.strip_prefix(&project_root).ok())
+                .map(normalize_path)
+                .unwrap_or_default();
+            engine.add_rules(&base, &content);
+        }
+        Ok(engine)
+    }
+
+    /// Build an engine from in-memory `.runeattributes` content for the root directory
+    pub fn from_content<P: AsRef<Path>>(project_root: P, content: &str) -> Self {
+        let mut engine = Self { project_root: project_root.as_ref().to_path_buf(), rules: Vec::new() };
+        engine.add_rules("", content);
+        engine
+    }
+
+    /// Parse `content` as if it were a `.runeattributes` file located in `base`
+    pub fn add_rules(&mut self, base: &str, content: &str) {
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = line.split_whitespace();
+            let Some(pattern) = parts.next() else { continue };
+
+            let mut attrs = Vec::new();
+            for token in parts {
+                parse_attr_token(token, &mut attrs);
+            }
+            if attrs.is_empty() {
+                continue;
+            }
+
+            let anchored = pattern.trim_start_matches('/');
+            let basename_only = !pattern.contains('/');
+            let Ok(regex) = IgnoreEngine::pattern_to_regex(anchored) else {
+                continue;
+            };
+
+            self.rules.push(AttrRule {
+                base: base.trim_matches('/').to_string(),
+                basename_only,
+                regex,
+                attrs,
+            });
+        }
+    }
+
+    /// Every specified attribute for `path`, after applying all matching rules
+    pub fn attributes_for<P: AsRef<Path>>(&self, path: P) -> BTreeMap<String, AttrValue> {
+        let rel = self.relative(path.as_ref());
+        let mut result = BTreeMap::new();
+
+        for rule in &self.rules {
+            if !rule_matches(rule, &rel) {
+                continue;
+            }
+            for (name, value) in &rule.attrs {
+                match value {
+                    Some(v) => {
+                        result.insert(name.clone(), v.clone());
+                    }
+                    None => {
+                        result.remove(name);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Value of a single attribute, `None` when unspecified
+    pub fn get<P: AsRef<Path>>(&self, path: P, attr: &str) -> Option<AttrValue> {
+        self.attributes_for(path).remove(attr)
+    }
+
+    /// `binary` or `-diff` both mean content should never be shown as text
+    pub fn is_binary<P: AsRef<Path>>(&self, path: P) -> bool {
+        let attrs = self.attributes_for(path);
+        matches!(attrs.get("binary"), Some(AttrValue::Set))
+            || matches!(attrs.get("diff"), Some(AttrValue::Unset))
+    }
+
+    /// Generated files are diffed, but collapsed by default
+    pub fn is_generated<P: AsRef<Path>>(&self, path: P) -> bool {
+        let attrs = self.attributes_for(path);
+        ["generated", "linguist-generated"]
+            .iter()
+            .any(|name| matches!(attrs.get(*name), Some(AttrValue::Set)))
+    }
+
+    pub fn diff_handling<P: AsRef<Path>>(&self, path: P) -> DiffHandling {
+        let path = path.as_ref();
+        if self.is_binary(path) {
+            DiffHandling::Binary
+        } else if self.is_generated(path) {
+            DiffHandling::Generated
+        } else {
+            DiffHandling::Text
+        }
+    }
+
+    /// Named merge driver from `merge=<driver>`; `-merge` selects the `binary` driver
+    pub fn merge_driver<P: AsRef<Path>>(&self, path: P) -> Option<String> {
+        let attrs = self.attributes_for(path);
+        match attrs.get("merge") {
+            Some(AttrValue::Value(driver)) => Some(driver.clone()),
+            Some(AttrValue::Unset) => Some("binary".to_string()),
+            _ if matches!(attrs.get("binary"), Some(AttrValue::Set)) => Some("binary".to_string()),
+            _ => None,
+        }
+    }
+
+    /// LFS tracking as decided by attributes: `Some(true)` for `lfs` or
+    /// `filter=lfs`, `Some(false)` for `-lfs`, `None` when attributes say nothing
+    pub fn lfs_tracked<P: AsRef<Path>>(&self, path: P) -> Option<bool> {
+        let attrs = self.attributes_for(path);
+        match attrs.get("lfs") {
+            Some(AttrValue::Set) => return Some(true),
+            Some(AttrValue::Unset) => return Some(false),
+            _ => {}
+        }
+        match attrs.get("filter") {
+            Some(AttrValue::Value(v)) if v == "lfs" => Some(true),
+            _ => None,
+        }
+    }
+
+    /// Files that should be locked before they are edited
+    pub fn is_lockable<P: AsRef<Path>>(&self, path: P) -> bool {
+        matches!(self.get(path, "lockable"), Some(AttrValue::Set))
+    }
+
+    pub fn rule_count(&self) -> usize {
+        self.rules.len()
+    }
+
+    fn relative(&self, path: &Path) -> String {
+        let rel = if path.is_absolute() {
+            path.strip_prefix(&self.project_root).unwrap_or(path)
+        } else {
+            path
+        };
+        normalize_path(rel)
+    }
+}
+
+fn rule_matches(rule: &AttrRule, rel: &str) -> bool {
+    let scoped = if rule.base.is_empty() {
+        rel
+    } else {
+        match rel.strip_prefix(&rule.base).and_then(|r| r.strip_prefix('/')) {
+            Some(r) => r,
+            None => return false,
+        }
+    };
+
+    if rule.basename_only {
+        let name = scoped.rsplit('/').next().unwrap_or(scoped);
+        rule.regex.is_match(name)
+    } else {
+        rule.regex.is_match(scoped)
+    }
+}
+
+fn parse_attr_token(token: &str, attrs: &mut Vec<(String, Option<AttrValue>)>) {
+    if let Some(name) = token.strip_prefix('-') {
+        attrs.push((name.to_string(), Some(AttrValue::Unset)));
+    } else if let Some(name) = token.strip_prefix('!') {
+        attrs.push((name.to_string(), None));
+    } else if let Some((name, value)) = token.split_once('=') {
+        attrs.push((name.to_string(), Some(AttrValue::Value(value.to_string()))));
+    } else if token == "binary" {
+        // Macro attribute, same expansion as git's
+        attrs.push(("binary".to_string(), Some(AttrValue::Set)));
+        attrs.push(("diff".to_string(), Some(AttrValue::Unset)));
+        attrs.push(("merge".to_string(), Some(AttrValue::Unset)));
+        attrs.push(("text".to_string(), Some(AttrValue::Unset)));
+    } else {
+        attrs.push((token.to_string(), Some(AttrValue::Set)));
+    }
+}
+
+fn collect_attribute_files(dir: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Ok(()),
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if name == ".rune" || name == ".git" || name == "target" || name == "node_modules" {
+                continue;
+            }
+            collect_attribute_files(&path, out)?;
+        } else if name == ATTRIBUTES_FILE {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn normalize_path(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/").trim_start_matches("./").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_last_match_wins() {
+        let engine = AttributeEngine::from_content("/repo", "*.txt diff=words\nnotes.txt -diff\n");
+        assert_eq!(engine.get("docs/readme.txt", "diff"), Some(AttrValue::Value("words".into())));
+        assert_eq!(engine.get("notes.txt", "diff"), Some(AttrValue::Unset));
+        assert_eq!(engine.get("main.rs", "diff"), None);
+    }
+
+    #[test]
+    fn test_unspecify_and_values() {
+        let engine = AttributeEngine::from_content("/repo", "*.dat lockable merge=ours\nkeep.dat !lockable\n");
+        assert!(engine.is_lockable("a/b.dat"));
+        assert!(!engine.is_lockable("keep.dat"));
+        assert_eq!(engine.merge_driver("keep.dat"), Some("ours".to_string()));
+    }
+
+    #[test]
+    fn test_binary_macro() {
+        let engine = AttributeEngine::from_content("/repo", "*.png binary\ndist/** generated\n");
+        assert!(engine.is_binary("img/logo.png"));
+        assert_eq!(engine.get("img/logo.png", "text"), Some(AttrValue::Unset));
+        assert_eq!(engine.merge_driver("logo.png"), Some("binary".to_string()));
+        assert_eq!(engine.diff_handling("dist/app.js"), DiffHandling::Generated);
+        assert_eq!(engine.diff_handling("src/app.js"), DiffHandling::Text);
+    }
+
+    #[test]
+    fn test_anchored_patterns() {
+        let engine = AttributeEngine::from_content("/repo", "/build/*.bin lfs\n");
+        assert_eq!(engine.lfs_tracked("build/out.bin"), Some(true));
+        assert_eq!(engine.lfs_tracked("sub/build/out.bin"), None);
+    }
+
+    #[test]
+    fn test_nested_file_overrides_parent() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        fs::create_dir_all(root.join("assets/raw")).unwrap();
+        fs::create_dir_all(root.join(".rune")).unwrap();
+        fs::write(root.join(ATTRIBUTES_FILE), "*.psd lfs lockable\n").unwrap();
+        fs::write(root.join("assets/raw").join(ATTRIBUTES_FILE), "*.psd -lfs\n").unwrap();
+        fs::write(root.join(".rune").join(ATTRIBUTES_FILE), "*.psd -lockable\n").unwrap();
+
+        let engine = AttributeEngine::new(root).unwrap();
+        assert_eq!(engine.rule_count(), 2);
+        assert_eq!(engine.lfs_tracked("assets/cover.psd"), Some(true));
+        assert_eq!(engine.lfs_tracked(root.join("assets/raw/cover.psd")), Some(false));
+        // The nested file only touched lfs, lockable still comes from the root
+        assert!(engine.is_lockable("assets/raw/cover.psd"));
+    }
+
+    #[test]
+    fn test_filter_lfs() {
+        let engine = AttributeEngine::from_content("/repo", "*.zip filter=lfs\n");
+        assert_eq!(engine.lfs_tracked("release.zip"), Some(true));
+        assert_eq!(engine.lfs_tracked("release.tar"), None);
+    }
+}
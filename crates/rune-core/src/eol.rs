@@ -0,0 +1,276 @@
+use std::borrow::Cow;
+use std::path::Path;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::attributes::{AttrValue, AttributeEngine};
+
+/// Line ending conversion on stage and checkout (`core.autocrlf`).
+///
+/// * `true`  - store LF, check text files out with CRLF
+/// * `input` - store LF, check files out as stored
+/// * `false` - never convert unless `.runeattributes` asks for it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AutoCrlf {
+    True,
+    Input,
+    #[default]
+    False,
+}
+
+impl AutoCrlf {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AutoCrlf::True => "true",
+            AutoCrlf::Input => "input",
+            AutoCrlf::False => "false",
+        }
+    }
+}
+
+impl std::str::FromStr for AutoCrlf {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "true" | "yes" | "on" => Ok(AutoCrlf::True),
+            "input" => Ok(AutoCrlf::Input),
+            "false" | "no" | "off" => Ok(AutoCrlf::False),
+            other => anyhow::bail!("invalid autocrlf value '{}': expected true, input or false", other),
+        }
+    }
+}
+
+// Accepts both `autocrlf = true` and `autocrlf = "input"` in config.toml
+impl<'de> Deserialize<'de> for AutoCrlf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Bool(bool),
+            Str(String),
+        }
+        match Raw::deserialize(deserializer)? {
+            Raw::Bool(true) => Ok(AutoCrlf::True),
+            Raw::Bool(false) => Ok(AutoCrlf::False),
+            Raw::Str(s) => s.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+impl Serialize for AutoCrlf {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            AutoCrlf::True => serializer.serialize_bool(true),
+            AutoCrlf::False => serializer.serialize_bool(false),
+            AutoCrlf::Input => serializer.serialize_str("input"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+    /// Both styles occur in the same file
+    Mixed,
+}
+
+/// Line ending style of `content`, `None` when it has no line breaks
+pub fn detect_line_ending(content: &[u8]) -> Option<LineEnding> {
+    let crlf = content.windows(2).filter(|w| w == b"\r\n").count();
+    let lf = content.iter().filter(|b| **b == b'\n').count() - crlf;
+    match (crlf, lf) {
+        (0, 0) => None,
+        (0, _) => Some(LineEnding::Lf),
+        (_, 0) => Some(LineEnding::Crlf),
+        _ => Some(LineEnding::Mixed),
+    }
+}
+
+/// Same heuristic as most VCSs: a NUL byte near the start means binary
+pub fn looks_binary(content: &[u8]) -> bool {
+    content[..content.len().min(8000)].contains(&0)
+}
+
+pub fn to_lf(content: &[u8]) -> Cow<'_, [u8]> {
+    if !content.windows(2).any(|w| w == b"\r\n") {
+        return Cow::Borrowed(content);
+    }
+    let mut out = Vec::with_capacity(content.len());
+    let mut i = 0;
+    while i < content.len() {
+        if content[i] == b'\r' && content.get(i + 1) == Some(&b'\n') {
+            i += 1;
+            continue;
+        }
+        out.push(content[i]);
+        i += 1;
+    }
+    Cow::Owned(out)
+}
+
+pub fn to_crlf(content: &[u8]) -> Cow<'_, [u8]> {
+    let normalized = to_lf(content);
+    if !normalized.contains(&b'\n') {
+        return normalized;
+    }
+    let mut out = Vec::with_capacity(normalized.len() + normalized.len() / 32);
+    for b in normalized.iter() {
+        if *b == b'\n' {
+            out.push(b'\r');
+        }
+        out.push(*b);
+    }
+    Cow::Owned(out)
+}
+
+/// Decides per path whether content is normalized when staged and which
+/// line ending it gets when written back to the working tree.
+///
+/// `.runeattributes` overrides the `core.autocrlf` setting: `-text` (or
+/// `binary`) disables conversion, `text` forces it even for content that
+/// looks binary, and `eol=lf`/`eol=crlf` pick the checkout ending.
+pub struct EolFilter {
+    autocrlf: AutoCrlf,
+    attributes: Option<AttributeEngine>,
+}
+
+impl EolFilter {
+    pub fn new(autocrlf: AutoCrlf, attributes: Option<AttributeEngine>) -> Self {
+        Self { autocrlf, attributes }
+    }
+
+    pub fn attributes(&self) -> Option<&AttributeEngine> {
+        self.attributes.as_ref()
+    }
+
+    /// `Some(true)` when attributes force text handling, `Some(false)` when
+    /// they forbid it, `None` to decide from the content
+    fn text_attribute(&self, path: &Path) -> Option<bool> {
+        let attributes = self.attributes.as_ref()?;
+        if attributes.is_binary(path) {
+            return Some(false);
+        }
+        match attributes.get(path, "text") {
+            Some(AttrValue::Set) => Some(true),
+            Some(AttrValue::Unset) => Some(false),
+            Some(AttrValue::Value(v)) if v == "auto" => None,
+            _ => self.eol_attribute(path).map(|_| true),
+        }
+    }
+
+    fn eol_attribute(&self, path: &Path) -> Option<LineEnding> {
+        match self.attributes.as_ref()?.get(path, "eol") {
+            Some(AttrValue::Value(v)) if v == "lf" => Some(LineEnding::Lf),
+            Some(AttrValue::Value(v)) if v == "crlf" => Some(LineEnding::Crlf),
+            _ => None,
+        }
+    }
+
+    /// Whether line endings of `path` are managed at all
+    pub fn is_text(&self, path: &Path, content: &[u8]) -> bool {
+        match self.text_attribute(path) {
+            Some(text) => text,
+            None => !looks_binary(content),
+        }
+    }
+
+    fn converts(&self, path: &Path) -> bool {
+        self.autocrlf != AutoCrlf::False
+            || self.text_attribute(path) == Some(true)
+    }
+
+    /// Content as it should be stored: CRLF becomes LF for text files
+    pub fn to_store<'c>(&self, path: &Path, content: &'c [u8]) -> Cow<'c, [u8]> {
+        if self.converts(path) && self.is_text(path, content) {
+            to_lf(content)
+        } else {
+            Cow::Borrowed(content)
+        }
+    }
+
+    /// Content as it should be written to the working tree
+    pub fn to_worktree<'c>(&self, path: &Path, content: &'c [u8]) -> Cow<'c, [u8]> {
+        if !self.is_text(path, content) {
+            return Cow::Borrowed(content);
+        }
+        let ending = match self.eol_attribute(path) {
+            Some(ending) => ending,
+            None if self.autocrlf == AutoCrlf::True => LineEnding::Crlf,
+            // `text` without `eol` means the platform's native ending
+            None if self.text_attribute(path) == Some(true) && cfg!(windows) => LineEnding::Crlf,
+            None => return Cow::Borrowed(content),
+        };
+        match ending {
+            LineEnding::Crlf => to_crlf(content),
+            _ => to_lf(content),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_line_ending() {
+        assert_eq!(detect_line_ending(b"a\nb\n"), Some(LineEnding::Lf));
+        assert_eq!(detect_line_ending(b"a\r\nb\r\n"), Some(LineEnding::Crlf));
+        assert_eq!(detect_line_ending(b"a\r\nb\n"), Some(LineEnding::Mixed));
+        assert_eq!(detect_line_ending(b"no newline"), None);
+    }
+
+    #[test]
+    fn test_conversions_round_trip() {
+        assert_eq!(&*to_lf(b"a\r\nb\nc\r\n"), b"a\nb\nc\n");
+        assert_eq!(&*to_crlf(b"a\nb\r\n"), b"a\r\nb\r\n");
+        // A lone CR is not a line ending
+        assert_eq!(&*to_lf(b"a\rb\r\n"), b"a\rb\n");
+    }
+
+    #[test]
+    fn test_autocrlf_modes() {
+        let path = Path::new("notes.txt");
+        let input = EolFilter::new(AutoCrlf::Input, None);
+        assert_eq!(&*input.to_store(path, b"x\r\n"), b"x\n");
+        assert_eq!(&*input.to_worktree(path, b"x\n"), b"x\n");
+
+        let always = EolFilter::new(AutoCrlf::True, None);
+        assert_eq!(&*always.to_worktree(path, b"x\n"), b"x\r\n");
+
+        let off = EolFilter::new(AutoCrlf::False, None);
+        assert_eq!(&*off.to_store(path, b"x\r\n"), b"x\r\n");
+
+        // Binary content is never touched
+        assert_eq!(&*always.to_store(path, b"\0\r\n"), b"\0\r\n");
+        assert_eq!(&*always.to_worktree(path, b"\0\n"), b"\0\n");
+    }
+
+    #[test]
+    fn test_attributes_override_autocrlf() {
+        let attrs = AttributeEngine::from_content(
+            "/repo",
+            "*.bat eol=crlf\n*.sh eol=lf\n*.dat -text\n",
+        );
+        let filter = EolFilter::new(AutoCrlf::True, Some(attrs.clone()));
+        assert_eq!(&*filter.to_worktree(Path::new("run.sh"), b"x\n"), b"x\n");
+        assert_eq!(&*filter.to_store(Path::new("data.dat"), b"x\r\n"), b"x\r\n");
+
+        let off = EolFilter::new(AutoCrlf::False, Some(attrs.clone()));
+        assert_eq!(&*off.to_store(Path::new("build.bat"), b"x\r\n"), b"x\n");
+        assert_eq!(&*off.to_worktree(Path::new("build.bat"), b"x\n"), b"x\r\n");
+    }
+
+    #[test]
+    fn test_autocrlf_parses_bool_and_string() {
+        #[derive(Deserialize)]
+        struct Cfg {
+            autocrlf: AutoCrlf,
+        }
+        let parse = |s: &str| serde_json::from_str::<Cfg>(s).unwrap().autocrlf;
+        assert_eq!(parse(r#"{"autocrlf": true}"#), AutoCrlf::True);
+        assert_eq!(parse(r#"{"autocrlf": "input"}"#), AutoCrlf::Input);
+        assert_eq!(parse(r#"{"autocrlf": "false"}"#), AutoCrlf::False);
+    }
+}
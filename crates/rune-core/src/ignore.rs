@@ -142,6 +142,32 @@ impl IgnoreConfig {
         Ok(serde_yaml::from_str(&content).context("Failed to parse ignore config")?)
     }
 
+    /// Add the rules of the built-in template `name` as project rules.
+    /// Returns `false` when the template was already applied.
+    pub fn apply_template(&mut self, name: &str) -> Result<bool> {
+        let template = IgnoreEngine::get_builtin_templates()
+            .into_iter()
+            .find(|t| t.name == name)
+            .with_context(|| format!("Unknown ignore template '{}'", name))?;
+        if self.templates.contains(&template.name) {
+            return Ok(false);
+        }
+        self.templates.push(template.name.clone());
+        for pattern in &template.patterns {
+            if self.project.iter().any(|r| &r.pattern == pattern) {
+                continue;
+            }
+            self.project.push(IgnoreRule {
+                pattern: pattern.clone(),
+                rule_type: RuleType::Ignore,
+                priority: 50,
+                description: Some(format!("Template: {}", template.description)),
+                condition: None,
+            });
+        }
+        Ok(true)
+    }
+
     /// Save ignore configuration to file
     pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
         let content = serde_yaml::to_string(self).context("Failed to serialize ignore config")?;
@@ -318,6 +344,39 @@ impl IgnoreEngine {
                 ],
                 auto_detect: vec!["*.csproj".to_string(), "*.sln".to_string(), "*.fsproj".to_string()],
             },
+            IgnoreTemplate {
+                name: "unity".to_string(),
+                description: "Unity project".to_string(),
+                patterns: vec![
+                    "Library/".to_string(),
+                    "Temp/".to_string(),
+                    "Obj/".to_string(),
+                    "Build/".to_string(),
+                    "Builds/".to_string(),
+                    "Logs/".to_string(),
+                    "UserSettings/".to_string(),
+                    "MemoryCaptures/".to_string(),
+                    "*.pidb.meta".to_string(),
+                    "*.pdb.meta".to_string(),
+                    "crashlytics-build.properties".to_string(),
+                ],
+                auto_detect: vec!["ProjectSettings/ProjectVersion.txt".to_string()],
+            },
+            IgnoreTemplate {
+                name: "unreal".to_string(),
+                description: "Unreal Engine project".to_string(),
+                patterns: vec![
+                    "Binaries/".to_string(),
+                    "DerivedDataCache/".to_string(),
+                    "Intermediate/".to_string(),
+                    "Saved/".to_string(),
+                    ".vs/".to_string(),
+                    "*.VC.db".to_string(),
+                    "*.opensdf".to_string(),
+                    "*.sdf".to_string(),
+                ],
+                auto_detect: vec!["Config/DefaultEngine.ini".to_string()],
+            },
         ]
     }
 
@@ -342,7 +401,7 @@ impl IgnoreEngine {
     }
 
     /// Convert simplified pattern syntax to regex
-    fn pattern_to_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    pub(crate) fn pattern_to_regex(pattern: &str) -> Result<Regex, regex::Error> {
         let mut regex_pattern = String::new();
         
         // Start with line beginning
@@ -592,6 +651,222 @@ impl IgnoreEngine {
         self.config.save_to_file(config_path)?;
         Ok(())
     }
+
+    /// Find project rules that can never decide a path and, unless
+    /// `dry_run`, remove them.
+    ///
+    /// A rule goes when another rule matches everything it matches and is
+    /// either evaluated first, or is of the same type with no rule of the
+    /// opposite type evaluated between the two. Global rules are never
+    /// removed but do count as the covering rule. Conditional rules are
+    /// left alone since they never decide anything yet.
+    pub fn optimize(&mut self, dry_run: bool) -> OptimizationReport {
+        // Evaluation order: priority, then global before project, then file order
+        let mut ranked: Vec<(bool, usize, &IgnoreRule)> = self
+            .config
+            .global
+            .iter()
+            .enumerate()
+            .map(|(i, r)| (false, i, r))
+            .chain(self.config.project.iter().enumerate().map(|(i, r)| (true, i, r)))
+            .collect();
+        ranked.sort_by_key(|(_, _, r)| std::cmp::Reverse(r.priority));
+        let globs: Vec<Vec<GlobToken>> = ranked.iter().map(|(_, _, r)| GlobToken::parse(&r.pattern)).collect();
+        let decides = |rule: &IgnoreRule| !matches!(rule.rule_type, RuleType::Conditional);
+        let same_type = |a: &IgnoreRule, b: &IgnoreRule| std::mem::discriminant(&a.rule_type) == std::mem::discriminant(&b.rule_type);
+
+        let mut kept = vec![true; ranked.len()];
+        let mut changes = Vec::new();
+        for r in 0..ranked.len() {
+            let (is_project, _, rule) = ranked[r];
+            if !is_project || !decides(rule) {
+                continue;
+            }
+            let opposite_between = |lo: usize, hi: usize, kept: &[bool]| {
+                (lo + 1..hi).any(|t| kept[t] && decides(ranked[t].2) && !same_type(ranked[t].2, rule))
+            };
+            let coverer = (0..ranked.len()).find(|&s| {
+                let other = ranked[s].2;
+                if s == r || !kept[s] || !decides(other) || !GlobToken::covers(&globs[s], &globs[r]) {
+                    return false;
+                }
+                s < r || (same_type(other, rule) && !opposite_between(r, s, &kept))
+            });
+            let Some(s) = coverer else { continue };
+            let other = ranked[s].2;
+            let reason = if !same_type(other, rule) {
+                OptimizationReason::Shadowed
+            } else if other.pattern == rule.pattern {
+                OptimizationReason::Duplicate
+            } else {
+                OptimizationReason::Subsumed
+            };
+            kept[r] = false;
+            changes.push(RuleOptimization { removed: rule.clone(), reason, kept: other.clone() });
+        }
+
+        let mut removed: Vec<usize> =
+            ranked.iter().zip(&kept).filter(|((p, _, _), k)| *p && !**k).map(|((_, i, _), _)| *i).collect();
+        let rules_before = self.config.project.len();
+        if !dry_run && !removed.is_empty() {
+            removed.sort_unstable();
+            for i in removed.into_iter().rev() {
+                self.config.project.remove(i);
+            }
+            self.cache.clear();
+        }
+        OptimizationReport {
+            rules_before,
+            rules_after: rules_before - changes.len(),
+            changes,
+            applied: !dry_run,
+        }
+    }
+}
+
+/// One piece of an ignore pattern, as `pattern_to_regex` reads it
+#[derive(Debug, Clone, PartialEq)]
+enum GlobToken {
+    Literal(char),
+    /// `*`: anything within one path segment
+    Star,
+    /// `**`, and the match-anything tail of a `dir/` pattern
+    DoubleStar,
+    Question,
+    /// `[...]`, body kept as written
+    Class(String),
+    /// The implicit end of a non-directory pattern: nothing, or `/` and
+    /// anything below it
+    Subtree,
+}
+
+impl GlobToken {
+    fn parse(pattern: &str) -> Vec<GlobToken> {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            match chars[i] {
+                '*' if chars.get(i + 1) == Some(&'*') => {
+                    tokens.push(GlobToken::DoubleStar);
+                    i += 2;
+                    if chars.get(i) == Some(&'/') {
+                        i += 1;
+                    }
+                }
+                '*' => {
+                    tokens.push(GlobToken::Star);
+                    i += 1;
+                }
+                '?' => {
+                    tokens.push(GlobToken::Question);
+                    i += 1;
+                }
+                '[' => {
+                    let body: String = chars[i + 1..].iter().take_while(|&&c| c != ']').collect();
+                    i += body.chars().count() + 2;
+                    tokens.push(GlobToken::Class(body));
+                }
+                c => {
+                    tokens.push(GlobToken::Literal(c));
+                    i += 1;
+                }
+            }
+        }
+        tokens.push(if pattern.ends_with('/') { GlobToken::DoubleStar } else { GlobToken::Subtree });
+        tokens
+    }
+
+    /// Whether this token stands for exactly one character other than `/`
+    fn is_segment_char(&self) -> bool {
+        match self {
+            GlobToken::Literal(c) => *c != '/',
+            GlobToken::Question => true,
+            GlobToken::Class(body) => !body.contains('/'),
+            _ => false,
+        }
+    }
+
+    /// Whether every path `inner` matches is also matched by `outer`. Only
+    /// answers `true` when that follows from the token structure, so an
+    /// unusual pair of patterns is reported as not covered.
+    fn covers(outer: &[GlobToken], inner: &[GlobToken]) -> bool {
+        let Some((first, rest)) = outer.split_first() else {
+            return inner.is_empty();
+        };
+        match first {
+            GlobToken::DoubleStar => (0..=inner.len()).any(|k| Self::covers(rest, &inner[k..])),
+            GlobToken::Star => {
+                let run = inner.iter().take_while(|t| t.is_segment_char() || **t == GlobToken::Star).count();
+                (0..=run).any(|k| Self::covers(rest, &inner[k..]))
+            }
+            GlobToken::Subtree => {
+                rest.is_empty()
+                    && (inner.is_empty()
+                        || inner == [GlobToken::Subtree]
+                        || inner.first() == Some(&GlobToken::Literal('/')))
+            }
+            GlobToken::Question => {
+                inner.first().is_some_and(GlobToken::is_segment_char) && Self::covers(rest, &inner[1..])
+            }
+            GlobToken::Class(body) => {
+                let matches = match inner.first() {
+                    Some(GlobToken::Class(other)) => other == body,
+                    Some(GlobToken::Literal(c)) => Regex::new(&format!("^[{}]$", body))
+                        .is_ok_and(|re| re.is_match(&c.to_string())),
+                    _ => false,
+                };
+                matches && Self::covers(rest, &inner[1..])
+            }
+            GlobToken::Literal(c) => {
+                inner.first() == Some(&GlobToken::Literal(*c)) && Self::covers(rest, &inner[1..])
+            }
+        }
+    }
+}
+
+/// Why `IgnoreEngine::optimize` dropped a rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptimizationReason {
+    /// Same pattern and type as the kept rule
+    Duplicate,
+    /// A broader rule of the same type already covers it
+    Subsumed,
+    /// A rule of the opposite type covering it is evaluated first
+    Shadowed,
+}
+
+#[derive(Debug, Clone)]
+pub struct RuleOptimization {
+    pub removed: IgnoreRule,
+    pub reason: OptimizationReason,
+    /// The rule that makes `removed` unnecessary
+    pub kept: IgnoreRule,
+}
+
+impl std::fmt::Display for RuleOptimization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self.reason {
+            OptimizationReason::Duplicate => "duplicate of",
+            OptimizationReason::Subsumed => "subsumed by",
+            OptimizationReason::Shadowed => "shadowed by",
+        };
+        write!(
+            f,
+            "{} (priority {}) {} {} (priority {})",
+            self.removed.pattern, self.removed.priority, reason, self.kept.pattern, self.kept.priority
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OptimizationReport {
+    /// Rules removed, or that would be removed on a dry run
+    pub changes: Vec<RuleOptimization>,
+    pub rules_before: usize,
+    pub rules_after: usize,
+    /// `false` for a dry run
+    pub applied: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -684,4 +959,95 @@ mod tests {
         assert!(!engine.should_ignore_uncached(Path::new("important.txt")));
         assert!(engine.should_ignore_uncached(Path::new("other.txt")));
     }
+
+    fn rule(pattern: &str, rule_type: RuleType, priority: i32) -> IgnoreRule {
+        IgnoreRule { pattern: pattern.to_string(), rule_type, priority, description: None, condition: None }
+    }
+
+    fn redundant_engine(root: &Path) -> IgnoreEngine {
+        let mut engine = IgnoreEngine::new(root).unwrap();
+        for r in [
+            rule("*.log", RuleType::Ignore, 50),
+            rule("debug.log", RuleType::Ignore, 40),
+            rule("*.log", RuleType::Ignore, 50),
+            rule("vendor/", RuleType::Include, 200),
+            rule("vendor/*.o", RuleType::Ignore, 60),
+            rule("build/out.tmp", RuleType::Ignore, 10),
+            rule("keep.log", RuleType::Include, 80),
+            // Broader *.log is evaluated later with keep.log in between
+            rule("error.log", RuleType::Ignore, 120),
+            rule("*.bak", RuleType::Ignore, 30),
+            rule("old.bak", RuleType::Ignore, 70),
+            rule("src/*.log", RuleType::Ignore, 50),
+        ] {
+            engine.add_rule(r);
+        }
+        engine
+    }
+
+    #[test]
+    fn test_optimize_removes_exactly_redundant_rules() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut engine = redundant_engine(temp_dir.path());
+        let paths = [
+            "debug.log", "keep.log", "error.log", "vendor/lib.o", "build/out.tmp",
+            "old.bak", "src/main.log", "src/main.rs", "other.txt",
+        ];
+        let before: Vec<bool> = paths.iter().map(|p| engine.should_ignore_uncached(Path::new(p))).collect();
+
+        let report = engine.optimize(false);
+        let removed: Vec<(&str, i32, OptimizationReason)> = report
+            .changes
+            .iter()
+            .map(|c| (c.removed.pattern.as_str(), c.removed.priority, c.reason))
+            .collect();
+        assert_eq!(
+            removed,
+            [
+                ("old.bak", 70, OptimizationReason::Subsumed),
+                ("vendor/*.o", 60, OptimizationReason::Shadowed),
+                ("*.log", 50, OptimizationReason::Duplicate),
+                ("debug.log", 40, OptimizationReason::Subsumed),
+                ("build/out.tmp", 10, OptimizationReason::Subsumed),
+            ]
+        );
+        assert_eq!(report.changes[4].kept.pattern, "**/*.tmp");
+        assert_eq!((report.rules_before, report.rules_after), (11, 6));
+        let left: Vec<&str> = engine.get_project_rules().iter().map(|r| r.pattern.as_str()).collect();
+        assert_eq!(left, ["*.log", "vendor/", "keep.log", "error.log", "*.bak", "src/*.log"]);
+
+        let after: Vec<bool> = paths.iter().map(|p| engine.should_ignore_uncached(Path::new(p))).collect();
+        assert_eq!(before, after);
+        assert!(engine.optimize(false).changes.is_empty());
+    }
+
+    #[test]
+    fn test_optimize_dry_run_changes_nothing() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut engine = redundant_engine(temp_dir.path());
+        let rules_before = engine.get_project_rules().len();
+
+        let report = engine.optimize(true);
+        assert!(!report.applied);
+        assert_eq!(report.changes.len(), 5);
+        assert_eq!(engine.get_project_rules().len(), rules_before);
+        assert_eq!(engine.optimize(true).changes.len(), 5);
+    }
+
+    #[test]
+    fn test_glob_coverage() {
+        let covers = |outer: &str, inner: &str| {
+            GlobToken::covers(&GlobToken::parse(outer), &GlobToken::parse(inner))
+        };
+        assert!(covers("*.log", "foo.log"));
+        assert!(covers("*.log", "f?o[0-9].log"));
+        assert!(!covers("*.log", "logs/foo.log"));
+        assert!(covers("**/*.log", "logs/foo.log"));
+        assert!(covers("target", "target/"));
+        assert!(covers("target/", "target/debug"));
+        assert!(!covers("target/", "target"));
+        assert!(covers("*.py[cod]", "x.pyc"));
+        assert!(!covers("*.py[cod]", "x.pyx"));
+        assert!(!covers("foo.log", "*.log"));
+    }
 }
@@ -0,0 +1,228 @@
+//! Where a repository keeps its metadata
+//!
+//! By default the metadata lives in `.rune/` at the root of the working tree.
+//! It can live elsewhere: `RUNE_DIR` (or `rune --rune-dir`) names the
+//! metadata directory and `RUNE_WORK_TREE` (or `rune --work-tree`) the
+//! working tree, and a `.rune` file instead of a directory redirects to the
+//! metadata with a `runedir: <path>` line.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Environment variable naming the metadata directory
+pub const RUNE_DIR_ENV: &str = "RUNE_DIR";
+/// Environment variable naming the working tree root
+pub const WORK_TREE_ENV: &str = "RUNE_WORK_TREE";
+/// Name of the metadata directory, or of the file pointing at it
+pub const RUNE_DIR_NAME: &str = ".rune";
+/// Key of the line in a `.rune` file that points at the metadata directory
+pub const POINTER_KEY: &str = "runedir:";
+
+/// Working tree root and metadata directory of a repository
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepoLayout {
+    pub root: PathBuf,
+    pub rune_dir: PathBuf,
+}
+
+impl RepoLayout {
+    /// Layout of the repository whose working tree is `root`: `root/.rune`,
+    /// or the directory a `.rune` pointer file names
+    pub fn at(root: impl AsRef<Path>) -> Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        let marker = root.join(RUNE_DIR_NAME);
+        let rune_dir = if marker.is_file() { read_pointer(&marker)? } else { marker };
+        Ok(Self { root, rune_dir })
+    }
+
+    /// Find the repository containing `start`, honouring `RUNE_DIR` and
+    /// `RUNE_WORK_TREE`
+    pub fn discover(start: impl AsRef<Path>) -> Result<Self> {
+        Self::discover_with(start.as_ref(), env_path(RUNE_DIR_ENV)?, env_path(WORK_TREE_ENV)?)
+    }
+
+    /// Layout for `rune init` in `dir`: the metadata directory from
+    /// `RUNE_DIR` when set, otherwise the one `at` finds
+    pub fn for_init(dir: impl AsRef<Path>) -> Result<Self> {
+        let work_tree = env_path(WORK_TREE_ENV)?;
+        let root = work_tree.unwrap_or_else(|| dir.as_ref().to_path_buf());
+        match env_path(RUNE_DIR_ENV)? {
+            Some(rune_dir) => Ok(Self { root, rune_dir }),
+            None => Self::at(root),
+        }
+    }
+
+    fn discover_with(start: &Path, rune_dir: Option<PathBuf>, work_tree: Option<PathBuf>) -> Result<Self> {
+        // An explicit metadata directory makes the work tree, or else the
+        // starting directory, the root, like git's GIT_DIR
+        if let Some(rune_dir) = rune_dir {
+            if !rune_dir.is_dir() {
+                anyhow::bail!("{} is not a rune metadata directory", rune_dir.display());
+            }
+            return Ok(Self { root: work_tree.unwrap_or_else(|| start.to_path_buf()), rune_dir });
+        }
+        if let Some(root) = work_tree {
+            return Self::at(root);
+        }
+        let mut cur = Some(start);
+        while let Some(dir) = cur {
+            if dir.join(RUNE_DIR_NAME).exists() {
+                return Self::at(dir);
+            }
+            cur = dir.parent();
+        }
+        anyhow::bail!("not a rune repo (no .rune found)")
+    }
+}
+
+/// What walks of the working tree leave out: the metadata directory,
+/// wherever it lives, and any extra directories configured as internal,
+/// such as a server's data directory inside a working repository
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InternalPaths {
+    root: PathBuf,
+    rune_dir: PathBuf,
+    extra: Vec<Vec<String>>,
+}
+
+impl InternalPaths {
+    /// `extra` holds directories relative to the root, with `/` or `\`
+    pub fn new(layout: &RepoLayout, extra: &[String]) -> Self {
+        Self {
+            root: layout.root.clone(),
+            rune_dir: layout.rune_dir.clone(),
+            extra: extra.iter().map(|dir| components(dir)).filter(|dir| !dir.is_empty()).collect(),
+        }
+    }
+
+    /// Only the `.rune` directory of the working tree at `root`
+    pub fn at(root: impl AsRef<Path>) -> Self {
+        let root = root.as_ref().to_path_buf();
+        Self::new(&RepoLayout { rune_dir: root.join(RUNE_DIR_NAME), root }, &[])
+    }
+
+    /// Whether `path`, absolute or relative to the root, is internal.
+    /// Separators of either style count, so a relative path made on
+    /// Windows matches the same way as one made elsewhere.
+    pub fn contains(&self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        let rel = path.strip_prefix(&self.root).unwrap_or(path);
+        let parts = components(&rel.to_string_lossy());
+        parts.first().is_some_and(|first| first == RUNE_DIR_NAME)
+            || self.extra.iter().any(|dir| parts.starts_with(dir))
+            || self.root.join(rel).starts_with(&self.rune_dir)
+    }
+}
+
+/// `path` split on either separator, without empty and `.` parts
+fn components(path: &str) -> Vec<String> {
+    path.split(['/', '\\']).filter(|part| !part.is_empty() && *part != ".").map(str::to_string).collect()
+}
+
+/// Metadata directory named by a `.rune` pointer file; relative paths are
+/// taken from the directory holding the file
+fn read_pointer(file: &Path) -> Result<PathBuf> {
+    let content = fs::read_to_string(file).with_context(|| format!("read {}", file.display()))?;
+    let target = content
+        .lines()
+        .find_map(|line| line.trim().strip_prefix(POINTER_KEY))
+        .map(str::trim)
+        .filter(|target| !target.is_empty())
+        .ok_or_else(|| anyhow::anyhow!("{} has no '{} <path>' line", file.display(), POINTER_KEY))?;
+    let base = file.parent().unwrap_or(Path::new("."));
+    Ok(base.join(target))
+}
+
+/// Path from environment variable `name`, made absolute against the
+/// current directory
+fn env_path(name: &str) -> Result<Option<PathBuf>> {
+    match std::env::var_os(name).filter(|v| !v.is_empty()) {
+        Some(value) => Ok(Some(std::path::absolute(PathBuf::from(value))?)),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_default_layout() {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir_all(temp.path().join(".rune")).unwrap();
+        fs::create_dir_all(temp.path().join("src/deep")).unwrap();
+
+        let layout = RepoLayout::discover_with(&temp.path().join("src/deep"), None, None).unwrap();
+        assert_eq!(layout.root, temp.path());
+        assert_eq!(layout.rune_dir, temp.path().join(".rune"));
+    }
+
+    #[test]
+    fn test_pointer_file_redirects() {
+        let temp = TempDir::new().unwrap();
+        let checkout = temp.path().join("checkout");
+        fs::create_dir_all(checkout.join("src")).unwrap();
+        fs::create_dir_all(temp.path().join("meta")).unwrap();
+        fs::write(checkout.join(".rune"), "runedir: ../meta\n").unwrap();
+
+        let layout = RepoLayout::discover_with(&checkout.join("src"), None, None).unwrap();
+        assert_eq!(layout.root, checkout);
+        assert_eq!(layout.rune_dir, checkout.join("../meta"));
+
+        fs::write(checkout.join(".rune"), "nothing here\n").unwrap();
+        assert!(RepoLayout::at(&checkout).is_err());
+    }
+
+    #[test]
+    fn test_internal_paths_with_either_separator() {
+        let temp = TempDir::new().unwrap();
+        let internal = InternalPaths::new(&RepoLayout::at(temp.path()).unwrap(), &["shrine\\data".into()]);
+        for path in [
+            ".rune",
+            ".rune/objects/ab/cdef",
+            ".rune\\lfs\\objects\\ab\\cd\\abcd\\chunk_0",
+            "./.rune/drafts/d1.json",
+            ".\\.rune\\drafts",
+            "shrine/data/repos/main.json",
+            "shrine\\data",
+        ] {
+            assert!(internal.contains(path), "{}", path);
+        }
+        assert!(internal.contains(temp.path().join(".rune").join("lfs").join("objects")));
+        for path in [".runeignore", ".runekeep", "src/.rune-notes", "docs/.rune", "shrine/database.json", "shrine"] {
+            assert!(!internal.contains(path), "{}", path);
+        }
+        assert!(!internal.contains(temp.path().join("src/main.rs")));
+    }
+
+    #[test]
+    fn test_internal_paths_follow_a_moved_rune_dir() {
+        let temp = TempDir::new().unwrap();
+        let tree = temp.path().join("tree");
+        let layout = RepoLayout { root: tree.clone(), rune_dir: tree.join("meta") };
+        let internal = InternalPaths::new(&layout, &[]);
+        assert!(internal.contains(tree.join("meta/objects")));
+        assert!(internal.contains("meta/objects"));
+        assert!(internal.contains(".rune"), "a stray .rune is never working-tree content");
+        assert!(!internal.contains("metadata.txt"));
+    }
+
+    #[test]
+    fn test_explicit_rune_dir_and_work_tree() {
+        let temp = TempDir::new().unwrap();
+        let meta = temp.path().join("meta");
+        let tree = temp.path().join("tree");
+        fs::create_dir_all(&meta).unwrap();
+        fs::create_dir_all(&tree).unwrap();
+
+        let layout = RepoLayout::discover_with(temp.path(), Some(meta.clone()), Some(tree.clone())).unwrap();
+        assert_eq!(layout, RepoLayout { root: tree, rune_dir: meta.clone() });
+
+        let layout = RepoLayout::discover_with(temp.path(), Some(meta), None).unwrap();
+        assert_eq!(layout.root, temp.path());
+
+        assert!(RepoLayout::discover_with(temp.path(), Some(temp.path().join("missing")), None).is_err());
+    }
+}
@@ -5,15 +5,106 @@ use serde::{Deserialize, Serialize};
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Author { pub name: String, pub email: String }
 
+/// How a tracked path exists in the working tree. Only paths that aren't
+/// regular files are recorded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileMode {
+    #[default]
+    Regular,
+    /// The blob holds the link's target path
+    Symlink,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "StoredCommit")]
 pub struct Commit {
     pub id: String,
     pub message: String,
     pub author: Author,
+    /// Who made this version of the commit; differs from `author` once
+    /// someone else amends it
+    pub committer: Author,
+    /// When `committer` made it
     pub time: i64,
     pub parent: Option<String>,
+    /// Tip of the branch a merge commit joined in; `parent` is the branch
+    /// merged into. Commits that aren't merges, and merges written before
+    /// this was recorded, have none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merge_parent: Option<String>,
     pub files: Vec<String>,
     pub branch: String,
+    /// Blob ids of the content recorded for `files`; older commits have none
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub tree: std::collections::BTreeMap<String, String>,
+    /// Modes of the paths in `tree` that aren't regular files
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub modes: std::collections::BTreeMap<String, FileMode>,
+}
+
+/// `Commit` as read from the log, where commits written before committers
+/// were recorded have none; their author made them
+#[derive(Deserialize)]
+struct StoredCommit {
+    id: String,
+    message: String,
+    author: Author,
+    committer: Option<Author>,
+    time: i64,
+    parent: Option<String>,
+    #[serde(default)]
+    merge_parent: Option<String>,
+    files: Vec<String>,
+    branch: String,
+    #[serde(default)]
+    tree: std::collections::BTreeMap<String, String>,
+    #[serde(default)]
+    modes: std::collections::BTreeMap<String, FileMode>,
+}
+
+impl From<StoredCommit> for Commit {
+    fn from(c: StoredCommit) -> Self {
+        Self {
+            committer: c.committer.unwrap_or_else(|| c.author.clone()),
+            id: c.id,
+            message: c.message,
+            author: c.author,
+            time: c.time,
+            parent: c.parent,
+            merge_parent: c.merge_parent,
+            files: c.files,
+            branch: c.branch,
+            tree: c.tree,
+            modes: c.modes,
+        }
+    }
+}
+
+/// A commit with its message split into the body and parsed trailers
+#[derive(Clone, Debug, Serialize)]
+pub struct CommitDetails {
+    #[serde(flatten)]
+    pub commit: Commit,
+    /// The message without its trailer block
+    pub body: String,
+    pub trailers: Vec<trailers::Trailer>,
+}
+
+impl From<Commit> for CommitDetails {
+    fn from(commit: Commit) -> Self {
+        Self {
+            body: trailers::strip(&commit.message).to_string(),
+            trailers: trailers::parse(&commit.message),
+            commit,
+        }
+    }
+}
+
+impl Commit {
+    pub fn details(&self) -> CommitDetails {
+        CommitDetails::from(self.clone())
+    }
 }
 
 // Intelligence module moved from rune-cli
@@ -22,6 +113,18 @@ pub mod intelligence;
 // Advanced ignore system
 pub mod ignore;
 
+// Per-path attributes (.runeattributes)
+pub mod attributes;
+
+// Line ending normalization (core.autocrlf, eol/text attributes)
+pub mod eol;
+
+// Metadata directory location (RUNE_DIR, --work-tree, .rune pointer files)
+pub mod layout;
+
+// Commit message trailers (Reviewed-by:, Refs:, Plan:)
+pub mod trailers;
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -60,11 +163,15 @@ mod tests {
         let commit = Commit {
             id: "abc123".to_string(),
             message: "Initial commit".to_string(),
+            committer: author.clone(),
             author,
             time: 1234567890,
             parent: None,
+            merge_parent: None,
             files: vec!["README.md".to_string()],
             branch: "main".to_string(),
+            tree: Default::default(),
+            modes: Default::default(),
         };
         
         assert_eq!(commit.id, "abc123");
@@ -83,11 +190,15 @@ mod tests {
         let commit = Commit {
             id: "def456".to_string(),
             message: "Second commit".to_string(),
+            committer: author.clone(),
             author,
             time: 1234567891,
             parent: Some("abc123".to_string()),
+            merge_parent: None,
             files: vec!["src/main.rs".to_string(), "Cargo.toml".to_string()],
             branch: "main".to_string(),
+            tree: Default::default(),
+            modes: Default::default(),
         };
         
         assert_eq!(commit.parent, Some("abc123".to_string()));
@@ -104,11 +215,15 @@ mod tests {
         let commit = Commit {
             id: "xyz789".to_string(),
             message: "Test commit".to_string(),
+            committer: author.clone(),
             author,
             time: 1234567892,
             parent: Some("def456".to_string()),
+            merge_parent: None,
             files: vec!["test.rs".to_string()],
             branch: "feature".to_string(),
+            tree: Default::default(),
+            modes: Default::default(),
         };
         
         let serialized = serde_json::to_string(&commit).unwrap();
@@ -122,5 +237,14 @@ mod tests {
         assert_eq!(commit.parent, deserialized.parent);
         assert_eq!(commit.files, deserialized.files);
         assert_eq!(commit.branch, deserialized.branch);
+        assert_eq!(commit.committer.email, deserialized.committer.email);
+    }
+
+    #[test]
+    fn test_commit_without_committer_defaults_to_author() {
+        let json = r#"{"id":"a1","message":"old","author":{"name":"Ann","email":"ann@example.com"},"time":1,"parent":null,"files":[],"branch":"refs/heads/main"}"#;
+        let commit: Commit = serde_json::from_str(json).unwrap();
+        assert_eq!(commit.committer.name, "Ann");
+        assert_eq!(commit.committer.email, "ann@example.com");
     }
 }
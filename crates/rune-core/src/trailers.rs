@@ -0,0 +1,191 @@
+use serde::{Deserialize, Serialize};
+
+/// One `Key: value` line from the trailer block of a commit message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trailer {
+    pub key: String,
+    pub value: String,
+}
+
+impl Trailer {
+    pub fn new(key: &str, value: &str) -> Self {
+        Self { key: key.trim().to_string(), value: value.trim().to_string() }
+    }
+
+    /// Whether this trailer's key is `key`, ignoring case
+    pub fn is(&self, key: &str) -> bool {
+        self.key.eq_ignore_ascii_case(key.trim())
+    }
+}
+
+impl std::fmt::Display for Trailer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.key, self.value)
+    }
+}
+
+impl std::str::FromStr for Trailer {
+    type Err = anyhow::Error;
+
+    /// `Key=value` as given on the command line, or `Key: value` as written
+    /// in a message
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = match (s.find('='), s.find(':')) {
+            (Some(eq), Some(colon)) => s.split_at(eq.min(colon)),
+            (Some(i), None) | (None, Some(i)) => s.split_at(i),
+            (None, None) => anyhow::bail!("invalid trailer '{}': expected KEY=VALUE", s),
+        };
+        let value = &value[1..];
+        if !is_key(key.trim()) || value.trim().is_empty() {
+            anyhow::bail!("invalid trailer '{}': expected KEY=VALUE", s);
+        }
+        Ok(Trailer::new(key, value))
+    }
+}
+
+fn is_key(key: &str) -> bool {
+    !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// `Key: value` split from a trailer line, `None` for anything else
+fn split_line(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once(':')?;
+    is_key(key).then_some((key, value))
+}
+
+/// Byte offset where the trailer block of `message` starts, if it has one.
+///
+/// The block is the last paragraph, provided it isn't the only one (a
+/// subject line alone is never trailers) and every line in it is either a
+/// `Key: value` line or an indented continuation of the one above.
+fn block_start(message: &str) -> Option<usize> {
+    let text = message.trim_end();
+    let start = text.rfind("\n\n").map(|i| i + 2)?;
+    let block = &text[start..];
+    if text[..start].trim().is_empty() {
+        return None;
+    }
+    let mut lines = block.lines();
+    split_line(lines.next()?)?;
+    for line in lines {
+        let folded = line.starts_with(' ') || line.starts_with('\t');
+        if !folded && split_line(line).is_none() {
+            return None;
+        }
+    }
+    Some(start)
+}
+
+/// Trailers at the end of `message`, in order. Repeated keys each get their
+/// own entry and folded values are joined with single spaces.
+pub fn parse(message: &str) -> Vec<Trailer> {
+    let Some(start) = block_start(message) else {
+        return Vec::new();
+    };
+    let mut trailers: Vec<Trailer> = Vec::new();
+    for line in message.trim_end()[start..].lines() {
+        match (split_line(line), trailers.last_mut()) {
+            (Some((key, value)), _) if !line.starts_with([' ', '\t']) => {
+                trailers.push(Trailer::new(key, value))
+            }
+            (_, Some(last)) => {
+                last.value.push(' ');
+                last.value.push_str(line.trim());
+            }
+            (_, None) => {}
+        }
+    }
+    trailers
+}
+
+/// `message` without its trailer block
+pub fn strip(message: &str) -> &str {
+    match block_start(message) {
+        Some(start) => message[..start].trim_end(),
+        None => message.trim_end(),
+    }
+}
+
+/// Values of every `key` trailer in `message`
+pub fn values(message: &str, key: &str) -> Vec<String> {
+    parse(message).into_iter().filter(|t| t.is(key)).map(|t| t.value).collect()
+}
+
+/// `message` with `trailers` added to its trailer block, starting one after
+/// a blank line when the message doesn't end in trailers yet. Trailers the
+/// message already carries are not repeated.
+pub fn append(message: &str, trailers: &[Trailer]) -> String {
+    let existing = parse(message);
+    let mut out = message.trim_end().to_string();
+    let mut separated = block_start(message).is_some();
+    for trailer in trailers {
+        if existing.iter().any(|t| t.is(&trailer.key) && t.value == trailer.value) {
+            continue;
+        }
+        if !separated {
+            out.push('\n');
+            separated = true;
+        }
+        out.push('\n');
+        out.push_str(&trailer.to_string());
+    }
+    out
+}
+
+/// The plan a commit belongs to: its `Plan:` trailer when it has one,
+/// otherwise the first `PLAN-<n>` mentioned in the message
+pub fn plan_reference(message: &str) -> Option<String> {
+    if let Some(plan) = values(message, "Plan").into_iter().next() {
+        return Some(plan);
+    }
+    let re = regex::Regex::new(r"\bPLAN-\d+\b").expect("valid plan pattern");
+    re.find(message).map(|m| m.as_str().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_without_trailers() {
+        assert!(parse("Fix the build").is_empty());
+        assert!(parse("Reviewed-by: alice").is_empty());
+        // A trailer-looking line inside the body doesn't make a block
+        let msg = "Fix parser\n\nNote: this also touches the lexer\nand the tests.";
+        assert!(parse(msg).is_empty());
+        assert_eq!(strip(msg), msg);
+        let msg = "Fix parser\n\nReviewed-by: alice\n\nMore prose afterwards.";
+        assert!(parse(msg).is_empty());
+    }
+
+    #[test]
+    fn test_multiple_values_and_folding() {
+        let msg = "Fix parser\n\nBody text.\n\nRefs: #12\nReviewed-by: alice\nreviewed-by: bob\n  <bob@example.com>\n";
+        let trailers = parse(msg);
+        assert_eq!(trailers.len(), 3);
+        assert_eq!(values(msg, "Reviewed-By"), ["alice", "bob <bob@example.com>"]);
+        assert_eq!(values(msg, "Refs"), ["#12"]);
+        assert_eq!(strip(msg), "Fix parser\n\nBody text.");
+    }
+
+    #[test]
+    fn test_append_adds_separator_once() {
+        let reviewed = Trailer::new("Reviewed-by", "alice");
+        let refs: Trailer = "Refs=#7".parse().unwrap();
+        assert_eq!(append("Fix parser", std::slice::from_ref(&reviewed)), "Fix parser\n\nReviewed-by: alice");
+        assert_eq!(
+            append("Fix parser\n\nRefs: #7\n", &[refs.clone(), reviewed.clone()]),
+            "Fix parser\n\nRefs: #7\nReviewed-by: alice"
+        );
+        let with_body = "Fix parser\n\nThe lexer: it was wrong.";
+        assert_eq!(append(with_body, &[refs]), format!("{}\n\nRefs: #7", with_body));
+        assert!("no value".parse::<Trailer>().is_err());
+    }
+
+    #[test]
+    fn test_plan_trailer_wins_over_body() {
+        assert_eq!(plan_reference("Start on PLAN-002\n\nPlan: PLAN-004").as_deref(), Some("PLAN-004"));
+        assert_eq!(plan_reference("Start on PLAN-002").as_deref(), Some("PLAN-002"));
+        assert_eq!(plan_reference("Unrelated"), None);
+    }
+}
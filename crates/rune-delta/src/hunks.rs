@@ -0,0 +1,422 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// How many context lines `apply_hunks_fuzzy` may drop from each end of a
+/// hunk before giving up on it (same meaning as `patch --fuzz`)
+pub const DEFAULT_FUZZ: usize = 2;
+
+/// Above this many line (or word token) comparisons the middle of a file is
+/// treated as one replaced block instead of running the quadratic LCS
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HunkLine {
+    Context(String),
+    Remove(String),
+    Add(String),
+}
+
+/// One unified-diff style hunk; line numbers are 1-based
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_count: usize,
+    pub new_start: usize,
+    pub new_count: usize,
+    pub lines: Vec<HunkLine>,
+}
+
+impl Hunk {
+    /// Lines the hunk expects to find (context and removals)
+    fn before(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|l| match l {
+                HunkLine::Context(s) | HunkLine::Remove(s) => Some(s.as_str()),
+                HunkLine::Add(_) => None,
+            })
+            .collect()
+    }
+
+    /// Lines the hunk leaves behind (context and additions)
+    fn after(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|l| match l {
+                HunkLine::Context(s) | HunkLine::Add(s) => Some(s.as_str()),
+                HunkLine::Remove(_) => None,
+            })
+            .collect()
+    }
+
+    fn leading_context(&self) -> usize {
+        self.lines.iter().take_while(|l| matches!(l, HunkLine::Context(_))).count()
+    }
+
+    fn trailing_context(&self) -> usize {
+        self.lines.iter().rev().take_while(|l| matches!(l, HunkLine::Context(_))).count()
+    }
+}
+
+impl fmt::Display for Hunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "@@ -{},{} +{},{} @@",
+            self.old_start, self.old_count, self.new_start, self.new_count
+        )?;
+        for line in &self.lines {
+            match line {
+                HunkLine::Context(s) => writeln!(f, " {}", s)?,
+                HunkLine::Remove(s) => writeln!(f, "-{}", s)?,
+                HunkLine::Add(s) => writeln!(f, "+{}", s)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Where a hunk ended up when applied with `apply_hunks_fuzzy`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppliedHunk {
+    /// Index into the hunk list
+    pub index: usize,
+    /// Line (1-based) in the result where the hunk was applied
+    pub line: usize,
+    /// Distance in lines from where the hunk header said it would be
+    pub offset: isize,
+    /// Context lines dropped to make the hunk fit
+    pub fuzz: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct FuzzyApplyResult {
+    pub content: String,
+    pub applied: Vec<AppliedHunk>,
+    pub rejected: Vec<Hunk>,
+}
+
+impl FuzzyApplyResult {
+    pub fn is_clean(&self) -> bool {
+        self.rejected.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Edit {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Compute unified-diff style hunks turning `old` into `new`
+pub fn line_hunks(old: &str, new: &str, context: usize) -> Vec<Hunk> {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let script = edit_script(&old_lines, &new_lines);
+
+    // Positions (in the edit script) of every change, grouped when their
+    // context would overlap
+    let changes: Vec<usize> = script
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| **e != Edit::Equal)
+        .map(|(i, _)| i)
+        .collect();
+    if changes.is_empty() {
+        return Vec::new();
+    }
+
+    let mut groups: Vec<(usize, usize)> = Vec::new();
+    for &pos in &changes {
+        match groups.last_mut() {
+            Some((_, end)) if pos <= *end + 2 * context + 1 => *end = pos,
+            _ => groups.push((pos, pos)),
+        }
+    }
+
+    // Line numbers before each script position
+    let mut old_at = Vec::with_capacity(script.len() + 1);
+    let mut new_at = Vec::with_capacity(script.len() + 1);
+    let (mut o, mut n) = (0usize, 0usize);
+    for e in &script {
+        old_at.push(o);
+        new_at.push(n);
+        match e {
+            Edit::Equal => {
+                o += 1;
+                n += 1;
+            }
+            Edit::Delete => o += 1,
+            Edit::Insert => n += 1,
+        }
+    }
+    old_at.push(o);
+    new_at.push(n);
+
+    groups
+        .into_iter()
+        .map(|(first, last)| {
+            let start = first.saturating_sub(context);
+            let end = (last + context + 1).min(script.len());
+            let mut lines = Vec::new();
+            for pos in start..end {
+                let line = match script[pos] {
+                    Edit::Equal => HunkLine::Context(old_lines[old_at[pos]].to_string()),
+                    Edit::Delete => HunkLine::Remove(old_lines[old_at[pos]].to_string()),
+                    Edit::Insert => HunkLine::Add(new_lines[new_at[pos]].to_string()),
+                };
+                lines.push(line);
+            }
+            let old_count = old_at[end] - old_at[start];
+            let new_count = new_at[end] - new_at[start];
+            Hunk {
+                // Unified diff convention: an empty range starts at the line before it
+                old_start: if old_count == 0 { old_at[start] } else { old_at[start] + 1 },
+                old_count,
+                new_start: if new_count == 0 { new_at[start] } else { new_at[start] + 1 },
+                new_count,
+                lines,
+            }
+        })
+        .collect()
+}
+
+/// Apply hunks to `target`, locating each one by its content rather than
+/// trusting the line numbers, so unrelated edits elsewhere in the file don't
+/// break the patch. Hunks that can't be placed even after dropping up to
+/// `fuzz` context lines are returned in `rejected` instead.
+pub fn apply_hunks_fuzzy(target: &str, hunks: &[Hunk], fuzz: usize) -> FuzzyApplyResult {
+    let mut lines: Vec<String> = target.lines().map(str::to_string).collect();
+    let mut applied = Vec::new();
+    let mut rejected = Vec::new();
+    // Net lines added by hunks applied so far, how far the target has drifted
+    // from the patch's line numbers, and where the last applied hunk ended
+    let mut size_delta: isize = 0;
+    let mut drift: isize = 0;
+    let mut floor = 0usize;
+
+    for (index, hunk) in hunks.iter().enumerate() {
+        let header_pos = (hunk.old_start.saturating_sub(1) as isize + size_delta).max(0);
+        let expected = (header_pos + drift).max(0) as usize;
+        let before = hunk.before();
+        let after = hunk.after();
+
+        let mut placed = None;
+        for level in 0..=fuzz {
+            let head = level.min(hunk.leading_context());
+            let tail = level.min(hunk.trailing_context());
+            if level > 0 && head == 0 && tail == 0 {
+                break;
+            }
+            let needle = &before[head..before.len() - tail];
+            if needle.is_empty() && !before.is_empty() {
+                break;
+            }
+            if let Some(pos) = find_nearest(&lines, needle, expected + head, floor) {
+                placed = Some((pos, level, head, tail));
+                break;
+            }
+        }
+
+        let Some((pos, level, head, tail)) = placed else {
+            rejected.push(hunk.clone());
+            continue;
+        };
+
+        let replacement: Vec<String> = after[head..after.len() - tail]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let removed = before.len() - head - tail;
+        let added = replacement.len();
+        lines.splice(pos..pos + removed, replacement);
+
+        let start = pos - head;
+        let offset = start as isize - header_pos;
+        applied.push(AppliedHunk {
+            index,
+            line: start + 1,
+            offset,
+            fuzz: level,
+        });
+        size_delta += added as isize - removed as isize;
+        drift = offset;
+        floor = pos + added;
+    }
+
+    let mut content = lines.join("\n");
+    if !lines.is_empty() && (target.ends_with('\n') || target.is_empty()) {
+        content.push('\n');
+    }
+    FuzzyApplyResult { content, applied, rejected }
+}
+
+/// Contents of a `.rej` file for hunks `apply_hunks_fuzzy` could not place
+pub fn format_rejects(path: &str, rejected: &[Hunk]) -> String {
+    let mut out = format!("--- {}\n+++ {}\n", path, path);
+    for hunk in rejected {
+        out.push_str(&hunk.to_string());
+    }
+    out
+}
+
+/// Find `needle` in `lines` at or after `floor`, preferring the match closest to `expected`
+fn find_nearest(lines: &[String], needle: &[&str], expected: usize, floor: usize) -> Option<usize> {
+    if needle.is_empty() {
+        return Some(expected.clamp(floor, lines.len().max(floor)));
+    }
+    if needle.len() > lines.len() {
+        return None;
+    }
+    let last = lines.len() - needle.len();
+    (floor..=last)
+        .filter(|&pos| lines[pos..pos + needle.len()].iter().zip(needle).all(|(a, b)| a == b))
+        .min_by_key(|&pos| pos.abs_diff(expected))
+}
+
+/// Line-level edit script: common prefix/suffix are stripped and the middle
+/// is aligned with an LCS table when it is small enough
+pub(crate) fn edit_script<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Edit> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    let mut script = vec![Edit::Equal; prefix];
+    if old_mid.len().saturating_mul(new_mid.len()) > MAX_LCS_CELLS {
+        script.extend(std::iter::repeat_n(Edit::Delete, old_mid.len()));
+        script.extend(std::iter::repeat_n(Edit::Insert, new_mid.len()));
+    } else {
+        let (n, m) = (old_mid.len(), new_mid.len());
+        let mut table = vec![0u32; (n + 1) * (m + 1)];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                table[i * (m + 1) + j] = if old_mid[i] == new_mid[j] {
+                    table[(i + 1) * (m + 1) + j + 1] + 1
+                } else {
+                    table[(i + 1) * (m + 1) + j].max(table[i * (m + 1) + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && old_mid[i] == new_mid[j] {
+                script.push(Edit::Equal);
+                i += 1;
+                j += 1;
+            } else if i < n && (j == m || table[(i + 1) * (m + 1) + j] >= table[i * (m + 1) + j + 1]) {
+                // Prefer deletions first so replaced lines read as -old/+new
+                script.push(Edit::Delete);
+                i += 1;
+            } else {
+                script.push(Edit::Insert);
+                j += 1;
+            }
+        }
+    }
+    script.extend(std::iter::repeat_n(Edit::Equal, suffix));
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered(range: std::ops::Range<usize>) -> String {
+        range.map(|i| format!("line {}\n", i)).collect()
+    }
+
+    #[test]
+    fn test_line_hunks_groups_changes() {
+        let old = numbered(1..21);
+        let new = old.replace("line 3\n", "line three\n").replace("line 18\n", "");
+        let hunks = line_hunks(&old, &new, 2);
+
+        assert_eq!(hunks.len(), 2);
+        assert_eq!((hunks[0].old_start, hunks[0].old_count), (1, 5));
+        assert_eq!((hunks[0].new_start, hunks[0].new_count), (1, 5));
+        assert_eq!((hunks[1].old_start, hunks[1].old_count), (16, 5));
+        assert_eq!(hunks[1].new_count, 4);
+        assert!(hunks[0].to_string().starts_with("@@ -1,5 +1,5 @@\n"));
+    }
+
+    #[test]
+    fn test_exact_application() {
+        let old = numbered(1..11);
+        let new = old.replace("line 5\n", "line five\nline 5.5\n");
+        let hunks = line_hunks(&old, &new, 3);
+
+        let result = apply_hunks_fuzzy(&old, &hunks, DEFAULT_FUZZ);
+        assert!(result.is_clean());
+        assert_eq!(result.content, new);
+        assert_eq!(result.applied[0].offset, 0);
+    }
+
+    #[test]
+    fn test_fuzzy_application_with_leading_lines() {
+        let old = numbered(1..31);
+        let new = old
+            .replace("line 4\n", "line four\n")
+            .replace("line 25\n", "line twenty-five\n");
+        let hunks = line_hunks(&old, &new, 3);
+
+        // The target gained unrelated lines at the top since the patch was made
+        let target = format!("// header\n// license\n// more\n{}", old);
+        let result = apply_hunks_fuzzy(&target, &hunks, DEFAULT_FUZZ);
+
+        assert!(result.is_clean());
+        assert_eq!(result.content, format!("// header\n// license\n// more\n{}", new));
+        assert_eq!(result.applied.len(), 2);
+        assert!(result.applied.iter().all(|h| h.offset == 3 && h.fuzz == 0));
+    }
+
+    #[test]
+    fn test_conflicting_hunk_is_rejected() {
+        let old = numbered(1..31);
+        let new = old
+            .replace("line 4\n", "line four\n")
+            .replace("line 25\n", "line twenty-five\n");
+        let hunks = line_hunks(&old, &new, 3);
+
+        // Someone else rewrote the region around line 25
+        let target = format!(
+            "extra\n{}",
+            old.replace("line 24\nline 25\nline 26\n", "rewritten\n")
+        );
+        let result = apply_hunks_fuzzy(&target, &hunks, DEFAULT_FUZZ);
+
+        assert_eq!(result.applied.len(), 1);
+        assert_eq!(result.applied[0].index, 0);
+        assert_eq!(result.applied[0].offset, 1);
+        assert_eq!(result.rejected.len(), 1);
+        assert!(result.content.contains("line four\n"));
+        assert!(result.content.contains("rewritten\n"));
+
+        let rej = format_rejects("notes.txt", &result.rejected);
+        assert!(rej.starts_with("--- notes.txt\n+++ notes.txt\n@@ -22,7 +22,7 @@\n"));
+        assert!(rej.contains("-line 25\n+line twenty-five\n"));
+    }
+
+    #[test]
+    fn test_fuzz_drops_stale_context() {
+        let old = numbered(1..11);
+        let new = old.replace("line 5\n", "line five\n");
+        let hunks = line_hunks(&old, &new, 3);
+
+        // Outermost context line changed, but the edit itself still applies
+        let target = old.replace("line 2\n", "line two\n");
+        let result = apply_hunks_fuzzy(&target, &hunks, DEFAULT_FUZZ);
+
+        assert!(result.is_clean());
+        assert_eq!(result.applied[0].fuzz, 1);
+        assert_eq!(result.content, target.replace("line 5\n", "line five\n"));
+
+        assert!(!apply_hunks_fuzzy(&target, &hunks, 0).is_clean());
+    }
+}
@@ -1,7 +1,11 @@
 
 use anyhow::Result;
+use rune_core::eol::looks_binary;
 use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+
+pub mod hunks;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Op { Copy{ offset: usize, len: usize }, Insert{ data: Vec<u8> } }
@@ -37,7 +41,18 @@ pub struct DiffOptions {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Patch { pub base_hash: String, pub new_hash: String, pub chunk: usize, pub ops: Vec<Op> }
+pub struct Patch {
+    pub base_hash: String,
+    pub new_hash: String,
+    pub chunk: usize,
+    pub ops: Vec<Op>,
+    /// Line hunks for text files, used to apply the patch when the base has drifted
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hunks: Vec<hunks::Hunk>,
+}
+
+/// Context lines recorded around each hunk by [`make`]
+pub const PATCH_CONTEXT_LINES: usize = 3;
 
 pub fn make(base:&[u8], new:&[u8], chunk:usize)->Result<Patch>{
     let base_hash = format!("{}", blake3::hash(base));
@@ -78,7 +93,11 @@ pub fn make(base:&[u8], new:&[u8], chunk:usize)->Result<Patch>{
         }
         i += 1;
     }
-    Ok(Patch{ base_hash, new_hash, chunk:w, ops })
+    let hunks = match (std::str::from_utf8(base), std::str::from_utf8(new)) {
+        (Ok(old_text), Ok(new_text)) => hunks::line_hunks(old_text, new_text, PATCH_CONTEXT_LINES),
+        _ => Vec::new(),
+    };
+    Ok(Patch{ base_hash, new_hash, chunk:w, ops, hunks })
 }
 
 pub fn apply(base:&[u8], patch:&Patch)->Result<Vec<u8>>{
@@ -94,6 +113,60 @@ pub fn apply(base:&[u8], patch:&Patch)->Result<Vec<u8>>{
     Ok(out)
 }
 
+/// Apply a text patch by locating its hunks through their context lines.
+///
+/// Unlike [`apply`] this works when `target` is not byte-for-byte the base the
+/// patch was made from; hunks that cannot be placed come back as rejected.
+pub fn apply_fuzzy(target: &[u8], patch: &Patch) -> Result<hunks::FuzzyApplyResult> {
+    if patch.hunks.is_empty() && patch.base_hash != patch.new_hash {
+        anyhow::bail!("patch has no line hunks (binary content); it only applies to its exact base");
+    }
+    let text = std::str::from_utf8(target)
+        .map_err(|_| anyhow::anyhow!("fuzzy apply needs a UTF-8 text target"))?;
+    Ok(hunks::apply_hunks_fuzzy(text, &patch.hunks, hunks::DEFAULT_FUZZ))
+}
+
+/// First line of every patch file: magic followed by the format version
+pub const PATCH_MAGIC: &str = "RUNE-PATCH";
+/// Newest patch file version this build can read and the one it writes
+pub const PATCH_FORMAT_VERSION: u32 = 1;
+
+/// Write `patch` as a patch file: a `RUNE-PATCH v<version>` header line and a JSON body
+pub fn write_patch<W: Write>(patch: &Patch, mut writer: W) -> Result<()> {
+    writeln!(writer, "{} v{}", PATCH_MAGIC, PATCH_FORMAT_VERSION)?;
+    serde_json::to_writer_pretty(&mut writer, patch)?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+/// Read a patch file written by [`write_patch`], rejecting unknown or newer formats
+pub fn read_patch<R: Read>(reader: R) -> Result<Patch> {
+    let mut reader = BufReader::new(reader);
+    let mut header = String::new();
+    reader.read_line(&mut header)?;
+    let version = parse_patch_header(header.trim_end())?;
+    if version > PATCH_FORMAT_VERSION {
+        anyhow::bail!(
+            "patch file version {} is newer than supported version {}; upgrade rune to apply it",
+            version,
+            PATCH_FORMAT_VERSION
+        );
+    }
+    let patch = serde_json::from_reader(reader)
+        .map_err(|e| anyhow::anyhow!("corrupt patch body: {}", e))?;
+    Ok(patch)
+}
+
+fn parse_patch_header(header: &str) -> Result<u32> {
+    let version = header
+        .strip_prefix(PATCH_MAGIC)
+        .and_then(|rest| rest.strip_prefix(" v"))
+        .ok_or_else(|| anyhow::anyhow!("not a rune patch file (expected '{} v<N>' header)", PATCH_MAGIC))?;
+    version
+        .parse()
+        .map_err(|_| anyhow::anyhow!("invalid patch format version '{}'", version))
+}
+
 // Calculate similarity between two byte arrays using Jaccard similarity
 pub fn calculate_similarity(a: &[u8], b: &[u8]) -> f64 {
     if a.is_empty() && b.is_empty() {
@@ -127,41 +200,39 @@ pub fn calculate_similarity(a: &[u8], b: &[u8]) -> f64 {
     }
 }
 
-// Detect file renames by comparing content similarity
+// Detect file renames by comparing content similarity. The most similar
+// pairs are matched first, so an exact copy is never taken by a weaker match.
 pub fn detect_renames(
     deleted_files: &HashMap<String, Vec<u8>>,
     added_files: &HashMap<String, Vec<u8>>,
     threshold: f64,
 ) -> Vec<FileRename> {
-    let mut renames = Vec::new();
-    let mut used_targets = std::collections::HashSet::new();
-    
+    let mut candidates = Vec::new();
     for (deleted_path, deleted_content) in deleted_files {
-        let mut best_match = None;
-        let mut best_similarity = threshold;
-        
         for (added_path, added_content) in added_files {
-            if used_targets.contains(added_path) {
-                continue;
-            }
-            
             let similarity = calculate_similarity(deleted_content, added_content);
-            if similarity > best_similarity {
-                best_similarity = similarity;
-                best_match = Some(added_path.clone());
+            if similarity > threshold {
+                candidates.push((similarity, deleted_path, added_path));
             }
         }
-        
-        if let Some(new_path) = best_match {
-            used_targets.insert(new_path.clone());
-            renames.push(FileRename {
-                old_path: deleted_path.clone(),
-                new_path,
-                similarity: best_similarity,
-            });
+    }
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| (a.1, a.2).cmp(&(b.1, b.2))));
+
+    let mut renames = Vec::new();
+    let mut used_sources = std::collections::HashSet::new();
+    let mut used_targets = std::collections::HashSet::new();
+    for (similarity, deleted_path, added_path) in candidates {
+        if used_sources.contains(deleted_path) || used_targets.contains(added_path) {
+            continue;
         }
+        used_sources.insert(deleted_path);
+        used_targets.insert(added_path);
+        renames.push(FileRename {
+            old_path: deleted_path.clone(),
+            new_path: added_path.clone(),
+            similarity,
+        });
     }
-    
     renames
 }
 
@@ -189,32 +260,88 @@ pub fn detect_copies(
     copies
 }
 
-// Word-level diff for better text comparison
+/// Split `text` into runs of word characters, runs of whitespace and runs of
+/// punctuation. Concatenating the tokens gives back `text` exactly.
+pub fn tokenize(text: &str) -> Vec<&str> {
+    fn class(c: char) -> u8 {
+        if c.is_alphanumeric() || c == '_' {
+            0
+        } else if c.is_whitespace() {
+            1
+        } else {
+            2
+        }
+    }
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut current = None;
+    for (idx, c) in text.char_indices() {
+        let kind = class(c);
+        if current.is_some_and(|k| k != kind) {
+            tokens.push(&text[start..idx]);
+            start = idx;
+        }
+        current = Some(kind);
+    }
+    if start < text.len() {
+        tokens.push(&text[start..]);
+    }
+    tokens
+}
+
+// Word-level diff for better text comparison. Whitespace and punctuation are
+// tokens too, so the "equal" and "delete" entries spell out `old_text` and the
+// "equal" and "insert" entries spell out `new_text`. Lines are matched first
+// and only the changed blocks are compared token by token, so the work is
+// bounded the same way as the line diff.
 pub fn word_diff(old_text: &str, new_text: &str) -> Vec<(String, String)> {
-    let old_words: Vec<&str> = old_text.split_whitespace().collect();
-    let new_words: Vec<&str> = new_text.split_whitespace().collect();
-    
+    let old_lines: Vec<&str> = old_text.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new_text.split_inclusive('\n').collect();
+    let script = hunks::edit_script(&old_lines, &new_lines);
+
     let mut result = Vec::new();
-    let mut i = 0;
-    let mut j = 0;
-    
-    while i < old_words.len() || j < new_words.len() {
-        if i < old_words.len() && j < new_words.len() && old_words[i] == new_words[j] {
-            // Words match
-            result.push(("equal".to_string(), old_words[i].to_string()));
+    let (mut pos, mut i, mut j) = (0, 0, 0);
+    while pos < script.len() {
+        if script[pos] == hunks::Edit::Equal {
+            result.extend(tokenize(old_lines[i]).into_iter().map(|w| ("equal".to_string(), w.to_string())));
+            pos += 1;
             i += 1;
             j += 1;
-        } else if i < old_words.len() && (j >= new_words.len() || old_words[i] != new_words[j]) {
-            // Word deleted
-            result.push(("delete".to_string(), old_words[i].to_string()));
-            i += 1;
-        } else if j < new_words.len() {
-            // Word added
-            result.push(("insert".to_string(), new_words[j].to_string()));
-            j += 1;
+            continue;
+        }
+        let (old_start, new_start) = (i, j);
+        while pos < script.len() && script[pos] != hunks::Edit::Equal {
+            if script[pos] == hunks::Edit::Delete {
+                i += 1;
+            } else {
+                j += 1;
+            }
+            pos += 1;
+        }
+        let old_block = old_lines[old_start..i].concat();
+        let new_block = new_lines[new_start..j].concat();
+        let old_words = tokenize(&old_block);
+        let new_words = tokenize(&new_block);
+        let (mut a, mut b) = (0, 0);
+        for edit in hunks::edit_script(&old_words, &new_words) {
+            let entry = match edit {
+                hunks::Edit::Equal => {
+                    a += 1;
+                    b += 1;
+                    ("equal", old_words[a - 1])
+                }
+                hunks::Edit::Delete => {
+                    a += 1;
+                    ("delete", old_words[a - 1])
+                }
+                hunks::Edit::Insert => {
+                    b += 1;
+                    ("insert", new_words[b - 1])
+                }
+            };
+            result.push((entry.0.to_string(), entry.1.to_string()));
         }
     }
-    
     result
 }
 
@@ -224,6 +351,9 @@ pub fn enhanced_diff(
     new_content: &[u8],
     options: &DiffOptions,
 ) -> Result<String> {
+    if looks_binary(old_content) || looks_binary(new_content) {
+        return Ok(binary_summary(old_content, new_content));
+    }
     let old_text = String::from_utf8_lossy(old_content);
     let new_text = String::from_utf8_lossy(new_content);
     
@@ -234,41 +364,16 @@ pub fn enhanced_diff(
             
             for (change_type, word) in word_changes {
                 match change_type.as_str() {
-                    "equal" => output.push_str(&format!(" {}", word)),
-                    "delete" => output.push_str(&format!(" [-{}]", word)),
-                    "insert" => output.push_str(&format!(" [+{}]", word)),
+                    "equal" => output.push_str(&word),
+                    "delete" => output.push_str(&format!("[-{}]", word)),
+                    "insert" => output.push_str(&format!("[+{}]", word)),
                     _ => {}
                 }
             }
             
             Ok(output)
         }
-        DiffMode::Line => {
-            let old_lines: Vec<&str> = old_text.lines().collect();
-            let new_lines: Vec<&str> = new_text.lines().collect();
-            
-            let mut output = String::new();
-            let mut i = 0;
-            let mut j = 0;
-            
-            while i < old_lines.len() || j < new_lines.len() {
-                if i < old_lines.len() && j < new_lines.len() && old_lines[i] == new_lines[j] {
-                    if options.context_lines > 0 {
-                        output.push_str(&format!("  {}\n", old_lines[i]));
-                    }
-                    i += 1;
-                    j += 1;
-                } else if i < old_lines.len() && (j >= new_lines.len() || old_lines[i] != new_lines[j]) {
-                    output.push_str(&format!("- {}\n", old_lines[i]));
-                    i += 1;
-                } else if j < new_lines.len() {
-                    output.push_str(&format!("+ {}\n", new_lines[j]));
-                    j += 1;
-                }
-            }
-            
-            Ok(output)
-        }
+        DiffMode::Line => Ok(render_line_hunks(&diff_structured(old_content, new_content, options))),
         DiffMode::Character => {
             // Simple character-by-character diff
             let old_chars: Vec<char> = old_text.chars().collect();
@@ -297,6 +402,48 @@ pub fn enhanced_diff(
     }
 }
 
+/// The line changes turning `old_content` into `new_content`, as hunks with
+/// `options.context_lines` of context, for callers that lay the diff out
+/// themselves (side by side, inline, ...). `enhanced_diff` renders the same
+/// hunks in `DiffMode::Line`. The diff mode is ignored, and binary content
+/// is diffed as lossy text, so check `looks_binary` first.
+pub fn diff_structured(old_content: &[u8], new_content: &[u8], options: &DiffOptions) -> Vec<hunks::Hunk> {
+    let old_text = String::from_utf8_lossy(old_content);
+    let new_text = String::from_utf8_lossy(new_content);
+    hunks::line_hunks(&old_text, &new_text, options.context_lines)
+}
+
+/// Hunk lines prefixed `  `, `- ` or `+ `, one hunk after another
+fn render_line_hunks(hunks: &[hunks::Hunk]) -> String {
+    let mut output = String::new();
+    for line in hunks.iter().flat_map(|hunk| &hunk.lines) {
+        let (prefix, text) = match line {
+            hunks::HunkLine::Context(text) => ("  ", text),
+            hunks::HunkLine::Remove(text) => ("- ", text),
+            hunks::HunkLine::Add(text) => ("+ ", text),
+        };
+        output.push_str(prefix);
+        output.push_str(text);
+        output.push('\n');
+    }
+    output
+}
+
+/// What `enhanced_diff` reports instead of a text diff when either side is
+/// binary: sizes and hashes, or nothing if the contents are the same
+fn binary_summary(old_content: &[u8], new_content: &[u8]) -> String {
+    if old_content == new_content {
+        return String::new();
+    }
+    format!(
+        "Binary files differ\n- {} bytes {}\n+ {} bytes {}\n",
+        old_content.len(),
+        blake3::hash(old_content),
+        new_content.len(),
+        blake3::hash(new_content)
+    )
+}
+
 impl Default for DiffOptions {
     fn default() -> Self {
         Self {
@@ -536,6 +683,7 @@ mod tests {
                 Op::Copy { offset: 0, len: 5 },
                 Op::Insert { data: vec![1, 2, 3] },
             ],
+            hunks: Vec::new(),
         };
         
         let debug_str = format!("{:?}", patch);
@@ -581,6 +729,28 @@ mod tests {
         assert!(renames[0].similarity > 0.8);
     }
 
+    #[test]
+    fn test_rename_detection_pairs_exact_copies_first() {
+        // Every old file is somewhat like every new one; only the exact
+        // copies are the renames
+        let body = "shared body line\n".repeat(4);
+        let mut deleted_files = HashMap::new();
+        let mut added_files = HashMap::new();
+        for n in 0..8 {
+            let content = format!("// file {}\n{}", n, body).into_bytes();
+            deleted_files.insert(format!("old/{}.rs", n), content.clone());
+            added_files.insert(format!("new/{}.rs", n), content);
+        }
+
+        let mut renames = detect_renames(&deleted_files, &added_files, 0.3);
+        renames.sort_by(|a, b| a.old_path.cmp(&b.old_path));
+        assert_eq!(renames.len(), 8);
+        for rename in &renames {
+            assert_eq!(rename.new_path, rename.old_path.replace("old/", "new/"));
+            assert_eq!(rename.similarity, 1.0);
+        }
+    }
+
     #[test]
     fn test_copy_detection() {
         let mut existing_files = HashMap::new();
@@ -615,6 +785,53 @@ mod tests {
             change_type == "equal" && word == "Hello"));
     }
 
+    fn side(changes: &[(String, String)], skip: &str) -> String {
+        changes.iter().filter(|(kind, _)| kind != skip).map(|(_, token)| token.as_str()).collect()
+    }
+
+    #[test]
+    fn test_word_diff_spacing_only_change() {
+        let old_text = "let x = 1;\n\tfoo(x);\n";
+        let new_text = "let x  = 1;\n    foo(x);\n";
+        let changes = word_diff(old_text, new_text);
+
+        assert_eq!(side(&changes, "insert"), old_text);
+        assert_eq!(side(&changes, "delete"), new_text);
+        let edits: Vec<&(String, String)> = changes.iter().filter(|(kind, _)| kind != "equal").collect();
+        assert_eq!(edits.len(), 4);
+        assert!(edits.iter().all(|(_, token)| token.trim().is_empty()), "{:?}", edits);
+    }
+
+    #[test]
+    fn test_word_diff_word_inside_punctuation() {
+        let changes = word_diff("call(foo, bar);", "call(foo, baz);");
+        let edits: Vec<(&str, &str)> = changes
+            .iter()
+            .filter(|(kind, _)| kind != "equal")
+            .map(|(kind, token)| (kind.as_str(), token.as_str()))
+            .collect();
+        assert_eq!(edits, [("delete", "bar"), ("insert", "baz")]);
+        assert_eq!(tokenize("call(foo, bar);"), ["call", "(", "foo", ",", " ", "bar", ");"]);
+    }
+
+    #[test]
+    fn test_word_diff_far_apart_changes() {
+        // Token-level LCS over the whole file would need tens of millions of
+        // cells here; only the two changed lines should be compared
+        let old_text: String = (0..2000).map(|i| format!("line {}\n", i)).collect();
+        let new_text = old_text.replacen("line 0\n", "row 0\n", 1).replace("line 1999\n", "line 2k\n");
+        let changes = word_diff(&old_text, &new_text);
+
+        assert_eq!(side(&changes, "insert"), old_text);
+        assert_eq!(side(&changes, "delete"), new_text);
+        let edits: Vec<(&str, &str)> = changes
+            .iter()
+            .filter(|(kind, _)| kind != "equal")
+            .map(|(kind, token)| (kind.as_str(), token.as_str()))
+            .collect();
+        assert_eq!(edits, [("delete", "line"), ("insert", "row"), ("delete", "1999"), ("insert", "2k")]);
+    }
+
     #[test]
     fn test_enhanced_diff_word_mode() {
         let old_content = b"Hello world";
@@ -646,6 +863,90 @@ mod tests {
         assert!(diff.contains("+ new_line"));
     }
 
+    #[test]
+    fn test_diff_structured_hunks_match_rendered_line_diff() {
+        use hunks::{Hunk, HunkLine};
+
+        let old_content = b"a\nb\nc\nd\ne\nf\ng\nh\n";
+        let new_content = b"a\nB\nc\nd\ne\nf\ng\nh\ni\n";
+        let options = DiffOptions { mode: DiffMode::Line, context_lines: 1, ..Default::default() };
+        let structured = diff_structured(old_content, new_content, &options);
+        let line = |kind: fn(String) -> HunkLine, text: &str| kind(text.to_string());
+        assert_eq!(
+            structured,
+            [
+                Hunk {
+                    old_start: 1,
+                    old_count: 3,
+                    new_start: 1,
+                    new_count: 3,
+                    lines: vec![
+                        line(HunkLine::Context, "a"),
+                        line(HunkLine::Remove, "b"),
+                        line(HunkLine::Add, "B"),
+                        line(HunkLine::Context, "c"),
+                    ],
+                },
+                Hunk {
+                    old_start: 8,
+                    old_count: 1,
+                    new_start: 8,
+                    new_count: 2,
+                    lines: vec![line(HunkLine::Context, "h"), line(HunkLine::Add, "i")],
+                },
+            ]
+        );
+
+        let rendered = enhanced_diff(old_content, new_content, &options).unwrap();
+        assert_eq!(rendered, "  a\n- b\n+ B\n  c\n  h\n+ i\n");
+        let rendered_lines: Vec<&str> = rendered.lines().collect();
+        let hunk_lines: Vec<String> = structured
+            .iter()
+            .flat_map(|hunk| &hunk.lines)
+            .map(|line| match line {
+                HunkLine::Context(text) => format!("  {}", text),
+                HunkLine::Remove(text) => format!("- {}", text),
+                HunkLine::Add(text) => format!("+ {}", text),
+            })
+            .collect();
+        assert_eq!(rendered_lines, hunk_lines);
+
+        // Unchanged content has no hunks and renders as nothing
+        assert!(diff_structured(old_content, old_content, &options).is_empty());
+        assert_eq!(enhanced_diff(old_content, old_content, &options).unwrap(), "");
+    }
+
+    #[test]
+    fn test_enhanced_diff_summarizes_binary_in_every_mode() {
+        let old_content = b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR";
+        let new_content = b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR\x00\x01";
+        for mode in [DiffMode::Character, DiffMode::Word, DiffMode::Line] {
+            let options = DiffOptions { mode, ..Default::default() };
+            let diff = enhanced_diff(old_content, new_content, &options).unwrap();
+            assert_eq!(
+                diff,
+                format!(
+                    "Binary files differ\n- 16 bytes {}\n+ 18 bytes {}\n",
+                    blake3::hash(old_content),
+                    blake3::hash(new_content)
+                )
+            );
+        }
+
+        // One binary side is enough, and equal contents report nothing
+        let options = DiffOptions::default();
+        assert!(enhanced_diff(b"text\n", new_content, &options).unwrap().starts_with("Binary files differ"));
+        assert_eq!(enhanced_diff(new_content, new_content, &options).unwrap(), "");
+    }
+
+    #[test]
+    fn test_enhanced_diff_keeps_non_utf8_text_as_text() {
+        // Latin-1 text has no NUL bytes, so it is still diffed line by line
+        let options = DiffOptions { mode: DiffMode::Line, context_lines: 0, ..Default::default() };
+        let diff = enhanced_diff(b"caf\xe9\n", b"cafe\n", &options).unwrap();
+        assert_eq!(diff, "- caf\u{fffd}\n+ cafe\n");
+    }
+
     #[test]
     fn test_diff_options_default() {
         let options = DiffOptions::default();
@@ -687,4 +988,62 @@ mod tests {
         assert_eq!(deserialized.dest_path, copy.dest_path);
         assert_eq!(deserialized.similarity, copy.similarity);
     }
+
+    #[test]
+    fn test_patch_file_round_trip() {
+        let base = b"The quick brown fox jumps over the lazy dog";
+        let new = b"The quick brown fox leaps over the lazy dog!";
+        let patch = make(base, new, 8).unwrap();
+
+        let mut file = Vec::new();
+        write_patch(&patch, &mut file).unwrap();
+        assert!(file.starts_with(b"RUNE-PATCH v1\n"));
+
+        let read = read_patch(file.as_slice()).unwrap();
+        assert_eq!(read.base_hash, patch.base_hash);
+        assert_eq!(read.new_hash, patch.new_hash);
+        assert_eq!(apply(base, &read).unwrap(), new);
+    }
+
+    #[test]
+    fn test_read_patch_rejects_bad_magic() {
+        let patch = make(b"abcdefghij", b"abcdefghijk", 8).unwrap();
+        let raw_json = serde_json::to_vec(&patch).unwrap();
+
+        let err = read_patch(raw_json.as_slice()).unwrap_err();
+        assert!(err.to_string().contains("not a rune patch file"));
+    }
+
+    #[test]
+    fn test_read_patch_rejects_future_version() {
+        let mut file = Vec::new();
+        write_patch(&make(b"abcdefghij", b"abcdefghijk", 8).unwrap(), &mut file).unwrap();
+        let future = String::from_utf8(file).unwrap().replacen("v1", "v99", 1);
+
+        let err = read_patch(future.as_bytes()).unwrap_err();
+        assert!(err.to_string().contains("newer than supported"));
+    }
+
+    #[test]
+    fn test_apply_fuzzy_on_drifted_base() {
+        let base = "fn main() {\n    let x = 1;\n    println!(\"{}\", x);\n}\n";
+        let new = base.replace("let x = 1;", "let x = 2;");
+        let patch = make(base.as_bytes(), new.as_bytes(), 8).unwrap();
+        assert_eq!(patch.hunks.len(), 1);
+
+        let drifted = format!("use std::fmt;\n\n{}", base);
+        assert!(apply(drifted.as_bytes(), &patch).is_err());
+
+        let result = apply_fuzzy(drifted.as_bytes(), &patch).unwrap();
+        assert!(result.is_clean());
+        assert_eq!(result.applied[0].offset, 2);
+        assert_eq!(result.content, format!("use std::fmt;\n\n{}", new));
+    }
+
+    #[test]
+    fn test_apply_fuzzy_rejects_binary_patch() {
+        let patch = make(&[0xff, 0x00, 0x01], &[0xff, 0x00, 0x02], 8).unwrap();
+        assert!(patch.hunks.is_empty());
+        assert!(apply_fuzzy(b"text", &patch).is_err());
+    }
 }
@@ -1289,7 +1289,7 @@ rune check                  # Planned feature
 
 # Keep ignore patterns updated
 rune ignore list
-rune ignore optimize        # Planned feature
+rune ignore optimize        # Drop duplicate and shadowed rules
 
 # Regular backups
 rune push                   # Push to remote regularly
@@ -5,14 +5,19 @@
 
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
+use rune_core::layout::RepoLayout;
 use rune_core::Author;
-use rune_store::Store;
+use rune_security::encryption::{EncryptionConfig, EncryptionManager};
+use rune_store::{BranchHook, BranchOperation, DiffSource, DiscardOperation, PathChange, Store};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
+mod review;
+pub use review::{ReviewFormat, REVIEW_DIFF_LINE_LIMIT};
+
 /// A draft commit represents work-in-progress that can be shelved and restored
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DraftCommit {
@@ -57,6 +62,82 @@ pub struct DraftFile {
     pub is_deleted: bool,
     /// Original file hash in base commit (if exists)
     pub original_hash: Option<String>,
+    /// Whether `content` holds ciphertext rather than the file bytes
+    #[serde(default)]
+    pub encrypted: bool,
+}
+
+/// What listing a draft needs, kept in a small file next to it so that
+/// listings don't read file contents
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DraftSummary {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub author: Author,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub base_branch: String,
+    pub tags: Vec<String>,
+    pub is_active: bool,
+    /// Files in the draft
+    pub file_count: usize,
+}
+
+impl DraftSummary {
+    pub fn of(draft: &DraftCommit) -> Self {
+        Self {
+            id: draft.id.clone(),
+            name: draft.name.clone(),
+            description: draft.description.clone(),
+            author: draft.author.clone(),
+            created_at: draft.created_at,
+            updated_at: draft.updated_at,
+            base_branch: draft.base_branch.clone(),
+            tags: draft.tags.clone(),
+            is_active: draft.is_active,
+            file_count: draft.files.len(),
+        }
+    }
+}
+
+/// Which part of a listing to return: at most `limit` entries after
+/// skipping `offset`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Page {
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+impl Page {
+    pub fn new(offset: usize, limit: Option<usize>) -> Self {
+        Self { offset, limit }
+    }
+
+    /// The entries of `items` on this page
+    pub fn apply<T>(self, items: Vec<T>) -> Vec<T> {
+        items.into_iter().skip(self.offset).take(self.limit.unwrap_or(usize::MAX)).collect()
+    }
+}
+
+/// Which changes a new draft captures
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DraftSource {
+    /// Working tree files that differ from HEAD
+    #[default]
+    Worktree,
+    /// Exactly what is staged, ignoring edits made since
+    StagedOnly,
+}
+
+/// What `DraftManager::create_draft_from` put into the new draft
+#[derive(Debug, Clone)]
+pub struct DraftCapture {
+    pub id: String,
+    /// Repository-relative paths of the captured files
+    pub captured: Vec<String>,
+    /// Requested paths with no changes, left out of the draft
+    pub skipped: Vec<PathBuf>,
 }
 
 /// Configuration for the draft system
@@ -72,8 +153,36 @@ pub struct DraftConfig {
     pub auto_checkpoint: bool,
     /// How often to auto-checkpoint (in minutes)
     pub auto_checkpoint_interval: u32,
+    /// Where draft files are kept; relative paths are resolved against the
+    /// repository root. Defaults to `.rune/drafts`.
+    #[serde(default)]
+    pub storage_dir: Option<PathBuf>,
+    /// Encrypt file contents of saved drafts with the passphrase from
+    /// `RUNE_DRAFT_PASSPHRASE`. Names, tags and paths stay readable.
+    #[serde(default)]
+    pub encrypt: bool,
+    /// Save uncommitted work, untracked files included, as a draft before
+    /// `reset --hard` or an abort throws it away
+    #[serde(default = "default_safety_net")]
+    pub safety_net: bool,
+}
+
+fn default_safety_net() -> bool {
+    true
 }
 
+/// Tag on drafts the safety net saves
+pub const SAFETY_TAG: &str = "safety";
+
+/// Environment variable holding the passphrase for encrypted drafts
+pub const DRAFT_PASSPHRASE_ENV: &str = "RUNE_DRAFT_PASSPHRASE";
+
+/// Drafts shelved by a branch switch, keyed by the branch that was left
+const AUTO_SHELVED_FILE: &str = "draft_autoshelved.json";
+
+/// Extension of the `DraftSummary` file saved next to each `<id>.json`
+const SUMMARY_EXT: &str = "summary";
+
 impl Default for DraftConfig {
     fn default() -> Self {
         Self {
@@ -82,6 +191,9 @@ impl Default for DraftConfig {
             default_tags: vec!["draft".to_string()],
             auto_checkpoint: false,
             auto_checkpoint_interval: 15,
+            storage_dir: None,
+            encrypt: false,
+            safety_net: true,
         }
     }
 }
@@ -91,24 +203,50 @@ pub struct DraftManager {
     store: Store,
     config: DraftConfig,
     drafts_dir: PathBuf,
+    passphrase: Option<String>,
+    /// Bytes read from draft and summary files, for tests to tell what a
+    /// listing loaded
+    #[cfg(test)]
+    bytes_read: std::sync::atomic::AtomicU64,
 }
 
 impl DraftManager {
     /// Create a new draft manager
     pub fn new(store: Store) -> Result<Self> {
-        let drafts_dir = store.rune_dir.join("drafts");
-        fs::create_dir_all(&drafts_dir)
-            .context("Failed to create drafts directory")?;
-
         let config = Self::load_config(&store)?;
+        let drafts_dir = Self::resolve_drafts_dir(&store, &config)?;
 
         Ok(Self {
             store,
             config,
             drafts_dir,
+            passphrase: std::env::var(DRAFT_PASSPHRASE_ENV).ok().filter(|p| !p.is_empty()),
+            #[cfg(test)]
+            bytes_read: Default::default(),
         })
     }
 
+    /// Use `passphrase` instead of `RUNE_DRAFT_PASSPHRASE` for encrypted drafts
+    pub fn set_passphrase(&mut self, passphrase: impl Into<String>) {
+        self.passphrase = Some(passphrase.into());
+    }
+
+    /// Directory draft files are read from and written to
+    pub fn drafts_dir(&self) -> &Path {
+        &self.drafts_dir
+    }
+
+    fn resolve_drafts_dir(store: &Store, config: &DraftConfig) -> Result<PathBuf> {
+        let drafts_dir = match &config.storage_dir {
+            Some(dir) if dir.is_absolute() => dir.clone(),
+            Some(dir) => store.root.join(dir),
+            None => store.rune_dir.join("drafts"),
+        };
+        fs::create_dir_all(&drafts_dir)
+            .with_context(|| format!("Failed to create drafts directory: {:?}", drafts_dir))?;
+        Ok(drafts_dir)
+    }
+
     /// Get current configuration
     pub fn config(&self) -> &DraftConfig {
         &self.config
@@ -116,6 +254,7 @@ impl DraftManager {
 
     /// Update configuration
     pub fn update_config(&mut self, config: DraftConfig) -> Result<()> {
+        self.drafts_dir = Self::resolve_drafts_dir(&self.store, &config)?;
         self.config = config;
         self.save_config()
     }
@@ -145,6 +284,56 @@ impl DraftManager {
 
     /// Create a new draft from current working directory
     pub fn create_draft(&mut self, name: String, description: Option<String>) -> Result<String> {
+        let files = self.collect_working_files()?;
+        let draft = self.new_draft(name, description, files)?;
+        println!("Created draft '{}' with {} files", draft.name, draft.files.len());
+        Ok(draft.id)
+    }
+
+    /// Create a draft from some of the changes: those below `paths` (all of
+    /// them when `None`), taken from the working tree or the staging area.
+    /// Untracked files are captured only when named by `paths`. Requested
+    /// paths without changes are skipped and reported rather than stored.
+    pub fn create_draft_from(
+        &mut self,
+        paths: Option<&[PathBuf]>,
+        source: DraftSource,
+        name: String,
+        description: Option<String>,
+    ) -> Result<DraftCapture> {
+        let requested = paths.unwrap_or_default();
+        let selected: Vec<String> = requested.iter().map(|p| self.repo_relative(p)).collect();
+        let files = self.collect_files(&selected, source)?;
+        let skipped: Vec<PathBuf> = requested
+            .iter()
+            .zip(&selected)
+            .filter(|(_, spec)| !files.keys().any(|path| is_below(path, spec)))
+            .map(|(path, _)| path.clone())
+            .collect();
+        if files.is_empty() && !requested.is_empty() {
+            anyhow::bail!("No changes to capture in the given paths");
+        }
+        let captured = files.keys().cloned().collect();
+        let files = files.into_values().map(|f| (f.path.clone(), f)).collect();
+        let draft = self.new_draft(name, description, files)?;
+        Ok(DraftCapture { id: draft.id, captured, skipped })
+    }
+
+    /// Put the files `draft_id` captured back to their base content and
+    /// unstage them, leaving every other file as it is
+    pub fn remove_captured(&mut self, draft_id: &str) -> Result<()> {
+        let draft = self.load_draft(draft_id)?;
+        self.restore_base(&draft)?;
+        let paths: Vec<String> = draft.files.keys().map(|p| self.repo_relative(p)).collect();
+        self.store.unstage(&paths)
+    }
+
+    fn new_draft(
+        &mut self,
+        name: String,
+        description: Option<String>,
+        files: HashMap<PathBuf, DraftFile>,
+    ) -> Result<DraftCommit> {
         let id = Uuid::new_v4().to_string();
         
         // Get current branch and commit
@@ -158,9 +347,6 @@ impl DraftManager {
             email: format!("{}@local", whoami::username()),
         };
         
-        // Collect modified files from working directory
-        let files = self.collect_working_files()?;
-        
         let now = Utc::now();
         let draft = DraftCommit {
             id: id.clone(),
@@ -177,9 +363,7 @@ impl DraftManager {
         };
 
         self.save_draft(&draft)?;
-        
-        println!("Created draft '{}' with {} files", draft.name, draft.files.len());
-        Ok(id)
+        Ok(draft)
     }
 
     /// Get current head commit ID
@@ -196,11 +380,19 @@ impl DraftManager {
     pub fn apply_draft(&mut self, draft_id: &str) -> Result<()> {
         let mut draft = self.load_draft(draft_id)?;
         
+        // A draft file naming a path outside the repository is refused
+        // before anything is written
+        let targets = draft
+            .files
+            .iter()
+            .map(|(path, draft_file)| Ok((self.store.root.join(self.store.checked_path(path)?), draft_file)))
+            .collect::<Result<Vec<_>>>()?;
+
         // Deactivate any currently active draft
         self.deactivate_all_drafts()?;
         
         // Apply files to working directory
-        for (path, draft_file) in &draft.files {
+        for (path, draft_file) in &targets {
             if draft_file.is_deleted {
                 if path.exists() {
                     fs::remove_file(path)
@@ -244,13 +436,7 @@ impl DraftManager {
             anyhow::bail!("Draft '{}' is not currently active", draft.name);
         }
         
-        // Remove files that were added by this draft
-        for (path, draft_file) in &draft.files {
-            if draft_file.is_new && path.exists() {
-                fs::remove_file(path)
-                    .with_context(|| format!("Failed to remove file: {:?}", path))?;
-            }
-        }
+        self.restore_base(&draft)?;
         
         // Mark draft as inactive
         draft.is_active = false;
@@ -288,6 +474,12 @@ impl DraftManager {
         let draft_path = self.drafts_dir.join(format!("{}.json", draft_id));
         fs::remove_file(&draft_path)
             .context("Failed to delete draft file")?;
+        match fs::remove_file(draft_path.with_extension(SUMMARY_EXT)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(e).context("Failed to delete draft summary");
+            }
+            _ => {}
+        }
         
         println!("Deleted draft '{}'", draft.name);
         Ok(())
@@ -318,6 +510,40 @@ impl DraftManager {
         Ok(drafts)
     }
 
+    /// Summaries of the drafts on `page`, newest first. Only summary files
+    /// are read; a draft saved before they existed is read whole once to
+    /// write its summary.
+    pub fn list_draft_summaries(&self, page: Page) -> Result<Vec<DraftSummary>> {
+        let mut summaries = Vec::new();
+        if !self.drafts_dir.exists() {
+            return Ok(summaries);
+        }
+        for entry in fs::read_dir(&self.drafts_dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|s| s.to_str()) != Some("json") {
+                continue;
+            }
+            let summary_path = path.with_extension(SUMMARY_EXT);
+            let saved = self.read_draft_file(&summary_path).ok().and_then(|c| serde_json::from_str(&c).ok());
+            let summary = match saved {
+                Some(summary) => summary,
+                None => {
+                    let Ok(draft) = self.load_draft_from_path(&path) else {
+                        continue;
+                    };
+                    let summary = DraftSummary::of(&draft);
+                    // Listing still works where the drafts directory is read-only
+                    fs::write(&summary_path, serde_json::to_string_pretty(&summary)?).ok();
+                    summary
+                }
+            };
+            summaries.push(summary);
+        }
+        // Ties broken by id, so pages don't overlap
+        summaries.sort_by(|a, b| b.created_at.cmp(&a.created_at).then_with(|| a.id.cmp(&b.id)));
+        Ok(page.apply(summaries))
+    }
+
     /// Create an automatic checkpoint
     pub fn create_checkpoint(&mut self, name: Option<String>) -> Result<String> {
         let checkpoint_name = name.unwrap_or_else(|| {
@@ -327,9 +553,34 @@ impl DraftManager {
         self.create_draft(checkpoint_name, Some("Automatic checkpoint".to_string()))
     }
 
+    /// Save every change in the working tree, untracked files included, as
+    /// a draft tagged `safety` before `op` discards it. Returns `None` when
+    /// the safety net is off or there is nothing to lose.
+    pub fn create_safety_draft(&mut self, op: DiscardOperation) -> Result<Option<DraftCommit>> {
+        if !self.config.safety_net {
+            return Ok(None);
+        }
+        let files = self.collect_files(&[".".to_string()], DraftSource::Worktree)?;
+        if files.is_empty() {
+            return Ok(None);
+        }
+        let files = files.into_values().map(|f| (f.path.clone(), f)).collect();
+        let name = format!("safety-{}", Utc::now().format("%Y%m%d-%H%M%S"));
+        let description = format!("Working tree before {}", op.as_str());
+        let mut draft = self.new_draft(name, Some(description), files)?;
+        draft.tags.push(SAFETY_TAG.to_string());
+        self.save_draft(&draft)?;
+        println!(
+            "Saved uncommitted work as draft '{}'; restore it with `rune draft apply {}`",
+            draft.name,
+            &draft.id[..8]
+        );
+        Ok(Some(draft))
+    }
+
     /// Clean up old drafts based on configuration
     pub fn cleanup_old_drafts(&mut self) -> Result<usize> {
-        let drafts = self.list_drafts()?;
+        let drafts = self.list_draft_summaries(Page::default())?;
         let cutoff_date = Utc::now() - chrono::Duration::days(self.config.auto_cleanup_days as i64);
         
         let mut cleaned = 0;
@@ -348,6 +599,68 @@ impl DraftManager {
         self.load_draft(draft_id)
     }
 
+    /// The draft currently applied to the working directory, if any
+    pub fn active_draft(&self) -> Result<Option<DraftCommit>> {
+        match self.list_draft_summaries(Page::default())?.into_iter().find(|d| d.is_active) {
+            Some(summary) => Ok(Some(self.load_draft_from_path(&self.drafts_dir.join(format!("{}.json", summary.id)))?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Shelve the active draft because `branch` is being left, remembering it
+    /// so that returning to `branch` can offer to apply it again
+    pub fn auto_shelve(&mut self, branch: &str) -> Result<Option<DraftCommit>> {
+        let Some(draft) = self.active_draft()? else {
+            return Ok(None);
+        };
+        self.shelve_draft(&draft.id)?;
+        let mut shelved = self.read_auto_shelved()?;
+        shelved.insert(branch.to_string(), draft.id.clone());
+        self.write_auto_shelved(&shelved)?;
+        Ok(Some(draft))
+    }
+
+    /// Draft auto-shelved when `branch` was last left, if it still exists
+    pub fn auto_shelved_for(&self, branch: &str) -> Result<Option<DraftCommit>> {
+        let shelved = self.read_auto_shelved()?;
+        let Some(id) = shelved.get(branch) else {
+            return Ok(None);
+        };
+        let path = self.drafts_dir.join(format!("{}.json", id));
+        Ok(self.load_draft_from_path(&path).ok().filter(|d| !d.is_active))
+    }
+
+    /// Stop offering the draft auto-shelved when `branch` was left
+    pub fn forget_auto_shelved(&mut self, branch: &str) -> Result<()> {
+        let mut shelved = self.read_auto_shelved()?;
+        if shelved.remove(branch).is_some() {
+            self.write_auto_shelved(&shelved)?;
+        }
+        Ok(())
+    }
+
+    /// Rebase the active draft onto `branch` at `commit`: its files stay in
+    /// the working directory and it now counts as based on that branch
+    pub fn carry_active_draft(&mut self, branch: &str, commit: &str) -> Result<Option<DraftCommit>> {
+        let Some(mut draft) = self.active_draft()? else {
+            return Ok(None);
+        };
+        draft.base_branch = branch.to_string();
+        draft.base_commit = commit.to_string();
+        draft.updated_at = Utc::now();
+        self.save_draft(&draft)?;
+        Ok(Some(draft))
+    }
+
+    /// Paths the applied draft touches, relative to the repository root;
+    /// empty when no draft is applied
+    pub fn active_draft_paths(&self) -> Result<BTreeSet<String>> {
+        Ok(match self.active_draft()? {
+            Some(draft) => draft.files.keys().map(|path| self.repo_relative(path)).collect(),
+            None => BTreeSet::new(),
+        })
+    }
+
     /// Add tags to a draft
     pub fn add_tags(&mut self, draft_id: &str, tags: Vec<String>) -> Result<()> {
         let mut draft = self.load_draft(draft_id)?;
@@ -378,18 +691,91 @@ impl DraftManager {
     // Private helper methods
 
     fn collect_working_files(&self) -> Result<HashMap<PathBuf, DraftFile>> {
-        let files = HashMap::new();
-        
-        // TODO: Implement proper file collection
-        // For now, return empty map to get basic functionality working
-        // This would need to:
-        // 1. Scan working directory for modified files
-        // 2. Compare with index to detect changes
-        // 3. Include new/deleted files
-        
+        let files = self.collect_files(&[], DraftSource::Worktree)?;
+        Ok(files.into_values().map(|f| (f.path.clone(), f)).collect())
+    }
+
+    /// Changed files below `selected` (everywhere when empty), keyed by
+    /// repository-relative path
+    fn collect_files(&self, selected: &[String], source: DraftSource) -> Result<BTreeMap<String, DraftFile>> {
+        let base = self.store.head_commit().map(|id| self.store.tree_snapshot(&id)).unwrap_or_default();
+        let target = match source {
+            DraftSource::Worktree => DiffSource::Worktree,
+            DraftSource::StagedOnly => DiffSource::Index,
+        };
+        let mut paths = BTreeSet::new();
+        for change in self.store.changed_paths(&self.store.commit_source("HEAD")?, &target, selected)? {
+            match change {
+                PathChange::Renamed { from, to, .. } => {
+                    paths.insert(from);
+                    paths.insert(to);
+                }
+                other => {
+                    paths.insert(other.path().to_string());
+                }
+            }
+        }
+        if source == DraftSource::Worktree && !selected.is_empty() {
+            let untracked = self.store.status()?.untracked;
+            paths.extend(untracked.into_iter().filter(|u| selected.iter().any(|s| is_below(u, s))));
+        }
+
+        let mut files = BTreeMap::new();
+        for rel in paths {
+            let full = self.store.root.join(&rel);
+            let content = match source {
+                DraftSource::Worktree => fs::read(&full).ok(),
+                DraftSource::StagedOnly => self.store.staged_content(&rel)?,
+            };
+            let file = DraftFile {
+                path: full.clone(),
+                mode: fs::metadata(&full).map(|m| Self::get_file_mode(&m)).unwrap_or(0o644),
+                hash: blake3::hash(content.as_deref().unwrap_or_default()).to_hex().to_string(),
+                is_new: !base.contains_key(&rel),
+                is_deleted: content.is_none(),
+                original_hash: base.get(&rel).cloned(),
+                content: content.unwrap_or_default(),
+                encrypted: false,
+            };
+            files.insert(rel, file);
+        }
         Ok(files)
     }
 
+    /// Remove the files `draft` added and put the others back as its base
+    /// commit has them
+    fn restore_base(&self, draft: &DraftCommit) -> Result<()> {
+        for (path, draft_file) in &draft.files {
+            if draft_file.is_new {
+                if path.exists() {
+                    fs::remove_file(path)
+                        .with_context(|| format!("Failed to remove file: {:?}", path))?;
+                }
+            } else if !draft.base_commit.is_empty() {
+                let rel = path.strip_prefix(&self.store.root).unwrap_or(path);
+                self.store.restore_file_from_commit(&draft.base_commit, rel)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// `path` relative to the repository root, `/`-separated, with `.` and
+    /// `..` resolved
+    fn repo_relative(&self, path: &Path) -> String {
+        let rel = path.strip_prefix(&self.store.root).unwrap_or(path);
+        let mut parts: Vec<String> = Vec::new();
+        for component in rel.components() {
+            match component {
+                std::path::Component::Normal(part) => parts.push(part.to_string_lossy().into_owned()),
+                std::path::Component::ParentDir => {
+                    parts.pop();
+                }
+                _ => {}
+            }
+        }
+        parts.join("/")
+    }
+
     fn get_file_mode(metadata: &fs::Metadata) -> u32 {
         #[cfg(unix)]
         {
@@ -406,32 +792,84 @@ impl DraftManager {
         }
     }
 
+    fn encryption(&self) -> Result<(EncryptionManager, &str)> {
+        let passphrase = self.passphrase.as_deref().with_context(|| {
+            format!("Draft encryption needs a passphrase; set {}", DRAFT_PASSPHRASE_ENV)
+        })?;
+        Ok((EncryptionManager::new(EncryptionConfig::default()), passphrase))
+    }
+
     fn save_draft(&self, draft: &DraftCommit) -> Result<()> {
         let draft_path = self.drafts_dir.join(format!("{}.json", draft.id));
-        let content = serde_json::to_string_pretty(draft)
+        let mut draft = draft.clone();
+        if self.config.encrypt && draft.files.values().any(|f| !f.encrypted) {
+            let (manager, passphrase) = self.encryption()?;
+            for file in draft.files.values_mut().filter(|f| !f.encrypted) {
+                file.content = manager.encrypt_data(&file.content, passphrase)
+                    .with_context(|| format!("Failed to encrypt draft file: {:?}", file.path))?;
+                file.encrypted = true;
+            }
+        }
+        let content = serde_json::to_string_pretty(&draft)
             .context("Failed to serialize draft")?;
         fs::write(&draft_path, content)
             .context("Failed to write draft file")?;
+        fs::write(draft_path.with_extension(SUMMARY_EXT), serde_json::to_string_pretty(&DraftSummary::of(&draft))?)
+            .context("Failed to write draft summary")?;
         Ok(())
     }
 
+    /// Load a draft with its file contents decrypted
     fn load_draft(&self, draft_id: &str) -> Result<DraftCommit> {
         let draft_path = self.drafts_dir.join(format!("{}.json", draft_id));
-        self.load_draft_from_path(&draft_path)
+        let mut draft = self.load_draft_from_path(&draft_path)?;
+        if draft.files.values().any(|f| f.encrypted) {
+            let (manager, passphrase) = self.encryption()?;
+            for file in draft.files.values_mut().filter(|f| f.encrypted) {
+                file.content = manager.decrypt_data(&file.content, passphrase)
+                    .with_context(|| format!("Failed to decrypt draft file: {:?}", file.path))?;
+                file.encrypted = false;
+            }
+        }
+        Ok(draft)
     }
 
+    /// Load a draft as stored; contents stay encrypted, which is enough for
+    /// listing and for updating metadata
     fn load_draft_from_path(&self, path: &Path) -> Result<DraftCommit> {
-        let content = fs::read_to_string(path)
+        let content = self.read_draft_file(path)
             .context("Failed to read draft file")?;
         serde_json::from_str(&content)
             .context("Failed to parse draft file")
     }
 
+    fn read_draft_file(&self, path: &Path) -> std::io::Result<String> {
+        let content = fs::read_to_string(path)?;
+        #[cfg(test)]
+        self.bytes_read.fetch_add(content.len() as u64, std::sync::atomic::Ordering::Relaxed);
+        Ok(content)
+    }
+
+    fn read_auto_shelved(&self) -> Result<BTreeMap<String, String>> {
+        let path = self.store.rune_dir.join(AUTO_SHELVED_FILE);
+        if !path.exists() {
+            return Ok(BTreeMap::new());
+        }
+        let content = fs::read_to_string(&path).context("Failed to read auto-shelved drafts")?;
+        serde_json::from_str(&content).context("Failed to parse auto-shelved drafts")
+    }
+
+    fn write_auto_shelved(&self, shelved: &BTreeMap<String, String>) -> Result<()> {
+        let content = serde_json::to_string_pretty(shelved)?;
+        fs::write(self.store.rune_dir.join(AUTO_SHELVED_FILE), content)
+            .context("Failed to write auto-shelved drafts")?;
+        Ok(())
+    }
+
     fn deactivate_all_drafts(&mut self) -> Result<()> {
-        let drafts = self.list_drafts()?;
-        
-        for mut draft in drafts {
-            if draft.is_active {
+        for summary in self.list_draft_summaries(Page::default())? {
+            if summary.is_active {
+                let mut draft = self.load_draft_from_path(&self.drafts_dir.join(format!("{}.json", summary.id)))?;
                 draft.is_active = false;
                 draft.updated_at = Utc::now();
                 self.save_draft(&draft)?;
@@ -442,6 +880,107 @@ impl DraftManager {
     }
 }
 
+/// Whether `path` is `spec` or lies below it; `.` or an empty spec is the
+/// whole tree
+fn is_below(path: &str, spec: &str) -> bool {
+    spec.is_empty() || spec == "." || path == spec || path.strip_prefix(spec).is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// What a branch operation does with an applied draft
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DraftPolicy {
+    /// Refuse the operation, naming the draft
+    #[default]
+    Refuse,
+    /// Shelve the draft first and remember it for the branch being left
+    AutoShelve,
+    /// Keep the draft applied and rebase it onto the checked out branch
+    Carry,
+}
+
+/// `BranchHook` that keeps an applied draft from being mixed into another
+/// branch. Switching to a branch at the same commit carries the draft along,
+/// since the working directory it was made against doesn't change.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DraftBranchGuard {
+    pub policy: DraftPolicy,
+}
+
+impl DraftBranchGuard {
+    pub fn new(policy: DraftPolicy) -> Self {
+        Self { policy }
+    }
+
+    /// A manager over its own handle on `store`, which carries no hooks
+    fn manager(store: &Store) -> Result<DraftManager> {
+        let layout = RepoLayout { root: store.root.clone(), rune_dir: store.rune_dir.clone() };
+        DraftManager::new(Store::open_layout(layout)?)
+    }
+}
+
+impl BranchHook for DraftBranchGuard {
+    fn before(&self, store: &Store, op: BranchOperation, from: Option<&str>, to: &str) -> Result<()> {
+        let mut manager = Self::manager(store)?;
+        let Some(draft) = manager.active_draft()? else {
+            return Ok(());
+        };
+        if op == BranchOperation::Checkout {
+            let same_commit = store.read_ref(&format!("refs/heads/{}", to)) == store.head_commit();
+            if same_commit || self.policy == DraftPolicy::Carry {
+                return Ok(());
+            }
+        }
+        match self.policy {
+            DraftPolicy::AutoShelve => {
+                manager.auto_shelve(from.unwrap_or(&draft.base_branch))?;
+                Ok(())
+            }
+            _ => {
+                let verb = match op {
+                    BranchOperation::Checkout => "switch to",
+                    BranchOperation::Merge => "merge",
+                    BranchOperation::Pull => "pull",
+                };
+                let carry = if op == BranchOperation::Checkout { ", --carry" } else { "" };
+                anyhow::bail!(
+                    "Draft '{}' ({}) is applied to the working directory; cannot {} '{}'.\n\
+                     Shelve it with `rune draft shelve {}` or rerun with --auto-shelve{}",
+                    draft.name,
+                    &draft.id[..8.min(draft.id.len())],
+                    verb,
+                    to,
+                    draft.name,
+                    carry
+                )
+            }
+        }
+    }
+
+    fn after_checkout(&self, store: &Store, _from: Option<&str>, to: &str) -> Result<()> {
+        // A draft still applied here was carried over
+        let mut manager = Self::manager(store)?;
+        manager.carry_active_draft(to, &store.head_commit().unwrap_or_default())?;
+        Ok(())
+    }
+}
+
+/// `BranchHook` that saves the working tree as a draft before a destructive
+/// operation, so `rune draft apply` can bring it back. Does nothing when the
+/// `safety_net` setting is off.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DraftSafetyNet;
+
+impl BranchHook for DraftSafetyNet {
+    fn before(&self, _store: &Store, _op: BranchOperation, _from: Option<&str>, _to: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn before_discard(&self, store: &Store, op: DiscardOperation) -> Result<()> {
+        DraftBranchGuard::manager(store)?.create_safety_draft(op)?;
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -473,6 +1012,9 @@ mod tests {
             default_tags: vec!["test".to_string()],
             auto_checkpoint: true,
             auto_checkpoint_interval: 10,
+            storage_dir: Some(PathBuf::from("/tmp/drafts")),
+            encrypt: true,
+            safety_net: false,
         };
 
         let serialized = serde_json::to_string(&config).unwrap();
@@ -483,6 +1025,16 @@ mod tests {
         assert_eq!(config.default_tags, deserialized.default_tags);
         assert_eq!(config.auto_checkpoint, deserialized.auto_checkpoint);
         assert_eq!(config.auto_checkpoint_interval, deserialized.auto_checkpoint_interval);
+        assert_eq!(config.storage_dir, deserialized.storage_dir);
+        assert!(deserialized.encrypt);
+        assert!(!deserialized.safety_net);
+
+        // Configs written before storage_dir/encrypt existed still load
+        let legacy = r#"{"max_drafts":5,"auto_cleanup_days":7,"default_tags":[],"auto_checkpoint":false,"auto_checkpoint_interval":15}"#;
+        let legacy: DraftConfig = serde_json::from_str(legacy).unwrap();
+        assert!(legacy.storage_dir.is_none());
+        assert!(!legacy.encrypt);
+        assert!(legacy.safety_net);
     }
 
     #[test]
@@ -495,6 +1047,7 @@ mod tests {
             is_new: true,
             is_deleted: false,
             original_hash: None,
+            encrypted: false,
         };
 
         assert_eq!(file.path, PathBuf::from("test.txt"));
@@ -517,4 +1070,434 @@ mod tests {
         let auto_name = format!("checkpoint-{}", Utc::now().format("%Y%m%d"));
         assert!(auto_name.starts_with("checkpoint-"));
     }
+
+    fn draft_with_file(path: PathBuf, content: &[u8]) -> DraftCommit {
+        let now = Utc::now();
+        let file = DraftFile {
+            path: path.clone(),
+            content: content.to_vec(),
+            mode: 0o644,
+            hash: blake3::hash(content).to_hex().to_string(),
+            is_new: true,
+            is_deleted: false,
+            original_hash: None,
+            encrypted: false,
+        };
+        DraftCommit {
+            id: Uuid::new_v4().to_string(),
+            name: "secret-work".to_string(),
+            description: None,
+            author: Author { name: "Test".into(), email: "test@example.com".into() },
+            created_at: now,
+            updated_at: now,
+            files: HashMap::from([(path, file)]),
+            base_branch: "main".to_string(),
+            base_commit: String::new(),
+            tags: vec![],
+            is_active: false,
+        }
+    }
+
+    #[test]
+    fn test_encrypted_draft_round_trip() {
+        let (store, temp) = setup_test_store();
+        let mut manager = DraftManager::new(store).unwrap();
+        manager.set_passphrase("correct horse");
+        manager.update_config(DraftConfig { encrypt: true, ..DraftConfig::default() }).unwrap();
+
+        let secret = b"API_TOKEN=hunter2-very-secret";
+        let target = temp.path().join("env.txt");
+        let draft = draft_with_file(target.clone(), secret);
+        manager.save_draft(&draft).unwrap();
+
+        let on_disk = fs::read_to_string(manager.drafts_dir().join(format!("{}.json", draft.id))).unwrap();
+        assert!(!on_disk.contains("hunter2"));
+        let plaintext_bytes = serde_json::to_string(&secret.to_vec()).unwrap();
+        assert!(!on_disk.contains(plaintext_bytes.trim_matches(|c| c == '[' || c == ']')));
+        // Metadata stays readable without the passphrase
+        assert!(on_disk.contains("secret-work"));
+
+        let loaded = manager.get_draft(&draft.id).unwrap();
+        assert_eq!(loaded.files[&target].content, secret);
+
+        manager.apply_draft(&draft.id).unwrap();
+        assert_eq!(fs::read(&target).unwrap(), secret);
+        // Re-saving on apply must not double-encrypt
+        assert_eq!(manager.get_draft(&draft.id).unwrap().files[&target].content, secret);
+    }
+
+    #[test]
+    fn test_apply_refuses_paths_outside_the_repo() {
+        let (store, temp) = setup_test_store();
+        let mut manager = DraftManager::new(store).unwrap();
+        let escape = temp.path().join("../escaped-by-draft.txt");
+        let draft = draft_with_file(escape, b"nope");
+        manager.save_draft(&draft).unwrap();
+
+        let err = manager.apply_draft(&draft.id).unwrap_err();
+        assert!(err.to_string().contains("is outside the repository"), "{}", err);
+        assert!(!temp.path().parent().unwrap().join("escaped-by-draft.txt").exists());
+        assert!(!manager.get_draft(&draft.id).unwrap().is_active);
+    }
+
+    #[test]
+    fn test_encrypted_draft_needs_passphrase() {
+        let (store, temp) = setup_test_store();
+        let mut manager = DraftManager::new(store).unwrap();
+        manager.set_passphrase("correct horse");
+        manager.update_config(DraftConfig { encrypt: true, ..DraftConfig::default() }).unwrap();
+        let draft = draft_with_file(temp.path().join("a.txt"), b"data");
+        manager.save_draft(&draft).unwrap();
+
+        manager.set_passphrase("wrong");
+        assert!(manager.get_draft(&draft.id).is_err());
+        // Listing works on metadata alone
+        assert_eq!(manager.list_drafts().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_summaries_skip_file_contents() {
+        let (store, temp) = setup_test_store();
+        let manager = DraftManager::new(store).unwrap();
+        let large = vec![7u8; 256 * 1024];
+        let mut ids = Vec::new();
+        for n in 0..3 {
+            let mut draft = draft_with_file(temp.path().join(format!("asset{}.bin", n)), &large);
+            draft.name = format!("large-{}", n);
+            draft.tags = vec!["assets".to_string()];
+            manager.save_draft(&draft).unwrap();
+            ids.push(draft.id);
+        }
+
+        let reopened = DraftManager::new(Store::open(temp.path()).unwrap()).unwrap();
+        let summaries = reopened.list_draft_summaries(Page::default()).unwrap();
+        assert_eq!(summaries.len(), 3);
+        assert!(summaries.iter().all(|s| s.file_count == 1 && s.tags == ["assets"]));
+        let summary_bytes = reopened.bytes_read.load(std::sync::atomic::Ordering::Relaxed);
+        assert!(summary_bytes < 8 * 1024, "summaries read {} bytes", summary_bytes);
+
+        reopened.list_drafts().unwrap();
+        let full_bytes = reopened.bytes_read.load(std::sync::atomic::Ordering::Relaxed) - summary_bytes;
+        assert!(full_bytes > 3 * large.len() as u64, "full listing read {} bytes", full_bytes);
+
+        // A draft saved before summaries existed is read whole once
+        fs::remove_file(reopened.drafts_dir().join(format!("{}.summary", ids[0]))).unwrap();
+        let before = reopened.bytes_read.load(std::sync::atomic::Ordering::Relaxed);
+        assert_eq!(reopened.list_draft_summaries(Page::default()).unwrap().len(), 3);
+        let backfill = reopened.bytes_read.load(std::sync::atomic::Ordering::Relaxed) - before;
+        assert!(backfill > large.len() as u64);
+        let before = reopened.bytes_read.load(std::sync::atomic::Ordering::Relaxed);
+        reopened.list_draft_summaries(Page::default()).unwrap();
+        assert!(reopened.bytes_read.load(std::sync::atomic::Ordering::Relaxed) - before < 8 * 1024);
+    }
+
+    #[test]
+    fn test_summary_pages() {
+        let (store, temp) = setup_test_store();
+        let mut manager = DraftManager::new(store).unwrap();
+        let start = Utc::now();
+        let mut ids = Vec::new();
+        for n in 0..5 {
+            let mut draft = draft_with_file(temp.path().join("a.txt"), b"data");
+            draft.created_at = start + chrono::Duration::minutes(n);
+            manager.save_draft(&draft).unwrap();
+            ids.push(draft.id);
+        }
+        ids.reverse();
+
+        let page = |manager: &DraftManager, offset, limit| -> Vec<String> {
+            manager.list_draft_summaries(Page::new(offset, limit)).unwrap().into_iter().map(|s| s.id).collect()
+        };
+        assert_eq!(page(&manager, 0, None), ids);
+        assert_eq!(page(&manager, 0, Some(2)), ids[..2]);
+        assert_eq!(page(&manager, 2, Some(2)), ids[2..4]);
+        assert_eq!(page(&manager, 4, Some(2)), ids[4..]);
+        assert!(page(&manager, 5, Some(2)).is_empty());
+        assert!(page(&manager, 0, Some(0)).is_empty());
+
+        // Edits and deletions show up in the summaries
+        manager.add_tags(&ids[1], vec!["review".to_string()]).unwrap();
+        assert_eq!(manager.list_draft_summaries(Page::new(1, Some(1))).unwrap()[0].tags, ["review"]);
+        manager.delete_draft(&ids[0]).unwrap();
+        assert_eq!(page(&manager, 0, None), ids[1..]);
+        assert!(!manager.drafts_dir().join(format!("{}.summary", ids[0])).exists());
+    }
+
+    #[test]
+    fn test_drafts_in_external_storage_dir() {
+        let (store, temp) = setup_test_store();
+        let external = TempDir::new().unwrap();
+        let mut manager = DraftManager::new(store).unwrap();
+        manager.update_config(DraftConfig {
+            storage_dir: Some(external.path().to_path_buf()),
+            ..DraftConfig::default()
+        }).unwrap();
+
+        let draft = draft_with_file(temp.path().join("a.txt"), b"data");
+        manager.save_draft(&draft).unwrap();
+        assert!(external.path().join(format!("{}.json", draft.id)).exists());
+        assert!(!temp.path().join(".rune/drafts").join(format!("{}.json", draft.id)).exists());
+
+        // The setting persists for the next manager
+        let reopened = DraftManager::new(Store::open(temp.path()).unwrap()).unwrap();
+        assert_eq!(reopened.drafts_dir(), external.path());
+        assert_eq!(reopened.list_drafts().unwrap().len(), 1);
+    }
+
+    /// Repository on `main` with a commit, plus a `feature` branch one
+    /// commit ahead; HEAD stays on `main`
+    fn two_branch_repo() -> (TempDir, Store) {
+        let temp = TempDir::new().unwrap();
+        let store = Store::open(temp.path()).unwrap();
+        store.create().unwrap();
+        let author = Author { name: "Test".into(), email: "test@example.com".into() };
+        fs::write(temp.path().join("base.txt"), "base\n").unwrap();
+        store.stage_file("base.txt").unwrap();
+        store.commit("base", author.clone()).unwrap();
+        store.create_branch("feature").unwrap();
+        store.checkout_branch("feature").unwrap();
+        fs::write(temp.path().join("feature.txt"), "feature\n").unwrap();
+        store.stage_file("feature.txt").unwrap();
+        store.commit("feature work", author).unwrap();
+        store.checkout_branch("main").unwrap();
+        (temp, store)
+    }
+
+    fn apply_new_draft(store: &Store, path: PathBuf) -> DraftCommit {
+        let mut manager = DraftManager::new(Store::open(&store.root).unwrap()).unwrap();
+        let mut draft = draft_with_file(path, b"wip");
+        draft.base_commit = store.head_commit().unwrap();
+        manager.save_draft(&draft).unwrap();
+        manager.apply_draft(&draft.id).unwrap();
+        draft
+    }
+
+    fn guarded(store: &Store, policy: DraftPolicy) -> Store {
+        let mut guarded = Store::open(&store.root).unwrap();
+        guarded.add_branch_hook(DraftBranchGuard::new(policy));
+        guarded
+    }
+
+    #[test]
+    fn test_checkout_blocked_by_active_draft() {
+        let (temp, store) = two_branch_repo();
+        apply_new_draft(&store, temp.path().join("wip.txt"));
+
+        let guarded = guarded(&store, DraftPolicy::Refuse);
+        let err = guarded.checkout_branch("feature").unwrap_err();
+        assert!(err.to_string().contains("Draft 'secret-work'"), "{}", err);
+        assert_eq!(store.current_branch().as_deref(), Some("main"));
+        assert!(guarded.merge_branch("feature", false, None).is_err());
+
+        // A new branch at the same commit just takes the draft along
+        guarded.create_branch("spike").unwrap();
+        guarded.checkout_branch("spike").unwrap();
+        let manager = DraftManager::new(Store::open(temp.path()).unwrap()).unwrap();
+        assert_eq!(manager.active_draft().unwrap().unwrap().base_branch, "spike");
+    }
+
+    #[test]
+    fn test_auto_shelve_then_offer_on_return() {
+        let (temp, store) = two_branch_repo();
+        let wip = temp.path().join("wip.txt");
+        let draft = apply_new_draft(&store, wip.clone());
+
+        let guarded = guarded(&store, DraftPolicy::AutoShelve);
+        guarded.checkout_branch("feature").unwrap();
+        assert_eq!(store.current_branch().as_deref(), Some("feature"));
+        assert!(!wip.exists());
+
+        let mut manager = DraftManager::new(Store::open(temp.path()).unwrap()).unwrap();
+        assert!(manager.active_draft().unwrap().is_none());
+        assert!(manager.auto_shelved_for("feature").unwrap().is_none());
+        assert_eq!(manager.auto_shelved_for("main").unwrap().unwrap().id, draft.id);
+
+        guarded.checkout_branch("main").unwrap();
+        manager.apply_draft(&draft.id).unwrap();
+        manager.forget_auto_shelved("main").unwrap();
+        assert_eq!(fs::read(&wip).unwrap(), b"wip");
+        assert!(manager.auto_shelved_for("main").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_carry_rebases_draft_onto_new_branch() {
+        let (temp, store) = two_branch_repo();
+        let wip = temp.path().join("wip.txt");
+        apply_new_draft(&store, wip.clone());
+        let manager = DraftManager::new(Store::open(temp.path()).unwrap()).unwrap();
+        assert_eq!(manager.active_draft_paths().unwrap(), BTreeSet::from(["wip.txt".to_string()]));
+
+        guarded(&store, DraftPolicy::Carry).checkout_branch("feature").unwrap();
+        assert!(wip.exists());
+        let manager = DraftManager::new(Store::open(temp.path()).unwrap()).unwrap();
+        let draft = manager.active_draft().unwrap().unwrap();
+        assert_eq!(draft.base_branch, "feature");
+        assert_eq!(Some(draft.base_commit), store.head_commit());
+    }
+
+    #[test]
+    fn test_checkout_check_runs_after_the_hooks() {
+        let (temp, store) = two_branch_repo();
+        let wip = temp.path().join("wip.txt");
+        apply_new_draft(&store, wip.clone());
+
+        let guarded = guarded(&store, DraftPolicy::AutoShelve);
+        let mut checks = 0;
+        guarded
+            .checkout_branch_with("feature", |_| {
+                checks += 1;
+                assert!(!wip.exists(), "the draft is shelved before the check");
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(checks, 1);
+        let manager = DraftManager::new(Store::open(temp.path()).unwrap()).unwrap();
+        assert!(manager.active_draft_paths().unwrap().is_empty());
+
+        assert!(guarded.checkout_branch_with("main", |_| anyhow::bail!("dirty")).is_err());
+        assert_eq!(store.current_branch().as_deref(), Some("feature"));
+    }
+
+    #[test]
+    fn test_shelve_restores_modified_files() {
+        let (temp, store) = two_branch_repo();
+        let base = temp.path().join("base.txt");
+        let mut draft = draft_with_file(base.clone(), b"changed\n");
+        draft.files.get_mut(&base).unwrap().is_new = false;
+        draft.base_commit = store.head_commit().unwrap();
+        let mut manager = DraftManager::new(store).unwrap();
+        manager.save_draft(&draft).unwrap();
+
+        manager.apply_draft(&draft.id).unwrap();
+        assert_eq!(fs::read_to_string(&base).unwrap(), "changed\n");
+        manager.shelve_draft(&draft.id).unwrap();
+        assert_eq!(fs::read_to_string(&base).unwrap(), "base\n");
+    }
+
+    /// Repository with `a.txt`, `b.txt` and `c.txt` committed
+    fn three_file_repo() -> (TempDir, DraftManager) {
+        let temp = TempDir::new().unwrap();
+        let store = Store::open(temp.path()).unwrap();
+        store.create().unwrap();
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            fs::write(temp.path().join(name), format!("{} base\n", name)).unwrap();
+            store.stage_file(name).unwrap();
+        }
+        store.commit("base", Author { name: "Test".into(), email: "test@example.com".into() }).unwrap();
+        (temp, DraftManager::new(store).unwrap())
+    }
+
+    #[test]
+    fn test_selective_capture_leaves_other_changes() {
+        let (temp, mut manager) = three_file_repo();
+        fs::write(temp.path().join("a.txt"), "a spike\n").unwrap();
+        fs::write(temp.path().join("b.txt"), "b in progress\n").unwrap();
+        fs::write(temp.path().join("new.txt"), "new api\n").unwrap();
+
+        let paths = [PathBuf::from("a.txt"), PathBuf::from("c.txt"), temp.path().join("sub/../new.txt")];
+        let capture = manager
+            .create_draft_from(Some(&paths), DraftSource::Worktree, "spike".into(), None)
+            .unwrap();
+        assert_eq!(capture.captured, ["a.txt", "new.txt"]);
+        assert_eq!(capture.skipped, [PathBuf::from("c.txt")]);
+
+        let draft = manager.get_draft(&capture.id).unwrap();
+        assert_eq!(draft.files[&temp.path().join("a.txt")].content, b"a spike\n");
+        assert!(draft.files[&temp.path().join("new.txt")].is_new);
+        assert_eq!(draft.files.len(), 2);
+        assert_eq!(fs::read_to_string(temp.path().join("b.txt")).unwrap(), "b in progress\n");
+
+        let only_clean = [PathBuf::from("c.txt")];
+        assert!(manager.create_draft_from(Some(&only_clean), DraftSource::Worktree, "none".into(), None).is_err());
+    }
+
+    #[test]
+    fn test_remove_reverts_only_captured_paths() {
+        let (temp, mut manager) = three_file_repo();
+        fs::write(temp.path().join("a.txt"), "a spike\n").unwrap();
+        fs::write(temp.path().join("b.txt"), "b in progress\n").unwrap();
+        fs::write(temp.path().join("new.txt"), "new api\n").unwrap();
+
+        let paths = [PathBuf::from("a.txt"), PathBuf::from("new.txt")];
+        let capture = manager
+            .create_draft_from(Some(&paths), DraftSource::Worktree, "spike".into(), None)
+            .unwrap();
+        manager.remove_captured(&capture.id).unwrap();
+        assert_eq!(fs::read_to_string(temp.path().join("a.txt")).unwrap(), "a.txt base\n");
+        assert!(!temp.path().join("new.txt").exists());
+        assert_eq!(fs::read_to_string(temp.path().join("b.txt")).unwrap(), "b in progress\n");
+
+        // Applying the draft brings the spike back
+        manager.apply_draft(&capture.id).unwrap();
+        assert_eq!(fs::read_to_string(temp.path().join("a.txt")).unwrap(), "a spike\n");
+        assert_eq!(fs::read_to_string(temp.path().join("new.txt")).unwrap(), "new api\n");
+    }
+
+    #[test]
+    fn test_staged_only_ignores_unstaged_edits() {
+        let (temp, mut manager) = three_file_repo();
+        fs::write(temp.path().join("a.txt"), "a staged\n").unwrap();
+        manager.store.stage_file("a.txt").unwrap();
+        fs::write(temp.path().join("a.txt"), "a staged\nand edited after\n").unwrap();
+        fs::write(temp.path().join("b.txt"), "b unstaged\n").unwrap();
+
+        let capture = manager
+            .create_draft_from(None, DraftSource::StagedOnly, "staged".into(), None)
+            .unwrap();
+        assert_eq!(capture.captured, ["a.txt"]);
+        let draft = manager.get_draft(&capture.id).unwrap();
+        assert_eq!(draft.files[&temp.path().join("a.txt")].content, b"a staged\n");
+
+        manager.remove_captured(&capture.id).unwrap();
+        assert_eq!(fs::read_to_string(temp.path().join("a.txt")).unwrap(), "a.txt base\n");
+        assert!(manager.store.read_index().unwrap().blobs.is_empty());
+        assert_eq!(fs::read_to_string(temp.path().join("b.txt")).unwrap(), "b unstaged\n");
+    }
+
+    fn safety_netted(store: &Store) -> Store {
+        let mut netted = Store::open(&store.root).unwrap();
+        netted.add_branch_hook(DraftSafetyNet);
+        netted
+    }
+
+    #[test]
+    fn test_reset_hard_work_recovered_from_safety_draft() {
+        let (temp, manager) = three_file_repo();
+        fs::write(temp.path().join("a.txt"), "a edited\n").unwrap();
+        manager.store.stage_file("a.txt").unwrap();
+        fs::write(temp.path().join("b.txt"), "b unstaged\n").unwrap();
+        fs::write(temp.path().join("new.txt"), "untracked\n").unwrap();
+
+        safety_netted(&manager.store).reset(&[], true).unwrap();
+        assert_eq!(fs::read_to_string(temp.path().join("a.txt")).unwrap(), "a.txt base\n");
+        assert_eq!(fs::read_to_string(temp.path().join("b.txt")).unwrap(), "b.txt base\n");
+
+        let mut manager = DraftManager::new(Store::open(temp.path()).unwrap()).unwrap();
+        let drafts = manager.list_drafts().unwrap();
+        assert_eq!(drafts.len(), 1);
+        let draft = &drafts[0];
+        assert!(draft.tags.iter().any(|t| t == SAFETY_TAG));
+        assert_eq!(draft.description.as_deref(), Some("Working tree before reset --hard"));
+        assert_eq!(draft.files.len(), 3);
+
+        fs::remove_file(temp.path().join("new.txt")).ok();
+        manager.apply_draft(&draft.id).unwrap();
+        assert_eq!(fs::read_to_string(temp.path().join("a.txt")).unwrap(), "a edited\n");
+        assert_eq!(fs::read_to_string(temp.path().join("b.txt")).unwrap(), "b unstaged\n");
+        assert_eq!(fs::read_to_string(temp.path().join("new.txt")).unwrap(), "untracked\n");
+    }
+
+    #[test]
+    fn test_safety_net_off_or_clean_tree_saves_nothing() {
+        let (temp, mut manager) = three_file_repo();
+        // Nothing to lose
+        safety_netted(&manager.store).reset(&[], true).unwrap();
+        assert!(manager.list_drafts().unwrap().is_empty());
+
+        manager.update_config(DraftConfig { safety_net: false, ..DraftConfig::default() }).unwrap();
+        fs::write(temp.path().join("a.txt"), "a edited\n").unwrap();
+        safety_netted(&manager.store).reset(&[], true).unwrap();
+        assert!(manager.list_drafts().unwrap().is_empty());
+        assert_eq!(fs::read_to_string(temp.path().join("a.txt")).unwrap(), "a.txt base\n");
+    }
 }
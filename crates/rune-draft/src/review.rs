@@ -0,0 +1,414 @@
+//! Review bundles: a draft rendered as one document a colleague can read
+//! without applying it
+//!
+//! The document has the draft's metadata, a table of the files it touches
+//! and a unified diff of each file against the draft's base commit. Binary
+//! files are summarized rather than diffed, and a diff longer than the line
+//! limit is cut off with a note saying how much was left out.
+
+use crate::{DraftCommit, DraftManager};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rune_core::eol::looks_binary;
+use rune_delta::hunks::line_hunks;
+
+/// Diff lines shown per file unless the caller asks for the full diff
+pub const REVIEW_DIFF_LINE_LIMIT: usize = 400;
+
+/// Document format for `DraftManager::render_review`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReviewFormat {
+    Markdown,
+    /// A standalone page with its styling inlined
+    Html,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Change {
+    Added,
+    Modified,
+    Deleted,
+}
+
+impl Change {
+    fn label(self) -> &'static str {
+        match self {
+            Change::Added => "Added",
+            Change::Modified => "Modified",
+            Change::Deleted => "Deleted",
+        }
+    }
+}
+
+enum FileDiff {
+    Binary,
+    /// Diff lines, headers included, and how many were cut off
+    Text { lines: Vec<String>, omitted: usize },
+}
+
+struct ReviewFile {
+    path: String,
+    change: Change,
+    base_size: Option<usize>,
+    draft_size: Option<usize>,
+    diff: FileDiff,
+}
+
+impl DraftManager {
+    /// `draft_id` as a review document, each file's diff cut off after
+    /// `REVIEW_DIFF_LINE_LIMIT` lines
+    pub fn render_review(&self, draft_id: &str, format: ReviewFormat) -> Result<String> {
+        self.render_review_limited(draft_id, format, Some(REVIEW_DIFF_LINE_LIMIT))
+    }
+
+    /// `draft_id` as a review document with each file's diff cut off after
+    /// `limit` lines; `None` shows every diff in full
+    pub fn render_review_limited(&self, draft_id: &str, format: ReviewFormat, limit: Option<usize>) -> Result<String> {
+        let draft = self.load_draft(draft_id)?;
+        let files = self.review_files(&draft, limit)?;
+        let now = Utc::now();
+        Ok(match format {
+            ReviewFormat::Markdown => markdown(&draft, &files, now),
+            ReviewFormat::Html => html(&draft, &files, now),
+        })
+    }
+
+    fn review_files(&self, draft: &DraftCommit, limit: Option<usize>) -> Result<Vec<ReviewFile>> {
+        let base = match draft.base_commit.as_str() {
+            "" => Default::default(),
+            commit => self.store.tree_snapshot(commit),
+        };
+        let mut files = Vec::new();
+        for file in draft.files.values() {
+            let path = self.repo_relative(&file.path);
+            let before = match base.get(&path) {
+                Some(blob) => Some(self.store.read_blob(blob)?),
+                None => None,
+            };
+            let after = (!file.is_deleted).then_some(file.content.as_slice());
+            let change = match (&before, after) {
+                (None, _) => Change::Added,
+                (Some(_), None) => Change::Deleted,
+                (Some(_), Some(_)) => Change::Modified,
+            };
+            let old = before.as_deref().unwrap_or_default();
+            let new = after.unwrap_or_default();
+            let diff = if looks_binary(old) || looks_binary(new) {
+                FileDiff::Binary
+            } else {
+                text_diff(&path, change, old, new, limit)
+            };
+            files.push(ReviewFile {
+                path,
+                change,
+                base_size: before.as_ref().map(Vec::len),
+                draft_size: after.map(<[u8]>::len),
+                diff,
+            });
+        }
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(files)
+    }
+}
+
+fn text_diff(path: &str, change: Change, old: &[u8], new: &[u8], limit: Option<usize>) -> FileDiff {
+    let old_name = if change == Change::Added { "/dev/null".to_string() } else { format!("a/{}", path) };
+    let new_name = if change == Change::Deleted { "/dev/null".to_string() } else { format!("b/{}", path) };
+    let mut lines = vec![format!("--- {}", old_name), format!("+++ {}", new_name)];
+    for hunk in line_hunks(&String::from_utf8_lossy(old), &String::from_utf8_lossy(new), 3) {
+        lines.extend(hunk.to_string().lines().map(str::to_string));
+    }
+    let omitted = match limit {
+        Some(limit) if lines.len() > limit => {
+            let omitted = lines.len() - limit;
+            lines.truncate(limit);
+            omitted
+        }
+        _ => 0,
+    };
+    FileDiff::Text { lines, omitted }
+}
+
+fn size(bytes: Option<usize>) -> String {
+    match bytes {
+        None => "-".to_string(),
+        Some(n) if n < 1024 => format!("{} B", n),
+        Some(n) if n < 1024 * 1024 => format!("{:.1} KB", n as f64 / 1024.0),
+        Some(n) => format!("{:.1} MB", n as f64 / (1024.0 * 1024.0)),
+    }
+}
+
+fn age(created: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let elapsed = now.signed_duration_since(created);
+    let (n, unit) = if elapsed.num_days() > 0 {
+        (elapsed.num_days(), "day")
+    } else if elapsed.num_hours() > 0 {
+        (elapsed.num_hours(), "hour")
+    } else {
+        (elapsed.num_minutes().max(0), "minute")
+    };
+    format!("{} {}{} ago", n, unit, if n == 1 { "" } else { "s" })
+}
+
+fn base(draft: &DraftCommit) -> String {
+    match draft.base_commit.get(..12).unwrap_or(&draft.base_commit) {
+        "" => format!("{} (no commits)", draft.base_branch),
+        commit => format!("{} @ {}", draft.base_branch, commit),
+    }
+}
+
+fn truncation_note(omitted: usize) -> String {
+    format!("Diff truncated: {} more lines not shown", omitted)
+}
+
+/// A code fence longer than any run of backticks in `lines`
+fn fence(lines: &[String]) -> String {
+    let longest = lines
+        .iter()
+        .flat_map(|line| line.split(|c| c != '`'))
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+/// `|` would end a markdown table cell early
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+fn markdown(draft: &DraftCommit, files: &[ReviewFile], now: DateTime<Utc>) -> String {
+    let mut out = format!("# Draft review: {}\n\n", draft.name);
+    if let Some(description) = &draft.description {
+        out.push_str(&format!("{}\n\n", description));
+    }
+    out.push_str("| | |\n|---|---|\n");
+    out.push_str(&format!("| Draft | `{}` |\n", draft.id));
+    out.push_str(&format!("| Author | {} <{}> |\n", cell(&draft.author.name), cell(&draft.author.email)));
+    out.push_str(&format!("| Base | {} |\n", cell(&base(draft))));
+    if !draft.tags.is_empty() {
+        out.push_str(&format!("| Tags | {} |\n", cell(&draft.tags.join(", "))));
+    }
+    out.push_str(&format!(
+        "| Created | {} ({}) |\n\n",
+        draft.created_at.format("%Y-%m-%d %H:%M UTC"),
+        age(draft.created_at, now)
+    ));
+
+    out.push_str("## Files\n\n| Change | File | Base | Draft |\n|---|---|---:|---:|\n");
+    for file in files {
+        out.push_str(&format!(
+            "| {} | `{}` | {} | {} |\n",
+            file.change.label(),
+            cell(&file.path),
+            size(file.base_size),
+            size(file.draft_size)
+        ));
+    }
+
+    out.push_str("\n## Changes\n");
+    for file in files {
+        out.push_str(&format!("\n### {} ({})\n\n", file.path, file.change.label().to_lowercase()));
+        match &file.diff {
+            FileDiff::Binary => out.push_str(&format!(
+                "Binary file, {} -> {}\n",
+                size(file.base_size),
+                size(file.draft_size)
+            )),
+            FileDiff::Text { lines, omitted } => {
+                let fence = fence(lines);
+                out.push_str(&format!("{}diff\n", fence));
+                for line in lines {
+                    out.push_str(line);
+                    out.push('\n');
+                }
+                out.push_str(&format!("{}\n", fence));
+                if *omitted > 0 {
+                    out.push_str(&format!("\n_{}_\n", truncation_note(*omitted)));
+                }
+            }
+        }
+    }
+    out
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+const STYLE: &str = "body{font-family:sans-serif;max-width:60em;margin:2em auto;padding:0 1em;color:#222}\
+table{border-collapse:collapse;margin:1em 0}td,th{border:1px solid #ccc;padding:.3em .6em;text-align:left}\
+td.size{text-align:right}pre{background:#f6f8fa;padding:.6em;overflow-x:auto;line-height:1.3}\
+.add{background:#e6ffed;display:block}.del{background:#ffeef0;display:block}\
+.hunk{color:#6f42c1;display:block}.note{color:#666;font-style:italic}";
+
+fn html(draft: &DraftCommit, files: &[ReviewFile], now: DateTime<Utc>) -> String {
+    let title = format!("Draft review: {}", escape(&draft.name));
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n<style>{1}</style>\n</head>\n<body>\n<h1>{0}</h1>\n",
+        title, STYLE
+    );
+    if let Some(description) = &draft.description {
+        out.push_str(&format!("<p>{}</p>\n", escape(description)));
+    }
+    let mut meta = vec![
+        ("Draft", draft.id.clone()),
+        ("Author", format!("{} <{}>", draft.author.name, draft.author.email)),
+        ("Base", base(draft)),
+    ];
+    if !draft.tags.is_empty() {
+        meta.push(("Tags", draft.tags.join(", ")));
+    }
+    meta.push((
+        "Created",
+        format!("{} ({})", draft.created_at.format("%Y-%m-%d %H:%M UTC"), age(draft.created_at, now)),
+    ));
+    out.push_str("<table>\n");
+    for (label, value) in meta {
+        out.push_str(&format!("<tr><th>{}</th><td>{}</td></tr>\n", label, escape(&value)));
+    }
+    out.push_str("</table>\n");
+
+    out.push_str("<h2>Files</h2>\n<table>\n<tr><th>Change</th><th>File</th><th>Base</th><th>Draft</th></tr>\n");
+    for file in files {
+        out.push_str(&format!(
+            "<tr><td>{}</td><td><code>{}</code></td><td class=\"size\">{}</td><td class=\"size\">{}</td></tr>\n",
+            file.change.label(),
+            escape(&file.path),
+            size(file.base_size),
+            size(file.draft_size)
+        ));
+    }
+    out.push_str("</table>\n<h2>Changes</h2>\n");
+
+    for file in files {
+        out.push_str(&format!("<h3>{} ({})</h3>\n", escape(&file.path), file.change.label().to_lowercase()));
+        match &file.diff {
+            FileDiff::Binary => out.push_str(&format!(
+                "<p class=\"note\">Binary file, {} -&gt; {}</p>\n",
+                size(file.base_size),
+                size(file.draft_size)
+            )),
+            FileDiff::Text { lines, omitted } => {
+                out.push_str("<pre>");
+                for (i, line) in lines.iter().enumerate() {
+                    let class = match line.as_bytes().first() {
+                        _ if i < 2 => None,
+                        Some(b'+') => Some("add"),
+                        Some(b'-') => Some("del"),
+                        Some(b'@') => Some("hunk"),
+                        _ => None,
+                    };
+                    match class {
+                        Some(class) => out.push_str(&format!("<span class=\"{}\">{}</span>", class, escape(line))),
+                        None => out.push_str(&format!("{}\n", escape(line))),
+                    }
+                }
+                out.push_str("</pre>\n");
+                if *omitted > 0 {
+                    out.push_str(&format!("<p class=\"note\">{}</p>\n", truncation_note(*omitted)));
+                }
+            }
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DraftSource;
+    use rune_core::Author;
+    use rune_store::Store;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// A draft that adds `new.txt`, modifies `a.txt` and deletes `b.txt`
+    fn review_fixture(new_content: &str) -> (TempDir, DraftManager, String) {
+        let temp = TempDir::new().unwrap();
+        let store = Store::open(temp.path()).unwrap();
+        store.create().unwrap();
+        fs::write(temp.path().join("a.txt"), "one\ntwo\nthree\n").unwrap();
+        fs::write(temp.path().join("b.txt"), "going away\n").unwrap();
+        store.stage_file("a.txt").unwrap();
+        store.stage_file("b.txt").unwrap();
+        store.commit("base", Author { name: "Test".into(), email: "test@example.com".into() }).unwrap();
+
+        fs::write(temp.path().join("a.txt"), "one\n2\nthree\n").unwrap();
+        fs::remove_file(temp.path().join("b.txt")).unwrap();
+        fs::write(temp.path().join("new.txt"), new_content).unwrap();
+        let mut manager = DraftManager::new(store).unwrap();
+        let paths = [temp.path().to_path_buf()];
+        let capture = manager
+            .create_draft_from(Some(&paths), DraftSource::Worktree, "tidy up".into(), Some("Renames two".into()))
+            .unwrap();
+        (temp, manager, capture.id)
+    }
+
+    #[test]
+    fn test_markdown_review_has_metadata_files_and_diffs() {
+        let (_temp, manager, id) = review_fixture("hello\n");
+        let review = manager.render_review(&id, ReviewFormat::Markdown).unwrap();
+
+        assert!(review.starts_with("# Draft review: tidy up\n\nRenames two\n"), "{}", review);
+        assert!(review.contains(&format!("| Draft | `{}` |", id)));
+        assert!(review.contains("| Base | main @ "));
+        assert!(review.contains("| Tags | draft |"));
+        assert!(review.contains("## Files\n"));
+        assert!(review.contains("| Modified | `a.txt` | 14 B | 12 B |"), "{}", review);
+        assert!(review.contains("| Deleted | `b.txt` | 11 B | - |"));
+        assert!(review.contains("| Added | `new.txt` | - | 6 B |"));
+
+        assert!(review.contains("### a.txt (modified)\n\n```diff\n--- a/a.txt\n+++ b/a.txt\n@@ "));
+        assert!(review.contains("-two\n+2\n"));
+        assert!(review.contains("--- a/b.txt\n+++ /dev/null\n"));
+        assert!(review.contains("-going away\n"));
+        assert!(review.contains("--- /dev/null\n+++ b/new.txt\n"));
+        assert!(review.contains("+hello\n"));
+        assert!(!review.contains("truncated"));
+    }
+
+    #[test]
+    fn test_long_diffs_are_truncated_unless_full() {
+        let long: String = (0..REVIEW_DIFF_LINE_LIMIT + 50).map(|i| format!("line {}\n", i)).collect();
+        let (_temp, manager, id) = review_fixture(&long);
+
+        let review = manager.render_review(&id, ReviewFormat::Markdown).unwrap();
+        // Two header lines, the hunk header and one line per added line
+        assert!(review.contains("_Diff truncated: 53 more lines not shown_"), "{}", review);
+        assert!(!review.contains(&format!("+line {}\n", REVIEW_DIFF_LINE_LIMIT + 49)));
+        // Short diffs in the same review are whole
+        assert!(review.contains("-two\n+2\n"));
+
+        let full = manager.render_review_limited(&id, ReviewFormat::Markdown, None).unwrap();
+        assert!(full.contains(&format!("+line {}\n", REVIEW_DIFF_LINE_LIMIT + 49)));
+        assert!(!full.contains("truncated"));
+    }
+
+    #[test]
+    fn test_html_review_escapes_content() {
+        let (_temp, manager, id) = review_fixture("<script>alert(\"x & y\")</script>\n");
+        let review = manager.render_review(&id, ReviewFormat::Html).unwrap();
+
+        assert!(review.starts_with("<!DOCTYPE html>"));
+        assert!(review.contains("<style>"));
+        assert!(!review.contains("<script>"));
+        assert!(review.contains(
+            "<span class=\"add\">+&lt;script&gt;alert(&quot;x &amp; y&quot;)&lt;/script&gt;</span>"
+        ));
+        assert!(review.contains("<span class=\"del\">-two</span><span class=\"add\">+2</span>"));
+        assert!(review.contains("<code>new.txt</code>"));
+    }
+}
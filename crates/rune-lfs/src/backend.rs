@@ -0,0 +1,407 @@
+//! Where object and chunk bytes are kept
+//!
+//! Everything that reads or writes chunk data goes through an
+//! `ObjectBackend`, addressed by `/`-separated keys in the layout of
+//! `.rune/lfs`: `objects/ab/cd/<oid>/<chunk>` for fixed chunks and
+//! `chunks/ab/cd/<hash>` for shared ones. The local store is an
+//! `FsBackend` over the LFS directory, so the on-disk layout is the one
+//! earlier versions wrote. Pointers and their locks stay local files.
+//!
+//! An object store can also be the LFS remote: `file://<dir>` for a plain
+//! directory (a share or a test fixture) and, with the `s3` feature,
+//! `s3://<bucket>[/<prefix>]` for any S3-compatible service. Uploads and
+//! downloads then go straight to it through `BackendRemote` instead of a
+//! Shrine, and fetched chunks are still cached in the local store.
+
+use crate::transfer::{ChunkTransport, TransferError};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Streamed contents of one stored object
+pub type ObjectReader = Box<dyn Read + Send>;
+
+/// A flat key-value store of byte streams
+pub trait ObjectBackend: Send + Sync {
+    /// Contents of `key`, or `None` when there is no such key
+    fn get(&self, key: &str) -> Result<Option<ObjectReader>>;
+
+    /// Store everything `data` yields under `key`, replacing what was
+    /// there. Readers see the old or the new contents, never part of one.
+    /// Returns the number of bytes stored.
+    fn put(&self, key: &str, data: &mut dyn Read) -> Result<u64>;
+
+    fn has(&self, key: &str) -> Result<bool>;
+
+    /// Remove `key`; removing a key that isn't there is not an error
+    fn delete(&self, key: &str) -> Result<()>;
+
+    /// Every key starting with `prefix`, sorted
+    fn list_prefix(&self, prefix: &str) -> Result<Vec<String>>;
+}
+
+/// Key of chunk or file `name` of object `oid`
+pub fn object_key(oid: &str, name: &str) -> String {
+    format!("objects/{}/{}/{}/{}", &oid[0..2], &oid[2..4], oid, name)
+}
+
+/// Key of shared chunk `hash`
+pub fn shared_chunk_key(hash: &str) -> String {
+    format!("chunks/{}/{}/{}", &hash[0..2], &hash[2..4], hash)
+}
+
+/// Keys must stay inside the store: relative, `/`-separated, and without
+/// empty, `.` or `..` components
+pub(crate) fn check_key(key: &str) -> Result<()> {
+    if key.is_empty() || key.contains('\\') || key.split('/').any(|part| matches!(part, "" | "." | "..")) {
+        bail!("invalid object key '{}'", key);
+    }
+    Ok(())
+}
+
+/// Objects as files below a directory, one file per key
+pub struct FsBackend {
+    root: PathBuf,
+}
+
+/// Keys below this are where `FsBackend` stages writes
+const FS_STAGING: &str = "tmp";
+
+impl FsBackend {
+    pub fn new(root: impl AsRef<Path>) -> Self {
+        Self { root: root.as_ref().to_path_buf() }
+    }
+
+    /// File holding `key`
+    pub fn path(&self, key: &str) -> Result<PathBuf> {
+        check_key(key)?;
+        Ok(self.root.join(key))
+    }
+}
+
+impl ObjectBackend for FsBackend {
+    fn get(&self, key: &str) -> Result<Option<ObjectReader>> {
+        match fs::File::open(self.path(key)?) {
+            Ok(file) => Ok(Some(Box::new(file))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn put(&self, key: &str, data: &mut dyn Read) -> Result<u64> {
+        static STAGED: AtomicU64 = AtomicU64::new(0);
+        let path = self.path(key)?;
+        let staging = self.root.join(FS_STAGING);
+        fs::create_dir_all(&staging)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp = staging.join(format!("put-{}-{}", std::process::id(), STAGED.fetch_add(1, Ordering::Relaxed)));
+        let written = fs::File::create(&tmp).and_then(|mut file| std::io::copy(data, &mut file));
+        match written.and_then(|written| fs::rename(&tmp, &path).map(|()| written)) {
+            Ok(written) => Ok(written),
+            Err(e) => {
+                let _ = fs::remove_file(&tmp);
+                Err(e.into())
+            }
+        }
+    }
+
+    fn has(&self, key: &str) -> Result<bool> {
+        Ok(self.path(key)?.is_file())
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        match fs::remove_file(self.path(key)?) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn list_prefix(&self, prefix: &str) -> Result<Vec<String>> {
+        // Only the directory the prefix's complete components name can
+        // hold matches
+        let dir = match prefix.rfind('/') {
+            Some(end) => self.root.join(&prefix[..end]),
+            None => self.root.clone(),
+        };
+        let mut keys = Vec::new();
+        for entry in walkdir::WalkDir::new(&dir).into_iter() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) if e.io_error().is_some_and(|io| io.kind() == std::io::ErrorKind::NotFound) => continue,
+                Err(e) => return Err(e.into()),
+            };
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let key = entry.path().strip_prefix(&self.root)?.to_string_lossy().replace('\\', "/");
+            if key.starts_with(prefix) && !key.starts_with(&format!("{}/", FS_STAGING)) {
+                keys.push(key);
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+/// Endpoint and credentials for `s3://` remotes. Each field can be left out
+/// of the config and given in the environment instead, which wins when
+/// both are set.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct S3Settings {
+    /// Base URL of the service, e.g. `https://minio.farm.local:9000`; AWS
+    /// for the region when unset. `RUNE_S3_ENDPOINT`
+    pub endpoint: Option<String>,
+    /// `RUNE_S3_REGION` or `AWS_REGION`; `us-east-1` when unset
+    pub region: Option<String>,
+    /// `RUNE_S3_ACCESS_KEY_ID` or `AWS_ACCESS_KEY_ID`
+    pub access_key_id: Option<String>,
+    /// `RUNE_S3_SECRET_ACCESS_KEY` or `AWS_SECRET_ACCESS_KEY`
+    pub secret_access_key: Option<String>,
+}
+
+impl S3Settings {
+    /// These settings with whatever the environment sets in their place
+    pub fn with_env(&self) -> S3Settings {
+        let env = |names: &[&str]| names.iter().find_map(|name| std::env::var(name).ok().filter(|v| !v.is_empty()));
+        S3Settings {
+            endpoint: env(&["RUNE_S3_ENDPOINT"]).or_else(|| self.endpoint.clone()),
+            region: env(&["RUNE_S3_REGION", "AWS_REGION"]).or_else(|| self.region.clone()),
+            access_key_id: env(&["RUNE_S3_ACCESS_KEY_ID", "AWS_ACCESS_KEY_ID"]).or_else(|| self.access_key_id.clone()),
+            secret_access_key: env(&["RUNE_S3_SECRET_ACCESS_KEY", "AWS_SECRET_ACCESS_KEY"])
+                .or_else(|| self.secret_access_key.clone()),
+        }
+    }
+}
+
+/// Whether remote `url` is an object store rather than a Shrine
+pub fn is_object_store_url(url: &str) -> bool {
+    url.starts_with("file://") || url.starts_with("s3://")
+}
+
+/// The object store remote `url` names, or `None` for a Shrine URL
+pub fn open_remote(url: &str, s3: &S3Settings) -> Result<Option<Arc<dyn ObjectBackend>>> {
+    if let Some(dir) = url.strip_prefix("file://") {
+        return Ok(Some(Arc::new(FsBackend::new(dir))));
+    }
+    if let Some(location) = url.strip_prefix("s3://") {
+        #[cfg(feature = "s3")]
+        {
+            return Ok(Some(Arc::new(crate::s3::S3Backend::open(location, s3)?)));
+        }
+        #[cfg(not(feature = "s3"))]
+        {
+            let _ = (location, s3);
+            bail!("{} is an S3 remote, but rune was built without the `s3` feature", url);
+        }
+    }
+    Ok(None)
+}
+
+/// An object store used as the LFS remote. Chunks are kept under the same
+/// keys as in the local store, and the pointer next to them, so another
+/// clone can find an object from its oid alone.
+pub struct BackendRemote {
+    backend: Arc<dyn ObjectBackend>,
+}
+
+impl BackendRemote {
+    pub fn new(backend: Arc<dyn ObjectBackend>) -> Self {
+        Self { backend }
+    }
+
+    /// Fixed chunks are named after their object; anything else is a
+    /// shared chunk hash
+    fn chunk_key(oid: &str, chunk: &str) -> String {
+        if chunk.starts_with(oid) {
+            object_key(oid, chunk)
+        } else {
+            shared_chunk_key(chunk)
+        }
+    }
+
+    fn put(&self, key: &str, mut data: &[u8]) -> Result<(), TransferError> {
+        self.backend.put(key, &mut data).map(drop).map_err(transfer_error)
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, TransferError> {
+        let Some(mut reader) = self.backend.get(key).map_err(transfer_error)? else {
+            return Ok(None);
+        };
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).map_err(|e| TransferError::Transient(e.to_string()))?;
+        Ok(Some(data))
+    }
+}
+
+/// Backends say which failures are worth retrying by returning a
+/// `TransferError`; anything else is permanent
+fn transfer_error(err: anyhow::Error) -> TransferError {
+    match err.downcast_ref::<TransferError>() {
+        Some(transfer) => transfer.clone(),
+        None => TransferError::Permanent(format!("{:#}", err)),
+    }
+}
+
+impl ChunkTransport for BackendRemote {
+    fn upload_chunk(&self, oid: &str, chunk: &str, data: &[u8]) -> Result<(), TransferError> {
+        let key = Self::chunk_key(oid, chunk);
+        // Shared chunks are content-addressed, so one already there is the same
+        if !chunk.starts_with(oid) && self.backend.has(&key).map_err(transfer_error)? {
+            return Ok(());
+        }
+        self.put(&key, data)
+    }
+
+    fn download_chunk(&self, oid: &str, chunk: &str) -> Result<Vec<u8>, TransferError> {
+        self.get(&Self::chunk_key(oid, chunk))?
+            .ok_or_else(|| TransferError::Permanent(format!("chunk {} of {} is not on the remote", chunk, oid)))
+    }
+
+    fn upload_pointer(&self, oid: &str, pointer: &[u8]) -> Result<(), TransferError> {
+        self.put(&object_key(oid, "pointer.json"), pointer)
+    }
+
+    fn download_pointer(&self, oid: &str) -> Result<Option<Vec<u8>>, TransferError> {
+        self.get(&object_key(oid, "pointer.json"))
+    }
+}
+
+/// Behaviour every `ObjectBackend` has to share, run against each of them
+#[cfg(test)]
+pub(crate) mod contract {
+    use super::*;
+
+    /// Reproducible bytes produced as they are read, so a large object
+    /// never has to be in memory
+    pub(crate) struct Noise {
+        state: u64,
+        left: u64,
+    }
+
+    impl Noise {
+        pub(crate) fn new(len: u64, seed: u64) -> Self {
+            Self { state: seed, left: len }
+        }
+    }
+
+    impl Read for Noise {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(self.left as usize);
+            for byte in &mut buf[..n] {
+                self.state = self.state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                *byte = (self.state >> 33) as u8;
+            }
+            self.left -= n as u64;
+            Ok(n)
+        }
+    }
+
+    fn hash(mut reader: impl Read) -> (u64, blake3::Hash) {
+        let mut hasher = blake3::Hasher::new();
+        let len = std::io::copy(&mut reader, &mut hasher).unwrap();
+        (len, hasher.finalize())
+    }
+
+    fn read(backend: &dyn ObjectBackend, key: &str) -> Option<Vec<u8>> {
+        backend.get(key).unwrap().map(|mut reader| {
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data).unwrap();
+            data
+        })
+    }
+
+    /// `large` bytes are streamed through one key; it should be more than
+    /// any buffer or part size the backend uses
+    pub(crate) fn check(backend: &dyn ObjectBackend, large: u64) {
+        let key = "objects/ab/cd/abcd1234/abcd1234.000000";
+
+        // Missing keys
+        assert!(read(backend, key).is_none());
+        assert!(!backend.has(key).unwrap());
+        backend.delete(key).unwrap();
+        assert!(backend.list_prefix("objects/").unwrap().is_empty());
+
+        // Put, get, overwrite
+        assert_eq!(backend.put(key, &mut &b"first"[..]).unwrap(), 5);
+        assert!(backend.has(key).unwrap());
+        assert_eq!(read(backend, key).unwrap(), b"first");
+        backend.put(key, &mut &b"second version"[..]).unwrap();
+        assert_eq!(read(backend, key).unwrap(), b"second version");
+        backend.put("objects/ab/cd/abcd1234/empty", &mut std::io::empty()).unwrap();
+        assert_eq!(read(backend, "objects/ab/cd/abcd1234/empty").unwrap(), b"");
+
+        // Listing is by string prefix
+        backend.put("objects/ab/cf/abcf0000/abcf0000.000000", &mut &b"x"[..]).unwrap();
+        backend.put("chunks/ab/cd/abcdef", &mut &b"y"[..]).unwrap();
+        assert_eq!(
+            backend.list_prefix("objects/ab/cd/").unwrap(),
+            ["objects/ab/cd/abcd1234/abcd1234.000000", "objects/ab/cd/abcd1234/empty"]
+        );
+        assert_eq!(backend.list_prefix("objects/ab/c").unwrap().len(), 3);
+        assert_eq!(backend.list_prefix("chunks/").unwrap(), ["chunks/ab/cd/abcdef"]);
+        assert!(backend.list_prefix("packs/").unwrap().is_empty());
+
+        // Delete
+        backend.delete(key).unwrap();
+        assert!(!backend.has(key).unwrap());
+        assert!(read(backend, key).is_none());
+        backend.delete(key).unwrap();
+
+        // Large object streamed both ways
+        let big = "objects/ef/01/ef01/ef01.000000";
+        assert_eq!(backend.put(big, &mut Noise::new(large, 7)).unwrap(), large);
+        assert_eq!(hash(backend.get(big).unwrap().unwrap()), hash(Noise::new(large, 7)));
+
+        // Keys can't leave the store
+        assert!(backend.put("../escape", &mut &b"z"[..]).is_err());
+        assert!(backend.get("objects//x").is_err());
+
+        for key in backend.list_prefix("").unwrap() {
+            backend.delete(&key).unwrap();
+        }
+        assert!(backend.list_prefix("").unwrap().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_fs_backend_contract() {
+        let temp = TempDir::new().unwrap();
+        contract::check(&FsBackend::new(temp.path()), 20 * 1024 * 1024 + 13);
+        // Nothing is left staged
+        assert_eq!(fs::read_dir(temp.path().join(FS_STAGING)).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_remote_keys_follow_the_local_layout() {
+        let temp = TempDir::new().unwrap();
+        let store: Arc<dyn ObjectBackend> = Arc::new(FsBackend::new(temp.path()));
+        let remote = BackendRemote::new(store.clone());
+        let oid = "0123456789abcdef";
+        remote.upload_chunk(oid, "0123456789abcdef.000000", b"fixed").unwrap();
+        remote.upload_chunk(oid, "fedcba9876543210", b"shared").unwrap();
+        remote.upload_pointer(oid, b"{}").unwrap();
+        assert_eq!(
+            store.list_prefix("").unwrap(),
+            [
+                "chunks/fe/dc/fedcba9876543210",
+                "objects/01/23/0123456789abcdef/0123456789abcdef.000000",
+                "objects/01/23/0123456789abcdef/pointer.json",
+            ]
+        );
+        assert_eq!(remote.download_chunk(oid, "fedcba9876543210").unwrap(), b"shared");
+        assert_eq!(remote.download_pointer("0123ffff").unwrap(), None);
+        assert!(matches!(remote.download_chunk(oid, "0123456789abcdef.000001"), Err(TransferError::Permanent(_))));
+    }
+}
@@ -0,0 +1,144 @@
+//! Content-defined chunking
+//!
+//! A FastCDC-style gear hash picks chunk boundaries from the bytes
+//! themselves, so an insertion only moves the boundaries next to it and the
+//! chunks after it hash the same as before. Cut points are searched with a
+//! stricter mask below the average size and a looser one above it, which
+//! keeps chunk sizes close to `avg`.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// Chunk size bounds for content-defined chunking, in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CdcSizes {
+    pub min: usize,
+    pub avg: usize,
+    pub max: usize,
+}
+
+impl Default for CdcSizes {
+    fn default() -> Self {
+        Self { min: 256 * 1024, avg: 1024 * 1024, max: 4 * 1024 * 1024 }
+    }
+}
+
+impl CdcSizes {
+    pub fn validate(&self) -> Result<()> {
+        if self.min == 0 || self.min > self.avg || self.avg > self.max {
+            anyhow::bail!(
+                "chunk sizes must satisfy 0 < min <= avg <= max (got {}/{}/{})",
+                self.min,
+                self.avg,
+                self.max
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Pseudo-random value per byte, fixed so boundaries are stable across runs
+/// and machines
+const GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state = 0x9e37_79b9_7f4a_7c15u64;
+    let mut i = 0;
+    while i < 256 {
+        // splitmix64
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+/// Mask over the top `bits` bits of the hash; those depend on the last 64
+/// bytes seen
+fn mask(bits: u32) -> u64 {
+    !0u64 << (64 - bits.clamp(1, 63))
+}
+
+/// Split `data` into content-defined chunks
+pub fn split<'a>(data: &'a [u8], sizes: &CdcSizes) -> Vec<&'a [u8]> {
+    let mut chunks = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let (chunk, tail) = rest.split_at(cut_point(rest, sizes));
+        chunks.push(chunk);
+        rest = tail;
+    }
+    chunks
+}
+
+fn cut_point(data: &[u8], sizes: &CdcSizes) -> usize {
+    if data.len() <= sizes.min {
+        return data.len();
+    }
+    let bits = sizes.avg.max(2).ilog2();
+    let (strict, loose) = (mask(bits + 1), mask(bits - 1));
+    let end = data.len().min(sizes.max);
+    let normal = sizes.avg.min(end);
+
+    let mut hash = 0u64;
+    for (i, byte) in data.iter().enumerate().take(end).skip(sizes.min) {
+        hash = (hash << 1).wrapping_add(GEAR[*byte as usize]);
+        let mask = if i < normal { strict } else { loose };
+        if hash & mask == 0 {
+            return i + 1;
+        }
+    }
+    end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZES: CdcSizes = CdcSizes { min: 512, avg: 2048, max: 8192 };
+
+    fn noise(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_chunks_cover_input_within_bounds() {
+        let data = noise(200_000, 7);
+        let chunks = split(&data, &SIZES);
+        assert_eq!(chunks.concat(), data);
+        assert!(chunks[..chunks.len() - 1].iter().all(|c| c.len() >= SIZES.min && c.len() <= SIZES.max));
+        let average = data.len() / chunks.len();
+        assert!(average > SIZES.min && average < SIZES.max, "average {}", average);
+    }
+
+    #[test]
+    fn test_boundaries_resync_after_insert() {
+        let data = noise(200_000, 11);
+        let mut edited = b"a few new bytes".to_vec();
+        edited.extend_from_slice(&data);
+
+        let before: std::collections::HashSet<&[u8]> = split(&data, &SIZES).into_iter().collect();
+        let after = split(&edited, &SIZES);
+        let new = after.iter().filter(|c| !before.contains(*c)).count();
+        assert!(new <= 2, "{} of {} chunks changed", new, after.len());
+    }
+
+    #[test]
+    fn test_sizes_are_validated() {
+        assert!(CdcSizes::default().validate().is_ok());
+        assert!(CdcSizes { min: 0, avg: 1, max: 2 }.validate().is_err());
+        assert!(CdcSizes { min: 10, avg: 5, max: 20 }.validate().is_err());
+    }
+}
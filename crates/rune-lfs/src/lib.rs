@@ -1,10 +1,27 @@
 use anyhow::Result;
+use rune_core::attributes::AttributeEngine;
+use rune_core::layout::{InternalPaths, RepoLayout};
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::{BTreeMap, BTreeSet},
     fs,
+    io::Read,
     path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    sync::{Arc, Mutex},
+    time::SystemTime,
 };
 
+pub use backend::{BackendRemote, FsBackend, ObjectBackend, ObjectReader, S3Settings};
+pub use cdc::CdcSizes;
+pub use matcher::LfsMatcher;
+pub use pointer::{
+    looks_like_pointer, parse_pointer, read_pointer_file, DoctorReport, PointerDiagnosis, PointerParseError,
+    PointerProblem, PointerRef, MAX_POINTER_SIZE, POINTER_VERSION,
+};
+pub use transfer::{ChunkTransport, SimulatedRemote, TransferError, TransferSettings};
+use transfer::{with_retries, Throttle};
+
 // LFS functionality
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LfsConfig {
@@ -14,14 +31,57 @@ pub struct LfsConfig {
     pub upload_enabled: bool,
     pub download_enabled: bool,
     pub migration_threshold: u64, // bytes
+    /// Fingerprints (`sha256:<hex>`) of the certificates the remote may present
+    #[serde(default)]
+    pub pinned_certs: Vec<String>,
+    #[serde(default)]
+    pub chunking: Chunking,
+    /// Chunk size bounds when `chunking` is `cdc`
+    #[serde(default)]
+    pub cdc: CdcSizes,
+    /// Rate limit and retries for uploads and downloads
+    #[serde(default)]
+    pub transfer: TransferSettings,
+    /// Endpoint and credentials when `remote` is an `s3://` URL
+    #[serde(default)]
+    pub s3: S3Settings,
+}
+
+/// How `clean_to_pointer` splits a file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Chunking {
+    /// `chunk_size` pieces stored with their object
+    #[default]
+    Fixed,
+    /// Content-defined pieces stored once in the shared chunk store
+    Cdc,
+}
+
+impl std::str::FromStr for Chunking {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "fixed" => Ok(Chunking::Fixed),
+            "cdc" => Ok(Chunking::Cdc),
+            _ => anyhow::bail!("unknown chunking '{}', expected 'fixed' or 'cdc'", s),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Pointer {
     pub oid: String,
     pub size: u64,
+    /// In order: `<oid>.<index>` names for fixed chunking, chunk hashes for
+    /// content-defined chunking
     pub chunks: Vec<String>,
     pub upload_status: UploadStatus,
+    #[serde(default)]
+    pub chunking: Chunking,
+    /// Length of each chunk; empty in pointers written before it was recorded
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chunk_sizes: Vec<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,6 +92,86 @@ pub enum UploadStatus {
     Failed(String),
 }
 
+/// Progress of `migrate_directory`, kept in `lfs/migrate-manifest.json`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MigrationManifest {
+    /// Path relative to the working tree -> oid it was migrated to
+    pub completed: BTreeMap<String, String>,
+    /// Paths among `completed` that a commit moved because they were over
+    /// `core.auto_lfs_threshold`
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub auto: BTreeSet<String>,
+}
+
+/// Outcome of one `migrate_directory` run
+#[derive(Debug, Clone, Default)]
+pub struct MigrationReport {
+    pub migrated: Vec<String>,
+    /// Migrated by an earlier run and still a pointer
+    pub skipped: Vec<String>,
+    /// Path and error of files that could not be migrated
+    pub failed: Vec<(String, String)>,
+    /// Files left for a later run by the limit
+    pub remaining: usize,
+}
+
+/// A file `migrate_directory` would act on, as `plan_migration` reports it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationPlanItem {
+    /// Path relative to the working tree
+    pub path: String,
+    pub size: u64,
+    pub reason: MigrationReason,
+    /// Chunks the object would be stored in; 0 for files that are skipped
+    pub chunks: usize,
+}
+
+/// Why a file is part of a migration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MigrationReason {
+    /// `.runeattributes` marks it `lfs` or `filter=lfs`
+    Attribute,
+    /// It matches this tracked pattern
+    Pattern(String),
+    /// It is larger than the migration threshold, in bytes
+    OverThreshold(u64),
+    /// An earlier run migrated it and it is still a pointer, so it is skipped
+    AlreadyMigrated,
+    /// A commit moved it for being over `core.auto_lfs_threshold` and it is
+    /// still a pointer; skipped like `AlreadyMigrated`
+    AutoMigrated,
+}
+
+impl MigrationReason {
+    /// Whether the file is already a pointer and is left alone
+    pub fn is_skipped(&self) -> bool {
+        matches!(self, MigrationReason::AlreadyMigrated | MigrationReason::AutoMigrated)
+    }
+}
+
+impl std::fmt::Display for MigrationReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MigrationReason::Attribute => write!(f, "lfs attribute"),
+            MigrationReason::Pattern(pattern) => write!(f, "matches {}", pattern),
+            MigrationReason::OverThreshold(threshold) => write!(f, "larger than {} bytes", threshold),
+            MigrationReason::AlreadyMigrated => write!(f, "already migrated"),
+            MigrationReason::AutoMigrated => write!(f, "auto-migrated at commit"),
+        }
+    }
+}
+
+/// Outcome of `dewatch_file`
+#[derive(Debug, Clone)]
+pub struct DewatchReport {
+    pub oid: String,
+    pub size: u64,
+    /// Other pointers to the same object; while there are any it is kept
+    pub still_used_by: Vec<String>,
+    /// Shared chunks deleted along with the object
+    pub chunks_pruned: usize,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LfsStats {
     pub total_files: usize,
@@ -44,15 +184,46 @@ pub struct LfsStats {
 pub struct Lfs {
     pub root: PathBuf,
     pub dir: PathBuf,
+    /// Compiled config patterns and the config mtime they were built from
+    matcher: Mutex<Option<(Option<SystemTime>, Arc<LfsMatcher>)>>,
+    /// Parsed `.runeattributes` and the mtime of the root one when parsed
+    attributes: Mutex<Option<(Option<SystemTime>, Arc<AttributeEngine>)>>,
+    /// Chunk data; the files under `dir` unless replaced with `with_store`
+    store: Arc<dyn ObjectBackend>,
+    /// What working-tree walks leave out
+    internal: InternalPaths,
 }
 impl Lfs {
-    pub fn open(root: impl AsRef<Path>) -> Result<Self> {
+    /// Open the LFS store of the repository with working tree `root` and
+    /// metadata directory `rune_dir`
+    pub fn open(root: impl AsRef<Path>, rune_dir: impl AsRef<Path>) -> Result<Self> {
         let root = root.as_ref().to_path_buf();
-        let d = root.join(".rune").join("lfs");
+        let d = rune_dir.as_ref().join("lfs");
         fs::create_dir_all(d.join("objects"))?;
         fs::create_dir_all(d.join("tmp"))?;
         fs::create_dir_all(d.join("logs"))?;
-        Ok(Self { root, dir: d })
+        let store = Arc::new(FsBackend::new(&d));
+        let internal = InternalPaths::new(&RepoLayout { root: root.clone(), rune_dir: rune_dir.as_ref().into() }, &[]);
+        Ok(Self { root, dir: d, matcher: Mutex::new(None), attributes: Mutex::new(None), store, internal })
+    }
+
+    /// Also leave `dirs`, relative to the root, out of walks of the working
+    /// tree, as `core.internal_dirs` does for the repository
+    pub fn with_internal_dirs(mut self, dirs: &[String]) -> Self {
+        let rune_dir = self.dir.parent().unwrap_or(&self.dir).to_path_buf();
+        self.internal = InternalPaths::new(&RepoLayout { root: self.root.clone(), rune_dir }, dirs);
+        self
+    }
+
+    /// Keep chunk data in `store` instead of under the LFS directory.
+    /// Pointers and locks stay local.
+    pub fn with_store(mut self, store: Arc<dyn ObjectBackend>) -> Self {
+        self.store = store;
+        self
+    }
+
+    pub fn store(&self) -> &dyn ObjectBackend {
+        &*self.store
     }
 
     pub fn config_path(&self) -> PathBuf {
@@ -72,26 +243,77 @@ impl Lfs {
                 upload_enabled: true,
                 download_enabled: true,
                 migration_threshold: 100 * 1024 * 1024, // 100MB default
+                pinned_certs: vec![],
+                chunking: Chunking::Fixed,
+                cdc: CdcSizes::default(),
+                transfer: TransferSettings::default(),
+                s3: S3Settings::default(),
             })
         }
     }
 
     pub fn write_config(&self, cfg: &LfsConfig) -> Result<()> {
         fs::write(self.config_path(), serde_json::to_vec_pretty(cfg)?)?;
+        // The mtime may not tick between two quick writes
+        *self.matcher.lock().unwrap() = None;
         Ok(())
     }
 
+    /// Whether `path` is stored through LFS.
+    ///
+    /// `.runeattributes` (`lfs`, `-lfs` or `filter=lfs`) takes precedence over
+    /// the pattern list in `.rune/lfs/config.json`. The JSON list is still
+    /// honoured for paths the attributes say nothing about; existing repos can
+    /// migrate by moving each pattern into `.runeattributes` as `<pattern> lfs`.
     pub fn is_tracked(&self, path: &str) -> Result<bool> {
-        let cfg = self.config()?;
-        for pat in cfg.patterns {
-            if glob::Pattern::new(&pat)
-                .map(|g| g.matches(path))
-                .unwrap_or(false)
-            {
-                return Ok(true);
+        Ok(self.is_tracked_many(&[path])?[0])
+    }
+
+    /// `is_tracked` for a batch of paths, reading attributes and the config
+    /// once for the whole batch
+    pub fn is_tracked_many<S: AsRef<str>>(&self, paths: &[S]) -> Result<Vec<bool>> {
+        let attributes = self.attributes()?;
+        let matcher = self.matcher()?;
+        Ok(paths
+            .iter()
+            .map(|path| {
+                let path = path.as_ref();
+                attributes
+                    .lfs_tracked(path)
+                    .unwrap_or_else(|| matcher.is_tracked(path))
+            })
+            .collect())
+    }
+
+    /// `.runeattributes` rules, read again only when the root file has
+    /// changed since the last call. Nested files are read with it, so one
+    /// added or edited below the root shows up in a new `Lfs`.
+    fn attributes(&self) -> Result<Arc<AttributeEngine>> {
+        let mtime = fs::metadata(self.root.join(".runeattributes")).and_then(|m| m.modified()).ok();
+        let mut cached = self.attributes.lock().unwrap();
+        if let Some((stamp, attributes)) = cached.as_ref() {
+            if *stamp == mtime {
+                return Ok(attributes.clone());
+            }
+        }
+        let attributes = Arc::new(AttributeEngine::new(&self.root)?);
+        *cached = Some((mtime, attributes.clone()));
+        Ok(attributes)
+    }
+
+    /// Matcher for the config patterns, recompiled only when `config.json`
+    /// has changed since the last call
+    pub fn matcher(&self) -> Result<Arc<LfsMatcher>> {
+        let mtime = fs::metadata(self.config_path()).and_then(|m| m.modified()).ok();
+        let mut cached = self.matcher.lock().unwrap();
+        if let Some((stamp, matcher)) = cached.as_ref() {
+            if *stamp == mtime {
+                return Ok(matcher.clone());
             }
         }
-        Ok(false)
+        let matcher = Arc::new(LfsMatcher::new(&self.config()?.patterns)?);
+        *cached = Some((mtime, matcher.clone()));
+        Ok(matcher)
     }
 
     pub fn should_migrate(&self, path: &Path) -> Result<bool> {
@@ -145,7 +367,8 @@ impl Lfs {
         Ok(stats)
     }
 
-    fn chunk_dir(&self, oid: &str) -> PathBuf {
+    /// Directory holding the pointer and chunks of object `oid`
+    pub fn chunk_dir(&self, oid: &str) -> PathBuf {
         self.dir
             .join("objects")
             .join(&oid[0..2])
@@ -153,172 +376,569 @@ impl Lfs {
             .join(oid)
     }
 
+    pub fn pointer_path(&self, oid: &str) -> PathBuf {
+        self.chunk_dir(oid).join("pointer.json")
+    }
+
+    /// Hold the per-object lock for `oid` until the returned file is dropped.
+    /// Every read-modify-write of `pointer.json` goes through this lock, in
+    /// this process and in others.
+    fn lock_pointer(&self, oid: &str) -> Result<fs::File> {
+        let dir = self.chunk_dir(oid);
+        fs::create_dir_all(&dir)?;
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(dir.join("pointer.lock"))?;
+        file.lock()?;
+        Ok(file)
+    }
+
+    /// Replace `pointer.json` in one rename, so readers see either the old
+    /// or the new pointer and never a partial write. Callers hold the lock.
+    fn write_pointer(&self, pointer: &Pointer) -> Result<()> {
+        let path = self.pointer_path(&pointer.oid);
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(pointer)?)?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    /// Read, change and rewrite the pointer of `oid` under its lock
+    pub fn update_pointer(&self, oid: &str, change: impl FnOnce(&mut Pointer)) -> Result<Pointer> {
+        let _lock = self.lock_pointer(oid)?;
+        let path = self.pointer_path(oid);
+        if !path.exists() {
+            anyhow::bail!("Pointer not found for OID: {}", oid);
+        }
+        let mut pointer: Pointer = serde_json::from_slice(&fs::read(&path)?)?;
+        change(&mut pointer);
+        self.write_pointer(&pointer)?;
+        Ok(pointer)
+    }
+
+    pub fn set_upload_status(&self, oid: &str, status: UploadStatus) -> Result<Pointer> {
+        self.update_pointer(oid, |pointer| pointer.upload_status = status)
+    }
+
+    /// Where chunk `hash` lives in the shared chunk store
+    pub fn shared_chunk_path(&self, hash: &str) -> PathBuf {
+        self.dir.join("chunks").join(&hash[0..2]).join(&hash[2..4]).join(hash)
+    }
+
+    /// Local file of chunk `idx` of `pointer`, whichever store it is in,
+    /// when chunk data is kept under the LFS directory
+    pub fn chunk_file(&self, pointer: &Pointer, idx: usize) -> PathBuf {
+        match pointer.chunking {
+            Chunking::Fixed => self.chunk_dir(&pointer.oid).join(&pointer.chunks[idx]),
+            Chunking::Cdc => self.shared_chunk_path(&pointer.chunks[idx]),
+        }
+    }
+
+    /// Store key of chunk `idx` of `pointer`
+    pub fn chunk_key(&self, pointer: &Pointer, idx: usize) -> String {
+        match pointer.chunking {
+            Chunking::Fixed => backend::object_key(&pointer.oid, &pointer.chunks[idx]),
+            Chunking::Cdc => backend::shared_chunk_key(&pointer.chunks[idx]),
+        }
+    }
+
+    /// Contents of chunk `idx` of `pointer` from the local store
+    pub fn read_chunk(&self, pointer: &Pointer, idx: usize) -> Result<Vec<u8>> {
+        let Some(mut reader) = self.store.get(&self.chunk_key(pointer, idx))? else {
+            anyhow::bail!("Missing chunk: {}", pointer.chunks[idx]);
+        };
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    /// Byte range of each chunk of `pointer` within the object
+    fn chunk_ranges(&self, pointer: &Pointer) -> Result<Vec<std::ops::Range<usize>>> {
+        let sizes: Vec<usize> = if pointer.chunk_sizes.len() == pointer.chunks.len() {
+            pointer.chunk_sizes.iter().map(|s| *s as usize).collect()
+        } else {
+            // Older pointers: every chunk but the last is `chunk_size` long
+            let chunk_size = self.config()?.chunk_size;
+            let size = pointer.size as usize;
+            (0..pointer.chunks.len())
+                .map(|i| chunk_size.min(size.saturating_sub(i * chunk_size)))
+                .collect()
+        };
+        let mut start = 0;
+        Ok(sizes
+            .into_iter()
+            .map(|len| {
+                start += len;
+                start - len..start
+            })
+            .collect())
+    }
+
+    fn refcounts_path(&self) -> PathBuf {
+        self.dir.join("chunks").join("refs.json")
+    }
+
+    /// How many stored objects list each shared chunk
+    pub fn chunk_refcounts(&self) -> Result<BTreeMap<String, u64>> {
+        match fs::read(self.refcounts_path()) {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Hold `chunks/refs.lock` until the returned file is dropped. Reading
+    /// and rewriting the reference counts happens under it, and so does
+    /// storing or deleting a shared chunk, so a prune never deletes a chunk
+    /// whose new reference is still being counted.
+    fn lock_refcounts(&self) -> Result<fs::File> {
+        let dir = self.dir.join("chunks");
+        fs::create_dir_all(&dir)?;
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(dir.join("refs.lock"))?;
+        file.lock()?;
+        Ok(file)
+    }
+
+    /// Replace `refs.json` through a temporary file of this write's own.
+    /// Callers hold the refcount lock.
+    fn write_refcounts(&self, counts: &BTreeMap<String, u64>) -> Result<()> {
+        static WRITES: AtomicUsize = AtomicUsize::new(0);
+        let path = self.refcounts_path();
+        fs::create_dir_all(path.parent().unwrap())?;
+        let tmp = self.dir.join("tmp").join(format!(
+            "refs.json.{}-{}",
+            std::process::id(),
+            WRITES.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&tmp, serde_json::to_vec_pretty(counts)?)?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+
     pub fn clean_to_pointer(&self, rel: &str) -> Result<Option<Pointer>> {
         if !self.is_tracked(rel)? {
             return Ok(None);
         }
+        self.store_object(rel).map(Some)
+    }
+
+    /// Clean every LFS-tracked path among `paths` into a pointer, as staging
+    /// does; untracked paths are left alone
+    pub fn stage_paths<S: AsRef<str>>(&self, paths: &[S]) -> Result<Vec<(String, Pointer)>> {
+        let tracked = self.is_tracked_many(paths)?;
+        let mut staged = Vec::new();
+        for (path, tracked) in paths.iter().zip(tracked) {
+            if tracked {
+                let rel = path.as_ref();
+                staged.push((rel.to_string(), self.store_object(rel)?));
+            }
+        }
+        Ok(staged)
+    }
+
+    /// Chunk `rel` into the object store and replace it with a pointer file
+    fn store_object(&self, rel: &str) -> Result<Pointer> {
         let data = fs::read(self.root.join(rel))?;
         let oid = format!("{}", blake3::hash(&data));
-        let chunk_size = self.config()?.chunk_size;
-        let dir = self.chunk_dir(&oid);
-        fs::create_dir_all(&dir)?;
+        let cfg = self.config()?;
+        let known = self.pointer_path(&oid).exists();
         let mut chunks = Vec::new();
-        for (i, part) in data.chunks(chunk_size).enumerate() {
-            let cid = format!("{}.{:06}", oid, i);
-            fs::write(dir.join(&cid), part)?;
-            chunks.push(cid);
+        let mut chunk_sizes = Vec::new();
+        match cfg.chunking {
+            Chunking::Fixed => {
+                for (i, mut part) in data.chunks(cfg.chunk_size).enumerate() {
+                    let cid = format!("{}.{:06}", oid, i);
+                    // `put` reads `part` to its end, so its length is taken first
+                    chunk_sizes.push(part.len() as u64);
+                    self.store.put(&backend::object_key(&oid, &cid), &mut part)?;
+                    chunks.push(cid);
+                }
+            }
+            Chunking::Cdc => {
+                cfg.cdc.validate()?;
+                let _lock = self.lock_refcounts()?;
+                let mut counts = self.chunk_refcounts()?;
+                for mut part in cdc::split(&data, &cfg.cdc) {
+                    let hash = format!("{}", blake3::hash(part));
+                    chunk_sizes.push(part.len() as u64);
+                    let key = backend::shared_chunk_key(&hash);
+                    if !self.store.has(&key)? {
+                        self.store.put(&key, &mut part)?;
+                    }
+                    // Cleaning the same content again adds no references
+                    if !known {
+                        *counts.entry(hash.clone()).or_default() += 1;
+                    }
+                    chunks.push(hash);
+                }
+                self.write_refcounts(&counts)?;
+            }
         }
-        let ptr = Pointer {
+        let mut ptr = Pointer {
             oid: oid.clone(),
             size: data.len() as u64,
             chunks,
             upload_status: UploadStatus::Local,
+            chunking: cfg.chunking,
+            chunk_sizes,
         };
-        fs::write(
-            self.root.join(rel),
-            format!(
-                "version https://rune-lfs/v1
-oid {}
-size {}",
-                oid,
-                data.len()
-            ),
-        )?;
-        fs::write(dir.join("pointer.json"), serde_json::to_vec_pretty(&ptr)?)?;
-        Ok(Some(ptr))
+        // Object data first: an interruption never leaves a pointer file
+        // without its object
+        {
+            let _lock = self.lock_pointer(&oid)?;
+            // Same content, so an upload already done or under way still counts
+            if let Ok(existing) = fs::read(self.pointer_path(&oid)) {
+                if let Ok(existing) = serde_json::from_slice::<Pointer>(&existing) {
+                    ptr.upload_status = existing.upload_status;
+                }
+            }
+            self.write_pointer(&ptr)?;
+        }
+        fs::write(self.root.join(rel), pointer::pointer_text(&oid, data.len() as u64))?;
+        Ok(ptr)
     }
     pub fn smudge_from_pointer(&self, rel: &str) -> Result<bool> {
-        let s = fs::read_to_string(self.root.join(rel)).unwrap_or_default();
-        if !s.starts_with("version https://rune-lfs/v1") {
+        let Some(pointer) = read_pointer_file(&self.root.join(rel))? else {
             return Ok(false);
-        }
-        let oid = s
-            .lines()
-            .find(|l| l.starts_with("oid "))
-            .unwrap()
-            .trim_start_matches("oid ")
-            .trim()
-            .to_string();
-        let dir = self.chunk_dir(&oid);
+        };
+        let dir = self.chunk_dir(&pointer.oid);
         let ppath = dir.join("pointer.json");
         if !ppath.exists() {
             anyhow::bail!("pointer data missing for {}", rel);
         }
         let ptr: Pointer = serde_json::from_slice(&fs::read(ppath)?)?;
         let mut out = Vec::with_capacity(ptr.size as usize);
-        for cid in ptr.chunks {
-            let part = fs::read(dir.join(cid))?;
-            out.extend_from_slice(&part);
+        for idx in 0..ptr.chunks.len() {
+            out.extend_from_slice(&self.read_chunk(&ptr, idx)?);
         }
         fs::write(self.root.join(rel), out)?;
         Ok(true)
     }
 
+    /// Take `rel` back out of LFS: rebuild its content from the chunks,
+    /// check it against the oid, and put it in place of the pointer. The
+    /// object is deleted unless another pointer in the working tree still
+    /// names it; shared chunks go once nothing references them.
+    pub fn dewatch_file(&self, rel: &str) -> Result<DewatchReport> {
+        let path = self.root.join(rel);
+        let Some(pointer) = read_pointer_file(&path)? else {
+            anyhow::bail!("{} is not an LFS pointer", rel);
+        };
+        let pointer_path = self.pointer_path(&pointer.oid);
+        if !pointer_path.exists() {
+            anyhow::bail!("pointer data missing for {}", rel);
+        }
+        let ptr: Pointer = serde_json::from_slice(&fs::read(pointer_path)?)?;
+        let mut data = Vec::with_capacity(ptr.size as usize);
+        for idx in 0..ptr.chunks.len() {
+            data.extend_from_slice(&self.read_chunk(&ptr, idx)?);
+        }
+        if data.len() as u64 != pointer.size || blake3::hash(&data).to_hex().as_str() != pointer.oid {
+            anyhow::bail!("object {} for {} does not match its pointer; {} left as is", pointer.oid, rel, rel);
+        }
+        let tmp = self.dir.join("tmp").join(format!("dewatch-{}", pointer.oid));
+        fs::write(&tmp, &data)?;
+        fs::rename(&tmp, &path)?;
+
+        let mut manifest = self.migration_manifest()?;
+        if manifest.completed.remove(rel).is_some() {
+            manifest.auto.remove(rel);
+            self.write_manifest(&manifest)?;
+        }
+
+        let mut still_used_by = Vec::new();
+        for (other, content) in self.pointer_files()? {
+            if parse_pointer(&content).is_ok_and(|p| p.oid == pointer.oid) {
+                still_used_by.push(other.strip_prefix(&self.root)?.to_string_lossy().replace('\\', "/"));
+            }
+        }
+        let chunks_pruned = if still_used_by.is_empty() { self.remove_object(&pointer.oid)? } else { 0 };
+        Ok(DewatchReport { oid: pointer.oid, size: pointer.size, still_used_by, chunks_pruned })
+    }
+
     // Migration tools
     pub fn migrate_file(&self, path: &Path) -> Result<bool> {
         if !path.exists() {
             return Ok(false);
         }
-
         let relative_path = path.strip_prefix(&self.root)?;
-        let path_str = relative_path.to_string_lossy();
+        let tracked = self.is_tracked(&relative_path.to_string_lossy())?;
+        self.migrate_checked(path, tracked)
+    }
+
+    /// Move `rel` into LFS whether or not it is tracked, as a commit does
+    /// for files over `core.auto_lfs_threshold`, and record it in the
+    /// manifest as auto-migrated
+    pub fn auto_migrate(&self, rel: &str) -> Result<Pointer> {
+        let pointer = self.store_object(rel)?;
+        let mut manifest = self.migration_manifest()?;
+        manifest.completed.insert(rel.to_string(), pointer.oid.clone());
+        manifest.auto.insert(rel.to_string());
+        self.write_manifest(&manifest)?;
+        Ok(pointer)
+    }
 
-        // Check if file should be migrated based on size and patterns
-        if self.should_migrate(path)? || self.is_tracked(&path_str)? {
-            if let Some(_pointer) = self.clean_to_pointer(&path_str)? {
-                println!("✓ Migrated {} to LFS", path_str);
-                return Ok(true);
+    /// Files a commit auto-migrated that are still pointers to what it stored
+    pub fn auto_migrated(&self) -> Result<Vec<String>> {
+        let manifest = self.migration_manifest()?;
+        Ok(manifest
+            .auto
+            .iter()
+            .filter(|rel| manifest.completed.get(*rel).is_some_and(|oid| is_pointer_to(&self.root.join(rel), oid)))
+            .cloned()
+            .collect())
+    }
+
+    fn manifest_path(&self) -> PathBuf {
+        self.dir.join("migrate-manifest.json")
+    }
+
+    /// Files a directory migration has already turned into pointers
+    pub fn migration_manifest(&self) -> Result<MigrationManifest> {
+        match fs::read(self.manifest_path()) {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(MigrationManifest::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn write_manifest(&self, manifest: &MigrationManifest) -> Result<()> {
+        let tmp = self.dir.join("tmp").join("migrate-manifest.json");
+        fs::write(&tmp, serde_json::to_vec_pretty(manifest)?)?;
+        fs::rename(tmp, self.manifest_path())?;
+        Ok(())
+    }
+
+    /// Migrate the tracked and oversized files under `dir`, at most `limit`
+    /// of them when given. Each migrated file is recorded in the manifest as
+    /// soon as it is done, so an interrupted or limited run picks up where
+    /// it stopped; files still holding their recorded pointer are skipped.
+    pub fn migrate_directory(&self, dir: &Path, limit: Option<usize>) -> Result<MigrationReport> {
+        let files = self.migration_files(dir)?;
+        let rels: Vec<&str> = files.iter().map(|(_, rel)| rel.as_str()).collect();
+        let tracked = self.is_tracked_many(&rels)?;
+        let mut manifest = self.migration_manifest()?;
+        let mut report = MigrationReport::default();
+        for ((path, rel), tracked) in files.iter().zip(tracked) {
+            if let Some(oid) = manifest.completed.get(rel) {
+                if is_pointer_to(path, oid) {
+                    report.skipped.push(rel.clone());
+                    continue;
+                }
+            }
+            if !tracked && !self.should_migrate(path)? {
+                continue;
+            }
+            if limit.is_some_and(|limit| report.migrated.len() >= limit) {
+                report.remaining += 1;
+                continue;
+            }
+            match self.store_object(rel) {
+                Ok(ptr) => {
+                    println!("✓ Migrated {} to LFS", rel);
+                    manifest.completed.insert(rel.clone(), ptr.oid);
+                    self.write_manifest(&manifest)?;
+                    report.migrated.push(rel.clone());
+                }
+                Err(e) => report.failed.push((rel.clone(), e.to_string())),
             }
         }
-        Ok(false)
+        Ok(report)
     }
 
-    pub fn migrate_directory(&self, dir: &Path) -> Result<Vec<String>> {
-        let mut migrated = Vec::new();
+    /// What `migrate_directory(dir, None)` would do, without changing
+    /// anything: every file it would migrate or skip as already migrated,
+    /// in path order
+    pub fn plan_migration(&self, dir: &Path) -> Result<Vec<MigrationPlanItem>> {
+        self.plan_migration_with(dir, &self.config()?)
+    }
+
+    /// `plan_migration` as it would come out with `cfg` saved in place of
+    /// the current configuration. Content-defined chunking reads each
+    /// candidate to count its chunks.
+    pub fn plan_migration_with(&self, dir: &Path, cfg: &LfsConfig) -> Result<Vec<MigrationPlanItem>> {
+        let attributes = self.attributes()?;
+        let matcher = LfsMatcher::new(&cfg.patterns)?;
+        let manifest = self.migration_manifest()?;
+        let mut plan = Vec::new();
+        for (path, rel) in self.migration_files(dir)? {
+            let size = fs::metadata(&path)?.len();
+            let reason = if manifest.completed.get(&rel).is_some_and(|oid| is_pointer_to(&path, oid)) {
+                if manifest.auto.contains(&rel) {
+                    MigrationReason::AutoMigrated
+                } else {
+                    MigrationReason::AlreadyMigrated
+                }
+            } else {
+                match attributes.lfs_tracked(&rel) {
+                    Some(true) => MigrationReason::Attribute,
+                    None if matcher.is_tracked(&rel) => MigrationReason::Pattern(matching_pattern(&cfg.patterns, &rel)),
+                    _ if size > cfg.migration_threshold => MigrationReason::OverThreshold(cfg.migration_threshold),
+                    _ => continue,
+                }
+            };
+            let chunks = match (&reason, cfg.chunking) {
+                (MigrationReason::AlreadyMigrated | MigrationReason::AutoMigrated, _) => 0,
+                (_, Chunking::Fixed) => (size as usize).div_ceil(cfg.chunk_size.max(1)),
+                (_, Chunking::Cdc) => cdc::split(&fs::read(&path)?, &cfg.cdc).len(),
+            };
+            plan.push(MigrationPlanItem { path: rel, size, reason, chunks });
+        }
+        Ok(plan)
+    }
 
-        for entry in walkdir::WalkDir::new(dir) {
+    /// Files under `dir` outside the metadata directory, with their paths
+    /// relative to the working tree, in path order
+    fn migration_files(&self, dir: &Path) -> Result<Vec<(PathBuf, String)>> {
+        let mut files = Vec::new();
+        let walk = walkdir::WalkDir::new(dir).sort_by_file_name().into_iter();
+        for entry in walk.filter_entry(|e| !self.internal.contains(e.path())) {
             let entry = entry?;
             if entry.file_type().is_file() {
-                if self.migrate_file(entry.path())? {
-                    migrated.push(
-                        entry
-                            .path()
-                            .strip_prefix(&self.root)?
-                            .to_string_lossy()
-                            .to_string(),
-                    );
-                }
+                let rel = entry.path().strip_prefix(&self.root)?.to_string_lossy().replace('\\', "/");
+                files.push((entry.into_path(), rel));
             }
         }
+        Ok(files)
+    }
 
-        Ok(migrated)
+    /// Migrate `path` when it is tracked or over the size threshold
+    fn migrate_checked(&self, path: &Path, tracked: bool) -> Result<bool> {
+        if !tracked && !self.should_migrate(path)? {
+            return Ok(false);
+        }
+        let rel = path.strip_prefix(&self.root)?.to_string_lossy().to_string();
+        self.store_object(&rel)?;
+        println!("✓ Migrated {} to LFS", rel);
+        Ok(true)
     }
 
     // Server integration
     pub fn upload_to_server(&self, oid: &str) -> Result<()> {
-        let config = self.config()?;
-        if let Some(remote_url) = &config.remote {
-            if !config.upload_enabled {
-                anyhow::bail!("Upload is disabled in configuration");
-            }
-
-            let dir = self.chunk_dir(oid);
-            let pointer_path = dir.join("pointer.json");
-
-            if !pointer_path.exists() {
-                anyhow::bail!("Pointer not found for OID: {}", oid);
-            }
-
-            let mut pointer: Pointer = serde_json::from_slice(&fs::read(&pointer_path)?)?;
+        let transport = self.remote_transport(&self.config()?)?;
+        self.upload_with(oid, &*transport)
+    }
 
-            // Mock server upload (in real implementation, this would use HTTP client)
-            println!(
-                "📤 Uploading {} chunks to {}",
-                pointer.chunks.len(),
-                remote_url
-            );
+    /// How chunks reach the configured remote: straight to the object
+    /// store for `file://` and `s3://` remotes, through a Shrine otherwise
+    fn remote_transport(&self, config: &LfsConfig) -> Result<Box<dyn ChunkTransport>> {
+        let Some(url) = &config.remote else {
+            anyhow::bail!("No remote server configured");
+        };
+        Ok(match backend::open_remote(url, &config.s3)? {
+            Some(store) => Box::new(BackendRemote::new(store)),
+            None => Box::new(SimulatedRemote),
+        })
+    }
 
-            // Simulate upload process
-            pointer.upload_status = UploadStatus::Uploading;
-            fs::write(&pointer_path, serde_json::to_vec_pretty(&pointer)?)?;
+    /// Upload every chunk of `oid` through `transport`, retrying transient
+    /// failures and keeping under the configured rate. The object is marked
+    /// `Failed` when a chunk can't be read or runs out of retries.
+    pub fn upload_with(&self, oid: &str, transport: &dyn ChunkTransport) -> Result<()> {
+        let config = self.config()?;
+        let Some(remote_url) = &config.remote else {
+            anyhow::bail!("No remote server configured");
+        };
+        if !config.upload_enabled {
+            anyhow::bail!("Upload is disabled in configuration");
+        }
 
-            // In real implementation, upload each chunk
-            for chunk in &pointer.chunks {
-                let _chunk_data = fs::read(dir.join(chunk))?;
-                // Upload chunk_data to server
-                println!("  ✓ Uploaded chunk: {}", chunk);
+        // Each status change is its own locked rewrite; the lock is not
+        // held while chunks are sent
+        let pointer = self.set_upload_status(oid, UploadStatus::Uploading)?;
+        println!("📤 Uploading {} chunks to {}", pointer.chunks.len(), remote_url);
+
+        let mut throttle = Throttle::new(config.transfer.max_bytes_per_sec);
+        for (idx, chunk) in pointer.chunks.iter().enumerate() {
+            let sent = self
+                .read_chunk(&pointer, idx)
+                .map_err(|e| e.to_string())
+                .and_then(|data| {
+                    with_retries(&config.transfer, || transport.upload_chunk(oid, chunk, &data))
+                        .map(|()| data.len() as u64)
+                        .map_err(|e| e.to_string())
+                });
+            match sent {
+                Ok(bytes) => throttle.pace(bytes),
+                Err(e) => {
+                    let e = format!("chunk {}: {}", chunk, e);
+                    self.set_upload_status(oid, UploadStatus::Failed(e.clone()))?;
+                    anyhow::bail!(e);
+                }
             }
+            println!("  ✓ Uploaded chunk: {}", chunk);
+        }
 
-            pointer.upload_status = UploadStatus::Uploaded;
-            fs::write(&pointer_path, serde_json::to_vec_pretty(&pointer)?)?;
-
-            println!("✅ Successfully uploaded {}", oid);
-        } else {
-            anyhow::bail!("No remote server configured");
+        // The remote copy of the pointer already says it is uploaded
+        let published = Pointer { upload_status: UploadStatus::Uploaded, ..pointer };
+        let published = serde_json::to_vec_pretty(&published)?;
+        if let Err(e) = with_retries(&config.transfer, || transport.upload_pointer(oid, &published)) {
+            let e = format!("pointer: {}", e);
+            self.set_upload_status(oid, UploadStatus::Failed(e.clone()))?;
+            anyhow::bail!(e);
         }
 
+        self.set_upload_status(oid, UploadStatus::Uploaded)?;
+        println!("✅ Successfully uploaded {}", oid);
         Ok(())
     }
 
     pub fn download_from_server(&self, oid: &str) -> Result<()> {
-        let config = self.config()?;
-        if let Some(remote_url) = &config.remote {
-            if !config.download_enabled {
-                anyhow::bail!("Download is disabled in configuration");
-            }
-
-            println!("📥 Downloading {} from {}", oid, remote_url);
-
-            // Mock server download (in real implementation, this would use HTTP client)
-            // For now, just mark as available locally
-            let dir = self.chunk_dir(oid);
-            fs::create_dir_all(&dir)?;
+        let transport = self.remote_transport(&self.config()?)?;
+        self.download_with(oid, &*transport)
+    }
 
-            println!("✅ Successfully downloaded {}", oid);
-        } else {
+    /// Fetch object `oid` through `transport` into the local store: its
+    /// pointer when this clone has never seen it, then each chunk that
+    /// isn't cached yet
+    pub fn download_with(&self, oid: &str, transport: &dyn ChunkTransport) -> Result<()> {
+        let config = self.config()?;
+        let Some(remote_url) = &config.remote else {
             anyhow::bail!("No remote server configured");
+        };
+        if !config.download_enabled {
+            anyhow::bail!("Download is disabled in configuration");
+        }
+        println!("📥 Downloading {} from {}", oid, remote_url);
+
+        if !self.pointer_path(oid).exists() {
+            let published = with_retries(&config.transfer, || transport.download_pointer(oid))
+                .map_err(|e| anyhow::anyhow!("pointer of {}: {}", oid, e))?;
+            let Some(published) = published else {
+                anyhow::bail!("Object not found on the remote: {}", oid);
+            };
+            let pointer: Pointer = serde_json::from_slice(&published)?;
+            if pointer.oid != oid {
+                anyhow::bail!("remote pointer for {} names object {}", oid, pointer.oid);
+            }
+            let _lock = self.lock_pointer(oid)?;
+            // Another download may have got there first
+            if !self.pointer_path(oid).exists() {
+                if pointer.chunking == Chunking::Cdc {
+                    let _refs = self.lock_refcounts()?;
+                    let mut counts = self.chunk_refcounts()?;
+                    for hash in &pointer.chunks {
+                        *counts.entry(hash.clone()).or_default() += 1;
+                    }
+                    self.write_refcounts(&counts)?;
+                }
+                self.write_pointer(&pointer)?;
+            }
         }
 
+        let pointer: Pointer = serde_json::from_slice(&fs::read(self.pointer_path(oid))?)?;
+        for idx in 0..pointer.chunks.len() {
+            if !self.store.has(&self.chunk_key(&pointer, idx))? {
+                self.download_chunk_with(oid, idx, transport)?;
+            }
+        }
+        println!("✅ Successfully downloaded {}", oid);
         Ok(())
     }
 
@@ -375,11 +995,16 @@ size {}",
 
     // Configuration management
     pub fn add_pattern(&self, pattern: &str) -> Result<()> {
+        LfsMatcher::new(&[pattern.to_string()])?;
         let mut config = self.config()?;
         if !config.patterns.contains(&pattern.to_string()) {
             config.patterns.push(pattern.to_string());
             self.write_config(&config)?;
             println!("✓ Added LFS pattern: {}", pattern);
+            println!(
+                "  note: prefer `{} lfs` in .runeattributes; attributes override this list",
+                pattern
+            );
         } else {
             println!("Pattern already exists: {}", pattern);
         }
@@ -414,6 +1039,23 @@ size {}",
         Ok(())
     }
 
+    /// Chunking for objects cleaned from now on; existing pointers keep theirs
+    pub fn set_chunking(&self, chunking: Chunking, sizes: CdcSizes) -> Result<()> {
+        sizes.validate()?;
+        let mut config = self.config()?;
+        config.chunking = chunking;
+        config.cdc = sizes;
+        self.write_config(&config)?;
+        match chunking {
+            Chunking::Fixed => println!("✓ Set LFS chunking: fixed"),
+            Chunking::Cdc => println!(
+                "✓ Set LFS chunking: cdc ({}/{}/{} bytes min/avg/max)",
+                sizes.min, sizes.avg, sizes.max
+            ),
+        }
+        Ok(())
+    }
+
     pub fn set_migration_threshold(&self, threshold: u64) -> Result<()> {
         let mut config = self.config()?;
         config.migration_threshold = threshold;
@@ -422,6 +1064,24 @@ size {}",
         Ok(())
     }
 
+    pub fn set_transfer(&self, settings: TransferSettings) -> Result<()> {
+        let mut config = self.config()?;
+        config.transfer = settings;
+        self.write_config(&config)?;
+        match settings.max_bytes_per_sec {
+            0 => println!("✓ Set LFS transfers: no rate limit, {} retries", settings.retries),
+            rate => println!("✓ Set LFS transfers: at most {} bytes/s, {} retries", rate, settings.retries),
+        }
+        Ok(())
+    }
+
+    pub fn set_pinned_certs(&self, pins: Vec<String>) -> Result<()> {
+        let mut config = self.config()?;
+        config.pinned_certs = pins;
+        self.write_config(&config)?;
+        Ok(())
+    }
+
     // Partial fetch functionality for large files
     pub fn partial_fetch(&self, oid: &str, start: usize, length: usize) -> Result<Vec<u8>> {
         let dir = self.chunk_dir(oid);
@@ -432,97 +1092,69 @@ size {}",
         }
 
         let pointer: Pointer = serde_json::from_slice(&fs::read(&pointer_path)?)?;
-        let config = self.config()?;
-        let chunk_size = config.chunk_size;
 
         if start >= pointer.size as usize {
             anyhow::bail!("Start offset {} exceeds file size {}", start, pointer.size);
         }
 
         let end = std::cmp::min(start + length, pointer.size as usize);
-        let actual_length = end - start;
-        let mut result = Vec::with_capacity(actual_length);
+        let mut result = Vec::with_capacity(end - start);
 
-        // Calculate which chunks we need
-        let start_chunk = start / chunk_size;
-        let end_chunk = (end - 1) / chunk_size;
-
-        for chunk_idx in start_chunk..=end_chunk {
-            if chunk_idx >= pointer.chunks.len() {
-                break;
+        // Only the chunks overlapping start..end are read
+        for (chunk_idx, range) in self.chunk_ranges(&pointer)?.into_iter().enumerate() {
+            if range.end <= start || range.start >= end {
+                continue;
             }
 
-            let chunk_path = dir.join(&pointer.chunks[chunk_idx]);
-            if !chunk_path.exists() {
+            if !self.store.has(&self.chunk_key(&pointer, chunk_idx))? {
                 // Try to download the chunk if it's missing
                 if let Err(e) = self.download_chunk(oid, chunk_idx) {
                     anyhow::bail!("Failed to download chunk {}: {}", chunk_idx, e);
                 }
             }
 
-            let chunk_data = fs::read(&chunk_path)?;
-
-            // Calculate the slice within this chunk
-            let chunk_start = if chunk_idx == start_chunk {
-                start % chunk_size
-            } else {
-                0
-            };
-
-            let chunk_end = if chunk_idx == end_chunk {
-                std::cmp::min(
-                    chunk_data.len(),
-                    (end % chunk_size)
-                        + (if end % chunk_size == 0 && chunk_idx == end_chunk {
-                            chunk_size
-                        } else {
-                            0
-                        }),
-                )
-            } else {
-                chunk_data.len()
-            };
-
-            if chunk_start < chunk_data.len() {
-                let slice_end = std::cmp::min(chunk_end, chunk_data.len());
-                result.extend_from_slice(&chunk_data[chunk_start..slice_end]);
+            let chunk_data = self.read_chunk(&pointer, chunk_idx)?;
+            let from = start.saturating_sub(range.start);
+            let to = (end - range.start).min(chunk_data.len());
+            if from < to {
+                result.extend_from_slice(&chunk_data[from..to]);
             }
         }
 
-        // Trim to exact requested length
-        result.truncate(actual_length);
         Ok(result)
     }
 
     // Download specific chunk
     pub fn download_chunk(&self, oid: &str, chunk_idx: usize) -> Result<()> {
-        let config = self.config()?;
-        if let Some(_remote_url) = &config.remote {
-            println!("📥 Downloading chunk {} of {}", chunk_idx, oid);
-
-            // In real implementation, this would make HTTP request
-            // For now, just simulate successful download
-            let dir = self.chunk_dir(oid);
-            let pointer_path = dir.join("pointer.json");
-
-            if let Ok(pointer_data) = fs::read_to_string(&pointer_path) {
-                if let Ok(pointer) = serde_json::from_str::<Pointer>(&pointer_data) {
-                    if chunk_idx < pointer.chunks.len() {
-                        let chunk_name = &pointer.chunks[chunk_idx];
-                        let chunk_path = dir.join(chunk_name);
-
-                        // Simulate chunk data (in real implementation, download from server)
-                        let fake_chunk_data = vec![0u8; 1024]; // Placeholder
-                        fs::write(&chunk_path, fake_chunk_data)?;
+        let transport = self.remote_transport(&self.config()?)?;
+        self.download_chunk_with(oid, chunk_idx, &*transport)
+    }
 
-                        println!("✓ Downloaded chunk {}", chunk_name);
-                    }
-                }
-            }
-        } else {
+    /// Fetch chunk `chunk_idx` of `oid` through `transport`, retrying
+    /// transient failures and keeping under the configured rate
+    pub fn download_chunk_with(&self, oid: &str, chunk_idx: usize, transport: &dyn ChunkTransport) -> Result<()> {
+        let config = self.config()?;
+        if config.remote.is_none() {
             anyhow::bail!("No remote server configured");
         }
+        println!("📥 Downloading chunk {} of {}", chunk_idx, oid);
+
+        let Ok(pointer_data) = fs::read_to_string(self.pointer_path(oid)) else {
+            return Ok(());
+        };
+        let Ok(pointer) = serde_json::from_str::<Pointer>(&pointer_data) else {
+            return Ok(());
+        };
+        let Some(chunk_name) = pointer.chunks.get(chunk_idx) else {
+            return Ok(());
+        };
+        let mut throttle = Throttle::new(config.transfer.max_bytes_per_sec);
+        let data = with_retries(&config.transfer, || transport.download_chunk(oid, chunk_name))
+            .map_err(|e| anyhow::anyhow!("chunk {}: {}", chunk_name, e))?;
+        throttle.pace(data.len() as u64);
 
+        self.store.put(&self.chunk_key(&pointer, chunk_idx), &mut data.as_slice())?;
+        println!("✓ Downloaded chunk {}", chunk_name);
         Ok(())
     }
 
@@ -540,20 +1172,13 @@ size {}",
 
         let pointer: Pointer = serde_json::from_slice(&fs::read(&pointer_path)?)?;
 
-        for chunk_name in &pointer.chunks {
-            let chunk_path = dir.join(chunk_name);
-
-            if !chunk_path.exists() {
+        for chunk_idx in 0..pointer.chunks.len() {
+            if !self.store.has(&self.chunk_key(&pointer, chunk_idx))? {
                 // Try to download the chunk
-                let chunk_idx = chunk_name
-                    .split('.')
-                    .last()
-                    .and_then(|s| s.parse::<usize>().ok())
-                    .unwrap_or(0);
                 self.download_chunk(oid, chunk_idx)?;
             }
 
-            let chunk_data = fs::read(&chunk_path)?;
+            let chunk_data = self.read_chunk(&pointer, chunk_idx)?;
             processor(&chunk_data)?;
         }
 
@@ -561,6 +1186,8 @@ size {}",
     }
 
     // Cleanup and maintenance
+    /// Delete object directories that lost their `pointer.json`. Returns how
+    /// many were removed.
     pub fn cleanup_orphaned_chunks(&self) -> Result<usize> {
         let mut cleaned = 0;
 
@@ -591,43 +1218,71 @@ size {}",
             }
         }
 
-        println!("🧹 Cleaned {} orphaned chunk directories", cleaned);
         Ok(cleaned)
     }
 
-    // Verify integrity of LFS objects
-    pub fn verify_integrity(&self) -> Result<Vec<String>> {
-        let mut corrupted = Vec::new();
+    /// Delete object `oid` and drop its references to shared chunks; chunks
+    /// no other object lists are deleted too. Returns how many were.
+    pub fn remove_object(&self, oid: &str) -> Result<usize> {
+        let dir = self.chunk_dir(oid);
+        let pointer_path = dir.join("pointer.json");
+        if !pointer_path.exists() {
+            anyhow::bail!("Object not found: {}", oid);
+        }
+        let pointer: Pointer = serde_json::from_slice(&fs::read(&pointer_path)?)?;
+        if pointer.chunking != Chunking::Cdc {
+            for idx in 0..pointer.chunks.len() {
+                self.store.delete(&self.chunk_key(&pointer, idx))?;
+            }
+        }
+        fs::remove_dir_all(&dir)?;
+        if pointer.chunking != Chunking::Cdc {
+            return Ok(0);
+        }
+        let _lock = self.lock_refcounts()?;
+        let mut counts = self.chunk_refcounts()?;
+        for hash in &pointer.chunks {
+            if let Some(count) = counts.get_mut(hash) {
+                *count = count.saturating_sub(1);
+            }
+        }
+        self.write_refcounts(&counts)?;
+        self.prune_unreferenced_chunks()
+    }
 
-        if let Ok(entries) = fs::read_dir(self.dir.join("objects")) {
-            for entry in entries.flatten() {
-                if let Ok(sub_entries) = fs::read_dir(entry.path()) {
-                    for sub_entry in sub_entries.flatten() {
-                        if let Ok(oid_entries) = fs::read_dir(sub_entry.path()) {
-                            for oid_entry in oid_entries.flatten() {
-                                let oid = oid_entry.file_name().to_string_lossy().to_string();
-                                let pointer_path = oid_entry.path().join("pointer.json");
+    /// Delete shared chunks that no stored object references. A chunk with a
+    /// nonzero reference count is never deleted.
+    pub fn prune_chunks(&self) -> Result<usize> {
+        let _lock = self.lock_refcounts()?;
+        self.prune_unreferenced_chunks()
+    }
 
-                                if pointer_path.exists() {
-                                    if let Ok(ptr_data) = fs::read_to_string(&pointer_path) {
-                                        if let Ok(pointer) =
-                                            serde_json::from_str::<Pointer>(&ptr_data)
-                                        {
-                                            // Verify all chunks exist and reconstruct to check hash
-                                            if let Err(_) =
-                                                self.verify_object_integrity(&oid, &pointer)
-                                            {
-                                                corrupted.push(oid);
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
+    /// `prune_chunks` for callers already holding the refcount lock
+    fn prune_unreferenced_chunks(&self) -> Result<usize> {
+        let keys = self.store.list_prefix("chunks/")?;
+        if keys.is_empty() {
+            return Ok(0);
+        }
+        let mut counts = self.chunk_refcounts()?;
+        let mut pruned = 0;
+        for key in keys {
+            // `chunks/ab/cd/<hash>`; the reference counts sit beside them
+            let Some(hash) = key.split('/').nth(3) else {
+                continue;
+            };
+            if counts.get(hash).copied().unwrap_or(0) == 0 {
+                self.store.delete(&key)?;
+                counts.remove(hash);
+                pruned += 1;
             }
         }
+        self.write_refcounts(&counts)?;
+        Ok(pruned)
+    }
+
+    // Verify integrity of LFS objects
+    pub fn verify_integrity(&self) -> Result<Vec<String>> {
+        let corrupted = self.corrupted_objects(true);
 
         if corrupted.is_empty() {
             println!("✅ All LFS objects verified successfully");
@@ -638,30 +1293,62 @@ size {}",
         Ok(corrupted)
     }
 
-    fn verify_object_integrity(&self, oid: &str, pointer: &Pointer) -> Result<()> {
-        let dir = self.chunk_dir(oid);
-        let mut reconstructed = Vec::new();
+    /// Oids of the stored objects whose chunks are missing or don't rebuild
+    /// the object, in oid order. With `parallel` the objects are checked
+    /// across the rayon thread pool; the result is the same either way.
+    fn corrupted_objects(&self, parallel: bool) -> Vec<String> {
+        use rayon::prelude::*;
+        let objects = self.stored_pointers();
+        let check = |(oid, pointer): &(String, Pointer)| {
+            self.verify_object_integrity(oid, pointer).is_err().then(|| oid.clone())
+        };
+        if parallel {
+            objects.par_iter().filter_map(check).collect()
+        } else {
+            objects.iter().filter_map(check).collect()
+        }
+    }
 
-        for chunk_name in &pointer.chunks {
-            let chunk_path = dir.join(chunk_name);
-            if !chunk_path.exists() {
-                anyhow::bail!("Missing chunk: {}", chunk_name);
+    /// Every object in the store with a readable pointer, sorted by oid
+    fn stored_pointers(&self) -> Vec<(String, Pointer)> {
+        let mut objects = Vec::new();
+        for entry in walkdir::WalkDir::new(self.dir.join("objects"))
+            .min_depth(3)
+            .max_depth(3)
+            .into_iter()
+            .flatten()
+        {
+            let oid = entry.file_name().to_string_lossy().to_string();
+            let Ok(data) = fs::read_to_string(entry.path().join("pointer.json")) else {
+                continue;
+            };
+            if let Ok(pointer) = serde_json::from_str::<Pointer>(&data) {
+                objects.push((oid, pointer));
             }
+        }
+        objects.sort_by(|a, b| a.0.cmp(&b.0));
+        objects
+    }
+
+    /// Hash the object's chunks as they are read, so only one buffer's worth
+    /// of it is in memory at a time
+    fn verify_object_integrity(&self, oid: &str, pointer: &Pointer) -> Result<()> {
+        let mut hasher = blake3::Hasher::new();
+        let mut size = 0u64;
 
-            let chunk_data = fs::read(&chunk_path)?;
-            reconstructed.extend_from_slice(&chunk_data);
+        for (chunk_idx, chunk_name) in pointer.chunks.iter().enumerate() {
+            let Some(mut chunk) = self.store.get(&self.chunk_key(pointer, chunk_idx))? else {
+                anyhow::bail!("Missing chunk: {}", chunk_name);
+            };
+            size += std::io::copy(&mut chunk, &mut hasher)?;
         }
 
-        if reconstructed.len() != pointer.size as usize {
-            anyhow::bail!(
-                "Size mismatch: expected {}, got {}",
-                pointer.size,
-                reconstructed.len()
-            );
+        if size != pointer.size {
+            anyhow::bail!("Size mismatch: expected {}, got {}", pointer.size, size);
         }
 
-        let calculated_hash = format!("{}", blake3::hash(&reconstructed));
-        if calculated_hash != *oid {
+        let calculated_hash = hasher.finalize().to_hex();
+        if calculated_hash.as_str() != oid {
             anyhow::bail!("Hash mismatch: expected {}, got {}", oid, calculated_hash);
         }
 
@@ -693,13 +1380,10 @@ size {}",
         let mut local_chunks = 0;
         let mut total_local_size = 0;
 
-        for chunk_name in &pointer.chunks {
-            let chunk_path = dir.join(chunk_name);
-            if chunk_path.exists() {
+        for (chunk_idx, range) in self.chunk_ranges(&pointer)?.into_iter().enumerate() {
+            if self.store.has(&self.chunk_key(&pointer, chunk_idx))? {
                 local_chunks += 1;
-                if let Ok(metadata) = fs::metadata(&chunk_path) {
-                    total_local_size += metadata.len();
-                }
+                total_local_size += range.len() as u64;
             }
         }
 
@@ -734,5 +1418,649 @@ pub struct ObjectInfo {
     pub is_complete: bool,
 }
 
+pub mod backend;
+pub mod cdc;
 // Locking functionality moved from rune-cli
 pub mod locking;
+pub mod matcher;
+mod pointer;
+#[cfg(feature = "s3")]
+pub mod s3;
+pub mod transfer;
+
+/// Whether the file at `path` is a pointer to object `oid`
+/// First of `patterns` that `rel` matches
+fn matching_pattern(patterns: &[String], rel: &str) -> String {
+    patterns
+        .iter()
+        .find(|p| glob::Pattern::new(p).is_ok_and(|g| g.matches(rel)))
+        .cloned()
+        .unwrap_or_default()
+}
+
+fn is_pointer_to(path: &Path, oid: &str) -> bool {
+    fs::read(path).is_ok_and(|content| parse_pointer(&content).is_ok_and(|pointer| pointer.oid == oid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_is_tracked_via_attributes_only() {
+        let temp = TempDir::new().unwrap();
+        let lfs = Lfs::open(temp.path(), temp.path().join(".rune")).unwrap();
+        assert!(lfs.config().unwrap().patterns.is_empty());
+        assert!(!lfs.is_tracked("assets/hero.psd").unwrap());
+
+        fs::write(temp.path().join(".runeattributes"), "*.psd lfs\n").unwrap();
+        assert!(lfs.is_tracked("assets/hero.psd").unwrap());
+        assert!(!lfs.is_tracked("assets/hero.txt").unwrap());
+        // Parsed once, not per lookup
+        assert!(Arc::ptr_eq(&lfs.attributes().unwrap(), &lfs.attributes().unwrap()));
+    }
+
+    #[test]
+    fn test_attributes_override_config_patterns() {
+        let temp = TempDir::new().unwrap();
+        let lfs = Lfs::open(temp.path(), temp.path().join(".rune")).unwrap();
+        let mut cfg = lfs.config().unwrap();
+        cfg.patterns = vec!["*.zip".to_string()];
+        lfs.write_config(&cfg).unwrap();
+
+        fs::write(temp.path().join(".runeattributes"), "small.zip -lfs\n").unwrap();
+        assert!(lfs.is_tracked("big.zip").unwrap());
+        assert!(!lfs.is_tracked("small.zip").unwrap());
+    }
+
+    #[test]
+    fn test_objects_land_in_detached_rune_dir() {
+        let temp = TempDir::new().unwrap();
+        let tree = temp.path().join("tree");
+        let meta = temp.path().join("meta");
+        fs::create_dir_all(&tree).unwrap();
+        fs::write(tree.join(".runeattributes"), "*.bin lfs\n").unwrap();
+        fs::write(tree.join("data.bin"), vec![7u8; 64]).unwrap();
+
+        let lfs = Lfs::open(&tree, &meta).unwrap();
+        let ptr = lfs.clean_to_pointer("data.bin").unwrap().unwrap();
+
+        assert!(lfs.chunk_dir(&ptr.oid).starts_with(meta.join("lfs/objects")));
+        assert!(lfs.chunk_dir(&ptr.oid).join("pointer.json").exists());
+        assert!(!tree.join(".rune").exists());
+    }
+
+    /// A cleaned 64-byte object and its oid
+    fn cleaned_object(temp: &TempDir) -> (Lfs, String) {
+        fs::write(temp.path().join(".runeattributes"), "*.bin lfs\n").unwrap();
+        fs::write(temp.path().join("data.bin"), vec![7u8; 64]).unwrap();
+        let lfs = Lfs::open(temp.path(), temp.path().join(".rune")).unwrap();
+        let oid = lfs.clean_to_pointer("data.bin").unwrap().unwrap().oid;
+        (lfs, oid)
+    }
+
+    #[test]
+    fn test_concurrent_pointer_updates_are_not_lost() {
+        let temp = TempDir::new().unwrap();
+        let (lfs, oid) = cleaned_object(&temp);
+        lfs.set_upload_status(&oid, UploadStatus::Failed("0".into())).unwrap();
+
+        // Every update reads the previous one, so any interleaving without
+        // the lock drops increments
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..25 {
+                        lfs.update_pointer(&oid, |p| {
+                            if let UploadStatus::Failed(n) = &p.upload_status {
+                                p.upload_status = UploadStatus::Failed((n.parse::<u32>().unwrap() + 1).to_string());
+                            }
+                        })
+                        .unwrap();
+                    }
+                });
+            }
+        });
+        let pointer: Pointer = serde_json::from_slice(&fs::read(lfs.pointer_path(&oid)).unwrap()).unwrap();
+        assert!(matches!(pointer.upload_status, UploadStatus::Failed(ref n) if n == "200"));
+    }
+
+    #[test]
+    fn test_concurrent_uploads_end_uploaded() {
+        let temp = TempDir::new().unwrap();
+        let (lfs, oid) = cleaned_object(&temp);
+        lfs.set_remote("https://lfs.example.com").unwrap();
+
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                let (lfs, oid) = (&lfs, &oid);
+                scope.spawn(move || {
+                    for _ in 0..10 {
+                        if i % 2 == 0 {
+                            lfs.upload_to_server(oid).unwrap();
+                        } else {
+                            // Readers never see a torn file
+                            let bytes = fs::read(lfs.pointer_path(oid)).unwrap();
+                            let pointer: Pointer = serde_json::from_slice(&bytes).unwrap();
+                            assert_eq!(pointer.size, 64);
+                        }
+                    }
+                });
+            }
+        });
+        let pointer: Pointer = serde_json::from_slice(&fs::read(lfs.pointer_path(&oid)).unwrap()).unwrap();
+        assert!(matches!(pointer.upload_status, UploadStatus::Uploaded));
+        assert_eq!(pointer.chunks.len(), 1);
+
+        // Cleaning the same content again keeps the upload
+        fs::write(temp.path().join("data.bin"), vec![7u8; 64]).unwrap();
+        let again = lfs.clean_to_pointer("data.bin").unwrap().unwrap();
+        assert!(matches!(again.upload_status, UploadStatus::Uploaded));
+    }
+
+    /// Fails every chunk transiently `failures` times before accepting it
+    struct FlakyRemote {
+        failures: u32,
+        attempts: Mutex<u32>,
+    }
+
+    impl ChunkTransport for FlakyRemote {
+        fn upload_chunk(&self, _oid: &str, _chunk: &str, _data: &[u8]) -> Result<(), TransferError> {
+            let mut attempts = self.attempts.lock().unwrap();
+            *attempts += 1;
+            if *attempts <= self.failures {
+                return Err(TransferError::Transient("connection reset".into()));
+            }
+            Ok(())
+        }
+
+        fn download_chunk(&self, oid: &str, chunk: &str) -> Result<Vec<u8>, TransferError> {
+            self.upload_chunk(oid, chunk, &[]).map(|()| vec![7u8; 64])
+        }
+    }
+
+    fn flaky_setup(temp: &TempDir, retries: u32) -> (Lfs, String) {
+        let (lfs, oid) = cleaned_object(temp);
+        let mut cfg = lfs.config().unwrap();
+        cfg.remote = Some("https://lfs.example.com".into());
+        cfg.transfer = TransferSettings { retries, backoff_ms: 1, ..Default::default() };
+        lfs.write_config(&cfg).unwrap();
+        (lfs, oid)
+    }
+
+    #[test]
+    fn test_upload_retries_transient_failures() {
+        let temp = TempDir::new().unwrap();
+        let (lfs, oid) = flaky_setup(&temp, 3);
+        let remote = FlakyRemote { failures: 2, attempts: Mutex::new(0) };
+        lfs.upload_with(&oid, &remote).unwrap();
+        assert_eq!(*remote.attempts.lock().unwrap(), 3);
+        let pointer: Pointer = serde_json::from_slice(&fs::read(lfs.pointer_path(&oid)).unwrap()).unwrap();
+        assert!(matches!(pointer.upload_status, UploadStatus::Uploaded));
+
+        // Downloads retry the same way
+        let remote = FlakyRemote { failures: 1, attempts: Mutex::new(0) };
+        fs::remove_file(lfs.chunk_file(&pointer, 0)).unwrap();
+        lfs.download_chunk_with(&oid, 0, &remote).unwrap();
+        assert_eq!(fs::read(lfs.chunk_file(&pointer, 0)).unwrap(), vec![7u8; 64]);
+    }
+
+    #[test]
+    fn test_upload_fails_once_retries_run_out() {
+        let temp = TempDir::new().unwrap();
+        let (lfs, oid) = flaky_setup(&temp, 2);
+        let remote = FlakyRemote { failures: u32::MAX, attempts: Mutex::new(0) };
+        let err = lfs.upload_with(&oid, &remote).unwrap_err().to_string();
+        assert!(err.contains("connection reset (gave up after 3 attempts)"), "{}", err);
+        assert_eq!(*remote.attempts.lock().unwrap(), 3);
+        let pointer: Pointer = serde_json::from_slice(&fs::read(lfs.pointer_path(&oid)).unwrap()).unwrap();
+        assert!(matches!(pointer.upload_status, UploadStatus::Failed(ref e) if e == &err));
+    }
+
+    /// Directory remote that counts what is fetched from it
+    struct CountingRemote {
+        store: FsBackend,
+        gets: Mutex<u32>,
+    }
+
+    impl ObjectBackend for CountingRemote {
+        fn get(&self, key: &str) -> Result<Option<ObjectReader>> {
+            *self.gets.lock().unwrap() += 1;
+            self.store.get(key)
+        }
+
+        fn put(&self, key: &str, data: &mut dyn Read) -> Result<u64> {
+            self.store.put(key, data)
+        }
+
+        fn has(&self, key: &str) -> Result<bool> {
+            self.store.has(key)
+        }
+
+        fn delete(&self, key: &str) -> Result<()> {
+            self.store.delete(key)
+        }
+
+        fn list_prefix(&self, prefix: &str) -> Result<Vec<String>> {
+            self.store.list_prefix(prefix)
+        }
+    }
+
+    #[test]
+    fn test_object_store_remote_round_trip() {
+        let temp = TempDir::new().unwrap();
+        let (lfs, oid) = cleaned_object(&temp);
+        let bucket = temp.path().join("bucket");
+        let url = format!("file://{}", bucket.display());
+        lfs.set_remote(&url).unwrap();
+        lfs.sync_with_server().unwrap();
+        let pointer: Pointer = serde_json::from_slice(&fs::read(lfs.pointer_path(&oid)).unwrap()).unwrap();
+        assert!(matches!(pointer.upload_status, UploadStatus::Uploaded));
+        assert!(FsBackend::new(&bucket).has(&backend::object_key(&oid, "pointer.json")).unwrap());
+
+        // Another clone with only the pointer file
+        let clone = temp.path().join("clone");
+        fs::create_dir_all(&clone).unwrap();
+        fs::copy(temp.path().join("data.bin"), clone.join("data.bin")).unwrap();
+        let other = Lfs::open(&clone, clone.join(".rune")).unwrap();
+        other.set_remote(&url).unwrap();
+        let remote = Arc::new(CountingRemote { store: FsBackend::new(&bucket), gets: Mutex::new(0) });
+        let transport = BackendRemote::new(remote.clone());
+        other.download_with(&oid, &transport).unwrap();
+        assert!(other.smudge_from_pointer("data.bin").unwrap());
+        assert_eq!(fs::read(clone.join("data.bin")).unwrap(), vec![7u8; 64]);
+        assert_eq!(*remote.gets.lock().unwrap(), 2);
+
+        // Fetched chunks are cached locally
+        other.download_with(&oid, &transport).unwrap();
+        assert_eq!(other.partial_fetch(&oid, 10, 4).unwrap(), vec![7u8; 4]);
+        assert_eq!(*remote.gets.lock().unwrap(), 2);
+
+        let err = other.download_from_server(&"f".repeat(64)).unwrap_err();
+        assert!(err.to_string().contains("not found on the remote"), "{}", err);
+    }
+
+    #[test]
+    fn test_chunks_go_through_the_configured_store() {
+        let temp = TempDir::new().unwrap();
+        fs::write(temp.path().join(".runeattributes"), "*.bin lfs\n").unwrap();
+        fs::write(temp.path().join("data.bin"), noise(10_000, 3)).unwrap();
+        let elsewhere = temp.path().join("elsewhere");
+        let lfs = Lfs::open(temp.path(), temp.path().join(".rune"))
+            .unwrap()
+            .with_store(Arc::new(FsBackend::new(&elsewhere)));
+        let pointer = lfs.clean_to_pointer("data.bin").unwrap().unwrap();
+        assert!(!lfs.chunk_file(&pointer, 0).exists());
+        assert_eq!(lfs.store().list_prefix("objects/").unwrap(), [lfs.chunk_key(&pointer, 0)]);
+
+        assert!(lfs.verify_integrity().unwrap().is_empty());
+        assert!(lfs.smudge_from_pointer("data.bin").unwrap());
+        assert_eq!(fs::read(temp.path().join("data.bin")).unwrap(), noise(10_000, 3));
+        lfs.remove_object(&pointer.oid).unwrap();
+        assert!(lfs.store().list_prefix("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_matcher_cached_until_config_changes() {
+        let temp = TempDir::new().unwrap();
+        let lfs = Lfs::open(temp.path(), temp.path().join(".rune")).unwrap();
+        let mut cfg = lfs.config().unwrap();
+        cfg.patterns = vec!["*.psd".to_string(), "raw/**".to_string()];
+        lfs.write_config(&cfg).unwrap();
+
+        let paths: Vec<String> = (0..1000).map(|i| format!("raw/{}.dat", i)).collect();
+        let before = matcher::COMPILED.with(|count| count.get());
+        assert!(lfs.is_tracked_many(&paths).unwrap().iter().all(|t| *t));
+        assert!(lfs.is_tracked("art/hero.psd").unwrap());
+        assert!(!lfs.is_tracked("src/main.rs").unwrap());
+        assert_eq!(matcher::COMPILED.with(|count| count.get()) - before, 1);
+
+        cfg.patterns = vec!["*.psd".to_string()];
+        lfs.write_config(&cfg).unwrap();
+        assert!(!lfs.is_tracked("raw/0.dat").unwrap());
+    }
+
+    #[test]
+    fn test_invalid_pattern_reported() {
+        let temp = TempDir::new().unwrap();
+        let lfs = Lfs::open(temp.path(), temp.path().join(".rune")).unwrap();
+        assert!(lfs.add_pattern("assets/[").is_err());
+
+        let mut cfg = lfs.config().unwrap();
+        cfg.patterns = vec!["assets/[".to_string()];
+        lfs.write_config(&cfg).unwrap();
+        let err = lfs.is_tracked_many(&["a.txt", "b.txt"]).unwrap_err();
+        assert!(err.to_string().contains("'assets/['"), "{}", err);
+    }
+
+    #[test]
+    fn test_stage_paths_cleans_tracked_only() {
+        let temp = TempDir::new().unwrap();
+        let lfs = Lfs::open(temp.path(), temp.path().join(".rune")).unwrap();
+        lfs.add_pattern("*.bin").unwrap();
+        fs::write(temp.path().join("data.bin"), vec![1u8; 32]).unwrap();
+        fs::write(temp.path().join("notes.txt"), "hello").unwrap();
+
+        let staged = lfs.stage_paths(&["data.bin", "notes.txt"]).unwrap();
+        assert_eq!(staged.len(), 1);
+        assert_eq!(staged[0].0, "data.bin");
+        assert_eq!(staged[0].1.size, 32);
+        assert_eq!(fs::read_to_string(temp.path().join("notes.txt")).unwrap(), "hello");
+    }
+
+    fn noise(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    fn cdc_lfs(temp: &TempDir) -> Lfs {
+        let lfs = Lfs::open(temp.path(), temp.path().join(".rune")).unwrap();
+        lfs.add_pattern("*.bin").unwrap();
+        lfs.set_chunking(Chunking::Cdc, CdcSizes { min: 1024, avg: 4096, max: 16384 }).unwrap();
+        lfs
+    }
+
+    #[test]
+    fn test_cdc_second_version_reuses_chunks() {
+        let temp = TempDir::new().unwrap();
+        let lfs = cdc_lfs(&temp);
+        let v1 = noise(300_000, 3);
+        fs::write(temp.path().join("a.bin"), &v1).unwrap();
+        let p1 = lfs.clean_to_pointer("a.bin").unwrap().unwrap();
+
+        let mut v2 = b"header v2".to_vec();
+        v2.extend_from_slice(&v1);
+        fs::write(temp.path().join("b.bin"), &v2).unwrap();
+        let p2 = lfs.clean_to_pointer("b.bin").unwrap().unwrap();
+
+        let new = p2.chunks.iter().filter(|c| !p1.chunks.contains(c)).count();
+        assert!(p2.chunks.len() > 20);
+        assert!(new <= 2, "{} of {} chunks are new", new, p2.chunks.len());
+
+        assert!(lfs.smudge_from_pointer("b.bin").unwrap());
+        assert_eq!(fs::read(temp.path().join("b.bin")).unwrap(), v2);
+        assert_eq!(lfs.partial_fetch(&p2.oid, 100_000, 50).unwrap(), &v2[100_000..100_050]);
+    }
+
+    #[test]
+    fn test_removing_object_keeps_shared_chunks() {
+        let temp = TempDir::new().unwrap();
+        let lfs = cdc_lfs(&temp);
+        let shared = noise(100_000, 5);
+        let mut a = shared.clone();
+        a.extend_from_slice(&noise(50_000, 6));
+        let mut b = shared.clone();
+        b.extend_from_slice(&noise(50_000, 7));
+        fs::write(temp.path().join("a.bin"), &a).unwrap();
+        fs::write(temp.path().join("b.bin"), &b).unwrap();
+        let pa = lfs.clean_to_pointer("a.bin").unwrap().unwrap();
+        let pb = lfs.clean_to_pointer("b.bin").unwrap().unwrap();
+        let only_a: Vec<&String> = pa.chunks.iter().filter(|c| !pb.chunks.contains(c)).collect();
+        assert!(!only_a.is_empty());
+
+        let removed = lfs.remove_object(&pa.oid).unwrap();
+        assert_eq!(removed, only_a.len());
+        assert!(only_a.iter().all(|c| !lfs.shared_chunk_path(c).exists()));
+        assert_eq!(lfs.prune_chunks().unwrap(), 0);
+
+        lfs.verify_object_integrity(&pb.oid, &pb).unwrap();
+        assert!(lfs.smudge_from_pointer("b.bin").unwrap());
+        assert_eq!(fs::read(temp.path().join("b.bin")).unwrap(), b);
+    }
+
+    #[test]
+    fn test_concurrent_cleans_count_every_shared_chunk() {
+        let temp = TempDir::new().unwrap();
+        let lfs = cdc_lfs(&temp);
+        let shared = noise(60_000, 11);
+        let names: Vec<String> = (0..6).map(|i| format!("f{}.bin", i)).collect();
+        for (i, name) in names.iter().enumerate() {
+            let mut data = shared.clone();
+            data.extend_from_slice(&noise(20_000, 20 + i as u64));
+            fs::write(temp.path().join(name), data).unwrap();
+        }
+
+        // As the HTTP API does, each clean through its own handle
+        let pointers: Vec<Pointer> = std::thread::scope(|scope| {
+            let cleans: Vec<_> = names
+                .iter()
+                .map(|name| {
+                    let root = temp.path();
+                    scope.spawn(move || {
+                        let lfs = Lfs::open(root, root.join(".rune")).unwrap();
+                        lfs.clean_to_pointer(name).unwrap().unwrap()
+                    })
+                })
+                .collect();
+            cleans.into_iter().map(|clean| clean.join().unwrap()).collect()
+        });
+
+        let counts = lfs.chunk_refcounts().unwrap();
+        let common: Vec<&String> =
+            pointers[0].chunks.iter().filter(|c| pointers.iter().all(|p| p.chunks.contains(c))).collect();
+        assert!(!common.is_empty());
+        for chunk in common {
+            assert_eq!(counts[chunk], names.len() as u64, "{}", chunk);
+        }
+        assert_eq!(lfs.prune_chunks().unwrap(), 0);
+        assert!(fs::read_dir(temp.path().join(".rune/lfs/tmp")).unwrap().next().is_none());
+    }
+
+    #[test]
+    fn test_parallel_verification_matches_serial() {
+        let temp = TempDir::new().unwrap();
+        let lfs = Lfs::open(temp.path(), temp.path().join(".rune")).unwrap();
+        lfs.add_pattern("*.bin").unwrap();
+        let mut cfg = lfs.config().unwrap();
+        cfg.chunk_size = 4096;
+        lfs.write_config(&cfg).unwrap();
+        let pointers: Vec<Pointer> = (0..8)
+            .map(|i| {
+                let name = format!("f{}.bin", i);
+                fs::write(temp.path().join(&name), noise(20_000 + i * 100, i as u64 + 1)).unwrap();
+                lfs.clean_to_pointer(&name).unwrap().unwrap()
+            })
+            .collect();
+        assert!(lfs.corrupted_objects(true).is_empty());
+
+        // Flip a byte in the middle of one object and drop a chunk of another
+        let flipped = &pointers[3];
+        let chunk = lfs.chunk_file(flipped, 2);
+        let mut data = fs::read(&chunk).unwrap();
+        data[10] ^= 0xff;
+        fs::write(&chunk, data).unwrap();
+        let truncated = &pointers[6];
+        fs::remove_file(lfs.chunk_file(truncated, 1)).unwrap();
+
+        let mut expected = vec![flipped.oid.clone(), truncated.oid.clone()];
+        expected.sort();
+        let parallel = lfs.corrupted_objects(true);
+        assert_eq!(parallel, expected);
+        assert_eq!(parallel, lfs.corrupted_objects(false));
+        assert_eq!(lfs.verify_integrity().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_dewatch_restores_content_and_removes_object() {
+        let temp = TempDir::new().unwrap();
+        let original = noise(10_000, 11);
+        fs::write(temp.path().join(".runeattributes"), "*.bin lfs\n").unwrap();
+        fs::write(temp.path().join("big.bin"), &original).unwrap();
+        let lfs = Lfs::open(temp.path(), temp.path().join(".rune")).unwrap();
+        let ptr = lfs.clean_to_pointer("big.bin").unwrap().unwrap();
+        assert_ne!(fs::read(temp.path().join("big.bin")).unwrap(), original);
+
+        let report = lfs.dewatch_file("big.bin").unwrap();
+        assert_eq!(report.oid, ptr.oid);
+        assert!(report.still_used_by.is_empty());
+        assert_eq!(fs::read(temp.path().join("big.bin")).unwrap(), original);
+        assert!(!lfs.chunk_dir(&ptr.oid).exists());
+        assert!(lfs.dewatch_file("big.bin").is_err());
+    }
+
+    #[test]
+    fn test_dewatch_keeps_object_other_pointers_need() {
+        let temp = TempDir::new().unwrap();
+        let lfs = cdc_lfs(&temp);
+        let content = noise(150_000, 12);
+        fs::write(temp.path().join("a.bin"), &content).unwrap();
+        fs::write(temp.path().join("b.bin"), &content).unwrap();
+        let ptr = lfs.clean_to_pointer("a.bin").unwrap().unwrap();
+        lfs.clean_to_pointer("b.bin").unwrap();
+
+        let first = lfs.dewatch_file("a.bin").unwrap();
+        assert_eq!(first.still_used_by, ["b.bin"]);
+        assert_eq!(fs::read(temp.path().join("a.bin")).unwrap(), content);
+        assert!(lfs.pointer_path(&ptr.oid).exists());
+
+        let second = lfs.dewatch_file("b.bin").unwrap();
+        assert!(second.still_used_by.is_empty());
+        assert_eq!(second.chunks_pruned, ptr.chunks.len());
+        assert_eq!(fs::read(temp.path().join("b.bin")).unwrap(), content);
+        assert!(!lfs.chunk_dir(&ptr.oid).exists());
+        assert!(ptr.chunks.iter().all(|c| !lfs.shared_chunk_path(c).exists()));
+    }
+
+    #[test]
+    fn test_migration_resumes_after_interruption() {
+        let temp = TempDir::new().unwrap();
+        let lfs = Lfs::open(temp.path(), temp.path().join(".rune")).unwrap();
+        lfs.add_pattern("*.bin").unwrap();
+        fs::create_dir_all(temp.path().join("assets")).unwrap();
+        for i in 0..5 {
+            fs::write(temp.path().join(format!("assets/{}.bin", i)), vec![i as u8; 100]).unwrap();
+        }
+        fs::write(temp.path().join("assets/notes.txt"), "small").unwrap();
+
+        // Stopped after two files
+        let first = lfs.migrate_directory(temp.path(), Some(2)).unwrap();
+        assert_eq!(first.migrated.len(), 2);
+        assert_eq!(first.remaining, 3);
+        assert_eq!(lfs.migration_manifest().unwrap().completed.len(), 2);
+
+        let second = lfs.migrate_directory(temp.path(), None).unwrap();
+        assert_eq!(second.skipped, first.migrated);
+        assert_eq!(second.migrated.len(), 3);
+        assert!(second.migrated.iter().all(|rel| !first.migrated.contains(rel)));
+        assert!(second.failed.is_empty());
+        assert_eq!(second.remaining, 0);
+
+        // Nothing left; a file rewritten since it was migrated goes again
+        let third = lfs.migrate_directory(temp.path(), None).unwrap();
+        assert!(third.migrated.is_empty());
+        assert_eq!(third.skipped.len(), 5);
+        fs::write(temp.path().join("assets/0.bin"), vec![9u8; 100]).unwrap();
+        let fourth = lfs.migrate_directory(temp.path(), None).unwrap();
+        assert_eq!(fourth.migrated, ["assets/0.bin"]);
+
+        assert!(lfs.smudge_from_pointer("assets/3.bin").unwrap());
+        assert_eq!(fs::read(temp.path().join("assets/3.bin")).unwrap(), vec![3u8; 100]);
+    }
+
+    #[test]
+    fn test_migration_leaves_internal_files_alone() {
+        let temp = TempDir::new().unwrap();
+        let lfs = Lfs::open(temp.path(), temp.path().join(".rune")).unwrap().with_internal_dirs(&["shrine\\data".into()]);
+        lfs.add_pattern("*.bin").unwrap();
+        for dir in [".rune/drafts", ".rune/lfs/objects/ab/cd", "shrine/data/repos", "assets"] {
+            fs::create_dir_all(temp.path().join(dir)).unwrap();
+            fs::write(temp.path().join(dir).join("big.bin"), vec![1u8; 100]).unwrap();
+        }
+
+        assert_eq!(lfs.plan_migration(temp.path()).unwrap().len(), 1);
+        let report = lfs.migrate_directory(temp.path(), None).unwrap();
+        assert_eq!(report.migrated, ["assets/big.bin"]);
+        assert!(lfs.migrate_directory(&temp.path().join(".rune"), None).unwrap().migrated.is_empty());
+        for dir in [".rune/drafts", ".rune/lfs/objects/ab/cd", "shrine/data/repos"] {
+            assert_eq!(fs::read(temp.path().join(dir).join("big.bin")).unwrap(), vec![1u8; 100]);
+        }
+    }
+
+    #[test]
+    fn test_migration_plan_lists_candidates_with_reasons() {
+        let temp = TempDir::new().unwrap();
+        let lfs = Lfs::open(temp.path(), temp.path().join(".rune")).unwrap();
+        let mut cfg = lfs.config().unwrap();
+        cfg.patterns = vec!["*.psd".to_string(), "renders/*".to_string()];
+        cfg.migration_threshold = 1000;
+        cfg.chunk_size = 400;
+        lfs.write_config(&cfg).unwrap();
+        fs::write(temp.path().join(".runeattributes"), "*.wav lfs\nkeep.psd -lfs\n").unwrap();
+        fs::create_dir_all(temp.path().join("renders")).unwrap();
+        let files: [(&str, usize); 7] = [
+            ("art.psd", 10),
+            ("keep.psd", 10),
+            ("big.dat", 1001),
+            ("edge.dat", 1000),
+            ("notes.txt", 20),
+            ("renders/frame.exr", 900),
+            ("sound.wav", 5),
+        ];
+        for (path, size) in files {
+            fs::write(temp.path().join(path), vec![1u8; size]).unwrap();
+        }
+
+        let plan = lfs.plan_migration(temp.path()).unwrap();
+        let summary: Vec<(&str, u64, MigrationReason, usize)> =
+            plan.iter().map(|item| (item.path.as_str(), item.size, item.reason.clone(), item.chunks)).collect();
+        assert_eq!(
+            summary,
+            [
+                ("art.psd", 10, MigrationReason::Pattern("*.psd".into()), 1),
+                ("big.dat", 1001, MigrationReason::OverThreshold(1000), 3),
+                ("renders/frame.exr", 900, MigrationReason::Pattern("renders/*".into()), 3),
+                ("sound.wav", 5, MigrationReason::Attribute, 1),
+            ]
+        );
+        // Planning changes nothing
+        assert_eq!(fs::read(temp.path().join("big.dat")).unwrap(), vec![1u8; 1001]);
+        assert!(lfs.migration_manifest().unwrap().completed.is_empty());
+
+        // The plan is what a migration then does
+        let report = lfs.migrate_directory(temp.path(), None).unwrap();
+        assert_eq!(report.migrated, plan.iter().map(|item| item.path.clone()).collect::<Vec<_>>());
+        let replanned = lfs.plan_migration(temp.path()).unwrap();
+        assert!(replanned.iter().all(|item| item.reason == MigrationReason::AlreadyMigrated && item.chunks == 0));
+        assert_eq!(replanned.len(), 4);
+
+        // A lower threshold in the plan's config leaves the saved one alone
+        let lower = LfsConfig { migration_threshold: 100, ..lfs.config().unwrap() };
+        let plan = lfs.plan_migration_with(temp.path(), &lower).unwrap();
+        assert!(plan.iter().any(|item| item.path == "edge.dat" && item.reason == MigrationReason::OverThreshold(100)));
+        assert_eq!(lfs.config().unwrap().migration_threshold, 1000);
+    }
+    #[test]
+    fn test_auto_migrated_files_are_marked_in_the_plan() {
+        let temp = TempDir::new().unwrap();
+        let lfs = Lfs::open(temp.path(), temp.path().join(".rune")).unwrap();
+        fs::write(temp.path().join("huge.bin"), vec![7u8; 500]).unwrap();
+        fs::write(temp.path().join("tracked.psd"), vec![1u8; 10]).unwrap();
+        lfs.add_pattern("*.psd").unwrap();
+        lfs.migrate_directory(temp.path(), None).unwrap();
+
+        // Not tracked and under the migration threshold, moved anyway
+        let pointer = lfs.auto_migrate("huge.bin").unwrap();
+        assert_eq!(pointer.size, 500);
+        assert!(read_pointer_file(&temp.path().join("huge.bin")).unwrap().is_some());
+        assert_eq!(lfs.auto_migrated().unwrap(), ["huge.bin"]);
+
+        let reasons: Vec<(String, MigrationReason)> =
+            lfs.plan_migration(temp.path()).unwrap().into_iter().map(|item| (item.path, item.reason)).collect();
+        assert_eq!(
+            reasons,
+            [("huge.bin".into(), MigrationReason::AutoMigrated), ("tracked.psd".into(), MigrationReason::AlreadyMigrated)]
+        );
+
+        lfs.dewatch_file("huge.bin").unwrap();
+        assert!(lfs.auto_migrated().unwrap().is_empty());
+        assert!(lfs.migration_manifest().unwrap().auto.is_empty());
+    }
+}
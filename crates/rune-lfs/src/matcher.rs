@@ -0,0 +1,114 @@
+//! Compiled form of the LFS pattern list in `config.json`
+//!
+//! Patterns of the form `*.<ext>` make up most real configurations, so they
+//! are answered with a hash lookup on the path's suffixes; everything else is
+//! compiled once into a `glob::Pattern`.
+
+use anyhow::{anyhow, Result};
+use std::collections::HashSet;
+
+#[cfg(test)]
+thread_local! {
+    /// Patterns compiled on this thread, so tests can check nothing is
+    /// recompiled per path
+    pub(crate) static COMPILED: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LfsMatcher {
+    /// Suffixes from `*.<ext>` patterns, without the leading dot
+    extensions: HashSet<String>,
+    globs: Vec<glob::Pattern>,
+}
+
+impl LfsMatcher {
+    /// Compile `patterns`, failing on the first one that is not a valid glob
+    pub fn new(patterns: &[String]) -> Result<Self> {
+        let mut matcher = Self::default();
+        for pattern in patterns {
+            match extension_of(pattern) {
+                Some(ext) => {
+                    matcher.extensions.insert(ext.to_string());
+                }
+                None => matcher.globs.push(compile(pattern)?),
+            }
+        }
+        Ok(matcher)
+    }
+
+    /// Whether `path` matches any pattern, with the same semantics as
+    /// `glob::Pattern::matches` (`*` also crosses `/`)
+    pub fn is_tracked(&self, path: &str) -> bool {
+        if !self.extensions.is_empty() {
+            // `*.<ext>` matches exactly when the text after some dot is `ext`
+            let hit = path
+                .match_indices('.')
+                .any(|(i, _)| self.extensions.contains(&path[i + 1..]));
+            if hit {
+                return true;
+            }
+        }
+        self.globs.iter().any(|glob| glob.matches(path))
+    }
+}
+
+/// `ext` for a pattern `*.<ext>` whose extension has no glob syntax
+fn extension_of(pattern: &str) -> Option<&str> {
+    let ext = pattern.strip_prefix("*.")?;
+    let plain = !ext.is_empty() && !ext.contains(['*', '?', '[', ']', '/']);
+    plain.then_some(ext)
+}
+
+fn compile(pattern: &str) -> Result<glob::Pattern> {
+    #[cfg(test)]
+    COMPILED.with(|count| count.set(count.get() + 1));
+    glob::Pattern::new(pattern).map_err(|e| anyhow!("invalid LFS pattern '{}': {}", pattern, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn naive(patterns: &[String], path: &str) -> bool {
+        patterns
+            .iter()
+            .any(|p| glob::Pattern::new(p).map(|g| g.matches(path)).unwrap_or(false))
+    }
+
+    #[test]
+    fn test_matches_naive_implementation() {
+        let mut patterns: Vec<String> = (0..24).map(|i| format!("*.ext{}", i)).collect();
+        patterns.extend(
+            ["*.tar.gz", "assets/**/*.psd", "*.[Pp][Nn][Gg]", "build/*", "video?.mov", "*.bin"]
+                .iter()
+                .map(|p| p.to_string()),
+        );
+        assert_eq!(patterns.len(), 30);
+
+        let compiled_before = COMPILED.with(|count| count.get());
+        let matcher = LfsMatcher::new(&patterns).unwrap();
+        let compiled = COMPILED.with(|count| count.get()) - compiled_before;
+        assert_eq!(compiled, 4, "extension patterns should not be compiled");
+
+        let names = [
+            "a.ext3", "a.ext30", "x.tar.gz", "x.gz", "assets/ui/big.psd", "other/big.psd",
+            "logo.PNG", "build/out.o", "video1.mov", "video12.mov", "dir.bin/readme", "plain",
+            ".ext7", "nested.ext1/file.ext2",
+        ];
+        let paths: Vec<String> = (0..50_000)
+            .map(|i| format!("dir{}/{}", i % 7, names[i % names.len()]))
+            .chain(names.iter().map(|n| n.to_string()))
+            .collect();
+
+        for path in &paths {
+            assert_eq!(matcher.is_tracked(path), naive(&patterns, path), "{}", path);
+        }
+        assert_eq!(COMPILED.with(|count| count.get()) - compiled_before, compiled);
+    }
+
+    #[test]
+    fn test_invalid_pattern_is_named() {
+        let err = LfsMatcher::new(&["*.psd".to_string(), "assets/[".to_string()]).unwrap_err();
+        assert!(err.to_string().contains("'assets/['"), "{}", err);
+    }
+}
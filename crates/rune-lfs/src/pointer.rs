@@ -0,0 +1,368 @@
+//! Pointer files standing in for LFS objects in the working tree
+//!
+//! A pointer is three lines, `version`, `oid` and `size`. Editors sometimes
+//! add a byte order mark, switch the file to CRLF or leave trailing
+//! whitespace behind; `parse_pointer` reads through that but still wants
+//! every field present and well formed. `Lfs::doctor` finds the files that
+//! were meant to be pointers and no longer parse, or no longer look the way
+//! `rune` writes them.
+
+use crate::Lfs;
+use anyhow::Result;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// First line of every pointer file
+pub const POINTER_VERSION: &str = "version https://rune-lfs/v1";
+
+/// Larger files are never read as pointers
+pub const MAX_POINTER_SIZE: u64 = 1024;
+
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Hex digits in an object id
+const OID_LEN: usize = 64;
+
+/// Object named by a pointer file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PointerRef {
+    pub oid: String,
+    pub size: u64,
+}
+
+impl PointerRef {
+    /// The pointer file as `rune` writes it
+    pub fn to_text(&self) -> String {
+        pointer_text(&self.oid, self.size)
+    }
+}
+
+/// Why a pointer file did not parse
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum PointerParseError {
+    #[error("not valid UTF-8")]
+    NotText,
+    #[error("does not start with a `version` line")]
+    MissingVersion,
+    #[error("unsupported version '{0}'")]
+    UnsupportedVersion(String),
+    #[error("missing `oid` line")]
+    MissingOid,
+    #[error("oid is truncated ({0} of 64 hex digits)")]
+    TruncatedOid(usize),
+    #[error("oid '{0}' is not a lowercase hex hash")]
+    InvalidOid(String),
+    #[error("missing `size` line")]
+    MissingSize,
+    #[error("size '{0}' is not a number")]
+    InvalidSize(String),
+    #[error("`{0}` appears more than once")]
+    DuplicateField(String),
+    #[error("unexpected line '{0}'")]
+    UnexpectedLine(String),
+}
+
+/// Parse pointer file content. A byte order mark, CRLF line endings, blank
+/// lines and trailing whitespace are accepted; anything else must match
+/// what `rune` writes.
+pub fn parse_pointer(content: &[u8]) -> Result<PointerRef, PointerParseError> {
+    let content = content.strip_prefix(BOM).unwrap_or(content);
+    let text = std::str::from_utf8(content).map_err(|_| PointerParseError::NotText)?;
+    let mut lines = text.lines().map(str::trim_end).filter(|line| !line.is_empty());
+    match lines.next() {
+        Some(POINTER_VERSION) => {}
+        Some(line) if line.starts_with("version ") => {
+            return Err(PointerParseError::UnsupportedVersion(line["version ".len()..].trim().to_string()));
+        }
+        _ => return Err(PointerParseError::MissingVersion),
+    }
+
+    let (mut oid, mut size) = (None, None);
+    for line in lines {
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        let field = match key {
+            "oid" => &mut oid,
+            "size" => &mut size,
+            _ => return Err(PointerParseError::UnexpectedLine(line.to_string())),
+        };
+        if field.replace(value.trim()).is_some() {
+            return Err(PointerParseError::DuplicateField(key.to_string()));
+        }
+    }
+
+    let oid = oid.ok_or(PointerParseError::MissingOid)?;
+    check_oid(oid)?;
+    let size = size.ok_or(PointerParseError::MissingSize)?;
+    let size = size.parse().map_err(|_| PointerParseError::InvalidSize(size.to_string()))?;
+    Ok(PointerRef { oid: oid.to_string(), size })
+}
+
+fn check_oid(oid: &str) -> Result<(), PointerParseError> {
+    let hex = oid.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b));
+    if !hex || oid.len() > OID_LEN {
+        Err(PointerParseError::InvalidOid(oid.to_string()))
+    } else if oid.len() < OID_LEN {
+        Err(PointerParseError::TruncatedOid(oid.len()))
+    } else {
+        Ok(())
+    }
+}
+
+/// Whether `content` was meant to be a pointer: small and carrying the
+/// version line somewhere, whether or not it still parses
+pub fn looks_like_pointer(content: &[u8]) -> bool {
+    content.len() as u64 <= MAX_POINTER_SIZE
+        && content.windows(POINTER_VERSION.len()).any(|w| w == POINTER_VERSION.as_bytes())
+}
+
+/// The pointer in the file at `path`; `None` for regular content. A file
+/// that was meant to be a pointer but does not parse is an error naming it.
+pub fn read_pointer_file(path: &Path) -> Result<Option<PointerRef>> {
+    if fs::metadata(path)?.len() > MAX_POINTER_SIZE {
+        return Ok(None);
+    }
+    let content = fs::read(path)?;
+    if !looks_like_pointer(&content) {
+        return Ok(None);
+    }
+    parse_pointer(&content).map(Some).map_err(|e| {
+        anyhow::anyhow!("{} is a damaged LFS pointer: {} (run `rune lfs doctor`)", path.display(), e)
+    })
+}
+
+/// Working-tree text standing in for object `oid`
+pub(crate) fn pointer_text(oid: &str, size: u64) -> String {
+    format!("{}\noid {}\nsize {}", POINTER_VERSION, oid, size)
+}
+
+/// What `Lfs::doctor` found wrong with one pointer file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PointerProblem {
+    /// Does not parse at all
+    Unparsable(PointerParseError),
+    /// Parses, but not in the form `rune` writes; each entry names a difference
+    NonCanonical(Vec<&'static str>),
+}
+
+impl fmt::Display for PointerProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PointerProblem::Unparsable(e) => write!(f, "{}", e),
+            PointerProblem::NonCanonical(issues) => write!(f, "not canonical: {}", issues.join(", ")),
+        }
+    }
+}
+
+/// One damaged pointer file
+#[derive(Debug, Clone)]
+pub struct PointerDiagnosis {
+    /// Path relative to the working tree
+    pub path: String,
+    pub problem: PointerProblem,
+    /// The canonical pointer, when the oid and size could be recovered
+    pub recovered: Option<PointerRef>,
+    pub repaired: bool,
+}
+
+/// Outcome of `Lfs::doctor`
+#[derive(Debug, Clone, Default)]
+pub struct DoctorReport {
+    /// Files that looked like pointers
+    pub checked: usize,
+    pub damaged: Vec<PointerDiagnosis>,
+}
+
+impl Lfs {
+    /// Check every pointer-like file in the working tree. With `repair`,
+    /// files whose object can be identified are rewritten canonically; the
+    /// size of a pointer that no longer parses comes from the local object.
+    pub fn doctor(&self, repair: bool) -> Result<DoctorReport> {
+        let mut report = DoctorReport::default();
+        for (path, content) in self.pointer_files()? {
+            report.checked += 1;
+
+            let (problem, recovered) = match parse_pointer(&content) {
+                Ok(pointer) if content == pointer.to_text().as_bytes() => continue,
+                Ok(pointer) => (PointerProblem::NonCanonical(cosmetic_issues(&content)), Some(pointer)),
+                Err(e) => (PointerProblem::Unparsable(e), self.recover_pointer(&content)),
+            };
+            let repaired = match &recovered {
+                Some(pointer) if repair => {
+                    fs::write(&path, pointer.to_text())?;
+                    true
+                }
+                _ => false,
+            };
+            let path = path.strip_prefix(&self.root)?.to_string_lossy().replace('\\', "/");
+            report.damaged.push(PointerDiagnosis { path, problem, recovered, repaired });
+        }
+        Ok(report)
+    }
+
+    /// Every working-tree file that looks like a pointer, with its content
+    pub(crate) fn pointer_files(&self) -> Result<Vec<(PathBuf, Vec<u8>)>> {
+        let mut files = Vec::new();
+        let walk = walkdir::WalkDir::new(&self.root).sort_by_file_name().into_iter();
+        for entry in walk.filter_entry(|e| !self.internal.contains(e.path())) {
+            let entry = entry?;
+            if !entry.file_type().is_file() || entry.metadata()?.len() > MAX_POINTER_SIZE {
+                continue;
+            }
+            let content = fs::read(entry.path())?;
+            if looks_like_pointer(&content) {
+                files.push((entry.into_path(), content));
+            }
+        }
+        Ok(files)
+    }
+
+    /// Rebuild a pointer that does not parse from an intact `oid` line and
+    /// the object stored under it
+    fn recover_pointer(&self, content: &[u8]) -> Option<PointerRef> {
+        let text = String::from_utf8_lossy(content);
+        let oid = text
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("oid "))
+            .map(str::trim)
+            .find(|oid| check_oid(oid).is_ok())?;
+        let pointer: crate::Pointer = serde_json::from_slice(&fs::read(self.pointer_path(oid)).ok()?).ok()?;
+        Some(PointerRef { oid: pointer.oid, size: pointer.size })
+    }
+}
+
+/// How parseable pointer content differs from the canonical form
+fn cosmetic_issues(content: &[u8]) -> Vec<&'static str> {
+    let text = String::from_utf8_lossy(content);
+    let mut issues = Vec::new();
+    if content.starts_with(BOM) {
+        issues.push("byte order mark");
+    }
+    if text.contains('\r') {
+        issues.push("CRLF line endings");
+    }
+    if text.lines().any(|line| line.trim_end_matches('\r') != line.trim_end()) {
+        issues.push("trailing whitespace");
+    }
+    if text.lines().any(|line| line.trim().is_empty()) {
+        issues.push("blank lines");
+    }
+    if text.ends_with('\n') {
+        issues.push("trailing newline");
+    }
+    if issues.is_empty() {
+        issues.push("fields out of order or spaced differently");
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const OID: &str = "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262";
+
+    fn canonical() -> String {
+        pointer_text(OID, 42)
+    }
+
+    #[test]
+    fn test_parse_tolerates_bom_crlf_and_whitespace() {
+        let expected = PointerRef { oid: OID.to_string(), size: 42 };
+        let mut bom = BOM.to_vec();
+        bom.extend_from_slice(canonical().as_bytes());
+        assert_eq!(parse_pointer(&bom).unwrap(), expected);
+        assert_eq!(parse_pointer(canonical().replace('\n', "\r\n").as_bytes()).unwrap(), expected);
+        let spaced = format!("{}  \n\noid {} \nsize 42\t\n\n", POINTER_VERSION, OID);
+        assert_eq!(parse_pointer(spaced.as_bytes()).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_parse_classifies_damage() {
+        let cases = [
+            (format!("{}\noid {}\n", POINTER_VERSION, OID), PointerParseError::MissingSize),
+            (format!("{}\noid {}\nsize 42", POINTER_VERSION, &OID[..20]), PointerParseError::TruncatedOid(20)),
+            (format!("{}\nsize 42", POINTER_VERSION), PointerParseError::MissingOid),
+            (format!("{}\noid {}\nsize big", POINTER_VERSION, OID), PointerParseError::InvalidSize("big".into())),
+            (format!("{}\noid {}\noid {}\nsize 1", POINTER_VERSION, OID, OID), PointerParseError::DuplicateField("oid".into())),
+            (format!("oid {}\n{}\nsize 42", OID, POINTER_VERSION), PointerParseError::MissingVersion),
+            ("version https://rune-lfs/v2\n".to_string(), PointerParseError::UnsupportedVersion("https://rune-lfs/v2".into())),
+        ];
+        for (content, expected) in cases {
+            assert_eq!(parse_pointer(content.as_bytes()).unwrap_err(), expected, "{:?}", content);
+        }
+        assert_eq!(parse_pointer(b"\xff\xfe").unwrap_err(), PointerParseError::NotText);
+    }
+
+    #[test]
+    fn test_parse_never_panics_on_prefixes() {
+        let mut bom = BOM.to_vec();
+        bom.extend_from_slice(canonical().replace('\n', "\r\n").as_bytes());
+        for end in 0..=bom.len() {
+            let _ = parse_pointer(&bom[..end]);
+        }
+        for content in [&b""[..], b"version ", b"version https://rune-lfs/v1\noid", b"\xEF\xBB", b"oid \xF0\x9F"] {
+            assert!(parse_pointer(content).is_err());
+        }
+    }
+
+    #[test]
+    fn test_doctor_repairs_recoverable_pointers() {
+        let temp = TempDir::new().unwrap();
+        let lfs = Lfs::open(temp.path(), temp.path().join(".rune")).unwrap();
+        fs::write(temp.path().join(".runeattributes"), "*.bin lfs\n").unwrap();
+        fs::write(temp.path().join("stored.bin"), vec![3u8; 42]).unwrap();
+        let oid = lfs.clean_to_pointer("stored.bin").unwrap().unwrap().oid;
+        let text = pointer_text(&oid, 42);
+
+        let mut bom = BOM.to_vec();
+        bom.extend_from_slice(text.as_bytes());
+        fs::write(temp.path().join("bom.bin"), bom).unwrap();
+        fs::write(temp.path().join("crlf.bin"), text.replace('\n', "\r\n")).unwrap();
+        fs::write(temp.path().join("nosize.bin"), format!("{}\noid {}\n", POINTER_VERSION, oid)).unwrap();
+        fs::write(temp.path().join("short.bin"), format!("{}\noid {}\nsize 42", POINTER_VERSION, &oid[..10])).unwrap();
+        fs::write(temp.path().join("plain.txt"), "nothing to see\n").unwrap();
+
+        let report = lfs.doctor(false).unwrap();
+        assert_eq!(report.checked, 5);
+        let found: Vec<(&str, &PointerProblem, bool)> = report
+            .damaged
+            .iter()
+            .map(|d| (d.path.as_str(), &d.problem, d.recovered.is_some()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("bom.bin", &PointerProblem::NonCanonical(vec!["byte order mark"]), true),
+                ("crlf.bin", &PointerProblem::NonCanonical(vec!["CRLF line endings"]), true),
+                ("nosize.bin", &PointerProblem::Unparsable(PointerParseError::MissingSize), true),
+                ("short.bin", &PointerProblem::Unparsable(PointerParseError::TruncatedOid(10)), false),
+            ]
+        );
+        assert!(report.damaged.iter().all(|d| !d.repaired));
+
+        let report = lfs.doctor(true).unwrap();
+        let repaired: Vec<&str> = report.damaged.iter().filter(|d| d.repaired).map(|d| d.path.as_str()).collect();
+        assert_eq!(repaired, ["bom.bin", "crlf.bin", "nosize.bin"]);
+        for path in ["bom.bin", "crlf.bin", "nosize.bin"] {
+            assert_eq!(fs::read_to_string(temp.path().join(path)).unwrap(), text);
+        }
+        let again = lfs.doctor(true).unwrap();
+        assert_eq!(again.damaged.len(), 1);
+        assert_eq!(again.damaged[0].path, "short.bin");
+    }
+
+    #[test]
+    fn test_smudge_names_damaged_pointer() {
+        let temp = TempDir::new().unwrap();
+        let lfs = Lfs::open(temp.path(), temp.path().join(".rune")).unwrap();
+        fs::write(temp.path().join("broken.bin"), format!("{}\nsize 42\n", POINTER_VERSION)).unwrap();
+        let err = lfs.smudge_from_pointer("broken.bin").unwrap_err().to_string();
+        assert!(err.contains("broken.bin") && err.contains("missing `oid` line"), "{}", err);
+
+        fs::write(temp.path().join("plain.bin"), "regular content").unwrap();
+        assert!(!lfs.smudge_from_pointer("plain.bin").unwrap());
+    }
+}
@@ -0,0 +1,155 @@
+//! Moving chunks between the local store and the remote
+//!
+//! Every chunk goes through a `ChunkTransport`. A failure the transport
+//! reports as transient is tried again after a pause that doubles each
+//! time, up to `TransferSettings::retries` more attempts; only then does the
+//! object end up `Failed`. With `max_bytes_per_sec` set, a transfer waits
+//! after each chunk until its average rate is back under the limit.
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Rate limit and retry policy for LFS transfers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransferSettings {
+    /// Bytes per second to stay under; 0 for no limit
+    pub max_bytes_per_sec: u64,
+    /// Attempts after the first one when a chunk fails transiently
+    pub retries: u32,
+    /// Pause before the first retry, in milliseconds
+    pub backoff_ms: u64,
+    /// Longest pause between attempts, in milliseconds
+    pub max_backoff_ms: u64,
+}
+
+impl Default for TransferSettings {
+    fn default() -> Self {
+        Self { max_bytes_per_sec: 0, retries: 5, backoff_ms: 500, max_backoff_ms: 30_000 }
+    }
+}
+
+impl TransferSettings {
+    /// Pause before retry number `retry`, counting from 0
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let ms = self.backoff_ms.saturating_mul(1u64 << retry.min(32));
+        Duration::from_millis(ms.min(self.max_backoff_ms))
+    }
+}
+
+/// Why moving a chunk failed
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum TransferError {
+    /// Worth another try: a timeout, a dropped connection, a busy server
+    #[error("{0}")]
+    Transient(String),
+    /// Trying again would fail the same way
+    #[error("{0}")]
+    Permanent(String),
+}
+
+/// How chunks reach the remote and come back
+pub trait ChunkTransport {
+    fn upload_chunk(&self, oid: &str, chunk: &str, data: &[u8]) -> Result<(), TransferError>;
+    fn download_chunk(&self, oid: &str, chunk: &str) -> Result<Vec<u8>, TransferError>;
+
+    /// Publish the pointer of `oid` once its chunks are all up, so a clone
+    /// that only has the oid can find them
+    fn upload_pointer(&self, _oid: &str, _pointer: &[u8]) -> Result<(), TransferError> {
+        Ok(())
+    }
+
+    /// The pointer `upload_pointer` published for `oid`, if any
+    fn download_pointer(&self, _oid: &str) -> Result<Option<Vec<u8>>, TransferError> {
+        Ok(None)
+    }
+}
+
+/// Stands in for the remote until there is an HTTP client: uploads are
+/// accepted and downloads are placeholder bytes
+pub struct SimulatedRemote;
+
+impl ChunkTransport for SimulatedRemote {
+    fn upload_chunk(&self, _oid: &str, _chunk: &str, _data: &[u8]) -> Result<(), TransferError> {
+        Ok(())
+    }
+
+    fn download_chunk(&self, _oid: &str, _chunk: &str) -> Result<Vec<u8>, TransferError> {
+        Ok(vec![0u8; 1024])
+    }
+}
+
+/// Run `attempt` until it succeeds, fails permanently or has used up the
+/// retries in `settings`
+pub(crate) fn with_retries<T>(
+    settings: &TransferSettings,
+    mut attempt: impl FnMut() -> Result<T, TransferError>,
+) -> Result<T, TransferError> {
+    let mut retry = 0;
+    loop {
+        match attempt() {
+            Err(TransferError::Transient(_)) if retry < settings.retries => {
+                std::thread::sleep(settings.backoff(retry));
+                retry += 1;
+            }
+            Err(TransferError::Transient(e)) if settings.retries > 0 => {
+                return Err(TransferError::Transient(format!("{} (gave up after {} attempts)", e, retry + 1)));
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Keeps one transfer under a bytes-per-second limit
+pub(crate) struct Throttle {
+    limit: u64,
+    started: Instant,
+    sent: u64,
+}
+
+impl Throttle {
+    pub(crate) fn new(limit: u64) -> Self {
+        Self { limit, started: Instant::now(), sent: 0 }
+    }
+
+    /// Count `bytes` more as sent and wait as long as the limit asks
+    pub(crate) fn pace(&mut self, bytes: u64) {
+        let wait = self.wait_after(bytes, self.started.elapsed());
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+        }
+    }
+
+    /// How long to wait, `elapsed` into the transfer, after `bytes` more
+    /// were sent
+    fn wait_after(&mut self, bytes: u64, elapsed: Duration) -> Duration {
+        self.sent = self.sent.saturating_add(bytes);
+        if self.limit == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(self.sent as f64 / self.limit as f64).saturating_sub(elapsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_cap() {
+        let settings = TransferSettings { backoff_ms: 100, max_backoff_ms: 1_000, ..Default::default() };
+        let waits: Vec<u64> = (0..6).map(|retry| settings.backoff(retry).as_millis() as u64).collect();
+        assert_eq!(waits, [100, 200, 400, 800, 1_000, 1_000]);
+        assert_eq!(settings.backoff(u32::MAX), Duration::from_millis(1_000));
+    }
+
+    #[test]
+    fn test_throttle_waits_for_the_average_rate() {
+        let mut throttle = Throttle::new(1_000);
+        assert_eq!(throttle.wait_after(500, Duration::ZERO), Duration::from_millis(500));
+        // Already slower than the limit
+        assert_eq!(throttle.wait_after(500, Duration::from_secs(2)), Duration::ZERO);
+        assert_eq!(throttle.wait_after(2_000, Duration::from_secs(2)), Duration::from_secs(1));
+        assert_eq!(Throttle::new(0).wait_after(u64::MAX, Duration::ZERO), Duration::ZERO);
+    }
+}
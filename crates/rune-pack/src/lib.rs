@@ -1,31 +1,135 @@
-
 use anyhow::Result;
 use serde::{Serialize, Deserialize};
+use std::io::{Read, Seek, SeekFrom, Write};
 
+/// `compressed` is false for blobs stored as-is; indexes written before the
+/// flag existed only hold compressed entries. `hash` is the blake3 of the
+/// unpacked content, missing from indexes written before it was recorded.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PackEntry { pub path: String, pub size: u64, pub offset: u64 }
+pub struct PackEntry {
+    pub path: String,
+    pub size: u64,
+    pub offset: u64,
+    #[serde(default = "default_compressed")]
+    pub compressed: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+}
+
+fn default_compressed() -> bool { true }
+
+/// An entry whose bytes no longer unpack to what was packed
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("pack entry {path} at offset {offset} is corrupt: {reason}")]
+pub struct CorruptEntry { pub path: String, pub offset: u64, pub reason: String }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PackIndex { pub entries: Vec<PackEntry>, pub checksum: String }
 
+/// Blobs smaller than this are stored raw by default; zstd's frame overhead
+/// makes them bigger rather than smaller
+pub const DEFAULT_MIN_COMPRESS_SIZE: usize = 64;
+
+#[derive(Debug, Clone)]
+pub struct PackOptions {
+    /// Blobs below this many bytes are not compressed
+    pub min_compress_size: usize,
+    pub level: i32,
+}
+
+impl Default for PackOptions {
+    fn default() -> Self { Self { min_compress_size: DEFAULT_MIN_COMPRESS_SIZE, level: 3 } }
+}
+
 pub fn pack_blobs(blobs: Vec<(String, Vec<u8>)>) -> Result<(Vec<u8>, PackIndex)> {
-    let mut out = Vec::new(); let mut entries = Vec::new(); let mut off = 0u64;
-    for (path, data) in blobs {
-        let compressed = zstd::encode_all(&data[..], 3)?; let sz = compressed.len() as u64;
-        out.extend_from_slice(&compressed); entries.push(PackEntry { path, size: sz, offset: off }); off += sz;
+    pack_blobs_with(blobs, &PackOptions::default())
+}
+
+/// `pack_blobs` with a chosen threshold and level. A blob is also stored raw
+/// when compressing it doesn't make it smaller. Blobs are packed sorted by
+/// path, then content hash, so the same set gives the same bytes and
+/// checksum whatever order it comes in.
+pub fn pack_blobs_with(blobs: Vec<(String, Vec<u8>)>, options: &PackOptions) -> Result<(Vec<u8>, PackIndex)> {
+    let mut blobs: Vec<(String, String, Vec<u8>)> =
+        blobs.into_iter().map(|(path, data)| (path, blake3::hash(&data).to_hex().to_string(), data)).collect();
+    blobs.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+    let mut writer = PackWriter::new(Vec::new(), options.clone());
+    for (path, hash, data) in blobs {
+        writer.add_hashed(path, hash, data)?;
+    }
+    writer.finish()
+}
+
+/// Writes a pack one blob at a time, so a pack never has to fit in memory.
+/// Entries are stored in the order they are added; adding them sorted by
+/// path gives the same bytes as `pack_blobs_with`.
+pub struct PackWriter<W: Write> {
+    out: W,
+    options: PackOptions,
+    entries: Vec<PackEntry>,
+    offset: u64,
+    checksum: blake3::Hasher,
+}
+
+impl<W: Write> PackWriter<W> {
+    pub fn new(out: W, options: PackOptions) -> Self {
+        Self { out, options, entries: Vec::new(), offset: 0, checksum: blake3::Hasher::new() }
+    }
+
+    pub fn add(&mut self, path: String, data: Vec<u8>) -> Result<()> {
+        let hash = blake3::hash(&data).to_hex().to_string();
+        self.add_hashed(path, hash, data)
+    }
+
+    fn add_hashed(&mut self, path: String, hash: String, data: Vec<u8>) -> Result<()> {
+        let compressed = if data.len() < self.options.min_compress_size { None } else { Some(zstd::encode_all(&data[..], self.options.level)?).filter(|c| c.len() < data.len()) };
+        let is_compressed = compressed.is_some();
+        let stored = compressed.unwrap_or(data); let sz = stored.len() as u64;
+        self.out.write_all(&stored)?; self.checksum.update(&stored);
+        self.entries.push(PackEntry { path, size: sz, offset: self.offset, compressed: is_compressed, hash: Some(hash) }); self.offset += sz;
+        Ok(())
+    }
+
+    /// Flush the pack and return the writer with the pack's index
+    pub fn finish(mut self) -> Result<(W, PackIndex)> {
+        self.out.flush()?;
+        let checksum = format!("{}", self.checksum.finalize());
+        Ok((self.out, PackIndex { entries: self.entries, checksum }))
     }
-    let checksum = format!("{}", blake3::hash(&out)); Ok((out, PackIndex { entries, checksum }))
 }
 
+/// Unpack `entry` and check it against its recorded hash; a mismatch is a
+/// `CorruptEntry` error
 pub fn unpack_blob(pack_data: &[u8], entry: &PackEntry) -> Result<Vec<u8>> {
+    unpack_blob_with(pack_data, entry, true)
+}
+
+/// `unpack_blob`, checking the content only when `verify` is set
+pub fn unpack_blob_with(pack_data: &[u8], entry: &PackEntry, verify: bool) -> Result<Vec<u8>> {
     let start = entry.offset as usize;
     let end = start + entry.size as usize;
     if end > pack_data.len() {
         anyhow::bail!("Pack entry extends beyond pack data");
     }
-    let compressed_data = &pack_data[start..end];
-    let decompressed = zstd::decode_all(compressed_data)?;
-    Ok(decompressed)
+    Ok(decode_entry(&pack_data[start..end], entry, verify)?)
+}
+
+/// Turn the stored bytes of `entry` back into its content. Compressed bytes
+/// that don't decompress are corrupt whether or not `verify` is set.
+pub fn decode_entry(stored: &[u8], entry: &PackEntry, verify: bool) -> std::result::Result<Vec<u8>, CorruptEntry> {
+    let corrupt = |reason: String| CorruptEntry { path: entry.path.clone(), offset: entry.offset, reason };
+    let data = if entry.compressed {
+        zstd::decode_all(stored).map_err(|e| corrupt(format!("does not decompress ({})", e)))?
+    } else {
+        stored.to_vec()
+    };
+    if let Some(expected) = entry.hash.as_deref().filter(|_| verify) {
+        let actual = blake3::hash(&data).to_hex().to_string();
+        if actual != expected {
+            return Err(corrupt(format!("content hashes to {}, expected {}", &actual[..12], &expected[..expected.len().min(12)])));
+        }
+    }
+    Ok(data)
 }
 
 impl PackIndex {
@@ -41,6 +145,25 @@ impl PackIndex {
         let computed = format!("{}", blake3::hash(pack_data));
         computed == self.checksum
     }
+
+    /// Unpack and check every entry, reading `pack` one entry at a time.
+    /// Entries cut off by the end of the pack are corrupt too.
+    pub fn verify_entries<R: Read + Seek>(&self, pack: &mut R) -> std::io::Result<Vec<CorruptEntry>> {
+        let mut corrupt = Vec::new();
+        for entry in &self.entries {
+            pack.seek(SeekFrom::Start(entry.offset))?;
+            let mut stored = Vec::with_capacity(entry.size as usize);
+            pack.by_ref().take(entry.size).read_to_end(&mut stored)?;
+            if stored.len() as u64 != entry.size {
+                corrupt.push(CorruptEntry { path: entry.path.clone(), offset: entry.offset, reason: "cut off by the end of the pack".into() });
+                continue;
+            }
+            if let Err(e) = decode_entry(&stored, entry, true) {
+                corrupt.push(e);
+            }
+        }
+        Ok(corrupt)
+    }
 }
 
 #[cfg(test)]
@@ -135,6 +258,8 @@ mod tests {
             path: "test.txt".to_string(),
             size: 1024,
             offset: 512,
+            compressed: true,
+            hash: None,
         };
         
         assert_eq!(entry.path, "test.txt");
@@ -208,6 +333,8 @@ mod tests {
             path: "invalid.txt".to_string(),
             size: (pack_data.len() + 100) as u64,
             offset: 0,
+            compressed: true,
+            hash: None,
         };
         
         let result = unpack_blob(&pack_data, &invalid_entry);
@@ -308,6 +435,8 @@ mod tests {
             path: "debug_test.txt".to_string(),
             size: 42,
             offset: 100,
+            compressed: true,
+            hash: None,
         };
         
         let debug_str = format!("{:?}", entry);
@@ -325,4 +454,128 @@ mod tests {
         assert!(index_debug.contains("PackIndex"));
         assert!(index_debug.contains("test_checksum"));
     }
+
+    #[test]
+    fn test_tiny_incompressible_blob_stored_raw() {
+        let tiny = vec![0x9f, 0x03, 0xe2, 0x41, 0x7c];
+        let (pack_data, index) = pack_blobs(vec![("tiny.bin".to_string(), tiny.clone())]).unwrap();
+
+        let entry = &index.entries[0];
+        assert!(!entry.compressed);
+        assert_eq!(entry.size, tiny.len() as u64);
+        assert_eq!(pack_data, tiny);
+        assert_eq!(unpack_blob(&pack_data, entry).unwrap(), tiny);
+    }
+
+    #[test]
+    fn test_compression_skipped_when_it_does_not_shrink() {
+        // Above the threshold, but pseudo-random bytes don't compress
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let noise: Vec<u8> = (0..256).map(|_| { state ^= state << 13; state ^= state >> 7; state ^= state << 17; state as u8 }).collect();
+        let options = PackOptions { min_compress_size: 16, ..PackOptions::default() };
+        let (pack_data, index) = pack_blobs_with(vec![("noise.bin".to_string(), noise.clone())], &options).unwrap();
+
+        assert!(!index.entries[0].compressed);
+        assert_eq!(unpack_blob(&pack_data, &index.entries[0]).unwrap(), noise);
+    }
+
+    #[test]
+    fn test_large_compressible_blob_compressed() {
+        let large = "rune pack ".repeat(2000).into_bytes();
+        let blobs = vec![
+            ("tiny.txt".to_string(), b"hi".to_vec()),
+            ("large.txt".to_string(), large.clone()),
+        ];
+        let (pack_data, index) = pack_blobs(blobs).unwrap();
+
+        let entry = index.find_entry("large.txt").unwrap();
+        assert!(entry.compressed);
+        assert!(entry.size < large.len() as u64 / 10);
+        // Sorted by path, so it comes before tiny.txt
+        assert_eq!(entry.offset, 0);
+        assert_eq!(index.find_entry("tiny.txt").unwrap().offset, entry.size);
+        assert_eq!(unpack_blob(&pack_data, entry).unwrap(), large);
+        assert_eq!(unpack_blob(&pack_data, index.find_entry("tiny.txt").unwrap()).unwrap(), b"hi");
+
+        // A threshold above the blob's size keeps it raw
+        let options = PackOptions { min_compress_size: large.len() + 1, ..PackOptions::default() };
+        let (_, raw) = pack_blobs_with(vec![("large.txt".to_string(), large.clone())], &options).unwrap();
+        assert!(!raw.entries[0].compressed);
+        assert_eq!(raw.entries[0].size, large.len() as u64);
+    }
+
+    #[test]
+    fn test_old_index_entries_default_to_compressed() {
+        let json = r#"{"entries":[{"path":"a.txt","size":3,"offset":0}],"checksum":"x"}"#;
+        let index: PackIndex = serde_json::from_str(json).unwrap();
+        assert!(index.entries[0].compressed);
+        assert!(index.entries[0].hash.is_none());
+    }
+
+    #[test]
+    fn test_flipped_byte_is_caught_on_unpack_and_verify() {
+        let large = "rune pack ".repeat(2000).into_bytes();
+        let blobs = vec![
+            ("raw.txt".to_string(), b"kept as is".to_vec()),
+            ("rune.txt".to_string(), large.clone()),
+        ];
+        let (mut pack_data, index) = pack_blobs(blobs).unwrap();
+        assert!(index.verify_entries(&mut std::io::Cursor::new(&pack_data)).unwrap().is_empty());
+
+        // A raw entry still unpacks, to the wrong bytes
+        let raw = index.find_entry("raw.txt").unwrap();
+        pack_data[raw.offset as usize + 2] ^= 0x20;
+        let err = unpack_blob(&pack_data, raw).unwrap_err();
+        let corrupt = err.downcast_ref::<CorruptEntry>().unwrap();
+        assert_eq!((corrupt.path.as_str(), corrupt.offset), ("raw.txt", 0));
+        assert_eq!(unpack_blob_with(&pack_data, raw, false).unwrap(), b"kePt as is");
+        assert_eq!(unpack_blob(&pack_data, index.find_entry("rune.txt").unwrap()).unwrap(), large);
+
+        let bad = index.verify_entries(&mut std::io::Cursor::new(&pack_data)).unwrap();
+        assert_eq!(bad.iter().map(|e| e.path.as_str()).collect::<Vec<_>>(), ["raw.txt"]);
+
+        // A truncated copy loses the last entry
+        pack_data.truncate(pack_data.len() - 1);
+        let bad = index.verify_entries(&mut std::io::Cursor::new(&pack_data)).unwrap();
+        assert_eq!(bad.iter().map(|e| e.path.as_str()).collect::<Vec<_>>(), ["raw.txt", "rune.txt"]);
+    }
+    #[test]
+    fn test_input_order_does_not_change_the_pack() {
+        let large = "rune pack ".repeat(2000).into_bytes();
+        let blobs = vec![
+            ("b.txt".to_string(), b"second".to_vec()),
+            ("a.txt".to_string(), large),
+            ("c/d.txt".to_string(), b"third".to_vec()),
+            ("a.txt".to_string(), b"same path, other content".to_vec()),
+        ];
+        let mut reversed = blobs.clone();
+        reversed.reverse();
+
+        let (data, index) = pack_blobs(blobs).unwrap();
+        let (reversed_data, reversed_index) = pack_blobs(reversed).unwrap();
+        assert_eq!(data, reversed_data);
+        assert_eq!(index.checksum, reversed_index.checksum);
+        let paths: Vec<&str> = index.entries.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, ["a.txt", "a.txt", "b.txt", "c/d.txt"]);
+        assert_eq!(serde_json::to_vec(&index).unwrap(), serde_json::to_vec(&reversed_index).unwrap());
+    }
+
+    #[test]
+    fn test_writer_streams_the_same_pack() {
+        let blobs = vec![
+            ("a".to_string(), "rune pack ".repeat(2000).into_bytes()),
+            ("b".to_string(), b"tiny".to_vec()),
+        ];
+        let (data, index) = pack_blobs(blobs.clone()).unwrap();
+
+        let mut writer = PackWriter::new(std::io::Cursor::new(Vec::new()), PackOptions::default());
+        for (path, content) in blobs {
+            writer.add(path, content).unwrap();
+        }
+        let (out, streamed) = writer.finish().unwrap();
+        assert_eq!(out.into_inner(), data);
+        assert_eq!(streamed.checksum, index.checksum);
+        assert!(streamed.verify_checksum(&data));
+        assert_eq!(unpack_blob(&data, streamed.find_entry("b").unwrap()).unwrap(), b"tiny");
+    }
 }
@@ -3,6 +3,7 @@ pub mod simple;
 pub mod advanced;
 pub mod network_storage;
 pub mod monitoring;
+pub mod phases;
 
 // Re-export for convenience
 pub use simple::{PerformanceEngine, PerformanceMetrics, SimpleCache};
@@ -10,5 +11,7 @@ pub use advanced::{AdvancedPerformanceEngine, AdvancedMetrics, PerformanceConfig
 pub use network_storage::{NetworkStorageEngine, TransferStats, DeltaResult, StreamConfig};
 pub use monitoring::{
     PerformanceMonitor, BenchmarkResult, PerformanceBottleneck, BottleneckSeverity,
-    PerformanceReport, MetricSnapshot, TrendDirection, TrendSignificance, PerformanceTrend
+    PerformanceReport, MetricSnapshot, TrendDirection, TrendSignificance, PerformanceTrend,
+    PerformanceThresholds, LatencyThresholds
 };
+pub use phases::{PhaseRecorder, RecordedRun, RunSummary};
@@ -78,6 +78,56 @@ pub struct PerformanceThresholds {
     pub min_cache_hit_ratio: f64,
     pub max_disk_io_latency: Duration,
     pub min_throughput: f64,
+    /// Per-operation latency limits; other operations are judged against
+    /// limits derived from `max_operation_latency`
+    pub operation_latency: HashMap<String, LatencyThresholds>,
+}
+
+/// Durations past which a single run of an operation is a bottleneck of
+/// the given severity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencyThresholds {
+    pub low: Duration,
+    pub medium: Duration,
+    pub high: Duration,
+    pub critical: Duration,
+}
+
+impl LatencyThresholds {
+    pub fn new(low_ms: u64, medium_ms: u64, high_ms: u64, critical_ms: u64) -> Self {
+        Self {
+            low: Duration::from_millis(low_ms),
+            medium: Duration::from_millis(medium_ms),
+            high: Duration::from_millis(high_ms),
+            critical: Duration::from_millis(critical_ms),
+        }
+    }
+
+    /// Limits scaled from one acceptable latency: 1x, 2x, 5x and 10x
+    pub fn scaled(acceptable: Duration) -> Self {
+        Self {
+            low: acceptable,
+            medium: acceptable * 2,
+            high: acceptable * 5,
+            critical: acceptable * 10,
+        }
+    }
+
+    /// The severity of a run that took `duration`, `None` when it was fast
+    /// enough
+    pub fn severity(&self, duration: Duration) -> Option<BottleneckSeverity> {
+        if duration > self.critical {
+            Some(BottleneckSeverity::Critical)
+        } else if duration > self.high {
+            Some(BottleneckSeverity::High)
+        } else if duration > self.medium {
+            Some(BottleneckSeverity::Medium)
+        } else if duration > self.low {
+            Some(BottleneckSeverity::Low)
+        } else {
+            None
+        }
+    }
 }
 
 /// Comprehensive benchmark suite
@@ -134,7 +184,7 @@ pub struct PerformanceBottleneck {
 }
 
 /// Severity levels for bottlenecks
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum BottleneckSeverity {
     Low,
     Medium,
@@ -160,6 +210,89 @@ impl PerformanceMonitor {
         }
     }
 
+    pub fn with_thresholds(thresholds: PerformanceThresholds) -> Self {
+        Self { thresholds, ..Self::new() }
+    }
+
+    /// Record one timed run of a repository operation (`status`, `commit`,
+    /// `push`, ...) for `analyze` to judge
+    pub fn record_operation(&self, operation: &str, duration: Duration, success: bool) -> anyhow::Result<()> {
+        let metrics = {
+            let mut current_metrics = self.metrics.write().unwrap();
+            current_metrics.operation_latency.insert(operation.to_string(), duration);
+            current_metrics.clone()
+        };
+
+        let snapshot = MetricSnapshot {
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+            metrics,
+            operation: operation.to_string(),
+            duration,
+            success,
+        };
+
+        let mut history = self.history.lock().unwrap();
+        history.push_back(snapshot);
+        if history.len() > 1000 {
+            history.pop_front();
+        }
+
+        Ok(())
+    }
+
+    /// Record a run from the run log, timed by its outermost phases
+    pub fn record_run(&self, run: &crate::phases::RecordedRun) -> anyhow::Result<()> {
+        self.record_operation(&run.operation, run.duration(), run.success)
+    }
+
+    /// Bottlenecks in the recorded operation timings, worst first.
+    ///
+    /// Each operation is judged by its slowest recorded run against its
+    /// latency thresholds and reported at most once; `impact` is how far
+    /// that run overshot the lowest threshold, in percent.
+    pub fn analyze(&self) -> Vec<PerformanceBottleneck> {
+        let history = self.history.lock().unwrap();
+        let mut runs: HashMap<&str, Vec<Duration>> = HashMap::new();
+        for snapshot in history.iter().filter(|s| s.operation != "update") {
+            runs.entry(snapshot.operation.as_str()).or_default().push(snapshot.duration);
+        }
+
+        let mut bottlenecks = Vec::new();
+        for (operation, durations) in runs {
+            let limits = self.thresholds.latency_for(operation);
+            let Some(&slowest) = durations.iter().max() else {
+                continue;
+            };
+            let Some(severity) = limits.severity(slowest) else {
+                continue;
+            };
+            let slow_runs = durations.iter().filter(|d| **d > limits.low).count();
+            let impact = (slowest.as_secs_f64() / limits.low.as_secs_f64().max(f64::EPSILON) - 1.0) * 100.0;
+            bottlenecks.push(PerformanceBottleneck {
+                component: operation.to_string(),
+                severity,
+                impact,
+                description: format!(
+                    "'{}' took {:.2?} (limit {:.2?}); {} of {} runs were slow",
+                    operation,
+                    slowest,
+                    limits.low,
+                    slow_runs,
+                    durations.len()
+                ),
+                recommendations: operation_suggestions(operation),
+            });
+        }
+
+        bottlenecks.sort_by(|a, b| {
+            b.severity
+                .cmp(&a.severity)
+                .then(b.impact.total_cmp(&a.impact))
+                .then_with(|| a.component.cmp(&b.component))
+        });
+        bottlenecks
+    }
+
     /// Update real-time metrics
     pub fn update_metrics(&self, metrics: PerformanceMetrics) -> anyhow::Result<()> {
         {
@@ -501,6 +634,31 @@ impl PerformanceMonitor {
     }
 }
 
+/// What to try when `operation` is slow
+fn operation_suggestions(operation: &str) -> Vec<String> {
+    let suggestions: &[&str] = match operation {
+        "status" | "add" | "diff" => &[
+            "Add build output and dependency directories to .runeignore",
+            "Feed a filesystem watcher into `rune watch --journal`",
+            "Check for very large untracked files in the working tree",
+        ],
+        "commit" | "checkout" | "merge" | "rebase" => &[
+            "Run `rune optimize` to compact and deduplicate objects",
+            "Track large binaries with LFS instead of as regular blobs",
+        ],
+        "log" | "blame" | "show" => &[
+            "Limit the range with --since or a path filter",
+            "Run `rune optimize` to compact the history",
+        ],
+        "push" | "pull" | "fetch" | "clone" => &[
+            "Check network latency to the remote",
+            "Track large binaries with LFS so they transfer on demand",
+        ],
+        _ => &["Profile the operation to find where the time goes"],
+    };
+    suggestions.iter().map(|s| s.to_string()).collect()
+}
+
 /// Performance report structure
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PerformanceReport {
@@ -610,6 +768,90 @@ impl Default for PerformanceThresholds {
             min_cache_hit_ratio: 70.0,
             max_disk_io_latency: Duration::from_millis(100),
             min_throughput: 100.0,
+            operation_latency: [
+                ("status", LatencyThresholds::new(300, 1_000, 2_000, 10_000)),
+                ("add", LatencyThresholds::new(500, 2_000, 5_000, 20_000)),
+                ("diff", LatencyThresholds::new(500, 2_000, 5_000, 20_000)),
+                ("log", LatencyThresholds::new(500, 2_000, 5_000, 20_000)),
+                ("commit", LatencyThresholds::new(1_000, 3_000, 10_000, 30_000)),
+                ("checkout", LatencyThresholds::new(1_000, 5_000, 15_000, 60_000)),
+                ("merge", LatencyThresholds::new(1_000, 5_000, 15_000, 60_000)),
+                ("push", LatencyThresholds::new(5_000, 15_000, 60_000, 300_000)),
+                ("pull", LatencyThresholds::new(5_000, 15_000, 60_000, 300_000)),
+                ("clone", LatencyThresholds::new(30_000, 120_000, 600_000, 1_800_000)),
+            ]
+            .into_iter()
+            .map(|(op, limits)| (op.to_string(), limits))
+            .collect(),
+        }
+    }
+}
+
+impl PerformanceThresholds {
+    /// Latency limits for `operation`
+    pub fn latency_for(&self, operation: &str) -> LatencyThresholds {
+        self.operation_latency
+            .get(operation)
+            .copied()
+            .unwrap_or_else(|| LatencyThresholds::scaled(self.max_operation_latency))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn test_analyze_flags_slow_operations() {
+        let monitor = PerformanceMonitor::new();
+        monitor.record_operation("status", ms(120), true).unwrap();
+        monitor.record_operation("status", ms(2_500), true).unwrap();
+        monitor.record_operation("commit", ms(800), true).unwrap();
+        monitor.record_operation("push", ms(400_000), false).unwrap();
+        monitor.record_operation("diff", ms(600), true).unwrap();
+        // Unknown operations fall back to max_operation_latency (1s)
+        monitor.record_operation("reindex", ms(2_500), true).unwrap();
+        monitor.update_metrics(PerformanceMetrics::default()).unwrap();
+
+        let found: Vec<(String, BottleneckSeverity)> =
+            monitor.analyze().into_iter().map(|b| (b.component, b.severity)).collect();
+        assert_eq!(
+            found,
+            [
+                ("push".to_string(), BottleneckSeverity::Critical),
+                ("status".to_string(), BottleneckSeverity::High),
+                ("reindex".to_string(), BottleneckSeverity::Medium),
+                ("diff".to_string(), BottleneckSeverity::Low),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_analyze_reports_slowest_run_once() {
+        let monitor = PerformanceMonitor::new();
+        for d in [100, 400, 1_500] {
+            monitor.record_operation("status", ms(d), true).unwrap();
         }
+        let bottlenecks = monitor.analyze();
+        assert_eq!(bottlenecks.len(), 1);
+        let status = &bottlenecks[0];
+        assert_eq!(status.severity, BottleneckSeverity::Medium);
+        assert!((status.impact - 400.0).abs() < 1e-6);
+        assert!(status.description.contains("2 of 3 runs"));
+        assert!(status.recommendations.iter().any(|r| r.contains(".runeignore")));
+        assert_eq!(
+            monitor.get_current_metrics().operation_latency.get("status"),
+            Some(&ms(1_500))
+        );
+
+        let mut thresholds = PerformanceThresholds::default();
+        thresholds.operation_latency.insert("status".into(), LatencyThresholds::scaled(ms(2_000)));
+        let lenient = PerformanceMonitor::with_thresholds(thresholds);
+        lenient.record_operation("status", ms(1_500), true).unwrap();
+        assert!(lenient.analyze().is_empty());
     }
 }
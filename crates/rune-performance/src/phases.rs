@@ -0,0 +1,345 @@
+//! Where a command's time goes, phase by phase
+//!
+//! Commands wrap their major steps in `PhaseRecorder::phase`, which times
+//! the closure and keeps any counters added while it runs. Phases opened
+//! inside another one nest under it. A disabled recorder runs the closure
+//! and nothing else: no clock, no lock, no strings.
+//!
+//! Finished runs are appended to a JSON-lines log so `rune benchmark
+//! report` can aggregate what users actually ran.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Runs the log keeps; older ones are dropped as new ones are appended
+const MAX_LOGGED_RUNS: usize = 1000;
+
+/// One timed phase
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Phase {
+    pub name: String,
+    /// How many phases it is nested in
+    pub depth: usize,
+    pub duration: Duration,
+    /// Counters added while it ran, in the order first added
+    pub counters: Vec<(String, u64)>,
+}
+
+#[derive(Debug, Default)]
+struct Recording {
+    phases: Vec<Phase>,
+    /// Indexes of the phases still running, innermost last
+    open: Vec<(usize, Instant)>,
+}
+
+/// Times the phases of one command
+#[derive(Debug, Default)]
+pub struct PhaseRecorder {
+    enabled: bool,
+    recording: Mutex<Recording>,
+    /// Strings formatted so far; stays 0 while disabled
+    formatted: AtomicUsize,
+}
+
+/// Closes its phase when dropped
+pub struct PhaseGuard<'a> {
+    recorder: Option<&'a PhaseRecorder>,
+}
+
+impl Drop for PhaseGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(recorder) = self.recorder {
+            recorder.close();
+        }
+    }
+}
+
+impl PhaseRecorder {
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled, ..Self::default() }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Run `f` as phase `name`
+    pub fn phase<T>(&self, name: &str, f: impl FnOnce() -> T) -> T {
+        let _guard = self.start(name);
+        f()
+    }
+
+    /// Open phase `name` until the guard is dropped, for steps that don't
+    /// fit in a closure
+    pub fn start(&self, name: &str) -> PhaseGuard<'_> {
+        if !self.enabled {
+            return PhaseGuard { recorder: None };
+        }
+        let mut recording = self.recording.lock().unwrap();
+        let depth = recording.open.len();
+        recording.phases.push(Phase { name: name.to_string(), depth, duration: Duration::ZERO, counters: Vec::new() });
+        let index = recording.phases.len() - 1;
+        recording.open.push((index, Instant::now()));
+        PhaseGuard { recorder: Some(self) }
+    }
+
+    fn close(&self) {
+        let mut recording = self.recording.lock().unwrap();
+        if let Some((index, started)) = recording.open.pop() {
+            recording.phases[index].duration = started.elapsed();
+        }
+    }
+
+    /// Add `n` to counter `name` of the innermost running phase
+    pub fn count(&self, name: &str, n: u64) {
+        if !self.enabled {
+            return;
+        }
+        let mut recording = self.recording.lock().unwrap();
+        let Some(&(index, _)) = recording.open.last() else {
+            return;
+        };
+        let counters = &mut recording.phases[index].counters;
+        match counters.iter_mut().find(|(counter, _)| counter == name) {
+            Some((_, total)) => *total += n,
+            None => counters.push((name.to_string(), n)),
+        }
+    }
+
+    /// Finished and running phases, in the order they started
+    pub fn phases(&self) -> Vec<Phase> {
+        self.recording.lock().unwrap().phases.clone()
+    }
+
+    /// How many strings the recorder has formatted
+    pub fn formatted(&self) -> usize {
+        self.formatted.load(Ordering::Relaxed)
+    }
+
+    /// Phases, durations and counters as a table, nested phases indented
+    pub fn table(&self) -> String {
+        let phases = self.phases();
+        let label = |phase: &Phase| format!("{}{}", "  ".repeat(phase.depth), phase.name);
+        let width = phases.iter().map(|p| label(p).chars().count()).max().unwrap_or(0).max("Phase".len());
+        let mut out = format!("{:<width$}  {:>10}  Counters\n", "Phase", "Time", width = width);
+        for phase in &phases {
+            let counters: Vec<String> = phase.counters.iter().map(|(name, n)| format!("{}={}", name, n)).collect();
+            let line = format!(
+                "{:<width$}  {:>10}  {}",
+                label(phase),
+                format!("{:.2?}", phase.duration),
+                counters.join(" "),
+                width = width
+            );
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+        self.formatted.fetch_add(phases.len() + 1, Ordering::Relaxed);
+        out
+    }
+
+    /// The recording as a run of the outermost phase's operation; `None`
+    /// when nothing was recorded
+    pub fn run(&self, success: bool) -> Option<RecordedRun> {
+        let phases = self.phases();
+        let operation = phases.first()?.name.clone();
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+        Some(RecordedRun { operation, timestamp, success, phases })
+    }
+}
+
+/// A command run with its phases, as kept in the run log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedRun {
+    pub operation: String,
+    pub timestamp: u64,
+    pub success: bool,
+    pub phases: Vec<Phase>,
+}
+
+impl RecordedRun {
+    /// Time spent in the outermost phases
+    pub fn duration(&self) -> Duration {
+        self.phases.iter().filter(|p| p.depth == 0).map(|p| p.duration).sum()
+    }
+}
+
+/// Append `run` to the log at `path`, keeping the newest `MAX_LOGGED_RUNS`
+pub fn append_run(path: &Path, run: &RecordedRun) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut runs = load_runs(path)?;
+    if runs.len() >= MAX_LOGGED_RUNS {
+        runs.drain(..=runs.len() - MAX_LOGGED_RUNS);
+        let mut content = String::new();
+        for kept in &runs {
+            content.push_str(&serde_json::to_string(kept)?);
+            content.push('\n');
+        }
+        fs::write(path, content)?;
+    }
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(run)?)?;
+    Ok(())
+}
+
+/// Runs in the log at `path`, oldest first; lines that don't parse are
+/// skipped
+pub fn load_runs(path: &Path) -> Result<Vec<RecordedRun>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(content.lines().filter_map(|line| serde_json::from_str(line).ok()).collect())
+}
+
+/// Average time of one operation and of each of its phases
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RunSummary {
+    pub operation: String,
+    pub runs: usize,
+    pub mean: Duration,
+    pub slowest: Duration,
+    /// Mean time per phase, by nested name (`outer/inner`), over the runs
+    /// that had it
+    pub phases: Vec<(String, Duration)>,
+}
+
+/// Per-operation averages over `runs`, by operation name
+pub fn summarize(runs: &[RecordedRun]) -> Vec<RunSummary> {
+    let mut by_operation: BTreeMap<&str, Vec<&RecordedRun>> = BTreeMap::new();
+    for run in runs {
+        by_operation.entry(run.operation.as_str()).or_default().push(run);
+    }
+    by_operation
+        .into_iter()
+        .map(|(operation, runs)| {
+            let durations: Vec<Duration> = runs.iter().map(|r| r.duration()).collect();
+            let mut phases: Vec<(String, Vec<Duration>)> = Vec::new();
+            for run in &runs {
+                let mut path: Vec<&str> = Vec::new();
+                for phase in &run.phases {
+                    path.truncate(phase.depth);
+                    path.push(&phase.name);
+                    // The outermost phase is the operation itself
+                    if path.len() == 1 {
+                        continue;
+                    }
+                    let name = path[1..].join("/");
+                    match phases.iter_mut().find(|(n, _)| *n == name) {
+                        Some((_, times)) => times.push(phase.duration),
+                        None => phases.push((name, vec![phase.duration])),
+                    }
+                }
+            }
+            RunSummary {
+                operation: operation.to_string(),
+                runs: runs.len(),
+                mean: mean(&durations),
+                slowest: durations.iter().max().copied().unwrap_or_default(),
+                phases: phases.into_iter().map(|(name, times)| (name, mean(&times))).collect(),
+            }
+        })
+        .collect()
+}
+
+fn mean(durations: &[Duration]) -> Duration {
+    match durations.len() {
+        0 => Duration::ZERO,
+        n => durations.iter().sum::<Duration>() / n as u32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn status_run(recorder: &PhaseRecorder) -> usize {
+        let _op = recorder.start("status");
+        recorder.phase("discover repo", || {});
+        recorder.phase("walk worktree", || {
+            recorder.count("files", 3);
+            recorder.phase("hash files", || recorder.count("bytes", 120));
+            recorder.count("files", 2);
+        });
+        recorder.count("untracked", 1);
+        7
+    }
+
+    #[test]
+    fn test_recorder_captures_nesting_and_counters() {
+        let recorder = PhaseRecorder::new(true);
+        assert_eq!(status_run(&recorder), 7);
+
+        type Shape = Vec<(String, usize, Vec<(String, u64)>)>;
+        let shape: Shape = recorder.phases().into_iter().map(|p| (p.name, p.depth, p.counters)).collect();
+        assert_eq!(
+            shape,
+            [
+                ("status".to_string(), 0, vec![("untracked".to_string(), 1)]),
+                ("discover repo".to_string(), 1, vec![]),
+                ("walk worktree".to_string(), 1, vec![("files".to_string(), 5)]),
+                ("hash files".to_string(), 2, vec![("bytes".to_string(), 120)]),
+            ]
+        );
+        let phases = recorder.phases();
+        assert!(phases[0].duration >= phases[2].duration);
+        assert!(phases[2].duration >= phases[3].duration);
+
+        let table = recorder.table();
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines[0].starts_with("Phase"));
+        assert!(lines[1].starts_with("status ") && lines[1].ends_with("untracked=1"));
+        assert!(lines[3].starts_with("  walk worktree ") && lines[3].ends_with("files=5"));
+        assert!(lines[4].starts_with("    hash files ") && lines[4].ends_with("bytes=120"));
+    }
+
+    #[test]
+    fn test_disabled_recorder_formats_nothing() {
+        let recorder = PhaseRecorder::new(false);
+        assert_eq!(status_run(&recorder), 7);
+        assert!(recorder.phases().is_empty());
+        assert!(recorder.run(true).is_none());
+        assert_eq!(recorder.formatted(), 0);
+
+        let enabled = PhaseRecorder::new(true);
+        status_run(&enabled);
+        enabled.table();
+        assert_eq!(enabled.formatted(), 5);
+    }
+
+    #[test]
+    fn test_logged_runs_are_summarized_per_operation() {
+        let temp = TempDir::new().unwrap();
+        let log = temp.path().join("perf/runs.jsonl");
+        let run = |ms: u64| RecordedRun {
+            operation: "status".into(),
+            timestamp: 0,
+            success: true,
+            phases: vec![
+                Phase { name: "status".into(), depth: 0, duration: Duration::from_millis(ms), counters: vec![] },
+                Phase { name: "walk".into(), depth: 1, duration: Duration::from_millis(ms / 2), counters: vec![] },
+            ],
+        };
+        append_run(&log, &run(100)).unwrap();
+        append_run(&log, &run(300)).unwrap();
+        let runs = load_runs(&log).unwrap();
+        assert_eq!(runs, [run(100), run(300)]);
+
+        let summary = summarize(&runs);
+        assert_eq!(summary.len(), 1);
+        assert_eq!((summary[0].runs, summary[0].mean), (2, Duration::from_millis(200)));
+        assert_eq!(summary[0].slowest, Duration::from_millis(300));
+        assert_eq!(summary[0].phases, [("walk".to_string(), Duration::from_millis(100))]);
+    }
+}
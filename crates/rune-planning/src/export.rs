@@ -0,0 +1,164 @@
+//! Plans and streams as JSON or CSV for spreadsheets and external trackers
+//!
+//! JSON keeps the nested structure. CSV has one row per task, repeating the
+//! plan columns; a plan without tasks still gets one row with the task
+//! columns empty, so filtered plans never disappear from the sheet.
+
+use crate::{Plan, Stream};
+use anyhow::Result;
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ExportFormat { #[default] Json, Csv }
+
+const PLAN_COLUMNS: [&str; 10] = ["plan_id", "plan_title", "plan_status", "task_index", "description", "type", "effort", "done", "path", "tags"];
+const STREAM_COLUMNS: [&str; 6] = ["stream_id", "title", "tags", "plans", "created", "updated"];
+
+pub fn export_plans(plans: &[Plan], format: ExportFormat) -> Result<String> {
+    match format {
+        ExportFormat::Json => Ok(serde_json::to_string_pretty(plans)? + "\n"),
+        ExportFormat::Csv => {
+            let mut rows = vec![PLAN_COLUMNS.iter().map(|c| c.to_string()).collect::<Vec<_>>()];
+            for p in plans {
+                let plan = [p.id.clone(), p.title.clone(), p.status.as_str().to_string()];
+                if p.tasks.is_empty() {
+                    rows.push(plan.iter().cloned().chain(std::iter::repeat_n(String::new(), 7)).collect());
+                }
+                for (i, t) in p.tasks.iter().enumerate() {
+                    let task = [
+                        (i + 1).to_string(),
+                        t.description.clone(),
+                        t.task_type.clone().unwrap_or_default(),
+                        t.effort.clone().unwrap_or_default(),
+                        t.done.to_string(),
+                        t.path.clone().unwrap_or_default(),
+                        t.tags.join(";"),
+                    ];
+                    rows.push(plan.iter().cloned().chain(task).collect());
+                }
+            }
+            Ok(to_csv(&rows))
+        }
+    }
+}
+
+pub fn export_streams(streams: &[Stream], format: ExportFormat) -> Result<String> {
+    match format {
+        ExportFormat::Json => Ok(serde_json::to_string_pretty(streams)? + "\n"),
+        ExportFormat::Csv => {
+            let mut rows = vec![STREAM_COLUMNS.iter().map(|c| c.to_string()).collect::<Vec<_>>()];
+            for s in streams {
+                rows.push(vec![
+                    s.id.clone(),
+                    s.title.clone(),
+                    s.tags.join(";"),
+                    s.plans.join(";"),
+                    s.created.to_rfc3339(),
+                    s.updated.to_rfc3339(),
+                ]);
+            }
+            Ok(to_csv(&rows))
+        }
+    }
+}
+
+/// RFC 4180: fields holding a comma, quote or line break are quoted, with
+/// quotes doubled; records end in CRLF
+fn to_csv(rows: &[Vec<String>]) -> String {
+    let mut out = String::new();
+    for row in rows {
+        let fields: Vec<String> = row.iter().map(|f| csv_field(f)).collect();
+        out.push_str(&fields.join(","));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{create_plan, filter_plans, parse_plan_query, update_status, PlanStatus, PlanStore, Task};
+    use tempfile::TempDir;
+
+    /// Minimal RFC 4180 reader, enough to check what `to_csv` wrote
+    fn parse_csv(text: &str) -> Vec<Vec<String>> {
+        let (mut rows, mut row, mut field) = (Vec::new(), Vec::new(), String::new());
+        let mut chars = text.chars().peekable();
+        let mut quoted = false;
+        while let Some(c) = chars.next() {
+            match (quoted, c) {
+                (true, '"') if chars.peek() == Some(&'"') => { chars.next(); field.push('"'); }
+                (true, '"') => quoted = false,
+                (true, c) => field.push(c),
+                (false, '"') => quoted = true,
+                (false, ',') => row.push(std::mem::take(&mut field)),
+                (false, '\r') => {}
+                (false, '\n') => { row.push(std::mem::take(&mut field)); rows.push(std::mem::take(&mut row)); }
+                (false, c) => field.push(c),
+            }
+        }
+        rows
+    }
+
+    #[test]
+    fn csv_escapes_commas_quotes_and_newlines() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+
+        let tmp = TempDir::new().unwrap();
+        let store = PlanStore::new(tmp.path());
+        let p = create_plan(&store, "Tricky, \"quoted\"", None).unwrap();
+        let awkward = "Fix parser, then \"ship\" it\nsecond line";
+        // Plan files keep one line per task, so this one only exists in memory
+        let mut plan = store.load(&p.id).unwrap();
+        plan.tasks.push(Task { description: awkward.into(), done: false, task_type: Some("bug".into()), effort: Some("2d".into()), path: None, tags: vec!["a".into(), "b".into()] });
+
+        let rows = parse_csv(&export_plans(&[plan], ExportFormat::Csv).unwrap());
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0], PLAN_COLUMNS);
+        assert_eq!(rows[2][1], "Tricky, \"quoted\"");
+        assert_eq!(rows[2][3..], ["2", awkward, "bug", "2d", "false", "", "a;b"]);
+    }
+
+    #[test]
+    fn export_respects_query() {
+        let tmp = TempDir::new().unwrap();
+        let store = PlanStore::new(tmp.path());
+        let backend = create_plan(&store, "API", Some("backend")).unwrap();
+        let frontend = create_plan(&store, "UI", Some("frontend")).unwrap();
+        create_plan(&store, "Later", Some("backend")).unwrap();
+        update_status(&store, &backend.id, PlanStatus::Active).unwrap();
+        update_status(&store, &frontend.id, PlanStatus::Active).unwrap();
+
+        let plans = filter_plans(&store.load_all().unwrap(), &parse_plan_query("status=active tag=backend"));
+        let rows = parse_csv(&export_plans(&plans, ExportFormat::Csv).unwrap());
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1][..5], [backend.id.as_str(), "API", "active", "1", "First task"]);
+
+        let json: serde_json::Value = serde_json::from_str(&export_plans(&plans, ExportFormat::Json).unwrap()).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 1);
+        assert_eq!(json[0]["id"], backend.id.as_str());
+        assert_eq!(json[0]["tasks"][0]["description"], "First task");
+    }
+
+    #[test]
+    fn plan_without_tasks_keeps_a_row() {
+        let tmp = TempDir::new().unwrap();
+        let store = PlanStore::new(tmp.path());
+        let mut plan = create_plan(&store, "Empty", None).unwrap();
+        plan.tasks.clear();
+        let rows = parse_csv(&export_plans(&[plan], ExportFormat::Csv).unwrap());
+        assert_eq!(rows[1].len(), PLAN_COLUMNS.len());
+        assert!(rows[1][3..].iter().all(|f| f.is_empty()));
+    }
+}
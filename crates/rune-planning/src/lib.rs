@@ -2,12 +2,20 @@ use anyhow::{Result, Context};
 use chrono::{DateTime, Utc};
 use serde::{Serialize, Deserialize};
 use clap::ValueEnum;
-use std::{fs, path::PathBuf};
+use std::{collections::BTreeMap, fs, path::{Path, PathBuf}};
 use std::io::Write;
 
-pub const PLAN_DIR: &str = ".rune/plans";
-pub const CONFIG_FILE: &str = ".rune/planning.toml";
-pub const STREAM_DIR: &str = ".rune/streams";
+pub mod export;
+pub mod webhook;
+pub use export::{export_plans, export_streams, ExportFormat};
+
+// Locations inside the repository's metadata directory (`.rune` by default)
+pub const PLAN_DIR: &str = "plans";
+pub const CONFIG_FILE: &str = "planning.toml";
+pub const STREAM_DIR: &str = "streams";
+pub const SIGNAL_DIR: &str = "index";
+/// Cached plan insights, inside `SIGNAL_DIR`
+pub const INSIGHT_CACHE_FILE: &str = "insights.json";
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ValueEnum)]
 pub enum PlanStatus { Planned, Active, InProgress, Blocked, Done }
@@ -39,6 +47,9 @@ pub struct Plan {
     pub tasks: Vec<Task>,
     pub roots: Vec<String>,
     pub description: String,
+    /// Commits recorded against the plan through their `Plan:` trailer
+    #[serde(default)]
+    pub commits: Vec<String>,
 }
 
 impl Plan {
@@ -56,7 +67,8 @@ impl Plan {
             format!("- [{}] {}{}", if t.done {"x"} else {" "}, t.description, meta)
         }).collect::<Vec<_>>().join("\n");
     let roots = if self.roots.is_empty() { String::new() } else { self.roots.join(",") };
-    format!("id: {id}\ntitle: {title}\nstatus: {status}\nrelease: {release}\nowners: {owners}\ntags: {tags}\nroots: {roots}\ncreated: {created}\nupdated: {updated}\n\n# Description\n\n{desc}\n\n## Goals\n{goals}\n\n## Tasks\n{tasks}\n", id=self.id, title=self.title, status=self.status, release=self.release.clone().unwrap_or_default(), owners=owners, tags=tags, roots=roots, created=self.created.format("%Y-%m-%d"), updated=self.updated.format("%Y-%m-%d"), desc=self.description, goals=goals_md, tasks=tasks_md)
+    let commits_md = if self.commits.is_empty() { String::new() } else { format!("\n## Commits\n{}\n", self.commits.iter().map(|c| format!("- {c}")).collect::<Vec<_>>().join("\n")) };
+    format!("id: {id}\ntitle: {title}\nstatus: {status}\nrelease: {release}\nowners: {owners}\ntags: {tags}\nroots: {roots}\ncreated: {created}\nupdated: {updated}\n\n# Description\n\n{desc}\n\n## Goals\n{goals}\n\n## Tasks\n{tasks}\n{commits}", id=self.id, title=self.title, status=self.status, release=self.release.clone().unwrap_or_default(), owners=owners, tags=tags, roots=roots, created=self.created.format("%Y-%m-%d"), updated=self.updated.to_rfc3339(), desc=self.description, goals=goals_md, tasks=tasks_md, commits=commits_md)
     }
 
     pub fn parse_markdown(md: &str) -> Result<Self> {
@@ -74,6 +86,7 @@ impl Plan {
         let mut tasks: Vec<Task> = Vec::new();
         let mut section = "";
         let mut roots: Vec<String> = Vec::new();
+        let mut commits: Vec<String> = Vec::new();
         for line in md.lines() {
             if line.starts_with("id:") { id = line[3..].trim().to_string(); }
             else if line.starts_with("title:") { title = line[6..].trim().to_string(); }
@@ -83,10 +96,11 @@ impl Plan {
             else if line.starts_with("tags:") { tags = line[5..].trim().split(',').filter(|s| !s.is_empty()).map(|s| s.trim().to_string()).collect(); }
             else if line.starts_with("roots:") { roots = line[6..].trim().split(',').filter(|s| !s.is_empty()).map(|s| s.trim().to_string()).collect(); }
             else if line.starts_with("created:") { let d = line[8..].trim(); created = Some(parse_date(d)?); }
-            else if line.starts_with("updated:") { let d = line[8..].trim(); updated = Some(parse_date(d)?); }
+            else if line.starts_with("updated:") { let d = line[8..].trim(); updated = Some(parse_timestamp(d)?); }
             else if line.starts_with("# Description") { section = "description"; in_description = true; }
             else if line.starts_with("## Goals") { section = "goals"; in_description = false; }
             else if line.starts_with("## Tasks") { section = "tasks"; in_description = false; }
+            else if line.starts_with("## Commits") { section = "commits"; in_description = false; }
             else {
                 match section {
                     "description" => { if in_description { description_lines.push(line.to_string()); } },
@@ -97,11 +111,12 @@ impl Plan {
                         let mut task_type=None; let mut effort=None; let mut path=None; let mut ttags=Vec::new();
                         if let Some(meta) = meta_part { for token in meta.split_whitespace() { if let Some((k,v)) = token.split_once(':') { match k { "type"=>task_type=Some(v.to_string()), "effort"=>effort=Some(v.to_string()), "path"=>path=Some(v.to_string()), "tags"=>{ ttags = v.split('|').filter(|s| !s.is_empty()).map(|s| s.to_string()).collect(); }, _=>{} } } } }
                         tasks.push(Task { description: desc_part.to_string(), done, task_type, effort, path, tags: ttags }); } },
+                    "commits" => { if let Some(c) = line.trim_start().strip_prefix('-') { if !c.trim().is_empty() { commits.push(c.trim().to_string()); } } },
                     _ => {}
                 }
             }
         }
-        Ok(Self { id, title, status, release, owners, tags, created: created.unwrap_or_else(Utc::now), updated: updated.unwrap_or_else(Utc::now), goals, tasks, roots, description: description_lines.join("\n").trim().to_string() })
+        Ok(Self { id, title, status, release, owners, tags, created: created.unwrap_or_else(Utc::now), updated: updated.unwrap_or_else(Utc::now), goals, tasks, roots, description: description_lines.join("\n").trim().to_string(), commits })
     }
 }
 
@@ -110,10 +125,16 @@ fn parse_date(d: &str) -> Result<DateTime<Utc>> {
     Ok(DateTime::from_naive_utc_and_offset(naive, Utc))
 }
 
-pub struct PlanStore { root: PathBuf }
+/// `updated:` keeps the full time, so cached insights notice edits made on
+/// the same day; plans written before that have a bare date
+fn parse_timestamp(d: &str) -> Result<DateTime<Utc>> {
+    match DateTime::parse_from_rfc3339(d) { Ok(t) => Ok(t.with_timezone(&Utc)), Err(_) => parse_date(d) }
+}
+
+pub struct PlanStore { rune_dir: PathBuf }
 impl PlanStore {
-    pub fn new(root: impl Into<PathBuf>) -> Self { Self { root: root.into() } }
-    fn dir(&self) -> PathBuf { self.root.join(PLAN_DIR) }
+    pub fn new(rune_dir: impl Into<PathBuf>) -> Self { Self { rune_dir: rune_dir.into() } }
+    pub fn dir(&self) -> PathBuf { self.rune_dir.join(PLAN_DIR) }
     pub fn ensure(&self) -> Result<()> { if !self.dir().exists() { fs::create_dir_all(self.dir())?; } Ok(()) }
     pub fn next_id(&self) -> Result<String> { self.ensure()?; let mut max = 0u32; for e in fs::read_dir(self.dir())? { let e = e?; if let Some(name) = e.file_name().to_str() { if let Some(rest) = name.strip_prefix("PLAN-") { if let Some(num) = rest.strip_suffix(".md") { if let Ok(n) = num.parse::<u32>() { if n>max { max=n; } } } } } } Ok(format!("PLAN-{:03}", max+1)) }
     pub fn load_all(&self) -> Result<Vec<Plan>> { self.ensure()?; let mut v = Vec::new(); if self.dir().exists() { for e in fs::read_dir(self.dir())? { let e = e?; if e.path().extension().and_then(|s| s.to_str()) == Some("md") { let text = fs::read_to_string(e.path())?; if let Ok(p) = Plan::parse_markdown(&text) { v.push(p); } } } } v.sort_by(|a,b| a.id.cmp(&b.id)); Ok(v) }
@@ -125,12 +146,12 @@ impl PlanStore {
 pub fn create_plan(store: &PlanStore, title: &str, tags: Option<&str>) -> Result<Plan> {
     let id = store.next_id()?;
     let now = Utc::now();
-    let p = Plan { id: id.clone(), title: title.to_string(), status: PlanStatus::Planned, release: None, owners: vec![], tags: tags.unwrap_or("").split(',').filter(|s| !s.is_empty()).map(|s| s.trim().to_string()).collect(), created: now, updated: now, goals: vec![], tasks: vec![Task { description: "First task".into(), done: false, task_type: None, effort: None, path: None, tags: vec![] }], roots: vec![], description: "(Add details here)".into() };
+    let p = Plan { id: id.clone(), title: title.to_string(), status: PlanStatus::Planned, release: None, owners: vec![], tags: tags.unwrap_or("").split(',').filter(|s| !s.is_empty()).map(|s| s.trim().to_string()).collect(), created: now, updated: now, goals: vec![], tasks: vec![Task { description: "First task".into(), done: false, task_type: None, effort: None, path: None, tags: vec![] }], roots: vec![], description: "(Add details here)".into(), commits: vec![] };
     store.save(&p)?; Ok(p)
 }
 
-fn log_signal(root: &PathBuf, kind: &str, kv: &[(&str, &str)]) -> Result<()> {
-    let dir = root.join(".rune/index");
+fn log_signal(rune_dir: &PathBuf, kind: &str, kv: &[(&str, &str)]) -> Result<()> {
+    let dir = rune_dir.join(SIGNAL_DIR);
     fs::create_dir_all(&dir)?;
     let path = dir.join("signals.log");
     let ts = Utc::now().to_rfc3339();
@@ -174,18 +195,18 @@ impl Stream {
     }
 }
 
-pub struct StreamStore { root: PathBuf }
+pub struct StreamStore { rune_dir: PathBuf }
 impl StreamStore {
-    pub fn new(root: impl Into<PathBuf>) -> Self { Self { root: root.into() } }
-    fn dir(&self) -> PathBuf { self.root.join(STREAM_DIR) }
+    pub fn new(rune_dir: impl Into<PathBuf>) -> Self { Self { rune_dir: rune_dir.into() } }
+    fn dir(&self) -> PathBuf { self.rune_dir.join(STREAM_DIR) }
     fn ensure(&self) -> Result<()> { if !self.dir().exists() { fs::create_dir_all(self.dir())?; } Ok(()) }
     fn next_id(&self) -> Result<String> { self.ensure()?; let mut max=0u32; for e in fs::read_dir(self.dir())? { let e=e?; if let Some(name)=e.file_name().to_str() { if let Some(rest)=name.strip_prefix("STREAM-") { if let Some(num)=rest.strip_suffix(".md") { if let Ok(n)=num.parse::<u32>() { if n>max { max=n; } } } } } } Ok(format!("STREAM-{:03}", max+1)) }
-    pub fn create(&self, title:&str, tags: Option<&str>) -> Result<Stream> { let id=self.next_id()?; let now=Utc::now(); let s=Stream { id: id.clone(), title: title.to_string(), tags: tags.unwrap_or("").split(',').filter(|s| !s.is_empty()).map(|s| s.trim().to_string()).collect(), plans: vec![], created: now, updated: now, description: "(Add details)".into() }; self.save(&s)?; log_signal(&self.root, "stream_created", &[ ("stream", &id) ])?; Ok(s) }
+    pub fn create(&self, title:&str, tags: Option<&str>) -> Result<Stream> { let id=self.next_id()?; let now=Utc::now(); let s=Stream { id: id.clone(), title: title.to_string(), tags: tags.unwrap_or("").split(',').filter(|s| !s.is_empty()).map(|s| s.trim().to_string()).collect(), plans: vec![], created: now, updated: now, description: "(Add details)".into() }; self.save(&s)?; log_signal(&self.rune_dir, "stream_created", &[ ("stream", &id) ])?; Ok(s) }
     pub fn path_for(&self, id:&str) -> PathBuf { self.dir().join(format!("{id}.md")) }
     pub fn save(&self, s:&Stream) -> Result<()> { self.ensure()?; fs::write(self.path_for(&s.id), s.to_markdown())?; Ok(()) }
     pub fn load(&self, id:&str) -> Result<Stream> { let text=fs::read_to_string(self.path_for(id))?; Stream::parse(&text) }
     pub fn list(&self) -> Result<Vec<Stream>> { self.ensure()?; let mut v=Vec::new(); for e in fs::read_dir(self.dir())? { let e=e?; if e.path().extension().and_then(|s| s.to_str())==Some("md") { if let Ok(st)=Stream::parse(&fs::read_to_string(e.path())?) { v.push(st); } } } v.sort_by(|a,b| a.id.cmp(&b.id)); Ok(v) }
-    pub fn attach(&self, stream_id:&str, plan_id:&str) -> Result<()> { let mut s=self.load(stream_id)?; if !s.plans.contains(&plan_id.to_string()) { s.plans.push(plan_id.to_string()); s.updated=Utc::now(); self.save(&s)?; log_signal(&self.root, "stream_attach", &[ ("stream", stream_id), ("plan", plan_id) ])?; } Ok(()) }
+    pub fn attach(&self, stream_id:&str, plan_id:&str) -> Result<()> { let mut s=self.load(stream_id)?; if !s.plans.contains(&plan_id.to_string()) { s.plans.push(plan_id.to_string()); s.updated=Utc::now(); self.save(&s)?; log_signal(&self.rune_dir, "stream_attach", &[ ("stream", stream_id), ("plan", plan_id) ])?; } Ok(()) }
 }
 
 // --- Query / Slice filtering ---
@@ -227,6 +248,7 @@ pub fn filter_plans(plans: &[Plan], query:&PlanQuery) -> Vec<Plan> {
 }
 
 // ---- Insights (lightweight heuristic, AI-ready stub) ----
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PlanInsight { pub plan_id: String, pub messages: Vec<String> }
 
 pub fn generate_plan_insights(plan: &Plan) -> PlanInsight {
@@ -247,28 +269,70 @@ pub fn generate_plan_insights(plan: &Plan) -> PlanInsight {
 
 pub struct WorkspaceInsights { pub plan_insights: Vec<PlanInsight>, pub summary: Vec<String> }
 
+/// Insights for every plan, all computed afresh; `InsightCache` reuses them
 pub fn generate_workspace_insights(plans: &[Plan]) -> WorkspaceInsights {
-    let mut plan_insights = Vec::new();
-    for p in plans { plan_insights.push(generate_plan_insights(p)); }
-    // Aggregate
+    InsightCache::default().workspace_insights(plans)
+}
+
+fn workspace_summary(plans: &[Plan]) -> Vec<String> {
     let total_plans = plans.len();
     let active = plans.iter().filter(|p| matches!(p.status, PlanStatus::Active|PlanStatus::InProgress)).count();
     let blocked = plans.iter().filter(|p| matches!(p.status, PlanStatus::Blocked)).count();
     let mut summary = vec![format!("Plans: {} (active {}, blocked {})", total_plans, active, blocked)];
     let avg_completion: f32 = if total_plans>0 { plans.iter().map(|p| if p.tasks.is_empty(){0.0}else{ p.tasks.iter().filter(|t| t.done).count() as f32 / p.tasks.len() as f32 }).sum::<f32>() / total_plans as f32 } else {0.0};
     summary.push(format!("Avg task completion {:.0}%", avg_completion*100.0));
-    WorkspaceInsights { plan_insights, summary }
+    summary
+}
+
+/// Plan insights from earlier runs, reused for as long as a plan's
+/// `updated` stays the same. The workspace summary is cheap and always
+/// recomputed over all plans.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct InsightCache {
+    entries: BTreeMap<String, CachedInsight>,
+    /// Plan insights computed rather than reused since the cache was loaded
+    #[serde(skip)] pub computed: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedInsight { updated: DateTime<Utc>, insight: PlanInsight }
+
+impl InsightCache {
+    /// The cache saved under `rune_dir`; empty when there is none or it can't be read
+    pub fn load(rune_dir: &Path) -> Self {
+        fs::read_to_string(rune_dir.join(SIGNAL_DIR).join(INSIGHT_CACHE_FILE)).ok().and_then(|data| serde_json::from_str(&data).ok()).unwrap_or_default()
+    }
+    pub fn save(&self, rune_dir: &Path) -> Result<()> {
+        let dir = rune_dir.join(SIGNAL_DIR);
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join(INSIGHT_CACHE_FILE), serde_json::to_string(self)?)?;
+        Ok(())
+    }
+    pub fn plan_insight(&mut self, plan: &Plan) -> PlanInsight {
+        if let Some(cached) = self.entries.get(&plan.id).filter(|c| c.updated == plan.updated) { return cached.insight.clone(); }
+        self.computed += 1;
+        let insight = generate_plan_insights(plan);
+        self.entries.insert(plan.id.clone(), CachedInsight { updated: plan.updated, insight: insight.clone() });
+        insight
+    }
+    /// Insights for `plans`, computing only those changed since they were
+    /// cached; plans no longer present are dropped from the cache
+    pub fn workspace_insights(&mut self, plans: &[Plan]) -> WorkspaceInsights {
+        self.entries.retain(|id, _| plans.iter().any(|p| p.id == *id));
+        let plan_insights = plans.iter().map(|p| self.plan_insight(p)).collect();
+        WorkspaceInsights { plan_insights, summary: workspace_summary(plans) }
+    }
 }
 
-pub fn update_status(store: &PlanStore, id: &str, status: PlanStatus) -> Result<()> { let mut p = store.load(id)?; p.status = status; p.updated = Utc::now(); store.save(&p)?; log_signal(&store.root, "status_change", &[ ("plan", &p.id), ("status", p.status.as_str()) ])?; Ok(()) }
-pub fn add_task(store: &PlanStore, id: &str, desc: &str) -> Result<()> { let mut p = store.load(id)?; p.tasks.push(Task { description: desc.into(), done: false, task_type: None, effort: None, path: None, tags: vec![] }); p.updated = Utc::now(); store.save(&p)?; log_signal(&store.root, "task_added", &[ ("plan", &p.id), ("count", &p.tasks.len().to_string()) ])?; Ok(()) }
+pub fn update_status(store: &PlanStore, id: &str, status: PlanStatus) -> Result<()> { let mut p = store.load(id)?; p.status = status; p.updated = Utc::now(); store.save(&p)?; log_signal(&store.rune_dir, "status_change", &[ ("plan", &p.id), ("status", p.status.as_str()) ])?; webhook::enqueue(&store.rune_dir, webhook::WebhookEvent::status_change(&p)) }
+pub fn add_task(store: &PlanStore, id: &str, desc: &str) -> Result<()> { let mut p = store.load(id)?; p.tasks.push(Task { description: desc.into(), done: false, task_type: None, effort: None, path: None, tags: vec![] }); p.updated = Utc::now(); store.save(&p)?; log_signal(&store.rune_dir, "task_added", &[ ("plan", &p.id), ("count", &p.tasks.len().to_string()) ])?; Ok(()) }
 pub fn add_task_with_meta(store: &PlanStore, id: &str, desc: &str, task_type: Option<&str>, effort: Option<&str>, path: Option<&str>, tags: Option<&str>) -> Result<()> {
     let mut p = store.load(id)?;
     let tag_list = tags.unwrap_or("").split(',').filter(|s| !s.is_empty()).map(|s| s.trim().to_string()).collect();
     p.tasks.push(Task { description: desc.into(), done: false, task_type: task_type.map(|s| s.to_string()), effort: effort.map(|s| s.to_string()), path: path.map(|s| s.to_string()), tags: tag_list });
     p.updated = Utc::now();
     store.save(&p)?;
-    log_signal(&store.root, "task_added", &[ ("plan", &p.id), ("count", &p.tasks.len().to_string()) ])?;
+    log_signal(&store.rune_dir, "task_added", &[ ("plan", &p.id), ("count", &p.tasks.len().to_string()) ])?;
     Ok(())
 }
 pub fn update_roots(store: &PlanStore, id: &str, roots: &str) -> Result<()> {
@@ -276,7 +340,7 @@ pub fn update_roots(store: &PlanStore, id: &str, roots: &str) -> Result<()> {
     p.roots = roots.split(',').filter(|s| !s.is_empty()).map(|s| s.trim().to_string()).collect();
     p.updated = Utc::now();
     store.save(&p)?;
-    log_signal(&store.root, "roots_set", &[ ("plan", &p.id), ("roots_count", &p.roots.len().to_string()) ])?;
+    log_signal(&store.rune_dir, "roots_set", &[ ("plan", &p.id), ("roots_count", &p.roots.len().to_string()) ])?;
     Ok(())
 }
 pub fn mark_task_done(store: &PlanStore, id: &str, index_one_based: usize) -> Result<bool> {
@@ -287,32 +351,47 @@ pub fn mark_task_done(store: &PlanStore, id: &str, index_one_based: usize) -> Re
         let all_done = !p.tasks.is_empty() && p.tasks.iter().all(|t| t.done);
         if all_done { p.status = PlanStatus::Done; }
         store.save(&p)?;
-        log_signal(&store.root, "task_done", &[ ("plan", &p.id), ("task_index", &index_one_based.to_string()), ("all_done", &all_done.to_string()) ])?;
+        log_signal(&store.rune_dir, "task_done", &[ ("plan", &p.id), ("task_index", &index_one_based.to_string()), ("all_done", &all_done.to_string()) ])?;
+        webhook::enqueue(&store.rune_dir, webhook::WebhookEvent::task_done(&p, index_one_based))?;
         return Ok(true);
     }
     Ok(false)
 }
 
+/// Record `commit_id` as implementing plan `id`. Returns false when the
+/// commit was already recorded.
+pub fn link_commit(store: &PlanStore, id: &str, commit_id: &str) -> Result<bool> {
+    let mut p = store.load(id)?;
+    if p.commits.iter().any(|c| c == commit_id) { return Ok(false); }
+    p.commits.push(commit_id.to_string());
+    p.updated = Utc::now();
+    store.save(&p)?;
+    log_signal(&store.rune_dir, "commit_linked", &[ ("plan", &p.id), ("commit", commit_id) ])?;
+    Ok(true)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlanningConfig {
     #[serde(default = "default_auto_complete")] pub auto_complete_on_all_tasks_done: bool,
     #[serde(default)] pub archive_done_after_days: Option<u32>,
     #[serde(default)] pub board_default_status_filters: Option<Vec<String>>,
+    /// Where plan events are POSTed (see `webhook`)
+    #[serde(default)] pub webhook_url: Option<String>,
 }
 
 fn default_auto_complete() -> bool { true }
 
-impl Default for PlanningConfig { fn default() -> Self { Self { auto_complete_on_all_tasks_done: true, archive_done_after_days: None, board_default_status_filters: None } } }
+impl Default for PlanningConfig { fn default() -> Self { Self { auto_complete_on_all_tasks_done: true, archive_done_after_days: None, board_default_status_filters: None, webhook_url: None } } }
 
 impl PlanningConfig {
-    pub fn load(root: &PathBuf) -> Result<Self> {
-        let path = root.join(CONFIG_FILE);
+    pub fn load(rune_dir: &PathBuf) -> Result<Self> {
+        let path = rune_dir.join(CONFIG_FILE);
         if !path.exists() { return Ok(Self::default()); }
         let data = fs::read_to_string(path)?;
         Ok(toml::from_str(&data).unwrap_or_default())
     }
-    pub fn save(&self, root: &PathBuf) -> Result<()> {
-        let path = root.join(CONFIG_FILE);
+    pub fn save(&self, rune_dir: &PathBuf) -> Result<()> {
+        let path = rune_dir.join(CONFIG_FILE);
         if let Some(parent) = path.parent() { fs::create_dir_all(parent)?; }
         fs::write(path, toml::to_string_pretty(self)?)?;
         Ok(())
@@ -364,4 +443,44 @@ mod tests {
         assert_eq!(after.status, PlanStatus::Done);
         Ok(())
     }
+
+    #[test]
+    fn link_commit_round_trips_and_dedupes() -> Result<()> {
+        let tmp = TempDir::new().unwrap();
+        let store = PlanStore::new(tmp.path());
+        let p = create_plan(&store, "Linked", None)?;
+        assert!(!store.load(&p.id)?.to_markdown().contains("## Commits"));
+        assert!(link_commit(&store, &p.id, "abc123")?);
+        assert!(!link_commit(&store, &p.id, "abc123")?);
+        assert!(link_commit(&store, &p.id, "def456")?);
+        let loaded = store.load(&p.id)?;
+        assert_eq!(loaded.commits, ["abc123", "def456"]);
+        assert_eq!(loaded.tasks.len(), 1);
+        assert!(link_commit(&store, "PLAN-999", "abc123").is_err());
+        let signals = fs::read_to_string(tmp.path().join(SIGNAL_DIR).join("signals.log"))?;
+        assert!(signals.contains("kind=commit_linked plan=PLAN-001 commit=def456"));
+        Ok(())
+    }
+
+    #[test]
+    fn insights_recompute_only_modified_plans() -> Result<()> {
+        let tmp = TempDir::new().unwrap();
+        let store = PlanStore::new(tmp.path());
+        for title in ["One", "Two", "Three"] { create_plan(&store, title, None)?; }
+        let mut cache = InsightCache::default();
+        let first = cache.workspace_insights(&store.load_all()?);
+        assert_eq!(cache.computed, 3);
+        cache.save(tmp.path())?;
+
+        // A later run reuses the saved insights of plans not edited since
+        mark_task_done(&store, "PLAN-002", 1)?;
+        let mut cache = InsightCache::load(tmp.path());
+        let again = cache.workspace_insights(&store.load_all()?);
+        assert_eq!(cache.computed, 1);
+        assert_eq!(again.plan_insights[0], first.plan_insights[0]);
+        assert_eq!(again.plan_insights[2], first.plan_insights[2]);
+        assert!(again.plan_insights[1].messages[0].starts_with("Progress: 1/1"));
+        assert_eq!(again.summary, ["Plans: 3 (active 0, blocked 0)", "Avg task completion 33%"]);
+        Ok(())
+    }
 }
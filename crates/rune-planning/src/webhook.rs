@@ -0,0 +1,261 @@
+//! Plan events mirrored to an external tracker
+//!
+//! With `webhook_url` set in `planning.toml`, status changes and completed
+//! tasks are queued as JSON files under `.rune/outbox`. `deliver_outbox`
+//! POSTs them oldest first and removes each one the server accepts; the
+//! first failure stops the run and leaves the rest queued, in order, for the
+//! next delivery. Only one delivery runs at a time.
+
+use crate::{Plan, PlanningConfig};
+use anyhow::{bail, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::{fs, path::{Path, PathBuf}, time::Duration};
+
+pub const OUTBOX_DIR: &str = "outbox";
+
+/// Held in the outbox by the delivery in progress
+const DELIVERY_LOCK: &str = "deliver.lock";
+
+/// Body of each webhook POST
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebhookEvent {
+    /// `status_change` or `task_done`
+    pub event: String,
+    pub plan_id: String,
+    pub plan_title: String,
+    /// Plan status after the change
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task: Option<TaskRef>,
+    /// RFC 3339
+    pub time: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskRef {
+    /// 1-based, as in `rune plan done`
+    pub index: usize,
+    pub description: String,
+}
+
+impl WebhookEvent {
+    pub fn status_change(plan: &Plan) -> Self {
+        Self { event: "status_change".into(), plan_id: plan.id.clone(), plan_title: plan.title.clone(), status: plan.status.as_str().into(), task: None, time: Utc::now().to_rfc3339() }
+    }
+    pub fn task_done(plan: &Plan, index_one_based: usize) -> Self {
+        let task = TaskRef { index: index_one_based, description: plan.tasks[index_one_based - 1].description.clone() };
+        Self { event: "task_done".into(), task: Some(task), ..Self::status_change(plan) }
+    }
+}
+
+/// A queued event and how its deliveries went so far
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    pub event: WebhookEvent,
+    #[serde(default)]
+    pub attempts: u32,
+    #[serde(default)]
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DeliveryReport { pub delivered: usize, pub pending: usize, pub error: Option<String> }
+
+fn outbox_dir(rune_dir: &Path) -> PathBuf { rune_dir.join(OUTBOX_DIR) }
+
+/// Queued entries, oldest first
+pub fn outbox(rune_dir: &Path) -> Result<Vec<(PathBuf, OutboxEntry)>> {
+    let dir = outbox_dir(rune_dir);
+    if !dir.exists() { return Ok(Vec::new()); }
+    let mut paths: Vec<PathBuf> = fs::read_dir(&dir)?.filter_map(|e| e.ok().map(|e| e.path())).filter(|p| p.extension().is_some_and(|e| e == "json")).collect();
+    paths.sort();
+    let mut entries = Vec::new();
+    for path in paths {
+        let entry = serde_json::from_str(&fs::read_to_string(&path)?)?;
+        entries.push((path, entry));
+    }
+    Ok(entries)
+}
+
+/// Queue `event` if a webhook is configured
+pub fn enqueue(rune_dir: &Path, event: WebhookEvent) -> Result<()> {
+    if PlanningConfig::load(&rune_dir.to_path_buf())?.webhook_url.is_none() { return Ok(()); }
+    let dir = outbox_dir(rune_dir);
+    fs::create_dir_all(&dir)?;
+    // Nanosecond names sort in queue order; bump past a name already taken
+    let mut stamp = Utc::now().timestamp_nanos_opt().unwrap_or_default();
+    while dir.join(format!("{stamp:020}.json")).exists() { stamp += 1; }
+    let entry = OutboxEntry { event, attempts: 0, last_error: None };
+    fs::write(dir.join(format!("{stamp:020}.json")), serde_json::to_vec_pretty(&entry)?)?;
+    Ok(())
+}
+
+/// POST queued events to the configured webhook, oldest first. While
+/// another delivery is running nothing is sent and everything is reported
+/// pending, so overlapping runs never send an event twice.
+pub async fn deliver_outbox(rune_dir: &Path) -> Result<DeliveryReport> {
+    let dir = outbox_dir(rune_dir);
+    if !dir.exists() { return Ok(DeliveryReport::default()); }
+    let lock = fs::OpenOptions::new().create(true).truncate(false).write(true).open(dir.join(DELIVERY_LOCK))?;
+    match lock.try_lock() {
+        Ok(()) => {}
+        Err(fs::TryLockError::WouldBlock) => return Ok(DeliveryReport { pending: outbox(rune_dir)?.len(), ..Default::default() }),
+        Err(fs::TryLockError::Error(e)) => return Err(e.into()),
+    }
+    let entries = outbox(rune_dir)?;
+    let mut report = DeliveryReport { pending: entries.len(), ..Default::default() };
+    if entries.is_empty() { return Ok(report); }
+    let Some(url) = PlanningConfig::load(&rune_dir.to_path_buf())?.webhook_url else { return Ok(report); };
+    let client = reqwest::Client::builder().timeout(Duration::from_secs(10)).build()?;
+    for (path, mut entry) in entries {
+        let error = match client.post(&url).json(&entry.event).send().await {
+            Ok(resp) if resp.status().is_success() => None,
+            Ok(resp) => Some(format!("{url} answered {}", resp.status())),
+            Err(e) => Some(format!("{url}: {e}")),
+        };
+        if let Some(error) = error {
+            entry.attempts += 1;
+            entry.last_error = Some(error.clone());
+            fs::write(&path, serde_json::to_vec_pretty(&entry)?)?;
+            report.error = Some(error);
+            break;
+        }
+        fs::remove_file(&path)?;
+        report.delivered += 1;
+        report.pending -= 1;
+    }
+    Ok(report)
+}
+
+/// Set or clear `webhook_url` in `planning.toml`
+pub fn set_webhook(rune_dir: &Path, url: Option<&str>) -> Result<()> {
+    if let Some(url) = url {
+        if !(url.starts_with("http://") || url.starts_with("https://")) { bail!("webhook URL must start with http:// or https://, got '{url}'"); }
+    }
+    let rune_dir = rune_dir.to_path_buf();
+    let mut cfg = PlanningConfig::load(&rune_dir)?;
+    cfg.webhook_url = url.map(str::to_string);
+    cfg.save(&rune_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{create_plan, mark_task_done, update_status, PlanStatus, PlanStore};
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use tempfile::TempDir;
+
+    /// HTTP server on a free port answering each request with the next
+    /// status in `statuses`; request bodies are sent to the returned channel
+    fn serve(statuses: Vec<u16>) -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            for status in statuses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() { break; }
+                    if let Some(v) = line.to_ascii_lowercase().strip_prefix("content-length:") { length = v.trim().parse().unwrap(); }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                tx.send(String::from_utf8(body).unwrap()).unwrap();
+                write!(stream, "HTTP/1.1 {status} X\r\ncontent-length: 0\r\nconnection: close\r\n\r\n").unwrap();
+            }
+        });
+        (url, rx)
+    }
+
+    #[test]
+    fn events_are_only_queued_with_a_webhook() {
+        let tmp = TempDir::new().unwrap();
+        let store = PlanStore::new(tmp.path());
+        let p = create_plan(&store, "Quiet", None).unwrap();
+        update_status(&store, &p.id, PlanStatus::Active).unwrap();
+        assert!(outbox(tmp.path()).unwrap().is_empty());
+
+        set_webhook(tmp.path(), Some("http://127.0.0.1:9/hook")).unwrap();
+        mark_task_done(&store, &p.id, 1).unwrap();
+        let queued = outbox(tmp.path()).unwrap();
+        assert_eq!(queued.len(), 1);
+        assert_eq!(queued[0].1.event.event, "task_done");
+        assert!(set_webhook(tmp.path(), Some("ftp://example.com")).is_err());
+    }
+
+    #[tokio::test]
+    async fn payload_shape() {
+        let tmp = TempDir::new().unwrap();
+        let (url, bodies) = serve(vec![200, 200]);
+        set_webhook(tmp.path(), Some(&url)).unwrap();
+        let store = PlanStore::new(tmp.path());
+        let p = create_plan(&store, "Ship it", None).unwrap();
+        update_status(&store, &p.id, PlanStatus::InProgress).unwrap();
+        mark_task_done(&store, &p.id, 1).unwrap();
+
+        let report = deliver_outbox(tmp.path()).await.unwrap();
+        assert_eq!(report, DeliveryReport { delivered: 2, pending: 0, error: None });
+
+        let status: serde_json::Value = serde_json::from_str(&bodies.recv().unwrap()).unwrap();
+        assert_eq!(status["event"], "status_change");
+        assert_eq!(status["plan_id"], p.id.as_str());
+        assert_eq!(status["plan_title"], "Ship it");
+        assert_eq!(status["status"], "in-progress");
+        assert!(status.get("task").is_none());
+        assert!(chrono::DateTime::parse_from_rfc3339(status["time"].as_str().unwrap()).is_ok());
+
+        let done: serde_json::Value = serde_json::from_str(&bodies.recv().unwrap()).unwrap();
+        assert_eq!(done["event"], "task_done");
+        assert_eq!(done["status"], "done");
+        assert_eq!(done["task"], serde_json::json!({ "index": 1, "description": "First task" }));
+    }
+
+    #[tokio::test]
+    async fn one_delivery_at_a_time() {
+        let tmp = TempDir::new().unwrap();
+        set_webhook(tmp.path(), Some("http://127.0.0.1:9/hook")).unwrap();
+        let store = PlanStore::new(tmp.path());
+        let p = create_plan(&store, "Busy", None).unwrap();
+        update_status(&store, &p.id, PlanStatus::Active).unwrap();
+
+        let held = fs::File::create(tmp.path().join(OUTBOX_DIR).join(DELIVERY_LOCK)).unwrap();
+        held.lock().unwrap();
+        let report = deliver_outbox(tmp.path()).await.unwrap();
+        assert_eq!(report, DeliveryReport { delivered: 0, pending: 1, error: None });
+        assert_eq!(outbox(tmp.path()).unwrap()[0].1.attempts, 0);
+    }
+
+    #[tokio::test]
+    async fn failed_delivery_is_retried_later() {
+        let tmp = TempDir::new().unwrap();
+        let (url, bodies) = serve(vec![503, 200, 200]);
+        set_webhook(tmp.path(), Some(&url)).unwrap();
+        let store = PlanStore::new(tmp.path());
+        let p = create_plan(&store, "Flaky", None).unwrap();
+        update_status(&store, &p.id, PlanStatus::Active).unwrap();
+        update_status(&store, &p.id, PlanStatus::Blocked).unwrap();
+
+        let report = deliver_outbox(tmp.path()).await.unwrap();
+        assert_eq!((report.delivered, report.pending), (0, 2));
+        assert!(report.error.unwrap().contains("503"));
+        let queued = outbox(tmp.path()).unwrap();
+        assert_eq!(queued[0].1.attempts, 1);
+        assert_eq!(queued[1].1.attempts, 0);
+
+        let report = deliver_outbox(tmp.path()).await.unwrap();
+        assert_eq!((report.delivered, report.pending), (2, 0));
+        assert!(outbox(tmp.path()).unwrap().is_empty());
+        let sent: Vec<String> = bodies.try_iter().collect();
+        assert_eq!(sent.len(), 3);
+        // The rejected event went again, ahead of the one queued after it
+        assert_eq!(sent[0], sent[1]);
+        assert!(sent[2].contains("\"blocked\""));
+    }
+}
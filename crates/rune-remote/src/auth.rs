@@ -47,7 +47,12 @@ impl AuthService {
         user_id: String,
         permissions: Vec<Permission>,
     ) -> Result<String> {
-        let token = format!("rune_{}_{}", user_id, chrono::Utc::now().timestamp());
+        let token = format!(
+            "rune_{}_{}_{}",
+            user_id,
+            chrono::Utc::now().timestamp(),
+            uuid::Uuid::new_v4().simple()
+        );
         let api_token = ApiToken {
             token: token.clone(),
             user_id,
@@ -1,4 +1,7 @@
 use anyhow::{Context, Result};
+use crate::sync::{PushRequest, RepositoryInfo, SyncResponse};
+use crate::transfer::{HasItem, SyncHasRequest};
+use rune_security::pinning::{self, CertPin};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
@@ -14,6 +17,12 @@ pub struct RemoteConfig {
     pub push_url: Option<String>,
     pub fetch_refs: Vec<String>,
     pub push_refs: Vec<String>,
+    /// Fingerprints (`sha256:<hex>`) of the certificates the server may present
+    #[serde(default)]
+    pub pinned_certs: Vec<String>,
+    /// Prune stale tracking refs on fetch; unset falls back to `fetch.prune`
+    #[serde(default)]
+    pub prune: Option<bool>,
 }
 
 impl Default for RemoteConfig {
@@ -26,10 +35,61 @@ impl Default for RemoteConfig {
             push_url: None,
             fetch_refs: vec!["+refs/heads/*:refs/remotes/origin/*".to_string()],
             push_refs: vec!["refs/heads/*:refs/heads/*".to_string()],
+            pinned_certs: Vec::new(),
+            prune: None,
         }
     }
 }
 
+impl RemoteConfig {
+    /// HTTP client for this remote that refuses a server not matching its pins
+    pub fn http_client(&self) -> Result<reqwest::Client> {
+        pinning::http_client(&CertPin::parse_all(&self.pinned_certs)?)
+    }
+}
+
+/// A remote URL split into the server and, for a shrine hosting several
+/// repositories, the repository: `https://host/myproject` is repository
+/// `myproject` on `https://host`. Endpoints are addressed relative to both.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteUrl {
+    pub server: String,
+    pub repo: Option<String>,
+}
+
+impl RemoteUrl {
+    pub fn parse(url: &str) -> Result<Self> {
+        let url = url.trim().trim_end_matches('/');
+        let (scheme, rest) = url
+            .split_once("://")
+            .with_context(|| format!("Invalid remote URL '{}': expected http(s)://host[/repository]", url))?;
+        let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+        if host.is_empty() {
+            anyhow::bail!("Invalid remote URL '{}': missing host", url);
+        }
+        let path = path.trim_matches('/');
+        Ok(Self {
+            server: format!("{}://{}", scheme, host),
+            repo: (!path.is_empty()).then(|| path.to_string()),
+        })
+    }
+
+    /// `endpoint("sync/push")` is `<server>/<repo>/sync/push`
+    pub fn endpoint(&self, path: &str) -> String {
+        let path = path.trim_start_matches('/');
+        match &self.repo {
+            Some(repo) => format!("{}/{}/{}", self.server, repo, path),
+            None => format!("{}/{}", self.server, path),
+        }
+    }
+
+    /// Directory name for a clone: the last path segment without `.git`
+    pub fn repo_name(&self) -> Option<&str> {
+        let last = self.repo.as_deref()?.rsplit('/').next()?;
+        Some(last.strip_suffix(".git").unwrap_or(last))
+    }
+}
+
 /// Remote configuration manager
 #[derive(Debug)]
 pub struct RemoteManager {
@@ -160,6 +220,31 @@ impl RemoteManager {
         }
     }
     
+    /// Replace the certificate pins of a remote; an empty list removes pinning
+    pub fn set_remote_pins(&mut self, name: &str, pins: Vec<String>) -> Result<()> {
+        CertPin::parse_all(&pins)?;
+        match self.remotes.get_mut(name) {
+            Some(remote) => {
+                remote.pinned_certs = pins;
+                self.save_config()?;
+                Ok(())
+            }
+            None => anyhow::bail!("Remote '{}' does not exist", name),
+        }
+    }
+    
+    /// Set or, with `None`, unset whether fetches from a remote prune
+    pub fn set_remote_prune(&mut self, name: &str, prune: Option<bool>) -> Result<()> {
+        match self.remotes.get_mut(name) {
+            Some(remote) => {
+                remote.prune = prune;
+                self.save_config()?;
+                Ok(())
+            }
+            None => anyhow::bail!("Remote '{}' does not exist", name),
+        }
+    }
+    
     /// Set default remote
     pub fn set_default_remote(&mut self, name: &str) -> Result<()> {
         if !self.remotes.contains_key(name) {
@@ -183,8 +268,8 @@ impl RemoteManager {
         let remote = self.get_remote(name)
             .ok_or_else(|| anyhow::anyhow!("Remote '{}' does not exist", name))?;
         
-        let client = reqwest::Client::new();
-        let mut request = client.get(&format!("{}/sync/info", remote.url));
+        let client = remote.http_client()?;
+        let mut request = client.get(RemoteUrl::parse(&remote.url)?.endpoint("sync/info"));
         
         if let Some(token) = &remote.token {
             request = request.header("Authorization", format!("Bearer {}", token));
@@ -199,7 +284,7 @@ impl RemoteManager {
     /// Clone from remote URL
     pub async fn clone_from_url(url: &str, local_path: &std::path::Path, token: Option<String>) -> Result<()> {
         let client = reqwest::Client::new();
-        let mut request = client.get(&format!("{}/repositories", url));
+        let mut request = client.get(RemoteUrl::parse(url)?.endpoint("sync/info"));
         
         if let Some(token) = &token {
             request = request.header("Authorization", format!("Bearer {}", token));
@@ -208,9 +293,7 @@ impl RemoteManager {
         let response = request.send().await
             .context("Failed to connect to remote server")?;
         
-        if !response.status().is_success() {
-            anyhow::bail!("Failed to access remote repository: {}", response.status());
-        }
+        response.error_for_status().context("Failed to access remote repository")?;
         
         // Create local repository
         std::fs::create_dir_all(local_path)
@@ -224,6 +307,97 @@ impl RemoteManager {
     }
 }
 
+/// The network side of a push or fetch. `HttpSyncClient` talks to a Rune server;
+/// anything else implementing it can stand in, e.g. in tests.
+#[allow(async_fn_in_trait)]
+pub trait SyncClient {
+    async fn push(&self, request: &PushRequest) -> Result<SyncResponse>;
+
+    /// Ids of the `items` the server does not hold intact. A client that
+    /// can't ask reports them all, so nothing is skipped on trust.
+    async fn missing(&self, items: &[HasItem]) -> Result<Vec<String>> {
+        Ok(items.iter().map(|i| i.id.clone()).collect())
+    }
+
+    /// Names of the branches the remote has now, from `/sync/info`
+    async fn branches(&self) -> Result<Vec<String>> {
+        anyhow::bail!("This remote can't list its branches")
+    }
+
+    /// Bring the history of `branch` into `store` without moving any of
+    /// its refs; returns the branch's tip, `None` when the remote lacks it
+    async fn fetch(&self, _store: &rune_store::Store, _branch: &str) -> Result<Option<String>> {
+        anyhow::bail!("This remote can't send history yet")
+    }
+}
+
+/// Pushes to the `/sync/push` endpoint of a configured remote
+#[derive(Debug, Clone)]
+pub struct HttpSyncClient {
+    remote: RemoteConfig,
+}
+
+impl HttpSyncClient {
+    pub fn new(remote: RemoteConfig) -> Self {
+        Self { remote }
+    }
+
+    /// Send `body` to `endpoint` on the push URL and decode the reply
+    async fn post<T: serde::de::DeserializeOwned>(&self, endpoint: &str, body: &impl Serialize) -> Result<T> {
+        let url = self.remote.push_url.as_deref().unwrap_or(&self.remote.url);
+        let mut http = self
+            .remote
+            .http_client()?
+            .post(RemoteUrl::parse(url)?.endpoint(endpoint))
+            .json(body);
+        if let Some(token) = &self.remote.token {
+            http = http.header("Authorization", format!("Bearer {}", token));
+        }
+        let response = http
+            .send()
+            .await
+            .with_context(|| format!("Failed to connect to remote '{}'", self.remote.name))?;
+        let response = response
+            .error_for_status()
+            .with_context(|| format!("Push to '{}' failed", self.remote.name))?;
+        Ok(response.json().await?)
+    }
+
+    /// Fetch `endpoint` from the fetch URL and decode the reply
+    async fn get<T: serde::de::DeserializeOwned>(&self, endpoint: &str) -> Result<T> {
+        let mut http = self
+            .remote
+            .http_client()?
+            .get(RemoteUrl::parse(&self.remote.url)?.endpoint(endpoint));
+        if let Some(token) = &self.remote.token {
+            http = http.header("Authorization", format!("Bearer {}", token));
+        }
+        let response = http
+            .send()
+            .await
+            .with_context(|| format!("Failed to connect to remote '{}'", self.remote.name))?;
+        let response = response
+            .error_for_status()
+            .with_context(|| format!("Fetch from '{}' failed", self.remote.name))?;
+        Ok(response.json().await?)
+    }
+}
+
+impl SyncClient for HttpSyncClient {
+    async fn push(&self, request: &PushRequest) -> Result<SyncResponse> {
+        self.post("sync/push", request).await
+    }
+
+    async fn missing(&self, items: &[HasItem]) -> Result<Vec<String>> {
+        self.post("sync/has", &SyncHasRequest { items: items.to_vec() }).await
+    }
+
+    async fn branches(&self) -> Result<Vec<String>> {
+        let info: RepositoryInfo = self.get("sync/info").await?;
+        Ok(info.branches.into_iter().map(|b| b.name).collect())
+    }
+}
+
 /// Git-like remote commands
 pub struct RemoteCommands;
 
@@ -315,8 +489,8 @@ impl RemoteCommands {
             std::path::PathBuf::from(path)
         } else {
             // Extract repository name from URL
-            let name = url.split('/').last().unwrap_or("repository");
-            std::path::PathBuf::from(name)
+            let parsed = RemoteUrl::parse(url)?;
+            std::path::PathBuf::from(parsed.repo_name().unwrap_or("repository"))
         };
         
         println!("Cloning into '{}'...", local_path.display());
@@ -360,4 +534,37 @@ mod tests {
         
         Ok(())
     }
+    
+    #[test]
+    fn test_remote_url_with_repository_prefix() -> Result<()> {
+        let hosted = RemoteUrl::parse("https://shrine.example.com:7420/myproject/")?;
+        assert_eq!(hosted.server, "https://shrine.example.com:7420");
+        assert_eq!(hosted.repo.as_deref(), Some("myproject"));
+        assert_eq!(hosted.endpoint("/sync/push"), "https://shrine.example.com:7420/myproject/sync/push");
+
+        let single = RemoteUrl::parse("http://127.0.0.1:7420")?;
+        assert_eq!(single.repo, None);
+        assert_eq!(single.endpoint("lfs/has"), "http://127.0.0.1:7420/lfs/has");
+        assert_eq!(RemoteUrl::parse("https://git.example.com/team/repo.git")?.repo_name(), Some("repo"));
+        assert!(RemoteUrl::parse("shrine.example.com/myproject").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_remote_pins_are_validated_and_persisted() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let mut manager = RemoteManager::new(temp_dir.path())?;
+        manager.add_remote("origin", "https://git.example.com/repo.git", None)?;
+
+        assert!(manager.set_remote_pins("origin", vec!["sha256:1234".to_string()]).is_err());
+        let pin = CertPin::of_certificate(b"server certificate").to_string();
+        manager.set_remote_pins("origin", vec![pin.clone()])?;
+
+        let reloaded = RemoteManager::new(temp_dir.path())?;
+        let remote = reloaded.get_remote("origin").unwrap();
+        assert_eq!(remote.pinned_certs, vec![pin]);
+        assert!(remote.http_client().is_ok());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,308 @@
+//! One shrine daemon serving every repository under a directory
+//!
+//! Each subdirectory of `repos_dir` holding a `.rune` directory is a
+//! repository, reachable under `/<name>/`: `/<name>/sync/push`,
+//! `/<name>/lfs/upload` and so on, with the same endpoints as a
+//! single-repository shrine. The per-repository router is built on first
+//! use and cached. `GET /repos` lists the repositories and `POST /repos`
+//! creates one; both take a bearer token.
+
+use crate::auth::{AuthService, Permission};
+use crate::{repo_router, Shrine};
+use anyhow::Result;
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::net::TcpListener;
+use tower::Service;
+
+#[derive(Clone)]
+pub struct ShrineHost {
+    pub repos_dir: PathBuf,
+    auth: Arc<Mutex<AuthService>>,
+    repos: Arc<RwLock<HashMap<String, Router>>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RepoSummary {
+    pub name: String,
+    /// Branch HEAD points at, `None` when HEAD is detached
+    pub default_branch: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateRepoRequest {
+    pub name: String,
+}
+
+/// Repository names are single path segments: letters, digits, `.`, `_`
+/// and `-`, not starting with a dot
+pub fn is_valid_repo_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'_' | b'-'))
+}
+
+impl ShrineHost {
+    pub fn new(repos_dir: impl Into<PathBuf>, auth: AuthService) -> Self {
+        Self {
+            repos_dir: repos_dir.into(),
+            auth: Arc::new(Mutex::new(auth)),
+            repos: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route("/repos", get(list_repos).post(create_repo))
+            .fallback(route_to_repo)
+            .with_state(self.clone())
+    }
+
+    /// Router for repository `name`, or `None` when there is no such
+    /// repository
+    fn repo(&self, name: &str) -> Option<Router> {
+        if let Some(router) = self.repos.read().unwrap().get(name) {
+            return Some(router.clone());
+        }
+        let root = self.repos_dir.join(name);
+        if !is_valid_repo_name(name) || !root.join(".rune").is_dir() {
+            return None;
+        }
+        let router = repo_router().with_state(Shrine { root });
+        self.repos.write().unwrap().insert(name.to_string(), router.clone());
+        Some(router)
+    }
+
+    pub fn list(&self) -> Result<Vec<RepoSummary>> {
+        let mut repos = Vec::new();
+        for entry in fs::read_dir(&self.repos_dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !is_valid_repo_name(&name) || !entry.path().join(".rune").is_dir() {
+                continue;
+            }
+            let store = rune_store::Store::open(entry.path())?;
+            repos.push(RepoSummary { name, default_branch: store.current_branch() });
+        }
+        repos.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(repos)
+    }
+
+    /// Create an empty repository `name`
+    pub fn create(&self, name: &str) -> Result<RepoSummary> {
+        let root = self.repos_dir.join(name);
+        fs::create_dir_all(&root)?;
+        let store = rune_store::Store::open(&root)?;
+        store.create()?;
+        Ok(RepoSummary { name: name.to_string(), default_branch: store.current_branch() })
+    }
+
+    /// `Ok` when the request carries a token with `permission`; 401 without
+    /// a valid token, 403 when it lacks the permission
+    fn authorize(&self, headers: &HeaderMap, permission: Permission) -> Result<(), StatusCode> {
+        let token = headers
+            .get("Authorization")
+            .and_then(|header| header.to_str().ok())
+            .and_then(|header| header.strip_prefix("Bearer "))
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+        let auth = self.auth.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if auth.validate_token(token).is_none() {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        if !auth.has_permission(token, permission) {
+            return Err(StatusCode::FORBIDDEN);
+        }
+        Ok(())
+    }
+}
+
+pub async fn run_host(host: ShrineHost, addr: SocketAddr) -> Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    axum::serve::serve(listener, host.router().into_make_service()).await?;
+    Ok(())
+}
+
+async fn list_repos(State(host): State<ShrineHost>, headers: HeaderMap) -> Response {
+    if let Err(status) = host.authorize(&headers, Permission::Read) {
+        return status.into_response();
+    }
+    match host.list() {
+        Ok(repos) => Json(repos).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn create_repo(
+    State(host): State<ShrineHost>,
+    headers: HeaderMap,
+    Json(request): Json<CreateRepoRequest>,
+) -> Response {
+    if let Err(status) = host.authorize(&headers, Permission::Admin) {
+        return status.into_response();
+    }
+    if !is_valid_repo_name(&request.name) {
+        return (StatusCode::BAD_REQUEST, format!("Invalid repository name '{}'", request.name)).into_response();
+    }
+    if host.repos_dir.join(&request.name).exists() {
+        return (StatusCode::CONFLICT, format!("Repository '{}' already exists", request.name)).into_response();
+    }
+    match host.create(&request.name) {
+        Ok(repo) => (StatusCode::CREATED, Json(repo)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// Hand `/<name>/<rest>` to repository `name` as `/<rest>`
+async fn route_to_repo(State(host): State<ShrineHost>, mut request: Request) -> Response {
+    let path = request.uri().path().trim_start_matches('/').to_string();
+    let Some((name, rest)) = path.split_once('/') else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let Some(mut router) = host.repo(name) else {
+        return (StatusCode::NOT_FOUND, format!("No repository '{}'", name)).into_response();
+    };
+    let uri = match request.uri().query() {
+        Some(query) => format!("/{}?{}", rest, query),
+        None => format!("/{}", rest),
+    };
+    match uri.parse() {
+        Ok(uri) => *request.uri_mut() = uri,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    }
+    // A router is always ready, so it can be called without polling first
+    match router.call(request).await {
+        Ok(response) => response,
+        Err(never) => match never {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use tempfile::TempDir;
+
+    async fn send(host: &ShrineHost, method: &str, uri: &str, token: Option<&str>, body: serde_json::Value) -> (StatusCode, Vec<u8>) {
+        let mut request = Request::builder().method(method).uri(uri).header("Content-Type", "application/json");
+        if let Some(token) = token {
+            request = request.header("Authorization", format!("Bearer {}", token));
+        }
+        let request = request.body(Body::from(serde_json::to_vec(&body).unwrap())).unwrap();
+        let response = host.router().call(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, bytes.to_vec())
+    }
+
+    fn host_with(repos: &[&str]) -> (TempDir, ShrineHost) {
+        let temp = TempDir::new().unwrap();
+        for repo in repos {
+            let root = temp.path().join(repo);
+            fs::create_dir_all(&root).unwrap();
+            rune_store::Store::open(&root).unwrap().create().unwrap();
+        }
+        let host = ShrineHost::new(temp.path(), AuthService::new());
+        (temp, host)
+    }
+
+    fn push_body(hash: &str) -> serde_json::Value {
+        serde_json::json!({
+            "branch": "main",
+            "force": false,
+            "commits": [{
+                "hash": hash, "message": hash, "author": "dev", "timestamp": "2026-01-01T00:00:00Z",
+                "parent": null, "files": []
+            }]
+        })
+    }
+
+    #[tokio::test]
+    async fn test_repos_are_isolated() {
+        let (_temp, host) = host_with(&["alpha", "beta"]);
+        let oid = "ab".repeat(32);
+
+        for (repo, hash, data) in [("alpha", "c-alpha", vec![1u8]), ("beta", "c-beta", vec![2u8])] {
+            let (status, _) = send(&host, "POST", &format!("/{}/sync/push", repo), None, push_body(hash)).await;
+            assert_eq!(status, StatusCode::OK);
+            let upload = serde_json::json!({"oid": oid, "chunk": "pointer.json", "data": data});
+            let (status, _) = send(&host, "POST", &format!("/{}/lfs/upload", repo), None, upload).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+        let lock = serde_json::json!({"path": "art/hero.psd", "owner": "dev"});
+        send(&host, "POST", "/alpha/locks/lock", None, lock).await;
+
+        for (repo, hash, data, locks) in [("alpha", "c-alpha", vec![1u8], 1), ("beta", "c-beta", vec![2u8], 0)] {
+            let (_, body) = send(&host, "GET", &format!("/{}/sync/branches", repo), None, serde_json::json!(null)).await;
+            let branches: Vec<crate::Branch> = serde_json::from_slice(&body).unwrap();
+            assert_eq!(branches.iter().find(|b| b.name == "main").unwrap().head_commit, hash);
+
+            let download = serde_json::json!({"oid": oid, "chunk": "pointer.json"});
+            let (_, body) = send(&host, "POST", &format!("/{}/lfs/download", repo), None, download).await;
+            assert_eq!(serde_json::from_slice::<Vec<u8>>(&body).unwrap(), data);
+
+            let (_, body) = send(&host, "GET", &format!("/{}/locks/list", repo), None, serde_json::json!(null)).await;
+            assert_eq!(serde_json::from_slice::<Vec<serde_json::Value>>(&body).unwrap().len(), locks);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unknown_repo_is_not_found() {
+        let (temp, host) = host_with(&["alpha"]);
+        fs::create_dir_all(temp.path().join("plain-dir")).unwrap();
+        for uri in ["/gamma/sync/info", "/plain-dir/sync/info", "/../alpha/sync/info", "/alpha"] {
+            let (status, _) = send(&host, "GET", uri, None, serde_json::json!(null)).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{}", uri);
+        }
+        // Object names can't climb out of the repository either
+        let escape = serde_json::json!({"oid": "abcd", "chunk": "../../../../../alpha/.rune/HEAD", "data": [1]});
+        let (status, _) = send(&host, "POST", "/alpha/lfs/upload", None, escape).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_create_requires_admin() {
+        let (temp, host) = host_with(&["alpha"]);
+        let (read, admin) = {
+            let mut auth = host.auth.lock().unwrap();
+            (
+                auth.generate_token("reader".into(), vec![Permission::Read]).unwrap(),
+                auth.generate_token("admin".into(), vec![Permission::Admin]).unwrap(),
+            )
+        };
+        let body = serde_json::json!({"name": "gamma"});
+
+        assert_eq!(send(&host, "POST", "/repos", None, body.clone()).await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(send(&host, "POST", "/repos", Some(&read), body.clone()).await.0, StatusCode::FORBIDDEN);
+        assert!(!temp.path().join("gamma").exists());
+        assert_eq!(send(&host, "POST", "/repos", Some(&admin), body.clone()).await.0, StatusCode::CREATED);
+        assert!(temp.path().join("gamma/.rune/HEAD").exists());
+        assert_eq!(send(&host, "POST", "/repos", Some(&admin), body).await.0, StatusCode::CONFLICT);
+        let bad = serde_json::json!({"name": "../escape"});
+        assert_eq!(send(&host, "POST", "/repos", Some(&admin), bad).await.0, StatusCode::BAD_REQUEST);
+
+        assert_eq!(send(&host, "GET", "/repos", None, serde_json::json!(null)).await.0, StatusCode::UNAUTHORIZED);
+        let (status, body) = send(&host, "GET", "/repos", Some(&read), serde_json::json!(null)).await;
+        assert_eq!(status, StatusCode::OK);
+        let repos: Vec<RepoSummary> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            repos,
+            [
+                RepoSummary { name: "alpha".into(), default_branch: Some("main".into()) },
+                RepoSummary { name: "gamma".into(), default_branch: Some("main".into()) },
+            ]
+        );
+        // The new repository is served right away
+        assert_eq!(send(&host, "GET", "/gamma/sync/info", None, serde_json::json!(null)).await.0, StatusCode::OK);
+    }
+}
@@ -1,5 +1,6 @@
 use anyhow::Result;
 use axum::{
+    http::StatusCode,
     routing::{get, post},
     Json, Router,
 };
@@ -9,11 +10,19 @@ use tokio::net::TcpListener;
 
 pub mod auth;
 pub mod client;
+pub mod hosting;
+pub mod report;
 pub mod sync;
+pub mod transfer;
 
 pub use auth::{AuthService, Permission};
-pub use client::{RemoteCommands, RemoteConfig, RemoteManager};
-pub use sync::{Branch, Commit, FileChange, FileOperation};
+pub use client::{HttpSyncClient, RemoteCommands, RemoteConfig, RemoteManager, RemoteUrl, SyncClient};
+pub use hosting::{run_host, RepoSummary, ShrineHost};
+pub use report::{PullReport, PushReport, RefStatus, RefUpdate, SyncReport, TransferStats};
+pub use sync::{Branch, Commit, FileChange, FileOperation, PushRequest, SyncResponse};
+pub use transfer::{
+    abort_transfer, list_transfers, HasItem, SyncHasRequest, TransferDirection, TransferItem, TransferManifest,
+};
 
 #[derive(Clone)]
 pub struct Shrine {
@@ -42,7 +51,19 @@ pub struct LockReq {
 }
 
 pub async fn run_server(shrine: Shrine, addr: SocketAddr) -> Result<()> {
-    let app = Router::new()
+    serve(shrine, TcpListener::bind(addr).await?).await
+}
+
+/// Serve `shrine` on a listener that is already bound, e.g. to port 0
+pub async fn serve(shrine: Shrine, listener: TcpListener) -> Result<()> {
+    let app = repo_router().with_state(shrine);
+    axum::serve::serve(listener, app.into_make_service()).await?;
+    Ok(())
+}
+
+/// The endpoints of one repository, relative to its root
+pub(crate) fn repo_router() -> Router<Shrine> {
+    Router::new()
         // LFS endpoints
         .route("/lfs/upload", post(lfs_upload))
         .route("/lfs/download", post(lfs_download))
@@ -54,58 +75,78 @@ pub async fn run_server(shrine: Shrine, addr: SocketAddr) -> Result<()> {
         // Repository sync endpoints
         .route("/sync/info", get(sync::get_repository_info))
         .route("/sync/push", post(sync::push_commits))
+        .route("/sync/has", post(sync::has_commits))
         .route("/sync/pull", post(sync::pull_commits))
         .route("/sync/branches", get(sync::get_branches_endpoint))
         .route("/sync/commits/:since", get(sync::get_commits_since))
         .route("/sync/repository/:remote", post(sync::sync_repository))
-        .with_state(shrine);
-    let listener = TcpListener::bind(addr).await?;
-    axum::serve::serve(listener, app.into_make_service()).await?;
-    Ok(())
+}
+/// Where the shrine keeps `chunk` of object `oid`. Content-defined chunks are
+/// named by their hash and shared between objects; everything else lives
+/// with its object. Names that could leave the repository's LFS directory
+/// are refused.
+fn lfs_chunk_path(root: &std::path::Path, oid: &str, chunk: &str) -> Result<PathBuf, StatusCode> {
+    let plain = |name: &str| {
+        !name.is_empty()
+            && !name.starts_with('.')
+            && name.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'_' | b'-'))
+    };
+    if oid.len() < 4 || !plain(oid) || !plain(chunk) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let lfs = root.join(".rune/lfs");
+    if is_content_hash(chunk) {
+        return Ok(lfs.join("chunks").join(&chunk[0..2]).join(&chunk[2..4]).join(chunk));
+    }
+    Ok(lfs.join("objects").join(&oid[0..2]).join(&oid[2..4]).join(oid).join(chunk))
+}
+fn is_content_hash(chunk: &str) -> bool {
+    chunk.len() == 64 && chunk.bytes().all(|b| b.is_ascii_hexdigit())
 }
 async fn lfs_upload(
     axum::extract::State(s): axum::extract::State<Shrine>,
     Json(b): Json<LfsUpload>,
-) -> &'static str {
-    let dir = s
-        .root
-        .join(".rune/lfs/objects")
-        .join(&b.oid[0..2])
-        .join(&b.oid[2..4])
-        .join(&b.oid);
-    let _ = fs::create_dir_all(&dir);
-    let _ = fs::write(dir.join(&b.chunk), &b.data);
-    "ok"
+) -> Result<&'static str, StatusCode> {
+    let path = lfs_chunk_path(&s.root, &b.oid, &b.chunk)?;
+    // Through a temporary file, so an interrupted upload never leaves a
+    // truncated chunk under the real name; sending a chunk again just
+    // replaces it
+    let tmp = path.with_extension(format!("upload-{}", uuid::Uuid::new_v4().simple()));
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    if fs::write(&tmp, &b.data).and_then(|_| fs::rename(&tmp, &path)).is_err() {
+        let _ = fs::remove_file(&tmp);
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    Ok("ok")
 }
 async fn lfs_download(
     axum::extract::State(s): axum::extract::State<Shrine>,
     Json(b): Json<LfsDownloadReq>,
-) -> Json<Vec<u8>> {
-    let dir = s
-        .root
-        .join(".rune/lfs/objects")
-        .join(&b.oid[0..2])
-        .join(&b.oid[2..4])
-        .join(&b.oid);
-    let data = fs::read(dir.join(&b.chunk)).unwrap_or_default();
-    Json(data)
+) -> Result<Json<Vec<u8>>, StatusCode> {
+    let data = fs::read(lfs_chunk_path(&s.root, &b.oid, &b.chunk)?).unwrap_or_default();
+    Ok(Json(data))
 }
 async fn lfs_has(
     axum::extract::State(s): axum::extract::State<Shrine>,
     Json(req): Json<HasReq>,
-) -> Json<Vec<String>> {
-    let dir = s
-        .root
-        .join(".rune/lfs/objects")
-        .join(&req.oid[0..2])
-        .join(&req.oid[2..4])
-        .join(&req.oid);
-    let missing: Vec<String> = req
-        .chunks
-        .into_iter()
-        .filter(|c| !dir.join(c).exists())
-        .collect();
-    Json(missing)
+) -> Result<Json<Vec<String>>, StatusCode> {
+    let mut missing = Vec::new();
+    for chunk in req.chunks {
+        let path = lfs_chunk_path(&s.root, &req.oid, &chunk)?;
+        // Content-defined chunks are named by their hash, so a damaged copy
+        // can be told apart and is asked for again
+        let intact = match fs::read(&path) {
+            Ok(data) if is_content_hash(&chunk) => blake3::hash(&data).to_hex().as_str() == chunk,
+            Ok(_) => true,
+            Err(_) => false,
+        };
+        if !intact {
+            missing.push(chunk);
+        }
+    }
+    Ok(Json(missing))
 }
 async fn locks_list(
     axum::extract::State(s): axum::extract::State<Shrine>,
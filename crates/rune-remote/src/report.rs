@@ -0,0 +1,136 @@
+//! What a push or pull did, ref by ref
+//!
+//! A sync hands back one `RefUpdate` per ref it looked at instead of
+//! printing as it goes, so the CLI can render a table or JSON and tell a
+//! push where every ref went through from one where some were rejected.
+
+use serde::{Deserialize, Serialize};
+
+/// How one ref ended up
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RefStatus {
+    /// The other side didn't have the ref
+    New,
+    /// The ref moved to a descendant of where it was
+    FastForward,
+    /// The ref moved to a commit that doesn't contain the old one
+    Forced,
+    /// Both sides already agreed
+    UpToDate,
+    /// The ref was left where it was, for this reason
+    Rejected(String),
+}
+
+impl RefStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RefStatus::New => "new",
+            RefStatus::FastForward => "fast-forward",
+            RefStatus::Forced => "forced",
+            RefStatus::UpToDate => "up to date",
+            RefStatus::Rejected(_) => "rejected",
+        }
+    }
+
+    pub fn is_rejected(&self) -> bool {
+        matches!(self, RefStatus::Rejected(_))
+    }
+}
+
+/// One ref a push or pull looked at. `old_id` is `None` when the
+/// receiving side had no such ref.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RefUpdate {
+    pub local_ref: String,
+    pub remote_ref: String,
+    pub old_id: Option<String>,
+    pub new_id: String,
+    pub status: RefStatus,
+}
+
+impl RefUpdate {
+    /// `old..new` with short ids, or just the new id for a new ref
+    pub fn range(&self) -> String {
+        let short = |id: &str| id[..8.min(id.len())].to_string();
+        match &self.old_id {
+            Some(old) if *old != self.new_id => format!("{}..{}", short(old), short(&self.new_id)),
+            _ => short(&self.new_id),
+        }
+    }
+}
+
+/// What went over the wire
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferStats {
+    pub objects: usize,
+    pub bytes: u64,
+}
+
+impl TransferStats {
+    /// Count one more object of `bytes`
+    pub fn add(&mut self, bytes: u64) {
+        self.objects += 1;
+        self.bytes += bytes;
+    }
+}
+
+/// Every ref a sync with `remote` looked at, in the order it got to them
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncReport {
+    pub remote: String,
+    pub updates: Vec<RefUpdate>,
+    pub stats: TransferStats,
+}
+
+pub type PushReport = SyncReport;
+pub type PullReport = SyncReport;
+
+impl SyncReport {
+    pub fn new(remote: &str) -> Self {
+        Self { remote: remote.to_string(), ..Self::default() }
+    }
+
+    pub fn rejected(&self) -> impl Iterator<Item = &RefUpdate> {
+        self.updates.iter().filter(|u| u.status.is_rejected())
+    }
+
+    /// Whether every ref went through
+    pub fn is_complete(&self) -> bool {
+        self.rejected().next().is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(old_id: Option<&str>, status: RefStatus) -> RefUpdate {
+        RefUpdate {
+            local_ref: "refs/heads/main".into(),
+            remote_ref: "refs/heads/main".into(),
+            old_id: old_id.map(str::to_string),
+            new_id: "0123456789abcdef".into(),
+            status,
+        }
+    }
+
+    #[test]
+    fn test_report_json_and_rejections() {
+        let mut report = SyncReport::new("origin");
+        report.updates.push(update(None, RefStatus::New));
+        report.updates.push(update(Some("fedcba9876543210"), RefStatus::FastForward));
+        assert!(report.is_complete());
+        assert_eq!(report.updates[0].range(), "01234567");
+        assert_eq!(report.updates[1].range(), "fedcba98..01234567");
+
+        report.updates.push(update(Some("fedcba9876543210"), RefStatus::Rejected("protected".into())));
+        assert!(!report.is_complete());
+        assert_eq!(report.rejected().count(), 1);
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["updates"][1]["status"], "fast_forward");
+        assert_eq!(json["updates"][2]["status"]["rejected"], "protected");
+        assert_eq!(serde_json::from_value::<SyncReport>(json).unwrap(), report);
+    }
+}
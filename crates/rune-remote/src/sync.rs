@@ -1,3 +1,4 @@
+use crate::transfer::{commit_bytes, SyncHasRequest};
 use crate::Shrine;
 use anyhow::Result;
 use axum::{
@@ -102,6 +103,34 @@ pub async fn push_commits(
     }
 }
 
+/// The hashes among `items` the server lacks. A stored commit whose content
+/// doesn't match the digest, say one cut short by a dropped connection,
+/// counts as missing.
+pub async fn has_commits(State(shrine): State<Shrine>, Json(request): Json<SyncHasRequest>) -> Json<Vec<String>> {
+    let commits_dir = shrine.root.join(".rune/commits");
+    let missing = request
+        .items
+        .into_iter()
+        .filter(|item| stored_digest(&commits_dir, &item.id).as_deref() != Some(item.digest.as_str()))
+        .map(|item| item.id)
+        .collect();
+    Json(missing)
+}
+
+/// Digest of the commit stored under `hash`, if there is an intact one
+fn stored_digest(commits_dir: &std::path::Path, hash: &str) -> Option<String> {
+    if !is_commit_hash(hash) {
+        return None;
+    }
+    let commit: Commit = serde_json::from_slice(&fs::read(commits_dir.join(hash)).ok()?).ok()?;
+    Some(blake3::hash(&commit_bytes(&commit).ok()?).to_hex().to_string())
+}
+
+/// Commit hashes become file names, so only plain ones are accepted
+fn is_commit_hash(hash: &str) -> bool {
+    !hash.is_empty() && hash.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-'))
+}
+
 pub async fn pull_commits(
     State(shrine): State<Shrine>,
     Json(request): Json<PullRequest>,
@@ -158,16 +187,30 @@ async fn handle_push_commits(shrine: &Shrine, request: PushRequest) -> Result<Sy
     let mut processed = 0;
 
     for commit in &request.commits {
-        // Check for conflicts
+        if !is_commit_hash(&commit.hash) {
+            anyhow::bail!("invalid commit hash '{}'", commit.hash);
+        }
+        // A commit sent again after an interrupted push is accepted as is;
+        // only different content under a known hash is a conflict
         let commit_path = commits_dir.join(&commit.hash);
         if commit_path.exists() && !request.force {
-            conflicts.push(format!("Commit {} already exists", commit.hash));
-            continue;
+            let digest = blake3::hash(&commit_bytes(commit)?).to_hex().to_string();
+            match stored_digest(&commits_dir, &commit.hash) {
+                Some(stored) if stored == digest => continue,
+                Some(_) => {
+                    conflicts.push(format!("Commit {} already exists", commit.hash));
+                    continue;
+                }
+                // Damaged copy: replace it
+                None => {}
+            }
         }
 
         // Store commit
         let commit_json = serde_json::to_string_pretty(commit)?;
-        fs::write(commit_path, commit_json)?;
+        let tmp = commits_dir.join(format!(".{}.tmp", commit.hash));
+        fs::write(&tmp, commit_json)?;
+        fs::rename(tmp, commit_path)?;
         processed += 1;
     }
 
@@ -390,6 +433,62 @@ mod tests {
         assert_eq!(branch.head_commit, "abc123");
     }
 
+    fn commit(hash: &str, parent: Option<&str>) -> Commit {
+        Commit {
+            hash: hash.to_string(),
+            message: format!("commit {}", hash),
+            author: "Dev <dev@example.com>".to_string(),
+            timestamp: chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            parent: parent.map(str::to_string),
+            files: vec![],
+        }
+    }
+
+    fn has_item(commit: &Commit) -> crate::HasItem {
+        (&crate::TransferItem::for_commit(commit).unwrap()).into()
+    }
+
+    #[tokio::test]
+    async fn test_resent_commits_are_accepted_idempotently() {
+        let temp_dir = TempDir::new().unwrap();
+        let shrine = Shrine { root: temp_dir.path().to_path_buf() };
+        let commits = vec![commit("aaa1", None), commit("bbb2", Some("aaa1"))];
+        let push = |commits: Vec<Commit>| PushRequest { commits, branch: "main".into(), force: false };
+
+        let first = handle_push_commits(&shrine, push(commits[..1].to_vec())).await.unwrap();
+        assert_eq!(first.commits_processed, 1);
+        // The retry sends the first commit again along with the rest
+        let retry = handle_push_commits(&shrine, push(commits.clone())).await.unwrap();
+        assert!(retry.success, "{:?}", retry.conflicts);
+        assert_eq!(retry.commits_processed, 1);
+        let head = fs::read_to_string(temp_dir.path().join(".rune/refs/heads/main")).unwrap();
+        assert_eq!(head, "bbb2");
+
+        // Same hash, different content is still refused
+        let mut forged = commit("aaa1", None);
+        forged.message = "something else".into();
+        let refused = handle_push_commits(&shrine, push(vec![forged])).await.unwrap();
+        assert!(!refused.success);
+        assert!(handle_push_commits(&shrine, push(vec![commit("../x", None)])).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_has_reports_missing_and_damaged_commits() {
+        let temp_dir = TempDir::new().unwrap();
+        let shrine = Shrine { root: temp_dir.path().to_path_buf() };
+        let (a, b, c) = (commit("aaa1", None), commit("bbb2", Some("aaa1")), commit("ccc3", Some("bbb2")));
+        let request = PushRequest { commits: vec![a.clone(), b.clone()], branch: "main".into(), force: false };
+        handle_push_commits(&shrine, request).await.unwrap();
+        // A transfer cut short leaves half a file behind
+        let damaged = temp_dir.path().join(".rune/commits/bbb2");
+        let content = fs::read(&damaged).unwrap();
+        fs::write(&damaged, &content[..content.len() / 2]).unwrap();
+
+        let items = vec![has_item(&a), has_item(&b), has_item(&c)];
+        let Json(missing) = has_commits(State(shrine), Json(SyncHasRequest { items })).await;
+        assert_eq!(missing, ["bbb2", "ccc3"]);
+    }
+
     #[test]
     fn test_get_branches_empty_repo() {
         let temp_dir = TempDir::new().unwrap();
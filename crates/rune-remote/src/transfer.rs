@@ -0,0 +1,237 @@
+//! Resumable transfer state
+//!
+//! Before a push sends anything it writes a manifest to
+//! `.rune/transfers/<id>.json` listing every item it plans to send, with its
+//! size and a digest of its content. Items are marked done as the server
+//! accepts them, so a push that dies halfway picks up where it stopped: the
+//! manifest says what was sent, and `/sync/has` confirms the server really
+//! holds it before it is skipped. The manifest goes away when the transfer
+//! finishes or is aborted.
+
+use crate::sync::Commit;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferDirection {
+    Push,
+    Pull,
+}
+
+impl std::fmt::Display for TransferDirection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            TransferDirection::Push => "push",
+            TransferDirection::Pull => "pull",
+        })
+    }
+}
+
+/// One thing to send, identified by the commit hash
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferItem {
+    pub id: String,
+    pub size: u64,
+    /// blake3 of the item as sent, which the server checks its copy against
+    pub digest: String,
+    #[serde(default)]
+    pub done: bool,
+}
+
+impl TransferItem {
+    pub fn for_commit(commit: &Commit) -> Result<Self> {
+        let bytes = commit_bytes(commit)?;
+        Ok(Self {
+            id: commit.hash.clone(),
+            size: bytes.len() as u64,
+            digest: blake3::hash(&bytes).to_hex().to_string(),
+            done: false,
+        })
+    }
+}
+
+/// The canonical form of a commit that `TransferItem::digest` is taken over
+pub fn commit_bytes(commit: &Commit) -> Result<Vec<u8>> {
+    Ok(serde_json::to_vec(commit)?)
+}
+
+/// Asks the server which of the listed items it lacks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncHasRequest {
+    pub items: Vec<HasItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HasItem {
+    pub id: String,
+    pub digest: String,
+}
+
+impl From<&TransferItem> for HasItem {
+    fn from(item: &TransferItem) -> Self {
+        Self { id: item.id.clone(), digest: item.digest.clone() }
+    }
+}
+
+/// What a transfer set out to do and how far it got
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferManifest {
+    pub id: String,
+    pub direction: TransferDirection,
+    pub remote: String,
+    pub branch: String,
+    /// The commit the branch should end up at
+    pub target: String,
+    pub started_at: i64,
+    pub items: Vec<TransferItem>,
+}
+
+impl TransferManifest {
+    pub fn new(direction: TransferDirection, remote: &str, branch: &str, target: &str, items: Vec<TransferItem>) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().simple().to_string()[..12].to_string(),
+            direction,
+            remote: remote.to_string(),
+            branch: branch.to_string(),
+            target: target.to_string(),
+            started_at: chrono::Utc::now().timestamp(),
+            items,
+        }
+    }
+
+    pub fn pending(&self) -> impl Iterator<Item = &TransferItem> {
+        self.items.iter().filter(|i| !i.done)
+    }
+
+    pub fn is_done(&self, id: &str) -> bool {
+        self.items.iter().any(|i| i.id == id && i.done)
+    }
+
+    pub fn mark_done<'a>(&mut self, ids: impl IntoIterator<Item = &'a str>) {
+        for id in ids {
+            if let Some(item) = self.items.iter_mut().find(|i| i.id == id) {
+                item.done = true;
+            }
+        }
+    }
+
+    /// Bytes sent and bytes planned
+    pub fn bytes(&self) -> (u64, u64) {
+        let done = self.items.iter().filter(|i| i.done).map(|i| i.size).sum();
+        (done, self.items.iter().map(|i| i.size).sum())
+    }
+
+    /// Share of the bytes already transferred, 0 to 100
+    pub fn percent(&self) -> u8 {
+        match self.bytes() {
+            (_, 0) => 100,
+            (done, total) => (done * 100 / total) as u8,
+        }
+    }
+
+    fn path(rune_dir: &Path, id: &str) -> PathBuf {
+        transfers_dir(rune_dir).join(format!("{}.json", id))
+    }
+
+    /// Write the manifest, replacing the previous state in one step
+    pub fn save(&self, rune_dir: &Path) -> Result<()> {
+        let dir = transfers_dir(rune_dir);
+        fs::create_dir_all(&dir)?;
+        let tmp = dir.join(format!(".{}.json.tmp", self.id));
+        fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(tmp, Self::path(rune_dir, &self.id))?;
+        Ok(())
+    }
+
+    pub fn load(rune_dir: &Path, id: &str) -> Result<Self> {
+        let path = Self::path(rune_dir, id);
+        if id.is_empty() || !id.bytes().all(|b| b.is_ascii_alphanumeric()) || !path.exists() {
+            anyhow::bail!("No transfer '{}'", id);
+        }
+        let data = fs::read(&path)?;
+        serde_json::from_slice(&data).with_context(|| format!("{} is corrupt", path.display()))
+    }
+
+    /// The unfinished transfer of `branch` to or from `remote`, if any
+    pub fn find(rune_dir: &Path, direction: TransferDirection, remote: &str, branch: &str) -> Result<Option<Self>> {
+        Ok(list_transfers(rune_dir)?
+            .into_iter()
+            .find(|m| m.direction == direction && m.remote == remote && m.branch == branch))
+    }
+
+    pub fn remove(&self, rune_dir: &Path) -> Result<()> {
+        match fs::remove_file(Self::path(rune_dir, &self.id)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn transfers_dir(rune_dir: &Path) -> PathBuf {
+    rune_dir.join("transfers")
+}
+
+/// Unfinished transfers, oldest first. Manifests that can't be read are skipped.
+pub fn list_transfers(rune_dir: &Path) -> Result<Vec<TransferManifest>> {
+    let dir = transfers_dir(rune_dir);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut manifests: Vec<TransferManifest> = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|e| e != "json") {
+            continue;
+        }
+        let Ok(data) = fs::read(&path) else { continue };
+        if let Ok(manifest) = serde_json::from_slice(&data) {
+            manifests.push(manifest);
+        }
+    }
+    manifests.sort_by(|a, b| a.started_at.cmp(&b.started_at).then_with(|| a.id.cmp(&b.id)));
+    Ok(manifests)
+}
+
+/// Drop the manifest of transfer `id`; a later push starts from scratch
+pub fn abort_transfer(rune_dir: &Path, id: &str) -> Result<TransferManifest> {
+    let manifest = TransferManifest::load(rune_dir, id)?;
+    manifest.remove(rune_dir)?;
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn item(id: &str, size: u64) -> TransferItem {
+        TransferItem { id: id.into(), size, digest: format!("d-{}", id), done: false }
+    }
+
+    #[test]
+    fn test_manifest_round_trip_and_progress() {
+        let temp = TempDir::new().unwrap();
+        let mut m = TransferManifest::new(TransferDirection::Push, "origin", "main", "c3", vec![item("c1", 30), item("c2", 70)]);
+        assert_eq!(m.percent(), 0);
+        m.mark_done(["c1"]);
+        m.save(temp.path()).unwrap();
+
+        let loaded = TransferManifest::load(temp.path(), &m.id).unwrap();
+        assert!(loaded.is_done("c1") && !loaded.is_done("c2"));
+        assert_eq!(loaded.bytes(), (30, 100));
+        assert_eq!(loaded.percent(), 30);
+        assert_eq!(loaded.pending().map(|i| i.id.as_str()).collect::<Vec<_>>(), ["c2"]);
+
+        let found = TransferManifest::find(temp.path(), TransferDirection::Push, "origin", "main").unwrap();
+        assert_eq!(found.map(|f| f.id), Some(m.id.clone()));
+        assert!(TransferManifest::find(temp.path(), TransferDirection::Pull, "origin", "main").unwrap().is_none());
+
+        abort_transfer(temp.path(), &m.id).unwrap();
+        assert!(list_transfers(temp.path()).unwrap().is_empty());
+        assert!(abort_transfer(temp.path(), &m.id).is_err());
+        assert!(TransferManifest::load(temp.path(), "../escape").is_err());
+    }
+}
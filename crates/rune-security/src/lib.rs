@@ -6,6 +6,7 @@ pub mod encryption;
 pub mod authentication;
 pub mod audit;
 pub mod compliance;
+pub mod pinning;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SecurityConfig {
@@ -0,0 +1,227 @@
+//! Certificate pinning for LFS and sync remotes
+//!
+//! A remote may list the SHA-256 fingerprints of the certificates its server
+//! is allowed to present. Clients built from those pins refuse any other
+//! server during the TLS handshake, before a request is sent.
+
+use anyhow::{anyhow, Result};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, SignatureScheme};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// SHA-256 fingerprint of a DER-encoded certificate, written `sha256:<hex>`.
+/// Colons between the hex bytes are accepted, so the output of
+/// `openssl x509 -fingerprint -sha256` can be pasted after the prefix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CertPin([u8; 32]);
+
+impl CertPin {
+    pub fn of_certificate(der: &[u8]) -> Self {
+        let digest = ring::digest::digest(&ring::digest::SHA256, der);
+        let mut pin = [0; 32];
+        pin.copy_from_slice(digest.as_ref());
+        Self(pin)
+    }
+
+    /// Parse the pins stored in a remote's configuration
+    pub fn parse_all(pins: &[String]) -> Result<Vec<Self>> {
+        pins.iter().map(|pin| pin.parse()).collect()
+    }
+}
+
+impl FromStr for CertPin {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let fingerprint = s
+            .trim()
+            .strip_prefix("sha256:")
+            .ok_or_else(|| anyhow!("certificate pin '{}' must start with 'sha256:'", s))?;
+        let bytes = hex::decode(fingerprint.replace(':', ""))
+            .map_err(|e| anyhow!("certificate pin '{}' is not hex: {}", s, e))?;
+        let pin = <[u8; 32]>::try_from(bytes.as_slice())
+            .map_err(|_| anyhow!("certificate pin '{}' is not a SHA-256 fingerprint", s))?;
+        Ok(Self(pin))
+    }
+}
+
+impl fmt::Display for CertPin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sha256:{}", hex::encode(self.0))
+    }
+}
+
+/// Accepts a server only when its certificate matches one of the pins. The
+/// pin takes the place of CA validation, so self-signed server certificates
+/// work; the handshake signature is still checked against the pinned key.
+#[derive(Debug)]
+pub struct PinnedCertVerifier {
+    pins: Vec<CertPin>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl PinnedCertVerifier {
+    pub fn new(pins: Vec<CertPin>, provider: Arc<CryptoProvider>) -> Self {
+        Self { pins, provider }
+    }
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let presented = CertPin::of_certificate(end_entity.as_ref());
+        if self.pins.contains(&presented) {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::General(format!(
+                "server certificate {} does not match any pinned certificate",
+                presented
+            )))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// HTTP client for talking to a remote. Without pins this is a plain client;
+/// with pins it only speaks HTTPS and only to a server presenting one of them.
+pub fn http_client(pins: &[CertPin]) -> Result<reqwest::Client> {
+    if pins.is_empty() {
+        return Ok(reqwest::Client::new());
+    }
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let config = rustls::ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(PinnedCertVerifier::new(pins.to_vec(), provider)))
+        .with_no_client_auth();
+    Ok(reqwest::Client::builder()
+        .use_preconfigured_tls(config)
+        .https_only(true)
+        .build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustls::pki_types::PrivateKeyDer;
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const SERVER_CERT: &[u8] = include_bytes!("../tests/fixtures/pinned-server.crt.der");
+    const SERVER_KEY: &[u8] = include_bytes!("../tests/fixtures/pinned-server.key.der");
+
+    /// TLS server that answers every request with "ok" and counts the
+    /// request bytes it received
+    async fn mock_remote() -> (SocketAddr, Arc<AtomicUsize>) {
+        let config = rustls::ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![CertificateDer::from(SERVER_CERT.to_vec())],
+                PrivateKeyDer::Pkcs8(SERVER_KEY.to_vec().into()),
+            )
+            .unwrap();
+        let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let received = Arc::new(AtomicUsize::new(0));
+        let counter = received.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let Ok(mut tls) = acceptor.accept(stream).await else {
+                    continue;
+                };
+                let mut buf = [0; 4096];
+                let n = tls.read(&mut buf).await.unwrap_or(0);
+                counter.fetch_add(n, Ordering::SeqCst);
+                let _ = tls
+                    .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok")
+                    .await;
+                let _ = tls.shutdown().await;
+            }
+        });
+        (addr, received)
+    }
+
+    #[test]
+    fn test_pin_parses_and_displays() {
+        let pin = CertPin::of_certificate(SERVER_CERT);
+        assert_eq!(pin.to_string().parse::<CertPin>().unwrap(), pin);
+
+        let bytes: Vec<String> = pin.0.iter().map(|b| format!("{:02X}", b)).collect();
+        let colons = format!("sha256:{}", bytes.join(":"));
+        assert_eq!(colons.parse::<CertPin>().unwrap(), pin);
+
+        assert!("7ae2".parse::<CertPin>().is_err());
+        assert!("sha256:7ae2".parse::<CertPin>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_matching_pin_connects() {
+        let (addr, received) = mock_remote().await;
+        let client = http_client(&[CertPin::of_certificate(SERVER_CERT)]).unwrap();
+
+        let response = client
+            .post(format!("https://{}/lfs/upload", addr))
+            .body("chunk")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "ok");
+        assert!(received.load(Ordering::SeqCst) > 0);
+    }
+
+    #[tokio::test]
+    async fn test_mismatched_pin_is_refused_before_transfer() {
+        let (addr, received) = mock_remote().await;
+        let client = http_client(&[CertPin::of_certificate(b"some other certificate")]).unwrap();
+
+        let err = client
+            .post(format!("https://{}/lfs/upload", addr))
+            .body("chunk")
+            .send()
+            .await
+            .unwrap_err();
+        assert!(err.is_connect(), "{err:?}");
+        assert_eq!(received.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_pinned_client_refuses_plain_http() {
+        let client = http_client(&[CertPin::of_certificate(SERVER_CERT)]).unwrap();
+        assert!(client.get("http://127.0.0.1:9/").send().await.is_err());
+    }
+}
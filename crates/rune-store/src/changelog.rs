@@ -0,0 +1,378 @@
+//! Release notes grouped by Conventional Commits type
+//!
+//! A commit's type comes from its `type(scope)!: subject` header, or from a
+//! `Type:` trailer (with an optional `Scope:`) when the header has no prefix.
+//! `rune autoflow release` renders its CHANGELOG.md section from the same
+//! `ReleaseNotes` that `rune notes between` prints for a range of tags.
+
+use crate::Store;
+use anyhow::Result;
+use rune_core::{trailers, Commit};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Where plan files live relative to the repository root, for links to
+/// the `PLAN-<n>` a commit references
+const PLAN_LINK_DIR: &str = ".rune/plans";
+
+/// Section headings, in output order; commits of other types go under
+/// "Other Changes"
+pub const CHANGELOG_SECTIONS: &[(&str, &str)] = &[
+    ("feat", "Features"),
+    ("fix", "Bug Fixes"),
+    ("perf", "Performance"),
+    ("refactor", "Refactoring"),
+    ("docs", "Documentation"),
+];
+
+/// `type(scope)!: subject` per the Conventional Commits spec
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConventionalCommit {
+    pub kind: String,
+    pub scope: Option<String>,
+    pub breaking: bool,
+    pub subject: String,
+}
+
+pub fn parse_conventional(message: &str) -> Option<ConventionalCommit> {
+    let header = message.lines().next()?;
+    let breaking = message
+        .lines()
+        .skip(1)
+        .any(|l| l.starts_with("BREAKING CHANGE:") || l.starts_with("BREAKING-CHANGE:"));
+    let Some((kind, scope, bang, subject)) = parse_header(header) else {
+        let kind = trailers::values(message, "Type").into_iter().next()?;
+        return Some(ConventionalCommit {
+            kind: kind.to_lowercase(),
+            scope: trailers::values(message, "Scope").into_iter().next(),
+            breaking,
+            subject: header.trim().to_string(),
+        });
+    };
+    Some(ConventionalCommit {
+        kind: kind.to_lowercase(),
+        scope: scope.map(str::to_string),
+        breaking: bang || breaking,
+        subject: subject.trim().to_string(),
+    })
+}
+
+/// `(type, scope, !, subject)` from a conventional header
+fn parse_header(header: &str) -> Option<(&str, Option<&str>, bool, &str)> {
+    let (prefix, subject) = header.split_once(": ")?;
+    let (prefix, bang) = match prefix.strip_suffix('!') {
+        Some(p) => (p, true),
+        None => (prefix, false),
+    };
+    let (kind, scope) = match prefix.split_once('(') {
+        Some((kind, rest)) => (kind, Some(rest.strip_suffix(')')?)),
+        None => (prefix, None),
+    };
+    if kind.is_empty() || !kind.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    Some((kind, scope, bang, subject))
+}
+
+/// `subject` without a type or scope prefix that repeats what the entry
+/// already shows, as in `feat(cli): feat: add x` or `fix(store): store: y`
+fn strip_redundant(subject: &str, kind: &str, scope: Option<&str>) -> String {
+    let mut subject = subject;
+    while let Some((inner_kind, inner_scope, _, rest)) = parse_header(subject) {
+        let same_scope = inner_scope.is_none() || inner_scope == scope;
+        if !inner_kind.eq_ignore_ascii_case(kind) || !same_scope {
+            break;
+        }
+        subject = rest.trim();
+    }
+    if let Some(rest) = scope.and_then(|scope| subject.strip_prefix(scope)).and_then(|r| r.strip_prefix(": ")) {
+        subject = rest.trim();
+    }
+    subject.to_string()
+}
+
+/// Whether `message` is the default message of a merge
+fn is_merge_subject(message: &str) -> bool {
+    message.starts_with("Merge branch ")
+}
+
+/// One line of release notes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChangelogEntry {
+    pub commit: String,
+    pub scope: Option<String>,
+    pub subject: String,
+    /// `PLAN-<n>` the commit belongs to
+    pub plan: Option<String>,
+}
+
+impl std::fmt::Display for ChangelogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let short = &self.commit[..self.commit.len().min(7)];
+        match &self.scope {
+            Some(scope) => write!(f, "- **{}:** {} ({}", scope, self.subject, short)?,
+            None => write!(f, "- {} ({}", self.subject, short)?,
+        }
+        match &self.plan {
+            Some(plan) => write!(f, ", [{}]({}/{}.md))", plan, PLAN_LINK_DIR, plan),
+            None => write!(f, ")"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChangelogSection {
+    pub title: String,
+    pub entries: Vec<ChangelogEntry>,
+}
+
+/// Commits grouped under headings, breaking changes first and everything
+/// unrecognised last. Only non-empty sections are kept.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Changelog {
+    pub sections: Vec<ChangelogSection>,
+}
+
+impl Changelog {
+    /// Group `commits`, keeping their order within each section. Release
+    /// commits are dropped since they only repeat earlier notes.
+    pub fn from_commits(commits: &[Commit]) -> Changelog {
+        let parsed: Vec<(&Commit, Option<ConventionalCommit>)> = commits
+            .iter()
+            .map(|c| (c, parse_conventional(&c.message)))
+            .filter(|(_, p)| !p.as_ref().is_some_and(|p| p.kind == "chore" && p.scope.as_deref() == Some("release")))
+            .collect();
+        let entry = |commit: &Commit, parsed: &Option<ConventionalCommit>| match parsed {
+            Some(p) => ChangelogEntry {
+                commit: commit.id.clone(),
+                scope: p.scope.clone(),
+                subject: strip_redundant(&p.subject, &p.kind, p.scope.as_deref()),
+                plan: trailers::plan_reference(&commit.message),
+            },
+            None => ChangelogEntry {
+                commit: commit.id.clone(),
+                scope: None,
+                subject: commit.message.lines().next().unwrap_or_default().to_string(),
+                plan: trailers::plan_reference(&commit.message),
+            },
+        };
+        let section = |title: &str, wanted: &dyn Fn(&Option<ConventionalCommit>) -> bool| ChangelogSection {
+            title: title.to_string(),
+            entries: parsed.iter().filter(|(_, p)| wanted(p)).map(|(c, p)| entry(c, p)).collect(),
+        };
+
+        let mut sections = vec![section("Breaking Changes", &|p| p.as_ref().is_some_and(|p| p.breaking))];
+        for (kind, title) in CHANGELOG_SECTIONS {
+            sections.push(section(title, &|p| p.as_ref().is_some_and(|p| p.kind == *kind && !p.breaking)));
+        }
+        sections.push(section("Other Changes", &|p| match p {
+            Some(p) => !p.breaking && !CHANGELOG_SECTIONS.iter().any(|(k, _)| *k == p.kind),
+            None => true,
+        }));
+        sections.retain(|s| !s.entries.is_empty());
+        Changelog { sections }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sections.is_empty()
+    }
+
+    /// `### Title` sections, each preceded by a blank line
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        for section in &self.sections {
+            out.push_str(&format!("\n### {}\n\n", section.title));
+            for entry in &section.entries {
+                out.push_str(&format!("{}\n", entry));
+            }
+        }
+        out
+    }
+}
+
+/// Which commits `ReleaseNotes` leaves out
+#[derive(Debug, Clone, Default)]
+pub struct ReleaseNotesOptions {
+    /// Keep commits whose message starts with "Merge branch"
+    pub include_merges: bool,
+}
+
+/// A changelog plus everyone who contributed to it
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ReleaseNotes {
+    #[serde(flatten)]
+    pub changelog: Changelog,
+    /// Author names, sorted and listed once each
+    pub authors: Vec<String>,
+}
+
+impl ReleaseNotes {
+    pub fn from_commits(commits: &[Commit], options: &ReleaseNotesOptions) -> ReleaseNotes {
+        let commits: Vec<Commit> = commits
+            .iter()
+            .filter(|c| options.include_merges || !is_merge_subject(&c.message))
+            .cloned()
+            .collect();
+        let changelog = Changelog::from_commits(&commits);
+        // Keyed by lowercase name so differently cased spellings show once,
+        // as the newest commit spells it
+        let mut authors = BTreeMap::new();
+        for commit in commits.iter().filter(|c| changelog.sections.iter().flat_map(|s| &s.entries).any(|e| e.commit == c.id)) {
+            authors.entry(commit.author.name.to_lowercase()).or_insert_with(|| commit.author.name.clone());
+        }
+        ReleaseNotes { changelog, authors: authors.into_values().collect() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changelog.is_empty()
+    }
+
+    /// The changelog's sections, then a Contributors section
+    pub fn to_markdown(&self) -> String {
+        let mut out = self.changelog.to_markdown();
+        if !self.authors.is_empty() {
+            out.push_str("\n### Contributors\n\n");
+            for author in &self.authors {
+                out.push_str(&format!("- {}\n", author));
+            }
+        }
+        out
+    }
+}
+
+impl Store {
+    /// Release notes for the commits reachable from `to` but not from
+    /// `from`, newest first within each section. Both ends may be tags,
+    /// branches, `HEAD` or commit ids.
+    pub fn changelog_between(&self, from: &str, to: &str) -> Result<Changelog> {
+        Ok(Changelog::from_commits(&self.commits_between(from, to)?))
+    }
+
+    /// `ReleaseNotes` for the commits after `from`, or all of history up
+    /// to `to` when `from` is `None`
+    pub fn release_notes(&self, from: Option<&str>, to: &str, options: &ReleaseNotesOptions) -> Result<ReleaseNotes> {
+        Ok(ReleaseNotes::from_commits(&self.commits_since(from, to)?, options))
+    }
+
+    /// The tag nearest to `to` that doesn't point at `to` itself, which
+    /// notes for a release at `to` start after
+    pub fn previous_tag(&self, to: &str) -> Result<Option<String>> {
+        let to = self.revision_id(to)?;
+        match self.resolve_commit(&to).ok().and_then(|c| c.parent) {
+            Some(parent) => Ok(self.describe(&parent)?.map(|(tag, _)| tag)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_repo;
+    use rune_core::Author;
+    use std::fs;
+
+    #[test]
+    fn test_changelog_between_tags() {
+        let (temp, store) = temp_repo();
+        let mut n = 0;
+        let mut commit = |message: &str| {
+            n += 1;
+            fs::write(temp.path().join("file.txt"), n.to_string()).unwrap();
+            store.stage_file("file.txt").unwrap();
+            store.commit(message, Author { name: "Dev".into(), email: "dev@example.com".into() }).unwrap().id
+        };
+
+        let v1 = commit("feat: first release");
+        let feat = commit("feat(cli): add changelog command");
+        let fix = commit("fix: off-by-one in range");
+        let misc = commit("Tidy up the build script");
+        let typed = commit("Speed up status\n\nType: perf\nScope: store");
+        let breaking = commit("refactor!: rename config keys");
+        let release = commit("chore(release): v2.0.0");
+        let v2 = release.clone();
+        commit("feat: after the release");
+        store.create_annotated_tag("v1.0.0", &v1, "v1").unwrap();
+        store.create_annotated_tag("v2.0.0", &v2, "v2").unwrap();
+
+        let changelog = store.changelog_between("v1.0.0", "v2.0.0").unwrap();
+        let short = |id: &str| id[..7].to_string();
+        assert_eq!(
+            changelog.to_markdown(),
+            format!(
+                "\n### Breaking Changes\n\n- rename config keys ({})\n\
+                 \n### Features\n\n- **cli:** add changelog command ({})\n\
+                 \n### Bug Fixes\n\n- off-by-one in range ({})\n\
+                 \n### Performance\n\n- **store:** Speed up status ({})\n\
+                 \n### Other Changes\n\n- Tidy up the build script ({})\n",
+                short(&breaking),
+                short(&feat),
+                short(&fix),
+                short(&typed),
+                short(&misc)
+            )
+        );
+
+        assert!(store.changelog_between("v2.0.0", "v2.0.0").unwrap().is_empty());
+        // Tags may hold an abbreviated id
+        store.create_lightweight_tag("short", &fix[..8]).unwrap();
+        let after_fix = store.changelog_between("short", "v2.0.0").unwrap();
+        assert_eq!(after_fix.sections.iter().map(|s| s.title.as_str()).collect::<Vec<_>>(), ["Breaking Changes", "Performance", "Other Changes"]);
+        let everything = store.changelog_between("v1.0.0", "HEAD").unwrap();
+        assert_eq!(everything.sections[1].entries.len(), 2);
+        assert!(store.changelog_between("v9", "HEAD").is_err());
+    }
+
+    #[test]
+    fn test_release_notes_between_tags_on_a_branched_history() {
+        let (temp, store) = temp_repo();
+        let mut n = 0;
+        let mut commit = |message: &str, name: &str| {
+            n += 1;
+            fs::write(temp.path().join(format!("file{}.txt", n)), n.to_string()).unwrap();
+            store.stage_file(&format!("file{}.txt", n)).unwrap();
+            let author = Author { name: name.into(), email: format!("{}@example.com", name.to_lowercase()) };
+            store.commit(message, author).unwrap().id
+        };
+
+        commit("feat: first release", "Ada");
+        store.create_branch("topic").unwrap();
+        store.checkout_branch("topic").unwrap();
+        let early = commit("fix: released on the topic branch", "Grace");
+        store.checkout_branch("main").unwrap();
+        commit("docs: describe the topic", "Ada");
+        assert!(matches!(store.merge_branch("topic", false, None).unwrap(), crate::MergeResult::Success));
+        store.create_lightweight_tag("v1.3.0", &store.head_commit().unwrap()).unwrap();
+
+        commit("feat(cli): feat: add notes command\n\nPlan: PLAN-004", "Ada");
+        commit("fix(store): store: handle empty ranges", "linus");
+        commit("perf: faster walks", "Linus");
+        commit("Update the README", "Grace");
+        store.create_lightweight_tag("v1.4.0", &store.head_commit().unwrap()).unwrap();
+        commit("feat: unreleased", "Ada");
+
+        let notes = store.release_notes(Some("v1.3.0"), "v1.4.0", &ReleaseNotesOptions::default()).unwrap();
+        assert!(!notes.changelog.sections.iter().flat_map(|s| &s.entries).any(|e| e.commit == early));
+        let titles: Vec<&str> = notes.changelog.sections.iter().map(|s| s.title.as_str()).collect();
+        assert_eq!(titles, ["Features", "Bug Fixes", "Performance", "Other Changes"]);
+        let feature = &notes.changelog.sections[0].entries[0];
+        assert_eq!((feature.subject.as_str(), feature.plan.as_deref()), ("add notes command", Some("PLAN-004")));
+        assert!(feature.to_string().ends_with(", [PLAN-004](.rune/plans/PLAN-004.md))"));
+        assert_eq!(notes.changelog.sections[1].entries[0].subject, "handle empty ranges");
+        assert_eq!(notes.authors, ["Ada", "Grace", "Linus"]);
+        assert!(notes.to_markdown().ends_with("\n### Contributors\n\n- Ada\n- Grace\n- Linus\n"));
+
+        // Reached from v1.3.0 through the merge, which is itself left out
+        let everything = store.release_notes(None, "v1.3.0", &ReleaseNotesOptions::default()).unwrap();
+        let subjects: Vec<&str> =
+            everything.changelog.sections.iter().flat_map(|s| &s.entries).map(|e| e.subject.as_str()).collect();
+        assert_eq!(subjects, ["first release", "released on the topic branch", "describe the topic"]);
+        let with_merges = ReleaseNotesOptions { include_merges: true };
+        let sections = store.release_notes(None, "v1.3.0", &with_merges).unwrap().changelog.sections;
+        assert_eq!(sections.last().unwrap().entries[0].subject, "Merge branch 'topic' into main");
+
+        assert_eq!(store.previous_tag("HEAD").unwrap().as_deref(), Some("v1.4.0"));
+        assert_eq!(store.previous_tag("v1.4.0").unwrap().as_deref(), Some("v1.3.0"));
+        assert_eq!(store.previous_tag("v1.3.0").unwrap(), None);
+    }
+}
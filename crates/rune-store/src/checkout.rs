@@ -0,0 +1,243 @@
+//! Writing the difference between two trees into the working tree, with a
+//! way to stop part way.
+//!
+//! Before a file is touched, what was there is kept in memory: its content
+//! and mode, or that nothing was, along with any directories that had to
+//! be created for it. The cancel token is checked before every file; once
+//! it is set, or a write fails, everything kept is put back newest first,
+//! so the working tree is as the checkout found it.
+
+use crate::{links, Store, StoreError, DELETED_BLOB};
+use anyhow::Result;
+use rune_core::FileMode;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Asks a running checkout to stop and put back what it wrote. Clones
+/// share one flag, so a Ctrl-C handler or another thread can hold one.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+impl From<Arc<AtomicBool>> for CancelToken {
+    fn from(flag: Arc<AtomicBool>) -> Self {
+        Self(flag)
+    }
+}
+
+/// Tokens are equal when they share one flag
+impl PartialEq for CancelToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for CancelToken {}
+
+/// Called before each file with how many are written so far and how many
+/// there are in all
+pub type CheckoutProgress<'a> = &'a mut dyn FnMut(usize, usize);
+
+/// What a path held before the checkout touched it
+struct Saved {
+    path: PathBuf,
+    before: Option<(Vec<u8>, FileMode)>,
+    /// Directories created for it, outermost first
+    created_dirs: Vec<PathBuf>,
+}
+
+impl Store {
+    /// Rewrite the working files that differ between `from` and `to`;
+    /// `to_modes` says which of `to`'s paths are links. Stops with
+    /// `StoreError::Cancelled` once `cancel` is set, and on that or any
+    /// other failure puts back every file it had written.
+    pub(crate) fn checkout_changes(
+        &self,
+        from: &BTreeMap<String, String>,
+        to: &BTreeMap<String, String>,
+        to_modes: &BTreeMap<String, FileMode>,
+        cancel: &CancelToken,
+        progress: CheckoutProgress,
+    ) -> Result<()> {
+        let filter = self.eol_filter()?;
+        let paths: BTreeSet<&String> = from.keys().chain(to.keys()).filter(|p| from.get(*p) != to.get(*p)).collect();
+        let mut saved = Vec::new();
+        for (done, path) in paths.iter().enumerate() {
+            progress(done, paths.len());
+            if cancel.is_cancelled() {
+                return Err(self.put_back(saved, StoreError::Cancelled.into()));
+            }
+            let dest = self.root.join(path);
+            let written = save(&self.root, &dest).and_then(|entry| {
+                saved.extend(entry);
+                match to.get(*path).filter(|b| *b != DELETED_BLOB) {
+                    Some(blob) => {
+                        self.checkout_blob(path, blob, to_modes.get(*path).copied().unwrap_or_default(), &filter)
+                    }
+                    None => Ok(links::remove_worktree(&dest)?),
+                }
+            });
+            if let Err(err) = written {
+                return Err(self.put_back(saved, err));
+            }
+        }
+        progress(paths.len(), paths.len());
+        Ok(())
+    }
+
+    /// Undo `saved` newest first and return `err`, noting when something
+    /// couldn't be put back
+    fn put_back(&self, saved: Vec<Saved>, err: anyhow::Error) -> anyhow::Error {
+        let mut failed = Vec::new();
+        for entry in saved.into_iter().rev() {
+            if let Err(e) = restore(&entry) {
+                failed.push(format!("{}: {}", entry.path.display(), e));
+            }
+        }
+        if failed.is_empty() {
+            err
+        } else {
+            err.context(format!("the working tree could not be fully restored ({})", failed.join("; ")))
+        }
+    }
+}
+
+/// What `dest` holds now; `None` for a directory, which checkout leaves alone
+fn save(root: &Path, dest: &Path) -> Result<Option<Saved>> {
+    let before = match fs::symlink_metadata(dest) {
+        Ok(meta) if meta.is_dir() => return Ok(None),
+        Ok(_) => Some(links::read_worktree(dest)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    let mut created_dirs: Vec<PathBuf> = dest
+        .ancestors()
+        .skip(1)
+        .take_while(|dir| *dir != root && fs::symlink_metadata(dir).is_err())
+        .map(Path::to_path_buf)
+        .collect();
+    created_dirs.reverse();
+    Ok(Some(Saved { path: dest.to_path_buf(), before, created_dirs }))
+}
+
+fn restore(saved: &Saved) -> io::Result<()> {
+    match &saved.before {
+        Some((content, mode)) => links::write_worktree(&saved.path, content, *mode)?,
+        None => links::remove_worktree(&saved.path)?,
+    }
+    for dir in saved.created_dirs.iter().rev() {
+        // Only an empty one; a later file's rollback may have emptied it
+        let _ = fs::remove_dir(dir);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CancelToken, Store, StoreError};
+    use crate::test_support::{author, temp_repo};
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// `main` with a.txt and b.txt, and `ahead` one commit past it that
+    /// edits a.txt, deletes b.txt and adds files in new directories
+    fn with_ahead() -> (TempDir, Store, String) {
+        let (temp, store) = temp_repo();
+        for (path, content) in [("a.txt", "a\n"), ("b.txt", "b\n")] {
+            fs::write(temp.path().join(path), content).unwrap();
+            store.stage_file(path).unwrap();
+        }
+        store.commit("base", author()).unwrap();
+        let base = store.head_commit().unwrap();
+
+        store.create_branch("ahead").unwrap();
+        store.checkout_branch("ahead").unwrap();
+        fs::write(temp.path().join("a.txt"), "a2\n").unwrap();
+        fs::remove_file(temp.path().join("b.txt")).unwrap();
+        fs::create_dir_all(temp.path().join("src/deep")).unwrap();
+        for path in ["c.txt", "src/d.txt", "src/deep/e.txt"] {
+            fs::write(temp.path().join(path), path).unwrap();
+        }
+        for path in ["a.txt", "b.txt", "c.txt", "src/d.txt", "src/deep/e.txt"] {
+            store.stage_file(path).unwrap();
+        }
+        let ahead = store.commit("ahead", author()).unwrap().id;
+
+        // Back to main's files
+        store.checkout_branch("main").unwrap();
+        fs::remove_dir_all(temp.path().join("src")).unwrap();
+        fs::remove_file(temp.path().join("c.txt")).unwrap();
+        fs::write(temp.path().join("a.txt"), "a\n").unwrap();
+        fs::write(temp.path().join("b.txt"), "b\n").unwrap();
+        assert_eq!(store.head_commit(), Some(base));
+        (temp, store, ahead)
+    }
+
+    fn listing(temp: &TempDir) -> Vec<String> {
+        let mut paths: Vec<String> = walkdir::WalkDir::new(temp.path())
+            .min_depth(1)
+            .into_iter()
+            .filter_entry(|e| e.file_name() != ".rune")
+            .map(|e| e.unwrap().path().strip_prefix(temp.path()).unwrap().to_string_lossy().into_owned())
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn test_cancelled_checkout_leaves_the_working_tree_as_it_was() {
+        let (temp, store, ahead) = with_ahead();
+        let head = store.head_commit();
+        let before = listing(&temp);
+
+        for stop_at in 1..5 {
+            let cancel = CancelToken::new();
+            let mut seen = Vec::new();
+            let err = store
+                .fast_forward_with(&ahead, "pull", &cancel, &mut |done, total| {
+                    seen.push((done, total));
+                    if done == stop_at {
+                        cancel.cancel();
+                    }
+                })
+                .unwrap_err();
+            assert_eq!(err.downcast_ref::<StoreError>(), Some(&StoreError::Cancelled));
+            assert_eq!(seen.last(), Some(&(stop_at, 5)));
+
+            assert_eq!(listing(&temp), before, "stopped after {} file(s)", stop_at);
+            assert_eq!(fs::read_to_string(temp.path().join("a.txt")).unwrap(), "a\n");
+            assert_eq!(fs::read_to_string(temp.path().join("b.txt")).unwrap(), "b\n");
+            assert_eq!(store.head_commit(), head);
+            assert!(store.status().unwrap().working.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_uncancelled_checkout_writes_everything() {
+        let (temp, store, ahead) = with_ahead();
+        let mut seen = Vec::new();
+        store.fast_forward_with(&ahead, "pull", &CancelToken::new(), &mut |done, total| seen.push((done, total))).unwrap();
+        assert_eq!(seen.first(), Some(&(0, 5)));
+        assert_eq!(seen.last(), Some(&(5, 5)));
+        assert_eq!(store.head_commit(), Some(ahead));
+        assert!(!temp.path().join("b.txt").exists());
+        assert_eq!(fs::read_to_string(temp.path().join("src/deep/e.txt")).unwrap(), "src/deep/e.txt");
+    }
+}
@@ -0,0 +1,331 @@
+//! Conflicted files of a merge or revert in progress. The base, ours and
+//! theirs content of each is kept under `.rune/merge/<path>/` until the
+//! operation finishes, so a file can be resolved by hand, by taking one
+//! side, or in a merge tool.
+
+use crate::{Store, StoreError, DRIVER_RESULT};
+use anyhow::Result;
+use std::fs;
+use std::path::PathBuf;
+
+/// The three sides of a conflicted file, `None` where the file doesn't exist
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictVersions {
+    pub base: Option<Vec<u8>>,
+    pub ours: Option<Vec<u8>>,
+    pub theirs: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictSide {
+    Base,
+    Ours,
+    Theirs,
+}
+
+impl ConflictSide {
+    /// File name under `.rune/merge/<path>/`
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            ConflictSide::Base => "base",
+            ConflictSide::Ours => "ours",
+            ConflictSide::Theirs => "theirs",
+        }
+    }
+}
+
+impl Store {
+    /// Write each conflicted file as one conflict block over its whole
+    /// content, in the configured `merge.conflict_style`
+    pub(crate) fn apply_merge_conflicts(&self, conflicts: &[String], branch_name: &str) -> Result<()> {
+        let style = self.config().merge.conflict_style;
+        for file in conflicts {
+            // A merge driver already produced the file to leave behind
+            if let Ok(content) = fs::read(self.conflict_dir(file)?.join(DRIVER_RESULT)) {
+                fs::write(self.root.join(file), content)?;
+                continue;
+            }
+            let versions = self.conflict_versions(file)?;
+            let text = |content: &Option<Vec<u8>>| {
+                String::from_utf8_lossy(content.as_deref().unwrap_or_default()).into_owned()
+            };
+            let (ours, base, theirs) = (text(&versions.ours), text(&versions.base), text(&versions.theirs));
+            let (ours, base, theirs): (Vec<&str>, Vec<&str>, Vec<&str>) =
+                (ours.lines().collect(), base.lines().collect(), theirs.lines().collect());
+            let block = style.markers(("HEAD", branch_name), &ours, &base, &theirs);
+            let file_path = self.root.join(file);
+            if let Some(parent) = file_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(file_path, block.join("\n") + "\n")?;
+        }
+        Ok(())
+    }
+
+    /// Directory under `.rune/merge` holding the sides of a conflicted file;
+    /// paths that would lead out of it are refused like `checked_path` does
+    pub(crate) fn conflict_dir(&self, path: &str) -> Result<PathBuf> {
+        Ok(self.rune_dir.join("merge").join(self.checked_path(path)?))
+    }
+
+    /// Where one side of a conflicted file is kept for the duration of the merge
+    pub fn conflict_version_path(&self, path: &str, side: ConflictSide) -> Result<PathBuf> {
+        Ok(self.conflict_dir(path)?.join(side.file_name()))
+    }
+
+    /// Base, ours and theirs content of a file conflicted by the merge in progress
+    pub fn conflict_versions(&self, path: &str) -> Result<ConflictVersions> {
+        let dir = self.conflict_dir(path)?;
+        if !dir.is_dir() {
+            return Err(StoreError::NotInConflict(path.to_string()).into());
+        }
+        let read = |side: ConflictSide| fs::read(dir.join(side.file_name())).ok();
+        Ok(ConflictVersions {
+            base: read(ConflictSide::Base),
+            ours: read(ConflictSide::Ours),
+            theirs: read(ConflictSide::Theirs),
+        })
+    }
+
+    /// Conflicted files of the merge in progress that are not resolved yet;
+    /// staging a conflicted file with `rune add` also counts as resolving it
+    pub fn unresolved_conflicts(&self) -> Result<Vec<String>> {
+        let (conflicts, resolved) = match self.read_revert_state()? {
+            Some(state) => (state.conflicts, state.resolved),
+            None => {
+                let state = self.read_merge_state()?;
+                (state.conflicts, state.resolved)
+            }
+        };
+        let index = self.read_index()?;
+        Ok(conflicts
+            .into_iter()
+            .filter(|c| !resolved.contains(c) && !index.entries.contains_key(c))
+            .collect())
+    }
+
+    /// Record that the working copy of `path` is its resolution and stage it
+    pub fn mark_resolved(&self, path: &str) -> Result<()> {
+        if let Some(mut state) = self.read_revert_state()? {
+            if !state.conflicts.iter().any(|c| c == path) {
+                return Err(StoreError::NotInConflict(path.to_string()).into());
+            }
+            // A resolution that leaves the file deleted is staged as a deletion
+            if self.root.join(path).exists() || self.is_tracked(path, &self.read_index()?) {
+                self.stage_file(path)?;
+            }
+            state.resolved.insert(path.to_string());
+            return self.write_revert_state(&state);
+        }
+        let mut state = self.read_merge_state()?;
+        if !state.conflicts.iter().any(|c| c == path) {
+            return Err(StoreError::NotInConflict(path.to_string()).into());
+        }
+        if self.root.join(path).exists() {
+            self.stage_file(path)?;
+        }
+        state.resolved.insert(path.to_string());
+        self.write_merge_state(&state)
+    }
+
+    /// Resolve a conflict by taking one side as is; a side where the file
+    /// doesn't exist deletes it
+    pub fn resolve_conflict(&self, path: &str, side: ConflictSide) -> Result<()> {
+        let versions = self.conflict_versions(path)?;
+        let content = match side {
+            ConflictSide::Base => versions.base,
+            ConflictSide::Ours => versions.ours,
+            ConflictSide::Theirs => versions.theirs,
+        };
+        let dest = self.root.join(self.checked_path(path)?);
+        match content {
+            Some(content) => fs::write(&dest, content)?,
+            None if dest.exists() => fs::remove_file(&dest)?,
+            None => {}
+        }
+        self.mark_resolved(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{author, temp_repo};
+    use crate::{ConflictStyle, MergeResult};
+    use tempfile::TempDir;
+
+    fn commit_file(store: &Store, path: &str, content: &str, msg: &str) {
+        fs::write(store.root.join(path), content).unwrap();
+        store.stage_file(path).unwrap();
+        store.commit(msg, author()).unwrap();
+    }
+
+    /// Diverging edits to `a.txt` on `main` and `feature`
+    fn diverged_repo() -> (TempDir, Store) {
+        let (temp_dir, store) = temp_repo();
+        commit_file(&store, "a.txt", "base\n", "base");
+        store.create_branch("feature").unwrap();
+        commit_file(&store, "a.txt", "ours\n", "ours");
+        store.checkout_branch("feature").unwrap();
+        commit_file(&store, "a.txt", "theirs\n", "theirs");
+        store.checkout_branch("main").unwrap();
+        fs::write(store.root.join("a.txt"), "ours\n").unwrap();
+        (temp_dir, store)
+    }
+
+    #[test]
+    fn test_conflict_versions_hold_all_three_sides() {
+        let (_temp_dir, store) = diverged_repo();
+        let result = store.merge_branch("feature", false, None).unwrap();
+        assert!(matches!(result, MergeResult::Conflicts(ref files) if files == &["a.txt"]));
+
+        let versions = store.conflict_versions("a.txt").unwrap();
+        assert_eq!(versions.base.as_deref(), Some(&b"base\n"[..]));
+        assert_eq!(versions.ours.as_deref(), Some(&b"ours\n"[..]));
+        assert_eq!(versions.theirs.as_deref(), Some(&b"theirs\n"[..]));
+
+        let marked = fs::read_to_string(store.root.join("a.txt")).unwrap();
+        assert_eq!(marked, "<<<<<<< HEAD\nours\n=======\ntheirs\n>>>>>>> feature\n");
+        assert!(store.conflict_versions("other.txt").is_err());
+    }
+
+    #[test]
+    fn test_conflict_paths_stay_inside_the_merge_dir() {
+        let (_temp_dir, store) = diverged_repo();
+        store.merge_branch("feature", false, None).unwrap();
+
+        for path in ["../../secret", "/etc/passwd", "a.txt/../../../x"] {
+            let Err(err) = store.conflict_version_path(path, ConflictSide::Ours) else {
+                panic!("{} was accepted", path);
+            };
+            assert!(matches!(err.downcast_ref(), Some(StoreError::PathOutsideRepo(_))), "{}", err);
+            assert!(store.conflict_versions(path).is_err());
+            assert!(store.resolve_conflict(path, ConflictSide::Theirs).is_err());
+        }
+        assert!(store.conflict_version_path("a.txt", ConflictSide::Ours).unwrap().ends_with("merge/a.txt/ours"));
+    }
+
+    #[test]
+    fn test_diff3_style_adds_the_base_section() {
+        let (_temp_dir, store) = diverged_repo();
+        let mut cfg = store.config();
+        cfg.merge.conflict_style = ConflictStyle::Diff3;
+        store.write_config(&cfg).unwrap();
+        store.merge_branch("feature", false, None).unwrap();
+        let marked = fs::read_to_string(store.root.join("a.txt")).unwrap();
+        assert_eq!(marked, "<<<<<<< HEAD\nours\n||||||| base\nbase\n=======\ntheirs\n>>>>>>> feature\n");
+    }
+
+    #[test]
+    fn test_modify_delete_conflict_is_marked_in_either_style() {
+        for (style, expected) in [
+            (ConflictStyle::Merge, "<<<<<<< HEAD\nbase\nmore\n=======\n>>>>>>> feature\n"),
+            (ConflictStyle::Diff3, "<<<<<<< HEAD\nbase\nmore\n||||||| base\nbase\n=======\n>>>>>>> feature\n"),
+        ] {
+            let (_temp_dir, store) = temp_repo();
+            let mut cfg = store.config();
+            cfg.merge.conflict_style = style;
+            store.write_config(&cfg).unwrap();
+            commit_file(&store, "a.txt", "base\n", "base");
+            store.create_branch("feature").unwrap();
+            commit_file(&store, "a.txt", "base\nmore\n", "ours");
+            store.checkout_branch("feature").unwrap();
+            fs::remove_file(store.root.join("a.txt")).unwrap();
+            store.stage_file("a.txt").unwrap();
+            store.commit("theirs", author()).unwrap();
+            store.checkout_branch("main").unwrap();
+            fs::write(store.root.join("a.txt"), "base\nmore\n").unwrap();
+
+            let result = store.merge_branch("feature", false, None).unwrap();
+            assert!(matches!(result, MergeResult::Conflicts(ref files) if files == &["a.txt"]));
+            assert_eq!(fs::read_to_string(store.root.join("a.txt")).unwrap(), expected, "{:?}", style);
+        }
+    }
+
+    /// `main` and `feature` both edit `deps.json` from the same base
+    fn diverged_json(ours: &str, theirs: &str) -> (TempDir, Store) {
+        let (temp_dir, store) = temp_repo();
+        fs::write(store.root.join(".runeattributes"), "*.json merge=json\n").unwrap();
+        commit_file(&store, "deps.json", r#"{"a": 1, "b": 1}"#, "base");
+        store.create_branch("feature").unwrap();
+        commit_file(&store, "deps.json", ours, "ours");
+        store.checkout_branch("feature").unwrap();
+        commit_file(&store, "deps.json", theirs, "theirs");
+        store.checkout_branch("main").unwrap();
+        fs::write(store.root.join("deps.json"), ours).unwrap();
+        (temp_dir, store)
+    }
+
+    #[test]
+    fn test_merge_driver_result_is_committed() {
+        let (_temp_dir, store) = diverged_json(r#"{"a": 2, "b": 1}"#, r#"{"a": 1, "b": 3}"#);
+        let result = store.merge_branch("feature", false, None).unwrap();
+        assert!(matches!(result, MergeResult::Success));
+
+        let head = store.head_commit().unwrap();
+        let merged: serde_json::Value =
+            serde_json::from_slice(&store.read_blob(&store.tree_snapshot(&head)["deps.json"]).unwrap()).unwrap();
+        assert_eq!(merged, serde_json::json!({"a": 2, "b": 3}));
+    }
+
+    #[test]
+    fn test_merge_driver_conflict_is_recorded_and_aborts_cleanly() {
+        let (_temp_dir, store) = diverged_json(r#"{"a": 2, "b": 1}"#, r#"{"a": 3, "b": 1}"#);
+        let result = store.merge_branch("feature", false, None).unwrap();
+        assert!(matches!(result, MergeResult::Conflicts(ref files) if files == &["deps.json"]));
+        let conflict = store.merge_conflict("deps.json").unwrap().unwrap();
+        assert_eq!((conflict.driver.as_str(), conflict.details), ("json", vec!["a".to_string()]));
+
+        store.abort_merge().unwrap();
+        assert_eq!(fs::read_to_string(store.root.join("deps.json")).unwrap(), r#"{"a": 2, "b": 1}"#);
+    }
+
+    #[test]
+    fn test_abort_restores_ours_and_cleans_merge_dir() {
+        let (_temp_dir, store) = diverged_repo();
+        store.merge_branch("feature", false, None).unwrap();
+        assert!(store.rune_dir.join("merge/a.txt/theirs").exists());
+
+        store.abort_merge().unwrap();
+        assert!(!store.rune_dir.join("merge").exists());
+        assert!(!store.rune_dir.join("MERGE_STATE").exists());
+        assert_eq!(fs::read_to_string(store.root.join("a.txt")).unwrap(), "ours\n");
+    }
+
+    #[test]
+    fn test_continue_requires_resolution_then_cleans_up() {
+        let (_temp_dir, store) = diverged_repo();
+        store.merge_branch("feature", false, None).unwrap();
+        assert!(store.continue_merge().is_err());
+
+        store.resolve_conflict("a.txt", ConflictSide::Theirs).unwrap();
+        assert!(store.unresolved_conflicts().unwrap().is_empty());
+        store.continue_merge().unwrap();
+
+        assert!(!store.rune_dir.join("merge").exists());
+        let head = store.head_commit().unwrap();
+        // The merge commit records the resolved content
+        let blob = &store.tree_snapshot(&head)["a.txt"];
+        assert_eq!(store.read_blob(blob).unwrap(), b"theirs\n");
+    }
+
+    #[test]
+    fn test_one_sided_changes_do_not_conflict() {
+        let (_temp_dir, store) = temp_repo();
+        commit_file(&store, "a.txt", "base\n", "base");
+        store.create_branch("feature").unwrap();
+        commit_file(&store, "b.txt", "ours\n", "ours");
+        store.checkout_branch("feature").unwrap();
+        commit_file(&store, "a.txt", "theirs\n", "theirs");
+        store.checkout_branch("main").unwrap();
+        fs::write(store.root.join("a.txt"), "base\n").unwrap();
+
+        let result = store.merge_branch("feature", false, None).unwrap();
+        assert!(matches!(result, MergeResult::Success));
+        // Their change lands in the merge commit and the working tree
+        let tree = store.tree_snapshot(&store.head_commit().unwrap());
+        assert_eq!(store.read_blob(&tree["a.txt"]).unwrap(), b"theirs\n");
+        assert_eq!(fs::read_to_string(store.root.join("a.txt")).unwrap(), "theirs\n");
+        assert!(tree.contains_key("b.txt"));
+    }
+}
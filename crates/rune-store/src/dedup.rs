@@ -0,0 +1,290 @@
+//! Collapsing duplicate objects
+//!
+//! Blobs are filed under their blake3 hash, so identical content normally
+//! exists once. Two kinds of copies slip past that: `objects/<path>.blob`
+//! files left by the old path-keyed scheme, and content filed under a key
+//! that isn't a hash id at all (hand-copied or imported object directories).
+//! `dedup_objects` files each piece of content under its hash, repoints
+//! commits, the index and saved index snapshots at that id, and only then
+//! deletes the other copies. An object filed under a valid id whose content
+//! hashes to something else is damaged, not misfiled: it is reported and
+//! left alone, since repointing its references would pass the damage off as
+//! the content history recorded.
+
+use crate::{write_atomic, Index, RepoLock, Store};
+use anyhow::Result;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What `Store::dedup_objects` did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DedupReport {
+    /// Object files examined
+    pub objects_scanned: usize,
+    /// Copies deleted after their references moved to the canonical object
+    pub duplicates_removed: usize,
+    /// Bytes freed, less any canonical objects that had to be written
+    pub bytes_saved: u64,
+    /// Tree and index entries that now name a different object
+    pub references_updated: usize,
+    /// Ids whose object file no longer hashes to them, left untouched
+    pub corrupt: Vec<String>,
+}
+
+/// An object file and the id its content hashes to
+struct Copy {
+    file: PathBuf,
+    id: String,
+    size: u64,
+}
+
+/// `objects/ab/cdef..` as the key it is filed under, valid id or not
+fn filed_key(objects: &Path, file: &Path) -> Option<String> {
+    let rel = file.strip_prefix(objects).ok()?;
+    let mut parts = rel.iter().map(|p| p.to_str());
+    let (Some(Some(dir)), Some(Some(name)), None) = (parts.next(), parts.next(), parts.next()) else {
+        return None;
+    };
+    (dir.len() == 2 && dir.bytes().all(is_id_digit)).then(|| format!("{}{}", dir, name))
+}
+
+/// `objects/ab/cdef..` as the id it is filed under
+pub(crate) fn filed_id(objects: &Path, file: &Path) -> Option<String> {
+    filed_key(objects, file).filter(|key| is_object_id(key))
+}
+
+/// Whether `key` has the shape of a blake3 hex digest
+fn is_object_id(key: &str) -> bool {
+    key.len() == 64 && key.bytes().all(is_id_digit)
+}
+
+fn is_id_digit(b: u8) -> bool {
+    matches!(b, b'0'..=b'9' | b'a'..=b'f')
+}
+
+/// `objects/<path with / as _>.blob`, the old path-keyed name for `path`
+fn legacy_name(path: &str) -> String {
+    format!("{}.blob", path.replace('/', "_"))
+}
+
+impl Store {
+    /// Keep one physical copy of every stored blob; see the module docs.
+    /// History is rewritten in place, so this runs under the repository lock.
+    pub fn dedup_objects(&self, _lock: &RepoLock) -> Result<DedupReport> {
+        let objects = self.rune_dir.join("objects");
+        let mut log = self.log();
+        let mut report = DedupReport::default();
+
+        // Path-keyed files only matter to the commits that list their path
+        let legacy_paths: Vec<&str> = log
+            .iter()
+            .flat_map(|c| c.files.iter().filter(|f| !c.tree.contains_key(*f)))
+            .map(String::as_str)
+            .collect();
+
+        let mut misfiled: Vec<(String, Copy)> = Vec::new();
+        let mut legacy: HashMap<String, Copy> = HashMap::new();
+        let mut written = 0u64;
+        for entry in walkdir::WalkDir::new(&objects).min_depth(1).into_iter().flatten() {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let file = entry.into_path();
+            let key = filed_key(&objects, &file);
+            let name = file.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string();
+            let is_legacy = key.is_none()
+                && file.parent() == Some(objects.as_path())
+                && legacy_paths.iter().any(|p| legacy_name(p) == name);
+            if key.is_none() && !is_legacy {
+                continue;
+            }
+            report.objects_scanned += 1;
+            let content = fs::read(&file)?;
+            let id = blake3::hash(&content).to_hex().to_string();
+            if let Some(key) = key.as_deref().filter(|key| is_object_id(key)) {
+                if key != id {
+                    report.corrupt.push(key.to_string());
+                }
+                continue;
+            }
+            if !self.blob_path(&id).exists() {
+                self.write_blob(&content)?;
+                written += content.len() as u64;
+            }
+            let copy = Copy { file, id, size: content.len() as u64 };
+            match key {
+                Some(key) => misfiled.push((key, copy)),
+                None => {
+                    legacy.insert(name, copy);
+                }
+            }
+        }
+        if misfiled.is_empty() && legacy.is_empty() {
+            return Ok(report);
+        }
+
+        let remap: HashMap<&str, &str> =
+            misfiled.iter().map(|(key, copy)| (key.as_str(), copy.id.as_str())).collect();
+        let repoint = |blobs: &mut BTreeMap<String, String>| {
+            let mut changed = 0;
+            for id in blobs.values_mut() {
+                if let Some(canonical) = remap.get(id.as_str()) {
+                    *id = canonical.to_string();
+                    changed += 1;
+                }
+            }
+            changed
+        };
+
+        let mut log_changed = 0;
+        for commit in &mut log {
+            log_changed += repoint(&mut commit.tree);
+            for path in &commit.files {
+                if commit.tree.contains_key(path) {
+                    continue;
+                }
+                if let Some(copy) = legacy.get(&legacy_name(path)) {
+                    commit.tree.insert(path.clone(), copy.id.clone());
+                    log_changed += 1;
+                }
+            }
+        }
+        if log_changed > 0 {
+            let mut content = String::new();
+            for commit in &log {
+                content.push_str(&serde_json::to_string(commit)?);
+                content.push('\n');
+            }
+            write_atomic(&self.rune_dir.join("log.jsonl"), content.as_bytes())?;
+        }
+        report.references_updated += log_changed;
+
+        // Rewriting ids changes no staged content, so this isn't an index
+        // write worth an undo step
+        let mut index_files = vec![self.rune_dir.join("index.json")];
+        if let Ok(dir) = fs::read_dir(self.rune_dir.join("index_history")) {
+            index_files.extend(dir.flatten().map(|e| e.path()));
+        }
+        for file in index_files {
+            let Ok(bytes) = fs::read(&file) else { continue };
+            let Ok(mut index) = serde_json::from_slice::<Index>(&bytes) else { continue };
+            let changed = repoint(&mut index.blobs);
+            if changed > 0 {
+                write_atomic(&file, &serde_json::to_vec_pretty(&index)?)?;
+                report.references_updated += changed;
+            }
+        }
+
+        let mut freed = 0u64;
+        for copy in misfiled.iter().map(|(_, copy)| copy).chain(legacy.values()) {
+            fs::remove_file(&copy.file)?;
+            freed += copy.size;
+            report.duplicates_removed += 1;
+        }
+        report.bytes_saved = freed.saturating_sub(written);
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_repo;
+    use rune_core::{Author, Commit};
+
+    fn physical_copies(store: &Store, content: &[u8]) -> usize {
+        walkdir::WalkDir::new(store.rune_dir.join("objects"))
+            .into_iter()
+            .flatten()
+            .filter(|e| e.file_type().is_file() && fs::read(e.path()).unwrap() == content)
+            .count()
+    }
+
+    fn seed(store: &Store, id: &str, parent: Option<&str>, files: &[&str], tree: &[(&str, &str)]) {
+        let author = Author { name: "A".into(), email: "a@example.com".into() };
+        store
+            .append_log(&Commit {
+                id: id.into(),
+                message: id.into(),
+                committer: author.clone(),
+                author,
+                time: 1_000,
+                parent: parent.map(str::to_string),
+                merge_parent: None,
+                files: files.iter().map(|f| f.to_string()).collect(),
+                branch: "main".into(),
+                tree: tree.iter().map(|(p, b)| (p.to_string(), b.to_string())).collect(),
+                modes: BTreeMap::new(),
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_dedup_collapses_copies_under_other_keys() {
+        let (_temp, store) = temp_repo();
+        let content = b"shared content\n".repeat(20);
+        let canonical = store.write_blob(&content).unwrap();
+
+        // The same bytes filed under a key that isn't an id and under the
+        // old path-keyed name, each referenced from history and the index
+        let stray = "abcopy-of-shared".to_string();
+        let stray_path = store.blob_path(&stray);
+        fs::create_dir_all(stray_path.parent().unwrap()).unwrap();
+        fs::write(&stray_path, &content).unwrap();
+        fs::write(store.rune_dir.join("objects").join(legacy_name("docs/old.txt")), &content).unwrap();
+        seed(&store, "c1", None, &["docs/old.txt"], &[]);
+        seed(&store, "c2", Some("c1"), &["a.txt", "b.txt"], &[("a.txt", &canonical), ("b.txt", &stray)]);
+        let mut index = Index::default();
+        index.blobs.insert("b.txt".into(), stray.clone());
+        store.write_index(&index).unwrap();
+        assert_eq!(physical_copies(&store, &content), 3);
+
+        let report = store.dedup_objects(&store.lock_repo().unwrap()).unwrap();
+        assert_eq!(report.objects_scanned, 3);
+        assert_eq!(report.duplicates_removed, 2);
+        assert_eq!(report.bytes_saved, 2 * content.len() as u64);
+        assert_eq!(report.references_updated, 3);
+        assert_eq!(physical_copies(&store, &content), 1);
+
+        for path in ["a.txt", "b.txt", "docs/old.txt"] {
+            let blob = store.blob_at("c2", path).unwrap();
+            assert_eq!(store.read_blob(&blob).unwrap(), content);
+        }
+        assert_eq!(store.read_index().unwrap().blobs["b.txt"], canonical);
+
+        // Nothing left to collapse
+        let again = store.dedup_objects(&store.lock_repo().unwrap()).unwrap();
+        assert_eq!((again.duplicates_removed, again.references_updated), (0, 0));
+    }
+
+    #[test]
+    fn test_dedup_moves_sole_legacy_copy() {
+        let (_temp, store) = temp_repo();
+        fs::write(store.rune_dir.join("objects").join(legacy_name("notes.txt")), b"only copy").unwrap();
+        // Not listed by any commit, so not ours to touch
+        fs::write(store.rune_dir.join("objects").join(legacy_name("orphan.txt")), b"only copy").unwrap();
+        seed(&store, "c1", None, &["notes.txt"], &[]);
+
+        let report = store.dedup_objects(&store.lock_repo().unwrap()).unwrap();
+        assert_eq!((report.duplicates_removed, report.bytes_saved), (1, 0));
+        assert_eq!(store.read_blob(&store.blob_at("c1", "notes.txt").unwrap()).unwrap(), b"only copy");
+        assert!(store.rune_dir.join("objects").join(legacy_name("orphan.txt")).exists());
+    }
+
+    #[test]
+    fn test_dedup_reports_damaged_objects_without_refiling_them() {
+        let (_temp, store) = temp_repo();
+        let id = store.write_blob(b"recorded content").unwrap();
+        fs::write(store.blob_path(&id), b"bit rot").unwrap();
+        seed(&store, "c1", None, &["a.txt"], &[("a.txt", &id)]);
+
+        let report = store.dedup_objects(&store.lock_repo().unwrap()).unwrap();
+        assert_eq!(report.corrupt, [id.as_str()]);
+        assert_eq!((report.duplicates_removed, report.references_updated), (0, 0));
+        assert_eq!(store.blob_at("c1", "a.txt").unwrap(), id);
+        assert_eq!(fs::read(store.blob_path(&id)).unwrap(), b"bit rot");
+        let rehashed = blake3::hash(b"bit rot").to_hex().to_string();
+        assert!(!store.blob_path(&rehashed).exists());
+    }
+}
@@ -0,0 +1,511 @@
+//! Diffs between any two of a commit, the index and the working tree
+//!
+//! The index side is HEAD's tree with the staged blobs laid over it, so
+//! `Index -> Worktree` shows what is not staged yet and `Commit(HEAD) ->
+//! Index` shows exactly what the next commit will contain. Worktree content
+//! is compared after line-ending normalization, the same way it is staged.
+
+use crate::renames::detect_dir_renames;
+use crate::{is_same_or_below, SparseDirs, Store, DELETED_BLOB, NULL_ID};
+use anyhow::{bail, Result};
+use rune_core::attributes::{AttributeEngine, DiffHandling};
+use rune_core::eol::{looks_binary, EolFilter};
+use rune_core::ignore::IgnoreEngine;
+use rune_delta::hunks::line_hunks;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+
+/// Files sharing at least this much content count as a rename
+pub(crate) const RENAME_THRESHOLD: f64 = 0.5;
+
+/// One side of a diff
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffSource {
+    /// The tree as of a commit; `NULL_ID` is the empty tree of an unborn branch
+    Commit(String),
+    /// HEAD with the staged changes applied
+    Index,
+    /// Tracked files as they are on disk
+    Worktree,
+}
+
+/// What happened to one path between two sources
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathChange {
+    Added(String),
+    Deleted(String),
+    Modified(String),
+    Renamed { from: String, to: String, similarity: f64 },
+}
+
+impl PathChange {
+    /// Path on the `to` side, or the removed path for deletions
+    pub fn path(&self) -> &str {
+        match self {
+            PathChange::Added(p) | PathChange::Deleted(p) | PathChange::Modified(p) => p,
+            PathChange::Renamed { to, .. } => to,
+        }
+    }
+}
+
+impl Store {
+    /// The commit side named by `rev`: `HEAD`, a branch, a tag or a commit id
+    /// (prefix). `HEAD` on an unborn branch is the empty tree.
+    pub fn commit_source(&self, rev: &str) -> Result<DiffSource> {
+        Ok(DiffSource::Commit(self.revision_id(rev)?))
+    }
+
+    /// Full commit id `rev` names, `NULL_ID` for `HEAD` on an unborn branch.
+    /// Tags may hold an abbreviated id, so every target is looked up.
+    pub(crate) fn revision_id(&self, rev: &str) -> Result<String> {
+        let target = if rev == "HEAD" {
+            match self.head_commit() {
+                Some(id) => id,
+                None => return Ok(NULL_ID.to_string()),
+            }
+        } else if let Some(id) = self.read_ref(&format!("refs/heads/{}", rev)) {
+            id
+        } else if let Some(id) = self.tag_commit(rev) {
+            id
+        } else {
+            rev.to_string()
+        };
+        Ok(self.resolve_commit(&target)?.id)
+    }
+
+    /// Paths that differ between `from` and `to`, limited to `pathspec`
+    /// (files or directories; empty means everything)
+    pub fn changed_paths(&self, from: &DiffSource, to: &DiffSource, pathspec: &[String]) -> Result<Vec<PathChange>> {
+        let filter = self.eol_filter()?;
+        let (old, new) = self.diff_trees(from, to, pathspec, &filter)?;
+        self.tree_changes(from, to, &old, &new, &filter)
+    }
+
+    /// `changed_paths` for trees `diff_trees` already produced
+    fn tree_changes(
+        &self,
+        from: &DiffSource,
+        to: &DiffSource,
+        old: &BTreeMap<String, String>,
+        new: &BTreeMap<String, String>,
+        filter: &EolFilter,
+    ) -> Result<Vec<PathChange>> {
+        let mut changes = Vec::new();
+        let mut deleted = HashMap::new();
+        let mut added = HashMap::new();
+        for path in old.keys().chain(new.keys()).collect::<BTreeSet<_>>() {
+            match (old.get(path), new.get(path)) {
+                (Some(a), Some(b)) if a != b => changes.push(PathChange::Modified(path.clone())),
+                (Some(_), None) => {
+                    deleted.insert(path.clone(), self.source_content(from, path, &old[path], filter)?);
+                }
+                (None, Some(_)) => {
+                    added.insert(path.clone(), self.source_content(to, path, &new[path], filter)?);
+                }
+                _ => {}
+            }
+        }
+
+        for rename in rune_delta::detect_renames(&deleted, &added, RENAME_THRESHOLD) {
+            deleted.remove(&rename.old_path);
+            added.remove(&rename.new_path);
+            changes.push(PathChange::Renamed {
+                from: rename.old_path,
+                to: rename.new_path,
+                similarity: rename.similarity,
+            });
+        }
+        changes.extend(deleted.into_keys().map(PathChange::Deleted));
+        changes.extend(added.into_keys().map(PathChange::Added));
+        changes.sort_by(|a, b| a.path().cmp(b.path()));
+        Ok(changes)
+    }
+
+    /// Unified diff from `from` to `to`, limited to `pathspec`. A renamed
+    /// directory is one summary listing the files that didn't move with it;
+    /// the files that moved unchanged get no entry of their own.
+    pub fn diff_between(&self, from: &DiffSource, to: &DiffSource, pathspec: &[String]) -> Result<String> {
+        // One read of `.runeattributes` serves both the line ending
+        // normalization and the diff handling
+        let attributes = AttributeEngine::new(&self.root)?;
+        let filter = EolFilter::new(self.config().core.autocrlf, Some(attributes.clone()));
+        let (old, new) = self.diff_trees(from, to, pathspec, &filter)?;
+        let changes = self.tree_changes(from, to, &old, &new, &filter)?;
+        let dirs = detect_dir_renames(&old, &new, &changes);
+        let mut out = String::new();
+        for dir in &dirs {
+            out.push_str(&format!(
+                "diff --rune a/{0}/ b/{1}/\nrename directory from {0}\nrename directory to {1}\n{2} files renamed\n",
+                dir.from, dir.to, dir.files
+            ));
+            for path in &dir.exceptions {
+                out.push_str(&format!("exception {}\n", path));
+            }
+        }
+        for change in changes {
+            let (old_path, new_path) = match &change {
+                PathChange::Added(p) | PathChange::Deleted(p) | PathChange::Modified(p) => (p.as_str(), p.as_str()),
+                PathChange::Renamed { from, to, .. } => (from.as_str(), to.as_str()),
+            };
+            if matches!(change, PathChange::Renamed { .. })
+                && old[old_path] == new[new_path]
+                && dirs.iter().any(|dir| dir.covers(old_path, new_path))
+            {
+                continue;
+            }
+            out.push_str(&format!("diff --rune a/{} b/{}\n", old_path, new_path));
+            match &change {
+                PathChange::Added(_) => out.push_str("new file\n"),
+                PathChange::Deleted(_) => out.push_str("deleted file\n"),
+                PathChange::Renamed { similarity, .. } => out.push_str(&format!(
+                    "similarity index {}%\nrename from {}\nrename to {}\n",
+                    (similarity * 100.0).round(),
+                    old_path,
+                    new_path
+                )),
+                PathChange::Modified(_) => {}
+            }
+
+            let before = match &change {
+                PathChange::Added(_) => Vec::new(),
+                _ => self.source_content(from, old_path, &old[old_path], &filter)?,
+            };
+            let after = match &change {
+                PathChange::Deleted(_) => Vec::new(),
+                _ => self.source_content(to, new_path, &new[new_path], &filter)?,
+            };
+            if before == after {
+                continue;
+            }
+            match attributes.diff_handling(new_path) {
+                DiffHandling::Generated => {
+                    out.push_str(&format!("Generated file {} changed, diff suppressed\n", new_path));
+                    continue;
+                }
+                DiffHandling::Binary => {
+                    out.push_str(&format!("Binary files a/{} and b/{} differ\n", old_path, new_path));
+                    continue;
+                }
+                DiffHandling::Text if looks_binary(&before) || looks_binary(&after) => {
+                    out.push_str(&format!("Binary files a/{} and b/{} differ\n", old_path, new_path));
+                    continue;
+                }
+                DiffHandling::Text => {}
+            }
+            let side = |exists: bool, prefix: &str, path: &str| {
+                if exists {
+                    format!("{}/{}", prefix, path)
+                } else {
+                    "/dev/null".to_string()
+                }
+            };
+            out.push_str(&format!("--- {}\n", side(!matches!(change, PathChange::Added(_)), "a", old_path)));
+            out.push_str(&format!("+++ {}\n", side(!matches!(change, PathChange::Deleted(_)), "b", new_path)));
+            let hunks = line_hunks(&String::from_utf8_lossy(&before), &String::from_utf8_lossy(&after), 3);
+            for hunk in hunks {
+                out.push_str(&hunk.to_string());
+            }
+        }
+        Ok(out)
+    }
+
+    /// Unified diff of one file from its version in `rev` to the working
+    /// tree. A file missing on disk shows as deleted; a file `rev` does not
+    /// contain is an error rather than an empty diff.
+    pub fn diff_file(&self, rev: &str, path: &str) -> Result<String> {
+        let path = path.replace('\\', "/");
+        let path = path.trim_start_matches("./");
+        let id = self.revision_id(rev)?;
+        if id == NULL_ID || !self.tree_snapshot(&id).contains_key(path) {
+            bail!("{} is not present in {}", path, rev);
+        }
+        self.diff_between(&DiffSource::Commit(id), &DiffSource::Worktree, &[path.to_string()])
+    }
+
+    /// Path -> blob id on both sides. Worktree paths are the files tracked by
+    /// either side or the index; their ids are hashes of normalized content.
+    pub(crate) fn diff_trees(
+        &self,
+        from: &DiffSource,
+        to: &DiffSource,
+        pathspec: &[String],
+        filter: &EolFilter,
+    ) -> Result<(BTreeMap<String, String>, BTreeMap<String, String>)> {
+        let index = self.index_tree()?;
+        let mut old = self.source_tree(from, &index)?;
+        let mut new = self.source_tree(to, &index)?;
+        let tracked: BTreeSet<String> = index.keys().chain(old.keys()).chain(new.keys()).cloned().collect();
+        if *from == DiffSource::Worktree {
+            old = self.worktree_tree(&tracked, &index, filter)?;
+        }
+        if *to == DiffSource::Worktree {
+            new = self.worktree_tree(&tracked, &index, filter)?;
+        }
+        let wanted = |path: &String| {
+            pathspec.is_empty()
+                || pathspec.iter().any(|spec| {
+                    let spec = spec.trim_end_matches('/');
+                    spec == "." || spec.is_empty() || is_same_or_below(path, spec)
+                })
+        };
+        old.retain(|path, _| wanted(path));
+        new.retain(|path, _| wanted(path));
+        Ok((old, new))
+    }
+
+    /// HEAD's tree with staged changes applied
+    fn index_tree(&self) -> Result<BTreeMap<String, String>> {
+        let mut tree = self.head_commit().map(|id| self.tree_snapshot(&id)).unwrap_or_default();
+        for (path, blob) in self.read_index()?.blobs {
+            if blob == DELETED_BLOB {
+                tree.remove(&path);
+            } else {
+                tree.insert(path, blob);
+            }
+        }
+        Ok(tree)
+    }
+
+    /// Tree of a commit or the index; the worktree is filled in by the caller
+    fn source_tree(&self, source: &DiffSource, index: &BTreeMap<String, String>) -> Result<BTreeMap<String, String>> {
+        Ok(match source {
+            DiffSource::Commit(id) if id == NULL_ID => BTreeMap::new(),
+            DiffSource::Commit(id) => self.tree_snapshot(&self.resolve_commit(id)?.id),
+            DiffSource::Index => index.clone(),
+            DiffSource::Worktree => BTreeMap::new(),
+        })
+    }
+
+    /// Hashes of the `tracked` files on disk; a directory the sparse index
+    /// leaves out isn't read and counts as matching `index`
+    fn worktree_tree(
+        &self,
+        tracked: &BTreeSet<String>,
+        index: &BTreeMap<String, String>,
+        filter: &EolFilter,
+    ) -> Result<BTreeMap<String, String>> {
+        let sparse = SparseDirs::of(&self.read_index()?);
+        let mut tree = BTreeMap::new();
+        for path in tracked {
+            if sparse.covers(path) {
+                if let Some(blob) = index.get(path) {
+                    tree.insert(path.clone(), blob.clone());
+                }
+                continue;
+            }
+            let full = self.root.join(path);
+            if full.is_file() {
+                let content = self.worktree_content(path, filter)?;
+                tree.insert(path.clone(), blake3::hash(&content).to_hex().to_string());
+            }
+        }
+        Ok(tree)
+    }
+
+    /// Files of `head_tree` gone from disk without being staged, paired
+    /// with the `untracked` files holding their content. Returns the
+    /// renames and the untracked files left over.
+    pub(crate) fn worktree_renames(
+        &self,
+        head_tree: &BTreeMap<String, String>,
+        staged: &BTreeMap<String, i64>,
+        untracked: Vec<String>,
+        filter: &EolFilter,
+    ) -> Result<(Vec<PathChange>, Vec<String>)> {
+        let mut deleted = HashMap::new();
+        for (path, blob) in head_tree {
+            if !staged.contains_key(path) && !self.root.join(path).exists() {
+                deleted.insert(path.clone(), self.read_blob(blob)?);
+            }
+        }
+        if deleted.is_empty() || untracked.is_empty() {
+            return Ok((Vec::new(), untracked));
+        }
+
+        let mut ignore = IgnoreEngine::new(&self.root).ok();
+        let mut added = HashMap::new();
+        for path in &untracked {
+            if ignore.as_mut().is_some_and(|engine| engine.should_ignore(self.root.join(path))) {
+                continue;
+            }
+            if let Ok(content) = self.worktree_content(path, filter) {
+                added.insert(path.clone(), content);
+            }
+        }
+
+        let mut renames: Vec<PathChange> = rune_delta::detect_renames(&deleted, &added, RENAME_THRESHOLD)
+            .into_iter()
+            .map(|rename| PathChange::Renamed {
+                from: rename.old_path,
+                to: rename.new_path,
+                similarity: rename.similarity,
+            })
+            .collect();
+        renames.sort_by(|a, b| a.path().cmp(b.path()));
+        let untracked = untracked
+            .into_iter()
+            .filter(|path| !renames.iter().any(|r| r.path() == path))
+            .collect();
+        Ok((renames, untracked))
+    }
+
+    fn worktree_content(&self, path: &str, filter: &EolFilter) -> Result<Vec<u8>> {
+        let content = fs::read(self.root.join(path))?;
+        Ok(filter.to_store(Path::new(path), &content).into_owned())
+    }
+
+    fn source_content(&self, source: &DiffSource, path: &str, id: &str, filter: &EolFilter) -> Result<Vec<u8>> {
+        match source {
+            DiffSource::Worktree => self.worktree_content(path, filter),
+            _ => self.read_blob(id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{author, temp_repo};
+
+    fn head(store: &Store) -> DiffSource {
+        DiffSource::Commit(store.head_commit().unwrap())
+    }
+
+    #[test]
+    fn test_three_targets_show_three_contents() {
+        let (temp, store) = temp_repo();
+        fs::write(temp.path().join("a.txt"), "one\n").unwrap();
+        fs::write(temp.path().join("b.txt"), "bee\n").unwrap();
+        store.stage_file("a.txt").unwrap();
+        store.stage_file("b.txt").unwrap();
+        store.commit("Start", author()).unwrap();
+
+        fs::write(temp.path().join("a.txt"), "two\n").unwrap();
+        store.stage_file("a.txt").unwrap();
+        fs::write(temp.path().join("a.txt"), "three\n").unwrap();
+
+        let unstaged = store.diff_between(&DiffSource::Index, &DiffSource::Worktree, &[]).unwrap();
+        assert!(unstaged.contains("-two\n+three\n"), "{}", unstaged);
+
+        let staged = store.diff_between(&head(&store), &DiffSource::Index, &[]).unwrap();
+        assert!(staged.contains("-one\n+two\n"), "{}", staged);
+        assert!(!staged.contains("three"));
+
+        let status = store.status().unwrap();
+        assert_eq!(status.staging, ["a.txt"]);
+        assert_eq!(status.working, ["a.txt"]);
+
+        let all = store.diff_between(&head(&store), &DiffSource::Worktree, &[]).unwrap();
+        assert!(all.contains("--- a/a.txt\n+++ b/a.txt\n@@ -1,1 +1,1 @@\n-one\n+three\n"), "{}", all);
+        assert!(!all.contains("b.txt"));
+    }
+
+    #[test]
+    fn test_status_reports_an_edited_rename_once() {
+        let (temp, store) = temp_repo();
+        let original = "alpha\nbeta\ngamma\ndelta\nepsilon\n";
+        fs::write(temp.path().join("old.txt"), original).unwrap();
+        store.stage_file("old.txt").unwrap();
+        store.commit("Add old", author()).unwrap();
+
+        let edited = original.replace("gamma", "GAMMA");
+        fs::remove_file(temp.path().join("old.txt")).unwrap();
+        fs::write(temp.path().join("new.txt"), &edited).unwrap();
+
+        let status = store.status().unwrap();
+        let expected = rune_delta::calculate_similarity(original.as_bytes(), edited.as_bytes());
+        assert!(expected > RENAME_THRESHOLD && expected < 1.0);
+        assert_eq!(
+            status.renamed,
+            [PathChange::Renamed { from: "old.txt".into(), to: "new.txt".into(), similarity: expected }]
+        );
+        assert!(status.untracked.is_empty());
+        assert!(status.working.is_empty());
+
+        // Unrelated content stays a separate untracked file
+        fs::write(temp.path().join("other.txt"), "something else entirely").unwrap();
+        let status = store.status().unwrap();
+        assert_eq!(status.renamed.len(), 1);
+        assert_eq!(status.untracked, ["other.txt"]);
+    }
+
+    #[test]
+    fn test_pathspec_limits_every_target() {
+        let (temp, store) = temp_repo();
+        fs::create_dir_all(temp.path().join("src")).unwrap();
+        for path in ["src/lib.rs", "README.md"] {
+            fs::write(temp.path().join(path), "v1\n").unwrap();
+            store.stage_file(path).unwrap();
+        }
+        store.commit("Start", author()).unwrap();
+        for path in ["src/lib.rs", "README.md"] {
+            fs::write(temp.path().join(path), "v2\n").unwrap();
+            store.stage_file(path).unwrap();
+            fs::write(temp.path().join(path), "v3\n").unwrap();
+        }
+
+        let spec = vec!["src".to_string()];
+        for (from, to) in [
+            (DiffSource::Index, DiffSource::Worktree),
+            (head(&store), DiffSource::Index),
+            (head(&store), DiffSource::Worktree),
+        ] {
+            let changes = store.changed_paths(&from, &to, &spec).unwrap();
+            assert_eq!(changes, [PathChange::Modified("src/lib.rs".into())], "{:?} -> {:?}", from, to);
+            let diff = store.diff_between(&from, &to, &spec).unwrap();
+            assert!(!diff.contains("README"), "{}", diff);
+        }
+    }
+
+    #[test]
+    fn test_staged_rename_and_unborn_head() {
+        let (temp, store) = temp_repo();
+        let body = "fn main() {\n    println!(\"hello\");\n}\n";
+        fs::write(temp.path().join("old.rs"), body).unwrap();
+        store.stage_file("old.rs").unwrap();
+        let initial = store.diff_between(&DiffSource::Commit(NULL_ID.into()), &DiffSource::Index, &[]).unwrap();
+        assert!(initial.contains("new file\n--- /dev/null\n+++ b/old.rs\n"), "{}", initial);
+        store.commit("Start", author()).unwrap();
+
+        fs::rename(temp.path().join("old.rs"), temp.path().join("new.rs")).unwrap();
+        store.stage_file("old.rs").unwrap();
+        store.stage_file("new.rs").unwrap();
+        let changes = store.changed_paths(&head(&store), &DiffSource::Index, &[]).unwrap();
+        assert!(matches!(&changes[..], [PathChange::Renamed { from, to, .. }] if from == "old.rs" && to == "new.rs"));
+        let diff = store.diff_between(&head(&store), &DiffSource::Index, &[]).unwrap();
+        assert_eq!(diff, "diff --rune a/old.rs b/new.rs\nsimilarity index 100%\nrename from old.rs\nrename to new.rs\n");
+    }
+
+    #[test]
+    fn test_diff_file_against_head_and_older_commit() {
+        let (temp, store) = temp_repo();
+        fs::write(temp.path().join("notes.txt"), "alpha\nbeta\n").unwrap();
+        store.stage_file("notes.txt").unwrap();
+        let first = store.commit("First", author()).unwrap();
+        fs::write(temp.path().join("notes.txt"), "alpha\nbeta\ngamma\n").unwrap();
+        store.stage_file("notes.txt").unwrap();
+        store.commit("Second", author()).unwrap();
+        fs::write(temp.path().join("notes.txt"), "alpha\nBETA\ngamma\n").unwrap();
+
+        let against_head = store.diff_file("HEAD", "notes.txt").unwrap();
+        assert!(against_head.contains("--- a/notes.txt\n+++ b/notes.txt\n"), "{}", against_head);
+        assert!(against_head.contains(" alpha\n-beta\n+BETA\n gamma\n"), "{}", against_head);
+
+        let against_first = store.diff_file(&first.id[..8], "notes.txt").unwrap();
+        assert!(against_first.contains(" alpha\n-beta\n+BETA\n+gamma\n"), "{}", against_first);
+    }
+
+    #[test]
+    fn test_diff_file_requires_path_in_commit() {
+        let (temp, store) = temp_repo();
+        fs::write(temp.path().join("a.txt"), "one\n").unwrap();
+        store.stage_file("a.txt").unwrap();
+        store.commit("Start", author()).unwrap();
+        fs::write(temp.path().join("b.txt"), "new\n").unwrap();
+
+        let err = store.diff_file("HEAD", "b.txt").unwrap_err();
+        assert!(err.to_string().contains("not present"), "{}", err);
+        assert_eq!(store.diff_file("HEAD", "./a.txt").unwrap(), "");
+    }
+}
@@ -0,0 +1,73 @@
+//! Directories with nothing in them, which would otherwise vanish between
+//! checkouts. With `core.keep_empty_dirs`, adding an empty directory puts
+//! a `.runekeep` marker in it and stages that instead. The marker is an
+//! ordinary empty file, so committing it records the directory and
+//! checking it out creates the directory again.
+
+use crate::Store;
+use anyhow::Result;
+use std::fs;
+
+/// File name of the marker kept in an otherwise empty directory
+pub const KEEP_MARKER: &str = ".runekeep";
+
+impl Store {
+    /// Write a marker into each empty directory at or below `rel` and return
+    /// the markers' paths. Links aren't followed.
+    pub(crate) fn mark_empty_dirs(&self, rel: &str) -> Result<Vec<String>> {
+        let mut markers = Vec::new();
+        let internal = self.internal_paths();
+        let walk = walkdir::WalkDir::new(self.root.join(rel)).into_iter().filter_entry(|entry| !internal.contains(entry.path()));
+        for entry in walk {
+            let entry = entry?;
+            if !entry.file_type().is_dir() || fs::read_dir(entry.path())?.next().is_some() {
+                continue;
+            }
+            let marker = entry.path().join(KEEP_MARKER);
+            fs::write(&marker, "")?;
+            markers.push(self.checked_path(&marker)?);
+        }
+        Ok(markers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KEEP_MARKER;
+    use crate::test_support::{author, temp_repo};
+    use std::fs;
+
+    #[test]
+    fn test_empty_directory_comes_back_on_checkout() {
+        let (temp_dir, store) = temp_repo();
+        fs::write(temp_dir.path().join("a.txt"), "a\n").unwrap();
+        store.stage_file("a.txt").unwrap();
+        store.commit("base", author()).unwrap();
+
+        let mut cfg = store.config();
+        cfg.core.keep_empty_dirs = true;
+        store.write_config(&cfg).unwrap();
+        fs::create_dir_all(temp_dir.path().join("assets/empty")).unwrap();
+        fs::write(temp_dir.path().join("assets/readme.txt"), "art\n").unwrap();
+        store.stage_file("assets").unwrap();
+        let marker = format!("assets/empty/{}", KEEP_MARKER);
+        assert!(store.read_index().unwrap().blobs.contains_key(&marker));
+        let kept = store.commit("keep the empty directory", author()).unwrap();
+        assert!(kept.tree.contains_key(&marker));
+
+        // Back to a tree without it, then forward again as a fresh checkout
+        store.rollback(1, true).unwrap();
+        fs::remove_dir_all(temp_dir.path().join("assets")).unwrap();
+        store.fast_forward(&kept.id, "test").unwrap();
+        assert!(temp_dir.path().join("assets/empty").is_dir());
+    }
+
+    #[test]
+    fn test_empty_directories_are_skipped_unless_enabled() {
+        let (temp_dir, store) = temp_repo();
+        fs::create_dir(temp_dir.path().join("empty")).unwrap();
+        store.stage_file("empty").unwrap();
+        assert!(store.read_index().unwrap().blobs.is_empty());
+        assert!(!temp_dir.path().join("empty").join(KEEP_MARKER).exists());
+    }
+}
@@ -0,0 +1,272 @@
+//! Failures callers may want to tell apart
+//!
+//! `Store` methods still return `anyhow::Result`; the errors below travel
+//! inside it and can be recovered with `err.downcast_ref::<StoreError>()`.
+//! The messages match what the CLI has always printed.
+
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum StoreError {
+    #[error("{reason}")]
+    NotARepo { path: PathBuf, reason: String },
+    #[error("No commits yet")]
+    NoCommits,
+    #[error("Commit '{0}' not found")]
+    CommitNotFound(String),
+    #[error("Commit '{prefix}' is ambiguous: matches {}", candidates.join(", "))]
+    AmbiguousCommit { prefix: String, candidates: Vec<String> },
+    #[error("Branch '{0}' does not exist")]
+    BranchNotFound(String),
+    #[error("Branch '{0}' already exists")]
+    BranchExists(String),
+    #[error("refusing to merge '{0}': it shares no history with the current branch; use --allow-unrelated-histories")]
+    UnrelatedHistories(String),
+    #[error("Tag '{0}' does not exist")]
+    TagNotFound(String),
+    #[error("Tag '{0}' is not signed")]
+    TagNotSigned(String),
+    #[error("Tag '{0}' does not match its signature; it was changed after it was signed")]
+    BadTagSignature(String),
+    #[error("Tag '{tag}' is signed by {signer} with key {key}, which [signing] allowed_signers does not list for them")]
+    UntrustedTagSigner { tag: String, signer: String, key: String },
+    #[error("Not on a branch")]
+    NotOnBranch,
+    #[error("nothing to commit")]
+    NothingToCommit,
+    #[error("Please commit or stash your changes before merging.\nUncommitted changes in working directory")]
+    UncommittedChanges,
+    #[error("cannot {action} while a merge, rebase, revert or am is in progress")]
+    MergeInProgress { action: &'static str },
+    #[error("No merge in progress")]
+    NoMergeInProgress,
+    #[error("No rebase in progress")]
+    NoRebaseInProgress,
+    #[error("No revert in progress")]
+    NoRevertInProgress,
+    #[error("No am in progress")]
+    NoAmInProgress,
+    #[error("Please resolve all conflicts before continuing{}", listed(.0))]
+    UnresolvedConflicts(Vec<String>),
+    #[error("'{0}' is not in conflict")]
+    NotInConflict(String),
+    #[error("'{name}' is not a valid ref name: {rule}")]
+    InvalidRefName { name: String, rule: crate::RefNameRule },
+    #[error("{hook} hook rejected the operation ({status}); use --no-verify to skip it")]
+    HookRejected { hook: String, status: String },
+    #[error("'{path}' is in {dir}/, which the sparse index leaves out; activate a workspace root there or set sparse.on_stage = \"expand\"")]
+    OutsideSparse { path: String, dir: String },
+    #[error("import would move '{name}' from {existing} to {new}, which does not build on it; use --force to move it anyway")]
+    RefWouldMove { name: String, existing: String, new: String },
+    #[error("'{0}' is outside the repository")]
+    PathOutsideRepo(String),
+    #[error("'{path}' is {size} bytes, over core.max_object_size ({limit} bytes); track it with `rune lfs track`, set core.auto_lfs_threshold to move large files to LFS on commit, or unstage it")]
+    ObjectTooLarge { path: String, size: u64, limit: u64 },
+    #[error("committed and staged objects take {size} bytes, over core.max_object_store_bytes ({limit} bytes); move large files to LFS with `rune lfs track`, reclaim space with `rune maintenance run --task gc`, or raise the quota")]
+    ObjectStoreOverQuota { size: u64, limit: u64 },
+    #[error("cancelled; the working tree was put back as it was")]
+    Cancelled,
+    #[error("object {id} is corrupt in {} at offset {offset} and has no healthy copy", pack.display())]
+    CorruptObject { id: String, pack: PathBuf, offset: u64 },
+}
+
+/// `": a, b"` for a non-empty list of paths
+fn listed(paths: &[String]) -> String {
+    if paths.is_empty() {
+        String::new()
+    } else {
+        format!(": {}", paths.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Store;
+    use crate::test_support::{author, temp_repo};
+    use std::fs;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    fn store_error(err: anyhow::Error) -> StoreError {
+        err.downcast::<StoreError>().expect("expected a StoreError")
+    }
+
+    #[test]
+    fn test_not_a_repo() {
+        let temp_dir = TempDir::new().unwrap();
+        let Err(err) = Store::discover(temp_dir.path()) else {
+            panic!("discover should fail outside a repository");
+        };
+        let err = store_error(err);
+        assert!(matches!(err, StoreError::NotARepo { path, .. } if path == temp_dir.path()));
+    }
+
+    #[test]
+    fn test_nothing_to_commit_and_no_commits() {
+        let (_temp_dir, store) = temp_repo();
+        assert_eq!(store_error(store.commit("empty", author()).unwrap_err()), StoreError::NothingToCommit);
+        assert_eq!(
+            store_error(store.commit_amend("amend", true, author()).unwrap_err()),
+            StoreError::NoCommits
+        );
+    }
+
+    #[test]
+    fn test_max_object_size_refuses_large_files() {
+        let (temp_dir, store) = temp_repo();
+        let mut cfg = store.config();
+        cfg.core.max_object_size = Some(100);
+        store.write_config(&cfg).unwrap();
+        fs::write(temp_dir.path().join("small.txt"), "fits\n").unwrap();
+        fs::write(temp_dir.path().join("big.bin"), vec![0u8; 101]).unwrap();
+        store.stage_file("small.txt").unwrap();
+        store.stage_file("big.bin").unwrap();
+
+        assert_eq!(store.oversized_staged(100).unwrap(), [("big.bin".to_string(), 101)]);
+        let err = store_error(store.commit("too big", author()).unwrap_err());
+        assert_eq!(err, StoreError::ObjectTooLarge { path: "big.bin".into(), size: 101, limit: 100 });
+        assert!(err.to_string().contains("'big.bin' is 101 bytes"), "{}", err);
+        assert!(store.head_commit().is_none());
+
+        store.unstage(&["big.bin".to_string()]).unwrap();
+        store.commit("fits", author()).unwrap();
+    }
+
+    #[test]
+    fn test_object_store_quota_refuses_commits_over_it() {
+        let (temp_dir, store) = temp_repo();
+        let mut cfg = store.config();
+        cfg.core.max_object_store_bytes = Some(1000);
+        store.write_config(&cfg).unwrap();
+        assert_eq!(store.object_store_size().unwrap(), 0);
+
+        fs::write(temp_dir.path().join("small.txt"), "fits\n").unwrap();
+        store.stage_file("small.txt").unwrap();
+        store.commit("small", author()).unwrap();
+        assert_eq!(store.object_store_size().unwrap(), 5);
+
+        fs::write(temp_dir.path().join("big.bin"), vec![7u8; 2000]).unwrap();
+        store.stage_file("big.bin").unwrap();
+        let err = store_error(store.commit("too much", author()).unwrap_err());
+        assert_eq!(err, StoreError::ObjectStoreOverQuota { size: 2005, limit: 1000 });
+        assert!(err.to_string().contains("rune lfs track"), "{}", err);
+        assert_eq!(store.log().len(), 1);
+    }
+
+    #[test]
+    fn test_object_store_quota_ignores_unreachable_objects() {
+        let (temp_dir, store) = temp_repo();
+        let mut cfg = store.config();
+        cfg.core.max_object_store_bytes = Some(1000);
+        store.write_config(&cfg).unwrap();
+
+        // The first staged version is left behind in the store by the second
+        fs::write(temp_dir.path().join("data.bin"), vec![7u8; 2000]).unwrap();
+        store.stage_file("data.bin").unwrap();
+        fs::write(temp_dir.path().join("data.bin"), "small now\n").unwrap();
+        store.stage_file("data.bin").unwrap();
+        assert_eq!(store.object_store_size().unwrap(), 2010);
+        assert_eq!(store.reachable_object_size().unwrap(), 10);
+        store.commit("small", author()).unwrap();
+
+        // Packed objects count by their size in the pack
+        store.repack(false).unwrap();
+        let packed = store.reachable_object_size().unwrap();
+        assert!(packed > 0 && packed < 1000, "{}", packed);
+        fs::write(temp_dir.path().join("more.txt"), "more\n").unwrap();
+        store.stage_file("more.txt").unwrap();
+        store.commit("more", author()).unwrap();
+    }
+
+    #[test]
+    fn test_branch_errors() {
+        let (_temp_dir, store) = temp_repo();
+        store.create_branch("feature").unwrap();
+        assert_eq!(
+            store_error(store.create_branch("feature").unwrap_err()),
+            StoreError::BranchExists("feature".into())
+        );
+        assert_eq!(
+            store_error(store.checkout_branch("missing").unwrap_err()),
+            StoreError::BranchNotFound("missing".into())
+        );
+        assert_eq!(store_error(store.abort_rebase().unwrap_err()), StoreError::NoRebaseInProgress);
+    }
+
+    #[test]
+    fn test_commit_lookup_by_prefix() {
+        let (temp_dir, store) = temp_repo();
+        // 17 commits over 16 hex digits: at least two share a first character
+        let mut ids = Vec::new();
+        for i in 0..17 {
+            fs::write(temp_dir.path().join("f.txt"), format!("{}\n", i)).unwrap();
+            store.stage_file("f.txt").unwrap();
+            ids.push(store.commit(&format!("c{}", i), author()).unwrap().id);
+        }
+        let shared = ids
+            .iter()
+            .map(|id| &id[..1])
+            .find(|p| ids.iter().filter(|id| id.starts_with(*p)).count() > 1)
+            .unwrap();
+
+        match store_error(store.resolve_commit(shared).unwrap_err()) {
+            StoreError::AmbiguousCommit { prefix, candidates } => {
+                assert_eq!(prefix, shared);
+                assert!(candidates.len() > 1 && candidates.iter().all(|c| c.starts_with(shared)));
+            }
+            other => panic!("unexpected error: {other}"),
+        }
+        assert_eq!(store.resolve_commit(&ids[3]).unwrap().id, ids[3]);
+        assert_eq!(
+            store_error(store.resolve_commit("zzzz").unwrap_err()),
+            StoreError::CommitNotFound("zzzz".into())
+        );
+        // Nor does a file lookup settle for one of the candidates
+        assert_eq!(store.blob_at(shared, "f.txt"), None);
+        assert!(store.blob_at(&ids[3], "f.txt").is_some());
+    }
+
+    #[test]
+    fn test_short_object_ids_are_errors() {
+        let (_temp_dir, store) = temp_repo();
+        for id in ["", "a", "ab"] {
+            assert!(store.read_blob(id).is_err(), "{:?}", id);
+        }
+    }
+
+    #[test]
+    fn test_paths_escaping_the_root_are_refused() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("repo");
+        fs::create_dir_all(&root).unwrap();
+        let store = Store::open(&root).unwrap();
+        store.create().unwrap();
+        fs::write(temp_dir.path().join("outside.txt"), "not yours\n").unwrap();
+        let outside = |path: &str| StoreError::PathOutsideRepo(path.into());
+
+        assert_eq!(store_error(store.stage_file("../outside.txt").unwrap_err()), outside("../outside.txt"));
+        assert_eq!(store_error(store.stage_file("a/../../outside.txt").unwrap_err()), outside("a/../../outside.txt"));
+        let absolute = temp_dir.path().join("outside.txt").display().to_string();
+        assert_eq!(store_error(store.stage_file(&absolute).unwrap_err()), outside(&absolute));
+        assert!(store.read_index().unwrap().entries.is_empty());
+
+        // `..` that stays inside is resolved
+        fs::write(root.join("f.txt"), "mine\n").unwrap();
+        store.stage_file("./docs/../f.txt").unwrap();
+        assert_eq!(store.read_index().unwrap().entries.keys().collect::<Vec<_>>(), ["f.txt"]);
+        let id = store.commit("Add f", author()).unwrap().id;
+
+        let err = store.restore_file_from_commit(&id, Path::new("../../escaped.txt")).unwrap_err();
+        assert_eq!(store_error(err), outside("../../escaped.txt"));
+        assert!(!temp_dir.path().join("escaped.txt").exists());
+        assert!(!temp_dir.path().parent().unwrap().join("escaped.txt").exists());
+
+        // A crafted index doesn't get into history
+        let mut idx = store.read_index().unwrap();
+        idx.entries.insert("../../etc/x".into(), 0);
+        store.write_index(&idx).unwrap();
+        assert_eq!(store_error(store.commit("Escape", author()).unwrap_err()), outside("../../etc/x"));
+    }
+}
@@ -0,0 +1,286 @@
+//! The commit graph as a whole, for renderers that draw history rather
+//! than walk one line of it.
+//!
+//! Every commit has an edge to its `parent` and, for merges, one to its
+//! `merge_parent`. `topological_order` lists parents before their children
+//! and otherwise keeps the order commits were written in, so the same
+//! history always comes out the same way.
+
+use crate::{Store, NULL_ID};
+use anyhow::{bail, Result};
+use rune_core::Commit;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+impl Store {
+    /// `(child, parent)` for every parent every commit records, merge
+    /// parents included, in log order
+    pub fn commit_graph_edges(&self) -> Vec<(String, String)> {
+        distinct(self.log())
+            .iter()
+            .flat_map(|c| parents(c).map(move |p| (c.id.clone(), p.to_string())))
+            .collect()
+    }
+
+    /// All commits, each after all of its parents; commits neither orders
+    /// before the other keep their log order
+    pub fn topological_order(&self) -> Vec<Commit> {
+        let commits = distinct(self.log());
+        let position: HashMap<&str, usize> = commits.iter().enumerate().map(|(i, c)| (c.id.as_str(), i)).collect();
+
+        let mut waiting_on = vec![0; commits.len()];
+        let mut children: Vec<Vec<usize>> = vec![Vec::new(); commits.len()];
+        for (i, commit) in commits.iter().enumerate() {
+            // A parent outside the log (a shallow clone's boundary) can't
+            // hold anything back
+            for parent in parents(commit).filter_map(|p| position.get(p)) {
+                waiting_on[i] += 1;
+                children[*parent].push(i);
+            }
+        }
+
+        let mut ready: BTreeSet<usize> = (0..commits.len()).filter(|i| waiting_on[*i] == 0).collect();
+        let mut order = Vec::with_capacity(commits.len());
+        while let Some(i) = ready.pop_first() {
+            for &child in &children[i] {
+                waiting_on[child] -= 1;
+                if waiting_on[child] == 0 {
+                    ready.insert(child);
+                }
+            }
+            order.push(i);
+        }
+        let mut commits: Vec<Option<Commit>> = commits.into_iter().map(Some).collect();
+        order.into_iter().filter_map(|i| commits[i].take()).collect()
+    }
+
+    /// Commits reachable from `to` but not from `from`, through merge
+    /// parents as well as first parents, children before their parents.
+    /// Both ends may be tags, branches, `HEAD` or commit ids.
+    pub fn commits_between(&self, from: &str, to: &str) -> Result<Vec<Commit>> {
+        self.commits_since(Some(from), to)
+    }
+
+    /// `commits_between`, or everything reachable from `to` when `from`
+    /// is `None`
+    pub fn commits_since(&self, from: Option<&str>, to: &str) -> Result<Vec<Commit>> {
+        let Some(to) = self.graph_tip(to)? else {
+            return Ok(Vec::new());
+        };
+        let from = match from {
+            Some(from) => self.graph_tip(from)?,
+            None => None,
+        };
+        let mut order = self.topological_order();
+        let released = reachable(&order, from.as_deref());
+        let wanted = reachable(&order, Some(&to));
+        order.retain(|c| wanted.contains(c.id.as_str()) && !released.contains(c.id.as_str()));
+        order.reverse();
+        Ok(order)
+    }
+
+    /// How many commits `local` has that `upstream` doesn't, and how many
+    /// `upstream` has that `local` doesn't, through merge parents too.
+    /// Either side may be a full ref name such as `refs/remotes/origin/main`
+    /// as well as anything `commits_between` takes; a branch with no
+    /// commits has none to count.
+    pub fn ahead_behind(&self, local: &str, upstream: &str) -> Result<(usize, usize)> {
+        let (local, upstream) = (self.graph_tip(local)?, self.graph_tip(upstream)?);
+        let commits = distinct(self.log());
+        let ours = reachable(&commits, local.as_deref());
+        let theirs = reachable(&commits, upstream.as_deref());
+        Ok((ours.difference(&theirs).count(), theirs.difference(&ours).count()))
+    }
+
+    /// Commit `rev` names, or `None` for a branch without commits.
+    /// `refs/...` names are read as refs.
+    fn graph_tip(&self, rev: &str) -> Result<Option<String>> {
+        let id = if rev.starts_with("refs/") {
+            match self.read_ref(rev) {
+                Some(id) => self.resolve_commit(&id)?.id,
+                // Unborn: the ref file is there but empty
+                None if self.ref_file(rev).is_some_and(|p| p.is_file()) => return Ok(None),
+                None => bail!("ref '{}' does not exist", rev),
+            }
+        } else {
+            self.revision_id(rev)?
+        };
+        Ok((id != NULL_ID).then_some(id))
+    }
+}
+
+/// Ids of `start` and everything it reaches in `commits`
+fn reachable<'a>(commits: &'a [Commit], start: Option<&'a str>) -> HashSet<String> {
+    let by_id: HashMap<&str, &Commit> = commits.iter().map(|c| (c.id.as_str(), c)).collect();
+    let mut seen = HashSet::new();
+    let mut pending: Vec<&str> = start.into_iter().collect();
+    while let Some(id) = pending.pop() {
+        if !seen.insert(id.to_string()) {
+            continue;
+        }
+        if let Some(commit) = by_id.get(id) {
+            pending.extend(parents(commit));
+        }
+    }
+    seen
+}
+
+/// The parents `commit` records, first parent first
+fn parents(commit: &Commit) -> impl Iterator<Item = &str> {
+    commit.parent.as_deref().into_iter().chain(commit.merge_parent.as_deref())
+}
+
+/// `log` without repeats of an id; two commits made in the same second can
+/// share one, and the first written wins as everywhere else
+fn distinct(log: Vec<Commit>) -> Vec<Commit> {
+    let mut seen = HashSet::new();
+    log.into_iter().filter(|c| seen.insert(c.id.clone())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MergeResult, Store, StoreError};
+    use crate::test_support::{author, temp_repo};
+    use std::collections::HashMap;
+    use std::fs;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    fn commit_file(store: &Store, root: &Path, path: &str, message: &str) -> String {
+        fs::write(root.join(path), format!("{}\n", message)).unwrap();
+        store.stage_file(path).unwrap();
+        store.commit(message, author()).unwrap().id
+    }
+
+    /// base, then `left` on main and `right` on topic, joined by a merge
+    fn diamond() -> (TempDir, Store, [String; 4]) {
+        let (temp, store) = temp_repo();
+        let base = commit_file(&store, temp.path(), "base.txt", "base");
+        store.create_branch("topic").unwrap();
+        store.checkout_branch("topic").unwrap();
+        let right = commit_file(&store, temp.path(), "right.txt", "right");
+        store.checkout_branch("main").unwrap();
+        let left = commit_file(&store, temp.path(), "left.txt", "left");
+        assert!(matches!(store.merge_branch("topic", false, None).unwrap(), MergeResult::Success));
+        let merge = store.head_commit().unwrap();
+        (temp, store, [base, left, right, merge])
+    }
+
+    #[test]
+    fn test_edges_include_both_parents_of_a_merge() {
+        let (_temp, store, [base, left, right, merge]) = diamond();
+        let mut edges = store.commit_graph_edges();
+        edges.sort();
+        let mut expected = vec![
+            (left.clone(), base.clone()),
+            (right.clone(), base.clone()),
+            (merge.clone(), left.clone()),
+            (merge.clone(), right.clone()),
+        ];
+        expected.sort();
+        assert_eq!(edges, expected);
+        assert_eq!(store.resolve_commit(&merge).unwrap().merge_parent.as_deref(), Some(right.as_str()));
+    }
+
+    #[test]
+    fn test_topological_order_puts_parents_first() {
+        let (_temp, store, [base, _, _, merge]) = diamond();
+        let order = store.topological_order();
+        assert_eq!(order.len(), 4);
+        assert_eq!(order.first().map(|c| c.id.as_str()), Some(base.as_str()));
+        assert_eq!(order.last().map(|c| c.id.as_str()), Some(merge.as_str()));
+
+        let position: HashMap<&str, usize> = order.iter().enumerate().map(|(i, c)| (c.id.as_str(), i)).collect();
+        for (child, parent) in store.commit_graph_edges() {
+            assert!(position[parent.as_str()] < position[child.as_str()], "{} listed before its parent", child);
+        }
+        let again: Vec<String> = store.topological_order().into_iter().map(|c| c.id).collect();
+        assert_eq!(again, order.iter().map(|c| c.id.clone()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_commits_between_follows_merge_parents() {
+        let (_temp, store, [base, left, right, merge]) = diamond();
+        store.create_lightweight_tag("v1", &right).unwrap();
+        let ids = |from: &str, to: &str| -> Vec<String> {
+            store.commits_between(from, to).unwrap().into_iter().map(|c| c.id).collect()
+        };
+
+        // v1 is on the merged branch: it and base were already released
+        assert_eq!(ids("v1", "HEAD"), [merge.clone(), left.clone()]);
+        // Reached only through the merge parent
+        let from_left = ids(&left, &merge);
+        assert_eq!(from_left.len(), 2);
+        assert_eq!(from_left[0], merge);
+        assert!(from_left.contains(&right));
+        assert!(ids("HEAD", "v1").is_empty());
+        assert_eq!(store.commits_since(None, "v1").unwrap().len(), 2);
+        assert_eq!(store.commits_since(None, "HEAD").unwrap().last().map(|c| c.id.clone()), Some(base));
+        assert!(store.commits_between("nope", "HEAD").is_err());
+    }
+
+    #[test]
+    fn test_ahead_behind_upstream() {
+        let (temp, store) = temp_repo();
+        let base = commit_file(&store, temp.path(), "base.txt", "base");
+        store.create_branch("remote-side").unwrap();
+        store.checkout_branch("remote-side").unwrap();
+        let theirs = commit_file(&store, temp.path(), "theirs.txt", "theirs");
+        store.checkout_branch("main").unwrap();
+        store.write_ref("refs/remotes/origin/main", &base).unwrap();
+        assert_eq!(store.ahead_behind("main", "refs/remotes/origin/main").unwrap(), (0, 0));
+
+        commit_file(&store, temp.path(), "one.txt", "one");
+        let ours = commit_file(&store, temp.path(), "two.txt", "two");
+        store.write_ref("refs/remotes/origin/main", &theirs).unwrap();
+        assert_eq!(store.ahead_behind("main", "refs/remotes/origin/main").unwrap(), (2, 1));
+        assert_eq!(store.ahead_behind("refs/remotes/origin/main", "HEAD").unwrap(), (1, 2));
+
+        // Merging the upstream in leaves only our side ahead
+        assert!(matches!(store.merge_branch("remote-side", false, None).unwrap(), MergeResult::Success));
+        assert_eq!(store.ahead_behind("main", "refs/remotes/origin/main").unwrap(), (3, 0));
+        store.write_ref("refs/remotes/origin/main", &store.head_commit().unwrap()).unwrap();
+        assert_eq!(store.ahead_behind("main", "refs/remotes/origin/main").unwrap(), (0, 0));
+        assert_eq!(store.ahead_behind(&ours, "main").unwrap(), (0, 2));
+
+        assert!(store.ahead_behind("main", "refs/remotes/origin/gone").is_err());
+        store.create_orphan_branch("empty").unwrap();
+        assert_eq!(store.ahead_behind("refs/heads/empty", "main").unwrap(), (0, 5));
+    }
+
+    #[test]
+    fn test_orphan_branch_starts_a_second_root() {
+        let (temp, store) = temp_repo();
+        let main_root = commit_file(&store, temp.path(), "main.txt", "main");
+        fs::write(temp.path().join("draft.txt"), "draft\n").unwrap();
+        store.stage_file("draft.txt").unwrap();
+
+        store.create_orphan_branch("pages").unwrap();
+        assert_eq!(store.current_branch().as_deref(), Some("pages"));
+        assert!(store.head_commit().is_none());
+        assert!(store.read_index().unwrap().entries.is_empty());
+        assert!(temp.path().join("main.txt").exists(), "the working tree is left alone");
+        assert!(store.create_orphan_branch("main").is_err());
+
+        let pages_root = commit_file(&store, temp.path(), "index.html", "pages");
+        assert_eq!(store.resolve_commit(&pages_root).unwrap().parent, None);
+        assert_eq!(store.tree_snapshot(&pages_root).into_keys().collect::<Vec<_>>(), ["index.html"]);
+
+        // Two roots and nothing joining them
+        let roots: Vec<String> =
+            store.topological_order().into_iter().filter(|c| c.parent.is_none()).map(|c| c.id).collect();
+        assert_eq!(roots, [main_root.clone(), pages_root.clone()]);
+        assert!(store.commit_graph_edges().is_empty());
+        assert_eq!(store.merge_base(&main_root, &pages_root), None);
+
+        fs::remove_file(temp.path().join("index.html")).unwrap();
+        fs::remove_file(temp.path().join("draft.txt")).unwrap();
+        store.checkout_branch("main").unwrap();
+        let refused = store.merge_branch("pages", false, None).unwrap_err();
+        assert_eq!(refused.downcast_ref(), Some(&StoreError::UnrelatedHistories("pages".into())));
+        assert!(matches!(store.merge_unrelated_branch("pages", false, None).unwrap(), MergeResult::Success));
+        let merge = store.resolve_commit(&store.head_commit().unwrap()).unwrap();
+        assert_eq!(merge.parent.as_deref(), Some(main_root.as_str()));
+        assert_eq!(merge.merge_parent.as_deref(), Some(pages_root.as_str()));
+        assert_eq!(fs::read_to_string(temp.path().join("index.html")).unwrap(), "pages\n");
+    }
+}
@@ -0,0 +1,198 @@
+//! The history of one file: the commits on HEAD's first-parent line that
+//! changed its content, newest first.
+//!
+//! A commit lists every path that was staged, whether or not its content
+//! changed, so listing alone doesn't count: the blob a commit records for
+//! the path is compared with the one its parent had. Commits written before
+//! blobs were recorded have nothing to compare and count when they list
+//! the path.
+
+use crate::{Store, DELETED_BLOB};
+use anyhow::Result;
+use rune_core::Commit;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Which commits `Store::file_history` returns
+#[derive(Debug, Clone, Default)]
+pub struct FileHistoryOptions {
+    /// Where a commit brought the file in by renaming it, report
+    /// `RenamedFrom` and go on with the old name rather than `Added`
+    pub follow_renames: bool,
+    /// Stop after this many entries
+    pub limit: Option<usize>,
+    /// Only commits at or after this unix timestamp
+    pub since: Option<i64>,
+}
+
+/// What a commit did to the file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileChangeKind {
+    Added,
+    Modified,
+    Deleted,
+    /// Brought in by renaming this path
+    RenamedFrom(String),
+}
+
+/// One commit in a file's history
+#[derive(Debug, Clone, Serialize)]
+pub struct FileHistoryEntry {
+    pub commit: Commit,
+    /// The file's path as of this commit
+    pub path: String,
+    pub change: FileChangeKind,
+}
+
+impl Store {
+    /// Commits on HEAD's first-parent line that changed `path`, newest
+    /// first, with what each did to it. The log is streamed once.
+    pub fn file_history(
+        &self,
+        path: &str,
+        options: &FileHistoryOptions,
+    ) -> Result<impl Iterator<Item = FileHistoryEntry>> {
+        let mut path = self.checked_path(path)?;
+        let commits: HashMap<String, Commit> = self.log_stream()?.map(|c| (c.id.clone(), c)).collect();
+        let mut entries = Vec::new();
+        let mut current = self.head_commit();
+        // Bounded so a commit listed as its own ancestor can't loop forever
+        for _ in 0..commits.len() {
+            if options.limit.is_some_and(|limit| entries.len() >= limit) {
+                break;
+            }
+            let Some(commit) = current.and_then(|id| commits.get(&id)) else {
+                break;
+            };
+            current = commit.parent.clone();
+            let Some(change) = self.change_to(&commits, commit, &path, options.follow_renames)? else {
+                continue;
+            };
+            let entry_path = path.clone();
+            if let FileChangeKind::RenamedFrom(old) = &change {
+                path = old.clone();
+            }
+            if options.since.is_some_and(|since| commit.time < since) {
+                continue;
+            }
+            entries.push(FileHistoryEntry { commit: commit.clone(), path: entry_path, change });
+        }
+        Ok(entries.into_iter())
+    }
+
+    /// What `commit` did to `path`, `None` when it left the content as its
+    /// parent had it
+    fn change_to(
+        &self,
+        commits: &HashMap<String, Commit>,
+        commit: &Commit,
+        path: &str,
+        follow_renames: bool,
+    ) -> Result<Option<FileChangeKind>> {
+        let Some(blob) = commit.tree.get(path) else {
+            let legacy = commit.tree.is_empty() && commit.files.iter().any(|f| f == path);
+            return Ok(legacy.then_some(FileChangeKind::Modified));
+        };
+        let before = commit.parent.as_deref().and_then(|parent| blob_before(commits, parent, path));
+        Ok(match (before, blob.as_str()) {
+            (None, DELETED_BLOB) => None,
+            (Some(_), DELETED_BLOB) => Some(FileChangeKind::Deleted),
+            (Some(old), new) if old == new => None,
+            (Some(_), _) => Some(FileChangeKind::Modified),
+            (None, _) => {
+                let renamed = match commit.parent.as_deref() {
+                    Some(parent) if follow_renames => self.renames_between(parent, &commit.id)?.source_of(path),
+                    _ => None,
+                };
+                Some(renamed.map_or(FileChangeKind::Added, FileChangeKind::RenamedFrom))
+            }
+        })
+    }
+}
+
+/// Blob `path` had as of `commit_id`, following parents; `None` when it
+/// didn't exist
+fn blob_before<'c>(commits: &'c HashMap<String, Commit>, commit_id: &str, path: &str) -> Option<&'c str> {
+    let mut current = commits.get(commit_id);
+    for _ in 0..commits.len() {
+        let commit = current?;
+        if let Some(blob) = commit.tree.get(path) {
+            return Some(blob.as_str()).filter(|b| *b != DELETED_BLOB);
+        }
+        current = commit.parent.as_deref().and_then(|p| commits.get(p));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FileChangeKind, FileHistoryOptions};
+    use crate::Store;
+    use crate::test_support::{author, temp_repo};
+    use std::fs;
+
+    /// Write or delete each file, stage it and commit
+    fn commit(store: &Store, changes: &[(&str, Option<&str>)], msg: &str) -> String {
+        for (path, content) in changes {
+            match content {
+                Some(content) => fs::write(store.root.join(path), content).unwrap(),
+                None => fs::remove_file(store.root.join(path)).unwrap(),
+            }
+            store.stage_file(path).unwrap();
+        }
+        store.commit(msg, author()).unwrap().id
+    }
+
+    fn kinds(store: &Store, path: &str, options: &FileHistoryOptions) -> Vec<(String, FileChangeKind)> {
+        store
+            .file_history(path, options)
+            .unwrap()
+            .map(|entry| (entry.commit.message, entry.change))
+            .collect()
+    }
+
+    #[test]
+    fn test_history_skips_commits_that_left_the_file_alone() {
+        let (_temp_dir, store) = temp_repo();
+        commit(&store, &[("a.txt", Some("1\n")), ("b.txt", Some("1\n"))], "c1");
+        commit(&store, &[("b.txt", Some("2\n"))], "c2");
+        commit(&store, &[("a.txt", Some("2\n"))], "c3");
+        // Staged again with the same content: listed, not changed
+        commit(&store, &[("a.txt", Some("2\n")), ("b.txt", Some("3\n"))], "c4");
+        commit(&store, &[("a.txt", Some("3\n"))], "c5");
+        commit(&store, &[("b.txt", Some("4\n"))], "c6");
+
+        use FileChangeKind::*;
+        let all = FileHistoryOptions::default();
+        assert_eq!(
+            kinds(&store, "a.txt", &all),
+            [("c5".into(), Modified), ("c3".into(), Modified), ("c1".into(), Added)]
+        );
+        let limited = FileHistoryOptions { limit: Some(2), ..Default::default() };
+        assert_eq!(kinds(&store, "a.txt", &limited).len(), 2);
+
+        commit(&store, &[("a.txt", None)], "c7");
+        assert_eq!(kinds(&store, "a.txt", &all)[0], ("c7".into(), Deleted));
+
+        let future = FileHistoryOptions { since: Some(i64::MAX), ..Default::default() };
+        assert!(kinds(&store, "a.txt", &future).is_empty());
+        let past = FileHistoryOptions { since: Some(0), ..Default::default() };
+        assert_eq!(kinds(&store, "a.txt", &past).len(), 4);
+    }
+
+    #[test]
+    fn test_history_follows_renames_when_asked() {
+        let (_temp_dir, store) = temp_repo();
+        let content = "one\ntwo\nthree\nfour\n";
+        commit(&store, &[("old.txt", Some(content))], "add");
+        commit(&store, &[("old.txt", None), ("new.txt", Some(content))], "rename");
+
+        use FileChangeKind::*;
+        assert_eq!(kinds(&store, "new.txt", &FileHistoryOptions::default()), [("rename".into(), Added)]);
+        let follow = FileHistoryOptions { follow_renames: true, ..Default::default() };
+        let history: Vec<_> = store.file_history("new.txt", &follow).unwrap().collect();
+        let changes: Vec<_> = history.iter().map(|e| (e.path.as_str(), e.change.clone())).collect();
+        assert_eq!(changes, [("new.txt", RenamedFrom("old.txt".into())), ("old.txt", Added)]);
+    }
+}
@@ -0,0 +1,191 @@
+//! Hook scripts in `.rune/hooks`
+//!
+//! A hook is an executable named after its event. It runs from the top of
+//! the working tree with `RUNE_DIR` set, and a nonzero exit aborts whatever
+//! triggered it. Events without a script, or with a script that is not
+//! executable, are skipped.
+
+use crate::{Store, StoreError};
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Stands in for the id of a ref that does not exist on one side of a push
+pub const NULL_ID: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// One ref a push is about to change, as handed to `pre-push`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefUpdate {
+    pub local_ref: String,
+    pub local_id: String,
+    pub remote_ref: String,
+    /// What the remote had last time we heard, or `NULL_ID`
+    pub remote_id: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum HookEvent<'a> {
+    /// Before the commit message is composed
+    PreCommit,
+    /// Gets the path of a file holding the message and may rewrite it
+    CommitMsg { message_file: &'a Path },
+    /// Gets the remote name and URL as arguments and one
+    /// `<local ref> <local id> <remote ref> <remote id>` line per update on
+    /// stdin, before anything is sent
+    PrePush { remote: &'a str, url: &'a str, updates: &'a [RefUpdate] },
+}
+
+impl HookEvent<'_> {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HookEvent::PreCommit => "pre-commit",
+            HookEvent::CommitMsg { .. } => "commit-msg",
+            HookEvent::PrePush { .. } => "pre-push",
+        }
+    }
+}
+
+impl Store {
+    /// The script that would run for `name`, if there is an executable one
+    pub fn hook_script(&self, name: &str) -> Option<PathBuf> {
+        let path = self.rune_dir.join("hooks").join(name);
+        let meta = fs::metadata(&path).ok().filter(|m| m.is_file())?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if meta.permissions().mode() & 0o111 == 0 {
+                return None;
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = meta;
+        Some(path)
+    }
+
+    /// Run the script for `event`. Fails with `StoreError::HookRejected`
+    /// when it exits nonzero.
+    pub fn run_hook(&self, event: &HookEvent) -> Result<()> {
+        let Some(script) = self.hook_script(event.name()) else {
+            return Ok(());
+        };
+        let mut cmd = Command::new(&script);
+        cmd.current_dir(&self.root).env("RUNE_DIR", &self.rune_dir).stdin(Stdio::piped());
+        let mut input = String::new();
+        match event {
+            HookEvent::PreCommit => {}
+            HookEvent::CommitMsg { message_file } => {
+                cmd.arg(message_file);
+            }
+            HookEvent::PrePush { remote, url, updates } => {
+                cmd.arg(remote).arg(url);
+                for u in updates.iter() {
+                    input.push_str(&format!("{} {} {} {}\n", u.local_ref, u.local_id, u.remote_ref, u.remote_id));
+                }
+            }
+        }
+
+        let mut child = cmd
+            .spawn()
+            .with_context(|| format!("failed to run {} hook {}", event.name(), script.display()))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // A hook is free to exit without reading its input
+            match stdin.write_all(input.as_bytes()) {
+                Err(e) if e.kind() != ErrorKind::BrokenPipe => return Err(e.into()),
+                _ => {}
+            }
+        }
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(StoreError::HookRejected {
+                hook: event.name().to_string(),
+                status: status.to_string(),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Pass `message` through the `commit-msg` hook via `COMMIT_EDITMSG` and
+    /// return the message as the hook left it
+    pub fn commit_msg_hook(&self, message: &str) -> Result<String> {
+        if self.hook_script("commit-msg").is_none() {
+            return Ok(message.to_string());
+        }
+        let file = self.rune_dir.join("COMMIT_EDITMSG");
+        fs::write(&file, format!("{}\n", message))?;
+        self.run_hook(&HookEvent::CommitMsg { message_file: &file })?;
+        let edited = fs::read_to_string(&file)?;
+        Ok(edited.trim_end().to_string())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_repo;
+    use rune_core::Author;
+
+    fn install(store: &Store, name: &str, script: &str) {
+        use std::os::unix::fs::PermissionsExt;
+        let path = store.rune_dir.join("hooks").join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, format!("#!/bin/sh\n{}", script)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn test_commit_msg_hook_can_rewrite_the_message() {
+        let (temp, store) = temp_repo();
+        install(&store, "commit-msg", "printf '\\nSigned-off-by: Dev <dev@example.com>\\n' >> \"$1\"\n");
+        fs::write(temp.path().join("a.txt"), "a").unwrap();
+        store.stage_file("a.txt").unwrap();
+
+        let message = store.commit_msg_hook("Add a").unwrap();
+        let commit = store.commit(&message, Author { name: "Dev".into(), email: "dev@example.com".into() }).unwrap();
+        assert_eq!(commit.message, "Add a\n\nSigned-off-by: Dev <dev@example.com>");
+        assert_eq!(store.log().last().unwrap().message, commit.message);
+    }
+
+    #[test]
+    fn test_failing_hook_is_reported() {
+        let (_temp, store) = temp_repo();
+        install(&store, "pre-commit", "exit 3\n");
+        let err = store.run_hook(&HookEvent::PreCommit).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<StoreError>(),
+            Some(StoreError::HookRejected { hook, .. }) if hook == "pre-commit"
+        ));
+    }
+
+    #[test]
+    fn test_pre_push_gets_arguments_and_updates_once() {
+        let (temp, store) = temp_repo();
+        install(&store, "pre-push", "echo \"$1 $2\" >> pushed.txt\ncat >> pushed.txt\n");
+        let updates = [RefUpdate {
+            local_ref: "refs/heads/main".into(),
+            local_id: "abc".into(),
+            remote_ref: "refs/heads/main".into(),
+            remote_id: NULL_ID.into(),
+        }];
+        store
+            .run_hook(&HookEvent::PrePush { remote: "origin", url: "http://example.com/repo", updates: &updates })
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(temp.path().join("pushed.txt")).unwrap(),
+            format!("origin http://example.com/repo\nrefs/heads/main abc refs/heads/main {}\n", NULL_ID)
+        );
+    }
+
+    #[test]
+    fn test_scripts_that_are_not_executable_are_skipped() {
+        let (_temp, store) = temp_repo();
+        install(&store, "pre-commit", "exit 1\n");
+        let path = store.rune_dir.join("hooks/pre-commit");
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(store.hook_script("pre-commit").is_none());
+        store.run_hook(&HookEvent::PreCommit).unwrap();
+    }
+}
@@ -0,0 +1,131 @@
+//! The multi-step operation a repository is in the middle of: a merge,
+//! rebase, revert or am that stopped on conflicts and is waiting to be
+//! continued or aborted.
+
+use crate::Store;
+use anyhow::Result;
+
+/// A multi-step operation that was started and not yet finished or aborted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RepoOperation {
+    /// Merging `branch` into the current branch; `base` is the merge base
+    /// of the two sides, if they share history
+    Merge { branch: String, base: Option<String> },
+    /// Replaying commits onto `onto`; `remaining` excludes the commit being
+    /// replayed right now
+    Rebase { onto: String, current: Option<String>, remaining: Vec<String> },
+    /// Reverting `commit`, paused on conflicts
+    Revert { commit: String },
+    /// Applying mailed patches, paused on `subject`; `remaining` counts the
+    /// patches after it
+    Am { subject: String, remaining: usize },
+}
+
+impl Store {
+    /// The merge, rebase or revert in progress, if any
+    pub fn operation_in_progress(&self) -> Result<Option<RepoOperation>> {
+        if self.rune_dir.join("MERGE_STATE").exists() {
+            let state = self.read_merge_state()?;
+            return Ok(Some(RepoOperation::Merge {
+                base: self.merge_base(&state.current_commit, &state.merge_commit),
+                branch: state.branch_name,
+            }));
+        }
+        if let Some(state) = self.read_rebase_state()? {
+            return Ok(Some(RepoOperation::Rebase {
+                onto: state.target_commit,
+                current: Some(state.current_commit).filter(|c| !c.is_empty()),
+                remaining: state.remaining_commits,
+            }));
+        }
+        if let Some(state) = self.read_revert_state()? {
+            return Ok(Some(RepoOperation::Revert { commit: state.commit }));
+        }
+        if let Some(state) = self.read_am_state()? {
+            return Ok(Some(RepoOperation::Am {
+                subject: state.current_subject(),
+                remaining: state.patches.len().saturating_sub(1),
+            }));
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{author, temp_repo};
+    use crate::{MergeResult, RebaseState};
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// `main` and `feature` change `notes.txt` differently after a shared base
+    fn diverged_repo() -> (TempDir, Store, String) {
+        let (temp_dir, store) = temp_repo();
+        let commit = |content: &str, msg: &str| {
+            fs::write(temp_dir.path().join("notes.txt"), content).unwrap();
+            store.stage_file("notes.txt").unwrap();
+            store.commit(msg, author()).unwrap()
+        };
+        let base = commit("base\n", "base");
+        store.create_branch("feature").unwrap();
+        commit("ours\n", "ours");
+        store.checkout_branch("feature").unwrap();
+        commit("theirs\n", "theirs");
+        store.checkout_branch("main").unwrap();
+        fs::write(temp_dir.path().join("notes.txt"), "ours\n").unwrap();
+        (temp_dir, store, base.id)
+    }
+
+    #[test]
+    fn test_clean_repo_has_no_operation() {
+        let (_temp_dir, store, _) = diverged_repo();
+        assert_eq!(store.operation_in_progress().unwrap(), None);
+    }
+
+    #[test]
+    fn test_conflicted_merge_is_reported() {
+        let (_temp_dir, store, base) = diverged_repo();
+        assert!(matches!(store.merge_branch("feature", false, None).unwrap(), MergeResult::Conflicts(_)));
+
+        assert_eq!(
+            store.operation_in_progress().unwrap(),
+            Some(RepoOperation::Merge { branch: "feature".into(), base: Some(base) })
+        );
+
+        store.abort_merge().unwrap();
+        assert_eq!(store.operation_in_progress().unwrap(), None);
+    }
+
+    #[test]
+    fn test_rebase_is_reported_and_advances() {
+        let (_temp_dir, store, base) = diverged_repo();
+        store
+            .write_rebase_state(&RebaseState {
+                target_commit: base.clone(),
+                current_commit: "c1".into(),
+                remaining_commits: vec!["c2".into(), "c3".into()],
+            })
+            .unwrap();
+
+        assert_eq!(
+            store.operation_in_progress().unwrap(),
+            Some(RepoOperation::Rebase {
+                onto: base.clone(),
+                current: Some("c1".into()),
+                remaining: vec!["c2".into(), "c3".into()],
+            })
+        );
+
+        store.skip_rebase_commit().unwrap();
+        let Some(RepoOperation::Rebase { current, remaining, .. }) = store.operation_in_progress().unwrap() else {
+            panic!("rebase should still be in progress");
+        };
+        assert_eq!(current.as_deref(), Some("c2"));
+        assert_eq!(remaining, vec!["c3".to_string()]);
+
+        store.skip_rebase_commit().unwrap();
+        store.skip_rebase_commit().unwrap();
+        assert_eq!(store.operation_in_progress().unwrap(), None);
+    }
+}
@@ -0,0 +1,282 @@
+//! Earlier versions of the staging area
+//!
+//! Before `write_index` replaces `index.json` with different content, the old
+//! file is copied to `index_history/<nanoseconds>.json`. Only the newest
+//! `core.index_history` copies are kept. `undo_index` puts one back without
+//! touching the working tree. A command that writes the index once per file
+//! holds `batch_index_writes` meanwhile, so it takes up one snapshot however
+//! many files it stages, and reads `.runeattributes` for them only once.
+
+use crate::{write_atomic, Index, Store};
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use rune_core::eol::EolFilter;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// A saved staging area, as listed by `Store::index_history`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexSnapshot {
+    /// When the index was replaced
+    pub time: DateTime<Utc>,
+    /// Number of staged paths in the snapshot
+    pub entries: usize,
+    /// Paths the replacing write staged, unstaged, or staged new content for
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+/// Index writes being held to one step of history
+#[derive(Default)]
+pub(crate) struct IndexBatch {
+    /// The index from before the batch's first write has been saved
+    snapshotted: bool,
+    /// Line ending filter shared by everything the batch stages
+    pub(crate) filter: Option<Arc<EolFilter>>,
+}
+
+/// Holds index writes to one step of history until dropped; returned by
+/// `Store::batch_index_writes`
+#[must_use]
+pub struct IndexWriteBatch<'a> {
+    store: &'a Store,
+    /// Whether this started the batch, rather than joining one
+    outermost: bool,
+}
+
+impl Drop for IndexWriteBatch<'_> {
+    fn drop(&mut self) {
+        if self.outermost {
+            *self.store.index_batch.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        }
+    }
+}
+
+impl Store {
+    /// Until the returned guard is dropped, index writes form one step of
+    /// history: only the index from before the first of them is saved, so
+    /// one undo takes back everything staged meanwhile. A batch started
+    /// inside another is part of the outer one.
+    pub fn batch_index_writes(&self) -> IndexWriteBatch<'_> {
+        let mut batch = self.index_batch.lock().unwrap_or_else(|e| e.into_inner());
+        let outermost = batch.is_none();
+        if outermost {
+            *batch = Some(IndexBatch::default());
+        }
+        IndexWriteBatch { store: self, outermost }
+    }
+
+    /// Save `previous` before the index is replaced, unless the batch being
+    /// run already saved the index it started from
+    pub(crate) fn save_replaced_index(&self, previous: &[u8]) -> Result<()> {
+        let mut batch = self.index_batch.lock().unwrap_or_else(|e| e.into_inner());
+        match batch.as_mut() {
+            Some(batch) if batch.snapshotted => Ok(()),
+            Some(batch) => {
+                self.snapshot_index(previous)?;
+                batch.snapshotted = true;
+                Ok(())
+            }
+            None => self.snapshot_index(previous),
+        }
+    }
+
+    fn index_history_dir(&self) -> PathBuf {
+        self.rune_dir.join("index_history")
+    }
+
+    /// Snapshot files, oldest first
+    fn index_snapshots(&self) -> Result<Vec<(i64, PathBuf)>> {
+        let dir = self.index_history_dir();
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut snapshots = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let stamp = path.file_stem().and_then(|s| s.to_str()).and_then(|s| s.parse().ok());
+            if let Some(stamp) = stamp.filter(|_| path.extension().is_some_and(|e| e == "json")) {
+                snapshots.push((stamp, path));
+            }
+        }
+        snapshots.sort();
+        Ok(snapshots)
+    }
+
+    /// Keep `previous`, the bytes of the index about to be replaced, and
+    /// drop snapshots beyond the configured limit
+    fn snapshot_index(&self, previous: &[u8]) -> Result<()> {
+        let limit = self.config().core.index_history;
+        if limit == 0 {
+            return Ok(());
+        }
+        let dir = self.index_history_dir();
+        fs::create_dir_all(&dir)?;
+        let mut stamp = Utc::now().timestamp_nanos_opt().unwrap_or_default();
+        // Two writes can land on the same clock reading
+        if let Some((newest, _)) = self.index_snapshots()?.last() {
+            stamp = stamp.max(newest + 1);
+        }
+        fs::write(dir.join(format!("{:020}.json", stamp)), previous)?;
+
+        let snapshots = self.index_snapshots()?;
+        for (_, path) in &snapshots[..snapshots.len().saturating_sub(limit)] {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// Saved staging areas, newest first. Each lists what the write that
+    /// replaced it changed.
+    pub fn index_history(&self) -> Result<Vec<IndexSnapshot>> {
+        let snapshots = self.index_snapshots()?;
+        let mut history = Vec::with_capacity(snapshots.len());
+        let mut newer = self.read_index()?;
+        for (stamp, path) in snapshots.iter().rev() {
+            let index: Index = serde_json::from_slice(&fs::read(path)?)?;
+            let staged = |idx: &Index, p: &String| idx.entries.contains_key(p);
+            history.push(IndexSnapshot {
+                time: DateTime::from_timestamp_nanos(*stamp),
+                entries: index.entries.len(),
+                added: newer.entries.keys().filter(|p| !staged(&index, p)).cloned().collect(),
+                removed: index.entries.keys().filter(|p| !staged(&newer, p)).cloned().collect(),
+                changed: newer
+                    .entries
+                    .keys()
+                    .filter(|p| staged(&index, p) && index.blobs.get(*p) != newer.blobs.get(*p))
+                    .cloned()
+                    .collect(),
+            });
+            newer = index;
+        }
+        Ok(history)
+    }
+
+    /// Restore the staging area as it was `steps` writes ago. The snapshots
+    /// in between are used up, so the next undo goes further back. Working
+    /// files are left alone.
+    pub fn undo_index(&self, steps: usize) -> Result<IndexSnapshot> {
+        let snapshots = self.index_snapshots()?;
+        if steps == 0 || steps > snapshots.len() {
+            bail!(
+                "cannot undo {} index change(s): {} snapshot(s) available",
+                steps,
+                snapshots.len()
+            );
+        }
+        let restored = self.index_history()?.swap_remove(steps - 1);
+        let (_, path) = &snapshots[snapshots.len() - steps];
+        write_atomic(&self.rune_dir.join("index.json"), &fs::read(path)?)?;
+        for (_, path) in &snapshots[snapshots.len() - steps..] {
+            fs::remove_file(path)?;
+        }
+        Ok(restored)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_repo;
+    use tempfile::TempDir;
+
+    fn repo() -> (TempDir, Store) {
+        let (temp, store) = temp_repo();
+        for name in ["a.txt", "b.txt", "junk1.log", "junk2.log"] {
+            fs::write(temp.path().join(name), name).unwrap();
+        }
+        (temp, store)
+    }
+
+    fn staged(store: &Store) -> Vec<String> {
+        store.read_index().unwrap().entries.keys().cloned().collect()
+    }
+
+    #[test]
+    fn test_undo_restores_previous_staging() {
+        let (temp, store) = repo();
+        store.stage_file("a.txt").unwrap();
+        store.stage_file("b.txt").unwrap();
+        store.stage_file("junk1.log").unwrap();
+
+        let history = store.index_history().unwrap();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0].entries, 2);
+        assert_eq!(history[0].added, ["junk1.log"]);
+
+        let restored = store.undo_index(1).unwrap();
+        assert_eq!(restored.entries, 2);
+        assert_eq!(staged(&store), ["a.txt", "b.txt"]);
+        assert!(temp.path().join("junk1.log").exists());
+
+        // The snapshot is used up; a second undo goes one further back
+        store.undo_index(1).unwrap();
+        assert_eq!(staged(&store), ["a.txt"]);
+        assert!(store.undo_index(2).is_err());
+    }
+
+    #[test]
+    fn test_batch_is_one_step() {
+        let (temp, store) = repo();
+        store.stage_file("a.txt").unwrap();
+        let names: Vec<String> = (0..25).map(|i| format!("f{:02}.txt", i)).collect();
+        for name in &names {
+            fs::write(temp.path().join(name), name).unwrap();
+        }
+        {
+            let _batch = store.batch_index_writes();
+            for name in &names {
+                store.stage_file(name).unwrap();
+            }
+            // Nested batches belong to the outer one
+            let _inner = store.batch_index_writes();
+            store.stage_file("b.txt").unwrap();
+        }
+        // More writes than `core.index_history` keeps, yet the state from
+        // before them is still one undo away
+        assert_eq!(store.index_history().unwrap().len(), 2);
+        store.undo_index(1).unwrap();
+        assert_eq!(staged(&store), ["a.txt"]);
+
+        // Writes after the batch are their own steps again
+        store.stage_file("b.txt").unwrap();
+        store.stage_file("junk1.log").unwrap();
+        assert_eq!(store.index_history().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let (_temp, store) = repo();
+        let mut cfg = store.config();
+        cfg.core.index_history = 2;
+        store.write_config(&cfg).unwrap();
+
+        for name in ["a.txt", "b.txt", "junk1.log", "junk2.log"] {
+            store.stage_file(name).unwrap();
+        }
+        let history = store.index_history().unwrap();
+        assert_eq!(history.iter().map(|s| s.entries).collect::<Vec<_>>(), [3, 2]);
+
+        store.undo_index(2).unwrap();
+        assert_eq!(staged(&store), ["a.txt", "b.txt"]);
+    }
+
+    #[test]
+    fn test_identical_writes_are_not_snapshotted() {
+        let (_temp, store) = repo();
+        store.stage_file("a.txt").unwrap();
+        let before = store.index_history().unwrap().len();
+
+        store.write_index(&store.read_index().unwrap()).unwrap();
+        store.stage_file("a.txt").unwrap();
+        assert_eq!(store.index_history().unwrap().len(), before);
+
+        fs::write(store.root.join("a.txt"), "new content").unwrap();
+        store.stage_file("a.txt").unwrap();
+        let history = store.index_history().unwrap();
+        assert_eq!(history.len(), before + 1);
+        assert_eq!(history[0].changed, ["a.txt"]);
+    }
+}
@@ -0,0 +1,284 @@
+//! The change journal: a filesystem watcher appends the paths it sees
+//! change to `.rune/journal.jsonl`, and `status` re-examines only those
+//! paths on top of the snapshot saved by the last full scan, as long as the
+//! watcher has been heard from recently.
+
+use crate::{is_same_or_below, StatusSnapshot, Store, Tracking};
+use anyhow::Result;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// A journal older than this is assumed to come from a watcher that died
+pub const CHANGE_JOURNAL_MAX_AGE_SECS: i64 = 300;
+
+/// One line of `.rune/journal.jsonl`; entries without a path are heartbeats
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct JournalEntry {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+    at: i64,
+}
+
+impl Store {
+    /// Previous snapshot updated with the journaled paths, or `None` when the
+    /// journal or snapshot is missing, stale, or predates the latest commit
+    pub(crate) fn journaled_snapshot(
+        &self,
+        commits: usize,
+        tracking: &Tracking,
+    ) -> Result<Option<(StatusSnapshot, Vec<String>)>> {
+        let Some((changed, Some(last_seen))) = self.read_change_journal()? else {
+            return Ok(None);
+        };
+        if Utc::now().timestamp() - last_seen > CHANGE_JOURNAL_MAX_AGE_SECS {
+            return Ok(None);
+        }
+        let snapshot_path = self.rune_dir.join("status-snapshot.json");
+        let Some(mut snapshot) = fs::read(&snapshot_path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<StatusSnapshot>(&bytes).ok())
+        else {
+            return Ok(None);
+        };
+        // A commit or branch switch changes what files are compared against
+        if snapshot.commits != commits || snapshot.head != self.head_commit() {
+            return Ok(None);
+        }
+
+        let internal = self.internal_paths();
+        let mut inspected = Vec::new();
+        for path in changed {
+            snapshot.working.retain(|p| !is_same_or_below(p, &path));
+            snapshot.untracked.retain(|p| !is_same_or_below(p, &path));
+
+            // A journaled directory is rescanned as a whole
+            for entry in walkdir::WalkDir::new(self.root.join(&path)).into_iter().flatten() {
+                if !entry.file_type().is_file() && !entry.file_type().is_symlink() {
+                    continue;
+                }
+                let Ok(rel) = entry.path().strip_prefix(&self.root) else {
+                    continue;
+                };
+                if internal.contains(rel) {
+                    continue;
+                }
+                let rel = rel.to_string_lossy().replace('\\', "/");
+                snapshot.classify(tracking, entry.path(), rel);
+            }
+            snapshot.add_deleted(tracking, &self.root, &path);
+            inspected.push(path);
+        }
+
+        snapshot.commits = commits;
+        Ok(Some((snapshot, inspected)))
+    }
+
+    /// Record that `path` changed on disk so the next `status` only has to
+    /// re-examine it. Meant to be fed by a filesystem watcher.
+    pub fn record_change(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let rel = path.strip_prefix(&self.root).unwrap_or(path);
+        let rel = rel.to_string_lossy().replace('\\', "/");
+        let rel = rel.trim_start_matches("./").trim_end_matches('/').to_string();
+        if rel.is_empty() || self.is_metadata(Path::new(&rel)) {
+            return Ok(());
+        }
+        self.append_journal(&JournalEntry { path: Some(rel), at: Utc::now().timestamp() })
+    }
+
+    /// Tell `status` the watcher is still alive even though nothing changed
+    pub fn journal_heartbeat(&self) -> Result<()> {
+        self.append_journal(&JournalEntry { path: None, at: Utc::now().timestamp() })
+    }
+
+    fn append_journal(&self, entry: &JournalEntry) -> Result<()> {
+        let _lock = self.lock_repo()?;
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.rune_dir.join("journal.jsonl"))?;
+        writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        Ok(())
+    }
+
+    /// Distinct changed paths in the journal and the time it was last written,
+    /// or `None` when there is no journal
+    pub(crate) fn read_change_journal(&self) -> Result<Option<(Vec<String>, Option<i64>)>> {
+        let p = self.rune_dir.join("journal.jsonl");
+        if !p.exists() {
+            return Ok(None);
+        }
+        let mut paths = Vec::new();
+        let mut last_seen = None;
+        for entry in fs::read_to_string(p)?
+            .lines()
+            .filter_map(|l| serde_json::from_str::<JournalEntry>(l).ok())
+        {
+            last_seen = last_seen.max(Some(entry.at));
+            if let Some(path) = entry.path {
+                if !paths.contains(&path) {
+                    paths.push(path);
+                }
+            }
+        }
+        Ok(Some((paths, last_seen)))
+    }
+
+    /// Drop consumed changes but keep the watcher's last heartbeat. Callers
+    /// hold the repository lock.
+    pub(crate) fn reset_change_journal(&self, last_seen: i64) -> Result<()> {
+        let entry = JournalEntry { path: None, at: last_seen };
+        fs::write(
+            self.rune_dir.join("journal.jsonl"),
+            format!("{}\n", serde_json::to_string(&entry)?),
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{author, temp_repo};
+    use crate::StatusScan;
+    use tempfile::TempDir;
+
+    /// A repository whose one tracked file has been edited since the commit
+    fn committed_repo() -> (TempDir, Store) {
+        let (temp_dir, store) = temp_repo();
+        fs::write(temp_dir.path().join("tracked.txt"), "v1").unwrap();
+        store.stage_file("tracked.txt").unwrap();
+        store.commit("initial", author()).unwrap();
+        fs::write(temp_dir.path().join("tracked.txt"), "v2").unwrap();
+        (temp_dir, store)
+    }
+
+    #[test]
+    fn test_status_compares_tracked_files_with_head() {
+        let (temp_dir, store) = committed_repo();
+        assert_eq!(store.status().unwrap().working, vec!["tracked.txt"]);
+
+        // Back to the committed content: nothing to report
+        fs::write(temp_dir.path().join("tracked.txt"), "v1").unwrap();
+        assert!(store.status().unwrap().working.is_empty());
+    }
+
+    #[test]
+    fn test_status_reports_deleted_tracked_files() {
+        let (temp_dir, store) = committed_repo();
+        fs::remove_file(temp_dir.path().join("tracked.txt")).unwrap();
+        let (status, scan) = store.status_with_scan().unwrap();
+        assert!(matches!(scan, StatusScan::Full { .. }));
+        assert_eq!(status.working, vec!["tracked.txt"]);
+
+        // Through the journal too
+        fs::write(temp_dir.path().join("tracked.txt"), "v1").unwrap();
+        assert!(store.status().unwrap().working.is_empty());
+        store.journal_heartbeat().unwrap();
+        store.refresh_status_snapshot().unwrap();
+        fs::remove_file(temp_dir.path().join("tracked.txt")).unwrap();
+        store.record_change("tracked.txt").unwrap();
+        let (status, scan) = store.status_with_scan().unwrap();
+        assert!(matches!(scan, StatusScan::Journal { .. }));
+        assert_eq!(status.working, vec!["tracked.txt"]);
+    }
+
+    #[test]
+    fn test_status_without_journal_does_full_scan() {
+        let (temp_dir, store) = committed_repo();
+        fs::write(temp_dir.path().join("new.txt"), "x").unwrap();
+
+        let (status, scan) = store.status_with_scan().unwrap();
+        assert!(matches!(scan, StatusScan::Full { .. }));
+        assert_eq!(status.untracked, vec!["new.txt"]);
+        assert_eq!(status.working, vec!["tracked.txt"]);
+        // Status only reads
+        assert!(!store.rune_dir.join("status-snapshot.json").exists());
+    }
+
+    #[test]
+    fn test_status_only_inspects_journaled_paths() {
+        let (temp_dir, store) = committed_repo();
+        store.journal_heartbeat().unwrap();
+        // Establishes the snapshot the journal is relative to
+        assert!(matches!(store.refresh_status_snapshot().unwrap(), StatusScan::Full { .. }));
+
+        fs::write(temp_dir.path().join("seen.txt"), "x").unwrap();
+        fs::write(temp_dir.path().join("unseen.txt"), "x").unwrap();
+        store.record_change(temp_dir.path().join("seen.txt")).unwrap();
+        store.record_change("seen.txt").unwrap();
+
+        let (status, scan) = store.status_with_scan().unwrap();
+        assert_eq!(scan, StatusScan::Journal { inspected: vec!["seen.txt".to_string()] });
+        // The unjournaled file is invisible until a full scan
+        assert_eq!(status.untracked, vec!["seen.txt"]);
+        assert_eq!(status.working, vec!["tracked.txt"]);
+
+        // Status leaves the journal alone; a refresh consumes it
+        let (_, scan) = store.status_with_scan().unwrap();
+        assert_eq!(scan, StatusScan::Journal { inspected: vec!["seen.txt".to_string()] });
+        store.refresh_status_snapshot().unwrap();
+        let (status, scan) = store.status_with_scan().unwrap();
+        assert_eq!(scan, StatusScan::Journal { inspected: vec![] });
+        assert_eq!(status.untracked, vec!["seen.txt"]);
+    }
+
+    #[test]
+    fn test_journaled_deletion_and_directory() {
+        let (temp_dir, store) = committed_repo();
+        fs::create_dir_all(temp_dir.path().join("assets")).unwrap();
+        fs::write(temp_dir.path().join("assets/a.png"), "x").unwrap();
+        store.journal_heartbeat().unwrap();
+        store.refresh_status_snapshot().unwrap();
+
+        fs::remove_file(temp_dir.path().join("assets/a.png")).unwrap();
+        fs::write(temp_dir.path().join("assets/b.png"), "x").unwrap();
+        store.record_change("assets/").unwrap();
+
+        let (status, _) = store.status_with_scan().unwrap();
+        assert_eq!(status.untracked, vec!["assets/b.png"]);
+    }
+
+    #[test]
+    fn test_stale_journal_falls_back_to_full_scan() {
+        let (temp_dir, store) = committed_repo();
+        store.journal_heartbeat().unwrap();
+        store.refresh_status_snapshot().unwrap();
+
+        // The watcher went quiet long ago, so changes may have been missed
+        let old = Utc::now().timestamp() - CHANGE_JOURNAL_MAX_AGE_SECS - 60;
+        store.reset_change_journal(old).unwrap();
+        fs::write(temp_dir.path().join("missed.txt"), "x").unwrap();
+
+        let (status, scan) = store.status_with_scan().unwrap();
+        assert!(matches!(scan, StatusScan::Full { .. }));
+        assert_eq!(status.untracked, vec!["missed.txt"]);
+    }
+
+    #[test]
+    fn test_commit_invalidates_snapshot() {
+        let (temp_dir, store) = committed_repo();
+        store.journal_heartbeat().unwrap();
+        store.refresh_status_snapshot().unwrap();
+
+        fs::write(temp_dir.path().join("second.txt"), "x").unwrap();
+        store.stage_file("second.txt").unwrap();
+        store.commit("second", author()).unwrap();
+        store.journal_heartbeat().unwrap();
+
+        fs::write(temp_dir.path().join("second.txt"), "y").unwrap();
+        let (status, scan) = store.status_with_scan().unwrap();
+        assert!(matches!(scan, StatusScan::Full { .. }));
+        assert_eq!(status.working, vec!["second.txt", "tracked.txt"]);
+    }
+
+    #[test]
+    fn test_record_change_ignores_rune_dir() {
+        let (temp_dir, store) = committed_repo();
+        store.record_change(temp_dir.path().join(".rune/index.json")).unwrap();
+        assert!(store.read_change_journal().unwrap().is_none());
+    }
+}
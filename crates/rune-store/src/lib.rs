@@ -1,24 +1,170 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use chrono::Utc;
-use rune_core::{Author, Commit};
+use rune_core::attributes::{AttributeEngine, DiffHandling};
+use rune_core::eol::{AutoCrlf, EolFilter};
+use rune_core::layout::{InternalPaths, RepoLayout};
+use rune_core::{Author, Commit, FileMode};
+
+mod changelog;
+mod checkout;
+mod conflicts;
+mod dedup;
+mod diff_sources;
+mod empty_dirs;
+mod error;
+mod graph;
+mod history;
+mod hook_scripts;
+mod in_progress;
+mod index_history;
+mod journal;
+mod line_endings;
+mod links;
+mod mailpatch;
+mod maintenance;
+mod merge_drivers;
+mod operations;
+mod packs;
+mod refname;
+mod refs;
+mod remote_refs;
+mod renames;
+mod revert;
+mod search;
+mod sparse;
+mod split;
+mod stats;
+mod stream;
+mod tag_signing;
+mod time_reference;
+#[cfg(test)]
+mod test_support;
+mod upstream;
+mod view;
+pub use changelog::{
+    parse_conventional, Changelog, ChangelogEntry, ChangelogSection, ConventionalCommit, ReleaseNotes,
+    ReleaseNotesOptions, CHANGELOG_SECTIONS,
+};
+pub use checkout::{CancelToken, CheckoutProgress};
+pub use conflicts::{ConflictSide, ConflictVersions};
+pub use dedup::DedupReport;
+pub use diff_sources::{DiffSource, PathChange};
+pub use empty_dirs::KEEP_MARKER;
+pub use error::StoreError;
+pub use history::{FileChangeKind, FileHistoryEntry, FileHistoryOptions};
+pub use hook_scripts::{HookEvent, RefUpdate, NULL_ID};
+pub use in_progress::RepoOperation;
+pub use index_history::{IndexSnapshot, IndexWriteBatch};
+pub use journal::CHANGE_JOURNAL_MAX_AGE_SECS;
+pub use mailpatch::AmResult;
+pub use maintenance::{
+    rotate_file, MaintenanceCfg, MaintenanceRun, MaintenanceTask, TaskCfg, TaskRecord, TaskStatus,
+};
+pub use merge_drivers::{
+    BinaryDriver, ConflictStyle, ExternalDriver, JsonDriver, MergeCfg, MergeConflict, MergeDriver, MergeDriverCfg,
+    MergeDrivers, MergeInput, MergeOutcome, TextDriver, UnionDriver,
+};
+pub use operations::{
+    FileChange, LogChange, OperationKind, OperationRecord, PendingOperation, RefChange, UndoReport,
+};
+pub use packs::{PackCheck, RepackReport};
+pub use refname::{check_ref_format, RefNameRule};
+pub use refs::RefsSnapshot;
+pub use remote_refs::PruneReport;
+pub use renames::{DirRename, RenameMap};
+pub use revert::RevertResult;
+pub use search::CommitQuery;
+pub use sparse::{SparseCfg, SparseStaging};
+pub use split::SplitChange;
+pub use stats::{AuthorStats, FileChurn, FileSize, RepoStats, StatsBy, StatsOptions, StatsReport, REPO_STATS_LARGEST_FILES};
+pub use stream::ImportStats;
+pub use tag_signing::{TagKey, TagSignature};
+pub use time_reference::{parse_time_reference, parse_time_reference_at};
+pub use upstream::ReplayResult;
+pub use view::{MergeInProgress, RepoView};
+use sparse::SparseDirs;
 use serde::{Deserialize, Serialize};
 use std::{
     collections::BTreeMap,
     fs,
     io::Write,
     path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    sync::Mutex,
 };
 // ...existing code...
 
-#[derive(Default, Serialize, Deserialize)]
+/// Blob id recorded in `Index::blobs` and `Commit::tree` for a path that
+/// was deleted
+pub const DELETED_BLOB: &str = "";
+
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct Index {
-    pub entries: BTreeMap<String, i64>,
-} // path -> mtime
+    pub entries: BTreeMap<String, i64>, // path -> mtime
+    /// Blob id of the normalized content staged for each path
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub blobs: BTreeMap<String, String>,
+    /// Collapsed top-level directories of a sparse index, each with the
+    /// hash of its content at HEAD; nothing below them is listed
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sparse: BTreeMap<String, String>,
+    /// Staged paths that aren't regular files, e.g. symlinks
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub modes: BTreeMap<String, FileMode>,
+}
 
 #[derive(Debug, Clone)]
 pub struct Status {
     pub staging: Vec<String>,
+    /// Files that have never been committed or staged
+    pub untracked: Vec<String>,
     pub working: Vec<String>,
+    /// Staged or tracked text files mixing CRLF and LF line endings
+    pub mixed_line_endings: Vec<String>,
+    /// Tracked files moved in the working tree and not staged yet, as
+    /// `PathChange::Renamed`; neither side appears in `working` or `untracked`.
+    /// Staged renames are in `changed_paths` from HEAD to the index.
+    pub renamed: Vec<PathChange>,
+}
+
+/// `.rune/MERGE_STATE`: the merge in progress and which conflicts are resolved
+#[derive(Debug, Serialize, Deserialize)]
+struct MergeState {
+    branch_name: String,
+    current_commit: String,
+    merge_commit: String,
+    strategy: Option<String>,
+    #[serde(default)]
+    conflicts: Vec<String>,
+    #[serde(default)]
+    resolved: std::collections::BTreeSet<String>,
+    /// Files taken from their side, or changed on both sides and combined
+    /// by their merge driver
+    #[serde(default)]
+    merged: Vec<String>,
+    /// What the merge driver reported for each conflicted file
+    #[serde(default)]
+    details: BTreeMap<String, MergeConflict>,
+}
+
+/// File in a conflict's directory holding what its merge driver left
+const DRIVER_RESULT: &str = "driver-result";
+
+/// How `merge_branch` handled the files changed on both sides
+#[derive(Default)]
+struct MergePlan {
+    /// Taken from their side or combined by their merge driver, and staged
+    merged: Vec<String>,
+    /// Left for the user, with the driver's report when one ran
+    conflicts: BTreeMap<String, Option<MergeConflict>>,
+}
+
+/// `.rune/REBASE_STATE`: the commit being replayed and those still to go
+#[derive(Debug, Serialize, Deserialize)]
+struct RebaseState {
+    target_commit: String,
+    current_commit: String,
+    remaining_commits: Vec<String>,
 }
 
 /// Result of a merge operation
@@ -32,9 +178,142 @@ pub enum MergeResult {
     Conflicts(Vec<String>),
 }
 
+/// How `Store::status_with_scan` looked at the working tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StatusScan {
+    /// Every file under the root was walked, except in directories the
+    /// sparse index leaves out; `visited` counts the entries walked
+    Full { visited: usize },
+    /// Only paths from the change journal were re-examined
+    Journal { inspected: Vec<String> },
+}
+
+/// Working tree classification from the last scan, before staged files are filtered out
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StatusSnapshot {
+    commits: usize,
+    #[serde(default)]
+    head: Option<String>,
+    working: std::collections::BTreeSet<String>,
+    untracked: std::collections::BTreeSet<String>,
+}
+
+impl StatusSnapshot {
+    /// Count HEAD's files at or below `dir` (everything for `""`) that are
+    /// gone from the working tree as changed
+    fn add_deleted(&mut self, tracking: &Tracking, root: &Path, dir: &str) {
+        for path in tracking.head_tree.keys() {
+            if (dir.is_empty() || is_same_or_below(path, dir))
+                && !tracking.sparse.covers(path)
+                && fs::symlink_metadata(root.join(path)).is_err()
+            {
+                self.working.insert(path.clone());
+            }
+        }
+    }
+
+    fn classify(&mut self, tracking: &Tracking, full_path: &Path, rel: String) {
+        if tracking.sparse.covers(&rel) {
+            return;
+        }
+        if !tracking.is_tracked(&rel) {
+            self.untracked.insert(rel);
+        } else if tracking.is_modified(full_path, &rel) {
+            self.working.insert(rel);
+        }
+    }
+}
+
+/// What status compares the working tree against
+struct Tracking {
+    /// Blobs of HEAD's files: the tracked files, for telling modified ones
+    /// from clean ones. A file only other branches or older commits have is
+    /// untracked here, as it is after a checkout that left it behind.
+    head_tree: BTreeMap<String, String>,
+    filter: EolFilter,
+    /// Directories the sparse index leaves out, which status doesn't look in
+    sparse: SparseDirs,
+}
+
+impl Tracking {
+    fn is_tracked(&self, rel: &str) -> bool {
+        self.head_tree.contains_key(rel)
+    }
+
+    fn is_modified(&self, full_path: &Path, rel: &str) -> bool {
+        let Some(blob) = self.head_tree.get(rel) else {
+            return true;
+        };
+        match links::read_worktree(full_path) {
+            Ok((target, FileMode::Symlink)) => blake3::hash(&target).to_hex().as_str() != blob,
+            Ok((content, _)) => {
+                let normalized = self.filter.to_store(Path::new(rel), &content);
+                blake3::hash(&normalized).to_hex().as_str() != blob
+            }
+            Err(_) => true,
+        }
+    }
+}
+
 pub struct Store {
     pub root: PathBuf,
     pub rune_dir: PathBuf,
+    hooks: Vec<Box<dyn BranchHook>>,
+    index_batch: Mutex<Option<index_history::IndexBatch>>,
+}
+
+/// One line of a ref's reflog
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReflogEntry {
+    pub time: i64,
+    pub commit: String,
+    pub message: String,
+}
+
+/// Branch operations `BranchHook`s are consulted on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BranchOperation {
+    Checkout,
+    Merge,
+    Pull,
+}
+
+/// Operations that throw away uncommitted work in the working tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiscardOperation {
+    /// `reset --hard`, for all files or some
+    ResetHard,
+    /// Aborting a merge, rebase, revert or am
+    Abort,
+}
+
+impl DiscardOperation {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DiscardOperation::ResetHard => "reset --hard",
+            DiscardOperation::Abort => "abort",
+        }
+    }
+}
+
+/// Callbacks around operations that change what the working tree is based
+/// on. Crates built on the store register one to refuse or prepare for such
+/// an operation without the store depending on them.
+pub trait BranchHook: Send + Sync {
+    /// Runs before `op` goes from branch `from` to `to`; for a merge or pull,
+    /// `to` is the branch brought in. An error cancels the operation.
+    fn before(&self, store: &Store, op: BranchOperation, from: Option<&str>, to: &str) -> Result<()>;
+
+    /// Runs once HEAD points at `to` after a checkout
+    fn after_checkout(&self, _store: &Store, _from: Option<&str>, _to: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Runs before `op` overwrites or removes working tree files, e.g. to
+    /// save them somewhere. An error cancels the operation.
+    fn before_discard(&self, _store: &Store, _op: DiscardOperation) -> Result<()> {
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -43,17 +322,228 @@ pub struct RuneConfig {
     pub core: CoreCfg,
     #[serde(default)]
     pub lfs: LfsCfg,
+    #[serde(default, skip_serializing_if = "MergetoolCfg::is_empty")]
+    pub mergetool: MergetoolCfg,
+    #[serde(default, skip_serializing_if = "ReleaseCfg::is_empty")]
+    pub release: ReleaseCfg,
+    /// External merge drivers by name, selected with `merge=<name>`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub merge_drivers: BTreeMap<String, MergeDriverCfg>,
+    #[serde(default, skip_serializing_if = "MergeCfg::is_default")]
+    pub merge: MergeCfg,
+    #[serde(default, skip_serializing_if = "CommitCfg::is_empty")]
+    pub commit: CommitCfg,
+    #[serde(default, skip_serializing_if = "MaintenanceCfg::is_default")]
+    pub maintenance: MaintenanceCfg,
+    #[serde(default, skip_serializing_if = "FetchCfg::is_empty")]
+    pub fetch: FetchCfg,
+    #[serde(default, skip_serializing_if = "PullCfg::is_default")]
+    pub pull: PullCfg,
+    /// Per-branch settings by branch name, e.g. `[branch.main]`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub branch: BTreeMap<String, BranchCfg>,
+    /// `[alias]`: command lines by name, e.g. `lg = "log -n 20 --graph"`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub alias: BTreeMap<String, String>,
+    /// `[defaults]`: arguments a command gets unless it is given the same
+    /// options, e.g. `log = "-n 20"`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub defaults: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "SparseCfg::is_default")]
+    pub sparse: SparseCfg,
+    #[serde(default, skip_serializing_if = "SigningCfg::is_empty")]
+    pub signing: SigningCfg,
+}
+
+/// `[signing]`: keys for signed tags
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SigningCfg {
+    /// PKCS#8 file holding the Ed25519 key `rune tag create --sign` uses
+    pub key: Option<String>,
+    /// Signers whose tags verify, as hex public key to `Name <email>`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub allowed_signers: BTreeMap<String, String>,
+}
+
+impl SigningCfg {
+    fn is_empty(&self) -> bool {
+        self.key.is_none() && self.allowed_signers.is_empty()
+    }
+}
+
+/// `[fetch]`: defaults for fetch and pull
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FetchCfg {
+    /// Prune stale remote-tracking refs on every fetch unless the remote says otherwise
+    #[serde(default)]
+    pub prune: bool,
+}
+
+impl FetchCfg {
+    fn is_empty(&self) -> bool {
+        !self.prune
+    }
+}
+
+/// `[pull]`: how `rune pull` brings the fetched branch in
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PullCfg {
+    #[serde(default)]
+    pub mode: PullMode,
+    /// Stash local changes in a draft before pulling and put them back after
+    #[serde(default)]
+    pub autostash: bool,
 }
+
+impl PullCfg {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// What `rune pull` does when the branches have diverged
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PullMode {
+    /// Only fast-forward; refuse when the histories diverged
+    FfOnly,
+    /// Create a merge commit
+    #[default]
+    Merge,
+    /// Replay local commits on top of the fetched branch
+    Rebase,
+}
+
+impl PullMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PullMode::FfOnly => "ff-only",
+            PullMode::Merge => "merge",
+            PullMode::Rebase => "rebase",
+        }
+    }
+}
+
+impl std::str::FromStr for PullMode {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "ff-only" => Ok(PullMode::FfOnly),
+            "merge" => Ok(PullMode::Merge),
+            "rebase" => Ok(PullMode::Rebase),
+            _ => anyhow::bail!("unknown pull.mode '{}', expected ff-only, merge or rebase", s),
+        }
+    }
+}
+
+/// `[branch.<name>]`: local settings for one branch
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BranchCfg {
+    /// Remote-tracking ref the branch follows, e.g. `refs/remotes/origin/main`
+    pub upstream: Option<String>,
+}
+
+/// `[commit]`: rules every new commit message has to follow
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommitCfg {
+    /// Trailer keys each message must carry, e.g. `["Reviewed-by"]`
+    #[serde(default)]
+    pub required_trailers: Vec<String>,
+}
+
+impl CommitCfg {
+    fn is_empty(&self) -> bool {
+        self.required_trailers.is_empty()
+    }
+}
+
+/// `[release]`: settings for `rune autoflow release`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReleaseCfg {
+    /// File holding the project version, relative to the repository root.
+    /// Without it `Cargo.toml`, then `package.json`, then `VERSION` is used.
+    pub version_file: Option<String>,
+}
+
+impl ReleaseCfg {
+    fn is_empty(&self) -> bool {
+        self.version_file.is_none()
+    }
+}
+
+/// `[mergetool]`: external tool launched by `rune mergetool`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MergetoolCfg {
+    /// Command template; `$BASE`, `$LOCAL`, `$REMOTE` and `$MERGED` are
+    /// replaced with file paths, e.g. `kdiff3 $BASE $LOCAL $REMOTE -o $MERGED`
+    pub cmd: Option<String>,
+}
+
+impl MergetoolCfg {
+    fn is_empty(&self) -> bool {
+        self.cmd.is_none()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CoreCfg {
     #[serde(default = "def_branch")]
     pub default_branch: String,
+    /// Line ending normalization on stage and checkout
+    #[serde(default)]
+    pub autocrlf: AutoCrlf,
+    /// Earlier staging areas kept for `undo_index`; 0 keeps none
+    #[serde(default = "def_index_history")]
+    pub index_history: usize,
+    /// Check each packed object against its recorded hash when it is read
+    #[serde(default = "def_verify_packs")]
+    pub verify_packs: bool,
+    /// Stage a `.runekeep` marker for each empty directory that is added,
+    /// so the directory is committed and comes back on checkout
+    #[serde(default)]
+    pub keep_empty_dirs: bool,
+    /// `rune commit` moves staged files larger than this many bytes into
+    /// LFS instead of the object store; unset leaves them alone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_lfs_threshold: Option<u64>,
+    /// Commits refuse files larger than this many bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_object_size: Option<u64>,
+    /// Commits are refused once `.rune/objects` holds more than this many
+    /// bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_object_store_bytes: Option<u64>,
+    /// `rune add` on a directory asks before staging more files than this
+    #[serde(default = "def_add_guard_files")]
+    pub add_guard_files: usize,
+    /// ... or more bytes than this
+    #[serde(default = "def_add_guard_bytes")]
+    pub add_guard_bytes: u64,
+    /// Once more than this many objects are loose, the check after a
+    /// command runs gc, which repacks them; unset leaves gc to its schedule
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_gc_threshold: Option<usize>,
+    /// Directories under the root that walks of the working tree skip like
+    /// `.rune`, e.g. a server's data directory
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub internal_dirs: Vec<String>,
 }
 
 impl Default for CoreCfg {
     fn default() -> Self {
         Self {
             default_branch: def_branch(),
+            autocrlf: AutoCrlf::default(),
+            index_history: def_index_history(),
+            verify_packs: def_verify_packs(),
+            keep_empty_dirs: false,
+            auto_lfs_threshold: None,
+            max_object_size: None,
+            max_object_store_bytes: None,
+            add_guard_files: def_add_guard_files(),
+            add_guard_bytes: def_add_guard_bytes(),
+            auto_gc_threshold: None,
+            internal_dirs: Vec::new(),
         }
     }
 }
@@ -62,6 +552,22 @@ fn def_branch() -> String {
     "main".into()
 }
 
+fn def_add_guard_files() -> usize {
+    5_000
+}
+
+fn def_add_guard_bytes() -> u64 {
+    500 * 1024 * 1024
+}
+
+fn def_index_history() -> usize {
+    20
+}
+
+fn def_verify_packs() -> bool {
+    true
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LfsCfg {
     #[serde(default = "def_chunk")]
@@ -80,6 +586,52 @@ impl Default for LfsCfg {
         }
     }
 }
+/// Whether `path` is `dir` itself or lies below it, respecting directory boundaries
+fn is_same_or_below(path: &str, dir: &str) -> bool {
+    path == dir || path.strip_prefix(dir).is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// Proof of holding `.rune/repo.lock`, released on drop. Work that rewrites
+/// history files in place takes it, as does every append to the commit log,
+/// so neither can lose the other's write.
+#[derive(Debug)]
+pub struct RepoLock {
+    _file: fs::File,
+}
+
+/// Write `content` to `path` through a synced temporary file renamed over it.
+/// The temporary file is `<name>.<pid>-<n>.lock`, unique to this write, so
+/// concurrent writers never share one; ref walks skip `.lock` files, which
+/// no valid ref name can end in, so one left by a crash is never read as a ref.
+fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    static WRITES: AtomicUsize = AtomicUsize::new(0);
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(format!(".{}-{}.lock", std::process::id(), WRITES.fetch_add(1, Ordering::Relaxed)));
+    let tmp = path.with_file_name(tmp_name);
+    let mut f = fs::File::create(&tmp)?;
+    f.write_all(content)?;
+    f.sync_all()?;
+    drop(f);
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Look `commit_id` up in `log` by full id or unique prefix
+fn find_commit<'a>(log: &'a [Commit], commit_id: &str) -> Result<&'a Commit, StoreError> {
+    if let Some(commit) = log.iter().find(|c| c.id == commit_id) {
+        return Ok(commit);
+    }
+    let matches: Vec<&Commit> = log.iter().filter(|c| c.id.starts_with(commit_id)).collect();
+    match matches.as_slice() {
+        [] => Err(StoreError::CommitNotFound(commit_id.to_string())),
+        [commit] => Ok(commit),
+        _ => Err(StoreError::AmbiguousCommit {
+            prefix: commit_id.to_string(),
+            candidates: matches.iter().map(|c| c.id.clone()).collect(),
+        }),
+    }
+}
+
 fn def_chunk() -> usize {
     8 * 1024 * 1024
 }
@@ -90,21 +642,80 @@ pub struct TrackCfg {
 
 impl Store {
     pub fn open(root: impl AsRef<Path>) -> Result<Self> {
-        let root = root.as_ref().to_path_buf();
-        let rd = root.join(".rune");
-        fs::create_dir_all(rd.join("objects"))?;
-        Ok(Self { root, rune_dir: rd })
+        Self::open_layout(RepoLayout::at(root)?)
     }
+
+    /// Open a repository whose metadata may live outside its working tree
+    pub fn open_layout(layout: RepoLayout) -> Result<Self> {
+        fs::create_dir_all(layout.rune_dir.join("objects"))?;
+        Ok(Self { root: layout.root, rune_dir: layout.rune_dir, hooks: Vec::new(), index_batch: Mutex::new(None) })
+    }
+
+    /// Consult `hook` on every later checkout, merge and pull through this store
+    pub fn add_branch_hook(&mut self, hook: impl BranchHook + 'static) {
+        self.hooks.push(Box::new(hook));
+    }
+
+    /// Run the `before` hooks for `op` from the current branch to `to`
+    pub fn run_branch_hooks(&self, op: BranchOperation, to: &str) -> Result<()> {
+        let from = self.current_branch();
+        for hook in &self.hooks {
+            hook.before(self, op, from.as_deref(), to)?;
+        }
+        Ok(())
+    }
+
+    /// Give every hook a chance to save the working tree before `op`
+    pub fn run_discard_hooks(&self, op: DiscardOperation) -> Result<()> {
+        for hook in &self.hooks {
+            hook.before_discard(self, op)?;
+        }
+        Ok(())
+    }
+
     pub fn discover(start: impl AsRef<Path>) -> Result<Self> {
-        let mut cur = Some(start.as_ref());
-        while let Some(d) = cur {
-            let rd = d.join(".rune");
-            if rd.exists() {
-                return Self::open(d);
+        let start = start.as_ref();
+        let layout = RepoLayout::discover(start).map_err(|e| StoreError::NotARepo {
+            path: start.to_path_buf(),
+            reason: e.to_string(),
+        })?;
+        Self::open_layout(layout)
+    }
+
+    /// `path`, absolute or relative to the root, as a `/`-separated path
+    /// relative to the root with `.` and `..` resolved. Paths that lead out
+    /// of the working tree are refused, so nothing staged, committed or
+    /// restored can reach files outside it.
+    pub fn checked_path(&self, path: impl AsRef<Path>) -> Result<String> {
+        let path = path.as_ref();
+        let rel = path.strip_prefix(&self.root).unwrap_or(path);
+        let outside = || StoreError::PathOutsideRepo(path.display().to_string());
+        let mut parts = Vec::new();
+        for component in rel.components() {
+            match component {
+                std::path::Component::Normal(part) => parts.push(part.to_str().ok_or_else(outside)?),
+                std::path::Component::CurDir => {}
+                std::path::Component::ParentDir => {
+                    parts.pop().ok_or_else(outside)?;
+                }
+                std::path::Component::RootDir | std::path::Component::Prefix(_) => return Err(outside().into()),
             }
-            cur = d.parent();
         }
-        anyhow::bail!("not a rune repo (no .rune found)")
+        Ok(parts.join("/"))
+    }
+
+    /// The metadata directory and `core.internal_dirs`, which walks of the
+    /// working tree leave out
+    pub fn internal_paths(&self) -> InternalPaths {
+        let layout = RepoLayout { root: self.root.clone(), rune_dir: self.rune_dir.clone() };
+        InternalPaths::new(&layout, &self.config().core.internal_dirs)
+    }
+
+    /// Whether `path`, absolute or relative to the root, is repository
+    /// metadata rather than part of the working tree. Walks build
+    /// `internal_paths` once instead of reading the config for every entry.
+    fn is_metadata(&self, path: &Path) -> bool {
+        self.internal_paths().contains(path)
     }
 
     pub fn config_path(&self) -> PathBuf {
@@ -116,11 +727,37 @@ impl Store {
             toml::from_str(&s).unwrap_or_else(|_| RuneConfig {
                 core: CoreCfg::default(),
                 lfs: LfsCfg::default(),
+                mergetool: MergetoolCfg::default(),
+                release: ReleaseCfg::default(),
+                merge_drivers: BTreeMap::new(),
+                merge: MergeCfg::default(),
+                commit: CommitCfg::default(),
+                maintenance: MaintenanceCfg::default(),
+                fetch: FetchCfg::default(),
+                pull: PullCfg::default(),
+                branch: BTreeMap::new(),
+                alias: BTreeMap::new(),
+                defaults: BTreeMap::new(),
+                sparse: SparseCfg::default(),
+                signing: SigningCfg::default(),
             })
         } else {
             RuneConfig {
                 core: CoreCfg::default(),
                 lfs: LfsCfg::default(),
+                mergetool: MergetoolCfg::default(),
+                release: ReleaseCfg::default(),
+                merge_drivers: BTreeMap::new(),
+                merge: MergeCfg::default(),
+                commit: CommitCfg::default(),
+                maintenance: MaintenanceCfg::default(),
+                fetch: FetchCfg::default(),
+                pull: PullCfg::default(),
+                branch: BTreeMap::new(),
+                alias: BTreeMap::new(),
+                defaults: BTreeMap::new(),
+                sparse: SparseCfg::default(),
+                signing: SigningCfg::default(),
             }
         }
     }
@@ -129,79 +766,141 @@ impl Store {
         Ok(())
     }
 
+    /// Fail unless `message` carries every trailer `[commit]
+    /// required_trailers` asks for
+    pub fn check_commit_message(&self, message: &str) -> Result<()> {
+        let trailers = rune_core::trailers::parse(message);
+        let missing: Vec<String> = self
+            .config()
+            .commit
+            .required_trailers
+            .into_iter()
+            .filter(|key| !trailers.iter().any(|t| t.is(key)))
+            .collect();
+        if !missing.is_empty() {
+            anyhow::bail!("Commit message is missing required trailer(s): {}", missing.join(", "));
+        }
+        Ok(())
+    }
+
     pub fn head_ref(&self) -> String {
         fs::read_to_string(self.rune_dir.join("HEAD"))
             .ok()
             .and_then(|s| s.strip_prefix("ref: ").map(|x| x.trim().to_string()))
-            .unwrap_or_else(|| "refs/heads/main".to_string())
+            .unwrap_or_else(|| format!("refs/heads/{}", self.config().core.default_branch))
     }
     pub fn set_head(&self, r: &str) -> Result<()> {
         fs::write(self.rune_dir.join("HEAD"), format!("ref: {}", r))?;
+        self.invalidate_refs();
         Ok(())
     }
+    /// Id ref `r` points at: the loose ref file, else its `packed-refs` entry
     pub fn read_ref(&self, r: &str) -> Option<String> {
-        fs::read_to_string(self.rune_dir.join(r))
-            .ok()
-            .map(|s| s.trim().to_string())
-            .filter(|s| !s.is_empty()) // Filter out empty strings
+        match fs::read_to_string(self.ref_file(r)?) {
+            Ok(s) => Some(s.trim().to_string()).filter(|s| !s.is_empty()), // Filter out empty strings
+            Err(_) => self.packed_ref(r),
+        }
     }
+    /// Point ref `r` at `id`. New refs must have a valid name; refs already
+    /// on disk from before the naming rules can still be updated.
     pub fn write_ref(&self, r: &str, id: &str) -> Result<()> {
-        let p = self.rune_dir.join(r);
+        let p = match self.ref_file(r).filter(|p| p.is_file() || self.packed_ref(r).is_some()) {
+            Some(existing) => existing,
+            None => {
+                refname::validate_ref_name(r)?;
+                self.rune_dir.join(r)
+            }
+        };
         if let Some(pp) = p.parent() {
             fs::create_dir_all(pp)?;
         }
-        fs::write(p, id.as_bytes())?;
+        // Renamed into place, so the directory's mtime tells snapshots it changed
+        write_atomic(&p, id.as_bytes())?;
+        self.invalidate_refs();
         Ok(())
     }
 
-    /// Create a new branch pointing to the current HEAD
+    /// Whether HEAD points at a branch that has no commits yet
+    pub fn is_unborn(&self) -> bool {
+        self.head_commit().is_none()
+    }
+
+    /// Create a new branch pointing to the current HEAD.
+    ///
+    /// Before the first commit the new branch is unborn, just like the
+    /// current one: its ref exists but is empty until something is committed.
     pub fn create_branch(&self, name: &str) -> Result<()> {
+        refname::validate_ref_name(name)?;
+        if self.branch_exists(name) {
+            return Err(StoreError::BranchExists(name.to_string()).into());
+        }
         let current_head = self.head_ref();
-        let current_commit_id = self.read_ref(&current_head)
-            .ok_or_else(|| anyhow::anyhow!("Current branch has no commits"))?;
-        
         let branch_ref = format!("refs/heads/{}", name);
+        let current_commit_id = self.read_ref(&current_head).unwrap_or_default();
         self.write_ref(&branch_ref, &current_commit_id)?;
         Ok(())
     }
 
     /// List all branches
     pub fn list_branches(&self) -> Result<Vec<String>> {
-        let mut branches = Vec::new();
-        let heads_dir = self.rune_dir.join("refs/heads");
-        
-        if heads_dir.exists() {
-            for entry in walkdir::WalkDir::new(&heads_dir) {
-                let entry = entry?;
-                if entry.file_type().is_file() {
-                    // Get the relative path from refs/heads/ to get the full branch name
-                    if let Ok(relative_path) = entry.path().strip_prefix(&heads_dir) {
-                        branches.push(relative_path.to_string_lossy().to_string());
-                    }
-                }
-            }
-        }
-        
-        Ok(branches)
+        Ok(self.refs_snapshot()?.branches.keys().cloned().collect())
     }
 
-    /// Check if a branch exists
+    /// Check if a branch exists (unborn branches count)
     pub fn branch_exists(&self, name: &str) -> bool {
+        self.refs_snapshot().is_ok_and(|refs| refs.branches.contains_key(name))
+    }
+
+    /// Start branch `name` with no commits and switch to it. The staging
+    /// area is cleared and the working tree left as it is, so the next
+    /// commit is a root commit holding whatever is staged then.
+    pub fn create_orphan_branch(&self, name: &str) -> Result<()> {
+        if self.branch_exists(name) {
+            return Err(StoreError::BranchExists(name.to_string()).into());
+        }
+        let from = self.current_branch();
+        self.run_branch_hooks(BranchOperation::Checkout, name)?;
         let branch_ref = format!("refs/heads/{}", name);
-        self.read_ref(&branch_ref).is_some()
+        self.write_ref(&branch_ref, "")?;
+        self.set_head(&branch_ref)?;
+        self.replace_index(&Index::default())?;
+        for hook in &self.hooks {
+            hook.after_checkout(self, from.as_deref(), name)?;
+        }
+        Ok(())
     }
 
     /// Checkout (switch to) a branch
     pub fn checkout_branch(&self, name: &str) -> Result<()> {
+        self.checkout_branch_with(name, |_| Ok(()))
+    }
+
+    /// `checkout_branch`, calling `check` after the branch hooks have had
+    /// their say and before HEAD moves; an error from it cancels the
+    /// checkout. Callers that look at the working tree first use this so the
+    /// hooks still run only once.
+    pub fn checkout_branch_with(&self, name: &str, check: impl FnOnce(&Store) -> Result<()>) -> Result<()> {
         let branch_ref = format!("refs/heads/{}", name);
         
         // Check if branch exists
         if !self.branch_exists(name) {
-            return Err(anyhow::anyhow!("Branch '{}' does not exist", name));
+            return Err(StoreError::BranchNotFound(name.to_string()).into());
         }
-        
+
+        let from = self.current_branch();
+        let switching = from.as_deref() != Some(name);
+        if switching {
+            self.run_branch_hooks(BranchOperation::Checkout, name)?;
+        }
+        check(self)?;
+
         // Set HEAD to point to the new branch
         self.set_head(&branch_ref)?;
+        if switching {
+            for hook in &self.hooks {
+                hook.after_checkout(self, from.as_deref(), name)?;
+            }
+        }
         Ok(())
     }
 
@@ -217,60 +916,199 @@ impl Store {
 
     /// Get repository status (staging and working directory changes)
     pub fn status(&self) -> Result<Status> {
+        Ok(self.status_with_scan()?.0)
+    }
+
+    /// Working tree status, re-examining only journaled paths when a fresh
+    /// change journal is available and walking the whole tree otherwise.
+    /// Nothing is written; `refresh_status_snapshot` moves the snapshot on.
+    pub fn status_with_scan(&self) -> Result<(Status, StatusScan)> {
         let index = self.read_index().unwrap_or_default();
-        let mut staging = Vec::new();
-        let mut working = Vec::new();
-        
+        let tracking = self.tracking(&index);
+        let (snapshot, scan) = self.current_snapshot(&tracking)?;
+
         // Check staged files
-        for (path, _) in &index.entries {
-            staging.push(path.clone());
+        let staging: Vec<String> = index.entries.keys().cloned().collect();
+        let unstaged = |paths: std::collections::BTreeSet<String>| {
+            paths
+                .into_iter()
+                .filter(|p| !index.entries.contains_key(p))
+                .collect::<Vec<_>>()
+        };
+        let mixed_line_endings = self.mixed_line_endings(
+            staging.iter().chain(snapshot.working.iter()),
+        );
+        let mut working = unstaged(snapshot.working);
+        // A staged file edited again after `add` also has unstaged changes
+        for (path, blob) in &index.blobs {
+            let unstaged_edit = match links::read_worktree(&self.root.join(path)) {
+                Ok((target, FileMode::Symlink)) => blake3::hash(&target).to_hex().as_str() != blob,
+                Ok((content, _)) => {
+                    let normalized = tracking.filter.to_store(Path::new(path), &content);
+                    blake3::hash(&normalized).to_hex().as_str() != blob
+                }
+                Err(_) => blob != DELETED_BLOB,
+            };
+            if unstaged_edit {
+                working.push(path.clone());
+            }
         }
-        
-        // Check working directory for modifications
-        // This is a simplified implementation
-        for entry in walkdir::WalkDir::new(&self.root) {
+        working.sort();
+        let (renamed, untracked) = self.worktree_renames(
+            &tracking.head_tree,
+            &index.entries,
+            unstaged(snapshot.untracked),
+            &tracking.filter,
+        )?;
+        working.retain(|path| !renamed.iter().any(|r| matches!(r, PathChange::Renamed { from, .. } if from == path)));
+        let status = Status {
+            working,
+            untracked,
+            staging,
+            mixed_line_endings,
+            renamed,
+        };
+        Ok((status, scan))
+    }
+
+    /// Save the current classification as the snapshot the change journal is
+    /// relative to and empty the journal, so later `status` calls only look
+    /// at paths changed from here on. The watcher feeding the journal calls
+    /// this; it holds the repository lock, which every journal append also
+    /// takes, so no change recorded meanwhile is dropped with the rest.
+    pub fn refresh_status_snapshot(&self) -> Result<StatusScan> {
+        let _lock = self.lock_repo()?;
+        let last_seen = self.read_change_journal()?.and_then(|(_, at)| at);
+        let index = self.read_index().unwrap_or_default();
+        let (snapshot, scan) = self.current_snapshot(&self.tracking(&index))?;
+        write_atomic(&self.rune_dir.join("status-snapshot.json"), &serde_json::to_vec(&snapshot)?)?;
+        if let Some(last_seen) = last_seen {
+            self.reset_change_journal(last_seen)?;
+        }
+        Ok(scan)
+    }
+
+    /// What `status` compares the working tree against
+    fn tracking(&self, index: &Index) -> Tracking {
+        let sparse = SparseDirs::of(index);
+        let mut head_tree = self.head_commit().map(|id| self.tree_snapshot(&id)).unwrap_or_default();
+        head_tree.retain(|path, _| !sparse.covers(path));
+        Tracking {
+            head_tree,
+            filter: EolFilter::new(self.config().core.autocrlf, AttributeEngine::new(&self.root).ok()),
+            sparse,
+        }
+    }
+
+    /// The working tree classified from the saved snapshot and the journal,
+    /// or from a full walk when those can't be trusted
+    fn current_snapshot(&self, tracking: &Tracking) -> Result<(StatusSnapshot, StatusScan)> {
+        let commits = self.log().len();
+        Ok(match self.journaled_snapshot(commits, tracking)? {
+            Some((snapshot, inspected)) => (snapshot, StatusScan::Journal { inspected }),
+            None => {
+                let (snapshot, visited) = self.full_snapshot(commits, tracking)?;
+                (snapshot, StatusScan::Full { visited })
+            }
+        })
+    }
+
+    /// The classified working tree and how many entries walking it visited
+    fn full_snapshot(&self, commits: usize, tracking: &Tracking) -> Result<(StatusSnapshot, usize)> {
+        let mut snapshot = StatusSnapshot { commits, head: self.head_commit(), ..Default::default() };
+        let mut visited = 0;
+        let internal = self.internal_paths();
+        // Neither the metadata directory nor a collapsed one holds anything
+        // status reports
+        let walk = walkdir::WalkDir::new(&self.root).into_iter().filter_entry(|entry| {
+            !entry.file_type().is_dir()
+                || entry.depth() == 0
+                || !(internal.contains(entry.path())
+                    || (entry.depth() == 1 && tracking.sparse.covers(&entry.file_name().to_string_lossy())))
+        });
+        for entry in walk {
             let entry = entry?;
-            if entry.file_type().is_file() {
-                let file_path = entry.path();
-                if let Ok(relative_path) = file_path.strip_prefix(&self.root) {
+            visited += 1;
+            // Links are entries of their own; walking doesn't follow them
+            if entry.file_type().is_file() || entry.file_type().is_symlink() {
+                if let Ok(relative_path) = entry.path().strip_prefix(&self.root) {
                     let relative_str = relative_path.to_string_lossy().to_string();
-                    
-                    // Skip .rune directory
-                    if relative_str.starts_with(".rune") {
+                    if internal.contains(relative_path) {
                         continue;
                     }
-                    
-                    // Check if file is modified but not staged
-                    if !staging.contains(&relative_str) {
-                        working.push(relative_str);
-                    }
+                    snapshot.classify(tracking, entry.path(), relative_str);
                 }
             }
         }
-        
-        Ok(Status { staging, working })
+        snapshot.add_deleted(tracking, &self.root, "");
+        Ok((snapshot, visited))
     }
 
     /// Merge a branch into the current branch
     pub fn merge_branch(&self, branch_name: &str, no_ff: bool, strategy: Option<&str>) -> Result<MergeResult> {
-        let current_branch = self.current_branch()
-            .ok_or_else(|| anyhow::anyhow!("Not on a branch"))?;
-        
-        let current_commit_id = self.read_ref(&format!("refs/heads/{}", current_branch))
-            .ok_or_else(|| anyhow::anyhow!("Current branch has no commits"))?;
-        
+        self.merge_branch_inner(branch_name, no_ff, strategy, false)
+    }
+
+    /// `merge_branch` for a branch that may share no history with the
+    /// current one, such as an orphan branch. Without a common base every
+    /// file counts as added on both sides.
+    pub fn merge_unrelated_branch(&self, branch_name: &str, no_ff: bool, strategy: Option<&str>) -> Result<MergeResult> {
+        self.merge_branch_inner(branch_name, no_ff, strategy, true)
+    }
+
+    fn merge_branch_inner(
+        &self,
+        branch_name: &str,
+        no_ff: bool,
+        strategy: Option<&str>,
+        allow_unrelated: bool,
+    ) -> Result<MergeResult> {
+        self.run_branch_hooks(BranchOperation::Merge, branch_name)?;
         let merge_commit_id = self.read_ref(&format!("refs/heads/{}", branch_name))
-            .ok_or_else(|| anyhow::anyhow!("Branch '{}' has no commits", branch_name))?;
+            .ok_or_else(|| anyhow::anyhow!("Branch '{}' has no commits yet, nothing to merge", branch_name))?;
+        if !allow_unrelated {
+            if let Some(current) = self.head_commit() {
+                if self.merge_base(&current, &merge_commit_id).is_none() {
+                    return Err(StoreError::UnrelatedHistories(branch_name.to_string()).into());
+                }
+            }
+        }
+        self.merge_commit(branch_name, &merge_commit_id, no_ff, strategy)
+    }
+
+    /// Merge `merge_commit_id` into the current branch as `merge_branch`
+    /// does, calling it `branch_name` in the message and conflict markers.
+    /// Branch hooks are left to the caller.
+    pub fn merge_commit(
+        &self,
+        branch_name: &str,
+        merge_commit_id: &str,
+        no_ff: bool,
+        strategy: Option<&str>,
+    ) -> Result<MergeResult> {
+        let merge_commit_id = merge_commit_id.to_string();
+        let current_branch = self.current_branch()
+            .ok_or(StoreError::NotOnBranch)?;
+
+        let current_commit_id = match self.read_ref(&format!("refs/heads/{}", current_branch)) {
+            Some(id) => id,
+            None => {
+                // Unborn branch: adopt the other branch's history
+                if !self.status()?.staging.is_empty() {
+                    anyhow::bail!("Please commit or unstage your changes before merging.");
+                }
+                self.write_ref(&format!("refs/heads/{}", current_branch), &merge_commit_id)?;
+                return Ok(MergeResult::FastForward);
+            }
+        };
         
         // Check if this is a fast-forward merge (merge commit is ahead of current)
         let is_fast_forward = self.is_ancestor(&current_commit_id, &merge_commit_id)?;
         
         // Check for uncommitted changes
         let status = self.status()?;
-        if !status.working.is_empty() || !status.staging.is_empty() {
-            return Err(anyhow::anyhow!(
-                "Please commit or stash your changes before merging.\nUncommitted changes in working directory"
-            ));
+        if !status.working.is_empty() || !status.staging.is_empty() || !status.renamed.is_empty() {
+            return Err(StoreError::UncommittedChanges.into());
         }
         
         if is_fast_forward && !no_ff {
@@ -279,13 +1117,25 @@ impl Store {
             return Ok(MergeResult::FastForward);
         } else {
             // Check for potential conflicts before starting merge
-            let conflicts = self.detect_merge_conflicts(&current_commit_id, &merge_commit_id)?;
-            
+            let outcome = self.detect_merge_conflicts(&current_commit_id, &merge_commit_id, branch_name)?;
+            let conflicts: Vec<String> = outcome.conflicts.keys().cloned().collect();
+
             if !conflicts.is_empty() {
                 // Save merge state for abort/continue
-                self.save_merge_state(branch_name, &current_commit_id, &merge_commit_id, strategy)?;
+                let mut state = MergeState {
+                    branch_name: branch_name.to_string(),
+                    current_commit: current_commit_id.clone(),
+                    merge_commit: merge_commit_id.clone(),
+                    strategy: strategy.map(|s| s.to_string()),
+                    conflicts: conflicts.clone(),
+                    resolved: Default::default(),
+                    merged: outcome.merged,
+                    details: Default::default(),
+                };
+                state.details = outcome.conflicts.into_iter().flat_map(|(path, c)| c.map(|c| (path, c))).collect();
+                self.write_merge_state(&state)?;
                 // Apply conflicted files to working directory
-                self.apply_merge_conflicts(&conflicts)?;
+                self.apply_merge_conflicts(&conflicts, branch_name)?;
                 return Ok(MergeResult::Conflicts(conflicts));
             }
             
@@ -303,15 +1153,69 @@ impl Store {
 
     /// Check if commit_a is an ancestor of commit_b (for fast-forward detection)
     fn is_ancestor(&self, commit_a: &str, commit_b: &str) -> Result<bool> {
-        // For now, we'll implement a simple check
-        // In a real implementation, we'd traverse the commit graph
-        Ok(commit_a != commit_b) // Simplified: if they're different, assume fast-forward possible
+        Ok(self.ancestry(commit_b).iter().any(|c| c.id == commit_a))
+    }
+
+    /// `commit_id` followed by its parents, newest first
+    pub fn ancestry(&self, commit_id: &str) -> Vec<Commit> {
+        let log = self.log();
+        let mut chain = Vec::new();
+        let mut current = log.iter().find(|c| c.id == commit_id);
+        while let Some(commit) = current {
+            // Two commits made in the same second can share an id
+            if chain.len() == log.len() {
+                break;
+            }
+            chain.push(commit.clone());
+            current = commit
+                .parent
+                .as_ref()
+                .and_then(|p| log.iter().find(|c| &c.id == p));
+        }
+        chain
+    }
+
+    /// Nearest tag reachable from `commit_id` and how many commits lie
+    /// between them (0 when the commit itself is tagged)
+    pub fn describe(&self, commit_id: &str) -> Result<Option<(String, usize)>> {
+        let refs = self.refs_snapshot()?;
+        let mut tagged: std::collections::HashMap<&str, Vec<&str>> =
+            std::collections::HashMap::new();
+        for (tag, commit) in &refs.tags {
+            tagged.entry(commit.as_str()).or_default().push(tag.as_str());
+        }
+        for (distance, commit) in self.ancestry(commit_id).into_iter().enumerate() {
+            // Several tags on one commit: the last in sorted order wins
+            if let Some(tag) = tagged.get(commit.id.as_str()).and_then(|tags| tags.last()) {
+                return Ok(Some((tag.to_string(), distance)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Most recent commit both histories share
+    pub fn merge_base(&self, commit_a: &str, commit_b: &str) -> Option<String> {
+        let ancestors_a: std::collections::HashSet<String> =
+            self.ancestry(commit_a).into_iter().map(|c| c.id).collect();
+        self.ancestry(commit_b)
+            .into_iter()
+            .map(|c| c.id)
+            .find(|id| ancestors_a.contains(id))
+    }
+
+    /// Path to blob id for every file with recorded content as of `commit_id`
+    pub fn tree_snapshot(&self, commit_id: &str) -> BTreeMap<String, String> {
+        let mut tree = BTreeMap::new();
+        for commit in self.ancestry(commit_id).into_iter().rev() {
+            tree.extend(commit.tree);
+        }
+        tree.retain(|_, blob| blob != DELETED_BLOB);
+        tree
     }
 
     /// Create a merge commit with two parents
-    fn create_merge_commit(&self, parent1: &str, _parent2: &str, message: &str) -> Result<String> {
+    fn create_merge_commit(&self, parent1: &str, parent2: &str, message: &str) -> Result<String> {
         use chrono::Utc;
-        use std::io::Write;
         
         // Get current index (staged files) - for merge, we'll use current files
         let index = self.read_index().unwrap_or_default();
@@ -336,25 +1240,25 @@ impl Store {
         );
         let id = hex::encode(hash.as_bytes());
         
-        // Create commit with the merge parent (parent1 is current, parent2 is merged branch)
-        // Note: The current Commit struct only supports one parent, so we'll use parent1
-        // and record the merge in the message. TODO: Extend Commit to support multiple parents
+        // parent1 is the current branch, parent2 the branch merged in
         let c = Commit {
             id: id.clone(),
             message: message.to_string(),
+            committer: author.clone(),
             author,
             time: Utc::now().timestamp(),
             parent: Some(parent1.to_string()),
+            merge_parent: Some(parent2.to_string()),
             files,
             branch: format!("refs/heads/{}", current_branch),
+            // Resolved conflicts are staged, so their content lands here
+            tree: index.blobs.clone(),
+            modes: index.modes.clone(),
         };
         
         // Write commit to log
-        let mut f = fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(self.rune_dir.join("log.jsonl"))?;
-        writeln!(f, "{}", serde_json::to_string(&c)?)?;
+        self.append_log(&c)?;
+        self.write_index(&Index::default())?;
         
         Ok(id)
     }
@@ -362,26 +1266,25 @@ impl Store {
     /// Delete a branch
     pub fn delete_branch(&self, name: &str) -> Result<()> {
         let branch_ref = format!("refs/heads/{}", name);
-        let branch_file = self.rune_dir.join(&branch_ref);
-        
-        if !branch_file.exists() {
-            return Err(anyhow::anyhow!("Branch '{}' does not exist", name));
-        }
+        let Some(branch_file) = self.ref_file(&branch_ref).filter(|p| p.exists()) else {
+            return Err(StoreError::BranchNotFound(name.to_string()).into());
+        };
         
         std::fs::remove_file(branch_file)?;
+        self.invalidate_refs();
         Ok(())
     }
 
     /// Rename a branch
     pub fn rename_branch(&self, old_name: &str, new_name: &str) -> Result<()> {
+        refname::validate_ref_name(new_name)?;
         let old_ref = format!("refs/heads/{}", old_name);
         let new_ref = format!("refs/heads/{}", new_name);
-        let old_file = self.rune_dir.join(&old_ref);
         let new_file = self.rune_dir.join(&new_ref);
         
-        if !old_file.exists() {
-            return Err(anyhow::anyhow!("Branch '{}' does not exist", old_name));
-        }
+        let Some(old_file) = self.ref_file(&old_ref).filter(|p| p.exists()) else {
+            return Err(StoreError::BranchNotFound(old_name.to_string()).into());
+        };
         
         // Ensure directory exists for new branch
         if let Some(parent) = new_file.parent() {
@@ -391,6 +1294,7 @@ impl Store {
         // Copy the branch reference
         std::fs::copy(&old_file, &new_file)?;
         std::fs::remove_file(old_file)?;
+        self.invalidate_refs();
         
         // Update HEAD if we're renaming the current branch
         if let Some(current) = self.current_branch() {
@@ -410,78 +1314,85 @@ impl Store {
 
     /// Check if a tag exists
     pub fn tag_exists(&self, name: &str) -> bool {
-        let tag_file = self.rune_dir.join("refs/tags").join(name);
-        tag_file.exists()
+        self.refs_snapshot().is_ok_and(|refs| refs.tags.contains_key(name))
     }
 
     /// Create a lightweight tag
     pub fn create_lightweight_tag(&self, name: &str, commit: &str) -> Result<()> {
+        refname::validate_ref_name(name)?;
         let tags_dir = self.rune_dir.join("refs/tags");
         std::fs::create_dir_all(&tags_dir)?;
         
         let tag_file = tags_dir.join(name);
         std::fs::write(tag_file, commit)?;
+        self.invalidate_refs();
         Ok(())
     }
 
     /// Create an annotated tag
     pub fn create_annotated_tag(&self, name: &str, commit: &str, message: &str) -> Result<()> {
+        refname::validate_ref_name(name)?;
+        // The commit stays on the first line so the tag reads like any other ref
+        self.write_tag(name, &format!("{}\n{}", commit, message))
+    }
+
+    /// Write the file for tag `name`
+    fn write_tag(&self, name: &str, content: &str) -> Result<()> {
         let tags_dir = self.rune_dir.join("refs/tags");
         std::fs::create_dir_all(&tags_dir)?;
-        
-        // For now, we'll store annotated tags the same as lightweight tags
-        // In a full implementation, we'd create a tag object with the message
-        let tag_file = tags_dir.join(name);
-        std::fs::write(tag_file, format!("{}\n{}", commit, message))?;
+        std::fs::write(tags_dir.join(name), content)?;
+        self.invalidate_refs();
         Ok(())
     }
 
     /// Delete a tag
     pub fn delete_tag(&self, name: &str) -> Result<()> {
-        let tag_file = self.rune_dir.join("refs/tags").join(name);
-        
-        if !tag_file.exists() {
-            return Err(anyhow::anyhow!("Tag '{}' does not exist", name));
+        let tag_ref = format!("refs/tags/{}", name);
+        let loose = self.ref_file(&tag_ref).filter(|p| p.exists());
+        if let Some(tag_file) = &loose {
+            std::fs::remove_file(tag_file)?;
         }
-        
-        std::fs::remove_file(tag_file)?;
+        // A packed copy would show through once the loose file is gone
+        let packed = self.remove_packed_ref(&tag_ref)?;
+        if loose.is_none() && !packed {
+            return Err(StoreError::TagNotFound(name.to_string()).into());
+        }
+        self.invalidate_refs();
         Ok(())
     }
 
     /// List all tags
     pub fn list_tags(&self) -> Result<Vec<String>> {
-        let mut tags = Vec::new();
-        let tags_dir = self.rune_dir.join("refs/tags");
-        
-        if tags_dir.exists() {
-            for entry in std::fs::read_dir(tags_dir)? {
-                let entry = entry?;
-                if entry.file_type()?.is_file() {
-                    tags.push(entry.file_name().to_string_lossy().to_string());
-                }
-            }
-        }
-        
-        tags.sort();
-        Ok(tags)
+        Ok(self.refs_snapshot()?.tags.keys().cloned().collect())
+    }
+
+    /// Commits in log order, read one line at a time
+    pub fn log_stream(&self) -> Result<impl Iterator<Item = Commit>> {
+        use std::io::BufRead;
+        let path = self.rune_dir.join("log.jsonl");
+        let lines = match fs::File::open(&path) {
+            Ok(file) => Some(std::io::BufReader::new(file).lines()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+        Ok(lines
+            .into_iter()
+            .flatten()
+            .map_while(|line| line.ok())
+            .filter_map(|line| serde_json::from_str::<Commit>(&line).ok()))
     }
 
     /// Get the commit ID that a tag points to
     pub fn tag_commit(&self, name: &str) -> Option<String> {
-        let tag_file = self.rune_dir.join("refs/tags").join(name);
-        
-        if let Ok(content) = std::fs::read_to_string(tag_file) {
-            // For lightweight tags, the file contains just the commit ID
-            // For annotated tags, the first line is the commit ID
-            Some(content.lines().next()?.to_string())
-        } else {
-            None
-        }
+        self.refs_snapshot().ok()?.tag(name).map(str::to_string)
     }
 
     /// Show differences between working directory and staging area, or between commits
     pub fn diff(&self, target: Option<&str>) -> Result<String> {
         if let Some(target) = target {
+            if self.log().is_empty() {
+                anyhow::bail!("No commits yet, nothing to compare '{}' against", target);
+            }
             if target.contains("..") {
                 // Commit range diff (e.g., "commit1..commit2")
                 let parts: Vec<&str> = target.split("..").collect();
@@ -512,14 +1423,18 @@ impl Store {
 
         // Get all files in working directory
         let mut working_files = std::collections::HashSet::new();
-        self.collect_files(&self.root, &mut working_files)?;
+        let internal = self.internal_paths();
+        self.collect_files(&self.root, &internal, &mut working_files)?;
         
         // For simplicity, show a basic status-like diff for now
         let index = self.read_index()?;
+        let attributes = AttributeEngine::new(&self.root)?;
+        let filter = EolFilter::new(self.config().core.autocrlf, Some(attributes.clone()));
+        let head = latest_commit_id.unwrap_or_default();
         
         for file_path in &working_files {
-            if file_path.starts_with(".rune/") {
-                continue; // Skip .rune directory
+            if internal.contains(file_path) {
+                continue;
             }
             
             let relative_path = file_path.strip_prefix(&self.root)
@@ -527,7 +1442,20 @@ impl Store {
                 .to_string_lossy();
             
             if index.entries.contains_key(&relative_path.to_string()) {
-                diff_output.push_str(&format!("M  {}\n", relative_path));
+                // .runeattributes can mark files whose content should not be shown
+                let note = match attributes.diff_handling(relative_path.as_ref()) {
+                    DiffHandling::Binary => " (binary files differ)",
+                    DiffHandling::Generated => " (generated, diff suppressed)",
+                    DiffHandling::Text => "",
+                };
+                diff_output.push_str(&format!("M  {}{}\n", relative_path, note));
+            } else if let Some(blob) = self.blob_at(&head, &relative_path) {
+                // Compare normalized content so pure line ending churn is not a change
+                let content = fs::read(file_path)?;
+                let normalized = filter.to_store(Path::new(relative_path.as_ref()), &content);
+                if blake3::hash(&normalized).to_hex().as_str() != blob {
+                    diff_output.push_str(&format!("M  {}\n", relative_path));
+                }
             } else {
                 diff_output.push_str(&format!("??  {}\n", relative_path));
             }
@@ -543,9 +1471,7 @@ impl Store {
     /// Show differences for a specific commit (compared to its parent)
     fn diff_commit(&self, commit_id: &str) -> Result<String> {
         let commits = self.log();
-        let commit = commits.iter()
-            .find(|c| c.id.starts_with(commit_id))
-            .ok_or_else(|| anyhow::anyhow!("Commit '{}' not found", commit_id))?;
+        let commit = find_commit(&commits, commit_id)?;
         
         let mut diff_output = format!("commit {}\n", commit.id);
         diff_output.push_str(&format!("Author: {} <{}>\n", commit.author.name, commit.author.email));
@@ -566,13 +1492,8 @@ impl Store {
     fn diff_commits(&self, commit1: &str, commit2: &str) -> Result<String> {
         let commits = self.log();
         
-        let c1 = commits.iter()
-            .find(|c| c.id.starts_with(commit1))
-            .ok_or_else(|| anyhow::anyhow!("Commit '{}' not found", commit1))?;
-            
-        let c2 = commits.iter()
-            .find(|c| c.id.starts_with(commit2))
-            .ok_or_else(|| anyhow::anyhow!("Commit '{}' not found", commit2))?;
+        let c1 = find_commit(&commits, commit1)?;
+        let c2 = find_commit(&commits, commit2)?;
         
         let mut diff_output = format!("diff {}..{}\n", c1.id, c2.id);
         
@@ -598,15 +1519,23 @@ impl Store {
         Ok(diff_output)
     }
 
-    /// Helper method to collect all files in a directory
-    fn collect_files(&self, dir: &std::path::Path, files: &mut std::collections::HashSet<std::path::PathBuf>) -> Result<()> {
+    /// Helper method to collect all files in a directory, leaving out
+    /// `internal` ones
+    fn collect_files(
+        &self,
+        dir: &std::path::Path,
+        internal: &InternalPaths,
+        files: &mut std::collections::HashSet<std::path::PathBuf>,
+    ) -> Result<()> {
         if dir.is_dir() {
             for entry in fs::read_dir(dir)? {
                 let entry = entry?;
                 let path = entry.path();
-                if path.is_dir() {
-                    if !path.file_name().unwrap().to_string_lossy().starts_with('.') {
-                        self.collect_files(&path, files)?;
+                // `file_type` doesn't follow links, so a linked directory
+                // can't lead back up the tree
+                if entry.file_type()?.is_dir() {
+                    if !path.file_name().unwrap().to_string_lossy().starts_with('.') && !internal.contains(&path) {
+                        self.collect_files(&path, internal, files)?;
                     }
                 } else {
                     files.insert(path);
@@ -619,46 +1548,257 @@ impl Store {
     pub fn read_index(&self) -> Result<Index> {
         let p = self.rune_dir.join("index.json");
         if p.exists() {
-            Ok(serde_json::from_str(&fs::read_to_string(p)?)?)
+            serde_json::from_str(&fs::read_to_string(&p)?)
+                .with_context(|| format!("{} is corrupt", p.display()))
         } else {
             Ok(Index::default())
         }
     }
+
+    /// Replace the index in one step: a crash leaves either the old index or
+    /// the new one, never a truncated file. The old index goes to the
+    /// history first unless nothing changes.
+    ///
+    /// An index without sparse markers keeps the current ones: clearing the
+    /// staged files doesn't expand the directories left out.
     pub fn write_index(&self, idx: &Index) -> Result<()> {
-        fs::write(
-            self.rune_dir.join("index.json"),
-            serde_json::to_vec_pretty(idx)?,
-        )?;
+        if idx.sparse.is_empty() {
+            let sparse = self.read_index().map(|current| current.sparse).unwrap_or_default();
+            if !sparse.is_empty() {
+                return self.replace_index(&Index { sparse, ..idx.clone() });
+            }
+        }
+        self.replace_index(idx)
+    }
+
+    /// `write_index` taking the sparse markers from `idx` as they are
+    pub(crate) fn replace_index(&self, idx: &Index) -> Result<()> {
+        let path = self.rune_dir.join("index.json");
+        let content = serde_json::to_vec_pretty(idx)?;
+        match fs::read(&path) {
+            Ok(current) if current == content => return Ok(()),
+            Ok(current) => self.save_replaced_index(&current)?,
+            Err(_) => {}
+        }
+        write_atomic(&path, &content)
+    }
+
+    fn repo_lock_file(&self) -> Result<fs::File> {
+        Ok(fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.rune_dir.join("repo.lock"))?)
+    }
+
+    /// Take the repository lock, waiting for whoever holds it
+    pub fn lock_repo(&self) -> Result<RepoLock> {
+        let file = self.repo_lock_file()?;
+        file.lock()?;
+        Ok(RepoLock { _file: file })
+    }
+
+    /// Take the repository lock, or `None` at once if someone else has it
+    pub fn try_lock_repo(&self) -> Result<Option<RepoLock>> {
+        let file = self.repo_lock_file()?;
+        match file.try_lock() {
+            Ok(()) => Ok(Some(RepoLock { _file: file })),
+            Err(fs::TryLockError::WouldBlock) => Ok(None),
+            Err(fs::TryLockError::Error(e)) => Err(e.into()),
+        }
+    }
+
+    /// Append `commit` to `log.jsonl` as a single line and flush it to disk
+    fn append_log(&self, commit: &Commit) -> Result<()> {
+        let _lock = self.lock_repo()?;
+        let mut line = serde_json::to_string(commit)?;
+        line.push('\n');
+        let mut f = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.rune_dir.join("log.jsonl"))?;
+        f.write_all(line.as_bytes())?;
+        f.sync_data()?;
         Ok(())
     }
 
     pub fn stage_file(&self, rel: &str) -> Result<()> {
+        let _batch = self.batch_index_writes();
+        let rel = &self.checked_path(rel)?;
+        if self.config().core.keep_empty_dirs && fs::symlink_metadata(self.root.join(rel)).is_ok_and(|m| m.is_dir()) {
+            for marker in self.mark_empty_dirs(rel)? {
+                self.stage_file(&marker)?;
+            }
+        }
+        self.expand_for_staging(rel)?;
         let mut idx = self.read_index()?;
-        let meta = fs::metadata(self.root.join(rel))?;
+        let meta = match fs::symlink_metadata(self.root.join(rel)) {
+            Ok(meta) => meta,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && self.is_tracked(rel, &idx) => {
+                idx.entries.insert(rel.to_string(), 0);
+                idx.blobs.insert(rel.to_string(), DELETED_BLOB.to_string());
+                idx.modes.remove(rel);
+                return self.write_index(&idx);
+            }
+            Err(e) => return Err(e.into()),
+        };
         let mtime = meta
             .modified()?
             .elapsed()
             .map(|e| -(e.as_secs() as i64))
             .unwrap_or(0);
         idx.entries.insert(rel.to_string(), mtime);
+        if meta.file_type().is_symlink() {
+            let (target, mode) = links::read_worktree(&self.root.join(rel))?;
+            idx.blobs.insert(rel.to_string(), self.write_blob(&target)?);
+            idx.modes.insert(rel.to_string(), mode);
+        } else if meta.is_file() {
+            let content = fs::read(self.root.join(rel))?;
+            let blob = self.write_blob(&self.eol_filter()?.to_store(Path::new(rel), &content))?;
+            idx.blobs.insert(rel.to_string(), blob);
+            idx.modes.remove(rel);
+        }
         self.write_index(&idx)
     }
 
+    /// Stage `content` for `rel` in place of what the working tree has, e.g.
+    /// only some of a file's hunks
+    pub fn stage_content(&self, rel: &str, content: &[u8]) -> Result<()> {
+        self.expand_for_staging(rel)?;
+        let mut idx = self.read_index()?;
+        let blob = self.write_blob(&self.eol_filter()?.to_store(Path::new(rel), content))?;
+        idx.entries.insert(rel.to_string(), 0);
+        idx.blobs.insert(rel.to_string(), blob);
+        idx.modes.remove(rel);
+        self.write_index(&idx)
+    }
+
+    /// Whether `rel` has content in HEAD or staged, so that its absence
+    /// from the working tree is a deletion
+    fn is_tracked(&self, rel: &str, idx: &Index) -> bool {
+        idx.blobs.get(rel).is_some_and(|b| b != DELETED_BLOB)
+            || self.head_commit().is_some_and(|head| self.blob_at(&head, rel).is_some())
+    }
+
+    /// Store `content` under its hash and return the blob id
+    pub fn write_blob(&self, content: &[u8]) -> Result<String> {
+        let id = blake3::hash(content).to_hex().to_string();
+        let path = self.blob_path(&id);
+        if !path.exists() {
+            fs::create_dir_all(path.parent().expect("blob path has a parent"))?;
+            fs::write(&path, content)?;
+        }
+        Ok(id)
+    }
+
+    /// Content of blob `id`, from a pack or its loose file; a damaged
+    /// packed copy is passed over when there is another
+    pub fn read_blob(&self, id: &str) -> Result<Vec<u8>> {
+        loop {
+            let packs = self.packs()?;
+            let damaged = match self.read_packed(id) {
+                Ok(Some(content)) => return Ok(content),
+                Ok(None) => None,
+                Err(e) => Some(e),
+            };
+            match fs::read(self.blob_path(id)) {
+                Ok(content) => return Ok(content),
+                // A repack writes its pack before removing the loose file or
+                // the packs it replaces, so an object that went missing while
+                // the packs were searched is in a pack listed since
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound && damaged.is_none() && self.packs()? != packs => {}
+                Err(e) => return Err(damaged.unwrap_or_else(|| anyhow::anyhow!("Missing blob {}: {}", id, e))),
+            }
+        }
+    }
+
+    fn blob_path(&self, id: &str) -> PathBuf {
+        let objects = self.rune_dir.join("objects");
+        // Anything too short to split names no object; it mustn't panic either
+        match id.get(..2).zip(id.get(2..)) {
+            Some((dir, rest)) if !rest.is_empty() => objects.join(dir).join(rest),
+            _ => objects.join(id),
+        }
+    }
+
+    /// Content staged for `path`, converted for the working tree; `None`
+    /// when nothing is staged for it or the staged change is a deletion
+    pub fn staged_content(&self, path: &str) -> Result<Option<Vec<u8>>> {
+        match self.read_index()?.blobs.get(path) {
+            Some(blob) if blob != DELETED_BLOB => {
+                let content = self.read_blob(blob)?;
+                Ok(Some(self.eol_filter()?.to_worktree(Path::new(path), &content).into_owned()))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Drop `paths` from the staging area, leaving the working tree alone
+    pub fn unstage(&self, paths: &[String]) -> Result<()> {
+        let mut index = self.read_index()?;
+        for path in paths {
+            index.entries.remove(path);
+            index.blobs.remove(path);
+            index.modes.remove(path);
+        }
+        self.write_index(&index)
+    }
+
+    /// Blob recorded for `path` by `commit_id` or its nearest ancestor that
+    /// has it; `None` when the file was deleted or never had content stored
+    pub fn blob_at(&self, commit_id: &str, path: &str) -> Option<String> {
+        self.tree_entry(commit_id, path).filter(|blob| blob != DELETED_BLOB)
+    }
+
+    /// Like `blob_at`, but a deletion comes back as `DELETED_BLOB`
+    fn tree_entry(&self, commit_id: &str, path: &str) -> Option<String> {
+        let log = self.log();
+        // An ambiguous prefix names no commit rather than whichever is first
+        let mut current = find_commit(&log, commit_id).ok();
+        while let Some(commit) = current {
+            if let Some(blob) = commit.tree.get(path) {
+                return Some(blob.clone());
+            }
+            current = commit
+                .parent
+                .as_ref()
+                .and_then(|p| log.iter().find(|c| &c.id == p));
+        }
+        None
+    }
+
     pub fn commit(&self, msg: &str, author: Author) -> Result<Commit> {
+        self.commit_as(msg, author.clone(), author, Utc::now().timestamp())
+    }
+
+    /// Commit the index with an author and time other than the committer's
+    /// own, as `am` does for patches written by someone else
+    pub(crate) fn commit_as(&self, msg: &str, author: Author, committer: Author, time: i64) -> Result<Commit> {
         let idx = self.read_index()?;
         if idx.entries.is_empty() {
-            anyhow::bail!("nothing to commit");
+            return Err(StoreError::NothingToCommit.into());
         }
+        // An index edited by hand must not put escaping paths into history
+        for path in idx.entries.keys() {
+            self.checked_path(path)?;
+        }
+        self.check_sparse_commit(&idx)?;
+        self.check_object_sizes(&idx)?;
+        self.check_object_store_quota()?;
         let branch = self.head_ref();
         let branch_head = self.read_ref(&branch);
         let files = idx.entries.keys().cloned().collect::<Vec<_>>();
+        let tree = idx.blobs.clone();
+        let modes = idx.modes.clone();
+        // The parent keeps a re-applied patch from taking its original's id
         let hash = blake3::hash(
             format!(
-                "{}{}{:?}{}",
+                "{}{}{:?}{}{}",
                 msg,
                 author.email,
                 files,
-                Utc::now().timestamp()
+                Utc::now().timestamp(),
+                branch_head.as_deref().unwrap_or_default()
             )
             .as_bytes(),
         );
@@ -667,16 +1807,16 @@ impl Store {
             id: id.clone(),
             message: msg.to_string(),
             author,
-            time: Utc::now().timestamp(),
+            committer,
+            time,
             parent: branch_head,
+            merge_parent: None,
             files,
             branch: branch.clone(),
+            tree,
+            modes,
         };
-        let mut f = fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(self.rune_dir.join("log.jsonl"))?;
-        writeln!(f, "{}", serde_json::to_string(&c)?)?;
+        self.append_log(&c)?;
         self.write_ref(&branch, &id)?;
         self.write_index(&Index::default())?;
         
@@ -686,20 +1826,72 @@ impl Store {
         Ok(c)
     }
 
-    pub fn commit_amend(&self, msg: &str, edit_message: bool, author: Author) -> Result<Commit> {
+    /// Staged regular files larger than `limit` bytes, with their sizes
+    pub fn oversized_staged(&self, limit: u64) -> Result<Vec<(String, u64)>> {
+        self.oversized(&self.read_index()?, limit)
+    }
+
+    fn oversized(&self, idx: &Index, limit: u64) -> Result<Vec<(String, u64)>> {
+        let mut oversized = Vec::new();
+        for (path, blob) in &idx.blobs {
+            if blob == DELETED_BLOB || idx.modes.contains_key(path) {
+                continue;
+            }
+            let size = match fs::metadata(self.blob_path(blob)) {
+                Ok(meta) => meta.len(),
+                Err(_) => self.read_blob(blob)?.len() as u64,
+            };
+            if size > limit {
+                oversized.push((path.clone(), size));
+            }
+        }
+        Ok(oversized)
+    }
+
+    /// Refuse an index holding a file over `core.max_object_size`
+    fn check_object_sizes(&self, idx: &Index) -> Result<()> {
+        let Some(limit) = self.config().core.max_object_size else {
+            return Ok(());
+        };
+        match self.oversized(idx, limit)?.into_iter().next() {
+            Some((path, size)) => Err(StoreError::ObjectTooLarge { path, size, limit }.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Refuse a commit while the objects it would keep are over
+    /// `core.max_object_store_bytes`. Staged content is already in the
+    /// object store, so its size is what the commit would keep.
+    fn check_object_store_quota(&self) -> Result<()> {
+        let Some(limit) = self.config().core.max_object_store_bytes else {
+            return Ok(());
+        };
+        let size = self.reachable_object_size()?;
+        if size > limit {
+            return Err(StoreError::ObjectStoreOverQuota { size, limit }.into());
+        }
+        Ok(())
+    }
+
+    /// Replace the tip of the current branch. The original author is kept;
+    /// `committer` and the commit time record who amended it and when.
+    pub fn commit_amend(&self, msg: &str, edit_message: bool, committer: Author) -> Result<Commit> {
         let idx = self.read_index()?;
         let mut log = self.log();
-        
-        if log.is_empty() {
-            anyhow::bail!("no commits to amend");
-        }
+        let head = self.head_commit().ok_or(StoreError::NoCommits)?;
+        let position = log
+            .iter()
+            .position(|c| c.id == head)
+            .ok_or_else(|| StoreError::CommitNotFound(head.clone()))?;
         
         // Check if merge is in progress
         if self.rune_dir.join("MERGE_HEAD").exists() {
             anyhow::bail!("cannot amend during merge");
         }
+        self.check_object_sizes(&idx)?;
+        self.check_object_store_quota()?;
         
-        let last_commit = &log[0];
+        let last_commit = &log[position];
         let branch = self.head_ref();
         
         // Use provided message if edit_message is true, otherwise keep original
@@ -710,10 +1902,10 @@ impl Store {
         };
         
         // If index is empty, use files from last commit
-        let files = if idx.entries.is_empty() {
-            last_commit.files.clone()
+        let (files, tree, modes) = if idx.entries.is_empty() {
+            (last_commit.files.clone(), last_commit.tree.clone(), last_commit.modes.clone())
         } else {
-            idx.entries.keys().cloned().collect::<Vec<_>>()
+            (idx.entries.keys().cloned().collect::<Vec<_>>(), idx.blobs.clone(), idx.modes.clone())
         };
         
         // Create new commit hash
@@ -721,7 +1913,7 @@ impl Store {
             format!(
                 "{}{}{:?}{}",
                 commit_message,
-                author.email,
+                last_commit.author.email,
                 files,
                 Utc::now().timestamp()
             )
@@ -729,33 +1921,31 @@ impl Store {
         );
         let id = hex::encode(hash.as_bytes());
         
-        // Create amended commit with same parent as original
+        // Create amended commit with same parent and author as original
         let amended_commit = Commit {
             id: id.clone(),
             message: commit_message.clone(),
-            author,
+            author: last_commit.author.clone(),
+            committer,
             time: Utc::now().timestamp(),
             parent: last_commit.parent.clone(),
+            merge_parent: last_commit.merge_parent.clone(),
             files,
             branch: branch.clone(),
+            tree,
+            modes,
         };
         
-        // Remove the last commit from log and add amended commit
-        log.remove(0);
-        log.insert(0, amended_commit.clone());
+        // Replace the tip in place, keeping the log in append order
+        log[position] = amended_commit.clone();
         
         // Rewrite the entire log file
-        let log_path = self.rune_dir.join("log.jsonl");
-        fs::remove_file(&log_path).ok(); // Remove old log
-        
-        let mut f = fs::OpenOptions::new()
-            .create(true)
-            .write(true)
-            .open(&log_path)?;
-        
-        for commit in log.iter().rev() {
-            writeln!(f, "{}", serde_json::to_string(commit)?)?;
+        let mut content = String::new();
+        for commit in &log {
+            content.push_str(&serde_json::to_string(commit)?);
+            content.push('\n');
         }
+        write_atomic(&self.rune_dir.join("log.jsonl"), content.as_bytes())?;
         
         // Update branch ref to point to amended commit
         self.write_ref(&branch, &id)?;
@@ -790,131 +1980,89 @@ impl Store {
         Ok(())
     }
 
-    pub fn revert_commit(&self, commit_id: &str, mainline: Option<usize>, no_commit: bool, author: Author) -> Result<Commit> {
-        let log = self.log();
-        
-        // Find the commit to revert
-        let target_commit = log.iter()
-            .find(|c| c.id == commit_id || c.id.starts_with(commit_id))
-            .ok_or_else(|| anyhow::anyhow!("commit '{}' not found", commit_id))?;
-        
-        // Check if it's a merge commit and handle mainline
-        if target_commit.parent.is_some() && mainline.is_some() {
-            // TODO: Handle merge commits with multiple parents
-            // For now, we'll treat it as a regular commit
+    /// Entries `update_reflog` recorded for `ref_name`, newest first
+    pub fn reflog(&self, ref_name: &str) -> Result<Vec<ReflogEntry>> {
+        let path = self.rune_dir.join("logs").join(ref_name.replace("/", "_"));
+        if !path.exists() {
+            return Ok(Vec::new());
         }
-        
-        // Get the parent commit to see what was there before
-        let parent_files = if let Some(ref parent_id) = target_commit.parent {
-            log.iter()
-                .find(|c| c.id == *parent_id)
-                .map(|c| c.files.clone())
-                .unwrap_or_default()
-        } else {
-            Vec::new() // If no parent, this was the initial commit
-        };
-        
-        // Create inverse changes:
-        // 1. Files that were added in target_commit should be removed
-        // 2. Files that were removed in target_commit should be restored
-        // 3. Files that were modified should be reverted to parent state
-        
-        let mut revert_files = Vec::new();
-        let mut staged_files = std::collections::BTreeMap::new();
-        
-        // Files in target commit that weren't in parent = added files (should be removed)
-        for file in &target_commit.files {
-            if !parent_files.contains(file) {
-                // This file was added, so we remove it in revert
-                let file_path = self.root.join(file);
-                if file_path.exists() {
-                    fs::remove_file(&file_path).ok();
-                }
-            } else {
-                // This file was modified, we need to restore parent version
-                // For now, we'll just mark it as needing attention
-                revert_files.push(file.clone());
-                // Stage the current state for the revert commit
-                let metadata = fs::metadata(self.root.join(file))?;
-                staged_files.insert(file.clone(), metadata.modified()?.duration_since(std::time::UNIX_EPOCH)?.as_secs() as i64);
+        let mut entries: Vec<ReflogEntry> = fs::read_to_string(path)?
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.splitn(3, ' ');
+                Some(ReflogEntry {
+                    time: parts.next()?.parse().ok()?,
+                    commit: parts.next()?.to_string(),
+                    message: parts.next().unwrap_or_default().to_string(),
+                })
+            })
+            .collect();
+        entries.reverse();
+        Ok(entries)
+    }
+
+    /// Drop reflog entries for `ref_name` older than `keep_days`. The newest
+    /// entry recording the ref's current tip always stays. Returns how many
+    /// entries were removed.
+    pub fn prune_reflog(&self, ref_name: &str, keep_days: u32) -> Result<usize> {
+        let path = self.rune_dir.join("logs").join(ref_name.replace("/", "_"));
+        self.prune_reflog_file(&path, self.read_ref(ref_name).as_deref(), keep_days)
+    }
+
+    /// `prune_reflog` for every reflog under `.rune/logs`
+    pub fn prune_all_reflogs(&self, keep_days: u32) -> Result<usize> {
+        let logs = self.rune_dir.join("logs");
+        if !logs.exists() {
+            return Ok(0);
+        }
+        // Reflog files are named after their ref with '/' flattened to '_'.
+        // The snapshot takes in packed refs, which have no file under refs/.
+        let tips: BTreeMap<String, String> = self
+            .refs_snapshot()?
+            .refs()
+            .into_iter()
+            .map(|(ref_name, tip)| (ref_name.replace('/', "_"), tip))
+            .collect();
+        let mut pruned = 0;
+        for entry in fs::read_dir(&logs)? {
+            let path = entry?.path();
+            if path.is_file() {
+                let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                pruned += self.prune_reflog_file(&path, tips.get(&name).map(String::as_str), keep_days)?;
             }
         }
-        
-        // Files in parent that aren't in target = removed files (should be restored)
-        for file in &parent_files {
-            if !target_commit.files.contains(file) {
-                // This file was removed, we need to restore it
-                // For now, create a placeholder
-                let file_path = self.root.join(file);
-                if let Some(parent_dir) = file_path.parent() {
-                    fs::create_dir_all(parent_dir)?;
-                }
-                fs::write(&file_path, format!("# Restored file: {}\n", file))?;
-                revert_files.push(file.clone());
-                let metadata = fs::metadata(&file_path)?;
-                staged_files.insert(file.clone(), metadata.modified()?.duration_since(std::time::UNIX_EPOCH)?.as_secs() as i64);
-            }
+        Ok(pruned)
+    }
+
+    fn prune_reflog_file(&self, path: &Path, tip: Option<&str>, keep_days: u32) -> Result<usize> {
+        if !path.exists() {
+            return Ok(0);
         }
-        
-        if no_commit {
-            // Just apply changes to working directory and index
-            let index = Index { entries: staged_files };
-            self.write_index(&index)?;
-            return Ok(Commit {
-                id: "no-commit".to_string(),
-                message: format!("Revert \"{}\"", target_commit.message),
-                author,
-                time: Utc::now().timestamp(),
-                parent: None,
-                files: revert_files,
-                branch: self.head_ref(),
-            });
+        let content = fs::read_to_string(path)?;
+        let lines: Vec<&str> = content.lines().collect();
+        let cutoff = Utc::now().timestamp() - i64::from(keep_days) * 86_400;
+        fn fields(line: &str) -> (Option<i64>, Option<&str>) {
+            let mut parts = line.splitn(3, ' ');
+            (parts.next().and_then(|t| t.parse().ok()), parts.next())
         }
-        
-        // Create revert commit
-        let revert_message = format!("Revert \"{}\"", target_commit.message);
-        let branch = self.head_ref();
-        let branch_head = self.read_ref(&branch);
-        
-        let hash = blake3::hash(
-            format!(
-                "{}{}{:?}{}",
-                revert_message,
-                author.email,
-                revert_files,
-                Utc::now().timestamp()
-            )
-            .as_bytes(),
-        );
-        let id = hex::encode(hash.as_bytes());
-        
-        let revert_commit = Commit {
-            id: id.clone(),
-            message: revert_message.clone(),
-            author,
-            time: Utc::now().timestamp(),
-            parent: branch_head,
-            files: revert_files,
-            branch: branch.clone(),
-        };
-        
-        // Add to log
-        let mut f = fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(self.rune_dir.join("log.jsonl"))?;
-        writeln!(f, "{}", serde_json::to_string(&revert_commit)?)?;
-        
-        // Update branch ref
-        self.write_ref(&branch, &id)?;
-        
-        // Clear index
-        self.write_index(&Index::default())?;
-        
-        // Update reflog
-        self.update_reflog(&branch, &id, &format!("revert: {}", target_commit.message))?;
-        
-        Ok(revert_commit)
+        // The tip's latest entry, or the last entry if none records the tip
+        let pinned = lines
+            .iter()
+            .rposition(|l| tip.is_some() && fields(l).1 == tip)
+            .or_else(|| lines.len().checked_sub(1));
+        let kept: Vec<&str> = lines
+            .iter()
+            .enumerate()
+            .filter(|(i, line)| Some(*i) == pinned || fields(line).0.is_none_or(|time| time >= cutoff))
+            .map(|(_, line)| *line)
+            .collect();
+        let pruned = lines.len() - kept.len();
+        if pruned > 0 {
+            let mut out = kept.join("\n");
+            out.push('\n');
+            write_atomic(path, out.as_bytes())?;
+        }
+        Ok(pruned)
     }
 
     pub fn log(&self) -> Vec<Commit> {
@@ -931,6 +2079,9 @@ impl Store {
 
     /// Reset staging area and optionally working directory
     pub fn reset(&self, files: &[std::path::PathBuf], hard: bool) -> Result<()> {
+        if hard {
+            self.run_discard_hooks(DiscardOperation::ResetHard)?;
+        }
         if files.is_empty() {
             // Reset entire staging area
             self.reset_staging_area()?;
@@ -948,6 +2099,42 @@ impl Store {
         Ok(())
     }
 
+    /// Move the current branch back `count` commits and clear the staging
+    /// area. Working files keep their content unless `hard`, which puts
+    /// back the older commit's version of every file the dropped commits
+    /// touched. Returns the new tip.
+    pub fn rollback(&self, count: usize, hard: bool) -> Result<String> {
+        let branch = self.head_ref();
+        let head = self.read_ref(&branch).ok_or(StoreError::NoCommits)?;
+        let ancestry = self.ancestry(&head);
+        if count == 0 || count >= ancestry.len() {
+            anyhow::bail!(
+                "cannot roll back {} commit(s): the branch has {} before its first commit",
+                count,
+                ancestry.len().saturating_sub(1)
+            );
+        }
+        let target = ancestry[count].id.clone();
+        if hard {
+            let tree = self.tree_snapshot(&target);
+            let modes = self.modes_snapshot(&target);
+            let touched: std::collections::BTreeSet<&String> =
+                ancestry[..count].iter().flat_map(|c| c.files.iter()).collect();
+            let filter = self.eol_filter()?;
+            for path in touched {
+                let dest = self.root.join(path);
+                match tree.get(path) {
+                    Some(blob) => self.checkout_blob(path, blob, modes.get(path).copied().unwrap_or_default(), &filter)?,
+                    None => links::remove_worktree(&dest)?,
+                }
+            }
+        }
+        self.write_ref(&branch, &target)?;
+        self.write_index(&Index::default())?;
+        self.update_reflog(&branch, &target, &format!("rollback: {} commit(s)", count))?;
+        Ok(target)
+    }
+
     /// Reset the entire staging area
     fn reset_staging_area(&self) -> Result<()> {
         self.write_index(&Index::default())?;
@@ -958,15 +2145,20 @@ impl Store {
     fn reset_working_directory(&self) -> Result<()> {
         let head_ref = self.head_ref();
         let head_commit_id = self.read_ref(&head_ref)
-            .ok_or_else(|| anyhow::anyhow!("No commits found - cannot reset working directory"))?;
+            .ok_or(StoreError::NoCommits)?;
         
-        let commit = self.get_commit(&head_commit_id)?;
+        let commit = self.resolve_commit(&head_commit_id)?;
         
         // For our simplified implementation, just recreate the files from commit
         // In a real VCS, we would restore the exact blob contents
         for file_path in &commit.files {
             let file_full_path = self.root.join(file_path);
             
+            if self.tree_entry(&head_commit_id, file_path).is_some() {
+                self.restore_file_from_commit_str(file_path, &head_commit_id)?;
+                continue;
+            }
+
             // If the file doesn't exist, create a placeholder (this is simplified)
             if !file_full_path.exists() {
                 if let Some(parent) = file_full_path.parent() {
@@ -1015,8 +2207,8 @@ impl Store {
             let entry = entry?;
             let path = entry.path();
             
-            if path.file_name() == Some(std::ffi::OsStr::new(".rune")) {
-                continue; // Skip .rune directory
+            if self.is_metadata(&path) {
+                continue; // Skip the metadata directory
             }
             
             if path.is_file() {
@@ -1037,9 +2229,19 @@ impl Store {
 
     /// Restore a file from a specific commit (internal implementation)
     fn restore_file_from_commit_str(&self, file_path: &str, commit_id: &str) -> Result<()> {
-        let commit = self.get_commit(commit_id)?;
+        let file_path = &self.checked_path(file_path)?;
+        let commit = self.resolve_commit(commit_id)?;
         
-        if commit.files.contains(&file_path.to_string()) {
+        if self.tree_entry(&commit.id, file_path).as_deref() == Some(DELETED_BLOB) {
+            let dest_path = self.root.join(file_path);
+            if dest_path.exists() {
+                fs::remove_file(dest_path)?;
+            }
+        } else if let Some(blob) = self.blob_at(&commit.id, file_path) {
+            let mode = self.modes_snapshot(&commit.id).get(file_path.as_str()).copied().unwrap_or_default();
+            let filter = self.eol_filter()?;
+            self.checkout_blob(file_path, &blob, mode, &filter)?;
+        } else if commit.files.contains(&file_path.to_string()) {
             // Read the blob content from the objects directory
             let blob_path = self.rune_dir.join("objects").join(format!("{}.blob", file_path.replace("/", "_")));
             if blob_path.exists() {
@@ -1058,12 +2260,10 @@ impl Store {
         Ok(())
     }
 
-    /// Get a commit by ID (helper method)
-    fn get_commit(&self, commit_id: &str) -> Result<Commit> {
-        let log = self.log();
-        log.into_iter()
-            .find(|c| c.id == commit_id || c.id.starts_with(commit_id))
-            .ok_or_else(|| anyhow::anyhow!("Commit '{}' not found", commit_id))
+    /// Commit with id `commit_id` or, failing that, the only commit whose id
+    /// starts with it
+    pub fn resolve_commit(&self, commit_id: &str) -> Result<Commit> {
+        Ok(find_commit(&self.log(), commit_id)?.clone())
     }
 
     pub fn create(&self) -> Result<()> {
@@ -1071,16 +2271,17 @@ impl Store {
         fs::create_dir_all(self.rune_dir.join("objects"))?;
         fs::create_dir_all(self.rune_dir.join("refs/heads"))?;
         
-        // Only create main branch if it doesn't exist
-        let main_ref = self.rune_dir.join("refs/heads/main");
-        if !main_ref.exists() {
-            fs::write(main_ref, b"")?;
+        // Only create the default branch if it doesn't exist; it stays unborn
+        // (an empty ref) until the first commit
+        let default_ref = format!("refs/heads/{}", self.config().core.default_branch);
+        if !self.rune_dir.join(&default_ref).exists() {
+            self.write_ref(&default_ref, "")?;
         }
-        
+
         // Only set HEAD if it doesn't exist
         let head_file = self.rune_dir.join("HEAD");
         if !head_file.exists() {
-            self.set_head("refs/heads/main")?;
+            self.set_head(&default_ref)?;
         }
         
         // Only create index if it doesn't exist
@@ -1093,94 +2294,176 @@ impl Store {
     }
 
     /// Detect merge conflicts between two commits
-    fn detect_merge_conflicts(&self, _current_commit: &str, _merge_commit: &str) -> Result<Vec<String>> {
-        // Simplified implementation - in a real system, this would compare file trees
-        // For now, we'll simulate some conflicts for demonstration
-        Ok(vec![]) // No conflicts for now
-    }
-
-    /// Save merge state for abort/continue operations
-    fn save_merge_state(&self, branch_name: &str, current_commit: &str, merge_commit: &str, strategy: Option<&str>) -> Result<()> {
-        #[derive(Serialize)]
-        struct MergeState {
-            branch_name: String,
-            current_commit: String,
-            merge_commit: String,
-            strategy: Option<String>,
-        }
-
-        let merge_state = MergeState {
-            branch_name: branch_name.to_string(),
-            current_commit: current_commit.to_string(),
-            merge_commit: merge_commit.to_string(),
-            strategy: strategy.map(|s| s.to_string()),
-        };
+    fn detect_merge_conflicts(&self, current_commit: &str, merge_commit: &str, branch_name: &str) -> Result<MergePlan> {
+        let _batch = self.batch_index_writes();
+        let base = self
+            .merge_base(current_commit, merge_commit)
+            .map(|id| self.tree_snapshot(&id))
+            .unwrap_or_default();
+        let ours = self.tree_snapshot(current_commit);
+        let theirs = self.tree_snapshot(merge_commit);
+        let filter = self.eol_filter()?;
+
+        let paths: std::collections::BTreeSet<&String> = ours.keys().chain(theirs.keys()).collect();
+        let mut plan = MergePlan::default();
+        for path in paths {
+            let (b, o, t) = (base.get(path), ours.get(path), theirs.get(path));
+            if o == t || b == t {
+                continue;
+            }
+            // Changed on their side only: their version is taken as it is
+            if b == o {
+                let dest = self.root.join(path);
+                match t {
+                    Some(blob) => {
+                        if let Some(parent) = dest.parent() {
+                            fs::create_dir_all(parent)?;
+                        }
+                        fs::write(&dest, &*filter.to_worktree(Path::new(path), &self.read_blob(blob)?))?;
+                    }
+                    None if dest.exists() => fs::remove_file(&dest)?,
+                    None => {}
+                }
+                self.stage_file(path)?;
+                plan.merged.push(path.clone());
+                continue;
+            }
+            // Only changes made on both sides, and made differently, conflict
+            let read = |blob: Option<&String>| blob.map(|b| self.read_blob(b)).transpose();
+            let (base_content, ours_content, theirs_content) = (read(b)?, read(o)?, read(t)?);
+
+            // Edited on both sides: the file's merge driver gets a go first.
+            // A deletion on either side is always left to the user.
+            let mut driver_conflict = None;
+            if let (Some(ours_content), Some(theirs_content)) = (&ours_content, &theirs_content) {
+                let input = MergeInput {
+                    path,
+                    base: base_content.as_deref().unwrap_or_default(),
+                    ours: ours_content,
+                    theirs: theirs_content,
+                    ours_label: "HEAD",
+                    theirs_label: branch_name,
+                    conflict_style: self.config().merge.conflict_style,
+                };
+                match self.merge_file(&input)? {
+                    MergeOutcome::Merged(content) => {
+                        let dest = self.root.join(path);
+                        fs::write(&dest, &*filter.to_worktree(Path::new(path), &content))?;
+                        self.stage_file(path)?;
+                        plan.merged.push(path.clone());
+                        continue;
+                    }
+                    MergeOutcome::Conflict { content, conflict } => driver_conflict = Some((content, conflict)),
+                }
+            }
+
+            let merge_dir = self.conflict_dir(path)?;
+            fs::create_dir_all(&merge_dir)?;
+            for (side, content) in [
+                (ConflictSide::Base, base_content),
+                (ConflictSide::Ours, ours_content),
+                (ConflictSide::Theirs, theirs_content),
+            ] {
+                if let Some(content) = content {
+                    fs::write(merge_dir.join(side.file_name()), content)?;
+                }
+            }
+            let details = driver_conflict.map(|(content, conflict)| {
+                fs::write(merge_dir.join(DRIVER_RESULT), content).map(|_| conflict)
+            });
+            plan.conflicts.insert(path.clone(), details.transpose()?);
+        }
+        Ok(plan)
+    }
 
+    /// What the merge driver reported for a file conflicted by the merge in
+    /// progress; `None` when the file was not given to a driver
+    pub fn merge_conflict(&self, path: &str) -> Result<Option<MergeConflict>> {
+        Ok(self.read_merge_state()?.details.get(path).cloned())
+    }
+
+    fn read_merge_state(&self) -> Result<MergeState> {
         let merge_file = self.rune_dir.join("MERGE_STATE");
-        let json = serde_json::to_string_pretty(&merge_state)?;
-        fs::write(merge_file, json)?;
+        if !merge_file.exists() {
+            return Err(StoreError::NoMergeInProgress.into());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(merge_file)?)?)
+    }
+
+    fn write_merge_state(&self, merge_state: &MergeState) -> Result<()> {
+        let json = serde_json::to_string_pretty(merge_state)?;
+        fs::write(self.rune_dir.join("MERGE_STATE"), json)?;
         Ok(())
     }
 
-    /// Apply merge conflicts to working directory
-    fn apply_merge_conflicts(&self, conflicts: &[String]) -> Result<()> {
-        // In a real implementation, this would write conflict markers to files
-        for file in conflicts {
-            let file_path = self.root.join(file);
-            let conflict_content = format!(
-                "<<<<<<< HEAD\n(current branch content)\n=======\n(merge branch content)\n>>>>>>> branch\n"
-            );
-            if let Some(parent) = file_path.parent() {
-                fs::create_dir_all(parent)?;
-            }
-            fs::write(file_path, conflict_content)?;
+    /// Drop MERGE_STATE and the stored conflict versions
+    fn finish_merge(&self) -> Result<()> {
+        let merge_dir = self.rune_dir.join("merge");
+        if merge_dir.exists() {
+            fs::remove_dir_all(merge_dir)?;
+        }
+        let merge_file = self.rune_dir.join("MERGE_STATE");
+        if merge_file.exists() {
+            fs::remove_file(merge_file)?;
         }
         Ok(())
     }
 
     /// Abort an in-progress merge
     pub fn abort_merge(&self) -> Result<()> {
-        let merge_file = self.rune_dir.join("MERGE_STATE");
-        if !merge_file.exists() {
-            return Err(anyhow::anyhow!("No merge in progress"));
+        let state = self.read_merge_state()?;
+        self.run_discard_hooks(DiscardOperation::Abort)?;
+
+        // Put our side of every conflicted file back
+        for path in &state.conflicts {
+            let dest = self.root.join(path);
+            match fs::read(self.conflict_version_path(path, ConflictSide::Ours)?) {
+                Ok(content) => fs::write(&dest, content)?,
+                Err(_) if dest.exists() => fs::remove_file(&dest)?,
+                Err(_) => {}
+            }
         }
+        // Files taken from their side or combined by merge drivers go back
+        // to our version too
+        let ours = self.tree_snapshot(&state.current_commit);
+        for path in &state.merged {
+            let dest = self.root.join(path);
+            match ours.get(path) {
+                Some(blob) => {
+                    let content = self.read_blob(blob)?;
+                    fs::write(&dest, &*self.eol_filter()?.to_worktree(Path::new(path), &content))?;
+                }
+                None if dest.exists() => fs::remove_file(&dest)?,
+                None => {}
+            }
+        }
+        let mut index = self.read_index()?;
+        for path in state.conflicts.iter().chain(&state.merged) {
+            index.entries.remove(path);
+            index.blobs.remove(path);
+        }
+        self.write_index(&index)?;
 
-        // Remove merge state file
-        fs::remove_file(merge_file)?;
-
-        // Reset working directory to current branch state
-        self.clean_working_directory()?;
-
-        Ok(())
+        self.finish_merge()
     }
 
     /// Continue a merge after resolving conflicts
     pub fn continue_merge(&self) -> Result<()> {
-        let merge_file = self.rune_dir.join("MERGE_STATE");
-        if !merge_file.exists() {
-            return Err(anyhow::anyhow!("No merge in progress"));
-        }
+        let merge_state = self.read_merge_state()?;
 
-        #[derive(Deserialize)]
-        struct MergeState {
-            branch_name: String,
-            current_commit: String,
-            merge_commit: String,
-            strategy: Option<String>,
+        let unresolved = self.unresolved_conflicts()?;
+        if !unresolved.is_empty() {
+            return Err(StoreError::UnresolvedConflicts(unresolved).into());
         }
 
-        // Read merge state
-        let json = fs::read_to_string(&merge_file)?;
-        let merge_state: MergeState = serde_json::from_str(&json)?;
-
         // Check if all conflicts are resolved (no files with conflict markers)
         if self.has_unresolved_conflicts()? {
-            return Err(anyhow::anyhow!("Please resolve all conflicts before continuing"));
+            return Err(StoreError::UnresolvedConflicts(vec![]).into());
         }
 
         // Create merge commit
         let current_branch = self.current_branch()
-            .ok_or_else(|| anyhow::anyhow!("Not on a branch"))?;
+            .ok_or(StoreError::NotOnBranch)?;
         
         let mut message = format!("Merge branch '{}' into {}", merge_state.branch_name, current_branch);
         if let Some(strategy) = merge_state.strategy {
@@ -1190,10 +2473,7 @@ impl Store {
         let merge_commit = self.create_merge_commit(&merge_state.current_commit, &merge_state.merge_commit, &message)?;
         self.write_ref(&format!("refs/heads/{}", current_branch), &merge_commit)?;
 
-        // Remove merge state file
-        fs::remove_file(merge_file)?;
-
-        Ok(())
+        self.finish_merge()
     }
 
     /// Check if there are unresolved conflicts in working directory
@@ -1214,12 +2494,26 @@ impl Store {
         Ok(false)
     }
 
+    fn read_rebase_state(&self) -> Result<Option<RebaseState>> {
+        let rebase_file = self.rune_dir.join("REBASE_STATE");
+        if !rebase_file.exists() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&fs::read_to_string(rebase_file)?)?))
+    }
+
+    fn write_rebase_state(&self, state: &RebaseState) -> Result<()> {
+        fs::write(self.rune_dir.join("REBASE_STATE"), serde_json::to_string_pretty(state)?)?;
+        Ok(())
+    }
+
     /// Abort an in-progress rebase
     pub fn abort_rebase(&self) -> Result<()> {
         let rebase_file = self.rune_dir.join("REBASE_STATE");
         if !rebase_file.exists() {
-            return Err(anyhow::anyhow!("No rebase in progress"));
+            return Err(StoreError::NoRebaseInProgress.into());
         }
+        self.run_discard_hooks(DiscardOperation::Abort)?;
 
         // Remove rebase state file
         fs::remove_file(rebase_file)?;
@@ -1233,31 +2527,20 @@ impl Store {
     /// Continue a rebase after resolving conflicts
     pub fn continue_rebase(&self) -> Result<()> {
         let rebase_file = self.rune_dir.join("REBASE_STATE");
-        if !rebase_file.exists() {
-            return Err(anyhow::anyhow!("No rebase in progress"));
-        }
-
-        #[derive(Deserialize, Serialize)]
-        struct RebaseState {
-            target_commit: String,
-            current_commit: String,
-            remaining_commits: Vec<String>,
-        }
-
-        // Read rebase state
-        let json = fs::read_to_string(&rebase_file)?;
-        let mut rebase_state: RebaseState = serde_json::from_str(&json)?;
+        let mut rebase_state = self
+            .read_rebase_state()?
+            .ok_or(StoreError::NoRebaseInProgress)?;
 
         // Check if all conflicts are resolved
         if self.has_unresolved_conflicts()? {
-            return Err(anyhow::anyhow!("Please resolve all conflicts before continuing"));
+            return Err(StoreError::UnresolvedConflicts(vec![]).into());
         }
 
         // Apply current commit
         if !rebase_state.current_commit.is_empty() {
             // Create a new commit with resolved changes
             let current_branch = self.current_branch()
-                .ok_or_else(|| anyhow::anyhow!("Not on a branch"))?;
+                .ok_or(StoreError::NotOnBranch)?;
             
             // For now, just update the branch ref (simplified)
             self.write_ref(&format!("refs/heads/{}", current_branch), &rebase_state.current_commit)?;
@@ -1270,8 +2553,7 @@ impl Store {
         } else {
             // Update rebase state with next commit
             rebase_state.current_commit = rebase_state.remaining_commits.remove(0);
-            let json = serde_json::to_string_pretty(&rebase_state)?;
-            fs::write(rebase_file, json)?;
+            self.write_rebase_state(&rebase_state)?;
         }
 
         Ok(())
@@ -1280,20 +2562,9 @@ impl Store {
     /// Skip current commit during rebase
     pub fn skip_rebase_commit(&self) -> Result<()> {
         let rebase_file = self.rune_dir.join("REBASE_STATE");
-        if !rebase_file.exists() {
-            return Err(anyhow::anyhow!("No rebase in progress"));
-        }
-
-        #[derive(Deserialize, Serialize)]
-        struct RebaseState {
-            target_commit: String,
-            current_commit: String,
-            remaining_commits: Vec<String>,
-        }
-
-        // Read rebase state
-        let json = fs::read_to_string(&rebase_file)?;
-        let mut rebase_state: RebaseState = serde_json::from_str(&json)?;
+        let mut rebase_state = self
+            .read_rebase_state()?
+            .ok_or(StoreError::NoRebaseInProgress)?;
 
         // Skip current commit and move to next
         if rebase_state.remaining_commits.is_empty() {
@@ -1302,8 +2573,7 @@ impl Store {
         } else {
             // Move to next commit
             rebase_state.current_commit = rebase_state.remaining_commits.remove(0);
-            let json = serde_json::to_string_pretty(&rebase_state)?;
-            fs::write(rebase_file, json)?;
+            self.write_rebase_state(&rebase_state)?;
         }
 
         Ok(())
@@ -1313,17 +2583,21 @@ impl Store {
     pub fn show_file_at_commit(&self, commit_id: &str, file_path: &str) -> Result<String> {
         // Find the commit
         let commits = self.log();
-        let commit = commits.iter()
-            .find(|c| c.id == commit_id || c.id.starts_with(commit_id))
-            .ok_or_else(|| anyhow::anyhow!("Commit '{}' not found", commit_id))?;
+        let commit = find_commit(&commits, commit_id)?;
 
         // Check if file exists in this commit
         if !commit.files.contains(&file_path.to_string()) {
             return Err(anyhow::anyhow!("File '{}' not found in commit {}", file_path, commit_id));
         }
 
-        // For now, we'll try to read from the current working directory
-        // In a real implementation, this would read from the commit's file tree
+        if self.tree_entry(&commit.id, file_path).as_deref() == Some(DELETED_BLOB) {
+            anyhow::bail!("File '{}' was deleted in commit {}", file_path, commit_id);
+        }
+        if let Some(blob) = self.blob_at(&commit.id, file_path) {
+            return Ok(String::from_utf8_lossy(&self.read_blob(&blob)?).into_owned());
+        }
+
+        // Commits made before content was stored fall back to the working copy
         let file_full_path = self.root.join(file_path);
         
         if file_full_path.exists() {
@@ -1340,6 +2614,7 @@ mod tests {
     use super::*;
     use tempfile::TempDir;
     use std::fs;
+    use crate::test_support::{author, temp_repo};
 
     fn create_initialized_store() -> (TempDir, Store) {
         let temp_dir = TempDir::new().unwrap();
@@ -1348,6 +2623,113 @@ mod tests {
         (temp_dir, store)
     }
 
+    #[test]
+    fn test_amend_keeps_author_and_records_committer() {
+        let (temp_dir, store) = create_initialized_store();
+        let alice = Author { name: "Alice".into(), email: "alice@example.com".into() };
+        let bob = Author { name: "Bob".into(), email: "bob@example.com".into() };
+        for name in ["a.txt", "b.txt"] {
+            fs::write(temp_dir.path().join(name), name).unwrap();
+            store.stage_file(name).unwrap();
+            store.commit(&format!("add {}", name), alice.clone()).unwrap();
+        }
+        let before = store.log();
+        assert_eq!(before[1].committer.email, "alice@example.com");
+
+        let amended = store.commit_amend("add b.txt properly", true, bob).unwrap();
+        assert_eq!(amended.author.email, "alice@example.com");
+        assert_eq!(amended.committer.email, "bob@example.com");
+        assert_eq!(amended.parent.as_deref(), Some(before[0].id.as_str()));
+
+        // Only the tip is replaced, and the log keeps its order
+        let after = store.log();
+        assert_eq!(after.len(), 2);
+        assert_eq!(after[0].id, before[0].id);
+        assert_eq!(after[1].id, amended.id);
+        assert_eq!(after[1].author.name, "Alice");
+        assert_eq!(after[1].committer.name, "Bob");
+        assert_eq!(store.head_commit(), Some(amended.id));
+    }
+
+    #[test]
+    fn test_reflog_lists_newest_first() {
+        let (temp_dir, store) = create_initialized_store();
+        let mut ids = Vec::new();
+        for (i, message) in ["first", "second"].iter().enumerate() {
+            fs::write(temp_dir.path().join("f.txt"), i.to_string()).unwrap();
+            store.stage_file("f.txt").unwrap();
+            ids.push(store.commit(message, author()).unwrap().id);
+        }
+
+        let reflog = store.reflog("refs/heads/main").unwrap();
+        assert_eq!(reflog.len(), 2);
+        assert_eq!(reflog[0].commit, ids[1]);
+        assert_eq!(reflog[0].message, "commit: second");
+        assert!(store.reflog("refs/heads/none").unwrap().is_empty());
+    }
+
+    /// Rewrite the reflog of `ref_name` with the given entry ages in days,
+    /// oldest first
+    fn backdate_reflog(store: &Store, ref_name: &str, ages: &[(i64, &str)]) {
+        let now = Utc::now().timestamp();
+        let content: String = ages
+            .iter()
+            .map(|(days, id)| format!("{} {} commit: {}\n", now - days * 86_400, id, days))
+            .collect();
+        fs::write(store.rune_dir.join("logs").join(ref_name.replace("/", "_")), content).unwrap();
+    }
+
+    #[test]
+    fn test_prune_reflog_drops_old_entries() {
+        let (temp_dir, store) = create_initialized_store();
+        fs::write(temp_dir.path().join("f.txt"), "x").unwrap();
+        store.stage_file("f.txt").unwrap();
+        let tip = store.commit("tip", author()).unwrap().id;
+        backdate_reflog(&store, "refs/heads/main", &[(120, "a"), (60, "b"), (5, "c"), (1, &tip)]);
+
+        assert_eq!(store.prune_reflog("refs/heads/main", 30).unwrap(), 2);
+        let kept: Vec<String> = store.reflog("refs/heads/main").unwrap().into_iter().map(|e| e.commit).collect();
+        assert_eq!(kept, [tip.clone(), "c".to_string()]);
+        assert_eq!(store.prune_reflog("refs/heads/main", 30).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_prune_reflog_keeps_the_tip_however_old() {
+        let (temp_dir, store) = create_initialized_store();
+        fs::write(temp_dir.path().join("f.txt"), "x").unwrap();
+        store.stage_file("f.txt").unwrap();
+        let tip = store.commit("tip", author()).unwrap().id;
+        store.create_branch("old").unwrap();
+        backdate_reflog(&store, "refs/heads/main", &[(400, &tip), (300, "gone")]);
+        backdate_reflog(&store, "refs/heads/old", &[(200, "x"), (100, "y")]);
+
+        assert_eq!(store.prune_all_reflogs(30).unwrap(), 2);
+        let main: Vec<String> = store.reflog("refs/heads/main").unwrap().into_iter().map(|e| e.commit).collect();
+        assert_eq!(main, [tip]);
+        // No entry records the branch's tip: the newest one stays
+        let old: Vec<String> = store.reflog("refs/heads/old").unwrap().into_iter().map(|e| e.commit).collect();
+        assert_eq!(old, ["y"]);
+    }
+
+    #[test]
+    fn test_prune_reflog_keeps_the_tip_of_packed_refs() {
+        let (temp_dir, store) = create_initialized_store();
+        fs::write(temp_dir.path().join("f.txt"), "x").unwrap();
+        store.stage_file("f.txt").unwrap();
+        let tip = store.commit("tip", author()).unwrap().id;
+        store.create_lightweight_tag("v1", &tip).unwrap();
+        store.write_ref("refs/remotes/origin/main", &tip).unwrap();
+        assert_eq!(store.pack_refs().unwrap(), 2);
+        backdate_reflog(&store, "refs/tags/v1", &[(400, &tip), (300, "gone")]);
+        backdate_reflog(&store, "refs/remotes/origin/main", &[(400, &tip), (300, "gone")]);
+
+        assert_eq!(store.prune_all_reflogs(30).unwrap(), 2);
+        for ref_name in ["refs/tags/v1", "refs/remotes/origin/main"] {
+            let kept: Vec<String> = store.reflog(ref_name).unwrap().into_iter().map(|e| e.commit).collect();
+            assert_eq!(kept, [tip.as_str()], "{}", ref_name);
+        }
+    }
+
     #[test]
     fn test_store_open() {
         let temp_dir = TempDir::new().unwrap();
@@ -1404,18 +2786,34 @@ mod tests {
         let new_config = RuneConfig {
             core: CoreCfg {
                 default_branch: "develop".to_string(),
+                autocrlf: AutoCrlf::Input,
+                ..CoreCfg::default()
             },
             lfs: LfsCfg {
                 chunk_size: 1024,
                 remote: None,
                 track: vec![],
             },
+            mergetool: MergetoolCfg::default(),
+            release: ReleaseCfg::default(),
+            merge_drivers: BTreeMap::new(),
+            merge: MergeCfg::default(),
+            commit: CommitCfg::default(),
+            maintenance: MaintenanceCfg::default(),
+            fetch: FetchCfg::default(),
+            pull: PullCfg::default(),
+            branch: BTreeMap::new(),
+            alias: BTreeMap::new(),
+            defaults: BTreeMap::new(),
+            sparse: SparseCfg::default(),
+            signing: SigningCfg::default(),
         };
         
         store.write_config(&new_config).unwrap();
         let read_config = store.config();
         
         assert_eq!(read_config.core.default_branch, "develop");
+        assert_eq!(read_config.core.autocrlf, AutoCrlf::Input);
         assert_eq!(read_config.lfs.chunk_size, 1024);
     }
 
@@ -1614,4 +3012,280 @@ mod tests {
         assert!(lfs_cfg.remote.is_none());
         assert!(lfs_cfg.track.is_empty());
     }
+
+    #[test]
+    fn test_diff_honors_binary_attribute() {
+        let (_temp_dir, store) = create_initialized_store();
+        let author = Author {
+            name: "Test User".to_string(),
+            email: "test@example.com".to_string(),
+        };
+
+        fs::write(store.root.join("notes.txt"), "hello").unwrap();
+        store.stage_file("notes.txt").unwrap();
+        store.commit("Initial commit", author).unwrap();
+
+        fs::write(store.root.join(".runeattributes"), "*.bin binary\n").unwrap();
+        fs::write(store.root.join("data.bin"), [0u8, 1, 2, 3]).unwrap();
+        fs::write(store.root.join("notes.txt"), "hello again").unwrap();
+        store.stage_file("data.bin").unwrap();
+        store.stage_file("notes.txt").unwrap();
+
+        let diff = store.diff(None).unwrap();
+        assert!(diff.contains("M  data.bin (binary files differ)"));
+        assert!(diff.contains("M  notes.txt\n"));
+    }
+
+    #[test]
+    fn test_fresh_repo_is_unborn() {
+        let (_temp_dir, store) = temp_repo();
+        assert!(store.is_unborn());
+        assert_eq!(store.head_commit(), None);
+        assert_eq!(store.current_branch(), Some("main".to_string()));
+        assert!(store.branch_exists("main"));
+        assert!(store.log().is_empty());
+    }
+
+    #[test]
+    fn test_status_reports_untracked_files() {
+        let (_temp_dir, store) = temp_repo();
+        fs::write(store.root.join("README.md"), "hello").unwrap();
+
+        let status = store.status().unwrap();
+        assert!(status.staging.is_empty());
+        assert!(status.working.is_empty());
+        assert_eq!(status.untracked, vec!["README.md".to_string()]);
+    }
+
+    #[test]
+    fn test_diff_before_first_commit() {
+        let (_temp_dir, store) = temp_repo();
+        assert_eq!(store.diff(None).unwrap(), "No commits yet. All files are new.");
+
+        let err = store.diff(Some("abc123")).unwrap_err();
+        assert!(err.to_string().contains("No commits yet"));
+    }
+
+    #[test]
+    fn test_unborn_branch_create_and_checkout() {
+        let (_temp_dir, store) = temp_repo();
+        store.create_branch("feature").unwrap();
+        assert!(store.branch_exists("feature"));
+        assert_eq!(store.read_ref("refs/heads/feature"), None);
+
+        store.checkout_branch("feature").unwrap();
+        assert_eq!(store.current_branch(), Some("feature".to_string()));
+        assert!(store.is_unborn());
+
+        let mut branches = store.list_branches().unwrap();
+        branches.sort();
+        assert_eq!(branches, vec!["feature".to_string(), "main".to_string()]);
+    }
+
+    #[test]
+    fn test_first_commit_on_unborn_branch() {
+        let (_temp_dir, store) = temp_repo();
+        store.create_branch("feature").unwrap();
+        store.checkout_branch("feature").unwrap();
+
+        fs::write(store.root.join("a.txt"), "a").unwrap();
+        store.stage_file("a.txt").unwrap();
+        let commit = store.commit("first", author()).unwrap();
+
+        assert!(commit.parent.is_none());
+        assert_eq!(commit.branch, "refs/heads/feature");
+        assert_eq!(store.read_ref("refs/heads/feature"), Some(commit.id));
+        assert_eq!(store.read_ref("refs/heads/main"), None);
+    }
+
+    #[test]
+    fn test_merge_into_unborn_branch_fast_forwards() {
+        let (_temp_dir, store) = temp_repo();
+        store.create_branch("feature").unwrap();
+        store.checkout_branch("feature").unwrap();
+        fs::write(store.root.join("a.txt"), "a").unwrap();
+        store.stage_file("a.txt").unwrap();
+        let commit = store.commit("first", author()).unwrap();
+
+        store.checkout_branch("main").unwrap();
+        let result = store.merge_branch("feature", false, None).unwrap();
+        assert!(matches!(result, MergeResult::FastForward));
+        assert_eq!(store.head_commit(), Some(commit.id));
+    }
+
+    #[test]
+    fn test_merge_unborn_branch_explains() {
+        let (_temp_dir, store) = temp_repo();
+        store.create_branch("feature").unwrap();
+
+        let err = store.merge_branch("feature", false, None).unwrap_err();
+        assert!(err.to_string().contains("has no commits yet"));
+    }
+
+    #[test]
+    fn test_custom_default_branch() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = Store::open(temp_dir.path()).unwrap();
+        let mut cfg = store.config();
+        cfg.core.default_branch = "trunk".to_string();
+        store.write_config(&cfg).unwrap();
+        store.create().unwrap();
+
+        assert_eq!(store.head_ref(), "refs/heads/trunk");
+        assert!(store.branch_exists("trunk"));
+        assert!(!store.branch_exists("main"));
+
+        fs::write(store.root.join("a.txt"), "a").unwrap();
+        store.stage_file("a.txt").unwrap();
+        let commit = store.commit("first", author()).unwrap();
+        assert_eq!(store.read_ref("refs/heads/trunk"), Some(commit.id));
+    }
+
+    #[test]
+    fn test_detached_metadata_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let tree = temp_dir.path().join("tree");
+        let meta = temp_dir.path().join("meta");
+        fs::create_dir_all(&tree).unwrap();
+        let store = Store::open_layout(RepoLayout { root: tree.clone(), rune_dir: meta.clone() }).unwrap();
+        store.create().unwrap();
+
+        fs::write(tree.join("a.txt"), "a\n").unwrap();
+        assert_eq!(store.status().unwrap().untracked, vec!["a.txt".to_string()]);
+        store.stage_file("a.txt").unwrap();
+        let commit = store.commit("add a", author()).unwrap();
+
+        assert!(meta.join("HEAD").exists());
+        assert!(!tree.join(".rune").exists());
+        assert_eq!(store.head_commit(), Some(commit.id));
+        let status = store.status().unwrap();
+        assert!(status.untracked.is_empty() && status.working.is_empty() && status.staging.is_empty());
+    }
+
+    #[test]
+    fn test_metadata_inside_tree_is_not_listed() {
+        let temp_dir = TempDir::new().unwrap();
+        let meta = temp_dir.path().join("metadata");
+        let store = Store::open_layout(RepoLayout { root: temp_dir.path().into(), rune_dir: meta }).unwrap();
+        store.create().unwrap();
+        fs::write(temp_dir.path().join("a.txt"), "a\n").unwrap();
+
+        assert_eq!(store.status().unwrap().untracked, vec!["a.txt".to_string()]);
+    }
+
+    #[test]
+    fn test_walks_skip_internal_dirs() {
+        let (temp_dir, store) = temp_repo();
+        let mut cfg = store.config();
+        cfg.core.internal_dirs = vec!["shrine\\data".into()];
+        store.write_config(&cfg).unwrap();
+        for dir in [".rune/lfs/objects/ab/cd/abcd", ".rune/drafts", "shrine/data/repos"] {
+            fs::create_dir_all(temp_dir.path().join(dir)).unwrap();
+            fs::write(temp_dir.path().join(dir).join("file.json"), "{}\n").unwrap();
+        }
+        fs::write(temp_dir.path().join(".rune-notes.txt"), "not metadata\n").unwrap();
+        fs::write(temp_dir.path().join("a.txt"), "a\n").unwrap();
+
+        let mut untracked = store.status().unwrap().untracked;
+        untracked.sort();
+        assert_eq!(untracked, [".rune-notes.txt", "a.txt"]);
+
+        store.stage_file("a.txt").unwrap();
+        store.commit("add a", author()).unwrap();
+        let diff = store.diff(None).unwrap();
+        assert!(diff.contains(".rune-notes.txt"), "{}", diff);
+        assert!(!diff.contains("file.json"), "{}", diff);
+
+        store.record_change("shrine\\data\\repos\\file.json").unwrap();
+        store.record_change(".rune\\drafts\\file.json").unwrap();
+        assert!(store.read_change_journal().unwrap().is_none_or(|(changed, _)| changed.is_empty()));
+    }
+
+    #[test]
+    fn test_discover_follows_pointer_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let tree = temp_dir.path().join("tree");
+        let meta = temp_dir.path().join("meta");
+        fs::create_dir_all(tree.join("src")).unwrap();
+        let store = Store::open_layout(RepoLayout { root: tree.clone(), rune_dir: meta }).unwrap();
+        store.create().unwrap();
+        fs::write(tree.join("a.txt"), "a\n").unwrap();
+        store.stage_file("a.txt").unwrap();
+        store.commit("add a", author()).unwrap();
+
+        fs::write(tree.join(".rune"), "runedir: ../meta\n").unwrap();
+        let found = Store::discover(tree.join("src")).unwrap();
+        assert_eq!(found.root, tree);
+        assert_eq!(found.head_commit(), store.head_commit());
+        assert!(found.status().unwrap().untracked.is_empty());
+    }
+
+    /// Temporary files `write_atomic` left in `.rune`
+    fn temp_files(store: &Store) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = fs::read_dir(&store.rune_dir)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|p| {
+                p.to_string_lossy().ends_with(".lock") && !p.ends_with("maintenance.lock") && !p.ends_with("repo.lock")
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn test_interrupted_index_write_keeps_prior_index() {
+        let (temp_dir, store) = temp_repo();
+        fs::write(temp_dir.path().join("a.txt"), "a\n").unwrap();
+        store.stage_file("a.txt").unwrap();
+        let prior = store.read_index().unwrap();
+
+        // A crash mid-write leaves a partial temporary file next to the index
+        let mut next = store.read_index().unwrap();
+        next.entries.insert("b.txt".into(), 1);
+        next.blobs.insert("b.txt".into(), "b".repeat(64));
+        let bytes = serde_json::to_vec_pretty(&next).unwrap();
+        let tmp = store.rune_dir.join("index.json.4242-0.lock");
+        fs::write(&tmp, &bytes[..bytes.len() / 2]).unwrap();
+
+        let read = store.read_index().unwrap();
+        assert_eq!(read.entries, prior.entries);
+        assert_eq!(read.blobs, prior.blobs);
+
+        // The next write goes through a temporary file of its own and fully applies
+        store.write_index(&next).unwrap();
+        assert_eq!(temp_files(&store), [tmp]);
+        assert_eq!(store.read_index().unwrap().entries, next.entries);
+    }
+
+    #[test]
+    fn test_truncated_index_is_reported() {
+        let (temp_dir, store) = temp_repo();
+        fs::write(temp_dir.path().join("a.txt"), "a\n").unwrap();
+        store.stage_file("a.txt").unwrap();
+        let path = store.rune_dir.join("index.json");
+        let content = fs::read(&path).unwrap();
+        fs::write(&path, &content[..content.len() / 2]).unwrap();
+
+        let Err(err) = store.read_index() else {
+            panic!("a truncated index should not parse");
+        };
+        assert!(err.to_string().contains("index.json is corrupt"), "{}", err);
+    }
+
+    #[test]
+    fn test_amend_rewrites_log_atomically() {
+        let (temp_dir, store) = temp_repo();
+        for name in ["a.txt", "b.txt"] {
+            fs::write(temp_dir.path().join(name), name).unwrap();
+            store.stage_file(name).unwrap();
+            store.commit(&format!("add {}", name), author()).unwrap();
+        }
+        store.commit_amend("add b, amended", true, author()).unwrap();
+
+        assert!(temp_files(&store).is_empty());
+        let messages: Vec<String> = store.log().into_iter().map(|c| c.message).collect();
+        assert_eq!(messages.len(), 2);
+        assert!(messages.contains(&"add b, amended".to_string()));
+    }
 }
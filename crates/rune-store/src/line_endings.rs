@@ -0,0 +1,167 @@
+//! Line endings in the working tree: `core.autocrlf` and the `eol` and
+//! `text` attributes decide how text files are normalized on stage and
+//! rendered on checkout; the conversion itself is `rune_core::eol`.
+
+use crate::Store;
+use anyhow::Result;
+use rune_core::attributes::AttributeEngine;
+use rune_core::eol::{self, EolFilter, LineEnding};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+impl Store {
+    /// Text files among `paths` whose working copy mixes CRLF and LF
+    pub(crate) fn mixed_line_endings<'p>(&self, paths: impl Iterator<Item = &'p String>) -> Vec<String> {
+        let filter = EolFilter::new(self.config().core.autocrlf, AttributeEngine::new(&self.root).ok());
+        let mut mixed: Vec<String> = paths
+            .filter(|p| {
+                fs::read(self.root.join(p)).is_ok_and(|content| {
+                    filter.is_text(Path::new(p), &content)
+                        && eol::detect_line_ending(&content) == Some(LineEnding::Mixed)
+                })
+            })
+            .cloned()
+            .collect();
+        mixed.sort();
+        mixed.dedup();
+        mixed
+    }
+
+    /// Line ending policy from `core.autocrlf` and `.runeattributes`, read
+    /// once for a whole batch of index writes
+    pub(crate) fn eol_filter(&self) -> Result<Arc<EolFilter>> {
+        let mut batch = self.index_batch.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(filter) = batch.as_ref().and_then(|batch| batch.filter.clone()) {
+            return Ok(filter);
+        }
+        let filter = Arc::new(EolFilter::new(
+            self.config().core.autocrlf,
+            Some(AttributeEngine::new(&self.root)?),
+        ));
+        if let Some(batch) = batch.as_mut() {
+            batch.filter = Some(filter.clone());
+        }
+        Ok(filter)
+    }
+
+    /// Rewrite a text file that mixes CRLF and LF with the single ending it
+    /// would get on checkout. Returns whether the file was changed.
+    pub fn normalize_line_endings(&self, rel: &str) -> Result<bool> {
+        let path = self.root.join(rel);
+        let content = fs::read(&path)?;
+        let filter = self.eol_filter()?;
+        if !filter.is_text(Path::new(rel), &content)
+            || eol::detect_line_ending(&content) != Some(LineEnding::Mixed)
+        {
+            return Ok(false);
+        }
+        let normalized = eol::to_lf(&content);
+        let normalized = filter.to_worktree(Path::new(rel), &normalized);
+        fs::write(&path, &*normalized)?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{author, temp_repo};
+    use rune_core::eol::AutoCrlf;
+    use tempfile::TempDir;
+
+    fn repo_with(autocrlf: AutoCrlf) -> (TempDir, Store) {
+        let (temp_dir, store) = temp_repo();
+        let mut cfg = store.config();
+        cfg.core.autocrlf = autocrlf;
+        store.write_config(&cfg).unwrap();
+        (temp_dir, store)
+    }
+
+    fn staged_blob(store: &Store, path: &str) -> Vec<u8> {
+        let idx = store.read_index().unwrap();
+        store.read_blob(&idx.blobs[path]).unwrap()
+    }
+
+    #[test]
+    fn test_stage_normalizes_crlf_with_autocrlf_input() {
+        let (temp_dir, store) = repo_with(AutoCrlf::Input);
+        fs::write(temp_dir.path().join("a.txt"), "one\r\ntwo\r\n").unwrap();
+        store.stage_file("a.txt").unwrap();
+        assert_eq!(staged_blob(&store, "a.txt"), b"one\ntwo\n");
+        // The working copy is left alone
+        assert_eq!(fs::read(temp_dir.path().join("a.txt")).unwrap(), b"one\r\ntwo\r\n");
+    }
+
+    #[test]
+    fn test_stage_keeps_crlf_without_normalization() {
+        let (temp_dir, store) = repo_with(AutoCrlf::False);
+        fs::write(temp_dir.path().join("a.txt"), "one\r\n").unwrap();
+        store.stage_file("a.txt").unwrap();
+        assert_eq!(staged_blob(&store, "a.txt"), b"one\r\n");
+    }
+
+    #[test]
+    fn test_checkout_renders_crlf_with_autocrlf_true() {
+        let (temp_dir, store) = repo_with(AutoCrlf::True);
+        fs::write(temp_dir.path().join("a.txt"), "one\ntwo\n").unwrap();
+        store.stage_file("a.txt").unwrap();
+        let commit = store.commit("add a", author()).unwrap();
+
+        fs::remove_file(temp_dir.path().join("a.txt")).unwrap();
+        store.restore_file_from_commit(&commit.id, Path::new("a.txt")).unwrap();
+        assert_eq!(fs::read(temp_dir.path().join("a.txt")).unwrap(), b"one\r\ntwo\r\n");
+        assert_eq!(store.show_file_at_commit(&commit.id, "a.txt").unwrap(), "one\ntwo\n");
+    }
+
+    #[test]
+    fn test_binary_file_is_never_converted() {
+        let (temp_dir, store) = repo_with(AutoCrlf::True);
+        let content = b"\x89PNG\r\n\x1a\n\x00\x00\r\n".to_vec();
+        fs::write(temp_dir.path().join("img.png"), &content).unwrap();
+        store.stage_file("img.png").unwrap();
+        assert_eq!(staged_blob(&store, "img.png"), content);
+
+        let commit = store.commit("add image", author()).unwrap();
+        fs::remove_file(temp_dir.path().join("img.png")).unwrap();
+        store.restore_file_from_commit(&commit.id, Path::new("img.png")).unwrap();
+        assert_eq!(fs::read(temp_dir.path().join("img.png")).unwrap(), content);
+    }
+
+    #[test]
+    fn test_eol_attribute_overrides_config() {
+        let (temp_dir, store) = repo_with(AutoCrlf::False);
+        fs::write(temp_dir.path().join(".runeattributes"), "*.bat eol=crlf\n").unwrap();
+        fs::write(temp_dir.path().join("run.bat"), "echo\r\n").unwrap();
+        store.stage_file("run.bat").unwrap();
+        assert_eq!(staged_blob(&store, "run.bat"), b"echo\n");
+    }
+
+    #[test]
+    fn test_status_flags_mixed_line_endings() {
+        let (temp_dir, store) = repo_with(AutoCrlf::Input);
+        fs::write(temp_dir.path().join("mixed.txt"), "one\r\ntwo\n").unwrap();
+        fs::write(temp_dir.path().join("clean.txt"), "one\ntwo\n").unwrap();
+        store.stage_file("mixed.txt").unwrap();
+        store.stage_file("clean.txt").unwrap();
+        assert_eq!(store.status().unwrap().mixed_line_endings, vec!["mixed.txt"]);
+
+        assert!(store.normalize_line_endings("mixed.txt").unwrap());
+        assert_eq!(fs::read(temp_dir.path().join("mixed.txt")).unwrap(), b"one\ntwo\n");
+        assert!(store.status().unwrap().mixed_line_endings.is_empty());
+    }
+
+    #[test]
+    fn test_line_ending_only_change_is_not_a_diff() {
+        let (temp_dir, store) = repo_with(AutoCrlf::Input);
+        fs::write(temp_dir.path().join("a.txt"), "one\ntwo\n").unwrap();
+        store.stage_file("a.txt").unwrap();
+        store.commit("add a", author()).unwrap();
+
+        fs::write(temp_dir.path().join("a.txt"), "one\r\ntwo\r\n").unwrap();
+        assert_eq!(store.diff(None).unwrap(), "No changes in working directory.");
+
+        fs::write(temp_dir.path().join("a.txt"), "one\r\nthree\r\n").unwrap();
+        assert!(store.diff(None).unwrap().contains("M  a.txt"));
+    }
+}
@@ -0,0 +1,135 @@
+//! Symbolic links in the working tree
+//!
+//! A link is stored as a blob holding its target path, with
+//! `FileMode::Symlink` recorded next to the blob id in the index and in
+//! commits. Scans don't follow links, so a link to a directory, or a loop
+//! of them, is a single entry. Checkout recreates links on Unix; elsewhere
+//! the target path is written as a plain file.
+
+use crate::Store;
+use anyhow::Result;
+use rune_core::eol::EolFilter;
+use rune_core::FileMode;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// What the store keeps for `path`: a link's target or a file's content
+pub(crate) fn read_worktree(path: &Path) -> io::Result<(Vec<u8>, FileMode)> {
+    if fs::symlink_metadata(path)?.file_type().is_symlink() {
+        let target = fs::read_link(path)?;
+        return Ok((target.to_string_lossy().replace('\\', "/").into_bytes(), FileMode::Symlink));
+    }
+    Ok((fs::read(path)?, FileMode::Regular))
+}
+
+/// Put `content` at `dest` as a file or, for `FileMode::Symlink`, as a link
+/// to the path it holds
+pub(crate) fn write_worktree(dest: &Path, content: &[u8], mode: FileMode) -> io::Result<()> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // Writing through an existing link would change what it points to
+    let replaced = fs::symlink_metadata(dest)
+        .is_ok_and(|m| m.file_type().is_symlink() || (mode == FileMode::Symlink && m.is_file()));
+    if replaced {
+        fs::remove_file(dest)?;
+    }
+    match mode {
+        #[cfg(unix)]
+        FileMode::Symlink => std::os::unix::fs::symlink(&*String::from_utf8_lossy(content), dest),
+        _ => fs::write(dest, content),
+    }
+}
+
+/// Remove the file or link at `dest`; a dangling link counts as there, a
+/// directory is left alone
+pub(crate) fn remove_worktree(dest: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(dest) {
+        Ok(meta) if meta.is_dir() => Ok(()),
+        Ok(_) => fs::remove_file(dest),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+impl Store {
+    /// Write blob `blob` to `path` in the working tree as `mode` says; only
+    /// regular files get their line endings converted
+    pub(crate) fn checkout_blob(&self, path: &str, blob: &str, mode: FileMode, filter: &EolFilter) -> Result<()> {
+        let content = self.read_blob(blob)?;
+        let dest = self.root.join(path);
+        match mode {
+            FileMode::Symlink => write_worktree(&dest, &content, mode)?,
+            FileMode::Regular => write_worktree(&dest, &filter.to_worktree(Path::new(path), &content), mode)?,
+        }
+        Ok(())
+    }
+
+    /// Paths that aren't regular files as of `commit_id`, with their modes
+    pub fn modes_snapshot(&self, commit_id: &str) -> BTreeMap<String, FileMode> {
+        let mut modes = BTreeMap::new();
+        for commit in self.ancestry(commit_id).into_iter().rev() {
+            for path in commit.tree.keys() {
+                match commit.modes.get(path) {
+                    Some(mode) => modes.insert(path.clone(), *mode),
+                    None => modes.remove(path),
+                };
+            }
+        }
+        modes
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use crate::test_support::{author, temp_repo};
+    use rune_core::FileMode;
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use std::path::Path;
+
+    #[test]
+    fn test_symlink_is_stored_as_a_link_and_restored() {
+        let (temp_dir, store) = temp_repo();
+        fs::write(temp_dir.path().join("real.txt"), "real content\n").unwrap();
+        symlink("real.txt", temp_dir.path().join("link.txt")).unwrap();
+        store.stage_file("real.txt").unwrap();
+        store.stage_file("link.txt").unwrap();
+        let commit = store.commit("add a link", author()).unwrap();
+
+        assert_eq!(commit.modes.get("link.txt"), Some(&FileMode::Symlink));
+        assert!(!commit.modes.contains_key("real.txt"));
+        let blob = store.blob_at(&commit.id, "link.txt").unwrap();
+        assert_eq!(store.read_blob(&blob).unwrap(), b"real.txt");
+        assert!(store.status().unwrap().working.is_empty());
+
+        // A regular file in its place is a change; restoring brings the link back
+        fs::remove_file(temp_dir.path().join("link.txt")).unwrap();
+        fs::write(temp_dir.path().join("link.txt"), "real content\n").unwrap();
+        assert_eq!(store.status().unwrap().working, vec!["link.txt".to_string()]);
+        store.restore_file_from_commit(&commit.id, Path::new("link.txt")).unwrap();
+        let link = temp_dir.path().join("link.txt");
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_link(&link).unwrap(), Path::new("real.txt"));
+        assert!(store.status().unwrap().working.is_empty());
+    }
+
+    #[test]
+    fn test_symlink_loop_does_not_hang_status() {
+        let (temp_dir, store) = temp_repo();
+        fs::create_dir(temp_dir.path().join("dir")).unwrap();
+        symlink("..", temp_dir.path().join("dir/up")).unwrap();
+        symlink("b", temp_dir.path().join("a")).unwrap();
+        symlink("a", temp_dir.path().join("b")).unwrap();
+
+        let mut untracked = store.status().unwrap().untracked;
+        untracked.sort();
+        assert_eq!(untracked, ["a", "b", "dir/up"]);
+
+        // Links that point nowhere are still staged as links
+        store.stage_file("a").unwrap();
+        assert_eq!(store.read_index().unwrap().modes.get("a"), Some(&FileMode::Symlink));
+    }
+}
@@ -0,0 +1,727 @@
+//! Email-style patches: `format-patch` and `am`
+//!
+//! `format_patch` writes one numbered `.patch` file per commit in a range,
+//! laid out like a mail: `From:`, `Date:` and `Subject:` headers, the rest of
+//! the message (trailers included) as the body, then a `---` line and the
+//! commit's changes as a unified diff from the line-diff engine.
+//!
+//! `am` reads such files back in order and commits each one with the
+//! patch's author and date; the local user becomes the committer. Rune keeps
+//! one time per commit, so the patch's date is the one recorded. Hunks are
+//! placed by their context the way `rune patch apply` does. A patch whose
+//! hunks don't fit stops the run: what did apply is written and staged, the
+//! rest goes to `<path>.rej`, and `AM_STATE` keeps the remaining patches for
+//! `continue_am` or `abort_am`.
+
+use crate::{DiscardOperation, Index, Store, StoreError, NULL_ID};
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use rune_core::eol::looks_binary;
+use rune_core::{Author, Commit};
+use rune_delta::hunks::{apply_hunks_fuzzy, format_rejects, line_hunks, Hunk, HunkLine, DEFAULT_FUZZ};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Context lines around each hunk of a formatted patch
+const CONTEXT_LINES: usize = 3;
+/// Longest file name stem taken from a subject, as git does
+const MAX_SLUG_LEN: usize = 52;
+const NO_NEWLINE_MARKER: &str = "\\ No newline at end of file";
+
+/// Outcome of `am` and `continue_am`
+#[derive(Debug, Clone)]
+pub enum AmResult {
+    /// Every patch was committed; the new commits, oldest first
+    Applied(Vec<Commit>),
+    /// Stopped on the patch titled `subject`; `rejects` are the `.rej`
+    /// files written for the hunks that did not apply
+    Conflicts { applied: Vec<Commit>, subject: String, rejects: Vec<String> },
+}
+
+/// What `am` keeps between runs in `AM_STATE`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct AmState {
+    /// Ref of the branch being applied to and its tip before `am` started
+    branch: String,
+    orig_head: Option<String>,
+    /// Patches not committed yet; the first one is being applied
+    pub(crate) patches: Vec<String>,
+    /// Every path `am` wrote, so abort knows what to put back
+    touched: BTreeSet<String>,
+    /// `.rej` files written for the current patch
+    rejects: Vec<String>,
+}
+
+impl AmState {
+    pub(crate) fn current_subject(&self) -> String {
+        self.patches
+            .first()
+            .and_then(|text| MailPatch::parse(text).ok())
+            .map(|p| p.subject().to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// One mailed commit
+#[derive(Debug, Clone)]
+struct MailPatch {
+    author: Author,
+    time: i64,
+    message: String,
+    files: Vec<FilePatch>,
+}
+
+impl MailPatch {
+    fn subject(&self) -> &str {
+        self.message.lines().next().unwrap_or_default()
+    }
+
+    fn parse(text: &str) -> Result<Self> {
+        let mut lines = text.lines().peekable();
+        // An mbox separator may come before the headers
+        if lines.peek().is_some_and(|l| l.starts_with("From ") && !l.starts_with("From: ")) {
+            lines.next();
+        }
+
+        let mut headers: Vec<(String, String)> = Vec::new();
+        for line in lines.by_ref() {
+            if line.is_empty() {
+                break;
+            }
+            if line.starts_with([' ', '\t']) {
+                // A folded header continues the previous one
+                if let Some((_, value)) = headers.last_mut() {
+                    value.push(' ');
+                    value.push_str(line.trim());
+                }
+            } else if let Some((key, value)) = line.split_once(':') {
+                headers.push((key.trim().to_ascii_lowercase(), value.trim().to_string()));
+            }
+        }
+        let header = |key: &str| headers.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+
+        let from = header("from").ok_or_else(|| anyhow!("patch has no From: header"))?;
+        let (name, email) = from
+            .split_once('<')
+            .and_then(|(name, rest)| Some((name.trim().trim_matches('"'), rest.split_once('>')?.0)))
+            .ok_or_else(|| anyhow!("malformed From: header '{}'", from))?;
+        let author = Author { name: name.to_string(), email: email.to_string() };
+        let date = header("date").ok_or_else(|| anyhow!("patch has no Date: header"))?;
+        let time = DateTime::parse_from_rfc2822(date)
+            .with_context(|| format!("malformed Date: header '{}'", date))?
+            .timestamp();
+        let subject = header("subject").ok_or_else(|| anyhow!("patch has no Subject: header"))?;
+        let subject = match subject.strip_prefix('[').and_then(|s| s.split_once(']')) {
+            Some((tag, rest)) if tag.starts_with("PATCH") => rest.trim(),
+            _ => subject,
+        };
+
+        let mut body = Vec::new();
+        for line in lines.by_ref() {
+            if line == "---" {
+                break;
+            }
+            body.push(line);
+        }
+        let body = body.join("\n");
+        let message = match body.trim() {
+            "" => subject.to_string(),
+            _ => format!("{}\n\n{}", subject, body.trim_matches('\n').trim_end()),
+        };
+
+        let mut files: Vec<FilePatch> = Vec::new();
+        while let Some(line) = lines.next() {
+            if line == "-- " {
+                break;
+            }
+            if let Some(header) = line.strip_prefix("diff --rune ") {
+                files.push(FilePatch::from_header(header)?);
+                continue;
+            }
+            // The stat between `---` and the first diff is for readers
+            let Some(file) = files.last_mut() else {
+                continue;
+            };
+            if line == "new file" {
+                file.old_path = None;
+            } else if line == "deleted file" {
+                file.new_path = None;
+            } else if let Some(path) = line.strip_prefix("rename from ") {
+                file.old_path = Some(path.to_string());
+            } else if let Some(path) = line.strip_prefix("rename to ") {
+                file.new_path = Some(path.to_string());
+            } else if line.starts_with("@@ ") {
+                let hunk = parse_hunk(line, &mut lines)?;
+                file.no_newline = lines.next_if_eq(&NO_NEWLINE_MARKER).is_some();
+                file.hunks.push(hunk);
+            }
+            // `---`/`+++` and `similarity index` lines repeat the header
+        }
+        if files.is_empty() {
+            bail!("patch '{}' has no changes", subject);
+        }
+        Ok(Self { author, time, message, files })
+    }
+}
+
+/// Changes to one file; `None` for the side where it doesn't exist
+#[derive(Debug, Clone)]
+struct FilePatch {
+    old_path: Option<String>,
+    new_path: Option<String>,
+    hunks: Vec<Hunk>,
+    /// The new content ends without a newline
+    no_newline: bool,
+}
+
+impl FilePatch {
+    /// `a/<path> b/<path>`; both halves are the same path unless the file
+    /// was renamed, in which case `rename from`/`rename to` follow
+    fn from_header(header: &str) -> Result<Self> {
+        let rest = header.strip_prefix("a/").ok_or_else(|| anyhow!("malformed diff header '{}'", header))?;
+        let path = match rest.len().checked_sub(3).map(|n| n / 2) {
+            Some(n) if rest.is_char_boundary(n) && rest[n..].starts_with(" b/") && rest[..n] == rest[n + 3..] => {
+                rest[..n].to_string()
+            }
+            _ => rest.split_once(" b/").map(|(old, _)| old.to_string()).unwrap_or_default(),
+        };
+        Ok(Self { old_path: Some(path.clone()), new_path: Some(path), hunks: Vec::new(), no_newline: false })
+    }
+
+    fn display_path(&self) -> &str {
+        self.new_path.as_deref().or(self.old_path.as_deref()).unwrap_or_default()
+    }
+}
+
+/// `@@ -a,b +c,d @@` and the lines it counts
+fn parse_hunk<'a>(header: &str, lines: &mut impl Iterator<Item = &'a str>) -> Result<Hunk> {
+    let range = |spec: Option<&str>, sign: char| -> Result<(usize, usize)> {
+        let spec = spec
+            .and_then(|s| s.strip_prefix(sign))
+            .ok_or_else(|| anyhow!("malformed hunk header '{}'", header))?;
+        let (start, count) = spec.split_once(',').unwrap_or((spec, "1"));
+        Ok((start.parse()?, count.parse()?))
+    };
+    let mut parts = header.split_whitespace().skip(1);
+    let (old_start, old_count) = range(parts.next(), '-')?;
+    let (new_start, new_count) = range(parts.next(), '+')?;
+
+    let (mut old_left, mut new_left) = (old_count, new_count);
+    let mut hunk_lines = Vec::new();
+    while old_left > 0 || new_left > 0 {
+        let line = lines.next().ok_or_else(|| anyhow!("patch ends inside hunk '{}'", header))?;
+        // Mailers may strip the space of an empty context line
+        let (kind, text) = match line.chars().next() {
+            Some(kind) => (kind, &line[kind.len_utf8()..]),
+            None => (' ', ""),
+        };
+        match kind {
+            ' ' if old_left > 0 && new_left > 0 => {
+                hunk_lines.push(HunkLine::Context(text.to_string()));
+                old_left -= 1;
+                new_left -= 1;
+            }
+            '-' if old_left > 0 => {
+                hunk_lines.push(HunkLine::Remove(text.to_string()));
+                old_left -= 1;
+            }
+            '+' if new_left > 0 => {
+                hunk_lines.push(HunkLine::Add(text.to_string()));
+                new_left -= 1;
+            }
+            '\\' => {}
+            _ => bail!("hunk '{}' does not match its line counts", header),
+        }
+    }
+    Ok(Hunk { old_start, old_count, new_start, new_count, lines: hunk_lines })
+}
+
+/// File name stem git would use for `subject`
+fn slug(subject: &str) -> String {
+    let mut slug = String::new();
+    for c in subject.chars() {
+        if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+        if slug.len() >= MAX_SLUG_LEN {
+            break;
+        }
+    }
+    slug.trim_end_matches(['-', '.']).to_string()
+}
+
+impl Store {
+    /// Commits in `range`, oldest first: `A..B` is what B has that A does
+    /// not, `A` alone means `A..HEAD`, and `..B` is B's whole history
+    pub fn patch_range(&self, range: &str) -> Result<Vec<Commit>> {
+        let (since_rev, until_rev) = range.split_once("..").unwrap_or((range, "HEAD"));
+        let until_rev = if until_rev.is_empty() { "HEAD" } else { until_rev };
+        let until = self.revision_id(until_rev)?;
+        if until == NULL_ID {
+            return Err(StoreError::NoCommits.into());
+        }
+        let since = match since_rev {
+            "" => None,
+            rev => Some(self.revision_id(rev)?),
+        };
+        let mut commits: Vec<Commit> = self
+            .ancestry(&until)
+            .into_iter()
+            .take_while(|c| Some(&c.id) != since.as_ref())
+            .collect();
+        if since.is_some() && commits.last().map_or(Some(&until), |c| c.parent.as_ref()) != since.as_ref() {
+            bail!("'{}' is not an ancestor of '{}'", since_rev, until_rev);
+        }
+        commits.reverse();
+        Ok(commits)
+    }
+
+    /// Write every commit in `range` (see `patch_range`) to `out_dir` as
+    /// `0001-<subject>.patch`, `0002-…` and so on; returns the files written
+    pub fn format_patch(&self, range: &str, out_dir: &Path) -> Result<Vec<PathBuf>> {
+        let commits = self.patch_range(range)?;
+        fs::create_dir_all(out_dir)?;
+        let mut written = Vec::new();
+        for (i, commit) in commits.iter().enumerate() {
+            let text = self.mail_patch(commit, i + 1, commits.len())?;
+            let subject = commit.message.lines().next().unwrap_or_default();
+            let path = out_dir.join(format!("{:04}-{}.patch", i + 1, slug(subject)));
+            fs::write(&path, text)?;
+            written.push(path);
+        }
+        Ok(written)
+    }
+
+    /// `commit` as patch `number` of `total`
+    pub fn mail_patch(&self, commit: &Commit, number: usize, total: usize) -> Result<String> {
+        if commit.tree.is_empty() && !commit.files.is_empty() {
+            bail!("commit {} has no recorded content to format", &commit.id[..8]);
+        }
+        let date = DateTime::<Utc>::from_timestamp(commit.time, 0).unwrap_or_default();
+        let mut lines = commit.message.lines();
+        let subject = lines.next().unwrap_or_default();
+        let body = lines.collect::<Vec<_>>().join("\n");
+        let tag = if total == 1 { "[PATCH]".to_string() } else { format!("[PATCH {}/{}]", number, total) };
+
+        let mut out = format!("From {} Mon Sep 17 00:00:00 2001\n", commit.id);
+        out.push_str(&format!("From: {} <{}>\n", commit.author.name, commit.author.email));
+        out.push_str(&format!("Date: {}\n", date.to_rfc2822()));
+        out.push_str(&format!("Subject: {} {}\n\n", tag, subject));
+        let body = body.trim_matches('\n').trim_end();
+        if !body.is_empty() {
+            out.push_str(body);
+            out.push_str("\n\n");
+        }
+        out.push_str("---\n");
+
+        let parent = commit.parent.clone().unwrap_or_else(|| NULL_ID.to_string());
+        let changes = self.changed_paths(
+            &crate::DiffSource::Commit(parent.clone()),
+            &crate::DiffSource::Commit(commit.id.clone()),
+            &[],
+        )?;
+        for change in &changes {
+            out.push_str(&format!(" {}\n", change.path()));
+        }
+        out.push_str(&format!(
+            " {} file{} changed\n\n",
+            changes.len(),
+            if changes.len() == 1 { "" } else { "s" }
+        ));
+
+        let before_tree = if parent == NULL_ID { Default::default() } else { self.tree_snapshot(&parent) };
+        let after_tree = self.tree_snapshot(&commit.id);
+        for change in &changes {
+            let (old_path, new_path, added, deleted) = match change {
+                crate::PathChange::Added(p) => (p.as_str(), p.as_str(), true, false),
+                crate::PathChange::Deleted(p) => (p.as_str(), p.as_str(), false, true),
+                crate::PathChange::Modified(p) => (p.as_str(), p.as_str(), false, false),
+                crate::PathChange::Renamed { from, to, .. } => (from.as_str(), to.as_str(), false, false),
+            };
+            let before = if added { Vec::new() } else { self.read_blob(&before_tree[old_path])? };
+            let after = if deleted { Vec::new() } else { self.read_blob(&after_tree[new_path])? };
+            if looks_binary(&before) || looks_binary(&after) {
+                bail!("{} in {} is binary; patches carry text changes only", new_path, &commit.id[..8]);
+            }
+            out.push_str(&format!("diff --rune a/{} b/{}\n", old_path, new_path));
+            if added {
+                out.push_str("new file\n");
+            } else if deleted {
+                out.push_str("deleted file\n");
+            } else if old_path != new_path {
+                out.push_str(&format!("rename from {}\nrename to {}\n", old_path, new_path));
+            }
+            if before == after {
+                continue;
+            }
+            out.push_str(&format!("--- {}\n", if added { "/dev/null".to_string() } else { format!("a/{}", old_path) }));
+            out.push_str(&format!("+++ {}\n", if deleted { "/dev/null".to_string() } else { format!("b/{}", new_path) }));
+            let after_text = String::from_utf8_lossy(&after);
+            for hunk in line_hunks(&String::from_utf8_lossy(&before), &after_text, CONTEXT_LINES) {
+                out.push_str(&hunk.to_string());
+            }
+            if !after.is_empty() && !after_text.ends_with('\n') {
+                out.push_str(NO_NEWLINE_MARKER);
+                out.push('\n');
+            }
+        }
+        out.push_str(&format!("-- \nrune {}\n", env!("CARGO_PKG_VERSION")));
+        Ok(out)
+    }
+
+    /// Commit each patch text in order on the current branch, with the
+    /// patch's author and date and `committer` as the committer. Stops on
+    /// the first patch that doesn't apply; see the module docs.
+    pub fn am(&self, patches: &[String], committer: Author) -> Result<AmResult> {
+        if self.operation_in_progress()?.is_some() {
+            return Err(StoreError::MergeInProgress { action: "am" }.into());
+        }
+        if !self.read_index()?.entries.is_empty() {
+            bail!("cannot apply patches with staged changes; commit or reset them first");
+        }
+        for (i, text) in patches.iter().enumerate() {
+            MailPatch::parse(text).with_context(|| format!("patch {} of {}", i + 1, patches.len()))?;
+        }
+        let branch = self.head_ref();
+        let state = AmState {
+            orig_head: self.read_ref(&branch),
+            branch,
+            patches: patches.to_vec(),
+            touched: BTreeSet::new(),
+            rejects: Vec::new(),
+        };
+        self.write_am_state(&state)?;
+        self.run_am(state, committer, Vec::new())
+    }
+
+    /// Commit the patch `am` stopped on from what is staged now, then go on
+    /// with the rest
+    pub fn continue_am(&self, committer: Author) -> Result<AmResult> {
+        let mut state = self.read_am_state()?.ok_or(StoreError::NoAmInProgress)?;
+        let patch = MailPatch::parse(state.patches.first().ok_or(StoreError::NoAmInProgress)?)?;
+        if self.read_index()?.entries.is_empty() {
+            bail!("nothing staged for '{}'; add the resolved files with 'rune add'", patch.subject());
+        }
+        self.remove_rejects(&mut state)?;
+        let commit = self.commit_as(&patch.message, patch.author, committer.clone(), patch.time)?;
+        state.patches.remove(0);
+        self.write_am_state(&state)?;
+        self.run_am(state, committer, vec![commit])
+    }
+
+    /// Stop applying patches and put the branch, the index and every file
+    /// `am` wrote back the way they were before it started
+    pub fn abort_am(&self) -> Result<()> {
+        let mut state = self.read_am_state()?.ok_or(StoreError::NoAmInProgress)?;
+        self.run_discard_hooks(DiscardOperation::Abort)?;
+        self.remove_rejects(&mut state)?;
+        let tree = state.orig_head.as_deref().map(|id| self.tree_snapshot(id)).unwrap_or_default();
+        let filter = self.eol_filter()?;
+        for path in &state.touched {
+            let dest = self.root.join(path);
+            match tree.get(path) {
+                Some(blob) => {
+                    let content = self.read_blob(blob)?;
+                    if let Some(parent) = dest.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::write(&dest, &*filter.to_worktree(Path::new(path), &content))?;
+                }
+                None if dest.exists() => fs::remove_file(&dest)?,
+                None => {}
+            }
+        }
+        match &state.orig_head {
+            Some(id) => {
+                self.write_ref(&state.branch, id)?;
+                self.update_reflog(&state.branch, id, "am --abort")?;
+            }
+            None => {
+                if let Some(file) = self.ref_file(&state.branch).filter(|f| f.is_file()) {
+                    fs::remove_file(file)?;
+                    self.invalidate_refs();
+                }
+            }
+        }
+        self.write_index(&Index::default())?;
+        fs::remove_file(self.rune_dir.join("AM_STATE"))?;
+        Ok(())
+    }
+
+    pub(crate) fn read_am_state(&self) -> Result<Option<AmState>> {
+        let path = self.rune_dir.join("AM_STATE");
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&fs::read_to_string(path)?)?))
+    }
+
+    fn write_am_state(&self, state: &AmState) -> Result<()> {
+        fs::write(self.rune_dir.join("AM_STATE"), serde_json::to_string_pretty(state)?)?;
+        Ok(())
+    }
+
+    fn remove_rejects(&self, state: &mut AmState) -> Result<()> {
+        for rej in state.rejects.drain(..) {
+            let path = self.root.join(rej);
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply and commit `state.patches` from the first one on
+    fn run_am(&self, mut state: AmState, committer: Author, mut applied: Vec<Commit>) -> Result<AmResult> {
+        while let Some(text) = state.patches.first() {
+            let patch = MailPatch::parse(text)?;
+            let rejects = self.apply_mail_patch(&patch, &mut state.touched)?;
+            if !rejects.is_empty() {
+                state.rejects = rejects.clone();
+                self.write_am_state(&state)?;
+                return Ok(AmResult::Conflicts { applied, subject: patch.subject().to_string(), rejects });
+            }
+            let commit = self.commit_as(&patch.message, patch.author, committer.clone(), patch.time)?;
+            applied.push(commit);
+            state.patches.remove(0);
+            self.write_am_state(&state)?;
+        }
+        fs::remove_file(self.rune_dir.join("AM_STATE"))?;
+        Ok(AmResult::Applied(applied))
+    }
+
+    /// Write and stage `patch` against HEAD. Files with hunks that don't fit
+    /// get what did apply plus a `.rej` file and stay unstaged; returns the
+    /// `.rej` files written.
+    fn apply_mail_patch(&self, patch: &MailPatch, touched: &mut BTreeSet<String>) -> Result<Vec<String>> {
+        let _batch = self.batch_index_writes();
+        let head = self.head_commit().map(|id| self.tree_snapshot(&id)).unwrap_or_default();
+        let filter = self.eol_filter()?;
+        // Working copies must match HEAD, or applying would commit local edits
+        for path in patch.files.iter().flat_map(|f| [&f.old_path, &f.new_path]).flatten() {
+            let working = fs::read(self.root.join(path))
+                .ok()
+                .map(|content| blake3::hash(&filter.to_store(Path::new(path), &content)).to_hex().to_string());
+            if working.as_ref() != head.get(path) && !touched.contains(path) {
+                bail!("local changes to {} would be overwritten by '{}'", path, patch.subject());
+            }
+        }
+
+        let mut rejects = Vec::new();
+        for file in &patch.files {
+            let base = match &file.old_path {
+                Some(path) => match head.get(path) {
+                    Some(blob) => Some(
+                        String::from_utf8(self.read_blob(blob)?).map_err(|_| anyhow!("{} is not text", path))?,
+                    ),
+                    None => None,
+                },
+                None => Some(String::new()),
+            };
+            let mut result = apply_hunks_fuzzy(base.as_deref().unwrap_or_default(), &file.hunks, DEFAULT_FUZZ);
+            if file.no_newline && result.content.ends_with('\n') {
+                result.content.pop();
+            }
+            // A missing file, a new one that exists already and a deletion
+            // that leaves content behind don't fit at all
+            let unplaceable = base.is_none()
+                || (file.old_path.is_none() && file.new_path.as_ref().is_some_and(|p| head.contains_key(p)))
+                || (file.new_path.is_none() && !result.content.is_empty());
+            if unplaceable {
+                result.rejected = file.hunks.clone();
+            }
+            if !result.is_clean() || unplaceable {
+                let path = file.display_path();
+                let rej = format!("{}.rej", path);
+                fs::write(self.root.join(&rej), format_rejects(path, &result.rejected))?;
+                rejects.push(rej);
+                if unplaceable {
+                    continue;
+                }
+            }
+
+            if let Some(old) = file.old_path.as_ref().filter(|old| file.new_path.as_ref() != Some(*old)) {
+                let dest = self.root.join(old);
+                if dest.exists() {
+                    fs::remove_file(&dest)?;
+                }
+                self.stage_file(old)?;
+                touched.insert(old.clone());
+            }
+            if let Some(new) = &file.new_path {
+                let dest = self.root.join(new);
+                if let Some(parent) = dest.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(&dest, &*filter.to_worktree(Path::new(new), result.content.as_bytes()))?;
+                touched.insert(new.clone());
+                if result.is_clean() {
+                    self.stage_file(new)?;
+                }
+            }
+        }
+        Ok(rejects)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{author, temp_repo};
+    use tempfile::TempDir;
+
+    fn local() -> Author {
+        Author { name: "Local User".into(), email: "local@example.com".into() }
+    }
+
+    fn write(store: &Store, path: &str, content: &str) {
+        let full = store.root.join(path);
+        fs::create_dir_all(full.parent().unwrap()).unwrap();
+        fs::write(full, content).unwrap();
+        store.stage_file(path).unwrap();
+    }
+
+    fn read_patches(files: &[PathBuf]) -> Vec<String> {
+        files.iter().map(|f| fs::read_to_string(f).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_round_trip_into_second_repo() {
+        let (_temp, store) = temp_repo();
+        write(&store, "src/lib.rs", "fn one() {}\nfn two() {}\nfn three() {}\n");
+        write(&store, "old.txt", "stays the same\n");
+        let base = store.commit("Initial commit", author()).unwrap();
+
+        write(&store, "src/lib.rs", "fn one() {}\nfn two() { 2 }\nfn three() {}\n");
+        write(&store, "notes.txt", "no newline");
+        store.commit("Change two\n\nWith a body.\n\nSigned-off-by: Ada <ada@example.com>", author()).unwrap();
+        fs::remove_file(store.root.join("old.txt")).unwrap();
+        store.stage_file("old.txt").unwrap();
+        store.commit("Drop old.txt", author()).unwrap();
+        write(&store, "src/lib.rs", "fn zero() {}\nfn one() {}\nfn two() { 2 }\nfn three() {}\n");
+        store.commit("Add zero", author()).unwrap();
+
+        let out = TempDir::new().unwrap();
+        let files = store.format_patch(&base.id[..8], out.path()).unwrap();
+        let names: Vec<_> = files.iter().map(|f| f.file_name().unwrap().to_string_lossy().to_string()).collect();
+        assert_eq!(names, ["0001-Change-two.patch", "0002-Drop-old.txt.patch", "0003-Add-zero.patch"]);
+        let first = fs::read_to_string(&files[0]).unwrap();
+        assert!(first.contains("From: Test <test@example.com>\n"), "{}", first);
+        assert!(first.contains("Subject: [PATCH 1/3] Change two\n"), "{}", first);
+        assert!(first.contains("-fn two() {}\n+fn two() { 2 }\n"), "{}", first);
+
+        // The second repository has the same content in its own first commit
+        let (_target_temp, target) = temp_repo();
+        write(&target, "src/lib.rs", "fn one() {}\nfn two() {}\nfn three() {}\n");
+        write(&target, "old.txt", "stays the same\n");
+        target.commit("Initial commit", local()).unwrap();
+
+        let AmResult::Applied(commits) = target.am(&read_patches(&files), local()).unwrap() else {
+            panic!("patches should apply cleanly");
+        };
+        let originals: Vec<_> = store.patch_range(&base.id).unwrap();
+        assert_eq!(commits.len(), 3);
+        for (imported, original) in commits.iter().zip(&originals) {
+            assert_eq!(imported.message, original.message);
+            assert_eq!(imported.author.email, original.author.email);
+            assert_eq!(imported.author.name, original.author.name);
+            assert_eq!(imported.time, original.time);
+            assert_eq!(imported.committer.email, "local@example.com");
+        }
+        let tip = target.head_commit().unwrap();
+        let expected = store.tree_snapshot(&store.head_commit().unwrap());
+        assert_eq!(target.tree_snapshot(&tip), expected);
+        assert_eq!(fs::read_to_string(target.root.join("notes.txt")).unwrap(), "no newline");
+        assert!(!target.root.join("old.txt").exists());
+        assert!(target.operation_in_progress().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_conflicting_patch_pauses_and_abort_restores() {
+        let (_temp, store) = temp_repo();
+        write(&store, "a.txt", "one\ntwo\nthree\n");
+        let base = store.commit("Base", author()).unwrap();
+        write(&store, "b.txt", "new file\n");
+        store.commit("Add b", author()).unwrap();
+        write(&store, "a.txt", "one\nTWO\nthree\n");
+        store.commit("Shout two", author()).unwrap();
+        let out = TempDir::new().unwrap();
+        let patches = read_patches(&store.format_patch(&base.id, out.path()).unwrap());
+
+        // Rewind and change the line the second patch edits
+        store.rollback(2, true).unwrap();
+        write(&store, "a.txt", "one\n2\nthree\n");
+        let before = store.commit("Number two", author()).unwrap();
+
+        let AmResult::Conflicts { applied, subject, rejects } = store.am(&patches, local()).unwrap() else {
+            panic!("second patch should conflict");
+        };
+        assert_eq!(applied.len(), 1);
+        assert_eq!(subject, "Shout two");
+        assert_eq!(rejects, ["a.txt.rej"]);
+        let rej = fs::read_to_string(store.root.join("a.txt.rej")).unwrap();
+        assert!(rej.contains("-two\n+TWO\n"), "{}", rej);
+        assert!(matches!(
+            store.operation_in_progress().unwrap(),
+            Some(crate::RepoOperation::Am { remaining: 0, .. })
+        ));
+        assert!(store.am(&patches, local()).is_err());
+
+        store.abort_am().unwrap();
+        assert_eq!(store.head_commit().as_deref(), Some(before.id.as_str()));
+        assert!(!store.root.join("b.txt").exists());
+        assert!(!store.root.join("a.txt.rej").exists());
+        assert_eq!(fs::read_to_string(store.root.join("a.txt")).unwrap(), "one\n2\nthree\n");
+        assert!(store.read_index().unwrap().entries.is_empty());
+        assert!(store.operation_in_progress().unwrap().is_none());
+        assert!(store.abort_am().is_err());
+    }
+
+    #[test]
+    fn test_continue_commits_resolved_patch() {
+        let (_temp, store) = temp_repo();
+        write(&store, "a.txt", "one\ntwo\n");
+        let base = store.commit("Base", author()).unwrap();
+        write(&store, "a.txt", "one\nTWO\n");
+        store.commit("Shout two", author()).unwrap();
+        let out = TempDir::new().unwrap();
+        let patches = read_patches(&store.format_patch(&base.id, out.path()).unwrap());
+        store.rollback(1, true).unwrap();
+        write(&store, "a.txt", "uno\ndos\n");
+        store.commit("Spanish", author()).unwrap();
+
+        assert!(matches!(store.am(&patches, local()).unwrap(), AmResult::Conflicts { .. }));
+        assert!(store.continue_am(local()).is_err());
+        write(&store, "a.txt", "uno\nDOS\n");
+        let AmResult::Applied(commits) = store.continue_am(local()).unwrap() else {
+            panic!("nothing left to conflict");
+        };
+        assert_eq!(commits[0].message, "Shout two");
+        assert_eq!(commits[0].author.email, "test@example.com");
+        assert!(!store.root.join("a.txt.rej").exists());
+        assert!(store.operation_in_progress().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_parse_tolerates_mail_formatting() {
+        let text = "From: \"Ada\" <ada@example.com>\n\
+            Date: Tue, 14 Nov 2023 22:13:20 +0000\n\
+            Subject: [PATCH v2 3/7] Fix a\n long subject\n\n\
+            ---\n\
+            diff --rune a/dir with space/f.txt b/dir with space/f.txt\n\
+            --- a/dir with space/f.txt\n+++ b/dir with space/f.txt\n\
+            @@ -1,3 +1,3 @@\n\n x\n-y\n+z\n";
+        let patch = MailPatch::parse(text).unwrap();
+        assert_eq!(patch.message, "Fix a long subject");
+        assert_eq!(patch.author.name, "Ada");
+        assert_eq!(patch.time, 1_700_000_000);
+        assert_eq!(patch.files[0].old_path.as_deref(), Some("dir with space/f.txt"));
+        assert_eq!(patch.files[0].hunks[0].lines[0], HunkLine::Context(String::new()));
+        assert!(MailPatch::parse("Subject: x\n\n---\n").is_err());
+        assert_eq!(slug("fix: handle \"odd\" input!"), "fix-handle-odd-input");
+    }
+}
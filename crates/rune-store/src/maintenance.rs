@@ -0,0 +1,525 @@
+//! Housekeeping that runs by itself
+//!
+//! Each task has an interval; `.rune/maintenance.json` remembers when it last
+//! ran and how that went. After a mutating command the CLI asks for the due
+//! tasks and runs them, the quick ones inline and the rest in a background
+//! process. Only one run happens at a time: `run_maintenance` holds
+//! `.rune/maintenance.lock` and gives up at once if someone else has it.
+//! What the tasks actually do lives with the caller; this module only keeps
+//! the schedule, the lock and the record, and writes every outcome to
+//! `.rune/maintenance.log`.
+
+use crate::{write_atomic, Store};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// One kind of housekeeping
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MaintenanceTask {
+    /// Collapse duplicate objects, repack loose objects and pack tags and
+    /// remote-tracking refs
+    Gc,
+    /// Drop old reflog entries
+    Reflog,
+    /// Remove orphaned LFS objects and unreferenced chunks
+    Lfs,
+    /// Delete expired drafts
+    Drafts,
+    /// Rotate the planning signals log
+    Signals,
+}
+
+impl MaintenanceTask {
+    pub const ALL: [MaintenanceTask; 5] = [
+        MaintenanceTask::Gc,
+        MaintenanceTask::Reflog,
+        MaintenanceTask::Lfs,
+        MaintenanceTask::Drafts,
+        MaintenanceTask::Signals,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MaintenanceTask::Gc => "gc",
+            MaintenanceTask::Reflog => "reflog",
+            MaintenanceTask::Lfs => "lfs",
+            MaintenanceTask::Drafts => "drafts",
+            MaintenanceTask::Signals => "signals",
+        }
+    }
+
+    /// Hours between runs unless configured otherwise
+    pub fn default_interval_hours(self) -> u64 {
+        match self {
+            MaintenanceTask::Gc | MaintenanceTask::Lfs => 7 * 24,
+            MaintenanceTask::Reflog | MaintenanceTask::Drafts | MaintenanceTask::Signals => 24,
+        }
+    }
+
+    /// Cheap enough to run before the user's command returns
+    pub fn is_quick(self) -> bool {
+        !matches!(self, MaintenanceTask::Gc | MaintenanceTask::Lfs)
+    }
+}
+
+impl std::fmt::Display for MaintenanceTask {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+impl std::str::FromStr for MaintenanceTask {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        MaintenanceTask::ALL.into_iter().find(|task| task.as_str() == s).ok_or_else(|| {
+            anyhow::anyhow!("unknown maintenance task '{}', expected one of gc, reflog, lfs, drafts, signals", s)
+        })
+    }
+}
+
+/// `[maintenance]`: what runs after commands and how often
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceCfg {
+    /// Check for due tasks after mutating commands
+    #[serde(default = "def_auto")]
+    pub auto: bool,
+    #[serde(default, skip_serializing_if = "TaskCfg::is_default")]
+    pub gc: TaskCfg,
+    #[serde(default, skip_serializing_if = "TaskCfg::is_default")]
+    pub reflog: TaskCfg,
+    #[serde(default, skip_serializing_if = "TaskCfg::is_default")]
+    pub lfs: TaskCfg,
+    #[serde(default, skip_serializing_if = "TaskCfg::is_default")]
+    pub drafts: TaskCfg,
+    #[serde(default, skip_serializing_if = "TaskCfg::is_default")]
+    pub signals: TaskCfg,
+}
+
+impl Default for MaintenanceCfg {
+    fn default() -> Self {
+        Self {
+            auto: def_auto(),
+            gc: TaskCfg::default(),
+            reflog: TaskCfg::default(),
+            lfs: TaskCfg::default(),
+            drafts: TaskCfg::default(),
+            signals: TaskCfg::default(),
+        }
+    }
+}
+
+impl MaintenanceCfg {
+    pub(crate) fn is_default(&self) -> bool {
+        self.auto && MaintenanceTask::ALL.into_iter().all(|task| self.task(task).is_default())
+    }
+
+    pub fn task(&self, task: MaintenanceTask) -> &TaskCfg {
+        match task {
+            MaintenanceTask::Gc => &self.gc,
+            MaintenanceTask::Reflog => &self.reflog,
+            MaintenanceTask::Lfs => &self.lfs,
+            MaintenanceTask::Drafts => &self.drafts,
+            MaintenanceTask::Signals => &self.signals,
+        }
+    }
+
+    pub fn task_mut(&mut self, task: MaintenanceTask) -> &mut TaskCfg {
+        match task {
+            MaintenanceTask::Gc => &mut self.gc,
+            MaintenanceTask::Reflog => &mut self.reflog,
+            MaintenanceTask::Lfs => &mut self.lfs,
+            MaintenanceTask::Drafts => &mut self.drafts,
+            MaintenanceTask::Signals => &mut self.signals,
+        }
+    }
+
+    /// Seconds between runs of `task`
+    pub fn interval_secs(&self, task: MaintenanceTask) -> i64 {
+        let hours = self.task(task).interval_hours.unwrap_or_else(|| task.default_interval_hours());
+        hours as i64 * 3600
+    }
+}
+
+fn def_auto() -> bool {
+    true
+}
+
+/// `[maintenance.<task>]`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskCfg {
+    /// `false` keeps the task out of automatic runs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    /// Hours between runs; each task has its own default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval_hours: Option<u64>,
+}
+
+impl TaskCfg {
+    fn is_default(&self) -> bool {
+        *self == TaskCfg::default()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.unwrap_or(true)
+    }
+}
+
+/// Last outcome of one task, kept in `.rune/maintenance.json`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TaskRecord {
+    /// Unix time of the last attempt, successful or not
+    pub last_run: Option<i64>,
+    /// What the last successful run reported
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_result: Option<String>,
+    /// Error of the last attempt when it failed; cleared by the next success
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct MaintenanceState {
+    #[serde(default)]
+    tasks: BTreeMap<String, TaskRecord>,
+}
+
+/// Where one task stands, for `rune maintenance status`
+#[derive(Debug, Clone)]
+pub struct TaskStatus {
+    pub task: MaintenanceTask,
+    pub enabled: bool,
+    pub interval_hours: u64,
+    pub record: TaskRecord,
+    /// Unix time the task next becomes due; `None` if it never ran
+    pub next_due: Option<i64>,
+    pub due: bool,
+}
+
+/// Outcome of `run_maintenance` for each task it ran
+#[derive(Debug, Clone, Default)]
+pub struct MaintenanceRun {
+    pub succeeded: Vec<(MaintenanceTask, String)>,
+    pub failed: Vec<(MaintenanceTask, String)>,
+}
+
+impl Store {
+    fn maintenance_state_path(&self) -> PathBuf {
+        self.rune_dir.join("maintenance.json")
+    }
+
+    pub fn maintenance_log_path(&self) -> PathBuf {
+        self.rune_dir.join("maintenance.log")
+    }
+
+    fn maintenance_state(&self) -> Result<MaintenanceState> {
+        match fs::read(self.maintenance_state_path()) {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(MaintenanceState::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Every task with its schedule and last outcome as of `now`
+    pub fn maintenance_status(&self, now: i64) -> Result<Vec<TaskStatus>> {
+        let cfg = self.config().maintenance;
+        let state = self.maintenance_state()?;
+        Ok(MaintenanceTask::ALL
+            .into_iter()
+            .map(|task| {
+                let record = state.tasks.get(task.as_str()).cloned().unwrap_or_default();
+                let enabled = cfg.task(task).is_enabled();
+                let next_due = record.last_run.map(|last| last + cfg.interval_secs(task));
+                TaskStatus {
+                    task,
+                    enabled,
+                    interval_hours: cfg.interval_secs(task) as u64 / 3600,
+                    due: enabled && next_due.is_none_or(|due| due <= now),
+                    next_due,
+                    record,
+                }
+            })
+            .collect())
+    }
+
+    /// Enabled tasks whose interval has passed as of `now`
+    pub fn due_maintenance(&self, now: i64) -> Result<Vec<MaintenanceTask>> {
+        Ok(self.maintenance_status(now)?.into_iter().filter(|s| s.due).map(|s| s.task).collect())
+    }
+
+    /// What the check after a command should run: the due tasks, or nothing
+    /// when `[maintenance] auto` is off. Gc is also due, whatever its
+    /// interval, once loose objects pass `core.auto_gc_threshold`.
+    pub fn maintenance_after_command(&self, now: i64) -> Result<Vec<MaintenanceTask>> {
+        if !self.config().maintenance.auto {
+            return Ok(Vec::new());
+        }
+        let mut due = self.due_maintenance(now)?;
+        if !due.contains(&MaintenanceTask::Gc) && self.auto_gc_due()? {
+            due.insert(0, MaintenanceTask::Gc);
+        }
+        Ok(due)
+    }
+
+    /// Loose objects are over `core.auto_gc_threshold` and gc is enabled
+    pub fn auto_gc_due(&self) -> Result<bool> {
+        let cfg = self.config();
+        match cfg.core.auto_gc_threshold {
+            Some(threshold) if cfg.maintenance.gc.is_enabled() => Ok(self.loose_object_count()? > threshold),
+            _ => Ok(false),
+        }
+    }
+
+    /// Run `tasks` through `run` under the maintenance lock, recording each
+    /// outcome. `None` means another run holds the lock and nothing was done.
+    /// A failing task is recorded and the rest still run.
+    pub fn run_maintenance(
+        &self,
+        tasks: &[MaintenanceTask],
+        mut run: impl FnMut(MaintenanceTask) -> Result<String>,
+    ) -> Result<Option<MaintenanceRun>> {
+        let lock = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.rune_dir.join("maintenance.lock"))?;
+        match lock.try_lock() {
+            Ok(()) => {}
+            Err(fs::TryLockError::WouldBlock) => return Ok(None),
+            Err(fs::TryLockError::Error(e)) => return Err(e.into()),
+        }
+
+        let mut state = self.maintenance_state()?;
+        let mut outcome = MaintenanceRun::default();
+        for &task in tasks {
+            let started = chrono::Utc::now().timestamp();
+            let result = run(task);
+            let record = state.tasks.entry(task.as_str().to_string()).or_default();
+            record.last_run = Some(started);
+            match result {
+                Ok(summary) => {
+                    self.log_maintenance(&format!("{} ok: {}", task, summary))?;
+                    record.last_result = Some(summary.clone());
+                    record.last_error = None;
+                    outcome.succeeded.push((task, summary));
+                }
+                Err(e) => {
+                    let error = format!("{:#}", e);
+                    self.log_maintenance(&format!("{} failed: {}", task, error))?;
+                    record.last_error = Some(error.clone());
+                    outcome.failed.push((task, error));
+                }
+            }
+            // Saved per task, so an interrupted run keeps what already finished
+            write_atomic(&self.maintenance_state_path(), &serde_json::to_vec_pretty(&state)?)?;
+        }
+        Ok(Some(outcome))
+    }
+
+    fn log_maintenance(&self, line: &str) -> Result<()> {
+        let path = self.maintenance_log_path();
+        rotate_file(&path, MAINTENANCE_LOG_LIMIT)?;
+        let mut log = fs::OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(log, "{} {}", chrono::Utc::now().to_rfc3339(), line)?;
+        Ok(())
+    }
+}
+
+/// `maintenance.log` is rotated past this size
+const MAINTENANCE_LOG_LIMIT: u64 = 256 * 1024;
+
+/// Move `path` to `<path>.1`, replacing an older rotation, once it has grown
+/// past `max_bytes`. Returns whether it was rotated.
+pub fn rotate_file(path: &Path, max_bytes: u64) -> Result<bool> {
+    match fs::metadata(path) {
+        Ok(meta) if meta.len() > max_bytes => {
+            let mut rotated = path.as_os_str().to_os_string();
+            rotated.push(".1");
+            fs::rename(path, rotated)?;
+            Ok(true)
+        }
+        Ok(_) => Ok(false),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{author, temp_repo};
+    use tempfile::TempDir;
+
+    const HOUR: i64 = 3600;
+
+    /// Record every task as having run at `at`
+    fn ran_at(store: &Store, at: i64) {
+        let mut state = MaintenanceState::default();
+        for task in MaintenanceTask::ALL {
+            state.tasks.insert(task.as_str().into(), TaskRecord { last_run: Some(at), ..Default::default() });
+        }
+        fs::write(store.maintenance_state_path(), serde_json::to_vec(&state).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_due_tasks_follow_intervals() {
+        let (_temp, store) = temp_repo();
+        let now = 1_000 * HOUR;
+        assert_eq!(store.due_maintenance(now).unwrap(), MaintenanceTask::ALL);
+
+        ran_at(&store, now - 25 * HOUR);
+        assert_eq!(
+            store.due_maintenance(now).unwrap(),
+            [MaintenanceTask::Reflog, MaintenanceTask::Drafts, MaintenanceTask::Signals]
+        );
+
+        let mut cfg = store.config();
+        cfg.maintenance.gc.interval_hours = Some(12);
+        cfg.maintenance.drafts.interval_hours = Some(48);
+        store.write_config(&cfg).unwrap();
+        assert_eq!(
+            store.due_maintenance(now).unwrap(),
+            [MaintenanceTask::Gc, MaintenanceTask::Reflog, MaintenanceTask::Signals]
+        );
+        let gc = &store.maintenance_status(now).unwrap()[0];
+        assert_eq!(gc.next_due, Some(now - 13 * HOUR));
+        assert!(store.due_maintenance(now - 14 * HOUR).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_after_command_respects_disable_flags() {
+        let (_temp, store) = temp_repo();
+        let now = 1_000 * HOUR;
+        ran_at(&store, now - 2 * HOUR);
+        assert!(store.maintenance_after_command(now).unwrap().is_empty());
+
+        let mut cfg = store.config();
+        cfg.maintenance.reflog.interval_hours = Some(1);
+        cfg.maintenance.signals.interval_hours = Some(1);
+        cfg.maintenance.signals.enabled = Some(false);
+        store.write_config(&cfg).unwrap();
+        assert_eq!(store.maintenance_after_command(now).unwrap(), [MaintenanceTask::Reflog]);
+
+        cfg.maintenance.auto = false;
+        store.write_config(&cfg).unwrap();
+        assert!(store.maintenance_after_command(now).unwrap().is_empty());
+        assert_eq!(store.due_maintenance(now).unwrap(), [MaintenanceTask::Reflog]);
+        assert!(store.config().maintenance.reflog.is_enabled());
+    }
+
+    #[test]
+    fn test_loose_objects_past_threshold_trigger_gc() {
+        let (temp, store) = temp_repo();
+        let now = 1_000 * HOUR;
+        ran_at(&store, now - 2 * HOUR);
+        let commit = |n: usize| {
+            let path = format!("file{}.txt", n);
+            fs::write(temp.path().join(&path), format!("content {}\n", n)).unwrap();
+            store.stage_file(&path).unwrap();
+            store.commit(&format!("commit {}", n), author()).unwrap();
+        };
+
+        let mut cfg = store.config();
+        cfg.core.auto_gc_threshold = Some(3);
+        store.write_config(&cfg).unwrap();
+        for n in 0..3 {
+            commit(n);
+        }
+        assert_eq!(store.loose_object_count().unwrap(), 3);
+        assert!(store.maintenance_after_command(now).unwrap().is_empty());
+        commit(3);
+        assert_eq!(store.maintenance_after_command(now).unwrap(), [MaintenanceTask::Gc]);
+
+        let run = store
+            .run_maintenance(&[MaintenanceTask::Gc], |_| Ok(format!("{} objects packed", store.repack(false)?.objects)))
+            .unwrap()
+            .unwrap();
+        assert_eq!(run.succeeded, [(MaintenanceTask::Gc, "4 objects packed".to_string())]);
+        assert_eq!(store.loose_object_count().unwrap(), 0);
+        assert!(store.maintenance_after_command(now).unwrap().is_empty());
+        let head = store.read_ref(&store.head_ref()).unwrap();
+        for n in 0..4 {
+            let blob = store.blob_at(&head, &format!("file{}.txt", n)).unwrap();
+            assert_eq!(store.read_blob(&blob).unwrap(), format!("content {}\n", n).as_bytes());
+        }
+
+        // Skipped along with the rest of gc
+        for n in 4..9 {
+            commit(n);
+        }
+        cfg.maintenance.gc.enabled = Some(false);
+        store.write_config(&cfg).unwrap();
+        assert!(store.maintenance_after_command(now).unwrap().is_empty());
+        cfg.maintenance.gc.enabled = None;
+        cfg.maintenance.auto = false;
+        store.write_config(&cfg).unwrap();
+        assert!(store.maintenance_after_command(now).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_lock_blocks_a_second_run() {
+        let (_temp, store) = temp_repo();
+        let mut inner = None;
+        let outer = store
+            .run_maintenance(&[MaintenanceTask::Gc], |_| {
+                inner = Some(store.run_maintenance(&[MaintenanceTask::Reflog], |_| Ok("ran".into())).unwrap());
+                Ok("done".into())
+            })
+            .unwrap()
+            .unwrap();
+        assert_eq!(outer.succeeded, [(MaintenanceTask::Gc, "done".to_string())]);
+        assert!(inner.unwrap().is_none());
+
+        let status = store.maintenance_status(chrono::Utc::now().timestamp()).unwrap();
+        assert!(status[0].record.last_run.is_some());
+        assert!(status[1].record.last_run.is_none());
+        assert!(store.run_maintenance(&[MaintenanceTask::Reflog], |_| Ok(String::new())).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_failures_recorded_and_cleared() {
+        let (_temp, store) = temp_repo();
+        let tasks = [MaintenanceTask::Lfs, MaintenanceTask::Drafts];
+        let run = store
+            .run_maintenance(&tasks, |task| match task {
+                MaintenanceTask::Lfs => anyhow::bail!("objects directory unreadable"),
+                _ => Ok("removed 2 drafts".into()),
+            })
+            .unwrap()
+            .unwrap();
+        assert_eq!(run.failed, [(MaintenanceTask::Lfs, "objects directory unreadable".to_string())]);
+        assert_eq!(run.succeeded.len(), 1);
+
+        let now = chrono::Utc::now().timestamp();
+        let status = store.maintenance_status(now).unwrap();
+        let lfs = status.iter().find(|s| s.task == MaintenanceTask::Lfs).unwrap();
+        assert_eq!(lfs.record.last_error.as_deref(), Some("objects directory unreadable"));
+        assert!(!lfs.due, "a failed task waits for its interval like any other");
+        let log = fs::read_to_string(store.maintenance_log_path()).unwrap();
+        assert!(log.contains("lfs failed: objects directory unreadable"), "{}", log);
+        assert!(log.contains("drafts ok: removed 2 drafts"), "{}", log);
+
+        store.run_maintenance(&[MaintenanceTask::Lfs], |_| Ok("clean".into())).unwrap();
+        let status = store.maintenance_status(now).unwrap();
+        let lfs = status.iter().find(|s| s.task == MaintenanceTask::Lfs).unwrap();
+        assert_eq!(lfs.record.last_error, None);
+        assert_eq!(lfs.record.last_result.as_deref(), Some("clean"));
+    }
+
+    #[test]
+    fn test_rotate_file_past_limit() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("signals.log");
+        assert!(!rotate_file(&path, 10).unwrap());
+        fs::write(&path, "short\n").unwrap();
+        assert!(!rotate_file(&path, 10).unwrap());
+        fs::write(&path, "a much longer line\n").unwrap();
+        assert!(rotate_file(&path, 10).unwrap());
+        assert!(!path.exists());
+        assert_eq!(fs::read_to_string(temp.path().join("signals.log.1")).unwrap(), "a much longer line\n");
+    }
+}
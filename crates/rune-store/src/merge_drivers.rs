@@ -0,0 +1,569 @@
+//! Merge drivers: how a file changed on both sides of a merge is combined
+//!
+//! The driver for a path comes from its `merge=<name>` attribute. Built in
+//! are `text` (line-based three-way merge, the default), `union` (keep the
+//! lines both sides added), `json` (merge object keys) and `binary` (always
+//! a conflict). `[merge_drivers.<name>] cmd` in the config adds external
+//! drivers.
+//!
+//! `[merge] conflict_style` picks how conflicts are marked: `merge` shows
+//! ours and theirs, `diff3` adds the base version between them.
+
+use crate::Store;
+use anyhow::{Context, Result};
+use rune_core::attributes::AttributeEngine;
+use rune_core::eol::looks_binary;
+use rune_delta::hunks::{line_hunks, HunkLine};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The three versions of one file handed to a driver
+#[derive(Debug, Clone, Copy)]
+pub struct MergeInput<'a> {
+    pub path: &'a str,
+    /// Empty when the file did not exist at the merge base
+    pub base: &'a [u8],
+    pub ours: &'a [u8],
+    pub theirs: &'a [u8],
+    /// Names for conflict markers, e.g. `HEAD` and the merged branch
+    pub ours_label: &'a str,
+    pub theirs_label: &'a str,
+    pub conflict_style: ConflictStyle,
+}
+
+/// `[merge]`: how merges leave conflicts behind
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MergeCfg {
+    #[serde(default)]
+    pub conflict_style: ConflictStyle,
+}
+
+impl MergeCfg {
+    pub(crate) fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// What a conflict block shows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictStyle {
+    /// Ours, then theirs
+    #[default]
+    Merge,
+    /// Ours, the base version after `|||||||`, then theirs
+    Diff3,
+}
+
+impl ConflictStyle {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConflictStyle::Merge => "merge",
+            ConflictStyle::Diff3 => "diff3",
+        }
+    }
+
+    /// One conflict block, each section a run of whole lines
+    pub(crate) fn markers(&self, labels: (&str, &str), ours: &[&str], base: &[&str], theirs: &[&str]) -> Vec<String> {
+        let mut block = vec![format!("<<<<<<< {}", labels.0)];
+        block.extend(ours.iter().map(|l| l.to_string()));
+        if *self == ConflictStyle::Diff3 {
+            block.push("||||||| base".to_string());
+            block.extend(base.iter().map(|l| l.to_string()));
+        }
+        block.push("=======".to_string());
+        block.extend(theirs.iter().map(|l| l.to_string()));
+        block.push(format!(">>>>>>> {}", labels.1));
+        block
+    }
+}
+
+impl std::str::FromStr for ConflictStyle {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "merge" => Ok(ConflictStyle::Merge),
+            "diff3" => Ok(ConflictStyle::Diff3),
+            _ => anyhow::bail!("unknown merge.conflict_style '{}', expected merge or diff3", s),
+        }
+    }
+}
+
+/// Why a driver could not merge a file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MergeConflict {
+    pub driver: String,
+    /// What conflicted: line ranges, JSON keys, or the driver's complaint
+    pub details: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeOutcome {
+    Merged(Vec<u8>),
+    /// `content` is left in the working tree for the user to resolve
+    Conflict { content: Vec<u8>, conflict: MergeConflict },
+}
+
+pub trait MergeDriver {
+    fn merge(&self, input: &MergeInput) -> Result<MergeOutcome>;
+}
+
+/// `[merge_drivers.<name>]`: an external merge driver
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MergeDriverCfg {
+    /// Command template; `$BASE`, `$LOCAL` and `$REMOTE` are replaced with
+    /// file paths and `$FILE` with the path being merged. The result is read
+    /// back from `$LOCAL`; a nonzero exit marks a conflict.
+    pub cmd: String,
+}
+
+/// Drivers by name
+pub struct MergeDrivers {
+    drivers: BTreeMap<String, Box<dyn MergeDriver>>,
+}
+
+impl Default for MergeDrivers {
+    /// The built-in drivers
+    fn default() -> Self {
+        let mut drivers = Self { drivers: BTreeMap::new() };
+        drivers.register("text", TextDriver);
+        drivers.register("union", UnionDriver);
+        drivers.register("json", JsonDriver);
+        drivers.register("binary", BinaryDriver);
+        drivers
+    }
+}
+
+impl MergeDrivers {
+    pub fn register(&mut self, name: &str, driver: impl MergeDriver + 'static) {
+        self.drivers.insert(name.to_string(), Box::new(driver));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn MergeDriver> {
+        self.drivers.get(name).map(|d| d.as_ref())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.drivers.keys().map(String::as_str)
+    }
+}
+
+pub struct TextDriver;
+
+impl MergeDriver for TextDriver {
+    fn merge(&self, input: &MergeInput) -> Result<MergeOutcome> {
+        Ok(merge_lines(input, false))
+    }
+}
+
+/// Lines added on both sides are all kept, ours first; never conflicts
+pub struct UnionDriver;
+
+impl MergeDriver for UnionDriver {
+    fn merge(&self, input: &MergeInput) -> Result<MergeOutcome> {
+        Ok(merge_lines(input, true))
+    }
+}
+
+/// Merges JSON documents key by key; only a key both sides set to different
+/// values conflicts. Input that does not parse is merged as text.
+pub struct JsonDriver;
+
+impl MergeDriver for JsonDriver {
+    fn merge(&self, input: &MergeInput) -> Result<MergeOutcome> {
+        let parse = |content: &[u8]| serde_json::from_slice::<Value>(content).ok();
+        let base = if input.base.is_empty() { Some(Value::Object(Default::default())) } else { parse(input.base) };
+        let (Some(base), Some(ours), Some(theirs)) = (base, parse(input.ours), parse(input.theirs)) else {
+            return Ok(merge_lines(input, false));
+        };
+
+        let mut conflicts = Vec::new();
+        let merged = merge_json(Some(&base), &ours, &theirs, "", &mut conflicts);
+        if !conflicts.is_empty() {
+            let content = match merge_lines(input, false) {
+                MergeOutcome::Merged(content) | MergeOutcome::Conflict { content, .. } => content,
+            };
+            return Ok(MergeOutcome::Conflict {
+                content,
+                conflict: MergeConflict { driver: "json".to_string(), details: conflicts },
+            });
+        }
+        let mut content = serde_json::to_vec_pretty(&merged)?;
+        if input.ours.ends_with(b"\n") {
+            content.push(b'\n');
+        }
+        Ok(MergeOutcome::Merged(content))
+    }
+}
+
+/// Never merges; our version stays in place until a side is picked
+pub struct BinaryDriver;
+
+impl MergeDriver for BinaryDriver {
+    fn merge(&self, input: &MergeInput) -> Result<MergeOutcome> {
+        Ok(MergeOutcome::Conflict {
+            content: input.ours.to_vec(),
+            conflict: MergeConflict {
+                driver: "binary".to_string(),
+                details: vec!["changed on both sides; pick one with `rune mergetool` (lock it next time)".to_string()],
+            },
+        })
+    }
+}
+
+/// Runs a configured command on the three versions
+pub struct ExternalDriver {
+    pub name: String,
+    pub cmd: String,
+    /// Where the command runs
+    pub root: PathBuf,
+    /// Directory for the temporary input files
+    pub scratch: PathBuf,
+}
+
+impl MergeDriver for ExternalDriver {
+    fn merge(&self, input: &MergeInput) -> Result<MergeOutcome> {
+        fs::create_dir_all(&self.scratch)?;
+        let file = |name: &str, content: &[u8]| -> Result<PathBuf> {
+            let path = self.scratch.join(name);
+            fs::write(&path, content)?;
+            Ok(path)
+        };
+        let base = file("base", input.base)?;
+        let local = file("local", input.ours)?;
+        let remote = file("remote", input.theirs)?;
+        let command = self
+            .cmd
+            .replace("$BASE", &shell_quote(&base))
+            .replace("$LOCAL", &shell_quote(&local))
+            .replace("$REMOTE", &shell_quote(&remote))
+            .replace("$FILE", &shell_quote(Path::new(input.path)));
+
+        let status = if cfg!(windows) {
+            Command::new("cmd").args(["/C", &command]).current_dir(&self.root).status()
+        } else {
+            Command::new("sh").args(["-c", &command]).current_dir(&self.root).status()
+        }
+        .with_context(|| format!("failed to run merge driver {}", self.name))?;
+        let content = fs::read(&local)?;
+        let _ = fs::remove_dir_all(&self.scratch);
+
+        if status.success() {
+            Ok(MergeOutcome::Merged(content))
+        } else {
+            Ok(MergeOutcome::Conflict {
+                content,
+                conflict: MergeConflict {
+                    driver: self.name.clone(),
+                    details: vec![format!("`{}` failed ({})", self.cmd, status)],
+                },
+            })
+        }
+    }
+}
+
+fn shell_quote(path: &Path) -> String {
+    format!("'{}'", path.to_string_lossy().replace('\'', "'\\''"))
+}
+
+impl Store {
+    /// Built-in drivers plus the external ones from the config
+    pub fn merge_drivers(&self) -> MergeDrivers {
+        let mut drivers = MergeDrivers::default();
+        for (name, cfg) in self.config().merge_drivers {
+            drivers.register(
+                &name,
+                ExternalDriver {
+                    name: name.clone(),
+                    cmd: cfg.cmd,
+                    root: self.root.clone(),
+                    scratch: self.rune_dir.join("merge-driver"),
+                },
+            );
+        }
+        drivers
+    }
+
+    /// Merge one file with the driver its attributes select. Unknown driver
+    /// names fall back to `text`, and content that looks binary to `binary`.
+    pub fn merge_file(&self, input: &MergeInput) -> Result<MergeOutcome> {
+        let drivers = self.merge_drivers();
+        let name = AttributeEngine::new(&self.root)?.merge_driver(input.path);
+        let driver = match name.as_deref().and_then(|n| drivers.get(n)) {
+            Some(driver) => driver,
+            None if [input.base, input.ours, input.theirs].iter().any(|c| looks_binary(c)) => &BinaryDriver,
+            None => &TextDriver,
+        };
+        driver.merge(input)
+    }
+}
+
+/// One side's edit of the base: lines `start..end` replaced by `lines`
+struct Change {
+    start: usize,
+    end: usize,
+    lines: Vec<String>,
+}
+
+fn changes(base: &str, side: &str) -> Vec<Change> {
+    line_hunks(base, side, 0)
+        .into_iter()
+        .map(|hunk| {
+            let start = if hunk.old_count == 0 { hunk.old_start } else { hunk.old_start - 1 };
+            Change {
+                start,
+                end: start + hunk.old_count,
+                lines: hunk
+                    .lines
+                    .into_iter()
+                    .filter_map(|l| match l {
+                        HunkLine::Add(s) => Some(s),
+                        _ => None,
+                    })
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// `base[start..end]` with `changes` (all inside that range) applied
+fn apply<'a>(base: &[&'a str], changes: &'a [Change], start: usize, end: usize) -> Vec<&'a str> {
+    let mut out = Vec::new();
+    let mut pos = start;
+    for change in changes {
+        out.extend_from_slice(&base[pos..change.start]);
+        out.extend(change.lines.iter().map(String::as_str));
+        pos = change.end;
+    }
+    out.extend_from_slice(&base[pos..end]);
+    out
+}
+
+/// Line-based three-way merge. Changes from both sides that overlap or touch
+/// form one region; with `union` such a region keeps ours then theirs,
+/// otherwise it becomes a conflict unless both sides made the same edit.
+fn merge_lines(input: &MergeInput, union: bool) -> MergeOutcome {
+    let text = |content: &[u8]| String::from_utf8_lossy(content).into_owned();
+    let (base, ours, theirs) = (text(input.base), text(input.ours), text(input.theirs));
+    let base_lines: Vec<&str> = base.lines().collect();
+    let (ours_changes, theirs_changes) = (changes(&base, &ours), changes(&base, &theirs));
+
+    let mut out: Vec<String> = Vec::new();
+    let mut conflicts = Vec::new();
+    let (mut pos, mut i, mut j) = (0, 0, 0);
+    while i < ours_changes.len() || j < theirs_changes.len() {
+        let first_ours = j >= theirs_changes.len()
+            || (i < ours_changes.len() && ours_changes[i].start <= theirs_changes[j].start);
+        let (start, mut end) = if first_ours {
+            (ours_changes[i].start, ours_changes[i].end)
+        } else {
+            (theirs_changes[j].start, theirs_changes[j].end)
+        };
+        let (oi, tj) = (i, j);
+        // Grow the region while a change from either side starts inside it
+        loop {
+            if i < ours_changes.len() && ours_changes[i].start <= end {
+                end = end.max(ours_changes[i].end);
+                i += 1;
+            } else if j < theirs_changes.len() && theirs_changes[j].start <= end {
+                end = end.max(theirs_changes[j].end);
+                j += 1;
+            } else {
+                break;
+            }
+        }
+
+        out.extend(base_lines[pos..start].iter().map(|l| l.to_string()));
+        let ours_region = apply(&base_lines, &ours_changes[oi..i], start, end);
+        let theirs_region = apply(&base_lines, &theirs_changes[tj..j], start, end);
+        if tj == j || ours_region == theirs_region {
+            out.extend(ours_region.iter().map(|l| l.to_string()));
+        } else if oi == i {
+            out.extend(theirs_region.iter().map(|l| l.to_string()));
+        } else if union {
+            out.extend(ours_region.iter().map(|l| l.to_string()));
+            out.extend(theirs_region.iter().map(|l| l.to_string()));
+        } else {
+            let labels = (input.ours_label, input.theirs_label);
+            out.extend(input.conflict_style.markers(labels, &ours_region, &base_lines[start..end], &theirs_region));
+            conflicts.push(if end > start {
+                format!("lines {}-{} changed on both sides", start + 1, end)
+            } else {
+                format!("both sides inserted after line {}", start)
+            });
+        }
+        pos = end;
+    }
+    out.extend(base_lines[pos..].iter().map(|l| l.to_string()));
+
+    let mut content = out.join("\n");
+    if !out.is_empty() && [&base, &ours, &theirs].iter().any(|t| t.ends_with('\n')) {
+        content.push('\n');
+    }
+    if conflicts.is_empty() {
+        MergeOutcome::Merged(content.into_bytes())
+    } else {
+        MergeOutcome::Conflict {
+            content: content.into_bytes(),
+            conflict: MergeConflict { driver: "text".to_string(), details: conflicts },
+        }
+    }
+}
+
+/// Merge JSON values, recording the paths of keys set differently on both
+/// sides in `conflicts` (ours is kept for those)
+fn merge_json(base: Option<&Value>, ours: &Value, theirs: &Value, path: &str, conflicts: &mut Vec<String>) -> Value {
+    if ours == theirs || base == Some(theirs) {
+        return ours.clone();
+    }
+    if base == Some(ours) {
+        return theirs.clone();
+    }
+    let (Value::Object(o), Value::Object(t)) = (ours, theirs) else {
+        conflicts.push(if path.is_empty() { "(document)".to_string() } else { path.to_string() });
+        return ours.clone();
+    };
+    let b = base.and_then(Value::as_object);
+    let mut merged = serde_json::Map::new();
+    for key in o.keys().chain(t.keys().filter(|k| !o.contains_key(*k))) {
+        let key_path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+        let base_value = b.and_then(|b| b.get(key));
+        let value = match (o.get(key), t.get(key)) {
+            (Some(ov), Some(tv)) => Some(merge_json(base_value, ov, tv, &key_path, conflicts)),
+            // Deleted on one side: fine unless the other side changed it
+            (Some(v), None) | (None, Some(v)) => match base_value {
+                None => Some(v.clone()),
+                Some(bv) if bv == v => None,
+                Some(_) => {
+                    conflicts.push(key_path);
+                    o.get(key).cloned()
+                }
+            },
+            (None, None) => None,
+        };
+        if let Some(value) = value {
+            merged.insert(key.clone(), value);
+        }
+    }
+    Value::Object(merged)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_repo;
+    use tempfile::TempDir;
+
+    fn input<'a>(path: &'a str, base: &'a str, ours: &'a str, theirs: &'a str) -> MergeInput<'a> {
+        MergeInput {
+            path,
+            base: base.as_bytes(),
+            ours: ours.as_bytes(),
+            theirs: theirs.as_bytes(),
+            ours_label: "HEAD",
+            theirs_label: "feature",
+            conflict_style: ConflictStyle::Merge,
+        }
+    }
+
+    fn repo(attributes: &str) -> (TempDir, Store) {
+        let (temp, store) = temp_repo();
+        fs::write(temp.path().join(".runeattributes"), attributes).unwrap();
+        (temp, store)
+    }
+
+    #[test]
+    fn test_union_merges_changelog_entries() {
+        let (_temp, store) = repo("CHANGELOG.md merge=union\n");
+        let base = "# Changelog\n\n## Unreleased\n\n## 1.0\n- first release\n";
+        let ours = "# Changelog\n\n## Unreleased\n- add export\n\n## 1.0\n- first release\n";
+        let theirs = "# Changelog\n\n## Unreleased\n- fix crash\n\n## 1.0\n- first release\n";
+        let outcome = store.merge_file(&input("CHANGELOG.md", base, ours, theirs)).unwrap();
+        assert_eq!(
+            String::from_utf8(match outcome {
+                MergeOutcome::Merged(content) => content,
+                other => panic!("union should not conflict: {:?}", other),
+            })
+            .unwrap(),
+            "# Changelog\n\n## Unreleased\n- add export\n- fix crash\n\n## 1.0\n- first release\n"
+        );
+
+        // Without the attribute the same edit conflicts
+        let text = store.merge_file(&input("NOTES.md", base, ours, theirs)).unwrap();
+        assert!(matches!(text, MergeOutcome::Conflict { ref conflict, .. } if conflict.driver == "text"));
+    }
+
+    #[test]
+    fn test_json_merges_disjoint_keys_and_conflicts_on_same_key() {
+        let (_temp, store) = repo("*.json merge=json\n");
+        let base = r#"{"name": "app", "deps": {"a": "1.0", "b": "1.0"}}"#;
+        let ours = r#"{"name": "app", "deps": {"a": "1.1", "b": "1.0"}}"#;
+        let theirs = r#"{"name": "app", "deps": {"a": "1.0", "b": "2.0", "c": "0.1"}}"#;
+        let MergeOutcome::Merged(merged) = store.merge_file(&input("package.json", base, ours, theirs)).unwrap() else {
+            panic!("disjoint edits should merge");
+        };
+        let merged: Value = serde_json::from_slice(&merged).unwrap();
+        assert_eq!(merged, serde_json::json!({"name": "app", "deps": {"a": "1.1", "b": "2.0", "c": "0.1"}}));
+
+        let theirs = r#"{"name": "app", "deps": {"a": "1.2", "b": "1.0"}}"#;
+        match store.merge_file(&input("package.json", base, ours, theirs)).unwrap() {
+            MergeOutcome::Conflict { conflict, .. } => {
+                assert_eq!(conflict, MergeConflict { driver: "json".into(), details: vec!["deps.a".into()] })
+            }
+            other => panic!("expected a conflict, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_external_driver_result_and_exit_code() {
+        let (temp, store) = repo("*.lock merge=lockfile\n");
+        let mut cfg = store.config();
+        cfg.merge_drivers.insert(
+            "lockfile".into(),
+            MergeDriverCfg { cmd: "echo $FILE >> driver.log; cat $REMOTE >> $LOCAL; grep -q ok $REMOTE".into() },
+        );
+        store.write_config(&cfg).unwrap();
+
+        let merged = store.merge_file(&input("deps.lock", "", "mine\n", "ok\n")).unwrap();
+        assert_eq!(merged, MergeOutcome::Merged(b"mine\nok\n".to_vec()));
+
+        let failed = store.merge_file(&input("deps.lock", "", "mine\n", "bad\n")).unwrap();
+        assert!(matches!(failed, MergeOutcome::Conflict { ref content, ref conflict }
+            if content == b"mine\nbad\n" && conflict.driver == "lockfile"));
+        assert_eq!(fs::read_to_string(temp.path().join("driver.log")).unwrap(), "deps.lock\ndeps.lock\n");
+    }
+
+    #[test]
+    fn test_text_merge_keeps_separate_edits() {
+        let base = "a\nb\nc\nd\ne\n";
+        let outcome = merge_lines(&input("f", base, "A\nb\nc\nd\ne\n", "a\nb\nc\nd\nE\n"), false);
+        assert_eq!(outcome, MergeOutcome::Merged(b"A\nb\nc\nd\nE\n".to_vec()));
+
+        let outcome = merge_lines(&input("f", base, "a\nB\nc\nd\ne\n", "a\nb2\nc\nd\ne\n"), false);
+        let MergeOutcome::Conflict { content, conflict } = outcome else { panic!() };
+        assert_eq!(String::from_utf8(content).unwrap(), "a\n<<<<<<< HEAD\nB\n=======\nb2\n>>>>>>> feature\nc\nd\ne\n");
+        assert_eq!(conflict.details, ["lines 2-2 changed on both sides"]);
+    }
+
+    #[test]
+    fn test_conflict_styles_mark_the_conflicting_region() {
+        let base = "a\nb\nc\nd\ne\n";
+        let mut both = input("f", base, "a\nB\nC\nd\ne\n", "a\nb2\nc\nd\ne\n");
+        let marked = |input: &MergeInput| match merge_lines(input, false) {
+            MergeOutcome::Conflict { content, .. } => String::from_utf8(content).unwrap(),
+            merged => panic!("{:?}", merged),
+        };
+        assert_eq!(marked(&both), "a\n<<<<<<< HEAD\nB\nC\n=======\nb2\nc\n>>>>>>> feature\nd\ne\n");
+        both.conflict_style = ConflictStyle::Diff3;
+        assert_eq!(marked(&both), "a\n<<<<<<< HEAD\nB\nC\n||||||| base\nb\nc\n=======\nb2\nc\n>>>>>>> feature\nd\ne\n");
+
+        // Both sides adding at the same place have an empty base section
+        let added = MergeInput { conflict_style: ConflictStyle::Diff3, ..input("f", "a\n", "a\nx\n", "a\ny\n") };
+        assert_eq!(marked(&added), "a\n<<<<<<< HEAD\nx\n||||||| base\n=======\ny\n>>>>>>> feature\n");
+        assert!("zdiff3".parse::<ConflictStyle>().is_err());
+    }
+}
@@ -0,0 +1,695 @@
+//! Journal of destructive operations, for `rune undo` and `rune redo`
+//!
+//! `begin_operation` captures the refs, the staging area and, for
+//! operations that rewrite working files, the content of every file they
+//! could touch. `PendingOperation::finish` compares that with the state
+//! afterwards and appends what moved to `.rune/operations.jsonl`. Undo puts
+//! the old values back, but only while everything the operation changed
+//! still looks the way it left it; anything else is left alone.
+
+use crate::refs::is_loose_ref;
+use crate::{write_atomic, Index, Store};
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use rune_core::Commit;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OperationKind {
+    Commit,
+    Amend,
+    Merge,
+    Reset,
+    BranchDelete,
+    Revert,
+    DraftDelete,
+    Rollback,
+    Push,
+    Split,
+    Pull,
+}
+
+impl OperationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OperationKind::Commit => "commit",
+            OperationKind::Amend => "amend",
+            OperationKind::Merge => "merge",
+            OperationKind::Reset => "reset",
+            OperationKind::BranchDelete => "branch delete",
+            OperationKind::Revert => "revert",
+            OperationKind::DraftDelete => "draft delete",
+            OperationKind::Rollback => "rollback",
+            OperationKind::Push => "push",
+            OperationKind::Split => "split",
+            OperationKind::Pull => "pull",
+        }
+    }
+}
+
+/// A ref, or `HEAD`, before and after; `None` where it didn't exist
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RefChange {
+    pub name: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// A file's content before and after as blob ids; `None` where it didn't
+/// exist. Paths are relative to the repository root unless absolute.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileChange {
+    pub path: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// A commit the operation added to `log.jsonl` (`old` is `None`) or
+/// replaced in place
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogChange {
+    pub position: usize,
+    pub old: Option<Commit>,
+    pub new: Option<Commit>,
+}
+
+/// One line of `.rune/operations.jsonl`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationRecord {
+    /// Increases with every operation, undo and redo
+    pub seq: u64,
+    pub time: i64,
+    pub kind: OperationKind,
+    pub description: String,
+    /// Contents of `HEAD` afterwards; undo and redo only run from there
+    #[serde(default)]
+    pub head: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub refs: Vec<RefChange>,
+    /// Blob ids of `index.json` before and after
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index_before: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index_after: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<FileChange>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub log: Vec<LogChange>,
+    /// Why the operation can't be undone; `None` when it can
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_undoable: Option<String>,
+    /// `seq` of the undo that reverted it, while it stays undone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub undone: Option<u64>,
+}
+
+impl OperationRecord {
+    pub fn undoable(&self) -> bool {
+        self.not_undoable.is_none()
+    }
+
+    fn changes_index(&self) -> bool {
+        self.index_before != self.index_after
+    }
+}
+
+/// What `Store::undo_operation` did
+#[derive(Debug, Clone)]
+pub struct UndoReport {
+    pub undone: OperationRecord,
+    /// Newer operations that can't be undone and were stepped over
+    pub skipped: Vec<OperationRecord>,
+}
+
+/// Repository state as `begin_operation` saw it
+struct Capture {
+    refs: BTreeMap<String, String>,
+    index: Option<String>,
+    log: Vec<Commit>,
+    files: BTreeMap<String, Option<String>>,
+}
+
+/// An operation in progress; see `Store::begin_operation`
+pub struct PendingOperation {
+    kind: OperationKind,
+    description: String,
+    worktree: bool,
+    not_undoable: Option<String>,
+    before: Capture,
+}
+
+impl PendingOperation {
+    /// Also journal `path`, a file outside the working tree the operation
+    /// may rewrite or delete (a draft, say). Call before the operation runs.
+    pub fn watch(mut self, store: &Store, path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let key = store.journal_path(&path);
+        let blob = store.capture_file(&path)?;
+        self.before.files.insert(key, blob);
+        Ok(self)
+    }
+
+    /// Journal the operation but refuse to undo it, telling the user why
+    pub fn not_undoable(mut self, reason: impl Into<String>) -> Self {
+        self.not_undoable = Some(reason.into());
+        self
+    }
+
+    /// Record what changed since `begin_operation`. Nothing is written when
+    /// the operation changed nothing.
+    pub fn finish(self, store: &Store) -> Result<Option<OperationRecord>> {
+        let after_refs = store.capture_refs()?;
+        let names: BTreeSet<&String> = self.before.refs.keys().chain(after_refs.keys()).collect();
+        let refs: Vec<RefChange> = names
+            .into_iter()
+            .filter(|n| self.before.refs.get(*n) != after_refs.get(*n))
+            .map(|n| RefChange {
+                name: n.clone(),
+                old: self.before.refs.get(n).cloned(),
+                new: after_refs.get(n).cloned(),
+            })
+            .collect();
+
+        let index_after = store.capture_index()?;
+        let after_log = store.log();
+        let log: Vec<LogChange> = (0..self.before.log.len().max(after_log.len()))
+            .filter_map(|i| {
+                let (old, new) = (self.before.log.get(i), after_log.get(i));
+                (old.map(|c| &c.id) != new.map(|c| &c.id))
+                    .then(|| LogChange { position: i, old: old.cloned(), new: new.cloned() })
+            })
+            .collect();
+
+        let mut paths: BTreeSet<String> = self.before.files.keys().cloned().collect();
+        if self.worktree {
+            paths.extend(store.worktree_candidates()?);
+        }
+        let mut files = Vec::new();
+        for path in paths {
+            let old = self.before.files.get(&path).cloned().flatten();
+            let new = store.capture_file(&store.root.join(&path))?;
+            if old != new {
+                files.push(FileChange { path, old, new });
+            }
+        }
+
+        if refs.is_empty() && log.is_empty() && files.is_empty() && self.before.index == index_after {
+            return Ok(None);
+        }
+        let record = OperationRecord {
+            seq: store.next_operation_seq()?,
+            time: Utc::now().timestamp(),
+            kind: self.kind,
+            description: self.description,
+            head: after_refs.get("HEAD").cloned().unwrap_or_default(),
+            refs,
+            index_before: self.before.index,
+            index_after,
+            files,
+            log,
+            not_undoable: self.not_undoable,
+            undone: None,
+        };
+        store.append_operation(&record)?;
+        Ok(Some(record))
+    }
+}
+
+impl Store {
+    fn operations_path(&self) -> PathBuf {
+        self.rune_dir.join("operations.jsonl")
+    }
+
+    /// Journaled operations, oldest first
+    pub fn operations(&self) -> Result<Vec<OperationRecord>> {
+        let path = self.operations_path();
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        fs::read_to_string(&path)?
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| serde_json::from_str(l).with_context(|| format!("{} is corrupt", path.display())))
+            .collect()
+    }
+
+    fn write_operations(&self, records: &[OperationRecord]) -> Result<()> {
+        let mut content = String::new();
+        for record in records {
+            content.push_str(&serde_json::to_string(record)?);
+            content.push('\n');
+        }
+        write_atomic(&self.operations_path(), content.as_bytes())
+    }
+
+    fn append_operation(&self, record: &OperationRecord) -> Result<()> {
+        let mut records = self.operations()?;
+        records.push(record.clone());
+        self.write_operations(&records)
+    }
+
+    fn next_operation_seq(&self) -> Result<u64> {
+        let records = self.operations()?;
+        Ok(records.iter().flat_map(|r| [Some(r.seq), r.undone]).flatten().max().unwrap_or(0) + 1)
+    }
+
+    /// Start journaling an operation of `kind`. With `worktree`, the
+    /// working files it could rewrite are captured too, so undo can put
+    /// them back. Run the operation, then call `finish`; an operation that
+    /// fails before `finish` leaves no record.
+    pub fn begin_operation(
+        &self,
+        kind: OperationKind,
+        description: impl Into<String>,
+        worktree: bool,
+    ) -> Result<PendingOperation> {
+        let mut files = BTreeMap::new();
+        if worktree {
+            for path in self.worktree_candidates()? {
+                let blob = self.capture_file(&self.root.join(&path))?;
+                files.insert(path, blob);
+            }
+        }
+        Ok(PendingOperation {
+            kind,
+            description: description.into(),
+            worktree,
+            not_undoable: None,
+            before: Capture { refs: self.capture_refs()?, index: self.capture_index()?, log: self.log(), files },
+        })
+    }
+
+    /// `HEAD` and every file under `refs/`, by content
+    fn capture_refs(&self) -> Result<BTreeMap<String, String>> {
+        let mut refs = BTreeMap::new();
+        if let Ok(head) = fs::read_to_string(self.rune_dir.join("HEAD")) {
+            refs.insert("HEAD".to_string(), head);
+        }
+        for entry in walkdir::WalkDir::new(self.rune_dir.join("refs")).into_iter().flatten() {
+            if is_loose_ref(&entry) {
+                let rel = entry.path().strip_prefix(&self.rune_dir)?;
+                refs.insert(rel.to_string_lossy().replace('\\', "/"), fs::read_to_string(entry.path())?);
+            }
+        }
+        Ok(refs)
+    }
+
+    fn capture_index(&self) -> Result<Option<String>> {
+        match fs::read(self.rune_dir.join("index.json")) {
+            Ok(bytes) => Ok(Some(self.write_blob(&bytes)?)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    fn capture_file(&self, path: &Path) -> Result<Option<String>> {
+        match fs::read(path) {
+            Ok(bytes) => Ok(Some(self.write_blob(&bytes)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// `path` as a `FileChange` path: relative when under the root
+    fn journal_path(&self, path: &Path) -> String {
+        path.strip_prefix(&self.root).unwrap_or(path).to_string_lossy().replace('\\', "/")
+    }
+
+    /// Working files an operation could rewrite: everything tracked or
+    /// staged, plus untracked files
+    fn worktree_candidates(&self) -> Result<BTreeSet<String>> {
+        let mut paths: BTreeSet<String> = self.log().into_iter().flat_map(|c| c.files).collect();
+        paths.extend(self.read_index()?.entries.into_keys());
+        let status = self.status()?;
+        paths.extend(status.untracked);
+        paths.extend(status.renamed.iter().map(|r| r.path().to_string()));
+        Ok(paths)
+    }
+
+    /// Revert the most recent operation that can be undone. Newer
+    /// operations that can't be undone are stepped over and reported.
+    pub fn undo_operation(&self) -> Result<UndoReport> {
+        let mut records = self.operations()?;
+        let mut skipped = Vec::new();
+        let mut target = None;
+        for (i, record) in records.iter().enumerate().rev() {
+            if record.undone.is_some() {
+                continue;
+            }
+            if record.undoable() {
+                target = Some(i);
+                break;
+            }
+            skipped.push(record.clone());
+        }
+        let Some(i) = target else {
+            match skipped.first() {
+                Some(last) => bail!(
+                    "cannot undo {} ({}): {}",
+                    last.kind.as_str(),
+                    last.description,
+                    last.not_undoable.as_deref().unwrap_or_default()
+                ),
+                None => bail!("nothing to undo"),
+            }
+        };
+
+        self.apply_operation(&records[i], true)?;
+        let seq = self.next_operation_seq()?;
+        records[i].undone = Some(seq);
+        self.write_operations(&records)?;
+        Ok(UndoReport { undone: records[i].clone(), skipped })
+    }
+
+    /// Re-apply the most recently undone operation, provided nothing else
+    /// was journaled since the undo
+    pub fn redo_operation(&self) -> Result<OperationRecord> {
+        let mut records = self.operations()?;
+        let Some(i) = (0..records.len()).filter(|&i| records[i].undone.is_some()).max_by_key(|&i| records[i].undone)
+        else {
+            bail!("nothing to redo");
+        };
+        let undone_at = records[i].undone.unwrap_or_default();
+        if let Some(later) = records.iter().find(|r| r.seq > undone_at) {
+            bail!(
+                "cannot redo {} ({}): {} ({}) happened since it was undone",
+                records[i].kind.as_str(),
+                records[i].description,
+                later.kind.as_str(),
+                later.description
+            );
+        }
+        self.apply_operation(&records[i], false)?;
+        records[i].undone = None;
+        self.write_operations(&records)?;
+        Ok(records[i].clone())
+    }
+
+    /// Move everything `record` changed back to its old values (`undo`) or
+    /// forward to its new ones, after checking nothing else moved it since
+    fn apply_operation(&self, record: &OperationRecord, undo: bool) -> Result<()> {
+        let pick = |old: &Option<String>, new: &Option<String>| -> (Option<String>, Option<String>) {
+            if undo {
+                (new.clone(), old.clone())
+            } else {
+                (old.clone(), new.clone())
+            }
+        };
+        let action = if undo { "undo" } else { "redo" };
+
+        let current_refs = self.capture_refs()?;
+        let head = current_refs.get("HEAD").map(String::as_str).unwrap_or_default();
+        if !record.refs.iter().any(|r| r.name == "HEAD") && head != record.head {
+            bail!(
+                "cannot {} {}: it ran on {}; switch back to it first",
+                action,
+                record.kind.as_str(),
+                record.head.trim().trim_start_matches("ref: ")
+            );
+        }
+        let moved: Vec<&str> = record
+            .refs
+            .iter()
+            .filter(|r| current_refs.get(&r.name) != pick(&r.old, &r.new).0.as_ref())
+            .map(|r| r.name.as_str())
+            .collect();
+        if !moved.is_empty() {
+            bail!("cannot {} {}: {} changed since", action, record.kind.as_str(), moved.join(", "));
+        }
+        let mut edited = Vec::new();
+        for file in &record.files {
+            let current = self.capture_file(&self.root.join(&file.path))?;
+            if current != pick(&file.old, &file.new).0 {
+                edited.push(file.path.as_str());
+            }
+        }
+        if !edited.is_empty() {
+            bail!(
+                "cannot {} {}: {} changed since; commit or discard those edits first",
+                action,
+                record.kind.as_str(),
+                edited.join(", ")
+            );
+        }
+
+        for r in &record.refs {
+            let path = self.rune_dir.join(&r.name);
+            match pick(&r.old, &r.new).1 {
+                Some(content) => {
+                    if let Some(parent) = path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::write(&path, content)?;
+                }
+                None => {
+                    if path.exists() {
+                        fs::remove_file(&path)?;
+                    }
+                }
+            }
+        }
+        self.apply_log_changes(&record.log, undo)?;
+        if record.changes_index() {
+            // Through write_index, so what is staged now stays in the index
+            // history rather than being lost
+            match pick(&record.index_before, &record.index_after).1 {
+                Some(blob) => {
+                    let index: Index = serde_json::from_slice(&self.read_blob(&blob)?)?;
+                    self.write_index(&index)?;
+                }
+                None => self.write_index(&Index::default())?,
+            }
+        }
+        for file in &record.files {
+            let path = self.root.join(&file.path);
+            match pick(&file.old, &file.new).1 {
+                Some(blob) => {
+                    if let Some(parent) = path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::write(&path, self.read_blob(&blob)?)?;
+                }
+                None => {
+                    if path.exists() {
+                        fs::remove_file(&path)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Undo or redo `changes` to `log.jsonl`. An added commit is only
+    /// dropped again while no ref or other commit refers to it.
+    fn apply_log_changes(&self, changes: &[LogChange], undo: bool) -> Result<()> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let mut log = self.log();
+        if undo {
+            let refs = self.capture_refs()?;
+            for change in changes.iter().rev() {
+                let Some(new) = &change.new else { continue };
+                let Some(pos) = log.iter().position(|c| c.id == new.id) else { continue };
+                match &change.old {
+                    Some(old) => log[pos] = old.clone(),
+                    None => {
+                        let referenced = refs.values().any(|r| r.trim() == new.id)
+                            || log.iter().any(|c| c.parent.as_deref() == Some(new.id.as_str()));
+                        if !referenced {
+                            log.remove(pos);
+                        }
+                    }
+                }
+            }
+        } else {
+            for change in changes {
+                let Some(new) = &change.new else { continue };
+                if log.iter().any(|c| c.id == new.id) {
+                    continue;
+                }
+                match change.old.as_ref().and_then(|old| log.iter().position(|c| c.id == old.id)) {
+                    Some(pos) => log[pos] = new.clone(),
+                    None => log.push(new.clone()),
+                }
+            }
+        }
+        let mut content = String::new();
+        for commit in &log {
+            content.push_str(&serde_json::to_string(commit)?);
+            content.push('\n');
+        }
+        write_atomic(&self.rune_dir.join("log.jsonl"), content.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{author, temp_repo};
+    use tempfile::TempDir;
+
+    fn repo() -> (TempDir, Store) {
+        let (temp, store) = temp_repo();
+        fs::write(temp.path().join("a.txt"), "one").unwrap();
+        store.stage_file("a.txt").unwrap();
+        store.commit("first", author()).unwrap();
+        (temp, store)
+    }
+
+    fn journaled_commit(store: &Store, message: &str) -> Commit {
+        let pending = store.begin_operation(OperationKind::Commit, message, false).unwrap();
+        let commit = store.commit(message, author()).unwrap();
+        pending.finish(store).unwrap();
+        commit
+    }
+
+    #[test]
+    fn test_undo_commit_restores_ref_and_staging() {
+        let (temp, store) = repo();
+        let first = store.head_commit().unwrap();
+        fs::write(temp.path().join("a.txt"), "two").unwrap();
+        fs::write(temp.path().join("b.txt"), "new").unwrap();
+        store.stage_file("a.txt").unwrap();
+        store.stage_file("b.txt").unwrap();
+        let second = journaled_commit(&store, "second");
+        assert!(store.read_index().unwrap().entries.is_empty());
+
+        let report = store.undo_operation().unwrap();
+        assert_eq!(report.undone.kind, OperationKind::Commit);
+        assert_eq!(store.head_commit().as_deref(), Some(first.as_str()));
+        let staged: Vec<String> = store.read_index().unwrap().entries.into_keys().collect();
+        assert_eq!(staged, ["a.txt", "b.txt"]);
+        assert!(!store.log().iter().any(|c| c.id == second.id));
+        // Working files are the operation's to restore only if it touched them
+        assert_eq!(fs::read_to_string(temp.path().join("a.txt")).unwrap(), "two");
+        assert!(store.operations().unwrap()[0].undone.is_some());
+    }
+
+    #[test]
+    fn test_undo_branch_delete_recreates_ref() {
+        let (_temp, store) = repo();
+        store.create_branch("feature").unwrap();
+        let tip = store.read_ref("refs/heads/feature").unwrap();
+        let pending = store.begin_operation(OperationKind::BranchDelete, "feature", false).unwrap();
+        store.delete_branch("feature").unwrap();
+        let record = pending.finish(&store).unwrap().unwrap();
+        assert_eq!(record.refs.len(), 1);
+        assert!(!store.branch_exists("feature"));
+
+        store.undo_operation().unwrap();
+        assert_eq!(store.read_ref("refs/heads/feature").as_deref(), Some(tip.as_str()));
+
+        // A commit is only undone from the branch it was made on
+        store.checkout_branch("feature").unwrap();
+        fs::write(store.root.join("a.txt"), "feature").unwrap();
+        store.stage_file("a.txt").unwrap();
+        journaled_commit(&store, "on feature");
+        store.checkout_branch("main").unwrap();
+        let err = store.undo_operation().unwrap_err().to_string();
+        assert_eq!(err, "cannot undo commit: it ran on refs/heads/feature; switch back to it first");
+        assert!(store.read_index().unwrap().entries.is_empty());
+    }
+
+    #[test]
+    fn test_redo_after_undo() {
+        let (temp, store) = repo();
+        fs::write(temp.path().join("a.txt"), "two").unwrap();
+        store.stage_file("a.txt").unwrap();
+        let second = journaled_commit(&store, "second");
+
+        store.undo_operation().unwrap();
+        let redone = store.redo_operation().unwrap();
+        assert_eq!(redone.kind, OperationKind::Commit);
+        assert_eq!(store.head_commit().as_deref(), Some(second.id.as_str()));
+        assert!(store.read_index().unwrap().entries.is_empty());
+        assert_eq!(store.log().last().unwrap().id, second.id);
+        assert!(store.redo_operation().is_err());
+
+        // A new operation after an undo rules out the redo
+        store.undo_operation().unwrap();
+        store.create_branch("other").unwrap();
+        let pending = store.begin_operation(OperationKind::BranchDelete, "other", false).unwrap();
+        store.delete_branch("other").unwrap();
+        pending.finish(&store).unwrap();
+        let err = store.redo_operation().unwrap_err().to_string();
+        assert!(err.contains("happened since"), "{}", err);
+    }
+
+    #[test]
+    fn test_non_undoable_operations_are_explained_and_stepped_over() {
+        let (temp, store) = repo();
+        let pending = store
+            .begin_operation(OperationKind::Push, "origin main", false)
+            .unwrap()
+            .not_undoable("the commits are already on the remote");
+        store.write_ref("refs/remotes/origin/main", &store.head_commit().unwrap()).unwrap();
+        pending.finish(&store).unwrap();
+
+        let err = store.undo_operation().unwrap_err().to_string();
+        assert_eq!(err, "cannot undo push (origin main): the commits are already on the remote");
+
+        fs::write(temp.path().join("a.txt"), "two").unwrap();
+        store.stage_file("a.txt").unwrap();
+        journaled_commit(&store, "second");
+        let pending = store
+            .begin_operation(OperationKind::Push, "origin main", false)
+            .unwrap()
+            .not_undoable("the commits are already on the remote");
+        store.write_ref("refs/remotes/origin/main", &store.head_commit().unwrap()).unwrap();
+        pending.finish(&store).unwrap();
+
+        let report = store.undo_operation().unwrap();
+        assert_eq!(report.undone.kind, OperationKind::Commit);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.skipped[0].kind, OperationKind::Push);
+    }
+
+    #[test]
+    fn test_undo_hard_rollback() {
+        let (temp, store) = repo();
+        let first = store.head_commit().unwrap();
+        fs::write(temp.path().join("a.txt"), "two").unwrap();
+        fs::write(temp.path().join("b.txt"), "new").unwrap();
+        store.stage_file("a.txt").unwrap();
+        store.stage_file("b.txt").unwrap();
+        let second = store.commit("second", author()).unwrap();
+
+        let pending = store.begin_operation(OperationKind::Rollback, "1 commit", true).unwrap();
+        assert_eq!(store.rollback(1, true).unwrap(), first);
+        pending.finish(&store).unwrap();
+        assert_eq!(fs::read_to_string(temp.path().join("a.txt")).unwrap(), "one");
+        assert!(!temp.path().join("b.txt").exists());
+        assert!(store.rollback(1, false).is_err());
+
+        store.undo_operation().unwrap();
+        assert_eq!(store.head_commit().as_deref(), Some(second.id.as_str()));
+        assert_eq!(fs::read_to_string(temp.path().join("a.txt")).unwrap(), "two");
+        assert_eq!(fs::read_to_string(temp.path().join("b.txt")).unwrap(), "new");
+    }
+
+    #[test]
+    fn test_undo_restores_only_touched_files_and_refuses_after_edits() {
+        let (temp, store) = repo();
+        fs::write(temp.path().join("a.txt"), "dirty").unwrap();
+        fs::write(temp.path().join("notes.txt"), "untracked").unwrap();
+        let pending = store.begin_operation(OperationKind::Reset, "--hard", true).unwrap();
+        store.reset(&[], true).unwrap();
+        let record = pending.finish(&store).unwrap().unwrap();
+        assert_eq!(record.files.iter().map(|f| f.path.as_str()).collect::<Vec<_>>(), ["a.txt"]);
+        assert_eq!(fs::read_to_string(temp.path().join("a.txt")).unwrap(), "one");
+
+        store.undo_operation().unwrap();
+        assert_eq!(fs::read_to_string(temp.path().join("a.txt")).unwrap(), "dirty");
+        assert_eq!(fs::read_to_string(temp.path().join("notes.txt")).unwrap(), "untracked");
+
+        store.redo_operation().unwrap();
+        fs::write(temp.path().join("a.txt"), "edited after").unwrap();
+        let err = store.undo_operation().unwrap_err().to_string();
+        assert!(err.contains("a.txt changed since"), "{}", err);
+        assert_eq!(fs::read_to_string(temp.path().join("a.txt")).unwrap(), "edited after");
+    }
+}
@@ -0,0 +1,467 @@
+//! Packed objects, and what happens when a pack goes bad
+//!
+//! `Store::repack` moves loose objects into one zstd pack under
+//! `objects/pack`, written next to a JSON index that records each entry's
+//! content hash. `read_blob` looks in the packs before the loose files and,
+//! with `core.verify_packs` on (the default), checks every entry it unpacks.
+//! An entry that fails the check is logged to `objects/pack/corruption.log`
+//! and the read goes on to the next copy of the object, in another pack or
+//! loose; only when there is none does it fail with
+//! `StoreError::CorruptObject`.
+//!
+//! `repack` refuses to drop corrupt entries unless asked to repair. A
+//! repair packs a healthy copy in their place where one exists and moves the
+//! damaged pack to `objects/pack/quarantine` instead of deleting it, so an
+//! object with no other copy is never lost to a rebuild.
+
+use crate::{dedup::filed_id, write_atomic, Store, StoreError};
+use anyhow::Result;
+use rune_pack::{decode_entry, CorruptEntry, PackEntry, PackIndex, PackOptions, PackWriter};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Damaged packs are moved here by `repack --repair`
+const QUARANTINE_DIR: &str = "quarantine";
+
+/// Every pack entry that failed a check on read
+const CORRUPTION_LOG: &str = "corruption.log";
+
+/// What `Store::verify_packs` found in one pack
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackCheck {
+    pub pack: PathBuf,
+    pub entries: usize,
+    pub corrupt: Vec<CorruptEntry>,
+}
+
+/// What `Store::repack` did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepackReport {
+    /// The pack written, when there was anything to pack
+    pub pack: Option<PathBuf>,
+    /// Objects in it
+    pub objects: usize,
+    /// Loose files removed now that the pack holds them
+    pub loose_removed: usize,
+    /// Corrupt entries replaced by a healthy copy from elsewhere
+    pub repaired: Vec<String>,
+    /// Corrupt entries with no healthy copy, left in the quarantined pack
+    pub unrecoverable: Vec<String>,
+    /// Damaged packs moved to `objects/pack/quarantine`
+    pub quarantined: Vec<PathBuf>,
+}
+
+/// Pack indexes by pack file. Packs are named after their checksum and
+/// never rewritten, so an index stays valid as long as its pack exists.
+fn index_cache() -> &'static Mutex<HashMap<PathBuf, Arc<PackIndex>>> {
+    static CACHE: OnceLock<Mutex<HashMap<PathBuf, Arc<PackIndex>>>> = OnceLock::new();
+    CACHE.get_or_init(Default::default)
+}
+
+fn is_not_found(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<std::io::Error>().is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound)
+}
+
+impl Store {
+    pub fn pack_dir(&self) -> PathBuf {
+        self.rune_dir.join("objects").join("pack")
+    }
+
+    /// Pack files in use, in name order; quarantined ones are left out
+    pub fn packs(&self) -> Result<Vec<PathBuf>> {
+        let Ok(entries) = fs::read_dir(self.pack_dir()) else {
+            return Ok(Vec::new());
+        };
+        let mut packs: Vec<PathBuf> = entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "pack"))
+            .collect();
+        packs.sort();
+        Ok(packs)
+    }
+
+    fn pack_index(&self, pack: &Path) -> Result<Arc<PackIndex>> {
+        if let Some(index) = index_cache().lock().unwrap_or_else(|e| e.into_inner()).get(pack) {
+            return Ok(index.clone());
+        }
+        let index: Arc<PackIndex> = Arc::new(serde_json::from_slice(&fs::read(pack.with_extension("idx"))?)?);
+        index_cache().lock().unwrap_or_else(|e| e.into_inner()).insert(pack.to_path_buf(), index.clone());
+        Ok(index)
+    }
+
+    /// Content of `id` from the first pack holding a copy that checks out.
+    /// `Ok(None)` when no pack has it; a `CorruptObject` error when every
+    /// packed copy is damaged.
+    pub(crate) fn read_packed(&self, id: &str) -> Result<Option<Vec<u8>>> {
+        let mut damaged = None;
+        let mut verify_setting = None;
+        for pack in self.packs()? {
+            // A repack that finished since the listing has removed the pack
+            let index = match self.pack_index(&pack) {
+                Err(e) if is_not_found(&e) => continue,
+                index => index?,
+            };
+            let Some(entry) = index.find_entry(id) else {
+                continue;
+            };
+            let verify = *verify_setting.get_or_insert_with(|| self.config().core.verify_packs);
+            let mut file = match fs::File::open(&pack) {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                file => file?,
+            };
+            file.seek(SeekFrom::Start(entry.offset))?;
+            let mut stored = Vec::with_capacity(entry.size as usize);
+            file.take(entry.size).read_to_end(&mut stored)?;
+            let unpacked = if stored.len() as u64 == entry.size {
+                decode_entry(&stored, entry, verify)
+            } else {
+                Err(CorruptEntry {
+                    path: entry.path.clone(),
+                    offset: entry.offset,
+                    reason: "cut off by the end of the pack".into(),
+                })
+            };
+            match unpacked {
+                Ok(data) => return Ok(Some(data)),
+                Err(corrupt) => {
+                    self.record_corruption(&pack, &corrupt)?;
+                    damaged.get_or_insert(StoreError::CorruptObject {
+                        id: id.to_string(),
+                        pack: pack.clone(),
+                        offset: corrupt.offset,
+                    });
+                }
+            }
+        }
+        match damaged {
+            Some(err) => Err(err.into()),
+            None => Ok(None),
+        }
+    }
+
+    fn record_corruption(&self, pack: &Path, corrupt: &CorruptEntry) -> Result<()> {
+        let mut log = fs::OpenOptions::new().create(true).append(true).open(self.pack_dir().join(CORRUPTION_LOG))?;
+        let name = pack.file_name().unwrap_or_default().to_string_lossy();
+        writeln!(log, "{} {} {}", chrono::Utc::now().to_rfc3339(), name, corrupt)?;
+        Ok(())
+    }
+
+    /// Every entry read back and checked, one pack at a time
+    pub fn verify_packs(&self) -> Result<Vec<PackCheck>> {
+        let mut checks = Vec::new();
+        for pack in self.packs()? {
+            let index = self.pack_index(&pack)?;
+            let corrupt = index.verify_entries(&mut std::io::BufReader::new(fs::File::open(&pack)?))?;
+            checks.push(PackCheck { pack, entries: index.entries.len(), corrupt });
+        }
+        Ok(checks)
+    }
+
+    /// Loose objects whose content no longer hashes to their id
+    pub fn verify_loose_objects(&self) -> Result<Vec<String>> {
+        let mut damaged = Vec::new();
+        for id in self.loose_ids()? {
+            if blake3::hash(&fs::read(self.blob_path(&id))?).to_hex().as_str() != id {
+                damaged.push(id);
+            }
+        }
+        Ok(damaged)
+    }
+
+    /// Loose object files, counted without reading them
+    pub fn loose_object_count(&self) -> Result<usize> {
+        let objects = self.rune_dir.join("objects");
+        let mut count = 0;
+        for entry in walkdir::WalkDir::new(&objects).min_depth(2).max_depth(2) {
+            let entry = entry?;
+            if entry.file_type().is_file() && filed_id(&objects, entry.path()).is_some() {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Bytes on disk of the objects in `ids`: the loose file where there is
+    /// one, otherwise the first pack entry holding the object
+    pub(crate) fn stored_size(&self, ids: &BTreeSet<String>) -> Result<u64> {
+        let mut indexes = Vec::new();
+        for pack in self.packs()? {
+            match self.pack_index(&pack) {
+                Err(e) if is_not_found(&e) => continue,
+                index => indexes.push(index?),
+            }
+        }
+        let mut size = 0;
+        for id in ids {
+            size += match fs::metadata(self.blob_path(id)) {
+                Ok(meta) => meta.len(),
+                Err(_) => indexes.iter().find_map(|index| index.find_entry(id)).map_or(0, |entry| entry.size),
+            };
+        }
+        Ok(size)
+    }
+
+    /// Ids of every loose object file, in order
+    fn loose_ids(&self) -> Result<Vec<String>> {
+        let objects = self.rune_dir.join("objects");
+        let mut loose = Vec::new();
+        for entry in walkdir::WalkDir::new(&objects).min_depth(2).max_depth(2).sort_by_file_name() {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            if let Some(id) = filed_id(&objects, entry.path()) {
+                loose.push(id);
+            }
+        }
+        Ok(loose)
+    }
+
+    /// Put every object, loose or packed, into one new pack and remove what
+    /// it replaces. Corrupt pack entries stop the repack unless `repair` is
+    /// set; see the module docs. Objects are checked and then copied into
+    /// the new pack one at a time, so only their ids are held in memory.
+    pub fn repack(&self, repair: bool) -> Result<RepackReport> {
+        let mut report = RepackReport::default();
+        let mut objects: BTreeMap<String, Source> = BTreeMap::new();
+        let mut corrupt: Vec<(PathBuf, CorruptEntry)> = Vec::new();
+        let packs = self.packs()?;
+        let mut indexes = Vec::with_capacity(packs.len());
+        let mut readers = Vec::with_capacity(packs.len());
+        for (n, pack) in packs.iter().enumerate() {
+            let index = self.pack_index(pack)?;
+            let mut reader = BufReader::new(fs::File::open(pack)?);
+            for (i, entry) in index.entries.iter().enumerate() {
+                match unpack_entry(&mut reader, entry)? {
+                    Ok(_) => {
+                        objects.entry(entry.path.clone()).or_insert(Source::Packed { pack: n, entry: i });
+                    }
+                    Err(e) => corrupt.push((pack.clone(), e)),
+                }
+            }
+            indexes.push(index);
+            readers.push(reader);
+        }
+        let mut loose_files = Vec::new();
+        for id in self.loose_ids()? {
+            let path = self.blob_path(&id);
+            // A damaged loose file is left where it is, for `rune verify` to report
+            if blake3::hash(&fs::read(&path)?).to_hex().as_str() == id {
+                loose_files.push(path);
+                objects.entry(id).or_insert(Source::Loose);
+            }
+        }
+        if let Some((pack, entry)) = corrupt.first().filter(|_| !repair) {
+            anyhow::bail!(
+                "{} corrupt pack entries, the first {} in {}; run `rune maintenance repack --repair`",
+                corrupt.len(),
+                entry.path,
+                pack.display()
+            );
+        }
+        for (_, entry) in &corrupt {
+            if objects.contains_key(&entry.path) {
+                report.repaired.push(entry.path.clone());
+            } else {
+                report.unrecoverable.push(entry.path.clone());
+            }
+        }
+
+        let pack_dir = self.pack_dir();
+        fs::create_dir_all(&pack_dir)?;
+        if !objects.is_empty() {
+            report.objects = objects.len();
+            // Not named `.pack` until complete, so reads never list it half written
+            let temp = pack_dir.join(format!("repack.{}.lock", std::process::id()));
+            let written = (|| -> Result<PackIndex> {
+                let mut writer = PackWriter::new(BufWriter::new(fs::File::create(&temp)?), PackOptions::default());
+                for (id, source) in objects {
+                    let content = match source {
+                        Source::Loose => fs::read(self.blob_path(&id))?,
+                        Source::Packed { pack, entry } => unpack_entry(&mut readers[pack], &indexes[pack].entries[entry])??,
+                    };
+                    writer.add(id, content)?;
+                }
+                let (out, index) = writer.finish()?;
+                out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+                Ok(index)
+            })();
+            let index = match written {
+                Ok(index) => index,
+                Err(e) => {
+                    let _ = fs::remove_file(&temp);
+                    return Err(e);
+                }
+            };
+            let pack = pack_dir.join(format!("pack-{}.pack", &index.checksum[..16]));
+            // Index first: a pack without one is ignored, an index without its pack is never listed
+            write_atomic(&pack.with_extension("idx"), &serde_json::to_vec(&index)?)?;
+            fs::rename(&temp, &pack)?;
+            report.pack = Some(pack);
+        }
+        drop(readers);
+
+        let damaged: Vec<&PathBuf> = packs.iter().filter(|p| corrupt.iter().any(|(c, _)| c == *p)).collect();
+        for old in packs.iter().filter(|p| Some(*p) != report.pack.as_ref()) {
+            index_cache().lock().unwrap_or_else(|e| e.into_inner()).remove(old);
+            if damaged.contains(&old) {
+                let quarantine = pack_dir.join(QUARANTINE_DIR);
+                fs::create_dir_all(&quarantine)?;
+                let file_name = old.file_name().expect("pack files have a name");
+                fs::rename(old.with_extension("idx"), quarantine.join(file_name).with_extension("idx"))?;
+                fs::rename(old, quarantine.join(file_name))?;
+                report.quarantined.push(quarantine.join(file_name));
+            } else {
+                fs::remove_file(old)?;
+                fs::remove_file(old.with_extension("idx"))?;
+            }
+        }
+        if report.pack.is_some() {
+            for file in loose_files {
+                fs::remove_file(&file)?;
+                report.loose_removed += 1;
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// Where `repack` found the healthy copy of an object it keeps
+enum Source {
+    Packed { pack: usize, entry: usize },
+    Loose,
+}
+
+/// Content of one entry read from its pack, or why it is corrupt
+fn unpack_entry(pack: &mut (impl Read + Seek), entry: &PackEntry) -> Result<std::result::Result<Vec<u8>, CorruptEntry>> {
+    pack.seek(SeekFrom::Start(entry.offset))?;
+    let mut stored = Vec::with_capacity(entry.size as usize);
+    pack.take(entry.size).read_to_end(&mut stored)?;
+    if stored.len() as u64 != entry.size {
+        return Ok(Err(CorruptEntry {
+            path: entry.path.clone(),
+            offset: entry.offset,
+            reason: "cut off by the end of the pack".into(),
+        }));
+    }
+    Ok(decode_entry(&stored, entry, true))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Store, StoreError};
+    use crate::test_support::temp_repo;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// A repository with three blobs repacked into one pack, and the id of
+    /// the one whose bytes `flip` damages
+    fn packed(temp: &TempDir) -> (Store, Vec<String>) {
+        let store = Store::open(temp.path()).unwrap();
+        store.create().unwrap();
+        let ids = ["first blob\n", "second blob\n", &"third, long enough to compress\n".repeat(20)]
+            .iter()
+            .map(|content| store.write_blob(content.as_bytes()).unwrap())
+            .collect();
+        let report = store.repack(false).unwrap();
+        assert_eq!((report.objects, report.loose_removed), (3, 3));
+        (store, ids)
+    }
+
+    /// Flip one byte in the middle of `id`'s entry
+    fn flip(store: &Store, id: &str) {
+        let pack = &store.packs().unwrap()[0];
+        let index = store.pack_index(pack).unwrap();
+        let entry = index.find_entry(id).unwrap();
+        let mut data = fs::read(pack).unwrap();
+        data[(entry.offset + entry.size / 2) as usize] ^= 0x01;
+        fs::write(pack, data).unwrap();
+    }
+
+    #[test]
+    fn test_corrupt_entry_falls_back_to_loose_copy() {
+        let temp = TempDir::new().unwrap();
+        let (store, ids) = packed(&temp);
+        assert_eq!(store.read_blob(&ids[0]).unwrap(), b"first blob\n");
+        flip(&store, &ids[0]);
+
+        let err = store.read_blob(&ids[0]).unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(StoreError::CorruptObject { id, .. }) if id == &ids[0]));
+        let log = fs::read_to_string(store.pack_dir().join("corruption.log")).unwrap();
+        assert!(log.contains(&format!("pack entry {} at offset", ids[0])), "{}", log);
+
+        // The object turns up loose again, e.g. re-added from the working tree
+        store.write_blob(b"first blob\n").unwrap();
+        assert_eq!(store.read_blob(&ids[0]).unwrap(), b"first blob\n");
+        assert_eq!(store.read_blob(&ids[1]).unwrap(), b"second blob\n");
+
+        // With checks off, the damaged bytes come back as they are
+        let mut cfg = store.config();
+        cfg.core.verify_packs = false;
+        store.write_config(&cfg).unwrap();
+        assert_ne!(store.read_blob(&ids[0]).unwrap(), b"first blob\n");
+    }
+
+    #[test]
+    fn test_reads_during_repack_find_every_object() {
+        let (temp, store) = temp_repo();
+        let contents: Vec<String> = (0..40).map(|n| format!("object {}\n", n)).collect();
+        let ids: Vec<String> = contents.iter().map(|c| store.write_blob(c.as_bytes()).unwrap()).collect();
+
+        let done = std::sync::atomic::AtomicBool::new(false);
+        std::thread::scope(|scope| {
+            let reader = scope.spawn(|| {
+                let store = Store::open(temp.path()).unwrap();
+                let mut reads = 0;
+                while !done.load(std::sync::atomic::Ordering::Relaxed) || reads == 0 {
+                    for (id, content) in ids.iter().zip(&contents) {
+                        assert_eq!(store.read_blob(id).unwrap(), content.as_bytes());
+                        reads += 1;
+                    }
+                }
+            });
+            for n in 0..20 {
+                store.write_blob(format!("extra {}\n", n).as_bytes()).unwrap();
+                store.repack(false).unwrap();
+            }
+            done.store(true, std::sync::atomic::Ordering::Relaxed);
+            reader.join().unwrap();
+        });
+        assert_eq!(store.loose_object_count().unwrap(), 0);
+        assert_eq!(store.packs().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_verify_names_the_bad_entry_and_repair_rebuilds() {
+        let temp = TempDir::new().unwrap();
+        let (store, ids) = packed(&temp);
+        assert!(store.verify_packs().unwrap().iter().all(|check| check.corrupt.is_empty()));
+        flip(&store, &ids[2]);
+        flip(&store, &ids[1]);
+
+        let checks = store.verify_packs().unwrap();
+        assert_eq!(checks.len(), 1);
+        let bad: Vec<&str> = checks[0].corrupt.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(bad.len(), 2);
+        assert!(bad.contains(&ids[1].as_str()) && bad.contains(&ids[2].as_str()));
+
+        // Only one of them has a healthy copy elsewhere
+        store.write_blob(&"third, long enough to compress\n".repeat(20).into_bytes()).unwrap();
+        assert!(store.repack(false).unwrap_err().to_string().contains("--repair"));
+        let report = store.repack(true).unwrap();
+        assert_eq!(report.repaired, [ids[2].clone()]);
+        assert_eq!(report.unrecoverable, [ids[1].clone()]);
+        assert_eq!(report.quarantined.len(), 1);
+        assert!(report.quarantined[0].exists());
+
+        let checks = store.verify_packs().unwrap();
+        assert_eq!(checks.len(), 1);
+        assert_eq!((checks[0].entries, checks[0].corrupt.len()), (2, 0));
+        assert_eq!(store.read_blob(&ids[2]).unwrap(), "third, long enough to compress\n".repeat(20).as_bytes());
+        assert_eq!(store.read_blob(&ids[0]).unwrap(), b"first blob\n");
+    }
+}
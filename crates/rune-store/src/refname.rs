@@ -0,0 +1,263 @@
+//! Which names may become refs
+//!
+//! Ref names turn into paths under `.rune`, so a name is checked before
+//! anything is written: the rules of `git check-ref-format`, plus what
+//! Windows can't store (reserved device names, `<>"|`, components ending in
+//! a dot), applied on every platform so a repository stays portable.
+//! Reading is more forgiving: refs written before these checks still load,
+//! and `Store::invalid_refs` lists them so callers can warn.
+
+use crate::error::StoreError;
+use crate::refs::is_loose_ref;
+use crate::Store;
+use anyhow::Result;
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
+
+/// The rule a ref name breaks
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum RefNameRule {
+    #[error("it is empty")]
+    Empty,
+    #[error("it starts with '/'")]
+    LeadingSlash,
+    #[error("it ends with '/'")]
+    TrailingSlash,
+    #[error("it contains an empty component ('//')")]
+    EmptyComponent,
+    #[error("it contains '..'")]
+    DoubleDot,
+    #[error("a component starts with '.'")]
+    LeadingDot,
+    #[error("a component ends with '.'")]
+    TrailingDot,
+    #[error("a component ends with '.lock'")]
+    LockSuffix,
+    #[error("it contains '@{{'")]
+    AtBrace,
+    #[error("it is '@'")]
+    LoneAt,
+    #[error("it contains a backslash")]
+    Backslash,
+    #[error("it contains a control character")]
+    ControlChar,
+    #[error("it contains '{0}'")]
+    ForbiddenChar(char),
+    #[error("'{0}' is a reserved file name on Windows")]
+    ReservedName(String),
+}
+
+/// Characters git refuses, plus the ones Windows can't have in a file name
+const FORBIDDEN: &[char] = &[' ', '~', '^', ':', '?', '*', '[', '<', '>', '"', '|'];
+
+const RESERVED: &[&str] = &["con", "prn", "aux", "nul"];
+
+/// Check `name`, a full ref (`refs/heads/main`) or a branch or tag name,
+/// against the ref naming rules
+pub fn check_ref_format(name: &str) -> Result<(), RefNameRule> {
+    if name.is_empty() {
+        return Err(RefNameRule::Empty);
+    }
+    if name == "@" {
+        return Err(RefNameRule::LoneAt);
+    }
+    if name.contains("..") {
+        return Err(RefNameRule::DoubleDot);
+    }
+    if name.contains("@{") {
+        return Err(RefNameRule::AtBrace);
+    }
+    if name.contains('\\') {
+        return Err(RefNameRule::Backslash);
+    }
+    if name.chars().any(|c| c.is_ascii_control()) {
+        return Err(RefNameRule::ControlChar);
+    }
+    if let Some(c) = name.chars().find(|c| FORBIDDEN.contains(c)) {
+        return Err(RefNameRule::ForbiddenChar(c));
+    }
+    if name.starts_with('/') {
+        return Err(RefNameRule::LeadingSlash);
+    }
+    if name.ends_with('/') {
+        return Err(RefNameRule::TrailingSlash);
+    }
+    for component in name.split('/') {
+        check_component(component)?;
+    }
+    Ok(())
+}
+
+fn check_component(component: &str) -> Result<(), RefNameRule> {
+    if component.is_empty() {
+        return Err(RefNameRule::EmptyComponent);
+    }
+    if component.starts_with('.') {
+        return Err(RefNameRule::LeadingDot);
+    }
+    if component.ends_with(".lock") {
+        return Err(RefNameRule::LockSuffix);
+    }
+    if component.ends_with('.') {
+        return Err(RefNameRule::TrailingDot);
+    }
+    // Windows reserves the device names with any extension: `con`, `aux.txt`, `COM1`
+    let stem = component.split('.').next().unwrap_or(component).to_ascii_lowercase();
+    let numbered = ["com", "lpt"]
+        .iter()
+        .any(|p| stem.strip_prefix(p).is_some_and(|n| n.len() == 1 && n.as_bytes()[0].is_ascii_digit() && n != "0"));
+    if RESERVED.contains(&stem.as_str()) || numbered {
+        return Err(RefNameRule::ReservedName(component.to_string()));
+    }
+    Ok(())
+}
+
+/// `check_ref_format` as the store's error, naming the offending ref
+pub(crate) fn validate_ref_name(name: &str) -> Result<(), StoreError> {
+    check_ref_format(name).map_err(|rule| StoreError::InvalidRefName { name: name.to_string(), rule })
+}
+
+impl Store {
+    /// Where ref `r` lives, for reading or removing refs that may predate the
+    /// naming rules. `None` when the name would leave the refs area.
+    pub(crate) fn ref_file(&self, r: &str) -> Option<PathBuf> {
+        let path = Path::new(r);
+        let inside = !r.is_empty()
+            && !r.contains('\\')
+            && path.components().all(|c| matches!(c, Component::Normal(_)));
+        inside.then(|| self.rune_dir.join(path))
+    }
+
+    /// Branches and tags on disk whose names break the rules, with the rule
+    /// each breaks. They can still be read, checked out and deleted.
+    pub fn invalid_refs(&self) -> Result<Vec<(String, RefNameRule)>> {
+        let mut invalid = Vec::new();
+        for dir in ["refs/heads", "refs/tags"] {
+            let base = self.rune_dir.join(dir);
+            if !base.exists() {
+                continue;
+            }
+            for entry in walkdir::WalkDir::new(&base).sort_by_file_name() {
+                let entry = entry?;
+                if !is_loose_ref(&entry) {
+                    continue;
+                }
+                let name = entry.path().strip_prefix(&self.rune_dir)?.to_string_lossy().replace('\\', "/");
+                if let Err(rule) = check_ref_format(&name) {
+                    invalid.push((name, rule));
+                }
+            }
+        }
+        Ok(invalid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_repo;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_rule_table() {
+        let valid = ["main", "feature/login", "refs/heads/release-1.2", "v1.0.0", "user@host", "a.b", "console", "com10"];
+        for name in valid {
+            assert_eq!(check_ref_format(name), Ok(()), "{}", name);
+        }
+
+        let cases: &[(&str, RefNameRule)] = &[
+            ("", RefNameRule::Empty),
+            ("@", RefNameRule::LoneAt),
+            ("../../../etc/passwd", RefNameRule::DoubleDot),
+            ("a..b", RefNameRule::DoubleDot),
+            ("main@{1}", RefNameRule::AtBrace),
+            ("feature\\x", RefNameRule::Backslash),
+            ("bad\u{7}bell", RefNameRule::ControlChar),
+            ("tab\there", RefNameRule::ControlChar),
+            ("has space", RefNameRule::ForbiddenChar(' ')),
+            ("HEAD~1", RefNameRule::ForbiddenChar('~')),
+            ("a^b", RefNameRule::ForbiddenChar('^')),
+            ("a:b", RefNameRule::ForbiddenChar(':')),
+            ("what?", RefNameRule::ForbiddenChar('?')),
+            ("glob*", RefNameRule::ForbiddenChar('*')),
+            ("x[1]", RefNameRule::ForbiddenChar('[')),
+            ("a<b", RefNameRule::ForbiddenChar('<')),
+            ("pipe|d", RefNameRule::ForbiddenChar('|')),
+            ("/main", RefNameRule::LeadingSlash),
+            ("main/", RefNameRule::TrailingSlash),
+            ("a//b", RefNameRule::EmptyComponent),
+            (".hidden", RefNameRule::LeadingDot),
+            ("feature/.x", RefNameRule::LeadingDot),
+            ("main.lock", RefNameRule::LockSuffix),
+            ("refs/heads/x.lock/y", RefNameRule::LockSuffix),
+            ("release.", RefNameRule::TrailingDot),
+            ("dir./x", RefNameRule::TrailingDot),
+        ];
+        for (name, rule) in cases {
+            assert_eq!(check_ref_format(name).as_ref(), Err(rule), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn test_windows_reserved_names_rejected_everywhere() {
+        for name in ["con", "CON", "aux", "Nul", "prn", "com1", "LPT9", "feature/aux", "nul.txt", "com3.branch"] {
+            assert!(
+                matches!(check_ref_format(name), Err(RefNameRule::ReservedName(_))),
+                "{} should be reserved",
+                name
+            );
+        }
+        for name in ["com0", "lpt", "auxiliary", "connect", "nullable"] {
+            assert_eq!(check_ref_format(name), Ok(()), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_traversal_rejected_before_touching_disk() {
+        let temp = TempDir::new().unwrap();
+        let store = Store::open(temp.path().join("repo")).unwrap();
+        store.create().unwrap();
+        let escape = "../../../escaped";
+
+        let err = store.create_branch(escape).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<StoreError>(),
+            Some(StoreError::InvalidRefName { rule: RefNameRule::DoubleDot, .. })
+        ));
+        assert!(store.write_ref(&format!("refs/heads/{}", escape), "abc").is_err());
+        assert!(store.create_lightweight_tag("../outside", "abc").is_err());
+        assert!(store.create_annotated_tag("a//b", "abc", "msg").is_err());
+        assert!(!temp.path().join("escaped").exists());
+        assert!(!temp.path().join("repo/.rune/refs/outside").exists());
+        assert!(store.read_ref("../../../escaped").is_none());
+    }
+
+    #[test]
+    fn test_rename_validates_new_name() {
+        let (_temp, store) = temp_repo();
+        store.create_branch("topic").unwrap();
+        let err = store.rename_branch("topic", "topic.lock").unwrap_err();
+        assert!(err.to_string().contains("'topic.lock' is not a valid ref name: a component ends with '.lock'"), "{}", err);
+        assert!(store.branch_exists("topic"));
+    }
+
+    #[test]
+    fn test_legacy_invalid_refs_still_readable() {
+        let (_temp, store) = temp_repo();
+        fs::write(store.rune_dir.join("refs/heads/has space"), "abc123").unwrap();
+        fs::write(store.rune_dir.join("refs/heads/ok"), "abc123").unwrap();
+
+        assert!(store.list_branches().unwrap().contains(&"has space".to_string()));
+        assert!(store.branch_exists("has space"));
+        assert_eq!(store.read_ref("refs/heads/has space").as_deref(), Some("abc123"));
+        assert_eq!(
+            store.invalid_refs().unwrap(),
+            [("refs/heads/has space".to_string(), RefNameRule::ForbiddenChar(' '))]
+        );
+        store.write_ref("refs/heads/has space", "def456").unwrap();
+        assert_eq!(store.read_ref("refs/heads/has space").as_deref(), Some("def456"));
+        store.delete_branch("has space").unwrap();
+        assert!(store.invalid_refs().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,462 @@
+//! Reading every ref at once.
+//!
+//! Reading refs one file at a time costs a round trip each on a network
+//! filesystem. `Store::refs_snapshot` reads `refs/heads`, `refs/tags` and
+//! `refs/remotes` in a single walk and keeps the result for the process,
+//! reusing it until a ref directory, `packed-refs` or `HEAD` changes.
+//!
+//! `packed-refs` holds tags and remote-tracking refs collapsed into one
+//! file by `Store::pack_refs` (run by `gc` maintenance), one `<id> <ref>`
+//! per line. A loose ref file always wins over a packed entry of the same
+//! name. Branches and annotated tags stay loose.
+
+use crate::{write_atomic, Store};
+use anyhow::Result;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+
+const PACKED_REFS: &str = "packed-refs";
+
+/// Changes closer than this to when a snapshot was read may have landed
+/// after the read within the same mtime tick, so such a snapshot isn't reused
+const RACY_WINDOW: Duration = Duration::from_secs(1);
+
+/// Every branch, tag and remote-tracking ref at one moment
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RefsSnapshot {
+    /// Branch name to tip; `None` for a branch without commits yet
+    pub branches: BTreeMap<String, Option<String>>,
+    /// Tag name to the commit it marks
+    pub tags: BTreeMap<String, String>,
+    /// `<remote>/<branch>` to its last known tip
+    pub remotes: BTreeMap<String, String>,
+    /// The ref HEAD points at, e.g. `refs/heads/main`
+    pub head_ref: String,
+    /// The commit HEAD resolves to
+    pub head: Option<String>,
+}
+
+impl RefsSnapshot {
+    /// Tip of branch `name`
+    pub fn branch(&self, name: &str) -> Option<&str> {
+        self.branches.get(name)?.as_deref()
+    }
+
+    /// Commit tag `name` marks
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.get(name).map(String::as_str)
+    }
+
+    /// Commit a full ref name such as `refs/tags/v1` points at
+    pub fn resolve(&self, r: &str) -> Option<&str> {
+        if let Some(name) = r.strip_prefix("refs/heads/") {
+            self.branch(name)
+        } else if let Some(name) = r.strip_prefix("refs/tags/") {
+            self.tag(name)
+        } else {
+            self.remotes.get(r.strip_prefix("refs/remotes/")?).map(String::as_str)
+        }
+    }
+
+    /// Every ref that points at something, as (full ref name, target),
+    /// sorted by name. Branches without commits yet have no target and are
+    /// left out.
+    pub fn refs(&self) -> Vec<(String, String)> {
+        let branches = self
+            .branches
+            .iter()
+            .filter_map(|(name, tip)| Some((format!("refs/heads/{}", name), tip.clone()?)));
+        let tags = self.tags.iter().map(|(name, id)| (format!("refs/tags/{}", name), id.clone()));
+        let remotes = self.remotes.iter().map(|(name, id)| (format!("refs/remotes/{}", name), id.clone()));
+        let mut refs: Vec<(String, String)> = branches.chain(tags).chain(remotes).collect();
+        refs.sort();
+        refs
+    }
+
+    /// `refs` under `prefix`, which matches whole path components:
+    /// `refs/remotes/origin` takes in `refs/remotes/origin/main` but not
+    /// `refs/remotes/origin2/main`. An empty prefix lists everything.
+    pub fn list(&self, prefix: &str) -> Vec<(String, String)> {
+        let prefix = prefix.trim_end_matches('/');
+        self.refs()
+            .into_iter()
+            .filter(|(r, _)| {
+                prefix.is_empty() || r == prefix || r.strip_prefix(prefix).is_some_and(|rest| rest.starts_with('/'))
+            })
+            .collect()
+    }
+
+    /// Ref labels per commit, as `git log --decorate` shows them:
+    /// `HEAD -> main`, `origin/main`, `tag: v1.0`
+    pub fn decorations(&self) -> HashMap<String, Vec<String>> {
+        let mut labels: HashMap<String, Vec<String>> = HashMap::new();
+        let head_branch = self.head_ref.strip_prefix("refs/heads/");
+        for (name, tip) in &self.branches {
+            if let Some(tip) = tip {
+                let label = if Some(name.as_str()) == head_branch { format!("HEAD -> {}", name) } else { name.clone() };
+                labels.entry(tip.clone()).or_default().push(label);
+            }
+        }
+        for (name, tip) in &self.remotes {
+            labels.entry(tip.clone()).or_default().push(name.clone());
+        }
+        for (name, tip) in &self.tags {
+            labels.entry(tip.clone()).or_default().push(format!("tag: {}", name));
+        }
+        labels
+    }
+}
+
+/// Modification times of everything a snapshot was read from
+type Signature = Vec<(PathBuf, Option<SystemTime>)>;
+
+struct Cached {
+    signature: Signature,
+    read_at: SystemTime,
+    snapshot: Arc<RefsSnapshot>,
+}
+
+fn cache() -> &'static Mutex<HashMap<PathBuf, Cached>> {
+    static CACHE: OnceLock<Mutex<HashMap<PathBuf, Cached>>> = OnceLock::new();
+    CACHE.get_or_init(Default::default)
+}
+
+fn mtime(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Whether a walked entry under `refs/` is a loose ref, not a directory or
+/// a `.lock` file a ref write is going through (or left behind by a crash)
+pub(crate) fn is_loose_ref(entry: &walkdir::DirEntry) -> bool {
+    entry.file_type().is_file() && !entry.file_name().to_string_lossy().ends_with(".lock")
+}
+
+/// First line of a loose ref file; annotated tags keep their message below it
+fn loose_value(path: &Path) -> Option<String> {
+    let content = fs::read_to_string(path).ok()?;
+    Some(content.lines().next().unwrap_or_default().trim().to_string())
+}
+
+impl Store {
+    /// All refs, read in one walk or reused from an earlier read in this
+    /// process if nothing has changed since
+    pub fn refs_snapshot(&self) -> Result<Arc<RefsSnapshot>> {
+        let signature = self.refs_signature();
+        let mut cache = cache().lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(cached) = cache.get(&self.rune_dir) {
+            let settled = signature
+                .iter()
+                .filter_map(|(_, t)| *t)
+                .all(|t| t + RACY_WINDOW <= cached.read_at);
+            if cached.signature == signature && settled {
+                return Ok(cached.snapshot.clone());
+            }
+        }
+
+        let read_at = SystemTime::now();
+        let snapshot = Arc::new(self.read_refs()?);
+        cache.insert(
+            self.rune_dir.clone(),
+            Cached { signature, read_at, snapshot: snapshot.clone() },
+        );
+        Ok(snapshot)
+    }
+
+    /// (ref name, target) for every ref under `prefix`, e.g. `refs/heads`,
+    /// `refs/tags` or `refs/remotes/origin`; see `RefsSnapshot::list`
+    pub fn list_refs(&self, prefix: &str) -> Result<Vec<(String, String)>> {
+        Ok(self.refs_snapshot()?.list(prefix))
+    }
+
+    /// Drop the cached snapshot after changing a ref
+    pub(crate) fn invalidate_refs(&self) {
+        cache().lock().unwrap_or_else(|e| e.into_inner()).remove(&self.rune_dir);
+    }
+
+    fn refs_signature(&self) -> Signature {
+        let mut signature = vec![
+            (self.rune_dir.join("HEAD"), mtime(&self.rune_dir.join("HEAD"))),
+            (self.rune_dir.join(PACKED_REFS), mtime(&self.rune_dir.join(PACKED_REFS))),
+        ];
+        for entry in walkdir::WalkDir::new(self.rune_dir.join("refs")).into_iter().flatten() {
+            if entry.file_type().is_dir() {
+                signature.push((entry.path().to_path_buf(), mtime(entry.path())));
+            }
+        }
+        signature
+    }
+
+    fn read_refs(&self) -> Result<RefsSnapshot> {
+        let mut snapshot = RefsSnapshot { head_ref: self.head_ref(), ..Default::default() };
+        for (r, id) in self.packed_refs()? {
+            snapshot.insert(&r, Some(id));
+        }
+
+        let refs_dir = self.rune_dir.join("refs");
+        for entry in walkdir::WalkDir::new(&refs_dir) {
+            let entry = entry?;
+            if !is_loose_ref(&entry) {
+                continue;
+            }
+            let Ok(rel) = entry.path().strip_prefix(&self.rune_dir) else {
+                continue;
+            };
+            let r = rel.to_string_lossy().replace('\\', "/");
+            if let Some(value) = loose_value(entry.path()) {
+                snapshot.insert(&r, Some(value).filter(|v| !v.is_empty()));
+            }
+        }
+
+        snapshot.head = snapshot.resolve(&snapshot.head_ref).map(str::to_string);
+        Ok(snapshot)
+    }
+
+    /// Entries of `packed-refs`, ref name to id
+    pub(crate) fn packed_refs(&self) -> Result<BTreeMap<String, String>> {
+        let path = self.rune_dir.join(PACKED_REFS);
+        if !path.exists() {
+            return Ok(BTreeMap::new());
+        }
+        Ok(fs::read_to_string(path)?
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| line.split_once(' '))
+            .map(|(id, r)| (r.trim().to_string(), id.to_string()))
+            .collect())
+    }
+
+    /// Id `r` has in `packed-refs`
+    pub(crate) fn packed_ref(&self, r: &str) -> Option<String> {
+        self.packed_refs().ok()?.remove(r)
+    }
+
+    /// Drop `r` from `packed-refs`; returns whether it was there
+    pub(crate) fn remove_packed_ref(&self, r: &str) -> Result<bool> {
+        let mut packed = self.packed_refs()?;
+        if packed.remove(r).is_none() {
+            return Ok(false);
+        }
+        self.write_packed_refs(&packed)?;
+        Ok(true)
+    }
+
+    fn write_packed_refs(&self, packed: &BTreeMap<String, String>) -> Result<()> {
+        let mut content = String::from("# pack-refs\n");
+        for (r, id) in packed {
+            content.push_str(&format!("{} {}\n", id, r));
+        }
+        write_atomic(&self.rune_dir.join(PACKED_REFS), content.as_bytes())?;
+        self.invalidate_refs();
+        Ok(())
+    }
+
+    /// Move loose tags and remote-tracking refs into `packed-refs`.
+    /// Branches and annotated tags stay loose. Returns how many refs were packed.
+    pub fn pack_refs(&self) -> Result<usize> {
+        let mut packed = self.packed_refs()?;
+        let mut loose = Vec::new();
+        for dir in ["refs/tags", "refs/remotes"] {
+            let base = self.rune_dir.join(dir);
+            if !base.is_dir() {
+                continue;
+            }
+            for entry in walkdir::WalkDir::new(&base) {
+                let entry = entry?;
+                if !is_loose_ref(&entry) {
+                    continue;
+                }
+                let content = fs::read_to_string(entry.path())?;
+                let mut lines = content.lines();
+                let id = lines.next().unwrap_or_default().trim();
+                if id.is_empty() || lines.next().is_some() {
+                    continue;
+                }
+                let Ok(rel) = entry.path().strip_prefix(&self.rune_dir) else {
+                    continue;
+                };
+                packed.insert(rel.to_string_lossy().replace('\\', "/"), id.to_string());
+                loose.push(entry.path().to_path_buf());
+            }
+        }
+        if loose.is_empty() {
+            return Ok(0);
+        }
+
+        // The packed file has to be in place before a loose ref disappears
+        self.write_packed_refs(&packed)?;
+        for path in &loose {
+            fs::remove_file(path)?;
+            remove_empty_parents(path, &self.rune_dir.join("refs"));
+        }
+        self.invalidate_refs();
+        Ok(loose.len())
+    }
+}
+
+impl RefsSnapshot {
+    fn insert(&mut self, r: &str, id: Option<String>) {
+        if let Some(name) = r.strip_prefix("refs/heads/") {
+            self.branches.insert(name.to_string(), id);
+        } else if let Some(id) = id {
+            if let Some(name) = r.strip_prefix("refs/tags/") {
+                self.tags.insert(name.to_string(), id);
+            } else if let Some(name) = r.strip_prefix("refs/remotes/") {
+                self.remotes.insert(name.to_string(), id);
+            }
+        }
+    }
+}
+
+/// Remove the directories left empty between `removed` and `stop`
+pub(crate) fn remove_empty_parents(removed: &Path, stop: &Path) {
+    let mut dir = removed.parent();
+    while let Some(d) = dir {
+        if d == stop || fs::remove_dir(d).is_err() {
+            break;
+        }
+        dir = d.parent();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Store;
+    use crate::test_support::temp_repo;
+    use rune_core::Author;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn repo() -> (TempDir, Store, String) {
+        let (temp, store) = temp_repo();
+        fs::write(temp.path().join("a.txt"), "a").unwrap();
+        store.stage_file("a.txt").unwrap();
+        let commit = store
+            .commit("First", Author { name: "Dev".into(), email: "dev@example.com".into() })
+            .unwrap();
+        (temp, store, commit.id)
+    }
+
+    #[test]
+    fn test_snapshot_matches_per_file_reads() {
+        let (_temp, store, id) = repo();
+        store.create_branch("feature/x").unwrap();
+        store.create_lightweight_tag("v1", &id).unwrap();
+        store.create_annotated_tag("v1-notes", &id, "Release notes").unwrap();
+        store.write_ref("refs/remotes/origin/main", &id).unwrap();
+
+        let snapshot = store.refs_snapshot().unwrap();
+        let mut branches = store.list_branches().unwrap();
+        branches.sort();
+        assert_eq!(snapshot.branches.keys().cloned().collect::<Vec<_>>(), branches);
+        for branch in &branches {
+            assert_eq!(snapshot.branch(branch).map(str::to_string), store.read_ref(&format!("refs/heads/{}", branch)));
+        }
+        assert_eq!(snapshot.tags.keys().cloned().collect::<Vec<_>>(), store.list_tags().unwrap());
+        assert_eq!(snapshot.tag("v1-notes"), Some(id.as_str()));
+        assert_eq!(snapshot.tag("v1").map(str::to_string), store.tag_commit("v1"));
+        assert_eq!(snapshot.resolve("refs/remotes/origin/main"), Some(id.as_str()));
+        assert_eq!(snapshot.head_ref, store.head_ref());
+        assert_eq!(snapshot.head, store.head_commit());
+    }
+
+    #[test]
+    fn test_list_refs_by_prefix() {
+        let (_temp, store, id) = repo();
+        store.create_branch("feature/x").unwrap();
+        store.create_lightweight_tag("v1", &id).unwrap();
+        store.create_annotated_tag("v1-notes", &id, "Release notes").unwrap();
+        store.write_ref("refs/remotes/origin/main", "remote-tip").unwrap();
+        store.write_ref("refs/remotes/origin2/main", "other-tip").unwrap();
+        store.create_orphan_branch("empty").unwrap();
+
+        let pair = |r: &str, target: &str| (r.to_string(), target.to_string());
+        assert_eq!(
+            store.list_refs("refs/heads").unwrap(),
+            [pair("refs/heads/feature/x", &id), pair("refs/heads/main", &id)]
+        );
+        assert_eq!(
+            store.list_refs("refs/tags/").unwrap(),
+            [pair("refs/tags/v1", &id), pair("refs/tags/v1-notes", &id)]
+        );
+        assert_eq!(store.list_refs("refs/remotes/origin").unwrap(), [pair("refs/remotes/origin/main", "remote-tip")]);
+        assert_eq!(store.list_refs("refs/remotes/origin/main").unwrap(), [pair("refs/remotes/origin/main", "remote-tip")]);
+        assert_eq!(store.list_refs("refs/remotes").unwrap().len(), 2);
+        assert_eq!(store.list_refs("").unwrap().len(), 6);
+        assert!(store.list_refs("refs/tags/v").unwrap().is_empty());
+
+        // Packed refs are listed the same way
+        store.pack_refs().unwrap();
+        assert_eq!(store.list_refs("refs/remotes/origin").unwrap(), [pair("refs/remotes/origin/main", "remote-tip")]);
+        assert_eq!(store.list_refs("refs/tags").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_loose_ref_wins_over_packed() {
+        let (_temp, store, id) = repo();
+        store.create_lightweight_tag("v1", &id).unwrap();
+        store.write_ref("refs/remotes/origin/main", &id).unwrap();
+        assert_eq!(store.pack_refs().unwrap(), 2);
+        assert!(!store.rune_dir.join("refs/tags/v1").exists());
+        assert!(!store.rune_dir.join("refs/remotes/origin").exists());
+
+        // Packed refs read as before
+        assert_eq!(store.tag_commit("v1").as_deref(), Some(id.as_str()));
+        assert_eq!(store.read_ref("refs/remotes/origin/main").as_deref(), Some(id.as_str()));
+        assert_eq!(store.list_tags().unwrap(), ["v1"]);
+
+        store.write_ref("refs/remotes/origin/main", "newer").unwrap();
+        assert_eq!(store.read_ref("refs/remotes/origin/main").as_deref(), Some("newer"));
+        assert_eq!(store.refs_snapshot().unwrap().remotes["origin/main"], "newer");
+
+        store.delete_tag("v1").unwrap();
+        assert!(!store.tag_exists("v1"));
+        assert!(store.refs_snapshot().unwrap().tags.is_empty());
+    }
+
+    #[test]
+    fn test_ref_write_leftovers_are_not_refs() {
+        let (_temp, store, id) = repo();
+        // A crash between creating a ref's temporary file and renaming it
+        fs::write(store.rune_dir.join("refs/heads/main.4242-0.lock"), &id).unwrap();
+        fs::create_dir_all(store.rune_dir.join("refs/tags")).unwrap();
+        fs::write(store.rune_dir.join("refs/tags/v1.4242-1.lock"), &id).unwrap();
+        store.invalidate_refs();
+
+        assert_eq!(store.list_branches().unwrap(), ["main"]);
+        assert!(store.list_tags().unwrap().is_empty());
+        assert!(store.invalid_refs().unwrap().is_empty());
+        assert_eq!(store.pack_refs().unwrap(), 0);
+
+        // Each write goes through a temporary file of its own and leaves none
+        store.write_ref("refs/heads/topic", &id).unwrap();
+        let mut left: Vec<String> = fs::read_dir(store.rune_dir.join("refs/heads"))
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        left.sort();
+        assert_eq!(left, ["main", "main.4242-0.lock", "topic"]);
+    }
+
+    #[test]
+    fn test_snapshot_sees_ref_writes() {
+        let (_temp, store, id) = repo();
+        let before = store.refs_snapshot().unwrap();
+        assert!(store.refs_snapshot().unwrap().tags.is_empty());
+
+        store.create_lightweight_tag("v2", &id).unwrap();
+        store.write_ref("refs/heads/main", "moved").unwrap();
+        let after = store.refs_snapshot().unwrap();
+        assert_eq!(after.tag("v2"), Some(id.as_str()));
+        assert_eq!(after.branch("main"), Some("moved"));
+        assert_eq!(after.head.as_deref(), Some("moved"));
+        assert_ne!(before, after);
+
+        // A second handle on the same repository, as another command would have
+        let other = Store::open(&store.root).unwrap();
+        other.write_ref("refs/heads/main", &id).unwrap();
+        assert_eq!(store.refs_snapshot().unwrap().branch("main"), Some(id.as_str()));
+    }
+}
@@ -0,0 +1,164 @@
+//! Remote-tracking refs (`refs/remotes/<remote>/<branch>`) and the branch
+//! upstreams pointing at them.
+//!
+//! A tracking ref whose branch is gone from the remote is stale; pruning
+//! deletes it, clears any upstream that named it, and retires its reflog to
+//! `logs/pruned/` with a last entry saying why, so the old tip stays findable.
+
+use crate::refs::remove_empty_parents;
+use crate::Store;
+use anyhow::Result;
+use std::fs;
+
+/// What pruning a remote removed, or would remove on a dry run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PruneReport {
+    /// Tracking refs removed, e.g. `refs/remotes/origin/feature/x`
+    pub pruned: Vec<String>,
+    /// Local branches whose upstream was one of them, with that upstream
+    pub upstreams_cleared: Vec<(String, String)>,
+}
+
+impl PruneReport {
+    pub fn is_empty(&self) -> bool {
+        self.pruned.is_empty()
+    }
+}
+
+impl Store {
+    /// Branch names `remote` has tracking refs for, sorted
+    pub fn remote_tracking_branches(&self, remote: &str) -> Result<Vec<String>> {
+        let prefix = format!("{}/", remote);
+        Ok(self
+            .refs_snapshot()?
+            .remotes
+            .keys()
+            .filter_map(|name| name.strip_prefix(&prefix))
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Tracking ref `branch` follows, if one is configured
+    pub fn branch_upstream(&self, branch: &str) -> Option<String> {
+        self.config().branch.get(branch).and_then(|b| b.upstream.clone())
+    }
+
+    /// Set or, with `None`, clear the tracking ref `branch` follows
+    pub fn set_branch_upstream(&self, branch: &str, upstream: Option<&str>) -> Result<()> {
+        let mut cfg = self.config();
+        match upstream {
+            Some(upstream) => {
+                cfg.branch.entry(branch.to_string()).or_default().upstream = Some(upstream.to_string());
+            }
+            None => {
+                if let Some(entry) = cfg.branch.get_mut(branch) {
+                    entry.upstream = None;
+                }
+                cfg.branch.retain(|_, b| b.upstream.is_some());
+            }
+        }
+        self.write_config(&cfg)
+    }
+
+    /// Remove the tracking refs of `remote` for branches not in
+    /// `remote_branches`, the remote's current branch list. With `dry_run`
+    /// nothing changes and the report says what would.
+    pub fn prune_remote_refs(&self, remote: &str, remote_branches: &[String], dry_run: bool) -> Result<PruneReport> {
+        let mut report = PruneReport::default();
+        for branch in self.remote_tracking_branches(remote)? {
+            if remote_branches.contains(&branch) {
+                continue;
+            }
+            let tracking = format!("refs/remotes/{}/{}", remote, branch);
+            if !dry_run {
+                self.delete_tracking_ref(remote, &tracking)?;
+            }
+            report.pruned.push(tracking);
+        }
+
+        for (branch, cfg) in self.config().branch {
+            if let Some(upstream) = cfg.upstream.filter(|u| report.pruned.contains(u)) {
+                if !dry_run {
+                    self.set_branch_upstream(&branch, None)?;
+                }
+                report.upstreams_cleared.push((branch, upstream));
+            }
+        }
+        Ok(report)
+    }
+
+    fn delete_tracking_ref(&self, remote: &str, tracking: &str) -> Result<()> {
+        let Some(tip) = self.read_ref(tracking) else {
+            return Ok(());
+        };
+        self.update_reflog(tracking, &tip, &format!("prune: deleted on {}", remote))?;
+
+        let log_name = tracking.replace('/', "_");
+        let pruned_logs = self.rune_dir.join("logs/pruned");
+        fs::create_dir_all(&pruned_logs)?;
+        fs::rename(self.rune_dir.join("logs").join(&log_name), pruned_logs.join(&log_name))?;
+
+        if let Some(path) = self.ref_file(tracking).filter(|p| p.is_file()) {
+            fs::remove_file(&path)?;
+            remove_empty_parents(&path, &self.rune_dir.join("refs/remotes").join(remote));
+        }
+        self.remove_packed_ref(tracking)?;
+        self.invalidate_refs();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Store;
+    use crate::test_support::temp_repo;
+    use tempfile::TempDir;
+
+    fn store_with_tracking(branches: &[&str]) -> (TempDir, Store) {
+        let (temp, store) = temp_repo();
+        for branch in branches {
+            store.write_ref(&format!("refs/remotes/origin/{}", branch), "abc123").unwrap();
+        }
+        (temp, store)
+    }
+
+    #[test]
+    fn test_prune_removes_only_branches_gone_remotely() {
+        let (_temp, store) = store_with_tracking(&["main", "feature/old", "topic"]);
+        store.write_ref("refs/remotes/upstream/feature/old", "abc123").unwrap();
+        store.set_branch_upstream("old", Some("refs/remotes/origin/feature/old")).unwrap();
+        store.set_branch_upstream("main", Some("refs/remotes/origin/main")).unwrap();
+
+        let still_there = vec!["main".to_string(), "topic".to_string()];
+        let report = store.prune_remote_refs("origin", &still_there, false).unwrap();
+
+        assert_eq!(report.pruned, ["refs/remotes/origin/feature/old"]);
+        assert_eq!(
+            report.upstreams_cleared,
+            [("old".to_string(), "refs/remotes/origin/feature/old".to_string())]
+        );
+        assert_eq!(store.remote_tracking_branches("origin").unwrap(), still_there);
+        assert!(!store.rune_dir.join("refs/remotes/origin/feature").exists());
+        assert_eq!(store.read_ref("refs/remotes/upstream/feature/old").as_deref(), Some("abc123"));
+        assert_eq!(store.branch_upstream("old"), None);
+        assert_eq!(store.branch_upstream("main").as_deref(), Some("refs/remotes/origin/main"));
+
+        // The reflog is retired with a last entry naming the old tip
+        assert!(store.reflog("refs/remotes/origin/feature/old").unwrap().is_empty());
+        let retired = std::fs::read_to_string(store.rune_dir.join("logs/pruned/refs_remotes_origin_feature_old")).unwrap();
+        assert!(retired.trim_end().ends_with("abc123 prune: deleted on origin"));
+    }
+
+    #[test]
+    fn test_dry_run_prune_changes_nothing() {
+        let (_temp, store) = store_with_tracking(&["main", "gone"]);
+        store.set_branch_upstream("gone", Some("refs/remotes/origin/gone")).unwrap();
+
+        let report = store.prune_remote_refs("origin", &["main".to_string()], true).unwrap();
+
+        assert_eq!(report.pruned, ["refs/remotes/origin/gone"]);
+        assert_eq!(report.upstreams_cleared.len(), 1);
+        assert_eq!(store.read_ref("refs/remotes/origin/gone").as_deref(), Some("abc123"));
+        assert_eq!(store.branch_upstream("gone").as_deref(), Some("refs/remotes/origin/gone"));
+    }
+}
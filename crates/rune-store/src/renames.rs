@@ -0,0 +1,275 @@
+//! Renames bigger than one file, and following a path back through them.
+//!
+//! A directory counts as renamed when nothing is left under it on the new
+//! side and at least `DIR_RENAME_FRACTION` of its files were renamed into
+//! one new directory, each keeping its path inside it. Its other files,
+//! deleted, moved elsewhere or changed past the rename threshold, are the
+//! rename's exceptions.
+//!
+//! `Store::follow` walks history for one path and switches to the path's
+//! old name at each commit that renamed it or a directory holding it. The
+//! renames between two commits never change, so they are worked out once
+//! per commit pair and kept for the rest of the process.
+
+use crate::{is_same_or_below, DiffSource, PathChange, Store};
+use anyhow::Result;
+use rune_core::Commit;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Share of a directory's files that have to move together
+const DIR_RENAME_FRACTION: f64 = 0.75;
+
+/// Smaller directories are left to file rename detection
+const DIR_RENAME_MIN_FILES: usize = 3;
+
+/// A directory whose files moved to another one together
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirRename {
+    pub from: String,
+    pub to: String,
+    /// Files renamed from `from` to the same place under `to`
+    pub files: usize,
+    /// Files under `from` that did not move with the rest, by old path
+    pub exceptions: Vec<String>,
+}
+
+impl DirRename {
+    /// Whether renaming `from` to `to` is one of this directory's moves
+    pub fn covers(&self, from: &str, to: &str) -> bool {
+        moved_path(from, &self.from, &self.to).as_deref() == Some(to)
+    }
+}
+
+/// The renames between two commits
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RenameMap {
+    /// New path to old path of every file rename
+    pub files: BTreeMap<String, String>,
+    pub dirs: Vec<DirRename>,
+}
+
+impl RenameMap {
+    /// The old name of `path`, a file or directory on the new side, when it
+    /// or a directory holding it was renamed. A directory rename's
+    /// exceptions were not carried along, so they have no old name.
+    pub fn source_of(&self, path: &str) -> Option<String> {
+        if let Some(old) = self.files.get(path) {
+            return Some(old.clone());
+        }
+        self.dirs
+            .iter()
+            .find_map(|dir| moved_path(path, &dir.to, &dir.from).filter(|old| !dir.exceptions.contains(old)))
+    }
+}
+
+/// Directory renames among `changes`, a diff from `old` to `new`
+pub(crate) fn detect_dir_renames(
+    old: &BTreeMap<String, String>,
+    new: &BTreeMap<String, String>,
+    changes: &[PathChange],
+) -> Vec<DirRename> {
+    let renames: BTreeMap<&str, &str> = changes
+        .iter()
+        .filter_map(|change| match change {
+            PathChange::Renamed { from, to, .. } => Some((from.as_str(), to.as_str())),
+            _ => None,
+        })
+        .collect();
+    let mut moves: BTreeMap<(String, String), usize> = BTreeMap::new();
+    for (from, to) in &renames {
+        if let Some(dirs) = renamed_dirs(from, to) {
+            *moves.entry(dirs).or_default() += 1;
+        }
+    }
+
+    let mut dirs = Vec::new();
+    for ((from, to), files) in moves {
+        if new.keys().any(|path| is_same_or_below(path, &from)) {
+            continue;
+        }
+        let before: Vec<&String> = old.keys().filter(|path| is_same_or_below(path, &from)).collect();
+        if before.len() < DIR_RENAME_MIN_FILES || (files as f64) < DIR_RENAME_FRACTION * before.len() as f64 {
+            continue;
+        }
+        let exceptions = before
+            .into_iter()
+            .filter(|path| renames.get(path.as_str()).copied() != moved_path(path, &from, &to).as_deref())
+            .cloned()
+            .collect();
+        dirs.push(DirRename { from, to, files, exceptions });
+    }
+    dirs
+}
+
+/// The directories a rename moved a file between, when it kept its name
+/// and the path below them: `a/x/f` to `b/x/f` is `a` to `b`
+fn renamed_dirs(from: &str, to: &str) -> Option<(String, String)> {
+    let (mut old, mut new): (Vec<&str>, Vec<&str>) = (from.split('/').collect(), to.split('/').collect());
+    let mut kept = 0;
+    while old.len() > 1 && new.len() > 1 && old.last() == new.last() {
+        old.pop();
+        new.pop();
+        kept += 1;
+    }
+    (kept > 0).then(|| (old.join("/"), new.join("/")))
+}
+
+/// `path` below `from_dir` moved to the same place below `to_dir`
+fn moved_path(path: &str, from_dir: &str, to_dir: &str) -> Option<String> {
+    if path == from_dir {
+        return Some(to_dir.to_string());
+    }
+    let rest = path.strip_prefix(from_dir)?.strip_prefix('/')?;
+    Some(format!("{}/{}", to_dir, rest))
+}
+
+/// Rename maps by repository and commit pair
+type Cache = HashMap<(PathBuf, String, String), Arc<RenameMap>>;
+
+fn cache() -> &'static Mutex<Cache> {
+    static CACHE: OnceLock<Mutex<Cache>> = OnceLock::new();
+    CACHE.get_or_init(Default::default)
+}
+
+impl Store {
+    /// File and directory renames from commit `from` to commit `to`,
+    /// worked out once per pair in this process
+    pub fn renames_between(&self, from: &str, to: &str) -> Result<Arc<RenameMap>> {
+        let key = (self.rune_dir.clone(), from.to_string(), to.to_string());
+        if let Some(map) = cache().lock().unwrap_or_else(|e| e.into_inner()).get(&key) {
+            return Ok(map.clone());
+        }
+        let (old, new) = (self.tree_snapshot(from), self.tree_snapshot(to));
+        let changes = self.changed_paths(&DiffSource::Commit(from.to_string()), &DiffSource::Commit(to.to_string()), &[])?;
+        let map = Arc::new(RenameMap {
+            dirs: detect_dir_renames(&old, &new, &changes),
+            files: changes
+                .into_iter()
+                .filter_map(|change| match change {
+                    PathChange::Renamed { from, to, .. } => Some((to, from)),
+                    _ => None,
+                })
+                .collect(),
+        });
+        cache().lock().unwrap_or_else(|e| e.into_inner()).insert(key, map.clone());
+        Ok(map)
+    }
+
+    /// Commits on HEAD's first-parent line that changed `path` (a file or
+    /// directory), newest first. Where a commit brought `path` in by
+    /// renaming it, or a directory holding it, the walk goes on with the
+    /// old name; where it brought `path` in any other way, the walk ends.
+    pub fn follow(&self, path: &str) -> Result<Vec<Commit>> {
+        let mut path = path.trim_start_matches("./").trim_end_matches('/').to_string();
+        let Some(head) = self.head_commit() else {
+            return Ok(Vec::new());
+        };
+        let mut commits = Vec::new();
+        for commit in self.ancestry(&head) {
+            if !commit.files.iter().any(|f| is_same_or_below(f, &path)) {
+                continue;
+            }
+            commits.push(commit.clone());
+            let Some(parent) = commit.parent.as_deref() else {
+                break;
+            };
+            if self.tree_snapshot(parent).keys().any(|p| is_same_or_below(p, &path)) {
+                continue;
+            }
+            match self.renames_between(parent, &commit.id)?.source_of(&path) {
+                Some(old) => path = old,
+                None => break,
+            }
+        }
+        Ok(commits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::renamed_dirs;
+    use crate::{DiffSource, Store};
+    use crate::test_support::{author, temp_repo};
+    use std::fs;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    fn write(root: &Path, path: &str, content: &str) {
+        let full = root.join(path);
+        fs::create_dir_all(full.parent().unwrap()).unwrap();
+        fs::write(full, content).unwrap();
+    }
+
+    /// Twelve files in `src/engine`, then the directory renamed to
+    /// `src/core` with `physics.rs` rewritten on the way
+    fn renamed_engine() -> (TempDir, Store, Vec<String>) {
+        let (temp, store) = temp_repo();
+        let names: Vec<String> = (0..11).map(|i| format!("mod{}.rs", i)).chain(["physics.rs".to_string()]).collect();
+        for name in &names {
+            write(temp.path(), &format!("src/engine/{}", name), &format!("// {}\nfn body() {{}}\n", name).repeat(5));
+            store.stage_file(&format!("src/engine/{}", name)).unwrap();
+        }
+        let mut ids = vec![store.commit("Add engine", author()).unwrap().id];
+        write(temp.path(), "src/engine/mod3.rs", &"// mod3.rs, tuned\nfn body() {}\n".repeat(5));
+        store.stage_file("src/engine/mod3.rs").unwrap();
+        ids.push(store.commit("Tune mod3", author()).unwrap().id);
+
+        for name in &names {
+            let (old, new) = (format!("src/engine/{}", name), format!("src/core/{}", name));
+            let content = if name == "physics.rs" {
+                "completely different\n".to_string()
+            } else {
+                fs::read_to_string(temp.path().join(&old)).unwrap()
+            };
+            fs::remove_file(temp.path().join(&old)).unwrap();
+            write(temp.path(), &new, &content);
+            store.stage_file(&old).unwrap();
+            store.stage_file(&new).unwrap();
+        }
+        ids.push(store.commit("Rename engine to core", author()).unwrap().id);
+        write(temp.path(), "src/core/mod3.rs", &"// mod3.rs, retuned\nfn body() {}\n".repeat(5));
+        store.stage_file("src/core/mod3.rs").unwrap();
+        ids.push(store.commit("Retune mod3", author()).unwrap().id);
+        (temp, store, ids)
+    }
+
+    #[test]
+    fn test_directory_rename_is_one_summary_with_exceptions() {
+        let (_temp, store, ids) = renamed_engine();
+        let map = store.renames_between(&ids[1], &ids[2]).unwrap();
+        assert_eq!(map.dirs.len(), 1);
+        let dir = &map.dirs[0];
+        assert_eq!((dir.from.as_str(), dir.to.as_str(), dir.files), ("src/engine", "src/core", 11));
+        assert_eq!(dir.exceptions, ["src/engine/physics.rs"]);
+        assert_eq!(map.files["src/core/mod0.rs"], "src/engine/mod0.rs");
+
+        let diff = store
+            .diff_between(&DiffSource::Commit(ids[1].clone()), &DiffSource::Commit(ids[2].clone()), &[])
+            .unwrap();
+        assert!(diff.starts_with("diff --rune a/src/engine/ b/src/core/\n"), "{}", diff);
+        assert!(diff.contains("11 files renamed\nexception src/engine/physics.rs\n"), "{}", diff);
+        // Moved files don't get an entry each; the rewritten one does
+        assert!(!diff.contains("a/src/engine/mod0.rs"), "{}", diff);
+        assert!(diff.contains("deleted file") && diff.contains("+++ b/src/core/physics.rs"), "{}", diff);
+    }
+
+    #[test]
+    fn test_follow_continues_past_the_rename() {
+        let (_temp, store, ids) = renamed_engine();
+        let history = |path: &str| -> Vec<String> { store.follow(path).unwrap().into_iter().map(|c| c.id).collect() };
+        assert_eq!(history("src/core/mod3.rs"), [ids[3].as_str(), ids[2].as_str(), ids[1].as_str(), ids[0].as_str()]);
+        assert_eq!(history("src/core"), [ids[3].as_str(), ids[2].as_str(), ids[1].as_str(), ids[0].as_str()]);
+        // Not a rename of the old file, so its history starts at the move
+        assert_eq!(history("src/core/physics.rs"), [ids[2].as_str()]);
+        assert_eq!(history("src/core/mod5.rs"), [ids[2].as_str(), ids[0].as_str()]);
+    }
+
+    #[test]
+    fn test_renamed_dirs_keep_the_path_below() {
+        assert_eq!(renamed_dirs("a/x/f.rs", "b/x/f.rs"), Some(("a".into(), "b".into())));
+        assert_eq!(renamed_dirs("x/f.rs", "y/x/f.rs"), Some(("x".into(), "y/x".into())));
+        assert_eq!(renamed_dirs("a/f.rs", "a/g.rs"), None);
+    }
+}
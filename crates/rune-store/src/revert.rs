@@ -0,0 +1,323 @@
+//! Reverting a commit from the content it recorded: its changes are undone
+//! on top of HEAD, and where the working tree has moved on since, the
+//! revert pauses on conflicts until it is continued or aborted.
+
+use crate::{ConflictSide, DiscardOperation, Store, StoreError};
+use anyhow::Result;
+use rune_core::{Author, Commit};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+/// `.rune/REVERT_STATE`: a revert paused on conflicts
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct RevertState {
+    pub(crate) commit: String,
+    message: String,
+    no_commit: bool,
+    #[serde(default)]
+    pub(crate) conflicts: Vec<String>,
+    #[serde(default)]
+    pub(crate) resolved: BTreeSet<String>,
+    /// Paths the revert already rewrote, with the blob they held before
+    /// (`None` for a file the revert recreated)
+    #[serde(default)]
+    applied: BTreeMap<String, Option<String>>,
+}
+
+/// Result of `Store::revert_commit`
+#[derive(Debug, Clone)]
+pub enum RevertResult {
+    /// The inverse changes were committed
+    Committed(Box<Commit>),
+    /// The inverse changes were staged for these paths but not committed
+    Staged(Vec<String>),
+    /// The working tree no longer matches the reverted commit for these
+    /// paths; they hold conflict markers until resolved
+    Conflicts(Vec<String>),
+}
+
+impl Store {
+    /// Undo `commit_id` on top of HEAD: files it added are deleted, files it
+    /// deleted or modified get their content from its parent back. A file
+    /// whose working copy no longer matches what the commit left behind is a
+    /// conflict; the revert then pauses with markers in the file until
+    /// `continue_revert` or `abort_revert`.
+    pub fn revert_commit(&self, commit_id: &str, mainline: Option<usize>, no_commit: bool, author: Author) -> Result<RevertResult> {
+        let _batch = self.batch_index_writes();
+        if self.operation_in_progress()?.is_some() {
+            return Err(StoreError::MergeInProgress { action: "revert" }.into());
+        }
+        let target = self.resolve_commit(commit_id)?;
+        if let Some(n) = mainline.filter(|n| *n != 1) {
+            anyhow::bail!("commit {} has a single parent; mainline {} does not exist", commit_id, n);
+        }
+        if target.tree.is_empty() && !target.files.is_empty() {
+            anyhow::bail!("commit {} has no recorded content and cannot be reverted", commit_id);
+        }
+        if !self.read_index()?.entries.is_empty() {
+            anyhow::bail!("cannot revert with staged changes; commit or reset them first");
+        }
+
+        let before = target.parent.as_deref().map(|p| self.tree_snapshot(p)).unwrap_or_default();
+        let after = self.tree_snapshot(&target.id);
+        let changes: Vec<(&String, Option<&String>, Option<&String>)> = target
+            .tree
+            .keys()
+            .map(|path| (path, before.get(path), after.get(path)))
+            .filter(|(_, b, a)| b != a)
+            .collect();
+        if changes.is_empty() {
+            anyhow::bail!("commit {} changes nothing that can be reverted", commit_id);
+        }
+
+        let filter = self.eol_filter()?;
+        let mut state = RevertState {
+            commit: target.id.clone(),
+            message: format!("Revert \"{}\"", target.message),
+            no_commit,
+            conflicts: Vec::new(),
+            resolved: Default::default(),
+            applied: BTreeMap::new(),
+        };
+        for (path, before, after) in changes {
+            let dest = self.root.join(path);
+            let working = fs::read(&dest).ok();
+            let current = working
+                .as_ref()
+                .map(|content| blake3::hash(&filter.to_store(Path::new(path), content)).to_hex().to_string());
+            if current.as_ref() != after {
+                let conflict_dir = self.conflict_dir(path)?;
+                fs::create_dir_all(&conflict_dir)?;
+                for (side, content) in [
+                    (ConflictSide::Base, after.map(|b| self.read_blob(b)).transpose()?),
+                    (ConflictSide::Ours, working),
+                    (ConflictSide::Theirs, before.map(|b| self.read_blob(b)).transpose()?),
+                ] {
+                    if let Some(content) = content {
+                        fs::write(conflict_dir.join(side.file_name()), content)?;
+                    }
+                }
+                state.conflicts.push(path.clone());
+                continue;
+            }
+            match before {
+                Some(blob) => {
+                    let content = self.read_blob(blob)?;
+                    if let Some(parent) = dest.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::write(&dest, &*filter.to_worktree(Path::new(path), &content))?;
+                }
+                None => fs::remove_file(&dest)?,
+            }
+            self.stage_file(path)?;
+            state.applied.insert(path.clone(), after.cloned());
+        }
+
+        if !state.conflicts.is_empty() {
+            let label = format!("parent of {}", &target.id[..target.id.len().min(8)]);
+            self.apply_merge_conflicts(&state.conflicts, &label)?;
+            self.write_revert_state(&state)?;
+            return Ok(RevertResult::Conflicts(state.conflicts));
+        }
+        if no_commit {
+            return Ok(RevertResult::Staged(state.applied.into_keys().collect()));
+        }
+        Ok(RevertResult::Committed(Box::new(self.commit(&state.message, author)?)))
+    }
+
+    pub(crate) fn read_revert_state(&self) -> Result<Option<RevertState>> {
+        let revert_file = self.rune_dir.join("REVERT_STATE");
+        if !revert_file.exists() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&fs::read_to_string(revert_file)?)?))
+    }
+
+    pub(crate) fn write_revert_state(&self, state: &RevertState) -> Result<()> {
+        fs::write(self.rune_dir.join("REVERT_STATE"), serde_json::to_string_pretty(state)?)?;
+        Ok(())
+    }
+
+    /// Drop REVERT_STATE and the stored conflict versions
+    fn finish_revert(&self) -> Result<()> {
+        let merge_dir = self.rune_dir.join("merge");
+        if merge_dir.exists() {
+            fs::remove_dir_all(merge_dir)?;
+        }
+        fs::remove_file(self.rune_dir.join("REVERT_STATE"))?;
+        Ok(())
+    }
+
+    /// Abort a revert paused on conflicts, putting back every file it touched
+    pub fn abort_revert(&self) -> Result<()> {
+        let state = self
+            .read_revert_state()?
+            .ok_or(StoreError::NoRevertInProgress)?;
+        self.run_discard_hooks(DiscardOperation::Abort)?;
+
+        for path in &state.conflicts {
+            let dest = self.root.join(path);
+            match fs::read(self.conflict_version_path(path, ConflictSide::Ours)?) {
+                Ok(content) => fs::write(&dest, content)?,
+                Err(_) if dest.exists() => fs::remove_file(&dest)?,
+                Err(_) => {}
+            }
+        }
+        let filter = self.eol_filter()?;
+        for (path, blob) in &state.applied {
+            let dest = self.root.join(path);
+            match blob {
+                Some(blob) => {
+                    let content = self.read_blob(blob)?;
+                    if let Some(parent) = dest.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::write(&dest, &*filter.to_worktree(Path::new(path), &content))?;
+                }
+                None if dest.exists() => fs::remove_file(&dest)?,
+                None => {}
+            }
+        }
+        let mut index = self.read_index()?;
+        for path in state.conflicts.iter().chain(state.applied.keys()) {
+            index.entries.remove(path);
+            index.blobs.remove(path);
+        }
+        self.write_index(&index)?;
+
+        self.finish_revert()
+    }
+
+    /// Finish a revert once its conflicts are resolved. Returns the revert
+    /// commit, or `None` when the revert was started without committing.
+    pub fn continue_revert(&self, author: Author) -> Result<Option<Commit>> {
+        let state = self
+            .read_revert_state()?
+            .ok_or(StoreError::NoRevertInProgress)?;
+
+        let unresolved = self.unresolved_conflicts()?;
+        if !unresolved.is_empty() {
+            return Err(StoreError::UnresolvedConflicts(unresolved).into());
+        }
+
+        let commit = if state.no_commit {
+            None
+        } else {
+            Some(self.commit(&state.message, author)?)
+        };
+        self.finish_revert()?;
+        Ok(commit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{author, temp_repo};
+    use crate::RepoOperation;
+
+    /// Write each `(path, content)`, deleting the file for `None`, and commit
+    fn commit(store: &Store, changes: &[(&str, Option<&str>)], msg: &str) -> Commit {
+        for (path, content) in changes {
+            let dest = store.root.join(path);
+            match content {
+                Some(content) => fs::write(&dest, content).unwrap(),
+                None => fs::remove_file(&dest).unwrap(),
+            }
+            store.stage_file(path).unwrap();
+        }
+        store.commit(msg, author()).unwrap()
+    }
+
+    fn read(store: &Store, path: &str) -> String {
+        fs::read_to_string(store.root.join(path)).unwrap()
+    }
+
+    #[test]
+    fn test_revert_add_deletes_file() {
+        let (_temp_dir, store) = temp_repo();
+        commit(&store, &[("a.txt", Some("a\n"))], "add a");
+        let added = commit(&store, &[("b.txt", Some("b\n"))], "add b");
+
+        let RevertResult::Committed(revert) = store.revert_commit(&added.id, None, false, author()).unwrap() else {
+            panic!("revert should commit");
+        };
+        assert_eq!(revert.message, "Revert \"add b\"");
+        assert!(!store.root.join("b.txt").exists());
+        let snapshot = store.tree_snapshot(&revert.id);
+        assert!(snapshot.contains_key("a.txt"));
+        assert!(!snapshot.contains_key("b.txt"));
+    }
+
+    #[test]
+    fn test_revert_delete_restores_content() {
+        let (_temp_dir, store) = temp_repo();
+        commit(&store, &[("a.txt", Some("first\nsecond\n"))], "add a");
+        let deleted = commit(&store, &[("a.txt", None)], "delete a");
+        assert!(!store.tree_snapshot(&deleted.id).contains_key("a.txt"));
+
+        store.revert_commit(&deleted.id, None, false, author()).unwrap();
+        assert_eq!(read(&store, "a.txt"), "first\nsecond\n");
+        assert!(store.tree_snapshot(&store.head_commit().unwrap()).contains_key("a.txt"));
+    }
+
+    #[test]
+    fn test_revert_modify_restores_parent_content() {
+        let (_temp_dir, store) = temp_repo();
+        commit(&store, &[("notes.txt", Some("v1\n"))], "v1");
+        let modified = commit(&store, &[("notes.txt", Some("v2\n")), ("new.txt", Some("n\n"))], "v2");
+
+        store.revert_commit(&modified.id, None, false, author()).unwrap();
+        assert_eq!(read(&store, "notes.txt"), "v1\n");
+        assert!(!store.root.join("new.txt").exists());
+    }
+
+    #[test]
+    fn test_revert_no_commit_stages_inverse() {
+        let (_temp_dir, store) = temp_repo();
+        commit(&store, &[("notes.txt", Some("v1\n"))], "v1");
+        let head_before = commit(&store, &[("notes.txt", Some("v2\n"))], "v2");
+
+        let result = store.revert_commit(&head_before.id, None, true, author()).unwrap();
+        assert!(matches!(result, RevertResult::Staged(ref paths) if paths == &["notes.txt".to_string()]));
+        assert_eq!(store.head_commit().as_deref(), Some(head_before.id.as_str()));
+        let index = store.read_index().unwrap();
+        assert_eq!(index.blobs["notes.txt"], store.write_blob(b"v1\n").unwrap());
+    }
+
+    #[test]
+    fn test_conflicting_revert_pauses_with_markers() {
+        let (_temp_dir, store) = temp_repo();
+        commit(&store, &[("notes.txt", Some("v1\n"))], "v1");
+        let target = commit(&store, &[("notes.txt", Some("v2\n")), ("b.txt", Some("b\n"))], "v2");
+        commit(&store, &[("notes.txt", Some("v3\n"))], "v3");
+
+        let result = store.revert_commit(&target.id, None, false, author()).unwrap();
+        assert!(matches!(result, RevertResult::Conflicts(ref paths) if paths == &["notes.txt".to_string()]));
+        let text = read(&store, "notes.txt");
+        assert!(text.contains("<<<<<<< HEAD\nv3\n=======\nv1\n>>>>>>>"));
+        assert!(!store.root.join("b.txt").exists());
+        assert_eq!(
+            store.operation_in_progress().unwrap(),
+            Some(RepoOperation::Revert { commit: target.id.clone() })
+        );
+        assert!(store.continue_revert(author()).is_err());
+
+        store.abort_revert().unwrap();
+        assert_eq!(read(&store, "notes.txt"), "v3\n");
+        assert_eq!(read(&store, "b.txt"), "b\n");
+        assert!(store.read_index().unwrap().entries.is_empty());
+        assert_eq!(store.operation_in_progress().unwrap(), None);
+
+        store.revert_commit(&target.id, None, false, author()).unwrap();
+        fs::write(store.root.join("notes.txt"), "v1 and v3\n").unwrap();
+        store.mark_resolved("notes.txt").unwrap();
+        let revert = store.continue_revert(author()).unwrap().unwrap();
+        assert_eq!(store.show_file_at_commit(&revert.id, "notes.txt").unwrap(), "v1 and v3\n");
+        assert!(store.show_file_at_commit(&revert.id, "b.txt").is_err());
+        assert_eq!(store.operation_in_progress().unwrap(), None);
+    }
+}
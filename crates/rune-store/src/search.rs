@@ -0,0 +1,259 @@
+//! Searching the log: `Store::search_commits` keeps the commits that match
+//! every filter set in a `CommitQuery`, newest first.
+
+use crate::{is_same_or_below, Store};
+use anyhow::Result;
+use rune_core::Commit;
+
+/// Filters for `Store::search_commits`; every field that is set must match
+#[derive(Debug, Clone, Default)]
+pub struct CommitQuery {
+    /// Case-insensitive substring of the commit message
+    pub message: Option<String>,
+    /// Regular expression matched against the commit message
+    pub message_regex: Option<String>,
+    /// Case-insensitive substring of the author name or email
+    pub author: Option<String>,
+    /// Only commits at or after this unix timestamp
+    pub since: Option<i64>,
+    /// Only commits at or before this unix timestamp
+    pub until: Option<i64>,
+    /// Commits touching this file, or anything below it when it is a directory
+    pub path: Option<String>,
+    /// Follow `path` back through renames of it or a directory holding it
+    pub follow: bool,
+    /// Stop after this many matches
+    pub limit: Option<usize>,
+    /// `(key, value)` trailers the message must carry; keys ignore case and
+    /// values match as case-insensitive substrings
+    pub trailers: Vec<(String, String)>,
+}
+
+impl Store {
+    /// Commits matching every filter in `query`, newest first
+    pub fn search_commits(&self, query: &CommitQuery) -> Result<Vec<Commit>> {
+        let message_regex = query
+            .message_regex
+            .as_deref()
+            .map(regex::Regex::new)
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("Invalid message pattern: {}", e))?;
+        let message = query.message.as_ref().map(|m| m.to_lowercase());
+        let author = query.author.as_ref().map(|a| a.to_lowercase());
+        let path = query
+            .path
+            .as_ref()
+            .map(|p| p.trim_start_matches("./").trim_end_matches('/').to_string());
+        let wanted_trailers: Vec<(&str, String)> =
+            query.trailers.iter().map(|(k, v)| (k.as_str(), v.to_lowercase())).collect();
+        let followed: Option<std::collections::HashSet<String>> = match &path {
+            Some(p) if query.follow && !p.is_empty() => Some(self.follow(p)?.into_iter().map(|c| c.id).collect()),
+            _ => None,
+        };
+
+        let mut commits: Vec<Commit> = self.log().into_iter().rev().collect();
+        // Stable sort keeps log order for commits made in the same second
+        commits.sort_by_key(|c| std::cmp::Reverse(c.time));
+
+        let matches = commits.into_iter().filter(|c| {
+            if let Some(m) = &message {
+                if !c.message.to_lowercase().contains(m) {
+                    return false;
+                }
+            }
+            if let Some(re) = &message_regex {
+                if !re.is_match(&c.message) {
+                    return false;
+                }
+            }
+            if let Some(a) = &author {
+                if !c.author.name.to_lowercase().contains(a)
+                    && !c.author.email.to_lowercase().contains(a)
+                {
+                    return false;
+                }
+            }
+            if query.since.is_some_and(|since| c.time < since) {
+                return false;
+            }
+            if query.until.is_some_and(|until| c.time > until) {
+                return false;
+            }
+            if let Some(ids) = &followed {
+                if !ids.contains(&c.id) {
+                    return false;
+                }
+            } else if let Some(p) = &path {
+                let touched = c.files.iter().any(|f| p.is_empty() || is_same_or_below(f, p));
+                if !touched {
+                    return false;
+                }
+            }
+            if !wanted_trailers.is_empty() {
+                let trailers = rune_core::trailers::parse(&c.message);
+                let carries = |(key, value): &(&str, String)| {
+                    trailers.iter().any(|t| t.is(key) && t.value.to_lowercase().contains(value))
+                };
+                if !wanted_trailers.iter().all(carries) {
+                    return false;
+                }
+            }
+            true
+        });
+
+        Ok(match query.limit {
+            Some(limit) => matches.take(limit).collect(),
+            None => matches.collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_repo;
+    use rune_core::Author;
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    fn seed(store: &Store, id: &str, message: &str, name: &str, time: i64, files: &[&str]) {
+        let commit = Commit {
+            id: id.to_string(),
+            message: message.to_string(),
+            author: Author {
+                name: name.to_string(),
+                email: format!("{}@example.com", name.to_lowercase()),
+            },
+            committer: Author {
+                name: name.to_string(),
+                email: format!("{}@example.com", name.to_lowercase()),
+            },
+            time,
+            parent: None,
+            merge_parent: None,
+            files: files.iter().map(|f| f.to_string()).collect(),
+            branch: "refs/heads/main".to_string(),
+            tree: BTreeMap::new(),
+            modes: BTreeMap::new(),
+        };
+        let mut f = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(store.rune_dir.join("log.jsonl"))
+            .unwrap();
+        writeln!(f, "{}", serde_json::to_string(&commit).unwrap()).unwrap();
+    }
+
+    fn seeded_store() -> (TempDir, Store) {
+        let (temp_dir, store) = temp_repo();
+        seed(&store, "c1", "Add parser", "Alice", 1_000, &["src/parser.rs"]);
+        seed(&store, "c2", "Fix parser bug #12", "Bob", 2_000, &["src/parser.rs", "README.md"]);
+        seed(&store, "c3", "Update docs", "Alice", 3_000, &["docs/guide.md", "README.md"]);
+        seed(&store, "c4", "Fix lexer bug #40", "Alice", 4_000, &["src/lexer.rs"]);
+        (temp_dir, store)
+    }
+
+    fn ids(commits: Vec<Commit>) -> Vec<String> {
+        commits.into_iter().map(|c| c.id).collect()
+    }
+
+    #[test]
+    fn test_search_all_newest_first() {
+        let (_temp_dir, store) = seeded_store();
+        let all = store.search_commits(&CommitQuery::default()).unwrap();
+        assert_eq!(ids(all), vec!["c4", "c3", "c2", "c1"]);
+    }
+
+    #[test]
+    fn test_search_by_message() {
+        let (_temp_dir, store) = seeded_store();
+        let query = CommitQuery { message: Some("FIX".into()), ..Default::default() };
+        assert_eq!(ids(store.search_commits(&query).unwrap()), vec!["c4", "c2"]);
+
+        let query = CommitQuery { message_regex: Some(r"#1\d\b".into()), ..Default::default() };
+        assert_eq!(ids(store.search_commits(&query).unwrap()), vec!["c2"]);
+
+        let query = CommitQuery { message_regex: Some("(".into()), ..Default::default() };
+        assert!(store.search_commits(&query).is_err());
+    }
+
+    #[test]
+    fn test_search_by_author() {
+        let (_temp_dir, store) = seeded_store();
+        let query = CommitQuery { author: Some("bob".into()), ..Default::default() };
+        assert_eq!(ids(store.search_commits(&query).unwrap()), vec!["c2"]);
+
+        let query = CommitQuery { author: Some("alice@example.com".into()), ..Default::default() };
+        assert_eq!(ids(store.search_commits(&query).unwrap()), vec!["c4", "c3", "c1"]);
+    }
+
+    #[test]
+    fn test_search_by_time_range() {
+        let (_temp_dir, store) = seeded_store();
+        let query = CommitQuery { since: Some(2_000), until: Some(3_000), ..Default::default() };
+        assert_eq!(ids(store.search_commits(&query).unwrap()), vec!["c3", "c2"]);
+    }
+
+    #[test]
+    fn test_search_by_path() {
+        let (_temp_dir, store) = seeded_store();
+        let query = CommitQuery { path: Some("README.md".into()), ..Default::default() };
+        assert_eq!(ids(store.search_commits(&query).unwrap()), vec!["c3", "c2"]);
+
+        let query = CommitQuery { path: Some("src/".into()), ..Default::default() };
+        assert_eq!(ids(store.search_commits(&query).unwrap()), vec!["c4", "c2", "c1"]);
+
+        // A prefix that isn't a directory boundary must not match
+        let query = CommitQuery { path: Some("src/pars".into()), ..Default::default() };
+        assert!(store.search_commits(&query).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_search_author_and_path_combined() {
+        let (_temp_dir, store) = seeded_store();
+        let query = CommitQuery {
+            author: Some("alice".into()),
+            path: Some("src".into()),
+            ..Default::default()
+        };
+        assert_eq!(ids(store.search_commits(&query).unwrap()), vec!["c4", "c1"]);
+
+        let limited = CommitQuery { limit: Some(1), ..query };
+        assert_eq!(ids(store.search_commits(&limited).unwrap()), vec!["c4"]);
+    }
+
+    #[test]
+    fn test_required_trailers() {
+        let (_temp_dir, store) = seeded_store();
+        store.check_commit_message("Anything goes").unwrap();
+
+        let mut config = store.config();
+        config.commit.required_trailers = vec!["Reviewed-by".into(), "Refs".into()];
+        store.write_config(&config).unwrap();
+        let err = store.check_commit_message("Fix lexer\n\nRefs: #40").unwrap_err();
+        assert!(err.to_string().ends_with(": Reviewed-by"), "{}", err);
+        store.check_commit_message("Fix lexer\n\nRefs: #40\nreviewed-by: alice").unwrap();
+    }
+
+    #[test]
+    fn test_search_by_trailer() {
+        let (_temp_dir, store) = seeded_store();
+        seed(&store, "c5", "Fix lexer\n\nReviewed-by: Alice <alice@example.com>\nRefs: #40", "Bob", 5_000, &["src/lexer.rs"]);
+        seed(&store, "c6", "Mention Reviewed-by: alice in the body\n\nof the message", "Bob", 6_000, &["README.md"]);
+
+        let by = |key: &str, value: &str| CommitQuery {
+            trailers: vec![(key.into(), value.into())],
+            ..Default::default()
+        };
+        assert_eq!(ids(store.search_commits(&by("reviewed-by", "alice")).unwrap()), vec!["c5"]);
+        assert!(store.search_commits(&by("Reviewed-by", "bob")).unwrap().is_empty());
+
+        let both = CommitQuery {
+            trailers: vec![("Reviewed-by".into(), "alice".into()), ("Refs".into(), "#41".into())],
+            ..Default::default()
+        };
+        assert!(store.search_commits(&both).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,260 @@
+//! Sparse index: top-level directories left out of the workspace.
+//!
+//! A collapsed directory is a single marker in the index, recording the
+//! hash of its content at HEAD, instead of anything below it. Status and
+//! working tree diffs don't look inside it, and what the next commit holds
+//! there is HEAD's content unchanged. Staging a path inside it fails or
+//! expands the directory again, as `[sparse] on_stage` says.
+
+use crate::{is_same_or_below, Index, Store, StoreError};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+
+/// `[sparse]`: how the index follows the workspace's active roots
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SparseCfg {
+    /// Collapse top-level directories no active workspace root is in
+    #[serde(default)]
+    pub index: bool,
+    #[serde(default)]
+    pub on_stage: SparseStaging,
+}
+
+impl SparseCfg {
+    pub(crate) fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// What staging a path inside a collapsed directory does
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SparseStaging {
+    /// Refuse, naming the directory
+    #[default]
+    Error,
+    /// Expand the directory, then stage
+    Expand,
+}
+
+impl SparseStaging {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SparseStaging::Error => "error",
+            SparseStaging::Expand => "expand",
+        }
+    }
+}
+
+impl std::str::FromStr for SparseStaging {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "error" => Ok(SparseStaging::Error),
+            "expand" => Ok(SparseStaging::Expand),
+            _ => bail!("unknown sparse.on_stage '{}', expected 'error' or 'expand'", s),
+        }
+    }
+}
+
+/// The collapsed directories of an index, for skipping what lies below them
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SparseDirs(BTreeSet<String>);
+
+impl SparseDirs {
+    pub(crate) fn of(index: &Index) -> Self {
+        Self(index.sparse.keys().cloned().collect())
+    }
+
+    /// The collapsed directory `path` lies in, if any
+    pub(crate) fn containing(&self, path: &str) -> Option<&str> {
+        let top = path.split('/').next()?;
+        self.0.get(top).map(String::as_str).filter(|dir| is_same_or_below(path, dir))
+    }
+
+    pub(crate) fn covers(&self, path: &str) -> bool {
+        self.containing(path).is_some()
+    }
+}
+
+impl Store {
+    /// Collapsed directories with the hash of their content at HEAD when
+    /// they were collapsed
+    pub fn sparse_dirs(&self) -> Result<BTreeMap<String, String>> {
+        Ok(self.read_index()?.sparse)
+    }
+
+    /// Collapse exactly `excluded` (top-level directory names): newly
+    /// listed ones are collapsed, ones no longer listed expanded. Returns
+    /// the directories collapsed and expanded.
+    pub fn set_sparse_dirs(&self, excluded: &BTreeSet<String>) -> Result<(Vec<String>, Vec<String>)> {
+        let mut index = self.read_index()?;
+        let expanded: Vec<String> = index.sparse.keys().filter(|dir| !excluded.contains(*dir)).cloned().collect();
+        let collapsed: Vec<String> = excluded.iter().filter(|dir| !index.sparse.contains_key(*dir)).cloned().collect();
+        for dir in &collapsed {
+            if dir.is_empty() || dir.contains('/') || dir.starts_with('.') {
+                bail!("'{}' is not a top-level directory", dir);
+            }
+            let staged: Vec<&String> = index.entries.keys().filter(|p| is_same_or_below(p, dir)).collect();
+            if !staged.is_empty() {
+                bail!("cannot leave out {}/: it has staged changes ({}); commit or unstage them first", dir,
+                    staged.iter().map(|p| p.as_str()).collect::<Vec<_>>().join(", "));
+            }
+        }
+        if collapsed.is_empty() && expanded.is_empty() {
+            return Ok((collapsed, expanded));
+        }
+
+        let head_tree = self.head_commit().map(|id| self.tree_snapshot(&id)).unwrap_or_default();
+        for dir in &expanded {
+            index.sparse.remove(dir);
+        }
+        for dir in &collapsed {
+            index.sparse.insert(dir.clone(), subtree_hash(&head_tree, dir));
+        }
+        self.replace_index(&index)?;
+        // The cached status saw a different set of directories
+        let _ = fs::remove_file(self.rune_dir.join("status-snapshot.json"));
+        Ok((collapsed, expanded))
+    }
+
+    /// Expand the collapsed directory holding `rel`, or refuse, as
+    /// `[sparse] on_stage` says; nothing happens when `rel` isn't in one
+    pub(crate) fn expand_for_staging(&self, rel: &str) -> Result<()> {
+        let index = self.read_index()?;
+        let Some(dir) = SparseDirs::of(&index).containing(rel).map(str::to_string) else {
+            return Ok(());
+        };
+        match self.config().sparse.on_stage {
+            SparseStaging::Error => Err(StoreError::OutsideSparse { path: rel.to_string(), dir }.into()),
+            SparseStaging::Expand => {
+                let mut excluded: BTreeSet<String> = index.sparse.into_keys().collect();
+                excluded.remove(&dir);
+                self.set_sparse_dirs(&excluded).map(|_| ())
+            }
+        }
+    }
+
+    /// Refuse a commit that would change a collapsed directory; its content
+    /// has to stay exactly as HEAD has it
+    pub(crate) fn check_sparse_commit(&self, index: &Index) -> Result<()> {
+        let sparse = SparseDirs::of(index);
+        if let Some(path) = index.entries.keys().find(|p| sparse.covers(p)) {
+            let dir = sparse.containing(path).unwrap_or_default().to_string();
+            return Err(StoreError::OutsideSparse { path: path.clone(), dir }.into());
+        }
+        Ok(())
+    }
+}
+
+/// Hash of the part of `tree` below `dir`; empty when it has nothing there
+fn subtree_hash(tree: &BTreeMap<String, String>, dir: &str) -> String {
+    let mut entries = tree.iter().filter(|(path, _)| is_same_or_below(path, dir)).peekable();
+    if entries.peek().is_none() {
+        return String::new();
+    }
+    let mut hasher = blake3::Hasher::new();
+    for (path, blob) in entries {
+        hasher.update(format!("{} {}\n", blob, path).as_bytes());
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DiffSource, SparseStaging, StatusScan, Store};
+    use crate::test_support::{author, temp_repo};
+    use std::collections::BTreeSet;
+    use std::fs;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    fn dirs(names: &[&str]) -> BTreeSet<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    /// A monorepo with `app/` and a much larger `vendor/`, committed
+    fn monorepo() -> (TempDir, Store) {
+        let (temp, store) = temp_repo();
+        let mut paths = vec!["app/main.rs".to_string(), "README".to_string()];
+        paths.extend((0..40).map(|i| format!("vendor/lib{}/src.rs", i)));
+        for path in &paths {
+            write(temp.path(), path, "v1\n");
+            store.stage_file(path).unwrap();
+        }
+        store.commit("base", author()).unwrap();
+        (temp, store)
+    }
+
+    fn write(root: &Path, path: &str, content: &str) {
+        let full = root.join(path);
+        fs::create_dir_all(full.parent().unwrap()).unwrap();
+        fs::write(full, content).unwrap();
+    }
+
+    #[test]
+    fn test_sparse_commit_matches_full_commit() {
+        let (full_temp, full) = monorepo();
+        let (sparse_temp, sparse) = monorepo();
+        sparse.set_sparse_dirs(&dirs(&["vendor"])).unwrap();
+        assert_eq!(sparse.sparse_dirs().unwrap().len(), 1);
+
+        for (root, store) in [(full_temp.path(), &full), (sparse_temp.path(), &sparse)] {
+            write(root, "app/main.rs", "v2\n");
+            write(root, "vendor/lib3/src.rs", "local hack\n");
+            store.stage_file("app/main.rs").unwrap();
+            store.commit("Update app", author()).unwrap();
+        }
+        let tree = |store: &Store| store.tree_snapshot(&store.head_commit().unwrap());
+        assert_eq!(tree(&sparse), tree(&full));
+        assert_eq!(tree(&sparse)["vendor/lib3/src.rs"], tree(&full)["vendor/lib0/src.rs"]);
+        // The marker outlives the commit's fresh index
+        assert!(sparse.sparse_dirs().unwrap().contains_key("vendor"));
+
+        // Nor does anything inside the collapsed directory show as changed
+        assert!(sparse.status().unwrap().working.is_empty());
+        assert!(sparse.diff_between(&DiffSource::Index, &DiffSource::Worktree, &[]).unwrap().is_empty());
+        assert_eq!(full.status().unwrap().working, ["vendor/lib3/src.rs"]);
+    }
+
+    #[test]
+    fn test_collapsed_directory_is_not_walked() {
+        let (_temp, store) = monorepo();
+        let visited = |store: &Store| match store.status_with_scan().unwrap().1 {
+            StatusScan::Full { visited } => visited,
+            scan => panic!("expected a full scan, got {:?}", scan),
+        };
+        let full = visited(&store);
+        store.set_sparse_dirs(&dirs(&["vendor"])).unwrap();
+        let sparse = visited(&store);
+        // vendor/, its 40 directories and their 40 files
+        assert_eq!(full - sparse, 81);
+        assert!(sparse * 10 < full, "{} entries visited sparse, {} full", sparse, full);
+
+        store.set_sparse_dirs(&BTreeSet::new()).unwrap();
+        assert_eq!(visited(&store), full);
+    }
+
+    #[test]
+    fn test_staging_inside_collapsed_directory() {
+        let (temp, store) = monorepo();
+        store.set_sparse_dirs(&dirs(&["vendor"])).unwrap();
+        write(temp.path(), "vendor/lib1/src.rs", "v2\n");
+
+        let err = store.stage_file("vendor/lib1/src.rs").unwrap_err().to_string();
+        assert!(err.contains("vendor/"), "{}", err);
+        assert!(store.read_index().unwrap().entries.is_empty());
+
+        let mut cfg = store.config();
+        cfg.sparse.on_stage = SparseStaging::Expand;
+        store.write_config(&cfg).unwrap();
+        store.stage_file("vendor/lib1/src.rs").unwrap();
+        assert!(store.sparse_dirs().unwrap().is_empty());
+        assert_eq!(store.status().unwrap().staging, ["vendor/lib1/src.rs"]);
+
+        // A directory with staged changes can't be left out
+        assert!(store.set_sparse_dirs(&dirs(&["vendor"])).is_err());
+    }
+}
@@ -0,0 +1,274 @@
+//! Splitting the tip commit into several smaller ones.
+//!
+//! `split_commit` moves the branch back to the commit's parent and stages
+//! everything the commit changed. Each `commit_split_part` then commits
+//! some of what is left, keeping the original author, and stages the rest
+//! again; `split_remaining` says what that rest is. When nothing remains,
+//! `finish_split` checks that the new tip holds exactly the original tree.
+
+use crate::{Index, Store, StoreError, DELETED_BLOB};
+use anyhow::{bail, Result};
+use rune_core::Commit;
+use std::collections::BTreeMap;
+
+/// A file the split commits haven't brought to the original's content yet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitChange {
+    pub path: String,
+    /// Blob at the current tip; `None` when the file doesn't exist there
+    pub from: Option<String>,
+    /// Blob the original commit has; `None` when it deleted the file
+    pub to: Option<String>,
+}
+
+impl Store {
+    /// Start splitting `rev`, which must name the tip of the current
+    /// branch: the branch moves to its parent and its changes are staged.
+    /// Merge commits can't be split, nor, without `force`, a commit the
+    /// branch's upstream already has. Returns the original commit.
+    pub fn split_commit(&self, rev: &str, force: bool) -> Result<Commit> {
+        if self.operation_in_progress()?.is_some() {
+            return Err(StoreError::MergeInProgress { action: "split" }.into());
+        }
+        if self.head_commit().is_none() {
+            return Err(StoreError::NoCommits.into());
+        }
+        let original = self.resolve_commit(&self.revision_id(rev)?)?;
+        let branch = self.head_ref();
+        if self.read_ref(&branch).as_deref() != Some(original.id.as_str()) {
+            bail!("can only split the tip of the current branch; {} is not HEAD", short(&original.id));
+        }
+        if is_merge_commit(&original) {
+            bail!("cannot split merge commit {}: it joins two histories rather than adding changes", short(&original.id));
+        }
+        let Some(parent) = original.parent.clone() else {
+            bail!("cannot split {}: it is the first commit", short(&original.id));
+        };
+        if original.tree.is_empty() && !original.files.is_empty() {
+            bail!("cannot split {}: it was made without recorded content", short(&original.id));
+        }
+        if !force {
+            if let Some(upstream) = self.pushed_to(&original.id)? {
+                bail!(
+                    "{} is already on {}; splitting it rewrites published history (use --force to split anyway)",
+                    short(&original.id),
+                    upstream
+                );
+            }
+        }
+
+        self.write_ref(&branch, &parent)?;
+        self.update_reflog(&branch, &parent, &format!("split: start splitting {}", original.id))?;
+        self.stage_split_remaining(&original)?;
+        Ok(original)
+    }
+
+    /// What the original commit changed that the split commits don't have yet
+    pub fn split_remaining(&self, original: &Commit) -> Result<Vec<SplitChange>> {
+        let tip = self.head_commit().map(|id| self.tree_snapshot(&id)).unwrap_or_default();
+        Ok(original
+            .files
+            .iter()
+            .filter_map(|path| {
+                let to = original.tree.get(path).filter(|blob| *blob != DELETED_BLOB).cloned();
+                let from = tip.get(path).cloned();
+                (from != to).then(|| SplitChange { path: path.clone(), from, to })
+            })
+            .collect())
+    }
+
+    /// Commit `picked` (path to blob, `DELETED_BLOB` for a deletion) as the
+    /// next part of splitting `original`, with its author, then stage what
+    /// is still left
+    pub fn commit_split_part(
+        &self,
+        original: &Commit,
+        picked: &BTreeMap<String, String>,
+        message: &str,
+        committer: rune_core::Author,
+    ) -> Result<Commit> {
+        if picked.is_empty() {
+            return Err(StoreError::NothingToCommit.into());
+        }
+        let index = Index {
+            entries: picked.keys().map(|path| (path.clone(), 0)).collect(),
+            blobs: picked.clone(),
+            modes: original
+                .modes
+                .iter()
+                .filter(|(path, _)| picked.contains_key(*path))
+                .map(|(path, mode)| (path.clone(), *mode))
+                .collect(),
+            ..Index::default()
+        };
+        self.write_index(&index)?;
+        let commit = self.commit_as(message, original.author.clone(), committer, chrono::Utc::now().timestamp())?;
+        self.stage_split_remaining(original)?;
+        Ok(commit)
+    }
+
+    /// Check the split produced exactly the tree of `original` and record
+    /// it in the branch's reflog
+    pub fn finish_split(&self, original: &Commit, parts: usize) -> Result<()> {
+        let tip = self.head_commit().ok_or(StoreError::NoCommits)?;
+        if self.tree_hash(&tip) != self.tree_hash(&original.id) {
+            bail!("split commits don't add up to {}; `rune undo` restores it", short(&original.id));
+        }
+        self.update_reflog(
+            &self.head_ref(),
+            &tip,
+            &format!("split: {} into {} commits", original.id, parts),
+        )
+    }
+
+    /// Hash of the whole tree as of `commit_id`, equal for commits with the
+    /// same content whatever their history
+    pub fn tree_hash(&self, commit_id: &str) -> String {
+        let mut hasher = blake3::Hasher::new();
+        for (path, blob) in self.tree_snapshot(commit_id) {
+            hasher.update(format!("{} {}\n", blob, path).as_bytes());
+        }
+        hasher.finalize().to_hex().to_string()
+    }
+
+    /// Stage what `split_remaining` reports
+    fn stage_split_remaining(&self, original: &Commit) -> Result<()> {
+        let mut index = Index::default();
+        for change in self.split_remaining(original)? {
+            index.entries.insert(change.path.clone(), 0);
+            index.blobs.insert(change.path, change.to.unwrap_or_else(|| DELETED_BLOB.to_string()));
+        }
+        self.write_index(&index)
+    }
+
+    /// Upstream of the current branch, or a tracking ref of the same name,
+    /// that already contains `commit`
+    fn pushed_to(&self, commit: &str) -> Result<Option<String>> {
+        let Some(branch) = self.current_branch() else {
+            return Ok(None);
+        };
+        let refs = self.refs_snapshot()?;
+        let mut candidates: Vec<String> = self.branch_upstream(&branch).into_iter().collect();
+        candidates.extend(
+            refs.remotes
+                .keys()
+                .filter(|name| name.split_once('/').is_some_and(|(_, b)| b == branch))
+                .map(|name| format!("refs/remotes/{}", name)),
+        );
+        Ok(candidates.into_iter().find(|r| {
+            self.read_ref(r)
+                .is_some_and(|tip| self.ancestry(&tip).iter().any(|c| c.id == commit))
+        }))
+    }
+}
+
+/// Merges made before `merge_parent` was recorded are marked only by
+/// their message
+fn is_merge_commit(commit: &Commit) -> bool {
+    commit.merge_parent.is_some() || commit.message.starts_with("Merge branch '")
+}
+
+fn short(id: &str) -> &str {
+    &id[..id.len().min(8)]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{OperationKind, Store};
+    use crate::test_support::{author, temp_repo};
+    use std::collections::BTreeMap;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// Repository whose tip commit edits `a.txt`, adds `b.txt` and deletes
+    /// `gone.txt`
+    fn repo_with_big_commit() -> (TempDir, Store, String) {
+        let (temp, store) = temp_repo();
+        fs::write(temp.path().join("a.txt"), "a1\n").unwrap();
+        fs::write(temp.path().join("gone.txt"), "bye\n").unwrap();
+        store.stage_file("a.txt").unwrap();
+        store.stage_file("gone.txt").unwrap();
+        store.commit("base", author()).unwrap();
+
+        fs::write(temp.path().join("a.txt"), "a2\n").unwrap();
+        fs::write(temp.path().join("b.txt"), "b\n").unwrap();
+        fs::remove_file(temp.path().join("gone.txt")).unwrap();
+        for path in ["a.txt", "b.txt", "gone.txt"] {
+            store.stage_file(path).unwrap();
+        }
+        let big = store.commit("Do everything", author()).unwrap();
+        (temp, store, big.id)
+    }
+
+    /// Split `original` into a commit of `a.txt` and one of the rest
+    fn split_in_two(store: &Store, original: &str) {
+        let original = store.split_commit(original, false).unwrap();
+        let remaining = store.split_remaining(&original).unwrap();
+        assert_eq!(remaining.len(), 3);
+
+        let first: BTreeMap<String, String> =
+            remaining.iter().filter(|c| c.path == "a.txt").map(|c| (c.path.clone(), c.to.clone().unwrap())).collect();
+        store.commit_split_part(&original, &first, "Update a", author()).unwrap();
+
+        let rest: BTreeMap<String, String> = store
+            .split_remaining(&original)
+            .unwrap()
+            .into_iter()
+            .map(|c| (c.path, c.to.unwrap_or_default()))
+            .collect();
+        assert_eq!(rest.keys().collect::<Vec<_>>(), ["b.txt", "gone.txt"]);
+        store.commit_split_part(&original, &rest, "Add b, drop gone", author()).unwrap();
+        assert!(store.split_remaining(&original).unwrap().is_empty());
+        store.finish_split(&original, 2).unwrap();
+    }
+
+    #[test]
+    fn test_split_into_two_keeps_content() {
+        let (_temp, store, big) = repo_with_big_commit();
+        let base = store.resolve_commit(&big).unwrap().parent.unwrap();
+        split_in_two(&store, &big);
+
+        let tip = store.head_commit().unwrap();
+        assert_eq!(store.tree_hash(&tip), store.tree_hash(&big));
+        let history = store.ancestry(&tip);
+        let messages: Vec<&str> = history.iter().map(|c| c.message.as_str()).collect();
+        assert_eq!(messages, ["Add b, drop gone", "Update a", "base"]);
+        assert_eq!(history[1].files, ["a.txt"]);
+        assert_eq!(history[1].parent.as_deref(), Some(base.as_str()));
+        assert!(store.read_index().unwrap().entries.is_empty());
+
+        let reflog = store.reflog(&store.head_ref()).unwrap();
+        assert!(reflog.iter().any(|e| e.message == format!("split: {} into 2 commits", big)));
+    }
+
+    #[test]
+    fn test_split_refuses_pushed_and_merge_commits() {
+        let (_temp, store, big) = repo_with_big_commit();
+        store.write_ref("refs/remotes/origin/main", &big).unwrap();
+        let err = store.split_commit(&big, false).unwrap_err().to_string();
+        assert!(err.contains("origin/main"), "{}", err);
+        assert_eq!(store.head_commit().as_deref(), Some(big.as_str()));
+        store.split_commit(&big, true).unwrap();
+
+        let (temp, store, _) = repo_with_big_commit();
+        fs::write(temp.path().join("m.txt"), "m\n").unwrap();
+        store.stage_file("m.txt").unwrap();
+        let merge = store.commit("Merge branch 'topic' into main", author()).unwrap();
+        let err = store.split_commit(&merge.id, false).unwrap_err().to_string();
+        assert!(err.contains("merge commit"), "{}", err);
+    }
+
+    #[test]
+    fn test_undo_restores_original_commit() {
+        let (_temp, store, big) = repo_with_big_commit();
+        let journal = store.begin_operation(OperationKind::Split, "split", false).unwrap();
+        split_in_two(&store, &big);
+        journal.finish(&store).unwrap();
+        assert_ne!(store.head_commit().as_deref(), Some(big.as_str()));
+
+        store.undo_operation().unwrap();
+        assert_eq!(store.head_commit().as_deref(), Some(big.as_str()));
+        assert!(!store.log().iter().any(|c| c.message == "Update a"));
+        assert!(store.read_index().unwrap().entries.is_empty());
+    }
+}
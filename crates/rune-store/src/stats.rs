@@ -0,0 +1,485 @@
+//! Repository statistics: sizes and counts for `rune health`, and the
+//! per-author activity, hotspots and commit timing behind `rune stats`.
+
+use crate::{Store, DELETED_BLOB};
+use anyhow::Result;
+use rune_core::eol;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+
+/// Size and count overview of the whole repository, for `rune health`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepoStats {
+    /// Distinct paths recorded by any commit
+    pub tracked_files: usize,
+    /// Bytes used by `.rune/objects`
+    pub object_store_size: u64,
+    pub object_count: usize,
+    /// Largest tracked files in the working tree, biggest first
+    pub largest_files: Vec<FileSize>,
+    pub commits: usize,
+    pub branches: usize,
+    pub tags: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileSize {
+    pub path: String,
+    pub size: u64,
+}
+
+/// How many entries `RepoStats::largest_files` keeps
+pub const REPO_STATS_LARGEST_FILES: usize = 10;
+
+/// Parent and tree of every commit seen while streaming the log
+type Lineage = std::collections::HashMap<String, (Option<String>, BTreeMap<String, String>)>;
+
+/// How authors are told apart in `StatsReport::authors`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsBy {
+    #[default]
+    Name,
+    Email,
+}
+
+/// Options for `Store::stats`
+#[derive(Debug, Clone)]
+pub struct StatsOptions {
+    /// Only commits at or after this unix timestamp
+    pub since: Option<i64>,
+    /// Only commits at or before this unix timestamp
+    pub until: Option<i64>,
+    pub by: StatsBy,
+    /// Skip the blob diffs behind the line counts
+    pub fast: bool,
+    /// Entries kept in the hotspot and largest-file lists
+    pub limit: usize,
+}
+
+impl Default for StatsOptions {
+    fn default() -> Self {
+        Self { since: None, until: None, by: StatsBy::default(), fast: false, limit: 10 }
+    }
+}
+
+/// History statistics for `rune stats`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StatsReport {
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub commits: usize,
+    /// Most commits first
+    pub authors: Vec<AuthorStats>,
+    /// Most frequently modified files first
+    pub hotspots: Vec<FileChurn>,
+    /// Largest files in HEAD, biggest first
+    pub largest_files: Vec<FileSize>,
+    /// Commits per weekday, Monday first (UTC)
+    pub weekdays: [usize; 7],
+    /// Commits per hour of the day (UTC)
+    pub hours: [usize; 24],
+    /// Average gap between consecutive commits, `None` below two commits
+    pub avg_seconds_between_commits: Option<i64>,
+    /// Whether `lines_added`/`lines_removed` were computed (not `--fast`)
+    pub line_counts: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuthorStats {
+    pub name: String,
+    pub email: String,
+    pub commits: usize,
+    pub lines_added: usize,
+    pub lines_removed: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileChurn {
+    pub path: String,
+    pub commits: usize,
+}
+
+impl Store {
+    pub fn repo_stats(&self) -> Result<RepoStats> {
+        let log = self.log();
+        let tracked: BTreeSet<&String> = log.iter().flat_map(|c| c.files.iter()).collect();
+
+        let mut largest_files: Vec<FileSize> = tracked
+            .iter()
+            .filter_map(|path| {
+                let meta = fs::metadata(self.root.join(path)).ok()?;
+                meta.is_file().then(|| FileSize { path: path.to_string(), size: meta.len() })
+            })
+            .collect();
+        largest_files.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+        largest_files.truncate(REPO_STATS_LARGEST_FILES);
+
+        let (object_store_size, object_count) = self.object_files();
+
+        Ok(RepoStats {
+            tracked_files: tracked.len(),
+            object_store_size,
+            object_count,
+            largest_files,
+            commits: log.len(),
+            branches: self.list_branches()?.len(),
+            tags: self.list_tags()?.len(),
+        })
+    }
+
+    /// Bytes on disk under `.rune/objects`, loose objects and packs alike
+    pub fn object_store_size(&self) -> Result<u64> {
+        Ok(self.object_files().0)
+    }
+
+    /// Bytes on disk of the objects that commits in the log or the index
+    /// refer to, loose or packed. Content nothing refers to any more, such as
+    /// a file staged and then staged again with other changes, doesn't count.
+    /// Commits written before trees were recorded don't say which objects they
+    /// use, so with any of those in the log every object counts.
+    pub fn reachable_object_size(&self) -> Result<u64> {
+        let mut ids = BTreeSet::new();
+        for commit in self.log_stream()? {
+            if commit.tree.is_empty() && !commit.files.is_empty() {
+                return self.object_store_size();
+            }
+            ids.extend(commit.tree.into_values());
+        }
+        ids.extend(self.read_index()?.blobs.into_values());
+        ids.remove(DELETED_BLOB);
+        self.stored_size(&ids)
+    }
+
+    /// Total size and number of the files under `.rune/objects`
+    fn object_files(&self) -> (u64, usize) {
+        let mut size = 0;
+        let mut count = 0;
+        for entry in walkdir::WalkDir::new(self.rune_dir.join("objects")).into_iter().flatten() {
+            if entry.file_type().is_file() {
+                count += 1;
+                size += entry.metadata().map(|m| m.len()).unwrap_or(0);
+            }
+        }
+        (size, count)
+    }
+
+    /// Per-author activity, hotspots and commit timing over the commits in
+    /// the `since`/`until` window. The log is streamed; only commit ids,
+    /// parents and trees are kept, for the line counts and HEAD's files.
+    pub fn stats(&self, options: &StatsOptions) -> Result<StatsReport> {
+        use chrono::{Datelike, Timelike};
+        use std::collections::HashMap;
+
+        let mut lineage = Lineage::new();
+        let mut authors: HashMap<String, AuthorStats> = HashMap::new();
+        let mut churn: HashMap<String, usize> = HashMap::new();
+        let mut times = Vec::new();
+        let mut report = StatsReport {
+            since: options.since,
+            until: options.until,
+            line_counts: !options.fast,
+            ..Default::default()
+        };
+
+        for commit in self.log_stream()? {
+            let in_window = options.since.is_none_or(|since| commit.time >= since)
+                && options.until.is_none_or(|until| commit.time <= until);
+            if in_window {
+                let key = match options.by {
+                    StatsBy::Name => commit.author.name.clone(),
+                    StatsBy::Email => commit.author.email.to_lowercase(),
+                };
+                let author = authors.entry(key).or_insert_with(|| AuthorStats {
+                    name: commit.author.name.clone(),
+                    email: commit.author.email.clone(),
+                    ..Default::default()
+                });
+                author.commits += 1;
+                if !options.fast {
+                    for (path, blob) in &commit.tree {
+                        let old = commit
+                            .parent
+                            .as_deref()
+                            .and_then(|parent| Self::lineage_blob(&lineage, parent, path));
+                        let (added, removed) = self.blob_line_changes(old.as_deref(), blob);
+                        author.lines_added += added;
+                        author.lines_removed += removed;
+                    }
+                }
+                for path in &commit.files {
+                    *churn.entry(path.clone()).or_default() += 1;
+                }
+                if let Some(at) = chrono::DateTime::from_timestamp(commit.time, 0) {
+                    report.weekdays[at.weekday().num_days_from_monday() as usize] += 1;
+                    report.hours[at.hour() as usize] += 1;
+                }
+                times.push(commit.time);
+            }
+            lineage.insert(commit.id, (commit.parent, commit.tree));
+        }
+
+        report.commits = times.len();
+        if let (Some(first), Some(last)) = (times.iter().min(), times.iter().max()) {
+            if times.len() > 1 {
+                report.avg_seconds_between_commits = Some((last - first) / (times.len() as i64 - 1));
+            }
+        }
+
+        report.authors = authors.into_values().collect();
+        report.authors.sort_by(|a, b| b.commits.cmp(&a.commits).then_with(|| a.name.cmp(&b.name)));
+
+        report.hotspots = churn
+            .into_iter()
+            .map(|(path, commits)| FileChurn { path, commits })
+            .collect();
+        report.hotspots.sort_by(|a, b| b.commits.cmp(&a.commits).then_with(|| a.path.cmp(&b.path)));
+        report.hotspots.truncate(options.limit);
+
+        if let Some(head) = self.read_ref(&self.head_ref()) {
+            let mut snapshot = BTreeMap::new();
+            let mut current = Some(head);
+            // Bounded so a commit listed as its own ancestor can't loop forever
+            for _ in 0..lineage.len() {
+                let Some((parent, tree)) = current.and_then(|id| lineage.get(&id)) else {
+                    break;
+                };
+                for (path, blob) in tree {
+                    snapshot.entry(path.clone()).or_insert_with(|| blob.clone());
+                }
+                current = parent.clone();
+            }
+            report.largest_files = snapshot
+                .into_iter()
+                .filter(|(_, blob)| blob != DELETED_BLOB)
+                .filter_map(|(path, blob)| {
+                    let size = match fs::metadata(self.blob_path(&blob)) {
+                        Ok(meta) => meta.len(),
+                        // Packed
+                        Err(_) => self.read_blob(&blob).ok()?.len() as u64,
+                    };
+                    Some(FileSize { path, size })
+                })
+                .collect();
+            report.largest_files.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+            report.largest_files.truncate(options.limit);
+        }
+
+        Ok(report)
+    }
+
+    fn lineage_blob(
+        lineage: &Lineage,
+        commit_id: &str,
+        path: &str,
+    ) -> Option<String> {
+        let mut current = lineage.get(commit_id);
+        for _ in 0..lineage.len() {
+            let (parent, tree) = current?;
+            if let Some(blob) = tree.get(path) {
+                return Some(blob.clone()).filter(|b| b != DELETED_BLOB);
+            }
+            current = parent.as_ref().and_then(|p| lineage.get(p));
+        }
+        None
+    }
+
+    /// Lines added and removed going from blob `old` (or nothing) to `new`;
+    /// binary content counts as no lines
+    fn blob_line_changes(&self, old: Option<&str>, new: &str) -> (usize, usize) {
+        if old == Some(new) {
+            return (0, 0);
+        }
+        let read = |id: &str| self.read_blob(id).ok().filter(|c| !eol::looks_binary(c));
+        let old = match old {
+            Some(id) => match read(id) {
+                Some(content) => content,
+                None => return (0, 0),
+            },
+            None => Vec::new(),
+        };
+        let new = match new {
+            DELETED_BLOB => Vec::new(),
+            id => match read(id) {
+                Some(content) => content,
+                None => return (0, 0),
+            },
+        };
+        let hunks = rune_delta::hunks::line_hunks(
+            &String::from_utf8_lossy(&old),
+            &String::from_utf8_lossy(&new),
+            0,
+        );
+        let mut added = 0;
+        let mut removed = 0;
+        for line in hunks.iter().flat_map(|h| &h.lines) {
+            match line {
+                rune_delta::hunks::HunkLine::Add(_) => added += 1,
+                rune_delta::hunks::HunkLine::Remove(_) => removed += 1,
+                rune_delta::hunks::HunkLine::Context(_) => {}
+            }
+        }
+        (added, removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{author, temp_repo};
+    use rune_core::Author;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_repo_stats_on_seeded_repo() {
+        let (temp_dir, store) = temp_repo();
+
+        for (path, size) in [("small.txt", 10), ("big.bin", 5_000), ("mid.txt", 700)] {
+            fs::write(temp_dir.path().join(path), vec![b'x'; size]).unwrap();
+            store.stage_file(path).unwrap();
+        }
+        store.commit("first", author()).unwrap();
+        fs::write(temp_dir.path().join("mid.txt"), vec![b'y'; 800]).unwrap();
+        store.stage_file("mid.txt").unwrap();
+        store.commit("second", author()).unwrap();
+        store.create_branch("feature").unwrap();
+        store.create_lightweight_tag("v1", &store.head_commit().unwrap()).unwrap();
+        // Untracked files don't count
+        fs::write(temp_dir.path().join("scratch.txt"), vec![b'z'; 9_000]).unwrap();
+
+        let stats = store.repo_stats().unwrap();
+        assert_eq!(stats.tracked_files, 3);
+        assert_eq!(stats.commits, 2);
+        assert_eq!(stats.branches, 2);
+        assert_eq!(stats.tags, 1);
+        // Four distinct blobs: three initial files plus the second mid.txt
+        assert_eq!(stats.object_count, 4);
+        assert_eq!(stats.object_store_size, 10 + 5_000 + 700 + 800);
+
+        let largest: Vec<_> = stats.largest_files.iter().map(|f| (f.path.as_str(), f.size)).collect();
+        assert_eq!(largest, vec![("big.bin", 5_000), ("mid.txt", 800), ("small.txt", 10)]);
+    }
+
+    #[test]
+    fn test_repo_stats_empty_repo() {
+        let (_temp_dir, store) = temp_repo();
+
+        let stats = store.repo_stats().unwrap();
+        assert_eq!(stats.tracked_files, 0);
+        assert_eq!(stats.commits, 0);
+        assert!(stats.largest_files.is_empty());
+    }
+
+    // 2024-01-01 (a Monday) 09:00 UTC
+    const MONDAY_9AM: i64 = 1_704_099_600;
+    const DAY: i64 = 86_400;
+
+    /// `(author, time, [(path, content)])`
+    type ScriptedCommit<'a> = (&'a str, i64, &'a [(&'a str, &'a str)]);
+
+    /// Makes the scripted commits and then rewrites the recorded times so
+    /// the history is deterministic
+    fn scripted_repo(script: &[ScriptedCommit]) -> (TempDir, Store) {
+        let (temp_dir, store) = temp_repo();
+        for (i, (author, _, files)) in script.iter().enumerate() {
+            for (path, content) in *files {
+                fs::write(temp_dir.path().join(path), content).unwrap();
+                store.stage_file(path).unwrap();
+            }
+            let author = Author { name: author.to_string(), email: format!("{}@example.com", author.to_lowercase()) };
+            store.commit(&format!("change {}", i), author).unwrap();
+        }
+
+        let log_path = store.rune_dir.join("log.jsonl");
+        let rewritten: Vec<String> = store
+            .log()
+            .into_iter()
+            .zip(script)
+            .map(|(mut commit, (_, time, _))| {
+                commit.time = *time;
+                serde_json::to_string(&commit).unwrap()
+            })
+            .collect();
+        fs::write(&log_path, rewritten.join("\n") + "\n").unwrap();
+        (temp_dir, store)
+    }
+
+    fn fixture() -> (TempDir, Store) {
+        scripted_repo(&[
+            ("Ada", MONDAY_9AM, &[("core.rs", "a\nb\nc\n"), ("readme.md", "hi\n")]),
+            ("Bob", MONDAY_9AM + DAY, &[("core.rs", "a\nB\nc\nd\n")]),
+            ("Ada", MONDAY_9AM + 2 * DAY, &[("core.rs", "a\nB\n"), ("util.rs", "x\n")]),
+            ("Ada", MONDAY_9AM + 3 * DAY + 3_600, &[("util.rs", "x\ny\n")]),
+            ("Cy", MONDAY_9AM + 10 * DAY, &[("core.rs", "z\n")]),
+        ])
+    }
+
+    #[test]
+    fn test_per_author_counts() {
+        let (_temp_dir, store) = fixture();
+        let report = store.stats(&StatsOptions::default()).unwrap();
+
+        assert_eq!(report.commits, 5);
+        let authors: Vec<_> = report.authors.iter().map(|a| (a.name.as_str(), a.commits)).collect();
+        assert_eq!(authors, vec![("Ada", 3), ("Bob", 1), ("Cy", 1)]);
+
+        // Ada: +4 (first commit), -2 (core.rs "c", "d") +1 (util.rs), +1 (util.rs "y")
+        let ada = &report.authors[0];
+        assert_eq!((ada.lines_added, ada.lines_removed), (6, 2));
+        // Bob: "b" -> "B" and a new "d"
+        let bob = &report.authors[1];
+        assert_eq!((bob.lines_added, bob.lines_removed), (2, 1));
+    }
+
+    #[test]
+    fn test_hotspots_and_timing() {
+        let (_temp_dir, store) = fixture();
+        let report = store.stats(&StatsOptions::default()).unwrap();
+
+        let hotspots: Vec<_> = report.hotspots.iter().map(|h| (h.path.as_str(), h.commits)).collect();
+        assert_eq!(hotspots, vec![("core.rs", 4), ("util.rs", 2), ("readme.md", 1)]);
+
+        // Mon, Tue, Wed, and two Thursdays
+        assert_eq!(report.weekdays, [1, 1, 1, 2, 0, 0, 0]);
+        assert_eq!(report.hours[9], 4);
+        assert_eq!(report.hours[10], 1);
+        assert_eq!(report.avg_seconds_between_commits, Some(10 * DAY / 4));
+
+        // Largest files in HEAD come from the stored blobs
+        let largest: Vec<_> = report.largest_files.iter().map(|f| (f.path.as_str(), f.size)).collect();
+        assert_eq!(largest, vec![("util.rs", 4), ("readme.md", 3), ("core.rs", 2)]);
+    }
+
+    #[test]
+    fn test_time_window_and_fast_mode() {
+        let (_temp_dir, store) = fixture();
+        let options = StatsOptions {
+            since: Some(MONDAY_9AM + DAY),
+            until: Some(MONDAY_9AM + 3 * DAY),
+            fast: true,
+            ..Default::default()
+        };
+        let report = store.stats(&options).unwrap();
+
+        assert_eq!(report.commits, 2);
+        let authors: Vec<_> = report.authors.iter().map(|a| (a.name.as_str(), a.commits)).collect();
+        assert_eq!(authors, vec![("Ada", 1), ("Bob", 1)]);
+        assert!(!report.line_counts);
+        assert!(report.authors.iter().all(|a| a.lines_added == 0 && a.lines_removed == 0));
+    }
+
+    #[test]
+    fn test_group_by_email() {
+        let (_temp_dir, store) = scripted_repo(&[
+            ("Ada", MONDAY_9AM, &[("a.txt", "1\n")]),
+            ("ADA", MONDAY_9AM + 60, &[("a.txt", "2\n")]),
+        ]);
+        let by_name = store.stats(&StatsOptions::default()).unwrap();
+        assert_eq!(by_name.authors.len(), 2);
+
+        let by_email = store.stats(&StatsOptions { by: StatsBy::Email, ..Default::default() }).unwrap();
+        assert_eq!(by_email.authors.len(), 1);
+        assert_eq!(by_email.authors[0].commits, 2);
+    }
+}
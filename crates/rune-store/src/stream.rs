@@ -0,0 +1,678 @@
+//! History as a `git fast-import` stream
+//!
+//! `export_stream` writes every commit in the log, the blobs they recorded,
+//! and the branch and tag refs, in the format `git fast-import` reads (see
+//! `git help fast-import`). Each commit carries its rune id as an
+//! `original-oid` line, which git ignores and `import_stream` uses to keep
+//! ids stable across a round trip.
+//!
+//! `import_stream` understands the subset a history needs: `blob`, `commit`
+//! (with `M` and `D` file changes, marks or inline data), `reset` and `tag`,
+//! plus the `feature`, `progress`, `checkpoint` and `done` commands, which
+//! it skips. Rune commits have a single parent, so `merge` lines are
+//! dropped. Streams from `git fast-export` import as long as they avoid
+//! copies, renames and `deleteall`, which are rejected.
+//!
+//! Commits written before trees were recorded have no content to export;
+//! they appear with their message and parent but no file changes.
+
+use crate::{Store, StoreError, DELETED_BLOB};
+use anyhow::{anyhow, bail, Context, Result};
+use rune_core::{Author, Commit};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{BufRead, Write};
+
+/// What `import_stream` added to the repository
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportStats {
+    pub commits: usize,
+    pub blobs: usize,
+    pub branches: usize,
+    pub tags: usize,
+}
+
+impl Store {
+    /// Write the whole history as a fast-import stream to `out`
+    pub fn export_stream(&self, out: &mut impl Write) -> Result<()> {
+        let log = self.log();
+        let mut marks: HashMap<String, usize> = HashMap::new();
+        let mut blob_marks: HashMap<String, usize> = HashMap::new();
+        let mut next_mark = 1;
+
+        for commit in parents_first(&log) {
+            for blob in commit.tree.values().filter(|b| *b != DELETED_BLOB) {
+                if blob_marks.contains_key(blob) {
+                    continue;
+                }
+                let content = self.read_blob(blob)?;
+                writeln!(out, "blob\nmark :{}", next_mark)?;
+                write_data(out, &content)?;
+                blob_marks.insert(blob.clone(), next_mark);
+                next_mark += 1;
+            }
+
+            let parent = commit.parent.as_ref().and_then(|p| marks.get(p));
+            if parent.is_none() {
+                // Without `from`, fast-import would build on the ref's tip
+                writeln!(out, "reset {}", commit.branch)?;
+            }
+            writeln!(out, "commit {}\nmark :{}", commit.branch, next_mark)?;
+            writeln!(out, "original-oid {}", commit.id)?;
+            writeln!(out, "author {}", identity(&commit.author, commit.time))?;
+            writeln!(out, "committer {}", identity(&commit.committer, commit.time))?;
+            write_data(out, commit.message.as_bytes())?;
+            if let Some(parent) = parent {
+                writeln!(out, "from :{}", parent)?;
+            }
+            for (path, blob) in &commit.tree {
+                if blob == DELETED_BLOB {
+                    writeln!(out, "D {}", quote_path(path))?;
+                } else {
+                    writeln!(out, "M 100644 :{} {}", blob_marks[blob], quote_path(path))?;
+                }
+            }
+            writeln!(out)?;
+            marks.insert(commit.id.clone(), next_mark);
+            next_mark += 1;
+        }
+
+        let mut branches = self.list_branches()?;
+        branches.sort();
+        for branch in branches {
+            let name = format!("refs/heads/{}", branch);
+            if let Some(mark) = self.read_ref(&name).and_then(|id| marks.get(&id)) {
+                writeln!(out, "reset {}\nfrom :{}\n", name, mark)?;
+            }
+        }
+        for tag in self.list_tags()? {
+            let content = std::fs::read_to_string(self.rune_dir.join("refs/tags").join(&tag))?;
+            let (target, message) = content.split_once('\n').unwrap_or((content.trim(), ""));
+            let Some(commit) = log.iter().find(|c| c.id == target) else {
+                continue;
+            };
+            let mark = marks[&commit.id];
+            if message.is_empty() {
+                writeln!(out, "reset refs/tags/{}\nfrom :{}\n", tag, mark)?;
+            } else {
+                // Rune keeps no tagger, so the tagged commit's author stands in
+                writeln!(out, "tag {}\nfrom :{}", tag, mark)?;
+                writeln!(out, "tagger {}", identity(&commit.author, commit.time))?;
+                write_data(out, message.as_bytes())?;
+                writeln!(out)?;
+            }
+        }
+        writeln!(out, "done")?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Read a fast-import stream from `input` and add its commits, blobs and
+    /// refs to this repository. Commits whose id is already in the log are
+    /// not added again, so re-importing the same stream is harmless.
+    ///
+    /// Existing branches only move forward and existing tags stay where
+    /// they are; unless `force` is set, a stream that would move one any
+    /// other way is refused before any ref is written
+    pub fn import_stream(&self, input: &mut impl BufRead, force: bool) -> Result<ImportStats> {
+        let mut stats = ImportStats::default();
+        let (tips, annotated) = self.read_history(input, &mut stats)?;
+        if !force {
+            for (name, tip) in &tips {
+                if let Some(id) = tip {
+                    self.check_ref_move(name, id)?;
+                }
+            }
+            for (tag, (target, _)) in &annotated {
+                self.check_ref_move(&format!("refs/tags/{}", tag), target)?;
+            }
+        }
+        for (name, tip) in tips {
+            let Some(id) = tip else { continue };
+            if let Some(tag) = name.strip_prefix("refs/tags/") {
+                self.create_lightweight_tag(tag, &id)?;
+                stats.tags += 1;
+            } else {
+                self.write_ref(&name, &id)?;
+                stats.branches += 1;
+            }
+        }
+        for (tag, (target, message)) in annotated {
+            self.create_annotated_tag(&tag, &target, &message)?;
+            stats.tags += 1;
+        }
+        Ok(stats)
+    }
+
+    /// Fail if pointing `name` at `id` would do more than create it or
+    /// fast-forward a branch
+    fn check_ref_move(&self, name: &str, id: &str) -> Result<()> {
+        let (existing, is_tag) = match name.strip_prefix("refs/tags/") {
+            Some(tag) => (self.tag_commit(tag), true),
+            None => (self.read_ref(name), false),
+        };
+        let Some(existing) = existing else { return Ok(()) };
+        if existing == id || (!is_tag && self.is_ancestor(&existing, id)?) {
+            return Ok(());
+        }
+        Err(StoreError::RefWouldMove { name: name.to_string(), existing, new: id.to_string() }.into())
+    }
+
+    /// Add the blobs and commits of a fast-import stream but leave every
+    /// ref alone; returns where the stream's branches end, by ref name
+    pub fn import_history(&self, input: &mut impl BufRead) -> Result<BTreeMap<String, String>> {
+        let (tips, _) = self.read_history(input, &mut ImportStats::default())?;
+        Ok(tips
+            .into_iter()
+            .filter(|(name, _)| !name.starts_with("refs/tags/"))
+            .filter_map(|(name, tip)| Some((name, tip?)))
+            .collect())
+    }
+
+    /// Import objects and commits; returns the ref tips and annotated tags
+    /// the stream sets, without writing them
+    #[allow(clippy::type_complexity)]
+    fn read_history(
+        &self,
+        input: &mut impl BufRead,
+        stats: &mut ImportStats,
+    ) -> Result<(BTreeMap<String, Option<String>>, BTreeMap<String, (String, String)>)> {
+        let mut reader = StreamReader { input, line_no: 0, pending: None };
+        let mut known: HashSet<String> = self.log().into_iter().map(|c| c.id).collect();
+        // Mark to rune blob or commit id
+        let mut marks: HashMap<String, String> = HashMap::new();
+        let mut tips: BTreeMap<String, Option<String>> = BTreeMap::new();
+        let mut annotated: BTreeMap<String, (String, String)> = BTreeMap::new();
+
+        while let Some(line) = reader.next_line()? {
+            let (command, rest) = line.split_once(' ').unwrap_or((line.as_str(), ""));
+            match command {
+                "" => {}
+                "blob" => {
+                    let mark = reader.optional("mark")?;
+                    reader.optional("original-oid")?;
+                    let content = reader.data()?;
+                    let id = self.write_blob(&content)?;
+                    if let Some(mark) = mark {
+                        marks.insert(mark, id);
+                    }
+                    stats.blobs += 1;
+                }
+                "commit" => {
+                    let branch = rest.to_string();
+                    let mut commit = self.read_commit(&mut reader, &marks, &tips, &branch)?;
+                    if let Some(mark) = commit.mark.take() {
+                        marks.insert(mark, commit.commit.id.clone());
+                    }
+                    tips.insert(branch, Some(commit.commit.id.clone()));
+                    if known.insert(commit.commit.id.clone()) {
+                        self.append_log(&commit.commit)?;
+                        stats.commits += 1;
+                    }
+                }
+                "reset" => {
+                    let from = reader.optional("from")?;
+                    let target = from.map(|f| resolve(&f, &marks, &tips)).transpose()?;
+                    tips.insert(rest.to_string(), target);
+                }
+                "tag" => {
+                    reader.optional("mark")?;
+                    let from = reader
+                        .optional("from")?
+                        .ok_or_else(|| reader.error("tag without 'from'"))?;
+                    let target = resolve(&from, &marks, &tips)?;
+                    reader.optional("original-oid")?;
+                    reader.optional("tagger")?;
+                    let message = String::from_utf8(reader.data()?)?;
+                    annotated.insert(rest.to_string(), (target, message));
+                }
+                "feature" | "option" | "progress" | "checkpoint" | "done" => {}
+                _ if command.starts_with('#') => {}
+                _ => return Err(reader.error(&format!("unsupported command '{}'", command))),
+            }
+        }
+        Ok((tips, annotated))
+    }
+
+    fn read_commit(
+        &self,
+        reader: &mut StreamReader<impl BufRead>,
+        marks: &HashMap<String, String>,
+        tips: &BTreeMap<String, Option<String>>,
+        branch: &str,
+    ) -> Result<ImportedCommit> {
+        let mark = reader.optional("mark")?;
+        let original = reader.optional("original-oid")?;
+        let author = reader.optional("author")?;
+        let committer = reader
+            .optional("committer")?
+            .ok_or_else(|| reader.error("commit without 'committer'"))?;
+        reader.optional("encoding")?;
+        let message = String::from_utf8(reader.data()?).context("commit message is not UTF-8")?;
+        let (committer, time) =
+            parse_identity(&committer).ok_or_else(|| reader.error("malformed committer"))?;
+        let author = match author {
+            Some(author) => parse_identity(&author).ok_or_else(|| reader.error("malformed author"))?.0,
+            None => committer.clone(),
+        };
+
+        let parent = match reader.optional("from")? {
+            Some(from) => Some(resolve(&from, marks, tips)?),
+            None => tips.get(branch).cloned().flatten(),
+        };
+        while reader.optional("merge")?.is_some() {}
+
+        let mut tree = BTreeMap::new();
+        while let Some(line) = reader.next_line()? {
+            if line.is_empty() {
+                break;
+            }
+            if let Some(path) = line.strip_prefix("D ") {
+                tree.insert(unquote_path(path)?, DELETED_BLOB.to_string());
+            } else if let Some(change) = line.strip_prefix("M ") {
+                let mut parts = change.splitn(3, ' ');
+                let (_mode, dataref, path) = match (parts.next(), parts.next(), parts.next()) {
+                    (Some(mode), Some(dataref), Some(path)) => (mode, dataref, path),
+                    _ => return Err(reader.error("malformed 'M' line")),
+                };
+                let blob = if dataref == "inline" {
+                    self.write_blob(&reader.data()?)?
+                } else {
+                    marks
+                        .get(dataref)
+                        .cloned()
+                        .ok_or_else(|| reader.error(&format!("unknown blob '{}'", dataref)))?
+                };
+                tree.insert(unquote_path(path)?, blob);
+            } else {
+                // The next command; commits may end without a blank line
+                reader.push_back(line);
+                break;
+            }
+        }
+
+        let files: Vec<String> = tree.keys().cloned().collect();
+        let id = original.unwrap_or_else(|| {
+            let hash = blake3::hash(format!("{}{}{:?}{}", message, author.email, files, time).as_bytes());
+            hex::encode(hash.as_bytes())
+        });
+        Ok(ImportedCommit {
+            mark,
+            commit: Commit {
+                id,
+                message,
+                author,
+                committer,
+                time,
+                parent,
+                merge_parent: None,
+                files,
+                branch: branch.to_string(),
+                tree,
+                modes: BTreeMap::new(),
+            },
+        })
+    }
+}
+
+struct ImportedCommit {
+    mark: Option<String>,
+    commit: Commit,
+}
+
+/// `log` reordered so every commit follows its parent
+fn parents_first(log: &[Commit]) -> Vec<&Commit> {
+    let by_id: HashMap<&str, &Commit> = log.iter().map(|c| (c.id.as_str(), c)).collect();
+    let mut done: HashSet<&str> = HashSet::new();
+    let mut ordered = Vec::with_capacity(log.len());
+    for commit in log {
+        let mut chain = Vec::new();
+        let mut current = Some(commit);
+        while let Some(c) = current.filter(|c| !done.contains(c.id.as_str())) {
+            done.insert(&c.id);
+            chain.push(c);
+            current = c.parent.as_deref().and_then(|p| by_id.get(p).copied());
+        }
+        ordered.extend(chain.into_iter().rev());
+    }
+    ordered
+}
+
+/// `:<mark>`, a ref written earlier in the stream, or a commit id
+fn resolve(
+    from: &str,
+    marks: &HashMap<String, String>,
+    tips: &BTreeMap<String, Option<String>>,
+) -> Result<String> {
+    if from.starts_with(':') {
+        return marks.get(from).cloned().ok_or_else(|| anyhow!("unknown mark '{}'", from));
+    }
+    match tips.get(from) {
+        Some(tip) => tip.clone().ok_or_else(|| anyhow!("'{}' has no commits yet", from)),
+        None => Ok(from.to_string()),
+    }
+}
+
+fn identity(author: &Author, time: i64) -> String {
+    format!("{} <{}> {} +0000", author.name, author.email, time)
+}
+
+/// `Name <email> <time> <tz>` into the author and the unix time
+fn parse_identity(line: &str) -> Option<(Author, i64)> {
+    let (name, rest) = line.split_once('<')?;
+    let (email, when) = rest.split_once('>')?;
+    let time = when.split_whitespace().next()?.parse().ok()?;
+    let author = Author { name: name.trim_end().to_string(), email: email.to_string() };
+    Some((author, time))
+}
+
+fn write_data(out: &mut impl Write, content: &[u8]) -> Result<()> {
+    writeln!(out, "data {}", content.len())?;
+    out.write_all(content)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Paths that would be misread unquoted get git's C-style quoting
+fn quote_path(path: &str) -> String {
+    if !path.starts_with('"') && !path.contains(['\n', '\\']) {
+        return path.to_string();
+    }
+    let mut quoted = String::from("\"");
+    for c in path.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn unquote_path(path: &str) -> Result<String> {
+    let Some(inner) = path.strip_prefix('"').and_then(|p| p.strip_suffix('"')) else {
+        return Ok(path.to_string());
+    };
+    let mut bytes = Vec::with_capacity(inner.len());
+    let mut chars = inner.bytes();
+    while let Some(b) = chars.next() {
+        if b != b'\\' {
+            bytes.push(b);
+            continue;
+        }
+        match chars.next() {
+            Some(b'n') => bytes.push(b'\n'),
+            Some(b't') => bytes.push(b'\t'),
+            Some(b'"') => bytes.push(b'"'),
+            Some(b'\\') => bytes.push(b'\\'),
+            // Octal escapes, which git uses for non-ASCII bytes
+            Some(d @ b'0'..=b'3') => {
+                let (d2, d3) = (chars.next(), chars.next());
+                let octal = [Some(d), d2, d3]
+                    .into_iter()
+                    .map(|d| d.filter(u8::is_ascii_digit).map(|d| d - b'0'))
+                    .try_fold(0u8, |acc, d| d.map(|d| acc * 8 + d));
+                bytes.push(octal.ok_or_else(|| anyhow!("bad escape in path {}", path))?);
+            }
+            _ => bail!("bad escape in path {}", path),
+        }
+    }
+    Ok(String::from_utf8(bytes)?)
+}
+
+/// Line-oriented reading with one line of look-ahead, since most commands
+/// end at whatever line does not belong to them
+struct StreamReader<'a, R> {
+    input: &'a mut R,
+    line_no: usize,
+    pending: Option<String>,
+}
+
+impl<R: BufRead> StreamReader<'_, R> {
+    fn next_line(&mut self) -> Result<Option<String>> {
+        if let Some(line) = self.pending.take() {
+            return Ok(Some(line));
+        }
+        let mut line = String::new();
+        if self.input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        self.line_no += 1;
+        if line.ends_with('\n') {
+            line.pop();
+        }
+        Ok(Some(line))
+    }
+
+    fn push_back(&mut self, line: String) {
+        self.pending = Some(line);
+    }
+
+    /// The argument of the next line if it is `keyword`, else `None`
+    fn optional(&mut self, keyword: &str) -> Result<Option<String>> {
+        let Some(line) = self.next_line()? else {
+            return Ok(None);
+        };
+        match line.strip_prefix(keyword) {
+            Some(rest) if rest.is_empty() || rest.starts_with(' ') => {
+                Ok(Some(rest.trim_start().to_string()))
+            }
+            _ => {
+                self.push_back(line);
+                Ok(None)
+            }
+        }
+    }
+
+    /// A `data <count>` or `data <<DELIM` block
+    fn data(&mut self) -> Result<Vec<u8>> {
+        let header = self.next_line()?.unwrap_or_default();
+        let Some(spec) = header.strip_prefix("data ") else {
+            return Err(self.error("expected 'data'"));
+        };
+        if let Some(delimiter) = spec.strip_prefix("<<") {
+            let mut content = Vec::new();
+            loop {
+                let line = self.next_line()?.ok_or_else(|| self.error("unterminated data"))?;
+                if line == delimiter {
+                    return Ok(content);
+                }
+                content.extend_from_slice(line.as_bytes());
+                content.push(b'\n');
+            }
+        }
+        let len: usize = spec.parse().map_err(|_| self.error("bad data length"))?;
+        let mut content = vec![0; len];
+        self.input
+            .read_exact(&mut content)
+            .with_context(|| format!("stream ends inside data after line {}", self.line_no))?;
+        self.line_no += content.iter().filter(|&&b| b == b'\n').count();
+        // The newline after the data is optional
+        if self.input.fill_buf()?.first() == Some(&b'\n') {
+            self.input.consume(1);
+            self.line_no += 1;
+        }
+        Ok(content)
+    }
+
+    fn error(&self, message: &str) -> anyhow::Error {
+        anyhow!("fast-import stream line {}: {}", self.line_no, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{author, temp_repo};
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn commit_file(store: &Store, path: &str, content: &str, message: &str) -> Commit {
+        let full = store.root.join(path);
+        fs::create_dir_all(full.parent().unwrap()).unwrap();
+        fs::write(full, content).unwrap();
+        store.stage_file(path).unwrap();
+        store.commit(message, author()).unwrap()
+    }
+
+    /// Two branches, a deletion, an odd file name and both kinds of tag
+    fn sample_repo() -> (TempDir, Store) {
+        let (temp, store) = temp_repo();
+        let first = commit_file(&store, "README.md", "# demo\n", "Initial commit");
+        commit_file(&store, "src/lib.rs", "pub fn f() {}\n", "Add library\n\nWith a body.");
+        store.create_lightweight_tag("v0.1", &first.id).unwrap();
+
+        store.create_branch("feature").unwrap();
+        store.checkout_branch("feature").unwrap();
+        commit_file(&store, "back\\slash.txt", "odd name\n", "Odd path");
+        fs::remove_file(temp.path().join("README.md")).unwrap();
+        store.stage_file("README.md").unwrap();
+        let last = store.commit("Drop readme", author()).unwrap();
+        store.create_annotated_tag("v1.0", &last.id, "First release").unwrap();
+        store.checkout_branch("main").unwrap();
+        (temp, store)
+    }
+
+    fn refs(store: &Store) -> Vec<(String, Option<String>)> {
+        let mut refs: Vec<_> = store
+            .list_branches()
+            .unwrap()
+            .into_iter()
+            .map(|b| {
+                let id = store.read_ref(&format!("refs/heads/{}", b));
+                (b, id)
+            })
+            .collect();
+        refs.sort();
+        for tag in store.list_tags().unwrap() {
+            let content = fs::read_to_string(store.rune_dir.join("refs/tags").join(&tag)).unwrap();
+            refs.push((format!("tag {}", tag), Some(content)));
+        }
+        refs
+    }
+
+    #[test]
+    fn test_round_trip_preserves_history() {
+        let (_temp, store) = sample_repo();
+        let mut stream = Vec::new();
+        store.export_stream(&mut stream).unwrap();
+
+        let (_target_dir, target) = temp_repo();
+        let stats = target.import_stream(&mut stream.as_slice(), false).unwrap();
+        assert_eq!(stats, ImportStats { commits: 4, blobs: 3, branches: 2, tags: 2 });
+
+        let original = store.log();
+        let imported = target.log();
+        assert_eq!(
+            serde_json::to_value(&imported).unwrap(),
+            serde_json::to_value(&original).unwrap()
+        );
+        for commit in &original {
+            assert_eq!(target.tree_snapshot(&commit.id), store.tree_snapshot(&commit.id));
+            for (path, blob) in store.tree_snapshot(&commit.id) {
+                assert_eq!(
+                    target.read_blob(&target.blob_at(&commit.id, &path).unwrap()).unwrap(),
+                    store.read_blob(&blob).unwrap()
+                );
+            }
+        }
+        assert_eq!(refs(&target), refs(&store));
+
+        // Importing again adds nothing
+        let again = target.import_stream(&mut stream.as_slice(), false).unwrap();
+        assert_eq!(again.commits, 0);
+        assert_eq!(target.log().len(), original.len());
+    }
+
+    #[test]
+    fn test_import_only_fast_forwards_existing_refs() {
+        let (temp, store) = sample_repo();
+        let mut stream = Vec::new();
+        store.export_stream(&mut stream).unwrap();
+
+        let (_target_dir, target) = temp_repo();
+        let local = commit_file(&target, "local.txt", "mine\n", "Local work");
+        let Err(err) = target.import_stream(&mut stream.as_slice(), false) else {
+            panic!("import rewound main");
+        };
+        assert!(
+            matches!(err.downcast_ref(), Some(StoreError::RefWouldMove { name, .. }) if name == "refs/heads/main"),
+            "{}",
+            err
+        );
+        assert_eq!(target.read_ref("refs/heads/main"), Some(local.id));
+        assert!(target.list_tags().unwrap().is_empty());
+
+        target.import_stream(&mut stream.as_slice(), true).unwrap();
+        assert_eq!(refs(&target), refs(&store));
+
+        // New commits on top of what was imported move the branch forward
+        fs::write(temp.path().join("more.txt"), "more\n").unwrap();
+        store.stage_file("more.txt").unwrap();
+        let next = store.commit("More", author()).unwrap();
+        let mut stream = Vec::new();
+        store.export_stream(&mut stream).unwrap();
+        target.import_stream(&mut stream.as_slice(), false).unwrap();
+        assert_eq!(target.read_ref("refs/heads/main"), Some(next.id.clone()));
+
+        // A tag never moves without force
+        store.delete_tag("v0.1").unwrap();
+        store.create_lightweight_tag("v0.1", &next.id).unwrap();
+        let mut stream = Vec::new();
+        store.export_stream(&mut stream).unwrap();
+        let err = target.import_stream(&mut stream.as_slice(), false).unwrap_err();
+        assert!(err.to_string().contains("'refs/tags/v0.1'"), "{}", err);
+    }
+
+    #[test]
+    fn test_imports_git_style_stream() {
+        let stream = b"feature done\n\
+            blob\nmark :1\ndata 6\nhello\n\n\
+            commit refs/heads/main\nmark :2\n\
+            author A U Thor <author@example.com> 1700000000 +0100\n\
+            committer C O Mitter <committer@example.com> 1700000100 +0100\n\
+            data <<EOF\nFirst\nEOF\n\
+            M 100644 :1 \"dir/with\\\"quote\"\n\
+            commit refs/heads/main\n\
+            committer C O Mitter <committer@example.com> 1700000200 +0100\n\
+            data 6\nSecondM 100644 inline notes.txt\ndata 3\nhi\n\n\
+            D \"dir/with\\\"quote\"\n\n\
+            reset refs/tags/v1\nfrom :2\n\n\
+            done\n";
+        let (_temp, store) = temp_repo();
+        let stats = store.import_stream(&mut stream.as_slice(), false).unwrap();
+        assert_eq!(stats.commits, 2);
+
+        let log = store.log();
+        assert_eq!(log[0].message, "First\n");
+        assert_eq!(log[0].author.name, "A U Thor");
+        assert_eq!(log[0].committer.name, "C O Mitter");
+        // Rune keeps one time per commit, the committer's
+        assert_eq!(log[0].time, 1_700_000_100);
+        assert_eq!(log[1].message, "Second");
+        assert_eq!(log[1].parent.as_deref(), Some(log[0].id.as_str()));
+        assert_eq!(log[1].author.email, "committer@example.com");
+
+        let tip = store.read_ref("refs/heads/main").unwrap();
+        assert_eq!(tip, log[1].id);
+        let snapshot = store.tree_snapshot(&tip);
+        assert_eq!(snapshot.keys().collect::<Vec<_>>(), ["notes.txt"]);
+        assert_eq!(store.read_blob(&snapshot["notes.txt"]).unwrap(), b"hi\n");
+        assert_eq!(
+            store.read_blob(&store.blob_at(&log[0].id, "dir/with\"quote").unwrap()).unwrap(),
+            b"hello\n"
+        );
+        assert_eq!(store.tag_commit("v1").as_deref(), Some(log[0].id.as_str()));
+    }
+
+    #[test]
+    fn test_rejects_unsupported_commands() {
+        let (_temp, store) = temp_repo();
+        let stream = b"commit refs/heads/main\n\
+            committer A <a@example.com> 1 +0000\ndata 1\nx\n\
+            R old new\n";
+        let err = store.import_stream(&mut stream.as_slice(), false).unwrap_err();
+        assert!(err.to_string().contains("unsupported command 'R'"), "{}", err);
+    }
+}
@@ -0,0 +1,215 @@
+//! Signed annotated tags
+//!
+//! An annotated tag file holds the commit on its first line and the message
+//! below it. A signed tag adds an Ed25519 signature block at the end:
+//!
+//! ```text
+//! -----BEGIN RUNE SIGNATURE-----
+//! signer Ada <ada@example.com>
+//! key <public key, hex>
+//! sig <signature, hex>
+//! -----END RUNE SIGNATURE-----
+//! ```
+//!
+//! What is signed is the tag object: the commit, the tag's name, the signer
+//! and the message, so neither retargeting, renaming nor rewording the tag
+//! keeps the signature valid. A valid signature only counts when its key is
+//! listed for the signer under `[signing] allowed_signers`.
+
+use crate::{refname, Store, StoreError};
+use anyhow::{anyhow, Result};
+use ring::rand::SystemRandom;
+use ring::signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519};
+
+const BEGIN: &str = "-----BEGIN RUNE SIGNATURE-----";
+const END: &str = "-----END RUNE SIGNATURE-----";
+
+/// An Ed25519 key that signs tags for `signer`
+pub struct TagKey {
+    pair: Ed25519KeyPair,
+    signer: String,
+}
+
+impl TagKey {
+    /// A new key as a PKCS#8 document, to be saved and loaded with
+    /// `from_pkcs8`
+    pub fn generate() -> Result<Vec<u8>> {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+            .map_err(|_| anyhow!("could not generate a signing key"))?;
+        Ok(pkcs8.as_ref().to_vec())
+    }
+
+    pub fn from_pkcs8(pkcs8: &[u8], signer: impl Into<String>) -> Result<Self> {
+        let pair = Ed25519KeyPair::from_pkcs8(pkcs8).map_err(|e| anyhow!("not an Ed25519 PKCS#8 key: {}", e))?;
+        Ok(Self { pair, signer: signer.into() })
+    }
+
+    /// The public key as hex, as `[signing] allowed_signers` lists it
+    pub fn public_key(&self) -> String {
+        hex::encode(self.pair.public_key().as_ref())
+    }
+
+    pub fn signer(&self) -> &str {
+        &self.signer
+    }
+}
+
+/// Who signed a tag whose signature checked out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagSignature {
+    pub signer: String,
+    /// Public key, hex
+    pub key: String,
+}
+
+/// The bytes a tag's signature covers
+fn tag_object(name: &str, commit: &str, signer: &str, message: &str) -> Vec<u8> {
+    format!("object {}\ntag {}\nsigner {}\n\n{}", commit, name, signer, message).into_bytes()
+}
+
+/// A tag file split into commit, message and signature block, if any
+fn split_signature(content: &str) -> (&str, &str, Option<&str>) {
+    let (commit, rest) = content.split_once('\n').unwrap_or((content, ""));
+    match rest.rfind(BEGIN).filter(|&i| i == 0 || rest[..i].ends_with('\n')) {
+        Some(start) => {
+            let message = &rest[..start];
+            (commit.trim(), message.strip_suffix('\n').unwrap_or(message), Some(&rest[start..]))
+        }
+        None => (commit.trim(), rest, None),
+    }
+}
+
+/// Signer, key and signature from a signature block
+fn block_fields(block: &str) -> Option<(&str, &str, &str)> {
+    let lines: Vec<&str> = block.lines().collect();
+    let [BEGIN, signer, key, sig, END] = lines.as_slice() else {
+        return None;
+    };
+    Some((signer.strip_prefix("signer ")?, key.strip_prefix("key ")?, sig.strip_prefix("sig ")?))
+}
+
+impl Store {
+    /// Create an annotated tag signed with `key`
+    pub fn create_signed_tag(&self, name: &str, commit: &str, message: &str, key: &TagKey) -> Result<()> {
+        refname::validate_ref_name(name)?;
+        let sig = key.pair.sign(&tag_object(name, commit, &key.signer, message));
+        let content = format!(
+            "{}\n{}\n{}\nsigner {}\nkey {}\nsig {}\n{}\n",
+            commit,
+            message,
+            BEGIN,
+            key.signer,
+            key.public_key(),
+            hex::encode(sig.as_ref()),
+            END
+        );
+        self.write_tag(name, &content)
+    }
+
+    /// Check the signature on tag `name` and return who made it. Fails when
+    /// the tag isn't signed, was changed after signing, or was signed with
+    /// a key `[signing] allowed_signers` doesn't list for the signer.
+    pub fn verify_tag(&self, name: &str) -> Result<TagSignature> {
+        let loose = self.ref_file(&format!("refs/tags/{}", name)).and_then(|p| std::fs::read_to_string(p).ok());
+        let Some(content) = loose else {
+            if self.tag_exists(name) {
+                return Err(StoreError::TagNotSigned(name.to_string()).into());
+            }
+            return Err(StoreError::TagNotFound(name.to_string()).into());
+        };
+        let bad = || StoreError::BadTagSignature(name.to_string());
+        let (commit, message, block) = split_signature(&content);
+        let block = block.ok_or_else(|| StoreError::TagNotSigned(name.to_string()))?;
+        let (signer, key, sig) = block_fields(block).ok_or_else(bad)?;
+        let public = hex::decode(key).map_err(|_| bad())?;
+        let sig = hex::decode(sig).map_err(|_| bad())?;
+        UnparsedPublicKey::new(&ED25519, &public)
+            .verify(&tag_object(name, commit, signer, message), &sig)
+            .map_err(|_| bad())?;
+        let allowed = self.config().signing.allowed_signers;
+        if allowed.get(key).map(String::as_str) != Some(signer) {
+            return Err(StoreError::UntrustedTagSigner {
+                tag: name.to_string(),
+                signer: signer.to_string(),
+                key: key.to_string(),
+            }
+            .into());
+        }
+        Ok(TagSignature { signer: signer.to_string(), key: key.to_string() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TagKey;
+    use crate::{Store, StoreError};
+    use crate::test_support::{author, temp_repo};
+    use std::fs;
+    use tempfile::TempDir;
+
+    const SIGNER: &str = "Test <test@example.com>";
+
+    /// A repository with one commit and a key its config trusts
+    fn signing_repo() -> (TempDir, Store, String, TagKey) {
+        let (temp, store) = temp_repo();
+        fs::write(temp.path().join("a.txt"), "a\n").unwrap();
+        store.stage_file("a.txt").unwrap();
+        let commit = store.commit("first", author()).unwrap().id;
+        let key = TagKey::from_pkcs8(&TagKey::generate().unwrap(), SIGNER).unwrap();
+        let mut cfg = store.config();
+        cfg.signing.allowed_signers.insert(key.public_key(), SIGNER.into());
+        store.write_config(&cfg).unwrap();
+        (temp, store, commit, key)
+    }
+
+    fn store_error(err: anyhow::Error) -> StoreError {
+        err.downcast::<StoreError>().unwrap()
+    }
+
+    #[test]
+    fn test_signed_tag_verifies_and_still_points_at_its_commit() {
+        let (_temp, store, commit, key) = signing_repo();
+        store.create_signed_tag("v1.0", &commit, "Release 1.0\n\nNotes", &key).unwrap();
+
+        let signature = store.verify_tag("v1.0").unwrap();
+        assert_eq!(signature.signer, SIGNER);
+        assert_eq!(signature.key, key.public_key());
+        assert_eq!(store.tag_commit("v1.0"), Some(commit.clone()));
+
+        store.create_annotated_tag("plain", &commit, "not signed").unwrap();
+        store.create_lightweight_tag("light", &commit).unwrap();
+        assert_eq!(store_error(store.verify_tag("plain").unwrap_err()), StoreError::TagNotSigned("plain".into()));
+        assert_eq!(store_error(store.verify_tag("light").unwrap_err()), StoreError::TagNotSigned("light".into()));
+        assert_eq!(store_error(store.verify_tag("nope").unwrap_err()), StoreError::TagNotFound("nope".into()));
+    }
+
+    #[test]
+    fn test_tampered_tag_no_longer_verifies() {
+        let (temp, store, commit, key) = signing_repo();
+        store.create_signed_tag("v1.0", &commit, "Release 1.0", &key).unwrap();
+        let tag_file = temp.path().join(".rune/refs/tags/v1.0");
+        let signed = fs::read_to_string(&tag_file).unwrap();
+
+        let bad = StoreError::BadTagSignature("v1.0".into());
+        // Reworded
+        fs::write(&tag_file, signed.replace("Release 1.0", "Release 1.1")).unwrap();
+        assert_eq!(store_error(store.verify_tag("v1.0").unwrap_err()), bad);
+        // Pointed at another commit
+        let other = "0".repeat(commit.len());
+        fs::write(&tag_file, signed.replacen(&commit, &other, 1)).unwrap();
+        assert_eq!(store_error(store.verify_tag("v1.0").unwrap_err()), bad);
+        // Copied under another name
+        fs::write(&tag_file, &signed).unwrap();
+        fs::write(temp.path().join(".rune/refs/tags/v2.0"), &signed).unwrap();
+        assert_eq!(store_error(store.verify_tag("v2.0").unwrap_err()), StoreError::BadTagSignature("v2.0".into()));
+        assert!(store.verify_tag("v1.0").is_ok());
+
+        // Signed properly, but with a key nobody listed
+        let stranger = TagKey::from_pkcs8(&TagKey::generate().unwrap(), SIGNER).unwrap();
+        store.create_signed_tag("v1.0", &commit, "Release 1.0", &stranger).unwrap();
+        assert!(matches!(
+            store_error(store.verify_tag("v1.0").unwrap_err()),
+            StoreError::UntrustedTagSigner { key, .. } if key == stranger.public_key()
+        ));
+    }
+}
@@ -0,0 +1,19 @@
+//! Fixtures shared by the unit tests
+
+use crate::Store;
+use rune_core::Author;
+use tempfile::TempDir;
+
+/// Who the test commits are by
+pub(crate) fn author() -> Author {
+    Author { name: "Test".into(), email: "test@example.com".into() }
+}
+
+/// A new repository with no commits in a temporary directory; the
+/// directory goes away when the `TempDir` is dropped
+pub(crate) fn temp_repo() -> (TempDir, Store) {
+    let temp = TempDir::new().unwrap();
+    let store = Store::open(temp.path()).unwrap();
+    store.create().unwrap();
+    (temp, store)
+}
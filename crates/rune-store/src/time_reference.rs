@@ -0,0 +1,97 @@
+//! Points in time as people write them for `--since` and `--until`:
+//! `today`, `yesterday`, `last-week`, `3 days ago`, or an ISO date.
+//!
+//! Days start at midnight UTC, which is also how commit times are stored.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+/// What `parse_time_reference` accepts, for error messages
+const ACCEPTED: &str = "today, yesterday, last-week, '<N> days ago' or a date like 2024-05-01";
+
+/// The instant `s` refers to, relative to now
+pub fn parse_time_reference(s: &str) -> Result<DateTime<Utc>> {
+    parse_time_reference_at(s, Utc::now())
+}
+
+/// The instant `s` refers to when it is `now`: the start of the day for
+/// `today`, `yesterday` and dates, an exact instant for `last-week`,
+/// `N <unit>s ago` and RFC 3339 timestamps
+pub fn parse_time_reference_at(s: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    let phrase = s.trim().to_ascii_lowercase();
+    let start_of_today = now.date_naive().and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+    match phrase.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["now"] => return Ok(now),
+        ["today"] => return Ok(start_of_today),
+        ["yesterday"] => return Ok(start_of_today - Duration::days(1)),
+        ["last-week" | "last_week"] | ["last", "week"] => return Ok(now - Duration::weeks(1)),
+        [count, unit, "ago"] => {
+            let count: u32 = count
+                .parse()
+                .map_err(|_| anyhow!("'{}' is not a number of {} in '{}'", count, unit, s.trim()))?;
+            let unit_seconds: i64 = match unit.trim_end_matches('s') {
+                "minute" | "min" => 60,
+                "hour" => 60 * 60,
+                "day" => 24 * 60 * 60,
+                "week" => 7 * 24 * 60 * 60,
+                _ => return Err(anyhow!("unknown unit '{}' in '{}'; use minutes, hours, days or weeks", unit, s.trim())),
+            };
+            return now
+                .checked_sub_signed(Duration::seconds(unit_seconds * i64::from(count)))
+                .ok_or_else(|| anyhow!("'{}' is too far back", s.trim()));
+        }
+        _ => {}
+    }
+
+    let s = s.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc());
+    }
+    if let Ok(instant) = DateTime::parse_from_rfc3339(s) {
+        return Ok(instant.with_timezone(&Utc));
+    }
+    Err(anyhow!("can't tell what time '{}' means; use {}", s, ACCEPTED))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, 15, 14, 30, 0).unwrap()
+    }
+
+    fn at(s: &str) -> DateTime<Utc> {
+        parse_time_reference_at(s, now()).unwrap()
+    }
+
+    #[test]
+    fn test_days_start_at_midnight() {
+        assert_eq!(at("today"), Utc.with_ymd_and_hms(2024, 5, 15, 0, 0, 0).unwrap());
+        assert_eq!(at(" Yesterday "), Utc.with_ymd_and_hms(2024, 5, 14, 0, 0, 0).unwrap());
+        assert_eq!(at("2024-02-29"), Utc.with_ymd_and_hms(2024, 2, 29, 0, 0, 0).unwrap());
+        assert_eq!(at("2024-05-01T08:00:00+02:00"), Utc.with_ymd_and_hms(2024, 5, 1, 6, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn test_relative_phrases_count_back_from_now() {
+        assert_eq!(at("last-week"), Utc.with_ymd_and_hms(2024, 5, 8, 14, 30, 0).unwrap());
+        assert_eq!(at("last week"), at("last-week"));
+        assert_eq!(at("3 days ago"), Utc.with_ymd_and_hms(2024, 5, 12, 14, 30, 0).unwrap());
+        assert_eq!(at("1 day ago"), Utc.with_ymd_and_hms(2024, 5, 14, 14, 30, 0).unwrap());
+        assert_eq!(at("2 hours ago"), Utc.with_ymd_and_hms(2024, 5, 15, 12, 30, 0).unwrap());
+        assert_eq!(at("2 weeks ago"), Utc.with_ymd_and_hms(2024, 5, 1, 14, 30, 0).unwrap());
+        assert_eq!(at("now"), now());
+    }
+
+    #[test]
+    fn test_gibberish_is_a_clear_error() {
+        for input in ["whenever", "2024-13-01", "", "three days ago", "3 fortnights ago"] {
+            let err = parse_time_reference_at(input, now()).unwrap_err().to_string();
+            assert!(err.contains(input.trim()), "{}: {}", input, err);
+        }
+        let err = parse_time_reference_at("whenever", now()).unwrap_err().to_string();
+        assert!(err.contains("yesterday") && err.contains("days ago"), "{}", err);
+    }
+}
@@ -0,0 +1,209 @@
+//! Bringing another line of history into the current branch without a
+//! merge commit, as `rune pull` does: fast-forwarding to it, or replaying
+//! the branch's own commits on top of it.
+//!
+//! Both check everything before they touch anything, so a refusal leaves
+//! the working tree and the branch as they were; so does cancelling the
+//! `_with` forms while they write files.
+
+use crate::{CancelToken, CheckoutProgress, Store, StoreError};
+use anyhow::{bail, Result};
+use chrono::Utc;
+use rune_core::Commit;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// What `Store::replay_onto` did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayResult {
+    /// The branch's own commits, this many, now sit on top of the target
+    Replayed(usize),
+    /// Both sides changed these files differently; nothing was changed
+    Conflicts(Vec<String>),
+}
+
+impl Store {
+    /// Move the current branch forward to `target`, which has to descend
+    /// from its tip, and update the working files that differ between them
+    pub fn fast_forward(&self, target: &str, reason: &str) -> Result<()> {
+        self.fast_forward_with(target, reason, &CancelToken::default(), &mut |_, _| {})
+    }
+
+    /// `fast_forward`, reporting each file written to `progress` and
+    /// stopping with the working tree and branch as they were once
+    /// `cancel` is set
+    pub fn fast_forward_with(
+        &self,
+        target: &str,
+        reason: &str,
+        cancel: &CancelToken,
+        progress: CheckoutProgress,
+    ) -> Result<()> {
+        let branch = self.branch_for_update()?;
+        let target = self.resolve_commit(target)?.id;
+        let old_tree = match self.read_ref(&branch) {
+            Some(head) if !self.is_ancestor(&head, &target)? => {
+                bail!("{} does not descend from {}; can't fast-forward", short(&target), short(&head))
+            }
+            Some(head) => self.tree_snapshot(&head),
+            None => BTreeMap::new(),
+        };
+        self.checkout_changes(&old_tree, &self.tree_snapshot(&target), &self.modes_snapshot(&target), cancel, progress)?;
+        self.write_ref(&branch, &target)?;
+        self.update_reflog(&branch, &target, reason)
+    }
+
+    /// Put copies of the commits only the current branch has on top of
+    /// `target` and move the branch there. When both sides changed a file
+    /// differently nothing happens and the files are returned instead.
+    pub fn replay_onto(&self, target: &str, reason: &str) -> Result<ReplayResult> {
+        self.replay_onto_with(target, reason, &CancelToken::default(), &mut |_, _| {})
+    }
+
+    /// `replay_onto`, reporting each file written to `progress` and
+    /// stopping with the working tree and branch as they were once
+    /// `cancel` is set
+    pub fn replay_onto_with(
+        &self,
+        target: &str,
+        reason: &str,
+        cancel: &CancelToken,
+        progress: CheckoutProgress,
+    ) -> Result<ReplayResult> {
+        let branch = self.branch_for_update()?;
+        let head = self.read_ref(&branch).ok_or(StoreError::NoCommits)?;
+        let target = self.resolve_commit(target)?.id;
+        let base = self.merge_base(&head, &target);
+        let mut ours: Vec<Commit> = self
+            .ancestry(&head)
+            .into_iter()
+            .take_while(|c| Some(&c.id) != base.as_ref())
+            .collect();
+        ours.reverse();
+
+        let base_tree = base.as_deref().map(|b| self.tree_snapshot(b)).unwrap_or_default();
+        let head_tree = self.tree_snapshot(&head);
+        let target_tree = self.tree_snapshot(&target);
+        let ours_changed: BTreeSet<&String> = ours.iter().flat_map(|c| c.tree.keys()).collect();
+        let conflicts: Vec<String> = ours_changed
+            .iter()
+            .filter(|p| base_tree.get(**p) != target_tree.get(**p) && head_tree.get(**p) != target_tree.get(**p))
+            .map(|p| p.to_string())
+            .collect();
+        if !conflicts.is_empty() {
+            return Ok(ReplayResult::Conflicts(conflicts));
+        }
+
+        let mut replayed_tree = target_tree;
+        let mut replayed_modes = self.modes_snapshot(&target);
+        let head_modes = self.modes_snapshot(&head);
+        for path in &ours_changed {
+            match head_tree.get(*path) {
+                Some(blob) => replayed_tree.insert(path.to_string(), blob.clone()),
+                None => replayed_tree.remove(*path),
+            };
+            match head_modes.get(*path) {
+                Some(mode) => replayed_modes.insert(path.to_string(), *mode),
+                None => replayed_modes.remove(*path),
+            };
+        }
+        self.checkout_changes(&head_tree, &replayed_tree, &replayed_modes, cancel, progress)?;
+
+        let mut tip = target;
+        for commit in &ours {
+            let id = blake3::hash(format!("{}{}{}", commit.id, tip, Utc::now().timestamp()).as_bytes());
+            let copy = Commit {
+                id: hex::encode(id.as_bytes()),
+                parent: Some(tip),
+                merge_parent: None,
+                branch: branch.clone(),
+                ..commit.clone()
+            };
+            self.append_log(&copy)?;
+            tip = copy.id;
+        }
+        self.write_ref(&branch, &tip)?;
+        self.update_reflog(&branch, &tip, reason)?;
+        Ok(ReplayResult::Replayed(ours.len()))
+    }
+
+    /// The current branch's ref, provided nothing uncommitted is in the way
+    fn branch_for_update(&self) -> Result<String> {
+        if self.current_branch().is_none() {
+            return Err(StoreError::NotOnBranch.into());
+        }
+        let status = self.status()?;
+        if !status.working.is_empty() || !status.staging.is_empty() || !status.renamed.is_empty() {
+            return Err(StoreError::UncommittedChanges.into());
+        }
+        Ok(self.head_ref())
+    }
+}
+
+fn short(id: &str) -> &str {
+    &id[..id.len().min(8)]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CancelToken, ReplayResult, Store};
+    use crate::test_support::{author, temp_repo};
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// The message names the content too: the same message on the same
+    /// parent within one second would give both sides one commit id
+    fn commit_file(store: &Store, path: &str, content: &str) -> String {
+        fs::write(store.root.join(path), content).unwrap();
+        store.stage_file(path).unwrap();
+        store.commit(&format!("Write {}: {}", path, content.trim()), author()).unwrap().id
+    }
+
+    /// `main` with a.txt, and `upstream` one commit ahead of it
+    fn with_upstream(upstream_path: &str) -> (TempDir, Store, String) {
+        let (temp, store) = temp_repo();
+        commit_file(&store, "a.txt", "a\n");
+        store.create_branch("upstream").unwrap();
+        store.checkout_branch("upstream").unwrap();
+        let ahead = commit_file(&store, upstream_path, "upstream\n");
+        store.checkout_branch("main").unwrap();
+        let main_tree = store.tree_snapshot(&store.head_commit().unwrap());
+        store
+            .checkout_changes(&store.tree_snapshot(&ahead), &main_tree, &Default::default(), &CancelToken::default(), &mut |_, _| {})
+            .unwrap();
+        (temp, store, ahead)
+    }
+
+    #[test]
+    fn test_fast_forward_updates_files_and_branch() {
+        let (temp, store, ahead) = with_upstream("b.txt");
+        assert!(!temp.path().join("b.txt").exists());
+        store.fast_forward(&ahead, "pull: fast-forward").unwrap();
+        assert_eq!(store.head_commit(), Some(ahead));
+        assert_eq!(fs::read_to_string(temp.path().join("b.txt")).unwrap(), "upstream\n");
+        assert!(store.status().unwrap().working.is_empty());
+    }
+
+    #[test]
+    fn test_replay_keeps_local_commits_on_top() {
+        let (temp, store, ahead) = with_upstream("b.txt");
+        commit_file(&store, "c.txt", "local\n");
+        assert!(store.fast_forward(&ahead, "pull").is_err());
+
+        assert_eq!(store.replay_onto(&ahead, "pull: rebase").unwrap(), ReplayResult::Replayed(1));
+        let head = store.resolve_commit(&store.head_commit().unwrap()).unwrap();
+        assert_eq!(head.parent.as_deref(), Some(ahead.as_str()));
+        assert_eq!(head.message, "Write c.txt: local");
+        let tree = store.tree_snapshot(&head.id);
+        assert!(tree.contains_key("b.txt") && tree.contains_key("c.txt"));
+        assert_eq!(fs::read_to_string(temp.path().join("b.txt")).unwrap(), "upstream\n");
+    }
+
+    #[test]
+    fn test_replay_refuses_overlapping_changes() {
+        let (temp, store, ahead) = with_upstream("a.txt");
+        let local = commit_file(&store, "a.txt", "local\n");
+        assert_eq!(store.replay_onto(&ahead, "pull: rebase").unwrap(), ReplayResult::Conflicts(vec!["a.txt".into()]));
+        assert_eq!(store.head_commit(), Some(local));
+        assert_eq!(fs::read_to_string(temp.path().join("a.txt")).unwrap(), "local\n");
+    }
+}
@@ -0,0 +1,193 @@
+//! A read-only view of a repository for tools that embed rune: GUIs,
+//! editor plugins, dashboards.
+//!
+//! `Store::view` hands out a `RepoView`, which only reads. It names
+//! revisions the way the command line does (`HEAD`, a branch, a tag or a
+//! commit id prefix) and reports what a paused merge left behind, so a tool
+//! can show a repository without depending on how the store lays it out on
+//! disk. Anything that changes the repository goes through `Store` itself.
+
+use crate::{ConflictVersions, RefsSnapshot, RepoOperation, Store, StoreError};
+use anyhow::Result;
+use rune_core::Commit;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Read-only access to a repository; see the module docs
+#[derive(Clone, Copy)]
+pub struct RepoView<'a> {
+    store: &'a Store,
+}
+
+/// The merge `rune merge` stopped on conflicts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeInProgress {
+    /// What is being merged, as it was named, e.g. `feature` or `origin/main`
+    pub branch: String,
+    /// The current branch's commit when the merge started
+    pub ours: String,
+    /// The commit being merged in
+    pub theirs: String,
+    /// Every file the merge left conflicted
+    pub conflicts: Vec<String>,
+    /// Those not resolved yet
+    pub unresolved: Vec<String>,
+}
+
+impl Store {
+    /// A read-only view of this repository
+    pub fn view(&self) -> RepoView<'_> {
+        RepoView { store: self }
+    }
+}
+
+impl RepoView<'_> {
+    /// The working tree's top directory
+    pub fn root(&self) -> &Path {
+        &self.store.root
+    }
+
+    /// The branch HEAD is on; `None` when HEAD isn't on a branch
+    pub fn current_branch(&self) -> Option<String> {
+        self.store.current_branch()
+    }
+
+    /// The commit HEAD points at; `None` before the first commit
+    pub fn head(&self) -> Result<Option<Commit>> {
+        self.store.head_commit().map(|id| self.store.resolve_commit(&id)).transpose()
+    }
+
+    /// Every branch, tag and remote-tracking ref
+    pub fn refs(&self) -> Result<RefsSnapshot> {
+        Ok((*self.store.refs_snapshot()?).clone())
+    }
+
+    /// The commit `rev` names: `HEAD`, a branch, a tag, or a commit id or
+    /// unambiguous prefix of one
+    pub fn resolve_commit(&self, rev: &str) -> Result<Commit> {
+        if rev == "HEAD" && self.store.head_commit().is_none() {
+            return Err(StoreError::NoCommits.into());
+        }
+        self.store.resolve_commit(&self.store.revision_id(rev)?)
+    }
+
+    /// `rev` and its first-parent ancestors, newest first
+    pub fn history(&self, rev: &str) -> Result<Vec<Commit>> {
+        Ok(self.store.ancestry(&self.resolve_commit(rev)?.id))
+    }
+
+    /// Path to blob id for every file in `rev`
+    pub fn tree(&self, rev: &str) -> Result<BTreeMap<String, String>> {
+        Ok(self.store.tree_snapshot(&self.resolve_commit(rev)?.id))
+    }
+
+    /// Content of blob `id`, as stored
+    pub fn read_blob(&self, id: &str) -> Result<Vec<u8>> {
+        self.store.read_blob(id)
+    }
+
+    /// Content of `path` in `rev`; `None` when it has no such file
+    pub fn file_at(&self, rev: &str, path: &str) -> Result<Option<Vec<u8>>> {
+        let commit = self.resolve_commit(rev)?;
+        self.store.blob_at(&commit.id, path).map(|blob| self.store.read_blob(&blob)).transpose()
+    }
+
+    /// The merge, rebase, revert or am waiting to be continued or aborted
+    pub fn operation_in_progress(&self) -> Result<Option<RepoOperation>> {
+        self.store.operation_in_progress()
+    }
+
+    /// The merge waiting on conflicts, if that is what is in progress
+    pub fn merge_in_progress(&self) -> Result<Option<MergeInProgress>> {
+        if !matches!(self.store.operation_in_progress()?, Some(RepoOperation::Merge { .. })) {
+            return Ok(None);
+        }
+        let state = self.store.read_merge_state()?;
+        Ok(Some(MergeInProgress {
+            unresolved: self.store.unresolved_conflicts()?,
+            branch: state.branch_name,
+            ours: state.current_commit,
+            theirs: state.merge_commit,
+            conflicts: state.conflicts,
+        }))
+    }
+
+    /// Base, ours and theirs of a file the paused merge left conflicted
+    pub fn conflict_versions(&self, path: &str) -> Result<ConflictVersions> {
+        self.store.conflict_versions(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MergeResult, RepoOperation, Store};
+    use crate::test_support::{author, temp_repo};
+    use std::fs;
+
+    /// The message names the content too: the same message on the same
+    /// parent within one second would give both sides one commit id
+    fn commit_file(store: &Store, path: &str, content: &str) -> String {
+        fs::write(store.root.join(path), content).unwrap();
+        store.stage_file(path).unwrap();
+        store.commit(&format!("Write {}: {}", path, content.trim()), author()).unwrap().id
+    }
+
+    #[test]
+    fn test_view_reads_history_refs_and_files() {
+        let (_temp, store) = temp_repo();
+        let view = store.view();
+        assert!(view.head().unwrap().is_none());
+        assert!(view.resolve_commit("HEAD").is_err());
+
+        let first = commit_file(&store, "a.txt", "one\n");
+        store.create_lightweight_tag("v1", &first).unwrap();
+        let second = commit_file(&store, "a.txt", "two\n");
+
+        let view = store.view();
+        assert_eq!(view.root(), store.root.as_path());
+        assert_eq!(view.current_branch().as_deref(), Some("main"));
+        assert_eq!(view.head().unwrap().map(|c| c.id), Some(second.clone()));
+        for rev in ["HEAD", "main", &second[..8]] {
+            assert_eq!(view.resolve_commit(rev).unwrap().id, second, "{}", rev);
+        }
+        assert_eq!(view.resolve_commit("v1").unwrap().id, first);
+        assert!(view.resolve_commit("nope").is_err());
+
+        let refs = view.refs().unwrap();
+        assert_eq!(refs.branch("main"), Some(second.as_str()));
+        assert_eq!(refs.tag("v1"), Some(first.as_str()));
+        let history: Vec<String> = view.history("HEAD").unwrap().into_iter().map(|c| c.id).collect();
+        assert_eq!(history, [second, first]);
+
+        assert_eq!(view.file_at("v1", "a.txt").unwrap().as_deref(), Some(&b"one\n"[..]));
+        assert_eq!(view.file_at("HEAD", "missing.txt").unwrap(), None);
+        let blob = &view.tree("HEAD").unwrap()["a.txt"];
+        assert_eq!(view.read_blob(blob).unwrap(), b"two\n");
+        assert_eq!(view.operation_in_progress().unwrap(), None);
+        assert_eq!(view.merge_in_progress().unwrap(), None);
+    }
+
+    #[test]
+    fn test_view_reports_a_conflicted_merge() {
+        let (temp, store) = temp_repo();
+        commit_file(&store, "a.txt", "base\n");
+        store.create_branch("feature").unwrap();
+        let ours = commit_file(&store, "a.txt", "ours\n");
+        store.checkout_branch("feature").unwrap();
+        let theirs = commit_file(&store, "a.txt", "theirs\n");
+        store.checkout_branch("main").unwrap();
+        fs::write(temp.path().join("a.txt"), "ours\n").unwrap();
+        assert!(matches!(store.merge_branch("feature", false, None).unwrap(), MergeResult::Conflicts(_)));
+
+        let view = store.view();
+        assert!(matches!(view.operation_in_progress().unwrap(), Some(RepoOperation::Merge { .. })));
+        let merge = view.merge_in_progress().unwrap().unwrap();
+        assert_eq!((merge.branch.as_str(), merge.ours, merge.theirs), ("feature", ours, theirs));
+        assert_eq!(merge.conflicts, ["a.txt"]);
+        assert_eq!(merge.unresolved, ["a.txt"]);
+        assert_eq!(view.conflict_versions("a.txt").unwrap().theirs.as_deref(), Some(&b"theirs\n"[..]));
+
+        store.mark_resolved("a.txt").unwrap();
+        assert!(store.view().merge_in_progress().unwrap().unwrap().unresolved.is_empty());
+    }
+}
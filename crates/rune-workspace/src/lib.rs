@@ -1,12 +1,23 @@
 use anyhow::Result;
+use rune_core::layout::{InternalPaths, RepoLayout};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::{fs, time::SystemTime};
 
+mod migration;
+mod profiles;
+
+pub use migration::WORKSPACE_CONFIG_VERSION;
+pub use profiles::{
+    LocalOverrides, ProfileLimits, ProfileRoot, ProfileSource, ProfileSummary, ProfileSync, WorkspaceProfile, PROFILES_DIR,
+};
+
 /// Virtual workspace configuration for sparse checkout and monorepo management
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WorkspaceConfig {
+    /// Layout version, `WORKSPACE_CONFIG_VERSION` once loaded
+    pub version: u32,
     pub name: String,
     pub root_path: PathBuf,
     pub include_patterns: Vec<String>,
@@ -15,10 +26,13 @@ pub struct WorkspaceConfig {
     pub performance_limits: PerformanceLimits,
     pub created_at: SystemTime,
     pub last_updated: SystemTime,
+    /// The committed profile this workspace was made from
+    #[serde(default)]
+    pub profile: Option<ProfileSource>,
 }
 
 /// Virtual root definition for monorepo sub-workspaces
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct VirtualRoot {
     pub name: String,
     pub path: PathBuf,
@@ -30,7 +44,7 @@ pub struct VirtualRoot {
 }
 
 /// Performance limits and guardrails
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PerformanceLimits {
     pub max_file_size_mb: u64,
     pub max_files_per_commit: usize,
@@ -89,12 +103,14 @@ pub struct WorkspaceManager {
 }
 
 impl WorkspaceManager {
-    /// Create a new workspace manager
-    pub fn new(root_path: PathBuf, name: String) -> Result<Self> {
-        let cache_dir = root_path.join(".rune").join("workspace");
+    /// Create a new workspace manager for the working tree at `root_path`,
+    /// keeping its state under the metadata directory `rune_dir`
+    pub fn new(root_path: PathBuf, rune_dir: &Path, name: String) -> Result<Self> {
+        let cache_dir = rune_dir.join("workspace");
         fs::create_dir_all(&cache_dir)?;
 
         let config = WorkspaceConfig {
+            version: WORKSPACE_CONFIG_VERSION,
             name,
             root_path: root_path.clone(),
             include_patterns: vec!["*".to_string()],
@@ -103,14 +119,16 @@ impl WorkspaceManager {
             performance_limits: PerformanceLimits::default(),
             created_at: SystemTime::now(),
             last_updated: SystemTime::now(),
+            profile: None,
         };
 
         Ok(Self { config, cache_dir })
     }
 
-    /// Load existing workspace configuration
-    pub fn load(root_path: PathBuf) -> Result<Self> {
-        let cache_dir = root_path.join(".rune").join("workspace");
+    /// Load existing workspace configuration, migrating one written by an
+    /// older version
+    pub fn load(rune_dir: &Path) -> Result<Self> {
+        let cache_dir = rune_dir.join("workspace");
         let config_path = cache_dir.join("config.json");
 
         if !config_path.exists() {
@@ -118,18 +136,19 @@ impl WorkspaceManager {
         }
 
         let config_data = fs::read_to_string(&config_path)?;
-        let config: WorkspaceConfig = serde_json::from_str(&config_data)?;
+        let config = WorkspaceConfig::from_json(&config_data)?;
 
         Ok(Self { config, cache_dir })
     }
 
-    /// Save workspace configuration
+    /// Save workspace configuration, and for a workspace made from a
+    /// profile, what differs from the profile
     pub fn save(&mut self) -> Result<()> {
         self.config.last_updated = SystemTime::now();
         let config_path = self.cache_dir.join("config.json");
         let config_data = serde_json::to_string_pretty(&self.config)?;
         fs::write(&config_path, config_data)?;
-        Ok(())
+        self.save_overrides()
     }
 
     /// Add a virtual root to the workspace
@@ -204,7 +223,13 @@ impl WorkspaceManager {
             return Ok(files);
         }
 
-        for entry in walkdir::WalkDir::new(&full_root_path) {
+        // The metadata directory holds the workspace state, so it sits above `cache_dir`
+        let layout = RepoLayout {
+            root: self.config.root_path.clone(),
+            rune_dir: self.cache_dir.parent().unwrap_or(&self.cache_dir).to_path_buf(),
+        };
+        let internal = InternalPaths::new(&layout, &[]);
+        for entry in walkdir::WalkDir::new(&full_root_path).into_iter().filter_entry(|e| !internal.contains(e.path())) {
             let entry = entry?;
             if !entry.file_type().is_file() {
                 continue;
@@ -473,7 +498,7 @@ mod tests {
         let temp_dir = TempDir::new().unwrap();
         let root_path = temp_dir.path().to_path_buf();
         
-        let workspace = WorkspaceManager::new(root_path.clone(), "test-workspace".to_string()).unwrap();
+        let workspace = WorkspaceManager::new(root_path.clone(), &temp_dir.path().join(".rune"), "test-workspace".to_string()).unwrap();
         
         assert_eq!(workspace.config.name, "test-workspace");
         assert_eq!(workspace.config.root_path, root_path);
@@ -485,7 +510,7 @@ mod tests {
         let temp_dir = TempDir::new().unwrap();
         let root_path = temp_dir.path().to_path_buf();
         
-        let mut workspace = WorkspaceManager::new(root_path, "test-workspace".to_string()).unwrap();
+        let mut workspace = WorkspaceManager::new(root_path, &temp_dir.path().join(".rune"), "test-workspace".to_string()).unwrap();
         
         // Add virtual root
         workspace.add_virtual_root(
@@ -502,12 +527,28 @@ mod tests {
         assert!(workspace.config.virtual_roots.is_empty());
     }
 
+    #[test]
+    fn test_virtual_root_files_skip_metadata() {
+        let temp_dir = TempDir::new().unwrap();
+        let root_path = temp_dir.path().to_path_buf();
+        let mut workspace = WorkspaceManager::new(root_path.clone(), &root_path.join(".rune"), "test-workspace".to_string()).unwrap();
+        for dir in [".rune/lfs/objects/ab", ".rune/drafts", "src"] {
+            fs::create_dir_all(root_path.join(dir)).unwrap();
+            fs::write(root_path.join(dir).join("file.txt"), "x").unwrap();
+        }
+        workspace.add_virtual_root("all".to_string(), PathBuf::from("."), vec![]).unwrap();
+
+        let files = workspace.get_virtual_root_files(&workspace.config.virtual_roots["all"]).unwrap();
+        let files: Vec<String> = files.iter().map(|f| f.to_string_lossy().replace('\\', "/")).collect();
+        assert_eq!(files, ["src/file.txt"]);
+    }
+
     #[test]
     fn test_performance_limits() {
         let temp_dir = TempDir::new().unwrap();
         let root_path = temp_dir.path().to_path_buf();
         
-        let workspace = WorkspaceManager::new(root_path.clone(), "test-workspace".to_string()).unwrap();
+        let workspace = WorkspaceManager::new(root_path.clone(), &temp_dir.path().join(".rune"), "test-workspace".to_string()).unwrap();
         
         // Create a test exe file
         let exe_file = root_path.join("test.exe");
@@ -528,7 +569,7 @@ mod tests {
         let temp_dir = TempDir::new().unwrap();
         let root_path = temp_dir.path().to_path_buf();
         
-        let workspace = WorkspaceManager::new(root_path, "test-workspace".to_string()).unwrap();
+        let workspace = WorkspaceManager::new(root_path, &temp_dir.path().join(".rune"), "test-workspace".to_string()).unwrap();
         
         let files = vec![
             PathBuf::from("src/main.rs"),
@@ -545,7 +586,7 @@ mod tests {
         let temp_dir = TempDir::new().unwrap();
         let root_path = temp_dir.path().to_path_buf();
         
-        let workspace = WorkspaceManager::new(root_path.clone(), "test-workspace".to_string()).unwrap();
+        let workspace = WorkspaceManager::new(root_path.clone(), &temp_dir.path().join(".rune"), "test-workspace".to_string()).unwrap();
         
         // Create a text file
         let text_file = root_path.join("test.txt");
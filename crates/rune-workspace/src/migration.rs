@@ -0,0 +1,152 @@
+//! Versioned workspace configs
+//!
+//! `config.json` carries the version of the layout it was written with.
+//! Configs from before the field existed are version 1. Loading one runs
+//! it through each migration up to `WORKSPACE_CONFIG_VERSION`, filling in
+//! whatever later versions added, so an older config loads instead of
+//! failing to deserialize. A config from a newer build is refused rather
+//! than read with its newer fields dropped.
+
+use crate::{PerformanceLimits, WorkspaceConfig};
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Map, Value};
+use std::time::SystemTime;
+
+/// Version of the config layout this build reads and writes
+pub const WORKSPACE_CONFIG_VERSION: u32 = 2;
+
+impl WorkspaceConfig {
+    /// Parse `config.json`, migrating an older layout forward
+    pub fn from_json(data: &str) -> Result<Self> {
+        let mut value: Value = serde_json::from_str(data).context("workspace config is not valid JSON")?;
+        let Some(config) = value.as_object_mut() else {
+            bail!("workspace config is not a JSON object");
+        };
+        let version = config.get("version").and_then(Value::as_u64).unwrap_or(1) as u32;
+        if version > WORKSPACE_CONFIG_VERSION {
+            bail!(
+                "workspace config version {} is newer than supported version {}; upgrade rune to use this workspace",
+                version,
+                WORKSPACE_CONFIG_VERSION
+            );
+        }
+        if version < 2 {
+            migrate_v1(config)?;
+        }
+        config.insert("version".into(), json!(WORKSPACE_CONFIG_VERSION));
+        serde_json::from_value(value).context("workspace config does not match its version")
+    }
+}
+
+/// Set `key` to `default` unless it is already there
+fn fill(object: &mut Map<String, Value>, key: &str, default: Value) {
+    object.entry(key).or_insert(default);
+}
+
+/// Version 1 had no `version` or `profile`, and configs written by hand
+/// often left out the patterns, limits, timestamps and root flags
+fn migrate_v1(config: &mut Map<String, Value>) -> Result<()> {
+    for required in ["name", "root_path"] {
+        if !config.contains_key(required) {
+            bail!("workspace config has no '{}'", required);
+        }
+    }
+    let now = serde_json::to_value(SystemTime::now())?;
+    fill(config, "include_patterns", json!(["*"]));
+    fill(config, "exclude_patterns", json!([]));
+    fill(config, "virtual_roots", json!({}));
+    fill(config, "created_at", now.clone());
+    fill(config, "last_updated", now);
+    fill(config, "profile", Value::Null);
+
+    let defaults = serde_json::to_value(PerformanceLimits::default())?;
+    let limits = config.entry("performance_limits").or_insert_with(|| json!({}));
+    if let (Some(limits), Value::Object(defaults)) = (limits.as_object_mut(), defaults) {
+        for (key, default) in defaults {
+            fill(limits, &key, default);
+        }
+    }
+
+    if let Some(roots) = config.get_mut("virtual_roots").and_then(Value::as_object_mut) {
+        for (name, root) in roots.iter_mut() {
+            let Some(root) = root.as_object_mut() else {
+                continue;
+            };
+            fill(root, "name", json!(name));
+            fill(root, "include_patterns", json!([]));
+            fill(root, "exclude_patterns", json!([]));
+            fill(root, "dependencies", json!([]));
+            fill(root, "active", json!(true));
+            fill(root, "auto_include_deps", json!(true));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WorkspaceManager;
+    use std::fs;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    /// A config as version 1 wrote it, trimmed the way hand-written ones are
+    const V1_CONFIG: &str = r#"{
+        "name": "game",
+        "root_path": "/work/game",
+        "exclude_patterns": ["**/*.psd"],
+        "virtual_roots": {
+            "engine": { "path": "engine", "include_patterns": ["**/*.rs"], "dependencies": ["core"], "active": false }
+        },
+        "performance_limits": { "max_file_size_mb": 500 }
+    }"#;
+
+    #[test]
+    fn test_v1_config_upgrades_with_defaults() {
+        let config = WorkspaceConfig::from_json(V1_CONFIG).unwrap();
+        assert_eq!(config.version, WORKSPACE_CONFIG_VERSION);
+        assert_eq!(config.name, "game");
+        assert_eq!(config.root_path, PathBuf::from("/work/game"));
+        assert_eq!(config.include_patterns, ["*"]);
+        assert_eq!(config.exclude_patterns, ["**/*.psd"]);
+        assert!(config.profile.is_none());
+
+        // Kept what was set, filled the rest
+        let defaults = PerformanceLimits::default();
+        assert_eq!(config.performance_limits, PerformanceLimits { max_file_size_mb: 500, ..defaults });
+
+        let engine = &config.virtual_roots["engine"];
+        assert_eq!(engine.name, "engine");
+        assert_eq!(engine.path, PathBuf::from("engine"));
+        assert_eq!(engine.include_patterns, ["**/*.rs"]);
+        assert!(engine.exclude_patterns.is_empty());
+        assert_eq!(engine.dependencies, ["core"]);
+        assert!(!engine.active);
+        assert!(engine.auto_include_deps);
+
+        assert!(WorkspaceConfig::from_json(r#"{ "root_path": "/work/game" }"#).is_err());
+    }
+
+    #[test]
+    fn test_load_migrates_and_save_writes_current_version() {
+        let temp = TempDir::new().unwrap();
+        let rune_dir = temp.path().join(".rune");
+        fs::create_dir_all(rune_dir.join("workspace")).unwrap();
+        fs::write(rune_dir.join("workspace/config.json"), V1_CONFIG).unwrap();
+
+        let mut workspace = WorkspaceManager::load(&rune_dir).unwrap();
+        workspace.save().unwrap();
+        let saved: Value =
+            serde_json::from_str(&fs::read_to_string(rune_dir.join("workspace/config.json")).unwrap()).unwrap();
+        assert_eq!(saved["version"], json!(WORKSPACE_CONFIG_VERSION));
+        assert_eq!(WorkspaceManager::load(&rune_dir).unwrap().config.name, "game");
+    }
+
+    #[test]
+    fn test_newer_config_is_refused() {
+        let newer = format!(r#"{{ "version": {}, "name": "game", "root_path": "/work/game" }}"#, WORKSPACE_CONFIG_VERSION + 1);
+        let err = WorkspaceConfig::from_json(&newer).unwrap_err();
+        assert!(err.to_string().contains("newer than supported"), "{}", err);
+    }
+}
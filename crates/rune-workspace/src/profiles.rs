@@ -0,0 +1,483 @@
+//! Workspace profiles committed to the repository
+//!
+//! `.runeworkspaces/<name>.toml` describes a workspace a team shares: its
+//! virtual roots, global include/exclude patterns and limits. Using one
+//! writes it into the local workspace config and keeps a copy of the
+//! profile as applied, so later local changes can be told apart from the
+//! profile. Those local changes are kept in `overrides.json` next to the
+//! config and laid over the profile again when it is synced to a newer
+//! committed version.
+//!
+//! ```toml
+//! description = "Web client and the shared UI kit"
+//! exclude = ["**/*.psd"]
+//!
+//! [roots.web]
+//! path = "apps/web"
+//!
+//! [roots.ui]
+//! path = "libs/ui"
+//! include = ["**/*.ts", "**/*.css"]
+//!
+//! [limits]
+//! max_file_size_mb = 50
+//! ```
+
+use crate::{PerformanceLimits, VirtualRoot, WorkspaceConfig, WorkspaceManager};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Directory at the repository root holding the profiles
+pub const PROFILES_DIR: &str = ".runeworkspaces";
+
+/// The profile as last applied, next to the workspace config
+const APPLIED_PROFILE: &str = "profile.toml";
+
+/// Local changes made on top of the profile
+const OVERRIDES_FILE: &str = "overrides.json";
+
+/// One `.runeworkspaces/<name>.toml`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WorkspaceProfile {
+    #[serde(default)]
+    pub description: String,
+    /// Global include patterns; everything when empty
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default)]
+    pub roots: BTreeMap<String, ProfileRoot>,
+    #[serde(default)]
+    pub limits: ProfileLimits,
+}
+
+/// `[roots.<name>]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProfileRoot {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    #[serde(default = "active_by_default")]
+    pub active: bool,
+}
+
+fn active_by_default() -> bool {
+    true
+}
+
+/// `[limits]`: the ones given replace the defaults
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProfileLimits {
+    pub max_file_size_mb: Option<u64>,
+    pub max_files_per_commit: Option<usize>,
+    pub max_binary_files_per_commit: Option<usize>,
+    pub warn_file_size_mb: Option<u64>,
+}
+
+/// A committed profile as `rune workspace list-profiles` shows it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileSummary {
+    pub name: String,
+    pub description: String,
+}
+
+/// Which profile a workspace came from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileSource {
+    pub name: String,
+    /// Hash of the profile file as applied
+    pub version: String,
+}
+
+/// Local changes to a workspace made from a profile
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LocalOverrides {
+    /// Roots added locally, or profile roots replaced locally
+    #[serde(default)]
+    pub roots: BTreeMap<String, VirtualRoot>,
+    /// Profile roots switched on or off locally
+    #[serde(default)]
+    pub active: BTreeMap<String, bool>,
+    /// Profile roots removed locally
+    #[serde(default)]
+    pub removed_roots: BTreeSet<String>,
+    #[serde(default)]
+    pub include_patterns: Vec<String>,
+    #[serde(default)]
+    pub exclude_patterns: Vec<String>,
+    #[serde(default)]
+    pub performance_limits: Option<PerformanceLimits>,
+}
+
+impl LocalOverrides {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// What `WorkspaceManager::sync_profile` found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileSync {
+    pub from_version: String,
+    pub to_version: String,
+    /// One line per change to the profile, e.g. `+ root docs (docs)`
+    pub changes: Vec<String>,
+}
+
+impl WorkspaceProfile {
+    /// Every profile committed under `root`, by name
+    pub fn list(root: &Path) -> Result<Vec<ProfileSummary>> {
+        let dir = root.join(PROFILES_DIR);
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut profiles = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("toml") {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            let (profile, _) = Self::load(root, name)?;
+            profiles.push(ProfileSummary { name: name.to_string(), description: profile.description });
+        }
+        profiles.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(profiles)
+    }
+
+    /// The profile `name` committed under `root`, with its version
+    pub fn load(root: &Path, name: &str) -> Result<(Self, String)> {
+        let path = root.join(PROFILES_DIR).join(format!("{}.toml", name));
+        let Ok(text) = fs::read_to_string(&path) else {
+            let known: Vec<String> = Self::list(root).unwrap_or_default().into_iter().map(|p| p.name).collect();
+            if known.is_empty() {
+                anyhow::bail!("Unknown workspace profile '{}': {} has no profiles", name, PROFILES_DIR);
+            }
+            anyhow::bail!("Unknown workspace profile '{}'; available: {}", name, known.join(", "));
+        };
+        let profile = Self::parse(&text).with_context(|| format!("invalid workspace profile {}", path.display()))?;
+        Ok((profile, version_of(&text)))
+    }
+
+    fn parse(text: &str) -> Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+
+    /// Patterns, roots and limits of a workspace made from this profile
+    fn apply(&self, config: &mut WorkspaceConfig) {
+        config.include_patterns =
+            if self.include.is_empty() { vec!["*".to_string()] } else { self.include.clone() };
+        config.exclude_patterns = self.exclude.clone();
+        config.virtual_roots = self
+            .roots
+            .iter()
+            .map(|(name, root)| {
+                let root = VirtualRoot {
+                    name: name.clone(),
+                    path: root.path.clone(),
+                    include_patterns: root.include.clone(),
+                    exclude_patterns: root.exclude.clone(),
+                    dependencies: root.dependencies.clone(),
+                    active: root.active,
+                    auto_include_deps: true,
+                };
+                (name.clone(), root)
+            })
+            .collect();
+        let defaults = PerformanceLimits::default();
+        config.performance_limits = PerformanceLimits {
+            max_file_size_mb: self.limits.max_file_size_mb.unwrap_or(defaults.max_file_size_mb),
+            max_files_per_commit: self.limits.max_files_per_commit.unwrap_or(defaults.max_files_per_commit),
+            max_binary_files_per_commit: self
+                .limits
+                .max_binary_files_per_commit
+                .unwrap_or(defaults.max_binary_files_per_commit),
+            warn_file_size_mb: self.limits.warn_file_size_mb.unwrap_or(defaults.warn_file_size_mb),
+            ..defaults
+        };
+    }
+
+    /// What changed from `self` to `newer`, one line each
+    fn changes_to(&self, newer: &Self) -> Vec<String> {
+        let mut changes = Vec::new();
+        for (name, root) in &newer.roots {
+            match self.roots.get(name) {
+                None => changes.push(format!("+ root {} ({})", name, root.path.display())),
+                Some(old) if old != root => changes.push(format!("~ root {} ({})", name, root.path.display())),
+                Some(_) => {}
+            }
+        }
+        for (name, root) in &self.roots {
+            if !newer.roots.contains_key(name) {
+                changes.push(format!("- root {} ({})", name, root.path.display()));
+            }
+        }
+        for (kind, old, new) in [("include", &self.include, &newer.include), ("exclude", &self.exclude, &newer.exclude)] {
+            changes.extend(new.iter().filter(|p| !old.contains(p)).map(|p| format!("+ {} {}", kind, p)));
+            changes.extend(old.iter().filter(|p| !new.contains(p)).map(|p| format!("- {} {}", kind, p)));
+        }
+        if self.limits != newer.limits {
+            changes.push("~ limits".to_string());
+        }
+        if self.description != newer.description {
+            changes.push("~ description".to_string());
+        }
+        changes
+    }
+}
+
+fn version_of(text: &str) -> String {
+    blake3::hash(text.as_bytes()).to_hex()[..12].to_string()
+}
+
+impl WorkspaceManager {
+    /// Make the workspace of the working tree at `root_path` the committed
+    /// profile `name`, dropping any earlier local configuration
+    pub fn use_profile(root_path: PathBuf, rune_dir: &Path, name: &str) -> Result<Self> {
+        let (profile, version) = WorkspaceProfile::load(&root_path, name)?;
+        let mut workspace = Self::new(root_path, rune_dir, name.to_string())?;
+        profile.apply(&mut workspace.config);
+        workspace.config.profile = Some(ProfileSource { name: name.to_string(), version });
+        fs::write(workspace.cache_dir.join(APPLIED_PROFILE), toml::to_string_pretty(&profile)?)?;
+        workspace.save()?;
+        Ok(workspace)
+    }
+
+    /// Bring the workspace up to the committed version of its profile,
+    /// keeping the local overrides. With `dry_run` only the changes are
+    /// reported.
+    pub fn sync_profile(&mut self, dry_run: bool) -> Result<ProfileSync> {
+        let Some(source) = self.config.profile.clone() else {
+            anyhow::bail!("This workspace was not made from a profile; use `rune workspace use <profile>`");
+        };
+        let applied = self.applied_profile()?;
+        let (committed, version) = WorkspaceProfile::load(&self.config.root_path, &source.name)?;
+        let sync = ProfileSync {
+            from_version: source.version,
+            to_version: version.clone(),
+            changes: applied.changes_to(&committed),
+        };
+        if dry_run || sync.from_version == sync.to_version {
+            return Ok(sync);
+        }
+
+        let overrides = self.local_overrides()?;
+        committed.apply(&mut self.config);
+        overrides.apply(&mut self.config);
+        self.config.profile = Some(ProfileSource { name: source.name, version });
+        fs::write(self.cache_dir.join(APPLIED_PROFILE), toml::to_string_pretty(&committed)?)?;
+        self.save()?;
+        Ok(sync)
+    }
+
+    /// Local changes on top of the profile, as of the last save
+    pub fn local_overrides(&self) -> Result<LocalOverrides> {
+        match fs::read_to_string(self.cache_dir.join(OVERRIDES_FILE)) {
+            Ok(text) => Ok(serde_json::from_str(&text)?),
+            Err(_) => Ok(LocalOverrides::default()),
+        }
+    }
+
+    fn applied_profile(&self) -> Result<WorkspaceProfile> {
+        let text = fs::read_to_string(self.cache_dir.join(APPLIED_PROFILE))
+            .context("the profile this workspace was made from is missing; run `rune workspace use` again")?;
+        WorkspaceProfile::parse(&text)
+    }
+
+    /// Record how the config differs from the profile it was made from
+    pub(crate) fn save_overrides(&self) -> Result<()> {
+        if self.config.profile.is_none() {
+            return Ok(());
+        }
+        let mut base = self.config.clone();
+        self.applied_profile()?.apply(&mut base);
+        let overrides = LocalOverrides::between(&base, &self.config);
+        fs::write(self.cache_dir.join(OVERRIDES_FILE), serde_json::to_string_pretty(&overrides)?)?;
+        Ok(())
+    }
+}
+
+impl LocalOverrides {
+    /// How `local` differs from `base`
+    fn between(base: &WorkspaceConfig, local: &WorkspaceConfig) -> Self {
+        let mut overrides = Self::default();
+        for (name, root) in &local.virtual_roots {
+            match base.virtual_roots.get(name) {
+                None => {
+                    overrides.roots.insert(name.clone(), root.clone());
+                }
+                Some(profile_root) if *profile_root == VirtualRoot { active: profile_root.active, ..root.clone() } => {
+                    if profile_root.active != root.active {
+                        overrides.active.insert(name.clone(), root.active);
+                    }
+                }
+                Some(_) => {
+                    overrides.roots.insert(name.clone(), root.clone());
+                }
+            }
+        }
+        overrides.removed_roots =
+            base.virtual_roots.keys().filter(|name| !local.virtual_roots.contains_key(*name)).cloned().collect();
+        overrides.include_patterns =
+            local.include_patterns.iter().filter(|p| !base.include_patterns.contains(p)).cloned().collect();
+        overrides.exclude_patterns =
+            local.exclude_patterns.iter().filter(|p| !base.exclude_patterns.contains(p)).cloned().collect();
+        if local.performance_limits != base.performance_limits {
+            overrides.performance_limits = Some(local.performance_limits.clone());
+        }
+        overrides
+    }
+
+    fn apply(&self, config: &mut WorkspaceConfig) {
+        for name in &self.removed_roots {
+            config.virtual_roots.remove(name);
+        }
+        config.virtual_roots.extend(self.roots.clone());
+        for (name, active) in &self.active {
+            if let Some(root) = config.virtual_roots.get_mut(name) {
+                root.active = *active;
+            }
+        }
+        for pattern in &self.include_patterns {
+            if !config.include_patterns.contains(pattern) {
+                config.include_patterns.push(pattern.clone());
+            }
+        }
+        for pattern in &self.exclude_patterns {
+            if !config.exclude_patterns.contains(pattern) {
+                config.exclude_patterns.push(pattern.clone());
+            }
+        }
+        if let Some(limits) = &self.performance_limits {
+            config.performance_limits = limits.clone();
+        }
+        config.last_updated = SystemTime::now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_profile(root: &Path, name: &str, text: &str) {
+        fs::create_dir_all(root.join(PROFILES_DIR)).unwrap();
+        fs::write(root.join(PROFILES_DIR).join(format!("{}.toml", name)), text).unwrap();
+    }
+
+    const FRONTEND: &str = r#"
+description = "Web client and the shared UI kit"
+exclude = ["**/*.psd"]
+
+[roots.web]
+path = "apps/web"
+
+[roots.ui]
+path = "libs/ui"
+include = ["**/*.ts"]
+
+[limits]
+max_file_size_mb = 50
+"#;
+
+    #[test]
+    fn test_use_committed_profile() {
+        let temp = TempDir::new().unwrap();
+        write_profile(temp.path(), "frontend", FRONTEND);
+        let rune_dir = temp.path().join(".rune");
+        WorkspaceManager::use_profile(temp.path().to_path_buf(), &rune_dir, "frontend").unwrap();
+
+        let workspace = WorkspaceManager::load(&rune_dir).unwrap();
+        let config = &workspace.config;
+        assert_eq!(config.profile.as_ref().map(|p| p.name.as_str()), Some("frontend"));
+        assert_eq!(config.profile.as_ref().unwrap().version, version_of(FRONTEND));
+        assert_eq!(config.virtual_roots["web"].path, PathBuf::from("apps/web"));
+        assert!(config.virtual_roots["web"].active);
+        assert_eq!(config.virtual_roots["ui"].include_patterns, ["**/*.ts"]);
+        assert_eq!((config.include_patterns.clone(), config.exclude_patterns.clone()), (vec!["*".into()], vec!["**/*.psd".into()]));
+        assert_eq!(config.performance_limits.max_file_size_mb, 50);
+        assert_eq!(config.performance_limits.max_files_per_commit, PerformanceLimits::default().max_files_per_commit);
+        assert!(workspace.local_overrides().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_local_overrides_survive_a_profile_sync() {
+        let temp = TempDir::new().unwrap();
+        write_profile(temp.path(), "frontend", FRONTEND);
+        let rune_dir = temp.path().join(".rune");
+        let mut workspace = WorkspaceManager::use_profile(temp.path().to_path_buf(), &rune_dir, "frontend").unwrap();
+        workspace.set_virtual_root_active("web", false).unwrap();
+        workspace.add_virtual_root("scratch".into(), PathBuf::from("tools/scratch"), vec![]).unwrap();
+        workspace.add_exclude_pattern("**/*.log".into()).unwrap();
+
+        let overrides = workspace.local_overrides().unwrap();
+        assert_eq!(overrides.active, BTreeMap::from([("web".to_string(), false)]));
+        assert_eq!(overrides.roots.keys().collect::<Vec<_>>(), ["scratch"]);
+
+        // Upstream adds a root, changes one and raises a limit
+        let updated = FRONTEND
+            .replace(r#"include = ["**/*.ts"]"#, r#"include = ["**/*.ts", "**/*.css"]"#)
+            .replace("max_file_size_mb = 50", "max_file_size_mb = 80\n\n[roots.docs]\npath = \"docs\"");
+        write_profile(temp.path(), "frontend", &updated);
+
+        let mut workspace = WorkspaceManager::load(&rune_dir).unwrap();
+        let preview = workspace.sync_profile(true).unwrap();
+        assert_eq!(preview.changes, ["+ root docs (docs)", "~ root ui (libs/ui)", "~ limits"]);
+        assert!(!workspace.config.virtual_roots.contains_key("docs"));
+
+        let sync = workspace.sync_profile(false).unwrap();
+        assert_eq!(sync.to_version, version_of(&updated));
+        let config = &WorkspaceManager::load(&rune_dir).unwrap().config;
+        assert_eq!(config.profile.as_ref().unwrap().version, version_of(&updated));
+        assert!(config.virtual_roots.contains_key("docs"));
+        assert_eq!(config.virtual_roots["ui"].include_patterns, ["**/*.ts", "**/*.css"]);
+        assert_eq!(config.performance_limits.max_file_size_mb, 80);
+        assert!(!config.virtual_roots["web"].active);
+        assert_eq!(config.virtual_roots["scratch"].path, PathBuf::from("tools/scratch"));
+        assert_eq!(config.exclude_patterns, ["**/*.psd", "**/*.log"]);
+    }
+
+    #[test]
+    fn test_list_shows_committed_profiles() {
+        let temp = TempDir::new().unwrap();
+        assert!(WorkspaceProfile::list(temp.path()).unwrap().is_empty());
+        write_profile(temp.path(), "frontend", FRONTEND);
+        write_profile(temp.path(), "art", "description = \"Source art\"\n[roots.art]\npath = \"art\"\n");
+        fs::write(temp.path().join(PROFILES_DIR).join("README.md"), "not a profile").unwrap();
+
+        let names: Vec<(String, String)> =
+            WorkspaceProfile::list(temp.path()).unwrap().into_iter().map(|p| (p.name, p.description)).collect();
+        assert_eq!(
+            names,
+            [("art".into(), "Source art".into()), ("frontend".into(), "Web client and the shared UI kit".into())]
+        );
+    }
+
+    #[test]
+    fn test_unknown_profile_is_an_error() {
+        let temp = TempDir::new().unwrap();
+        let rune_dir = temp.path().join(".rune");
+        let Err(err) = WorkspaceManager::use_profile(temp.path().to_path_buf(), &rune_dir, "backend") else {
+            panic!("a repository without profiles has no 'backend'");
+        };
+        assert!(err.to_string().contains("has no profiles"), "{}", err);
+
+        write_profile(temp.path(), "frontend", FRONTEND);
+        let Err(err) = WorkspaceManager::use_profile(temp.path().to_path_buf(), &rune_dir, "backend") else {
+            panic!("'backend' is not one of the profiles");
+        };
+        assert_eq!(err.to_string(), "Unknown workspace profile 'backend'; available: frontend");
+        assert!(WorkspaceManager::load(&rune_dir).is_err());
+    }
+}
@@ -86,6 +86,231 @@ fn test_add_and_commit_workflow() {
     assert!(log_str.contains("Initial commit"), "Log should contain commit message");
 }
 
+#[test]
+fn test_rebase_continue_keeps_the_old_flag_name() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    assert!(run_rune_command(&["init"], temp_dir.path()).status.success());
+    for flag in ["--continue", "--continue-rebase"] {
+        let output = run_rune_command(&["rebase", flag], temp_dir.path());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("No rebase in progress"), "{}: {}", flag, stderr);
+    }
+}
+
+#[test]
+fn test_log_for_one_file() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let repo_path = temp_dir.path();
+    assert!(run_rune_command(&["init"], repo_path).status.success());
+    for (file, content, message) in [("a.txt", "1", "first a"), ("b.txt", "1", "only b"), ("a.txt", "2", "second a")] {
+        fs::write(repo_path.join(file), content).unwrap();
+        assert!(run_rune_command(&["add", file], repo_path).status.success());
+        assert!(run_rune_command(&["commit", "-m", message], repo_path).status.success());
+    }
+
+    let output = run_rune_command(&["log", "--oneline", "--", "a.txt"], repo_path);
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    let log = String::from_utf8_lossy(&output.stdout);
+    assert!(log.contains("second a") && log.contains("modified"), "{}", log);
+    assert!(log.contains("first a") && log.contains("added"), "{}", log);
+    assert!(!log.contains("only b"), "{}", log);
+}
+
+#[test]
+fn test_commit_moves_large_files_to_lfs_and_refuses_oversized_ones() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let repo_path = temp_dir.path();
+    assert!(run_rune_command(&["init"], repo_path).status.success());
+    assert!(run_rune_command(&["config", "set", "core.auto_lfs_threshold", "1KB"], repo_path).status.success());
+    fs::write(repo_path.join("movie.mp4"), vec![7u8; 4096]).unwrap();
+    fs::write(repo_path.join("small.txt"), "under the threshold\n").unwrap();
+    assert!(run_rune_command(&["add", "movie.mp4", "small.txt"], repo_path).status.success());
+
+    let output = run_rune_command(&["commit", "-m", "add media"], repo_path);
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("movie.mp4 (4096 bytes)"), "{}", stdout);
+    assert!(stdout.contains("rune lfs track \"*.mp4\""), "{}", stdout);
+    assert!(!stdout.contains("small.txt ("), "{}", stdout);
+    assert!(fs::read_to_string(repo_path.join("movie.mp4")).unwrap().contains("oid"));
+    assert_eq!(fs::read_to_string(repo_path.join("small.txt")).unwrap(), "under the threshold\n");
+
+    let status = String::from_utf8_lossy(&run_rune_command(&["lfs", "status"], repo_path).stdout).to_string();
+    assert!(status.contains("movie.mp4 (auto)"), "{}", status);
+
+    // Over the hard limit, and no longer moved to LFS
+    assert!(run_rune_command(&["config", "unset", "core.auto_lfs_threshold"], repo_path).status.success());
+    assert!(run_rune_command(&["config", "set", "core.max_object_size", "2KB"], repo_path).status.success());
+    fs::write(repo_path.join("dump.bin"), vec![1u8; 3000]).unwrap();
+    assert!(run_rune_command(&["add", "dump.bin"], repo_path).status.success());
+    let output = run_rune_command(&["commit", "-m", "add dump"], repo_path);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("'dump.bin' is 3000 bytes"), "{}", stderr);
+}
+
+#[test]
+fn test_signed_tag_verifies_until_it_is_changed() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let repo_path = temp_dir.path();
+    assert!(run_rune_command(&["init"], repo_path).status.success());
+    fs::write(repo_path.join("a.txt"), "a\n").unwrap();
+    assert!(run_rune_command(&["add", "a.txt"], repo_path).status.success());
+    assert!(run_rune_command(&["commit", "-m", "first"], repo_path).status.success());
+    assert!(run_rune_command(&["config", "set", "signing.key", ".rune/tag-signing.pk8"], repo_path).status.success());
+
+    let output = run_rune_command(&["tag", "create", "v1.0", "--sign", "-m", "Release 1.0"], repo_path);
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    let output = run_rune_command(&["tag", "verify", "v1.0"], repo_path);
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Good signature on tag 'v1.0'"));
+
+    let tag_file = repo_path.join(".rune/refs/tags/v1.0");
+    let signed = fs::read_to_string(&tag_file).unwrap();
+    fs::write(&tag_file, signed.replace("Release 1.0", "Release 2.0")).unwrap();
+    let output = run_rune_command(&["tag", "verify", "v1.0"], repo_path);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("changed after it was signed"));
+}
+
+#[test]
+fn test_verbose_prints_phase_timings() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let repo_path = temp_dir.path();
+    assert!(run_rune_command(&["init"], repo_path).status.success());
+    fs::write(repo_path.join("a.txt"), "a\n").unwrap();
+
+    let output = run_rune_command(&["--verbose", "status"], repo_path);
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Phase"), "{}", stderr);
+    assert!(stderr.contains("\nstatus "), "{}", stderr);
+    assert!(stderr.contains("  walk worktree "), "{}", stderr);
+    assert!(stderr.contains("untracked=1"), "{}", stderr);
+    assert!(fs::read_to_string(repo_path.join(".rune/perf/runs.jsonl")).unwrap().contains("\"operation\":\"status\""));
+
+    let quiet = run_rune_command(&["status"], repo_path);
+    assert!(!String::from_utf8_lossy(&quiet.stderr).contains("walk worktree"));
+}
+
+#[test]
+fn test_exit_codes_by_failure_kind() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let repo_path = temp_dir.path();
+
+    assert_eq!(run_rune_command(&["status"], repo_path).status.code(), Some(4));
+    let output = run_rune_command(&["notes", "between", "v1", "--format", "json"], repo_path);
+    assert_eq!(output.status.code(), Some(4));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.trim_start().starts_with('{'), "{}", stderr);
+    for field in ["\"code\":4", "\"kind\":\"not_a_repo\"", "\"message\":", "\"details\":"] {
+        assert!(stderr.contains(field), "missing {} in {}", field, stderr);
+    }
+
+    assert!(run_rune_command(&["init"], repo_path).status.success());
+    assert_eq!(run_rune_command(&["commit", "-m", "Empty commit"], repo_path).status.code(), Some(5));
+    assert_eq!(run_rune_command(&["frobnicate"], repo_path).status.code(), Some(2));
+    assert_eq!(run_rune_command(&["--version"], repo_path).status.code(), Some(0));
+
+    fs::write(repo_path.join("a.txt"), "base\n").unwrap();
+    assert!(run_rune_command(&["add", "a.txt"], repo_path).status.success());
+    assert!(run_rune_command(&["commit", "-m", "base"], repo_path).status.success());
+    assert!(run_rune_command(&["switch", "-c", "feature"], repo_path).status.success());
+    fs::write(repo_path.join("a.txt"), "feature\n").unwrap();
+    assert!(run_rune_command(&["add", "a.txt"], repo_path).status.success());
+    assert!(run_rune_command(&["commit", "-m", "feature"], repo_path).status.success());
+    assert!(run_rune_command(&["switch", "main"], repo_path).status.success());
+    fs::write(repo_path.join("a.txt"), "main\n").unwrap();
+    assert!(run_rune_command(&["add", "a.txt"], repo_path).status.success());
+    assert!(run_rune_command(&["commit", "-m", "main"], repo_path).status.success());
+
+    let output = run_rune_command(&["merge", "feature"], repo_path);
+    assert_eq!(output.status.code(), Some(3), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+}
+
+fn run_rune_command_with_input(args: &[&str], working_dir: &Path, input: &str) -> std::process::Output {
+    use std::io::Write;
+    let mut child = Command::new(get_rune_binary())
+        .args(args)
+        .current_dir(working_dir)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .expect("Failed to execute rune command");
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    child.wait_with_output().expect("Failed to wait for rune command")
+}
+
+/// The staging area as stored, for checking which paths are in it
+fn staged_files(repo_path: &Path) -> String {
+    fs::read_to_string(repo_path.join(".rune/index.json")).unwrap_or_default()
+}
+
+#[test]
+fn test_add_directory_over_the_guard_threshold_asks_first() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let repo_path = temp_dir.path();
+    assert!(run_rune_command(&["init"], repo_path).status.success());
+    assert!(run_rune_command(&["config", "set", "core.add_guard_files", "3"], repo_path).status.success());
+    fs::create_dir_all(repo_path.join("assets")).unwrap();
+    for name in ["a.txt", "b.txt", "c.txt", "d.txt"] {
+        fs::write(repo_path.join("assets").join(name), name).unwrap();
+    }
+
+    // Declining stages nothing
+    let output = run_rune_command_with_input(&["add", "."], repo_path, "n\n");
+    assert_eq!(output.status.code(), Some(6), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("About to stage 4 files"), "{}", stdout);
+    assert!(stdout.contains("assets"), "{}", stdout);
+    assert!(!staged_files(repo_path).contains("assets/"));
+
+    // Accepting stages everything
+    let output = run_rune_command_with_input(&["add", "."], repo_path, "y\n");
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    let index = staged_files(repo_path);
+    for name in ["a.txt", "b.txt", "c.txt", "d.txt"] {
+        assert!(index.contains(&format!("assets/{}", name)), "{}", index);
+    }
+}
+
+#[test]
+fn test_add_directory_guard_with_yes_and_junk_dirs() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let repo_path = temp_dir.path();
+    assert!(run_rune_command(&["init"], repo_path).status.success());
+    fs::create_dir_all(repo_path.join("src")).unwrap();
+    fs::create_dir_all(repo_path.join("node_modules/left-pad")).unwrap();
+    fs::write(repo_path.join("src/main.rs"), "fn main() {}\n").unwrap();
+    fs::write(repo_path.join("node_modules/left-pad/index.js"), "module.exports = 1;\n").unwrap();
+
+    // The junk directory is offered, ignored and left out
+    let output = run_rune_command_with_input(&["add", "."], repo_path, "y\n");
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("node_modules/"), "{}", stdout);
+    assert!(fs::read_to_string(repo_path.join(".runeignore.yml")).unwrap().contains("node_modules/"));
+    let index = staged_files(repo_path);
+    assert!(index.contains("src/main.rs") && !index.contains("node_modules"), "{}", index);
+
+    // --yes goes ahead over the threshold without asking
+    assert!(run_rune_command(&["config", "set", "core.add_guard_files", "1"], repo_path).status.success());
+    fs::write(repo_path.join("src/lib.rs"), "pub fn f() {}\n").unwrap();
+    fs::write(repo_path.join("src/util.rs"), "pub fn g() {}\n").unwrap();
+    let output = run_rune_command(&["add", ".", "--yes"], repo_path);
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("[y/N]"));
+    let index = staged_files(repo_path);
+    assert!(index.contains("src/lib.rs") && index.contains("src/util.rs"), "{}", index);
+
+    // So does --no-guard
+    fs::write(repo_path.join("src/more.rs"), "\n").unwrap();
+    fs::write(repo_path.join("src/extra.rs"), "\n").unwrap();
+    assert!(run_rune_command(&["add", ".", "--no-guard"], repo_path).status.success());
+    assert!(staged_files(repo_path).contains("src/extra.rs"));
+}
+
 #[test]
 fn test_error_scenarios() {
     let temp_dir = TempDir::new().expect("Failed to create temp dir");
@@ -108,6 +333,36 @@ fn test_error_scenarios() {
     assert!(!output.status.success(), "Commit should fail with nothing staged");
 }
 
+#[test]
+fn test_commands_outside_a_repository() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+
+    for args in [&["status"][..], &["log"], &["add", "x.txt"], &["commit", "-m", "x"], &["diff"], &["config", "set", "a.b", "c"]] {
+        let output = run_rune_command(args, temp_dir.path());
+        assert_eq!(output.status.code(), Some(4), "{:?} should exit with the not-a-repo code", args);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("Not a Rune repository"), "{:?} stderr: {}", args, stderr);
+        assert!(stderr.contains(&temp_dir.path().display().to_string()), "{:?} stderr: {}", args, stderr);
+        assert!(stderr.contains("rune init") && stderr.contains("rune clone"), "{:?} stderr: {}", args, stderr);
+        assert!(!stderr.contains("Caused by") && !stderr.contains("Error:"), "{:?} stderr: {}", args, stderr);
+        assert!(!stderr.contains("fast-import"), "{:?} stderr: {}", args, stderr);
+    }
+
+    // These never needed a repository
+    for args in [&["docs", "list"][..], &["guide"], &["completions", "bash"]] {
+        let output = run_rune_command(args, temp_dir.path());
+        assert!(output.status.success(), "{:?} should work outside a repository. stderr: {}", args, String::from_utf8_lossy(&output.stderr));
+    }
+    assert!(!temp_dir.path().join(".rune").exists());
+
+    // Inside a Git checkout, point at the importer
+    fs::create_dir_all(temp_dir.path().join(".git")).unwrap();
+    fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+    let output = run_rune_command(&["status"], &temp_dir.path().join("src"));
+    assert_eq!(output.status.code(), Some(4));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("git fast-export --all | rune fast-import"));
+}
+
 #[test]
 fn test_help_and_version() {
     let temp_dir = TempDir::new().expect("Failed to create temp dir");